@@ -1,126 +1,117 @@
 use mirage_libtegra::{
-    clock::{Car, Clock},
+    audio::i2s::{I2s, I2S_CG_SLCG_ENABLE, I2S_CTRL_MASTER_ENABLE},
+    board::Board,
+    clock::{
+        self,
+        fields::{
+            OscFrequency, SclkSource, SysState, CLK_SOURCE, CLK_SOURCE_SOR1, CLK_SYS_RATE,
+            OSC_CTRL, PLL_BASE, SCLK_BRST_POL, SPARE_REG0, SUPER_SCLK_DIV,
+        },
+        Car, Clock, ClockState,
+    },
     fuse,
-    gpio::{Gpio, GpioConfig},
-    i2c::{I2c, Device},
+    hw_rev::{self, SocRevision},
+    i2c::Device,
     mc,
-    pinmux::{Pinmux, INPUT},
-    pmc::Pmc,
+    peripherals::Peripherals,
+    pmc::{Pmc, SCRATCH188, SCRATCH190, SCRATCH20, SCRATCH49, SECURE_SCRATCH21},
     sdram,
-    //se::SecurityEngine,
-    sysctr0::Sysctr0Registers,
+    se::SecurityEngine,
+    sysctr0,
     sysreg::AhbRegisters,
-    timer::{TimerRegisters, usleep},
-    uart::Uart,
+    timer::usleep,
+    Error, Result,
 };
+#[cfg(feature = "profile")]
+use mirage_libtegra::profile;
 use mirage_mmio::{Mmio, VolatileStorage};
 
-/// The global instance of the Security Engine.
-//const SECURITY_ENGINE: SecurityEngine = SecurityEngine::new();
-
-/// Base address for I2S registers.
-const I2S_BASE: u32 = 0x702D_1000;
-
 /// Configures the Switch oscillators.
-fn config_oscillators(car: &Car, pmc: &Pmc) {
-    let sysctr0 = unsafe { Sysctr0Registers::get() };
-    let timer = unsafe { TimerRegisters::get() };
+fn config_oscillators(peripherals: &mut Peripherals) -> Result<()> {
+    let car = peripherals.car;
+    let pmc = peripherals.pmc;
 
     // Set CLK_M_DIVISOR to 2.
-    car.spare_reg0.write((car.spare_reg0.read() & 0xFFFF_FFF3) | 4);
-    // Set counter frequency.
-    sysctr0.CNTFID0.write(0x124F800);
-    // For 19.2MHz clk_m.
-    timer.TIMERUS_USEC_CFG.write(0x45F);
+    car.spare_reg0.write_field(SPARE_REG0::CLK_M_DIVISOR, 1);
+    // Set the counter frequency, the microsecond timer and TSC_MULT for 19.2MHz clk_m.
+    sysctr0::configure(19_200_000)?;
     // Set OSC to 38.4MHz and drive strength.
-    car.osc_ctrl.write(0x5000_0071);
+    car.osc_ctrl.write(
+        OSC_CTRL::OSC_FREQ.val(OscFrequency::Osc38P4.value())
+            | OSC_CTRL::XOFS.val(7)
+            | OSC_CTRL::XOE.val(1),
+    );
 
     // Set LP0 OSC drive strength.
     pmc.osc_edpd_over.write((pmc.osc_edpd_over.read() & 0xFFFF_FF81) | 0xE);
     pmc.osc_edpd_over.write((pmc.osc_edpd_over.read() & 0xFFBF_FFFF) | 0x400000);
     pmc.cntrl2.write((pmc.cntrl2.read() & 0xFFFF_EFFF) | 0x1000);
     // LP0 EMC2TMC_CFG_XM2COMP_PU_VREF_SEL_RANGE.
-    pmc.scratch188.write((pmc.scratch188.read() & 0xFCFF_FFFF) | 0x2000000);
+    pmc.scratch188
+        .write_field(SCRATCH188::XM2COMP_PU_VREF_SEL_RANGE, 2);
 
     // Set HCLK div to 2 and PCLK div to 1.
-    car.clk_sys_rate.write(0x10);
+    car.clk_sys_rate.write(CLK_SYS_RATE::AHB_RATE.val(1));
     // Disable PLLMB.
-    car.pllmb_base.write(car.pllmb_base.read() & 0xBFFF_FFFF);
-
-    // 0x249F = 19200000 * (16 / 32.768 kHz)
-    pmc.tsc_mult.write((pmc.tsc_mult.read() & 0xFFFF_0000) | 0x249F);
+    car.pllmb_base.write_field(PLL_BASE::ENABLE, 0);
 
     // Set SCLK div to 1.
-    car.clk_source_sys.write(0);
+    car.clk_source_sys.write(CLK_SOURCE::DIVISOR.val(0));
     // Set clk source to Run and PLLP_OUT2 (204MHz).
-    car.sclk_brst_pol.write(0x2000_4444);
+    let source = SclkSource::PllPOut2.value();
+    car.sclk_brst_pol.write(
+        SCLK_BRST_POL::SYS_STATE.val(SysState::Run.value())
+            | SCLK_BRST_POL::FIQ_SOURCE.val(source)
+            | SCLK_BRST_POL::IRQ_SOURCE.val(source)
+            | SCLK_BRST_POL::RUN_SOURCE.val(source)
+            | SCLK_BRST_POL::IDLE_SOURCE.val(source),
+    );
     // Enable SUPER_SDIV to 1.
-    car.super_sclk_div.write(0x8000_0000);
+    car.super_sclk_div.write(SUPER_SCLK_DIV::ENABLE.val(1));
     // Set HCLK div to 1 and PCLK div to 3.
-    car.clk_sys_rate.write(2);
-}
-
-/// Configures the GPIOs used by the Switch.
-fn config_gpios(pinmux: &Pinmux) {
-    pinmux.uart2_tx.write(0);
-    pinmux.uart3_tx.write(0);
+    car.clk_sys_rate.write(CLK_SYS_RATE::APB_RATE.val(2));
 
-    // Set Joy-Con IsAttached direction.
-    pinmux.pe6.write(INPUT);
-    pinmux.ph6.write(INPUT);
-
-    // Enable input logic for Joy-Con IsAttached and UART_B/C TX pins.
-    gpio!(G, 0).config(GpioConfig::Input);
-    gpio!(D, 1).config(GpioConfig::Input);
-    gpio!(E, 6).config(GpioConfig::Input);
-    gpio!(H, 6).config(GpioConfig::Input);
-
-    pinmux.configure_i2c(&I2c::C1);
-    pinmux.configure_i2c(&I2c::C5);
-    pinmux.configure_uart(&Uart::A);
-
-    // Configure Volume Up/Down as inputs.
-    Gpio::BUTTON_VOL_UP.config(GpioConfig::Input);
-    Gpio::BUTTON_VOL_DOWN.config(GpioConfig::Input);
+    Ok(())
 }
 
 /// Configures and locks the PMC scratch registers.
 fn config_pmc_scratch(pmc: &Pmc) {
-    pmc.scratch20.write(pmc.scratch20.read() & 0xFFF3_FFFF);
-    pmc.scratch190.write(pmc.scratch190.read() & 0xFFFF_FFFE);
-    pmc.secure_scratch21.write(pmc.secure_scratch21.read() | 0x10);
+    pmc.scratch20.write_field(SCRATCH20::DEBUG_CONSOLE, 0);
+    pmc.scratch190.write_field(SCRATCH190::DATA_DQ_E_IVREF, 0);
+    pmc.secure_scratch21
+        .write_field(SECURE_SCRATCH21::PRIVATEKEYDISABLE_TZ_STICKY, 1);
 }
 
+/// The clock configuration from before the MBIST workaround, to be passed
+/// as [`CleanupOptions::clocks`] before chainloading a payload.
+///
+/// [`CleanupOptions::clocks`]: ../../mirage_libtegra/chainload/struct.CleanupOptions.html#structfield.clocks
+pub static mut MBIST_CLOCKS: Option<ClockState> = None;
+
 fn mbist_workaround(car: &Car) {
-    let i2s1_cg = unsafe { &*((I2S_BASE + 0x88) as *const Mmio<u32>) };
-    let i2s1_ctrl = unsafe { &*((I2S_BASE + 0xA0) as *const Mmio<u32>) };
-    let i2s2_cg = unsafe { &*((I2S_BASE + 0x188) as *const Mmio<u32>) };
-    let i2s2_ctrl = unsafe { &*((I2S_BASE + 0x1A0) as *const Mmio<u32>) };
-    let i2s3_cg = unsafe { &*((I2S_BASE + 0x288) as *const Mmio<u32>) };
-    let i2s3_ctrl = unsafe { &*((I2S_BASE + 0x2A0) as *const Mmio<u32>) };
-    let i2s4_cg = unsafe { &*((I2S_BASE + 0x388) as *const Mmio<u32>) };
-    let i2s4_ctrl = unsafe { &*((I2S_BASE + 0x3A0) as *const Mmio<u32>) };
-    let i2s5_cg = unsafe { &*((I2S_BASE + 0x488) as *const Mmio<u32>) };
-    let i2s5_ctrl = unsafe { &*((I2S_BASE + 0x4A0) as *const Mmio<u32>) };
-
-    car.clk_source_sor1.write((car.clk_source_sor1.read() | 0x8000) & 0xFFFF_BFFF);
-    car.plld_base.write(car.plld_base.read() | 0x4080_0000);
+    // Allow restoring the clocks before chainloading a payload.
+    unsafe {
+        MBIST_CLOCKS = Some(clock::snapshot());
+    }
+
+    car.clk_source_sor1.modify(|v| {
+        let v = CLK_SOURCE_SOR1::SOR1_CLK_SEL1.set(v, 1);
+        CLK_SOURCE_SOR1::SOR1_CLK_SEL0.set(v, 0)
+    });
+    car.plld_base
+        .modify(|v| v | PLL_BASE::ENABLE.val(1) | PLL_BASE::PLLD_CSI_CLK_SRC.val(1));
     car.rst_dev_y_clr.write(0x40);
     car.rst_dev_x_clr.write(0x40000);
     car.rst_dev_l_clr.write(0x1800_0000);
     usleep(2);
 
     // Setup I2S.
-    i2s1_ctrl.write(i2s1_ctrl.read() | 0x400);
-    i2s1_cg.write(i2s1_cg.read() & 0xFFFF_FFFE);
-    i2s2_ctrl.write(i2s2_ctrl.read() | 0x400);
-    i2s2_cg.write(i2s2_cg.read() & 0xFFFF_FFFE);
-    i2s3_ctrl.write(i2s3_ctrl.read() | 0x400);
-    i2s3_cg.write(i2s3_cg.read() & 0xFFFF_FFFE);
-    i2s4_ctrl.write(i2s4_ctrl.read() | 0x400);
-    i2s4_cg.write(i2s4_cg.read() & 0xFFFF_FFFE);
-    i2s5_ctrl.write(i2s5_ctrl.read() | 0x400);
-    i2s5_cg.write(i2s5_cg.read() & 0xFFFF_FFFE);
+    for i2s in I2s::ALL.iter() {
+        let registers = i2s.registers();
+
+        registers.ctrl.write(registers.ctrl.read() | I2S_CTRL_MASTER_ENABLE);
+        registers.cg.write(registers.cg.read() & !I2S_CG_SLCG_ENABLE);
+    }
 
     unsafe {
         let dc_com_dsc_top_ctl = &*((0x5420_0000 + 0x33E * 4) as *const Mmio<u32>);
@@ -151,32 +142,206 @@ fn mbist_workaround(car: &Car) {
     car.lvl2_clk_gate_ovre.write(0);
 
     // Configure clock sources.
-    car.plld_base.write(car.plld_base.read() & 0x1F7F_FFFF);
-    car.clk_source_sor1.write(car.clk_source_sor1.read() & 0xFFFF_3FFF);
-    car.clk_source_vi.write((car.clk_source_vi.read() & 0x1FFF_FFFF) | 0x8000_0000);
-    car.clk_source_host1x.write((car.clk_source_host1x.read() & 0x1FFF_FFFF) | 0x8000_0000);
-    car.clk_source_nvenc.write((car.clk_source_nvenc.read() & 0x1FFF_FFFF) | 0x8000_0000);
+    car.plld_base.modify(|v| {
+        v & !(PLL_BASE::BYPASS.mask()
+            | PLL_BASE::ENABLE.mask()
+            | PLL_BASE::REF_DIS.mask()
+            | PLL_BASE::PLLD_CSI_CLK_SRC.mask())
+    });
+    car.clk_source_sor1.modify(|v| {
+        v & !(CLK_SOURCE_SOR1::SOR1_CLK_SEL1.mask() | CLK_SOURCE_SOR1::SOR1_CLK_SEL0.mask())
+    });
+    // Select PLLP_OUT0 as the source.
+    car.clk_source_vi.write_field(CLK_SOURCE::SOURCE, 4);
+    car.clk_source_host1x.write_field(CLK_SOURCE::SOURCE, 4);
+    car.clk_source_nvenc.write_field(CLK_SOURCE::SOURCE, 4);
 }
 
-/// Initializes the Switch hardware in an early bootrom context.
-pub fn hwinit() {
+/// The stages of the hardware initialization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Checks the SoC revision and clears state left behind by the bootrom.
+    Bootrom,
+    /// Applies the memory built-in self test workaround.
+    Mbist,
+    /// Brings up the SE, the fuse driver and the memory controller.
+    Controllers,
+    /// Configures the oscillators and the system clock dividers.
+    Oscillators,
+    /// Configures pinmux, GPIOs and the debug UART.
+    Pinmux,
+    /// Brings up CL-DVFS, TZRAM and the I2C controllers.
+    I2c,
+    /// Configures the PMIC and the SoC voltage.
+    Pmic,
+    /// Configures and locks the PMC scratch registers.
+    PmcScratch,
+    /// Moves the system clock over to PLLP_OUT0.
+    SystemClock,
+    /// Initializes SDRAM.
+    Sdram,
+}
+
+impl Stage {
+    /// Gets the name of the stage.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Bootrom => "bootrom",
+            Stage::Mbist => "mbist",
+            Stage::Controllers => "controllers",
+            Stage::Oscillators => "oscillators",
+            Stage::Pinmux => "pinmux",
+            Stage::I2c => "i2c",
+            Stage::Pmic => "pmic",
+            Stage::PmcScratch => "pmc scratch",
+            Stage::SystemClock => "system clock",
+            Stage::Sdram => "sdram",
+        }
+    }
+}
+
+/// A stage of the hardware initialization, together with its routine.
+pub struct InitStage {
+    /// The stage this routine implements.
+    pub stage: Stage,
+    /// The routine bringing up the hardware of the stage.
+    run: fn(&mut Peripherals) -> Result<()>,
+    /// Detects whether the hardware of the stage was already brought up.
+    is_initialized: fn(&Car, &Pmc) -> bool,
+}
+
+/// Creates an [`InitStage`] entry.
+///
+/// [`InitStage`]: struct.InitStage.html
+macro_rules! stage {
+    ($stage:ident, $run:expr, $is_initialized:expr) => {
+        InitStage {
+            stage: Stage::$stage,
+            run: $run,
+            is_initialized: $is_initialized,
+        }
+    };
+}
+
+/// All stages of the hardware initialization, in the order they must run.
+///
+/// SDRAM initialization is not part of this yet, as it gets stuck.
+pub static STAGES: [InitStage; 9] = [
+    stage!(Bootrom, init_bootrom, never),
+    stage!(Mbist, init_mbist, clk_m_configured),
+    stage!(Controllers, init_controllers, mc_enabled),
+    stage!(Oscillators, config_oscillators, clk_m_configured),
+    stage!(Pinmux, init_pinmux, never),
+    stage!(I2c, init_i2c, i2c_enabled),
+    stage!(Pmic, config_pmic, never),
+    stage!(PmcScratch, init_pmc_scratch, never),
+    stage!(SystemClock, init_system_clock, never),
+];
+
+/// The SDRAM initialization stage, which has to run after all of [`STAGES`].
+///
+/// [`STAGES`]: static.STAGES.html
+pub static SDRAM_STAGE: InitStage = stage!(Sdram, init_sdram, sdram_enabled);
+
+/// Options for [`hwinit_ex`].
+///
+/// [`hwinit_ex`]: fn.hwinit_ex.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitOptions {
+    /// Re-runs stages whose hardware was already brought up, e.g. by the
+    /// bootloader that chainloaded Mirage, instead of skipping them.
+    pub allow_reinit: bool,
+    /// Runs [`SDRAM_STAGE`] after all other stages.
+    ///
+    /// [`SDRAM_STAGE`]: static.SDRAM_STAGE.html
+    pub sdram: bool,
+}
+
+impl Default for InitOptions {
+    fn default() -> Self {
+        InitOptions {
+            allow_reinit: false,
+            sdram: false,
+        }
+    }
+}
+
+/// For stages which can safely be re-applied at any time.
+fn never(_: &Car, _: &Pmc) -> bool {
+    false
+}
+
+/// Whether CLK_M was divided down already, which the bootrom doesn't do.
+fn clk_m_configured(car: &Car, _: &Pmc) -> bool {
+    car.spare_reg0.read_field(SPARE_REG0::CLK_M_DIVISOR) == 1
+}
+
+/// Whether the memory controller is clocked and out of reset.
+///
+/// Reconfiguring its clocks while SDRAM is in use hangs the system.
+fn mc_enabled(car: &Car, _: &Pmc) -> bool {
+    car.clk_out_enb_h.read() & 1 != 0 && car.rst_dev_h.read() & 1 == 0
+}
+
+/// Whether the I2C controller of the PMIC is enabled.
+fn i2c_enabled(_: &Car, _: &Pmc) -> bool {
+    Clock::I2C_5.is_enabled()
+}
+
+/// Whether SDRAM is up, which is detected through PLLM clocking the EMC.
+fn sdram_enabled(car: &Car, _: &Pmc) -> bool {
+    car.pllm_base.read_field(PLL_BASE::ENABLE) != 0
+}
+
+/// Performs the SE setup that the bootrom skipped by going through RCM.
+///
+/// This loads the full SBK from the fuses into its keyslot and enables the
+/// SE for crypto operations.
+fn config_se_brom(se: &mut SecurityEngine, pmc: &Pmc) -> Result<()> {
+    se.config_brom()?;
+
+    // Enable the SE.
+    pmc.crypto_op.write(0);
+
+    Ok(())
+}
+
+/// Checks the SoC revision and clears state left behind by the bootrom.
+///
+/// The sequence is specific to Erista units, other revisions are refused
+/// before any register is touched.
+fn init_bootrom(peripherals: &mut Peripherals) -> Result<()> {
+    let pmc = peripherals.pmc;
+
+    hw_rev::require(SocRevision::Erista)?;
+
     let ahb = unsafe { AhbRegisters::get() };
-    let car = unsafe { Car::get() };
-    let pinmux = unsafe { Pinmux::get() };
-    let pmc = unsafe { Pmc::get() };
 
-    // TODO(Vale): Implement this.
-    // Bootrom stuff that was skipped by going through RCM.
-    // config_se_brom(pmc);
+    // Bootrom stuff that was skipped by going through RCM. The private key
+    // fuses are hidden on a regular boot, the SBK is set up already then.
+    fuse::make_registers_visible(true);
+    if fuse::is_private_key_visible() {
+        config_se_brom(&mut peripherals.se, pmc)?;
+    }
 
     ahb.AHB_SPARE_REG.write(ahb.AHB_SPARE_REG.read() & 0xFFFF_FF9F);
-    pmc.scratch49.write(pmc.scratch49.read() & 0xFFFF_FFFC);
+    pmc.scratch49.write_field(SCRATCH49::BOOTROM_FLAGS, 0);
+
+    Ok(())
+}
 
-    // Apply the memory built-in self test workaround.
-    mbist_workaround(car);
+/// Applies the memory built-in self test workaround.
+fn init_mbist(peripherals: &mut Peripherals) -> Result<()> {
+    mbist_workaround(peripherals.car);
 
+    Ok(())
+}
+
+/// Brings up the SE, the fuse driver and the memory controller.
+fn init_controllers(_: &mut Peripherals) -> Result<()> {
     // Reboot SE.
-    Clock::SE.enable();
+    let mut clock = Clock::SE;
+    clock.enable();
 
     // Initialize the fuse driver.
     fuse::init();
@@ -184,81 +349,155 @@ pub fn hwinit() {
     // Initialize the memory controller.
     mc::enable_mc();
 
-    // Configure oscillators.
-    config_oscillators(car, pmc);
+    Ok(())
+}
 
+/// Configures pinmux, GPIOs and the debug UART.
+#[allow(unused_variables)]
+fn init_pinmux(peripherals: &mut Peripherals) -> Result<()> {
     // Disable pinmux tristate input clamping.
     unsafe {
         (*((0x7000_0000 + 0x40) as *const Mmio<u32>)).write(0);
     }
 
-    // Configure GPIOs.
-    config_gpios(pinmux);
+    // Configure pinmux and GPIOs.
+    Board::detect().profile().apply();
 
     #[cfg(feature = "debug_uart_port")]
-    Uart::E.init(115_200);
+    peripherals.uart_e.init(115_200)?;
+
+    Ok(())
+}
 
+/// Brings up CL-DVFS, TZRAM and the I2C controllers.
+fn init_i2c(peripherals: &mut Peripherals) -> Result<()> {
     // Reboot CL-DVFS.
-    Clock::CL_DVFS.enable();
+    let mut clock = Clock::CL_DVFS;
+    clock.enable();
 
     // Reboot TZRAM.
-    Clock::TZRAM.enable();
+    let mut clock = Clock::TZRAM;
+    clock.enable();
 
     // Initialize I2C 1.
-    I2c::C1.init();
+    peripherals.i2c_1.init();
 
     // Initialize I2C 5.
-    I2c::C5.init();
-
-    // Configure the PMIC.
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x4, 0x40)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x41, 0x60)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x43, 0x38)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x44, 0x3A)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x45, 0x38)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x4A, 0xF)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x4E, 0xC7)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x4F, 0x4F)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x50, 0x29)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x52, 0x1B)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x56, 0x22)
-        .unwrap();
+    peripherals.i2c_5.init();
+
+    Ok(())
+}
+
+/// Configures the PMIC and the SoC voltage.
+fn config_pmic(peripherals: &mut Peripherals) -> Result<()> {
+    const PMIC_CONFIG: [(u8, u8); 11] = [
+        (0x4, 0x40),
+        (0x41, 0x60),
+        (0x43, 0x38),
+        (0x44, 0x3A),
+        (0x45, 0x38),
+        (0x4A, 0xF),
+        (0x4E, 0xC7),
+        (0x4F, 0x4F),
+        (0x50, 0x29),
+        (0x52, 0x1B),
+        (0x56, 0x22),
+    ];
+
+    for &(register, value) in PMIC_CONFIG.iter() {
+        peripherals.i2c_5.write_byte(Device::Max77620Pwr, register, value)?;
+    }
 
     // Configure SD0 voltage.
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x16, 42)
-        .unwrap();
+    peripherals.i2c_5.write_byte(Device::Max77620Pwr, 0x16, 42)?;
 
-    // Configure and lock PMC scratch registers.
+    Ok(())
+}
+
+/// Configures and locks the PMC scratch registers.
+fn init_pmc_scratch(peripherals: &mut Peripherals) -> Result<()> {
     // XXX: This was removed from 4.x ongoing, should this be done?
-    config_pmc_scratch(pmc);
+    config_pmc_scratch(peripherals.pmc);
+
+    Ok(())
+}
+
+/// Moves the system clock over to PLLP_OUT0.
+fn init_system_clock(peripherals: &mut Peripherals) -> Result<()> {
+    let car = peripherals.car;
 
     // Set super clock burst policy to PLLP_OUT (408MHz).
     car.sclk_brst_pol.write((car.sclk_brst_pol.read() & 0xFFFF_8888) | 0x3333);
 
-    // Initialize SDRAM.
-    //sdram::init(car, pmc); --- execution gets stuck here, no panic though
+    Ok(())
+}
+
+/// Initializes SDRAM.
+fn init_sdram(peripherals: &mut Peripherals) -> Result<()> {
+    sdram::init(peripherals.car, peripherals.pmc, &peripherals.i2c_5)?;
 
     // TODO(Vale): Save SDRAM LP0 parameters.
+
+    Ok(())
+}
+
+/// Runs the given initialization stages in order.
+///
+/// Stops at the first stage that fails and reports it together with
+/// the error. Callers may leave out stages whose hardware was already
+/// brought up, e.g. by a previous payload.
+pub fn run<'a, I>(
+    peripherals: &mut Peripherals,
+    stages: I,
+) -> core::result::Result<(), (Stage, Error)>
+where
+    I: IntoIterator<Item = &'a InitStage>,
+{
+    for stage in stages {
+        #[cfg(feature = "profile")]
+        let _span = profile::begin(stage.stage.name());
+
+        (stage.run)(peripherals).map_err(|err| (stage.stage, err))?;
+    }
+
+    Ok(())
+}
+
+/// Initializes the Switch hardware with the given options.
+///
+/// Runs all of [`STAGES`] and optionally [`SDRAM_STAGE`]. Unless
+/// re-initialization is allowed, stages whose hardware is detected to be
+/// up already are skipped, so Mirage can be chainloaded from another
+/// bootloader without hanging.
+///
+/// [`STAGES`]: static.STAGES.html
+/// [`SDRAM_STAGE`]: static.SDRAM_STAGE.html
+pub fn hwinit_ex(
+    peripherals: &mut Peripherals,
+    options: &InitOptions,
+) -> core::result::Result<(), (Stage, Error)> {
+    let car = peripherals.car;
+    let pmc = peripherals.pmc;
+    let sdram = if options.sdram {
+        Some(&SDRAM_STAGE)
+    } else {
+        None
+    };
+
+    // The detection runs lazily, right before a stage would run.
+    let stages = STAGES
+        .iter()
+        .chain(sdram)
+        .filter(|stage| options.allow_reinit || !(stage.is_initialized)(car, pmc));
+
+    run(peripherals, stages)
+}
+
+/// Initializes the Switch hardware in an early bootrom context.
+///
+/// Runs all of [`STAGES`], skipping those already brought up.
+///
+/// [`STAGES`]: static.STAGES.html
+pub fn hwinit(peripherals: &mut Peripherals) -> core::result::Result<(), (Stage, Error)> {
+    hwinit_ex(peripherals, &InitOptions::default())
 }
@@ -9,9 +9,20 @@
 //! Boot and Power Management Processor of the Tegra X1. It has a
 //! length of `0x20000`.
 //!
+//! As raw RCM payloads expect to run from there as well, the bootstrap
+//! first relocates itself to its link address at `0x40020000` in upper
+//! IRAM through a small relocator at the end of IRAM, see the `relocate`
+//! module.
+//!
 //! The low IRAM is located at `0x40003000`, right where the execution
-//! stack ends, with a length of `0x8000`. When chainloading other RCM
-//! payloads, this is where they are being loaded.
+//! stack ends, with a length of `0x8000`. When chainloading raw RCM
+//! payloads, this is where they are being loaded. Payloads in the Mirage
+//! container format specify their own load address and entry point in
+//! their header instead, see `mirage_libtegra::payload`.
+//!
+//! These areas are described by the constants in
+//! `mirage_libtegra::memory_map`, which are checked against the linker
+//! symbols on startup.
 //!
 //! # Tasks
 //!
@@ -45,27 +56,64 @@ extern crate mirage_libtegra;
 
 extern crate mirage_mmio;
 
-use core::{
-    fmt::Write,
-    panic::PanicInfo,
-};
+use core::panic::PanicInfo;
 
 use mirage_libtegra::{
-    display,
+    board::Board,
+    crashlog, display,
+    error::hex,
     gpio::{Gpio, GpioConfig},
+    memory_map::{self, Region, LIBTEGRA_REGIONS, PAYLOAD_STAGING},
+    peripherals::Peripherals,
     pinmux::{Pinmux, TRISTATE},
     timer::sleep,
     uart::Uart,
+    Error,
 };
 use mirage_mmio::VolatileStorage;
 
 use init::hwinit;
 
 mod init;
+#[cfg(feature = "panic_screen")]
+mod panic;
+mod relocate;
+
+extern "C" {
+    static __stack_top__: u8;
+}
+
+/// Validates that the bootstrap memory layout doesn't overlap
+/// with itself or the regions used by libtegra.
+unsafe fn check_memory_map() {
+    let stack_top = &__stack_top__ as *const u8 as u32;
+
+    // The stack may grow down until it reaches the staging area.
+    let stack_bottom = PAYLOAD_STAGING.end() as u32;
+
+    let regions = [
+        PAYLOAD_STAGING,
+        Region::new("stack", stack_bottom, stack_top - stack_bottom),
+        relocate::bootstrap(),
+        relocate::relocator(),
+        LIBTEGRA_REGIONS[0],
+        LIBTEGRA_REGIONS[1],
+        LIBTEGRA_REGIONS[2],
+    ];
+
+    memory_map::assert_valid(&regions);
+}
 
 #[panic_handler]
-fn panic(_info: &PanicInfo<'_>) -> ! {
+fn panic(info: &PanicInfo<'_>) -> ! {
+    // Keep a record of the panic for the next boot.
+    crashlog::record_panic(info);
+
+    #[cfg(feature = "panic_screen")]
+    panic::show(info);
+
     // TODO: Implement a proper panic handler.
+    #[cfg(not(feature = "panic_screen"))]
     loop {}
 }
 
@@ -85,14 +133,46 @@ unsafe fn backlight_poc() {
     display::hide_backlight();
 }
 
+/// Reports an error over the debug UART.
+///
+/// This avoids `core::fmt`, which doesn't fit into the IRAM budget.
+#[allow(unused_variables)]
+fn report_error(uart: &mut Uart, context: &str, error: Error) {
+    #[cfg(feature = "debug_uart_port")]
+    {
+        uart.write_bytes(b"Mirage: ");
+        uart.write_bytes(context.as_bytes());
+        uart.write_bytes(b": E");
+        uart.write_bytes(&hex(error.code()));
+        uart.write_bytes(b" ");
+        uart.write_bytes(error.as_str().as_bytes());
+        uart.write_bytes(b"\r\n");
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn main() {
+    let mut peripherals = Peripherals::take().unwrap();
+
     // Initialize the hardware.
-    hwinit();
+    if let Err((stage, error)) = hwinit(&mut peripherals) {
+        report_error(&mut peripherals.uart_e, stage.name(), error);
+        panic!("Failed to initialize the hardware");
+    }
+
+    // Make sure that the memory layout is sane.
+    check_memory_map();
 
     #[cfg(feature = "debug_uart_port")]
-    writeln!(&mut Uart::E, "Mirage: Ready!").ok();
+    peripherals.uart_e.write_bytes(b"Mirage: Ready!\r\n");
+
+    // Report the boot-time breakdown. This uses `core::fmt`, which is fine
+    // for profiling builds only.
+    #[cfg(all(feature = "debug_uart_port", feature = "profile"))]
+    mirage_libtegra::profile::dump(&mut peripherals.uart_e).ok();
 
     // Display backlight PoC for debugging.
-    backlight_poc();
+    if Board::detect().has_lcd_backlight() {
+        backlight_poc();
+    }
 }
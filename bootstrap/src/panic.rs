@@ -0,0 +1,89 @@
+//! Panic screen integration.
+//!
+//! When the `panic_screen` feature is enabled, panics bring up the
+//! display and render an error report, including a QR code with the
+//! panic location, and wait for the user to reboot into RCM.
+
+use core::{fmt, panic::PanicInfo};
+
+use mirage_libtegra::{
+    button::{self, Button},
+    display,
+    peripherals::Peripherals,
+    pmc,
+};
+
+static mut PANICKING: bool = false;
+
+/// A fixed-size buffer that silently truncates formatted output.
+struct Diagnostics {
+    buffer: [u8; display::QR_CAPACITY],
+    length: usize,
+}
+
+impl fmt::Write for Diagnostics {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if self.length < self.buffer.len() {
+                self.buffer[self.length] = byte;
+                self.length += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Derives a stable error code from the panic location.
+fn error_code(file: &str, line: u32) -> u32 {
+    // FNV-1a over the file name, mixed with the line number.
+    let mut hash: u32 = 0x811C_9DC5;
+    for byte in file.bytes() {
+        hash = (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193);
+    }
+
+    hash ^ line
+}
+
+/// Shows the panic screen and reboots into RCM once POWER is pressed.
+pub fn show(info: &PanicInfo<'_>) -> ! {
+    use core::fmt::Write;
+
+    // Don't try to render the screen again if we panicked while doing so.
+    unsafe {
+        if PANICKING {
+            loop {}
+        }
+
+        PANICKING = true;
+    }
+
+    let (file, line) = info
+        .location()
+        .map(|l| (l.file(), l.line()))
+        .unwrap_or(("unknown", 0));
+    let code = error_code(file, line);
+
+    let mut diagnostics = Diagnostics {
+        buffer: [0; display::QR_CAPACITY],
+        length: 0,
+    };
+    write!(&mut diagnostics, "MRG{:08X}:{}:{}", code, line, file).ok();
+
+    // `main` holds the handles, but never gets to use them again.
+    let peripherals = unsafe { Peripherals::steal() };
+
+    display::initialize(&peripherals.i2c_5);
+    display::initialize_framebuffer(display::FRAMEBUFFER_ADDRESS);
+    display::show_panic_screen(
+        code,
+        format_args!("{}", info),
+        &diagnostics.buffer[..diagnostics.length],
+    );
+    display::display_backlight();
+
+    // Wait for the power button to be pressed.
+    while !button::read(&peripherals.i2c_5).contains(Button::POWER) {}
+
+    pmc::reboot_to_rcm()
+}
@@ -0,0 +1,35 @@
+//! Relocation of the bootstrap.
+//!
+//! RCM loads the bootstrap to the start of the RCM payload area, which is
+//! also where raw RCM payloads expect to be executed from. To keep that
+//! area free, `start.S` relocates the bootstrap to its link address in
+//! upper IRAM before anything else runs. The relocator doing the copy is
+//! moved to the end of IRAM first, so source and destination may overlap.
+
+use mirage_libtegra::memory_map::Region;
+
+/// The size of the relocator area in upper IRAM.
+pub const RELOCATOR_SIZE: u32 = 0x1000;
+
+extern "C" {
+    static __payload_start__: u8;
+    static __bss_end__: u8;
+    static __relocator_address__: u8;
+}
+
+/// Gets the memory occupied by the relocated bootstrap, including `.bss`.
+pub fn bootstrap() -> Region {
+    unsafe {
+        let start = &__payload_start__ as *const u8 as u32;
+        let end = &__bss_end__ as *const u8 as u32;
+
+        Region::new("bootstrap", start, end - start)
+    }
+}
+
+/// Gets the memory the relocator is copied to.
+pub fn relocator() -> Region {
+    let address = unsafe { &__relocator_address__ as *const u8 as u32 };
+
+    Region::new("relocator", address, RELOCATOR_SIZE)
+}
@@ -0,0 +1,248 @@
+//! Tegra210 Activity Monitor driver.
+//!
+//! # Description
+//!
+//! The Activity Monitor (ACTMON) periodically samples how busy certain
+//! units of the SoC are, for example the memory controller or the AVP
+//! (BPMP). Each monitored device accumulates a weighted activity count
+//! per sample period and keeps a moving average of it. Watermarks on both
+//! raise interrupt status bits when the load leaves a given range.
+//!
+//! This makes ACTMON useful for rudimentary profiling of heavy flows like
+//! SD card reads or SE crypto, without instrumenting the code itself.
+//!
+//! # Implementation
+//!
+//! - [`Device`] enumerates the monitored devices.
+//!
+//! - [`start`] enables periodic sampling for a device, [`stop`] disables it.
+//! The sample period is shared by all devices and can be changed with
+//! [`set_sample_period`].
+//!
+//! - [`count`] returns the activity of the last sample period and
+//! [`average`] the moving average of it.
+//!
+//! - [`set_watermarks`] configures the thresholds of the moving average,
+//! crossings are reported by [`interrupt_status`] as [`Interrupts`].
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::actmon::{self, Device};
+//!
+//! fn main() {
+//!     actmon::start(Device::Emc);
+//!
+//!     // Run the workload...
+//!
+//!     let load = actmon::average(Device::Emc);
+//!     actmon::stop(Device::Emc);
+//! }
+//! ```
+//!
+//! [`Device`]: enum.Device.html
+//! [`start`]: fn.start.html
+//! [`stop`]: fn.stop.html
+//! [`set_sample_period`]: fn.set_sample_period.html
+//! [`count`]: fn.count.html
+//! [`average`]: fn.average.html
+//! [`set_watermarks`]: fn.set_watermarks.html
+//! [`interrupt_status`]: fn.interrupt_status.html
+//! [`Interrupts`]: struct.Interrupts.html
+
+use mirage_mmio::{BlockMmio, Mmio};
+
+use crate::clock::Clock;
+
+/// Base address for ACTMON registers.
+const ACTMON_BASE: u32 = 0x6000_C800;
+
+/// The default sample period in milliseconds.
+const DEFAULT_SAMPLE_PERIOD: u8 = 12;
+
+/// The weight each active cycle is counted with.
+const COUNT_WEIGHT: u32 = 0x400;
+
+/// The moving average covers `2^(K_VAL + 1)` sample periods.
+const K_VAL: u32 = 4;
+
+/// Enables the device monitor.
+const CTRL_ENB: u32 = 1 << 31;
+/// Enables the upper watermark of the moving average.
+const CTRL_AVG_ABOVE_WMARK_EN: u32 = 1 << 21;
+/// Enables the lower watermark of the moving average.
+const CTRL_AVG_BELOW_WMARK_EN: u32 = 1 << 20;
+/// Samples periodically instead of on demand.
+const CTRL_ENB_PERIODIC: u32 = 1 << 18;
+
+/// Representation of the ACTMON global registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+struct GlobalRegisters {
+    pub GLB_STATUS: Mmio<u32>,
+    pub GLB_PERIOD_CTRL: Mmio<u32>,
+}
+
+/// Representation of the registers of a monitored device.
+#[allow(non_snake_case)]
+#[repr(C)]
+struct DeviceRegisters {
+    pub CTRL: Mmio<u32>,
+    pub UPPER_WMARK: Mmio<u32>,
+    pub LOWER_WMARK: Mmio<u32>,
+    pub INIT_AVG: Mmio<u32>,
+    pub AVG_UPPER_WMARK: Mmio<u32>,
+    pub AVG_LOWER_WMARK: Mmio<u32>,
+    pub COUNT_WEIGHT: Mmio<u32>,
+    pub COUNT: Mmio<u32>,
+    pub AVG_COUNT: Mmio<u32>,
+    pub INTR_STATUS: Mmio<u32>,
+}
+
+bitflags! {
+    /// Representation of the interrupt status of a monitored device.
+    pub struct Interrupts: u32 {
+        /// The moving average rose above the upper watermark.
+        const AVG_ABOVE_WMARK = 1 << 24;
+        /// The moving average fell below the lower watermark.
+        const AVG_BELOW_WMARK = 1 << 25;
+        /// The count was below the lower watermark for several periods.
+        const CONSECUTIVE_LOWER = 1 << 30;
+        /// The count was above the upper watermark for several periods.
+        const CONSECUTIVE_UPPER = 1 << 31;
+    }
+}
+
+/// Devices monitored by ACTMON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    /// The AVP, also known as BPMP or COP.
+    Avp,
+    /// All memory clients.
+    Emc,
+    /// The CPU memory clients.
+    CpuEmc,
+}
+
+impl Device {
+    /// Gets the offset of the device registers within ACTMON.
+    fn offset(self) -> u32 {
+        match self {
+            Device::Avp => 0x0C0,
+            Device::Emc => 0x1C0,
+            Device::CpuEmc => 0x200,
+        }
+    }
+
+    /// Gets the registers of the device.
+    fn registers(self) -> BlockMmio<DeviceRegisters> {
+        unsafe { BlockMmio::new((ACTMON_BASE + self.offset()) as usize) }
+    }
+}
+
+/// Gets the global ACTMON registers.
+fn global_registers() -> BlockMmio<GlobalRegisters> {
+    unsafe { BlockMmio::new(ACTMON_BASE as usize) }
+}
+
+/// Sets the sample period of all devices in milliseconds.
+pub fn set_sample_period(milliseconds: u8) {
+    global_registers()
+        .GLB_PERIOD_CTRL
+        .write(u32::from(milliseconds.saturating_sub(1)));
+}
+
+/// Starts periodic sampling of a device, with the watermarks disabled.
+///
+/// The ACTMON clock is enabled and the sample period is set to its
+/// default if no device has been monitored before.
+pub fn start(device: Device) {
+    let mut clock = Clock::ACTMON;
+
+    if !clock.is_enabled() {
+        clock.enable();
+        set_sample_period(DEFAULT_SAMPLE_PERIOD);
+    }
+
+    let registers = device.registers();
+
+    registers.CTRL.write(0);
+    registers.INIT_AVG.write(0);
+    registers.COUNT_WEIGHT.write(COUNT_WEIGHT);
+    registers.INTR_STATUS.write(u32::max_value());
+    registers
+        .CTRL
+        .write(CTRL_ENB | CTRL_ENB_PERIODIC | (K_VAL << 10));
+}
+
+/// Stops sampling of a device.
+pub fn stop(device: Device) {
+    let registers = device.registers();
+
+    registers.CTRL.write(0);
+    registers.INTR_STATUS.write(u32::max_value());
+}
+
+/// Gets the activity count of the last sample period.
+pub fn count(device: Device) -> u32 {
+    device.registers().COUNT.read()
+}
+
+/// Gets the moving average of the activity count.
+pub fn average(device: Device) -> u32 {
+    device.registers().AVG_COUNT.read()
+}
+
+/// Sets the watermarks of the moving average and enables them.
+pub fn set_watermarks(device: Device, lower: u32, upper: u32) {
+    let registers = device.registers();
+
+    registers.AVG_LOWER_WMARK.write(lower);
+    registers.AVG_UPPER_WMARK.write(upper);
+    registers
+        .CTRL
+        .modify(|v| v | CTRL_AVG_ABOVE_WMARK_EN | CTRL_AVG_BELOW_WMARK_EN);
+}
+
+/// Gets the pending interrupts of a device.
+pub fn interrupt_status(device: Device) -> Interrupts {
+    Interrupts::from_bits_truncate(device.registers().INTR_STATUS.read())
+}
+
+/// Acknowledges the given interrupts of a device.
+pub fn clear_interrupts(device: Device, interrupts: Interrupts) {
+    device.registers().INTR_STATUS.write(interrupts.bits());
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// Tests the sampling configuration and watermarks of a device.
+    #[test]
+    fn sampling() {
+        let _session = mock::start();
+
+        // Pretend the clock is already running.
+        mock::set(0x6000_6360, 1 << 0x17);
+
+        start(Device::Emc);
+        assert_eq!(mock::get(0x6000_C9D8), u64::from(COUNT_WEIGHT));
+        assert_eq!(mock::get(0x6000_C9C0), 0x8004_1000);
+
+        set_watermarks(Device::Emc, 0x100, 0x200);
+        assert_eq!(mock::get(0x6000_C9D4), 0x100);
+        assert_eq!(mock::get(0x6000_C9D0), 0x200);
+        assert_eq!(mock::get(0x6000_C9C0), 0x8034_1000);
+
+        mock::set(0x6000_C9E0, 0x1234);
+        mock::set(0x6000_C9E4, 1 << 24);
+        assert_eq!(average(Device::Emc), 0x1234);
+        assert_eq!(interrupt_status(Device::Emc), Interrupts::AVG_ABOVE_WMARK);
+
+        stop(Device::Emc);
+        assert_eq!(mock::get(0x6000_C9C0), 0);
+    }
+}
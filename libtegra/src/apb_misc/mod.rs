@@ -1,32 +1,71 @@
 //! Abstractions over miscellaneous APB registers.
+//!
+//! # Description
+//!
+//! Besides identification and debug registers, the APB_MISC block holds
+//! the `GP` pad control registers, which configure the drive strength
+//! and slew rate of the pads of the SDMMC controllers. UHS and HS200/HS400
+//! modes need these tuned, otherwise data CRC errors occur on many cards.
+//!
+//! # Implementation
+//!
+//! - [`Padctl`] is the raw `GP` pad control register block.
+//!
+//! - [`PadGroup`] names the pads of an SDMMC controller, [`PadDrive`] holds
+//! their drive strength and slew rate settings. [`PadGroup::drive`] reads
+//! the current settings and [`PadGroup::set_drive`] writes new ones.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::apb_misc::PadGroup;
+//!
+//! fn main() {
+//!     let mut drive = PadGroup::Sdmmc1.drive();
+//!
+//!     drive.drvup = 0x11;
+//!     drive.drvdn = 0x15;
+//!     PadGroup::Sdmmc1.set_drive(drive).unwrap();
+//! }
+//! ```
+//!
+//! [`Padctl`]: struct.Padctl.html
+//! [`PadGroup`]: enum.PadGroup.html
+//! [`PadDrive`]: struct.PadDrive.html
+//! [`PadGroup::drive`]: enum.PadGroup.html#method.drive
+//! [`PadGroup::set_drive`]: enum.PadGroup.html#method.set_drive
 
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{register_bitfields, register_block, Field, Mmio, VolatileStorage};
+
+use crate::{Error, Result};
 
 /// Base address for PADCTL registers.
 pub(crate) const APB_PADCTL_BASE: u32 = 0x7000_0810;
 
-/// Representation of the PADCTL.
-#[repr(C)]
-pub struct Padctl {
-    pub asdbgreg: Mmio<u32>,
-    _reserved: [Mmio<u32>; 0x31],
-    pub sdmmc1_clk_lpbk_control: Mmio<u32>,
-    pub sdmmc3_clk_lpbk_control: Mmio<u32>,
-    pub emmc2_pad_cfg_control: Mmio<u32>,
-    pub emmc4_pad_cfg_control: Mmio<u32>,
-    _unk1: [Mmio<u32>; 0x6E],
-    pub sdmmc1_pad_cfgpadctrl: Mmio<u32>,
-    pub emmc2_pad_cfgpadctrl: Mmio<u32>,
-    pub emmc2_pad_drv_type_cfgpadctrl: Mmio<u32>,
-    pub emmc2_pad_pupd_cfgpadctrl: Mmio<u32>,
-    _unk2: [Mmio<u32>; 0x3],
-    pub sdmmc3_pad_cfgpadctrl: Mmio<u32>,
-    pub emmc4_pad_cfgpadctrl: Mmio<u32>,
-    pub emmc4_pad_drv_type_cfgpadctrl: Mmio<u32>,
-    pub emmc4_pad_pupd_cfgpadctrl: Mmio<u32>,
-    _unk3: [Mmio<u32>; 0x2E],
-    pub vgpio_gpio_mux_sel: Mmio<u32>,
-    pub qspi_sck_lpbk_control: Mmio<u32>,
+register_block! {
+    /// Representation of the PADCTL.
+    pub struct Padctl {
+        (0x000 => pub asdbgreg: Mmio<u32>),
+        (0x004 => _reserved0),
+        (0x0C4 => pub sdmmc1_clk_lpbk_control: Mmio<u32>),
+        (0x0C8 => pub sdmmc3_clk_lpbk_control: Mmio<u32>),
+        (0x0CC => pub emmc2_pad_cfg_control: Mmio<u32>),
+        (0x0D0 => pub emmc4_pad_cfg_control: Mmio<u32>),
+        (0x0D4 => _reserved1),
+        (0x288 => pub sdmmc1_pad_cfgpadctrl: Mmio<u32>),
+        (0x28C => pub emmc2_pad_cfgpadctrl: Mmio<u32>),
+        (0x290 => pub emmc2_pad_drv_type_cfgpadctrl: Mmio<u32>),
+        (0x294 => pub emmc2_pad_pupd_cfgpadctrl: Mmio<u32>),
+        (0x298 => _reserved2),
+        (0x2A0 => pub sdmmc3_pad_cfgpadctrl: Mmio<u32>),
+        (0x2A4 => pub emmc4_pad_cfgpadctrl: Mmio<u32>),
+        (0x2A8 => pub emmc4_pad_drv_type_cfgpadctrl: Mmio<u32>),
+        (0x2AC => pub emmc4_pad_pupd_cfgpadctrl: Mmio<u32>),
+        (0x2B0 => _reserved3),
+        (0x364 => pub vgpio_gpio_mux_sel: Mmio<u32>),
+        (0x368 => pub qspi_sck_lpbk_control: Mmio<u32>),
+        (0x36C => @END),
+    }
 }
 
 impl VolatileStorage for Padctl {
@@ -34,3 +73,169 @@ impl VolatileStorage for Padctl {
         APB_PADCTL_BASE as *const _
     }
 }
+
+register_bitfields! {
+    u32,
+    /// The `SDMMC1_PAD_CFGPADCTRL_0` and `SDMMC3_PAD_CFGPADCTRL_0` registers.
+    pub SDMMC_PAD_CFGPADCTRL [
+        /// The pull-down drive strength.
+        DRVDN OFFSET(12) NUMBITS(7),
+        /// The pull-up drive strength.
+        DRVUP OFFSET(20) NUMBITS(7),
+        /// The slew rate of the pull-down driver.
+        DRVDN_SLWR OFFSET(28) NUMBITS(2),
+        /// The slew rate of the pull-up driver.
+        DRVUP_SLWF OFFSET(30) NUMBITS(2),
+    ],
+    /// The `EMMC2_PAD_CFGPADCTRL_0` and `EMMC4_PAD_CFGPADCTRL_0` registers.
+    pub EMMC_PAD_CFGPADCTRL [
+        /// The pull-down drive strength.
+        DRVDN OFFSET(2) NUMBITS(6),
+        /// The pull-up drive strength.
+        DRVUP OFFSET(8) NUMBITS(6),
+        /// The slew rate of the pull-down driver.
+        DRVDN_SLWR OFFSET(28) NUMBITS(2),
+        /// The slew rate of the pull-up driver.
+        DRVUP_SLWF OFFSET(30) NUMBITS(2),
+    ],
+}
+
+/// The pads of the SDMMC controllers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadGroup {
+    /// The pads of SDMMC1, wired to the microSD slot.
+    Sdmmc1,
+    /// The pads of SDMMC2.
+    Emmc2,
+    /// The pads of SDMMC3.
+    Sdmmc3,
+    /// The pads of SDMMC4, wired to the eMMC.
+    Emmc4,
+}
+
+/// Drive strength and slew rate settings of a [`PadGroup`].
+///
+/// [`PadGroup`]: enum.PadGroup.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PadDrive {
+    /// The pull-up drive strength, up to `0x7F` for SDMMC and `0x3F` for
+    /// eMMC pads.
+    pub drvup: u32,
+    /// The pull-down drive strength, up to `0x7F` for SDMMC and `0x3F` for
+    /// eMMC pads.
+    pub drvdn: u32,
+    /// The slew rate of the pull-up driver, up to `0x3`.
+    pub drvup_slwf: u32,
+    /// The slew rate of the pull-down driver, up to `0x3`.
+    pub drvdn_slwr: u32,
+}
+
+impl PadGroup {
+    /// Gets the `CFGPADCTRL` register of the pads.
+    fn register(self, padctl: &Padctl) -> &Mmio<u32> {
+        match self {
+            PadGroup::Sdmmc1 => &padctl.sdmmc1_pad_cfgpadctrl,
+            PadGroup::Emmc2 => &padctl.emmc2_pad_cfgpadctrl,
+            PadGroup::Sdmmc3 => &padctl.sdmmc3_pad_cfgpadctrl,
+            PadGroup::Emmc4 => &padctl.emmc4_pad_cfgpadctrl,
+        }
+    }
+
+    /// Gets the `DRVUP`, `DRVDN`, `DRVUP_SLWF` and `DRVDN_SLWR` fields of
+    /// the register.
+    fn fields(self) -> [Field<u32>; 4] {
+        match self {
+            PadGroup::Sdmmc1 | PadGroup::Sdmmc3 => [
+                SDMMC_PAD_CFGPADCTRL::DRVUP,
+                SDMMC_PAD_CFGPADCTRL::DRVDN,
+                SDMMC_PAD_CFGPADCTRL::DRVUP_SLWF,
+                SDMMC_PAD_CFGPADCTRL::DRVDN_SLWR,
+            ],
+            PadGroup::Emmc2 | PadGroup::Emmc4 => [
+                EMMC_PAD_CFGPADCTRL::DRVUP,
+                EMMC_PAD_CFGPADCTRL::DRVDN,
+                EMMC_PAD_CFGPADCTRL::DRVUP_SLWF,
+                EMMC_PAD_CFGPADCTRL::DRVDN_SLWR,
+            ],
+        }
+    }
+
+    /// Reads the current drive settings of the pads.
+    pub fn drive(self) -> PadDrive {
+        let padctl = unsafe { Padctl::get() };
+        let value = self.register(padctl).read();
+        let [drvup, drvdn, drvup_slwf, drvdn_slwr] = self.fields();
+
+        PadDrive {
+            drvup: drvup.get(value),
+            drvdn: drvdn.get(value),
+            drvup_slwf: drvup_slwf.get(value),
+            drvdn_slwr: drvdn_slwr.get(value),
+        }
+    }
+
+    /// Writes new drive settings of the pads.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if a setting exceeds the
+    /// range of its field.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn set_drive(self, drive: PadDrive) -> Result<()> {
+        let padctl = unsafe { Padctl::get() };
+        let fields = self.fields();
+        let values = [drive.drvup, drive.drvdn, drive.drvup_slwf, drive.drvdn_slwr];
+
+        if fields.iter().zip(values.iter()).any(|(field, value)| *value > field.max()) {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.register(padctl).modify(|register| {
+            fields
+                .iter()
+                .zip(values.iter())
+                .fold(register, |register, (field, value)| field.set(register, *value))
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the registers match the offsets of the TRM.
+    #[test]
+    fn layout() {
+        let padctl = unsafe { Padctl::get() };
+        let address = |register: &Mmio<u32>| register as *const _ as u32;
+
+        assert_eq!(address(&padctl.sdmmc1_clk_lpbk_control), 0x7000_08D4);
+        assert_eq!(address(&padctl.sdmmc1_pad_cfgpadctrl), 0x7000_0A98);
+        assert_eq!(address(&padctl.sdmmc3_pad_cfgpadctrl), 0x7000_0AB0);
+        assert_eq!(address(&padctl.emmc4_pad_cfgpadctrl), 0x7000_0AB4);
+        assert_eq!(address(&padctl.vgpio_gpio_mux_sel), 0x7000_0B74);
+    }
+
+    /// Tests writing drive settings against mocked registers.
+    #[cfg(feature = "mock")]
+    #[test]
+    fn set_drive() {
+        let _session = mirage_mmio::mock::start();
+        let padctl = unsafe { Padctl::get() };
+
+        let drive = PadDrive {
+            drvup: 0x12,
+            drvdn: 0x15,
+            drvup_slwf: 1,
+            drvdn_slwr: 2,
+        };
+        PadGroup::Sdmmc1.set_drive(drive).unwrap();
+        assert_eq!(padctl.sdmmc1_pad_cfgpadctrl.read(), 0x6121_5000);
+        assert_eq!(PadGroup::Sdmmc1.drive(), drive);
+
+        let drive = PadDrive { drvup: 0x40, ..drive };
+        assert_eq!(PadGroup::Emmc4.set_drive(drive), Err(Error::InvalidArgument));
+        assert_eq!(padctl.emmc4_pad_cfgpadctrl.read(), 0);
+    }
+}
@@ -0,0 +1,491 @@
+//! APB DMA controller driver for Tegra210.
+//!
+//! # Description
+//!
+//! The APB DMA controller moves data between memory and the FIFOs of
+//! peripherals on the APB bus, such as the UARTs and I²C controllers,
+//! without involving the CPU for every single byte. It provides 32
+//! channels, each of which is triggered by the flow control request of
+//! a peripheral.
+//!
+//! # Implementation
+//!
+//! - [`Channel::allocate`] claims a free channel, which is released again
+//! when the [`Channel`] is dropped. [`init`] has to be called once before
+//! any channel can be used.
+//!
+//! - A [`Transfer`] describes the peripheral [`Request`], the [`Direction`]
+//! and the addresses and size of the data to move. Memory must be
+//! word-aligned, lie within IRAM or DRAM and is always accessed in words,
+//! the peripheral side is accessed with the given [`BusWidth`].
+//!
+//! - [`Channel::start`] kicks off a transfer. Completion can either be
+//! polled through [`Channel::wait`] or be signaled through a callback
+//! passed to [`Channel::start_with_callback`], which is invoked from
+//! [`poll`]. [`poll`] can be registered with the [`sched`] module to
+//! run periodically.
+//!
+//! - [`Channel::write`] and [`Channel::read`] are blocking convenience
+//! wrappers around word buffers.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{apbdma::{self, Channel}, peripherals::Peripherals};
+//!
+//! fn main() {
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!     apbdma::init();
+//!
+//!     let mut channel = Channel::allocate().unwrap();
+//!     peripherals.uart_b.write_dma(&mut channel, b"Hello from DMA!\r\n").unwrap();
+//! }
+//! ```
+//!
+//! [`Channel::allocate`]: struct.Channel.html#method.allocate
+//! [`Channel`]: struct.Channel.html
+//! [`init`]: fn.init.html
+//! [`Transfer`]: struct.Transfer.html
+//! [`Request`]: enum.Request.html
+//! [`Direction`]: enum.Direction.html
+//! [`BusWidth`]: enum.BusWidth.html
+//! [`Channel::start`]: struct.Channel.html#method.start
+//! [`Channel::wait`]: struct.Channel.html#method.wait
+//! [`Channel::start_with_callback`]: struct.Channel.html#method.start_with_callback
+//! [`poll`]: fn.poll.html
+//! [`sched`]: ../sched/index.html
+//! [`Channel::write`]: struct.Channel.html#method.write
+//! [`Channel::read`]: struct.Channel.html#method.read
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use crate::{
+    clock::Clock,
+    memory_map::{self, Region, DMA_MEMORIES},
+    timer::wait_for,
+    warn,
+};
+
+/// Base address for the APB DMA registers.
+pub(crate) const APBDMA_BASE: u32 = 0x6002_0000;
+
+/// Base address for the APB DMA channel registers.
+const APBDMA_CHANNEL_BASE: u32 = APBDMA_BASE + 0x1000;
+
+/// The size of a channel register block.
+const APBDMA_CHANNEL_STRIDE: u32 = 0x40;
+
+/// The number of APB DMA channels.
+pub const CHANNEL_COUNT: usize = 32;
+
+/// The maximum number of words a single transfer can move.
+pub const MAX_TRANSFER_WORDS: u32 = 0x1_0000;
+
+/// The default time to wait for a transfer to complete in microseconds.
+pub const DEFAULT_TIMEOUT: u32 = 1_000_000;
+
+/// Enables the APB DMA controller in `COMMAND`.
+const COMMAND_GEN: u32 = 1 << 31;
+
+/// Enables the channel.
+const CSR_ENB: u32 = 1 << 31;
+/// Raises an interrupt at the end of the transfer.
+const CSR_IE_EOC: u32 = 1 << 30;
+/// Transfers from memory to the peripheral.
+const CSR_DIR: u32 = 1 << 28;
+/// Stops the channel after a single transfer.
+const CSR_ONCE: u32 = 1 << 27;
+/// Uses the flow control request of the peripheral.
+const CSR_FLOW: u32 = 1 << 21;
+/// The shift of the request selector.
+const CSR_REQ_SEL_SHIFT: u32 = 16;
+
+/// The channel is busy.
+const STA_BUSY: u32 = 1 << 31;
+/// The transfer has completed.
+const STA_ISE_EOC: u32 = 1 << 30;
+
+/// Enables the AHB interrupt.
+const AHB_SEQ_INTR_ENB: u32 = 1 << 31;
+/// 32-bit memory accesses.
+const AHB_SEQ_BUS_WIDTH_32: u32 = 2 << 28;
+/// Single-word memory bursts.
+const AHB_SEQ_BURST_1: u32 = 4 << 24;
+
+/// Keeps the peripheral address fixed at a single word.
+const APB_SEQ_WRAP_WORD_1: u32 = 1 << 16;
+/// The shift of the peripheral bus width.
+const APB_SEQ_BUS_WIDTH_SHIFT: u32 = 28;
+
+/// Representation of the global APB DMA registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct Registers {
+    pub COMMAND: Mmio<u32>,
+    pub STATUS: Mmio<u32>,
+    _0x8: [Mmio<u32>; 2],
+    pub CNTRL_REG: Mmio<u32>,
+    pub IRQ_STA_CPU: Mmio<u32>,
+    pub IRQ_STA_COP: Mmio<u32>,
+    pub IRQ_MASK: Mmio<u32>,
+    pub IRQ_MASK_SET: Mmio<u32>,
+    pub IRQ_MASK_CLR: Mmio<u32>,
+    pub TRIG_REG: Mmio<u32>,
+    pub CHANNEL_TRIG_REG: Mmio<u32>,
+    pub DMA_STATUS: Mmio<u32>,
+    pub CHANNEL_EN_REG: Mmio<u32>,
+}
+
+impl VolatileStorage for Registers {
+    unsafe fn make_ptr() -> *const Self {
+        APBDMA_BASE as *const _
+    }
+}
+
+/// Representation of the registers of an APB DMA channel.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct ChannelRegisters {
+    pub CSR: Mmio<u32>,
+    pub STA: Mmio<u32>,
+    pub CSRE: Mmio<u32>,
+    _0xC: Mmio<u32>,
+    pub AHB_PTR: Mmio<u32>,
+    pub AHB_SEQ: Mmio<u32>,
+    pub APB_PTR: Mmio<u32>,
+    pub APB_SEQ: Mmio<u32>,
+    pub WCOUNT: Mmio<u32>,
+    pub WORD_TRANSFER: Mmio<u32>,
+}
+
+/// Enumeration of possible errors when using the APB DMA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// All channels are in use.
+    NoChannel,
+    /// The memory buffer is not word-aligned.
+    Misaligned,
+    /// The memory buffer doesn't lie within IRAM or DRAM.
+    OutOfBounds,
+    /// The transfer is empty or exceeds [`MAX_TRANSFER_WORDS`].
+    ///
+    /// [`MAX_TRANSFER_WORDS`]: constant.MAX_TRANSFER_WORDS.html
+    InvalidSize,
+    /// The channel is still busy with a previous transfer.
+    Busy,
+    /// The transfer didn't complete in time.
+    Timeout,
+}
+
+/// The flow control requests of peripherals which can trigger a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// UART A.
+    UartA = 8,
+    /// UART B.
+    UartB = 9,
+    /// UART C.
+    UartC = 10,
+    /// UART D.
+    UartD = 19,
+    /// I²C 1.
+    I2c1 = 21,
+    /// I²C 2.
+    I2c2 = 22,
+    /// I²C 3.
+    I2c3 = 23,
+    /// I²C 5.
+    I2c5 = 24,
+    /// I²C 4.
+    I2c4 = 26,
+    /// I²C 6.
+    I2c6 = 30,
+}
+
+/// The direction of a transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Reads from the peripheral FIFO into memory.
+    PeripheralToMemory,
+    /// Writes from memory into the peripheral FIFO.
+    MemoryToPeripheral,
+}
+
+/// The width of the peripheral accesses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusWidth {
+    /// 8-bit accesses, e.g. for UART FIFOs.
+    Bits8 = 0,
+    /// 16-bit accesses.
+    Bits16 = 1,
+    /// 32-bit accesses, e.g. for I²C packet FIFOs.
+    Bits32 = 2,
+}
+
+/// Description of a single transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    /// The peripheral whose request triggers the transfer.
+    pub request: Request,
+    /// The direction of the transfer.
+    pub direction: Direction,
+    /// The physical address of the peripheral FIFO.
+    pub peripheral: u32,
+    /// The width of the peripheral accesses.
+    pub width: BusWidth,
+    /// The physical, word-aligned address of the memory buffer.
+    pub memory: u32,
+    /// The number of words to transfer.
+    pub words: u32,
+}
+
+/// A callback to be run when a transfer has completed.
+pub type Callback = fn();
+
+static mut ALLOCATED: u32 = 0;
+
+static mut CALLBACKS: [Option<Callback>; CHANNEL_COUNT] = [None; CHANNEL_COUNT];
+
+/// Enables the APB DMA controller.
+pub fn init() {
+    let registers = unsafe { Registers::get() };
+
+    let mut clock = Clock::APBDMA;
+
+    if !clock.is_enabled() {
+        clock.enable();
+    }
+
+    registers.COMMAND.write(COMMAND_GEN);
+}
+
+/// Runs the callbacks of all channels whose transfers have completed.
+///
+/// Callbacks are only run once per transfer.
+pub fn poll() {
+    for index in 0..CHANNEL_COUNT {
+        let callback = unsafe { CALLBACKS[index] };
+
+        if let Some(callback) = callback {
+            let registers = unsafe { &*channel_registers(index as u8) };
+
+            if registers.STA.read() & STA_ISE_EOC != 0 {
+                registers.STA.write(STA_ISE_EOC);
+                unsafe {
+                    CALLBACKS[index] = None;
+                }
+
+                callback();
+            }
+        }
+    }
+}
+
+/// Gets a pointer to the registers of a channel.
+fn channel_registers(index: u8) -> *const ChannelRegisters {
+    (APBDMA_CHANNEL_BASE + u32::from(index) * APBDMA_CHANNEL_STRIDE) as *const _
+}
+
+/// An allocated APB DMA channel.
+#[derive(Debug)]
+pub struct Channel {
+    /// The index of the channel.
+    index: u8,
+    /// A pointer to the channel registers.
+    registers: *const ChannelRegisters,
+}
+
+impl Channel {
+    /// Claims a free channel.
+    pub fn allocate() -> Result<Self, Error> {
+        unsafe {
+            let index = (!ALLOCATED).trailing_zeros();
+            if index as usize >= CHANNEL_COUNT {
+                return Err(Error::NoChannel);
+            }
+
+            ALLOCATED |= 1 << index;
+
+            Ok(Channel {
+                index: index as u8,
+                registers: channel_registers(index as u8),
+            })
+        }
+    }
+
+    /// Gets the index of the channel.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Whether the channel is busy with a transfer.
+    pub fn is_busy(&self) -> bool {
+        let registers = unsafe { &*self.registers };
+
+        registers.CSR.read() & CSR_ENB != 0 && registers.STA.read() & STA_BUSY != 0
+    }
+
+    /// Starts a transfer.
+    ///
+    /// # Safety
+    ///
+    /// The memory described by the transfer must stay valid and must not
+    /// be accessed otherwise until the transfer has completed.
+    pub unsafe fn start(&mut self, transfer: &Transfer) -> Result<(), Error> {
+        if transfer.memory & 3 != 0 {
+            return Err(Error::Misaligned);
+        }
+
+        if transfer.words == 0 || transfer.words > MAX_TRANSFER_WORDS {
+            return Err(Error::InvalidSize);
+        }
+
+        let buffer = Region::new("APB DMA buffer", transfer.memory, transfer.words * 4);
+        if memory_map::check_bounds(&buffer, &DMA_MEMORIES).is_err() {
+            return Err(Error::OutOfBounds);
+        }
+
+        if self.is_busy() {
+            return Err(Error::Busy);
+        }
+
+        let registers = &*self.registers;
+
+        // Disable the channel and clear a stale completion.
+        registers.CSR.write(0);
+        registers.STA.write(STA_ISE_EOC);
+
+        registers.AHB_PTR.write(transfer.memory);
+        registers
+            .AHB_SEQ
+            .write(AHB_SEQ_INTR_ENB | AHB_SEQ_BUS_WIDTH_32 | AHB_SEQ_BURST_1);
+        registers.APB_PTR.write(transfer.peripheral);
+        registers
+            .APB_SEQ
+            .write(((transfer.width as u32) << APB_SEQ_BUS_WIDTH_SHIFT) | APB_SEQ_WRAP_WORD_1);
+        registers.WCOUNT.write(transfer.words - 1);
+
+        let mut csr =
+            CSR_IE_EOC | CSR_ONCE | CSR_FLOW | ((transfer.request as u32) << CSR_REQ_SEL_SHIFT);
+        if transfer.direction == Direction::MemoryToPeripheral {
+            csr |= CSR_DIR;
+        }
+
+        registers.CSR.write(csr);
+        registers.CSR.write(csr | CSR_ENB);
+
+        Ok(())
+    }
+
+    /// Starts a transfer and runs the given callback from [`poll`]
+    /// once it has completed.
+    ///
+    /// # Safety
+    ///
+    /// See [`Channel::start`].
+    ///
+    /// [`poll`]: fn.poll.html
+    /// [`Channel::start`]: struct.Channel.html#method.start
+    pub unsafe fn start_with_callback(
+        &mut self,
+        transfer: &Transfer,
+        callback: Callback,
+    ) -> Result<(), Error> {
+        CALLBACKS[self.index as usize] = Some(callback);
+
+        self.start(transfer).map_err(|error| {
+            CALLBACKS[self.index as usize] = None;
+            error
+        })
+    }
+
+    /// Waits for the current transfer to complete for at most
+    /// `timeout` microseconds.
+    ///
+    /// The channel is stopped if the transfer times out.
+    pub fn wait(&mut self, timeout: u32) -> Result<(), Error> {
+        let registers = unsafe { &*self.registers };
+
+        if wait_for(|| registers.STA.read() & STA_ISE_EOC != 0, timeout).is_err() {
+            warn!("APB DMA channel {} timed out", self.index());
+            self.stop();
+            return Err(Error::Timeout);
+        }
+
+        registers.STA.write(STA_ISE_EOC);
+
+        Ok(())
+    }
+
+    /// Stops the current transfer.
+    pub fn stop(&mut self) {
+        let registers = unsafe { &*self.registers };
+
+        registers.CSR.write(registers.CSR.read() & !CSR_ENB);
+        registers.STA.write(STA_ISE_EOC);
+
+        unsafe {
+            CALLBACKS[self.index as usize] = None;
+        }
+    }
+
+    /// Writes a buffer of words to a peripheral FIFO and waits
+    /// for the transfer to complete.
+    pub fn write(
+        &mut self,
+        request: Request,
+        peripheral: u32,
+        width: BusWidth,
+        buffer: &[u32],
+    ) -> Result<(), Error> {
+        let transfer = Transfer {
+            request,
+            direction: Direction::MemoryToPeripheral,
+            peripheral,
+            width,
+            memory: buffer.as_ptr() as u32,
+            words: buffer.len() as u32,
+        };
+
+        unsafe {
+            self.start(&transfer)?;
+        }
+
+        self.wait(DEFAULT_TIMEOUT)
+    }
+
+    /// Reads words from a peripheral FIFO into a buffer and waits
+    /// for the transfer to complete.
+    pub fn read(
+        &mut self,
+        request: Request,
+        peripheral: u32,
+        width: BusWidth,
+        buffer: &mut [u32],
+    ) -> Result<(), Error> {
+        let transfer = Transfer {
+            request,
+            direction: Direction::PeripheralToMemory,
+            peripheral,
+            width,
+            memory: buffer.as_mut_ptr() as u32,
+            words: buffer.len() as u32,
+        };
+
+        unsafe {
+            self.start(&transfer)?;
+        }
+
+        self.wait(DEFAULT_TIMEOUT)
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.stop();
+
+        unsafe {
+            ALLOCATED &= !(1 << self.index);
+        }
+    }
+}
@@ -0,0 +1,530 @@
+//! Cache maintenance, MMU and barrier helpers for the executing processor.
+//!
+//! # Description
+//!
+//! The BPMP (also known as AVP) is an ARM7TDMI core without a coprocessor
+//! for cache control. Instead, it sits behind a dedicated cache controller
+//! mapped to `0x50040000`, which also contains a small MMU that decides
+//! which address ranges may be cached.
+//!
+//! DMA engines like the TSEC falcon, the SE or the SDMMC ADMA access memory
+//! directly, bypassing this cache. Therefore, buffers shared with hardware
+//! must be cleaned before a device reads them and invalidated before the
+//! CPU reads what a device has written.
+//!
+//! # Implementation
+//!
+//! - [`enable_cache`] sets up the MMU with cacheable IRAM and DRAM entries
+//! and enables the cache. [`disable_cache`] cleans and turns it off again.
+//!
+//! - The BPMP cache is unified, so there are no separate instruction and
+//! data cache operations. [`clean_cache`], [`invalidate_cache`] and
+//! [`clean_invalidate_cache`] operate on all ways of the cache.
+//!
+//! - [`clean_range`], [`invalidate_range`] and [`clean_invalidate_range`]
+//! operate on all cache lines covering a range of physical memory.
+//!
+//! - [`last_abort`] reports the cause of the last access the MMU aborted.
+//!
+//! - [`data_barrier`] and [`instruction_barrier`] order memory accesses and
+//! instruction fetches respectively.
+//!
+//! - When built for the CCPLEX, the range and barrier operations use the
+//! AArch64 `dc` and `dsb`/`isb` instructions. On any other target, e.g. for
+//! host tests, all operations are no-ops.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::arch;
+//!
+//! fn main() {
+//!     let buffer = [0u8; 0x200];
+//!
+//!     // Make sure a DMA engine sees the contents of the buffer.
+//!     arch::clean_range(buffer.as_ptr() as usize, buffer.len());
+//! }
+//! ```
+//!
+//! [`enable_cache`]: fn.enable_cache.html
+//! [`disable_cache`]: fn.disable_cache.html
+//! [`clean_cache`]: fn.clean_cache.html
+//! [`invalidate_cache`]: fn.invalidate_cache.html
+//! [`clean_invalidate_cache`]: fn.clean_invalidate_cache.html
+//! [`clean_range`]: fn.clean_range.html
+//! [`invalidate_range`]: fn.invalidate_range.html
+//! [`clean_invalidate_range`]: fn.clean_invalidate_range.html
+//! [`last_abort`]: fn.last_abort.html
+//! [`data_barrier`]: fn.data_barrier.html
+//! [`instruction_barrier`]: fn.instruction_barrier.html
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+#[cfg(target_arch = "arm")]
+use mirage_mmio::Mmio;
+
+use crate::memory_map::{Region, DRAM, IRAM};
+#[cfg(target_arch = "arm")]
+use crate::timer::wait_for;
+
+/// Base address for the BPMP cache controller registers.
+#[cfg(target_arch = "arm")]
+pub(crate) const BPMP_CACHE_BASE: u32 = 0x5004_0000;
+
+/// The size of a cache line in bytes.
+#[cfg(target_arch = "aarch64")]
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// The size of a cache line in bytes.
+#[cfg(not(target_arch = "aarch64"))]
+pub const CACHE_LINE_SIZE: usize = 32;
+
+/// The number of entries in the BPMP MMU.
+pub const MMU_ENTRY_COUNT: usize = 16;
+
+/// Enables the cache in `CONFIG`.
+#[cfg(target_arch = "arm")]
+const CONFIG_ENABLE_CACHE: u32 = 1 << 0;
+/// Forces write-through for all cacheable accesses.
+#[cfg(target_arch = "arm")]
+const CONFIG_FORCE_WRITE_THROUGH: u32 = 1 << 3;
+/// Aborts accesses that hit a tag check error.
+#[cfg(target_arch = "arm")]
+const CONFIG_TAG_CHECK_ABORT_ON_ERROR: u32 = 1 << 14;
+
+/// Enables the sequencer in `MMU_CFG`.
+#[cfg(target_arch = "arm")]
+const MMU_CFG_SEQ_EN: u32 = 1 << 1;
+/// Enables the TLB in `MMU_CFG`.
+#[cfg(target_arch = "arm")]
+const MMU_CFG_TLB_EN: u32 = 1 << 2;
+/// Stores the address of the last abort.
+#[cfg(target_arch = "arm")]
+const MMU_CFG_ABORT_STORE_LAST: u32 = 1 << 4;
+
+/// Copies the shadow entries into the active MMU entries.
+#[cfg(target_arch = "arm")]
+const MMU_CMD_COPY_SHADOW: u32 = 2;
+
+/// The maintenance request has completed.
+#[cfg(target_arch = "arm")]
+const INT_MAINT_DONE: u32 = 1 << 0;
+
+/// Selects all four ways for a maintenance request.
+#[cfg(target_arch = "arm")]
+const MAINT_REQ_ALL_WAYS: u32 = 0xF << 8;
+
+/// The time in microseconds a maintenance request may take.
+#[cfg(target_arch = "arm")]
+const MAINT_TIMEOUT: u32 = 10_000;
+
+/// The entry caches accesses to its range.
+pub const MMU_CACHED: u32 = 1 << 0;
+/// The entry allows reads from its range.
+pub const MMU_READ: u32 = 1 << 2;
+/// The entry allows writes to its range.
+pub const MMU_WRITE: u32 = 1 << 3;
+/// The entry allows instruction fetches from its range.
+pub const MMU_EXECUTE: u32 = 1 << 4;
+/// The entry is enabled.
+pub const MMU_ENABLED: u32 = 1 << 31;
+
+/// Representation of the BPMP cache controller registers.
+#[cfg(target_arch = "arm")]
+#[allow(non_snake_case)]
+#[repr(C)]
+struct Registers {
+    CONFIG: Mmio<u32>,
+    LOCK: Mmio<u32>,
+    _0x8: Mmio<u32>,
+    SIZE: Mmio<u32>,
+    LFSR: Mmio<u32>,
+    TAG_STATUS: Mmio<u32>,
+    CLKEN_OVERRIDE: Mmio<u32>,
+    _0x1C: Mmio<u32>,
+    MAINT_ADDR: Mmio<u32>,
+    MAINT_DATA: Mmio<u32>,
+    MAINT_REQ: Mmio<u32>,
+    _0x2C: [Mmio<u32>; 5],
+    INT_MASK: Mmio<u32>,
+    INT_CLEAR: Mmio<u32>,
+    INT_RAW_EVENT: Mmio<u32>,
+    INT_STATUS: Mmio<u32>,
+    _0x50: [Mmio<u32>; 0xC],
+    RB_CFG: Mmio<u32>,
+    WB_CFG: Mmio<u32>,
+    _0x88: [Mmio<u32>; 6],
+    MMU_FALLBACK_ENTRY: Mmio<u32>,
+    MMU_SHADOW_COPY_MASK: Mmio<u32>,
+    _0xA8: Mmio<u32>,
+    MMU_CFG: Mmio<u32>,
+    MMU_CMD: Mmio<u32>,
+    MMU_ABORT_STAT: Mmio<u32>,
+    MMU_ABORT_ADDR: Mmio<u32>,
+    MMU_ACTIVE_ENTRIES: Mmio<u32>,
+    _0xC0: [Mmio<u32>; 0x10],
+    MMU_SHADOW_ENTRIES: [MmuEntryRegisters; MMU_ENTRY_COUNT],
+}
+
+/// Representation of a BPMP MMU entry.
+#[cfg(target_arch = "arm")]
+#[allow(non_snake_case)]
+#[repr(C)]
+struct MmuEntryRegisters {
+    MIN_ADDR: Mmio<u32>,
+    MAX_ADDR: Mmio<u32>,
+    ATTR: Mmio<u32>,
+    _0xC: Mmio<u32>,
+}
+
+/// Cache maintenance operations supported by the BPMP cache controller.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+enum Maintenance {
+    /// Cleans the line holding a physical address.
+    CleanPhysical = 1,
+    /// Invalidates the line holding a physical address.
+    InvalidatePhysical = 2,
+    /// Cleans and invalidates the line holding a physical address.
+    CleanInvalidatePhysical = 3,
+    /// Cleans all lines of the selected ways.
+    CleanWay = 17,
+    /// Invalidates all lines of the selected ways.
+    InvalidateWay = 18,
+    /// Cleans and invalidates all lines of the selected ways.
+    CleanInvalidateWay = 19,
+}
+
+/// An entry of the BPMP MMU, describing the attributes of a memory range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MmuEntry {
+    /// The first address covered by the entry.
+    pub start: u32,
+    /// The last address covered by the entry, inclusive.
+    pub end: u32,
+    /// The `MMU_*` attributes of the entry.
+    pub attributes: u32,
+}
+
+impl MmuEntry {
+    /// Creates an enabled entry that covers a memory region.
+    pub fn from_region(region: &Region, attributes: u32) -> Self {
+        MmuEntry {
+            start: region.start,
+            end: region.last(),
+            attributes: attributes | MMU_ENABLED,
+        }
+    }
+}
+
+/// Gets the default MMU entries, which make IRAM and DRAM cacheable.
+pub fn default_mmu_entries() -> [MmuEntry; 2] {
+    let attributes = MMU_CACHED | MMU_READ | MMU_WRITE | MMU_EXECUTE;
+
+    [
+        MmuEntry::from_region(&DRAM, attributes),
+        MmuEntry::from_region(&IRAM, attributes),
+    ]
+}
+
+#[cfg(target_arch = "arm")]
+#[inline]
+fn registers() -> &'static Registers {
+    unsafe { &*(BPMP_CACHE_BASE as *const Registers) }
+}
+
+/// Issues a maintenance request and blocks until it has completed.
+#[cfg(target_arch = "arm")]
+fn maintain(operation: Maintenance, address: u32, force: bool) {
+    let register_base = registers();
+
+    // Maintenance on a disabled cache is meaningless, except during setup.
+    if register_base.CONFIG.read() & CONFIG_ENABLE_CACHE == 0 && !force {
+        return;
+    }
+
+    register_base.INT_CLEAR.write(INT_MAINT_DONE);
+    register_base.MAINT_ADDR.write(address);
+    register_base
+        .MAINT_REQ
+        .write(MAINT_REQ_ALL_WAYS | operation as u32);
+
+    // There's no way to recover from a stuck cache controller, so carry on.
+    wait_for(
+        || register_base.INT_RAW_EVENT.read() & INT_MAINT_DONE != 0,
+        MAINT_TIMEOUT,
+    )
+    .ok();
+
+    register_base
+        .INT_CLEAR
+        .write(register_base.INT_RAW_EVENT.read());
+}
+
+/// Issues a maintenance request for every cache line covering a range.
+#[allow(unused_variables)]
+fn maintain_range(operation: Maintenance, address: usize, len: usize) {
+    if len == 0 {
+        return;
+    }
+
+    let start = address & !(CACHE_LINE_SIZE - 1);
+    let end = address + len;
+
+    data_barrier();
+
+    #[cfg(target_arch = "arm")]
+    for line in (start..end).step_by(CACHE_LINE_SIZE) {
+        maintain(operation, line as u32, false);
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    for line in (start..end).step_by(CACHE_LINE_SIZE) {
+        unsafe {
+            match operation {
+                Maintenance::CleanPhysical => {
+                    asm!("dc cvac, $0" :: "r"(line) : "memory" : "volatile")
+                }
+                Maintenance::InvalidatePhysical => {
+                    asm!("dc ivac, $0" :: "r"(line) : "memory" : "volatile")
+                }
+                _ => asm!("dc civac, $0" :: "r"(line) : "memory" : "volatile"),
+            }
+        }
+    }
+
+    data_barrier();
+}
+
+/// Whether the BPMP cache is enabled.
+pub fn is_cache_enabled() -> bool {
+    #[cfg(target_arch = "arm")]
+    return registers().CONFIG.read() & CONFIG_ENABLE_CACHE != 0;
+
+    #[cfg(not(target_arch = "arm"))]
+    false
+}
+
+/// Gets the status and the address of the last abort caught by the BPMP
+/// MMU, as `(status, address)`.
+pub fn last_abort() -> (u32, u32) {
+    #[cfg(target_arch = "arm")]
+    return (
+        registers().MMU_ABORT_STAT.read(),
+        registers().MMU_ABORT_ADDR.read(),
+    );
+
+    #[cfg(not(target_arch = "arm"))]
+    (0, 0)
+}
+
+/// Programs an entry of the BPMP MMU.
+///
+/// The entry takes effect once the shadow entries are applied,
+/// which [`enable_cache`] takes care of.
+///
+/// [`enable_cache`]: fn.enable_cache.html
+///
+/// # Panics
+///
+/// Panics if `index` is not below [`MMU_ENTRY_COUNT`].
+///
+/// [`MMU_ENTRY_COUNT`]: constant.MMU_ENTRY_COUNT.html
+#[allow(unused_variables)]
+pub fn set_mmu_entry(index: usize, entry: &MmuEntry) {
+    assert!(index < MMU_ENTRY_COUNT, "Invalid MMU entry index");
+
+    #[cfg(target_arch = "arm")]
+    {
+        let register_base = registers();
+        let shadow = &register_base.MMU_SHADOW_ENTRIES[index];
+
+        shadow.MIN_ADDR.write(entry.start);
+        shadow.MAX_ADDR.write(entry.end);
+        shadow.ATTR.write(entry.attributes);
+
+        register_base
+            .MMU_SHADOW_COPY_MASK
+            .write(register_base.MMU_SHADOW_COPY_MASK.read() | (1 << index));
+    }
+}
+
+/// Sets up the BPMP MMU with the [`default_mmu_entries`] and enables
+/// the cache.
+///
+/// [`default_mmu_entries`]: fn.default_mmu_entries.html
+pub fn enable_cache() {
+    #[cfg(target_arch = "arm")]
+    {
+        let register_base = registers();
+
+        if is_cache_enabled() {
+            return;
+        }
+
+        // Accesses outside of any entry go uncached.
+        register_base.MMU_SHADOW_COPY_MASK.write(0);
+        register_base
+            .MMU_FALLBACK_ENTRY
+            .write(MMU_READ | MMU_WRITE | MMU_EXECUTE);
+        register_base
+            .MMU_CFG
+            .write(MMU_CFG_SEQ_EN | MMU_CFG_TLB_EN | MMU_CFG_ABORT_STORE_LAST);
+
+        for (index, entry) in default_mmu_entries().iter().enumerate() {
+            set_mmu_entry(index, entry);
+        }
+        register_base.MMU_CMD.write(MMU_CMD_COPY_SHADOW);
+
+        maintain(Maintenance::InvalidateWay, 0, true);
+
+        register_base.CONFIG.write(
+            CONFIG_ENABLE_CACHE | CONFIG_FORCE_WRITE_THROUGH | CONFIG_TAG_CHECK_ABORT_ON_ERROR,
+        );
+
+        // Lines may be allocated while the cache is being enabled, invalidate again.
+        maintain(Maintenance::InvalidateWay, 0, false);
+    }
+}
+
+/// Cleans and disables the BPMP cache.
+pub fn disable_cache() {
+    #[cfg(target_arch = "arm")]
+    {
+        if !is_cache_enabled() {
+            return;
+        }
+
+        maintain(Maintenance::CleanInvalidateWay, 0, false);
+        registers().CONFIG.write(0);
+    }
+}
+
+/// Writes all dirty lines of the cache back to memory.
+pub fn clean_cache() {
+    #[cfg(target_arch = "arm")]
+    maintain(Maintenance::CleanWay, 0, false);
+}
+
+/// Discards all lines of the cache, including dirty ones.
+pub fn invalidate_cache() {
+    #[cfg(target_arch = "arm")]
+    maintain(Maintenance::InvalidateWay, 0, false);
+
+    instruction_barrier();
+}
+
+/// Writes all dirty lines of the cache back to memory and discards them.
+pub fn clean_invalidate_cache() {
+    #[cfg(target_arch = "arm")]
+    maintain(Maintenance::CleanInvalidateWay, 0, false);
+
+    instruction_barrier();
+}
+
+/// Writes the cache lines covering a range of memory back to memory,
+/// e.g. before a DMA engine reads from it.
+pub fn clean_range(address: usize, len: usize) {
+    maintain_range(Maintenance::CleanPhysical, address, len);
+}
+
+/// Discards the cache lines covering a range of memory,
+/// e.g. before reading what a DMA engine has written to it.
+///
+/// Note that dirty data in lines shared with neighboring memory is lost,
+/// so buffers should be aligned to [`CACHE_LINE_SIZE`].
+///
+/// [`CACHE_LINE_SIZE`]: constant.CACHE_LINE_SIZE.html
+pub fn invalidate_range(address: usize, len: usize) {
+    maintain_range(Maintenance::InvalidatePhysical, address, len);
+}
+
+/// Writes the cache lines covering a range of memory back
+/// to memory and discards them.
+pub fn clean_invalidate_range(address: usize, len: usize) {
+    maintain_range(Maintenance::CleanInvalidatePhysical, address, len);
+}
+
+/// Ensures that all memory accesses before the barrier have completed
+/// before any access after it.
+pub fn data_barrier() {
+    compiler_fence(Ordering::SeqCst);
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("dsb sy" ::: "memory" : "volatile");
+    }
+
+    // The ARM7 has no barrier instructions, reading back from the
+    // cache controller drains its write buffer instead.
+    #[cfg(target_arch = "arm")]
+    registers().CONFIG.read();
+}
+
+/// Ensures that instructions after the barrier are fetched anew,
+/// e.g. after code has been copied to memory.
+pub fn instruction_barrier() {
+    data_barrier();
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("isb" ::: "memory" : "volatile");
+    }
+}
+
+/// Masks IRQs and FIQs and returns the previous interrupt state.
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+pub(crate) fn disable_interrupts() -> u32 {
+    let cpsr: u32;
+
+    unsafe {
+        asm!("mrs $0, cpsr" : "=r"(cpsr) ::: "volatile");
+        asm!("msr cpsr_c, $0" :: "r"(cpsr | 0xC0) : "memory" : "volatile");
+    }
+
+    cpsr
+}
+
+/// Restores an interrupt state returned by `disable_interrupts`.
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+pub(crate) fn restore_interrupts(state: u32) {
+    unsafe {
+        asm!("msr cpsr_c, $0" :: "r"(state) : "memory" : "volatile");
+    }
+}
+
+/// Masks IRQs and FIQs and returns the previous interrupt state.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) fn disable_interrupts() -> u32 {
+    let daif: u64;
+
+    unsafe {
+        asm!("mrs $0, daif" : "=r"(daif) ::: "volatile");
+        asm!("msr daifset, #3" ::: "memory" : "volatile");
+    }
+
+    daif as u32
+}
+
+/// Restores an interrupt state returned by `disable_interrupts`.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) fn restore_interrupts(state: u32) {
+    unsafe {
+        asm!("msr daif, $0" :: "r"(u64::from(state)) : "memory" : "volatile");
+    }
+}
+
+/// Interrupts don't exist on hosted targets.
+#[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+pub(crate) fn disable_interrupts() -> u32 {
+    0
+}
+
+/// Interrupts don't exist on hosted targets.
+#[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+pub(crate) fn restore_interrupts(_state: u32) {}
@@ -0,0 +1,134 @@
+//! Audio hub routing and ADMA playback.
+//!
+//! # Description
+//!
+//! Samples reach an I2S controller through the audio hub (AHUB). The
+//! audio DMA controller (ADMA) reads them from memory and feeds them into
+//! an ADMAIF channel, from where the crossbar (XBAR) routes them to the
+//! receiving client, here the I2S controller.
+//!
+//! # Implementation
+//!
+//! - [`route`] connects ADMAIF1 to an I2S controller through the XBAR.
+//!
+//! - [`play`] loops a buffer of samples through ADMA channel 1 until
+//! [`stop`] is called.
+//!
+//! [`route`]: fn.route.html
+//! [`play`]: fn.play.html
+//! [`stop`]: fn.stop.html
+
+use mirage_mmio::Mmio;
+
+use super::i2s::{I2s, CIF_STEREO_16};
+
+/// Base address for the ADMAIF registers.
+const ADMAIF_BASE: u32 = 0x702D_0000;
+/// Base address for the XBAR registers.
+const XBAR_BASE: u32 = 0x702D_0800;
+/// Base address for the ADMA registers.
+const ADMA_BASE: u32 = 0x702E_2000;
+
+/// The `ADMAIF_TX1_ENABLE` register.
+const ADMAIF_TX1_ENABLE: u32 = 0x300;
+/// The `ADMAIF_TX1_SOFT_RESET` register.
+const ADMAIF_TX1_SOFT_RESET: u32 = 0x304;
+/// The `ADMAIF_TX1_CIF_CTRL` register.
+const ADMAIF_TX1_CIF_CTRL: u32 = 0x320;
+/// The `ADMAIF_TX1_FIFO_CTRL` register.
+const ADMAIF_TX1_FIFO_CTRL: u32 = 0x328;
+/// The `ADMAIF_GLOBAL_ENABLE` register.
+const ADMAIF_GLOBAL_ENABLE: u32 = 0x700;
+
+/// The `XBAR_PART0_I2S1_0` register, which selects the source of I2S1.
+const XBAR_I2S1: u32 = 0x40;
+/// The distance between the XBAR registers of two I2S controllers.
+const XBAR_I2S_STRIDE: u32 = 0x4;
+/// Selects ADMAIF1 as the source of a client.
+const XBAR_SOURCE_ADMAIF1: u32 = 1 << 0;
+
+/// The `ADMA_CH1_CMD` register.
+const ADMA_CH_CMD: u32 = 0x00;
+/// The `ADMA_CH1_STATUS` register.
+const ADMA_CH_STATUS: u32 = 0x0C;
+/// The `ADMA_CH1_INT_CLEAR` register.
+const ADMA_CH_INT_CLEAR: u32 = 0x1C;
+/// The `ADMA_CH1_CTRL` register.
+const ADMA_CH_CTRL: u32 = 0x24;
+/// The `ADMA_CH1_CONFIG` register.
+const ADMA_CH_CONFIG: u32 = 0x28;
+/// The `ADMA_CH1_AHUB_FIFO_CTRL` register.
+const ADMA_CH_FIFO_CTRL: u32 = 0x2C;
+/// The `ADMA_CH1_LOWER_SRC_ADDR` register.
+const ADMA_CH_LOWER_SRC_ADDR: u32 = 0x34;
+/// The `ADMA_CH1_TC` register.
+const ADMA_CH_TC: u32 = 0x44;
+/// The `ADMA_GLOBAL_CMD` register.
+const ADMA_GLOBAL_CMD: u32 = 0xC00;
+
+/// Starts a transfer in `ADMA_CH_CMD` and enables the controller in
+/// `ADMA_GLOBAL_CMD`.
+const ADMA_ENABLE: u32 = 1 << 0;
+/// Indicates a running transfer in `ADMA_CH_STATUS`.
+const ADMA_CH_STATUS_TRANSFER_ENABLED: u32 = 1 << 0;
+/// The `ADMA_CH_CTRL` value for a continuous transfer from memory to
+/// ADMAIF1 with flow control.
+const ADMA_CH_CTRL_PLAYBACK: u32 = (1 << 28) | (4 << 12) | (2 << 8) | (1 << 1);
+/// The `ADMA_CH_CONFIG` value for single-buffered 16-word bursts.
+const ADMA_CH_CONFIG_PLAYBACK: u32 = (5 << 20) | 1;
+/// The `ADMA_CH_FIFO_CTRL` and `ADMAIF_TX1_FIFO_CTRL` value.
+const FIFO_CTRL: u32 = (3 << 8) | 3;
+
+/// The number of times to poll for a stopped channel.
+const STOP_RETRIES: u32 = 10_000;
+
+/// Gets a register of the given block.
+fn register(base: u32, offset: u32) -> &'static Mmio<u32> {
+    unsafe { &*((base + offset) as *const Mmio<u32>) }
+}
+
+/// Routes the samples of ADMAIF1 to an I2S controller.
+pub fn route(i2s: I2s) {
+    register(ADMAIF_BASE, ADMAIF_GLOBAL_ENABLE).write(1);
+
+    register(ADMAIF_BASE, ADMAIF_TX1_SOFT_RESET).write(1);
+    register(ADMAIF_BASE, ADMAIF_TX1_CIF_CTRL).write(CIF_STEREO_16);
+    register(ADMAIF_BASE, ADMAIF_TX1_FIFO_CTRL).write(FIFO_CTRL);
+
+    register(XBAR_BASE, XBAR_I2S1 + i2s.index() * XBAR_I2S_STRIDE).write(XBAR_SOURCE_ADMAIF1);
+}
+
+/// Loops the given samples through ADMAIF1 until [`stop`] is called.
+///
+/// The buffer must stay alive and unchanged until then.
+///
+/// [`stop`]: fn.stop.html
+pub fn play(samples: &[u32]) {
+    register(ADMA_BASE, ADMA_GLOBAL_CMD).write(ADMA_ENABLE);
+
+    register(ADMA_BASE, ADMA_CH_CTRL).write(ADMA_CH_CTRL_PLAYBACK);
+    register(ADMA_BASE, ADMA_CH_CONFIG).write(ADMA_CH_CONFIG_PLAYBACK);
+    register(ADMA_BASE, ADMA_CH_FIFO_CTRL).write(FIFO_CTRL);
+    register(ADMA_BASE, ADMA_CH_LOWER_SRC_ADDR).write(samples.as_ptr() as u32);
+    register(ADMA_BASE, ADMA_CH_TC).write((samples.len() * 4) as u32);
+
+    register(ADMAIF_BASE, ADMAIF_TX1_ENABLE).write(1);
+    register(ADMA_BASE, ADMA_CH_CMD).write(ADMA_ENABLE);
+}
+
+/// Stops the playback started by [`play`].
+///
+/// [`play`]: fn.play.html
+pub fn stop() {
+    register(ADMA_BASE, ADMA_CH_CMD).write(0);
+
+    let status = register(ADMA_BASE, ADMA_CH_STATUS);
+    for _ in 0..STOP_RETRIES {
+        if status.read() & ADMA_CH_STATUS_TRANSFER_ENABLED == 0 {
+            break;
+        }
+    }
+
+    register(ADMA_BASE, ADMA_CH_INT_CLEAR).write(1);
+    register(ADMAIF_BASE, ADMAIF_TX1_ENABLE).write(0);
+}
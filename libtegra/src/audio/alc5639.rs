@@ -0,0 +1,124 @@
+//! Driver for the Realtek ALC5639 audio codec.
+//!
+//! # Description
+//!
+//! The ALC5639 converts the I²S samples of I2S1 into analog signals for
+//! the speakers and the headphone jack. It is controlled over I2C1 through
+//! 16-bit registers, which are transferred in big-endian byte order.
+//!
+//! # Implementation
+//!
+//! - [`init`] resets the codec, powers up the DAC and the speaker path and
+//! configures the I²S interface for 16-bit stereo samples, clocking the
+//! codec from the bit clock of I2S1.
+//!
+//! - [`write`] and [`read`] access raw codec registers.
+//!
+//! - All functions take the handle of the I2C1 controller.
+//!
+//! [`init`]: fn.init.html
+//! [`write`]: fn.write.html
+//! [`read`]: fn.read.html
+
+use crate::{
+    i2c::{Device, I2c},
+    timer::msleep,
+    Result,
+};
+
+/// The `RT5639_RESET` register.
+const RESET: u8 = 0x00;
+/// The `RT5639_SPK_VOL` register.
+const SPK_VOL: u8 = 0x01;
+/// The `RT5639_HP_VOL` register.
+const HP_VOL: u8 = 0x02;
+/// The `RT5639_DAC1_DIG_VOL` register.
+const DAC1_DIG_VOL: u8 = 0x19;
+/// The `RT5639_STO_DAC_MIXER` register.
+const STO_DAC_MIXER: u8 = 0x2A;
+/// The `RT5639_SPK_L_MIXER` register.
+const SPK_L_MIXER: u8 = 0x46;
+/// The `RT5639_SPK_R_MIXER` register.
+const SPK_R_MIXER: u8 = 0x47;
+/// The `RT5639_SPO_L_MIXER` register.
+const SPO_L_MIXER: u8 = 0x48;
+/// The `RT5639_SPO_R_MIXER` register.
+const SPO_R_MIXER: u8 = 0x49;
+/// The `RT5639_PWR_DIG1` register.
+const PWR_DIG1: u8 = 0x61;
+/// The `RT5639_PWR_ANLG1` register.
+const PWR_ANLG1: u8 = 0x63;
+/// The `RT5639_PWR_ANLG2` register.
+const PWR_ANLG2: u8 = 0x64;
+/// The `RT5639_PWR_MIXER` register.
+const PWR_MIXER: u8 = 0x65;
+/// The `RT5639_PWR_VOL` register.
+const PWR_VOL: u8 = 0x66;
+/// The `RT5639_I2S1_SDP` register.
+const I2S1_SDP: u8 = 0x70;
+/// The `RT5639_ADDA_CLK1` register.
+const ADDA_CLK1: u8 = 0x73;
+/// The `RT5639_GLB_CLK` register.
+const GLB_CLK: u8 = 0x80;
+/// The `RT5639_PLL_CTRL1` register.
+const PLL_CTRL1: u8 = 0x81;
+/// The `RT5639_PLL_CTRL2` register.
+const PLL_CTRL2: u8 = 0x82;
+
+/// The time the codec needs to settle after a reset, in milliseconds.
+const RESET_DELAY: u32 = 10;
+
+/// The register writes that bring up the speaker path, in order.
+static INIT_SEQUENCE: [(u8, u16); 16] = [
+    // Clock the system from PLL1, which is fed by BCLK1.
+    (GLB_CLK, 0x5000),
+    (PLL_CTRL1, 0x0F00),
+    (PLL_CTRL2, 0x0800),
+    (ADDA_CLK1, 0x1000),
+    // I2S1 as slave with 16-bit I²S samples.
+    (I2S1_SDP, 0x0000),
+    // Power up I2S1, the DACs, the bias and the PLL.
+    (PWR_DIG1, 0x9800),
+    (PWR_ANLG1, 0xA810),
+    (PWR_ANLG2, 0x0200),
+    (PWR_MIXER, 0x3000),
+    (PWR_VOL, 0xC000),
+    // Route DAC1 through the speaker mixers to the speaker outputs.
+    (STO_DAC_MIXER, 0x1414),
+    (SPK_L_MIXER, 0x003C),
+    (SPK_R_MIXER, 0x003C),
+    (SPO_L_MIXER, 0xD806),
+    (SPO_R_MIXER, 0x2800),
+    (DAC1_DIG_VOL, 0xAFAF),
+];
+
+/// Writes a 16-bit codec register.
+pub fn write(i2c: &I2c, register: u8, value: u16) -> Result<()> {
+    i2c.write(Device::Alc5639, register, &value.to_be_bytes())?;
+
+    Ok(())
+}
+
+/// Reads a 16-bit codec register.
+pub fn read(i2c: &I2c, register: u8) -> Result<u16> {
+    let mut buffer = [0; 2];
+    i2c.read(Device::Alc5639, register, &mut buffer)?;
+
+    Ok(u16::from_be_bytes(buffer))
+}
+
+/// Resets the codec and brings up its speaker path.
+///
+/// I2C1 has to be initialized beforehand.
+pub fn init(i2c: &I2c) -> Result<()> {
+    write(i2c, RESET, 0)?;
+    msleep(RESET_DELAY);
+
+    for &(register, value) in INIT_SEQUENCE.iter() {
+        write(i2c, register, value)?;
+    }
+
+    // Unmute the outputs last to avoid pops.
+    write(i2c, SPK_VOL, 0x0808)?;
+    write(i2c, HP_VOL, 0x0808)
+}
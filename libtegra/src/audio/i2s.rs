@@ -0,0 +1,179 @@
+//! Tegra210 I2S controller driver.
+//!
+//! # Description
+//!
+//! The audio hub (AHUB) of the Audio Processing Engine contains five I2S
+//! controllers, which serialize samples from the AHUB crossbar to an
+//! external codec and back. The internal codec of the Switch is attached
+//! to I2S1.
+//!
+//! Each controller receives samples from the crossbar through its RX
+//! client interface (CIF) and sends samples it received from the codec
+//! through its TX CIF.
+//!
+//! # Implementation
+//!
+//! - [`I2s`] represents an I2S controller and holds constants for all of
+//! them in [`I2s::ALL`].
+//!
+//! - [`I2s::configure`] sets a controller up as I²S bus master for 16-bit
+//! stereo samples, [`I2s::start`] and [`I2s::stop`] control the playback
+//! path from the crossbar to the codec.
+//!
+//! [`I2s`]: struct.I2s.html
+//! [`I2s::ALL`]: struct.I2s.html#associatedconstant.ALL
+//! [`I2s::configure`]: struct.I2s.html#method.configure
+//! [`I2s::start`]: struct.I2s.html#method.start
+//! [`I2s::stop`]: struct.I2s.html#method.stop
+
+use mirage_mmio::{assert_layout, register_block, Mmio};
+
+use crate::clock::Clock;
+
+/// Base address for the I2S1 registers.
+const I2S_BASE: u32 = 0x702D_1000;
+
+/// The distance between the register blocks of two controllers.
+const I2S_STRIDE: u32 = 0x100;
+
+/// Enables a path or the controller.
+const I2S_ENABLE: u32 = 1 << 0;
+/// Enables second-level clock gating in `CG`.
+pub const I2S_CG_SLCG_ENABLE: u32 = 1 << 0;
+/// Makes the controller drive the bit and frame clocks in `CTRL`.
+pub const I2S_CTRL_MASTER_ENABLE: u32 = 1 << 10;
+/// Selects the LRCK frame format with a high left channel in `CTRL`.
+const I2S_CTRL_FRAME_FORMAT_LRCK: u32 = 1 << 9;
+/// Selects 16-bit samples in `CTRL`.
+const I2S_CTRL_BIT_SIZE_16: u32 = 3;
+/// Delays the data by one bit clock after the frame clock edge, in
+/// `RX_CTRL` and `TX_CTRL`.
+const I2S_DATA_OFFSET_I2S: u32 = 1 << 8;
+/// The number of bit clocks per channel, minus one, in `TIMING`.
+const I2S_TIMING_CHANNEL_BIT_COUNT_16: u32 = 15;
+
+/// The CIF configuration for 16-bit stereo samples.
+///
+/// Both the audio and the client side use two channels of 16 bits.
+pub(super) const CIF_STEREO_16: u32 = (1 << 20) | (1 << 16) | (3 << 12) | (3 << 8);
+
+register_block! {
+    /// Representation of the I2S registers.
+    pub struct Registers {
+        (0x00 => pub rx_enable: Mmio<u32>),
+        (0x04 => pub rx_soft_reset: Mmio<u32>),
+        (0x08 => _reserved0),
+        (0x20 => pub rx_cif_ctrl: Mmio<u32>),
+        (0x24 => pub rx_ctrl: Mmio<u32>),
+        (0x28 => _reserved1),
+        (0x40 => pub tx_enable: Mmio<u32>),
+        (0x44 => pub tx_soft_reset: Mmio<u32>),
+        (0x48 => _reserved2),
+        (0x60 => pub tx_cif_ctrl: Mmio<u32>),
+        (0x64 => pub tx_ctrl: Mmio<u32>),
+        (0x68 => _reserved3),
+        (0x80 => pub enable: Mmio<u32>),
+        (0x84 => pub soft_reset: Mmio<u32>),
+        (0x88 => pub cg: Mmio<u32>),
+        (0x8C => _reserved4),
+        (0xA0 => pub ctrl: Mmio<u32>),
+        (0xA4 => pub timing: Mmio<u32>),
+        (0xA8 => _reserved5),
+        (0x100 => @END),
+    }
+}
+
+assert_layout!(registers_layout, Registers, 0x100, {
+    rx_cif_ctrl: 0x20,
+    tx_enable: 0x40,
+    enable: 0x80,
+    cg: 0x88,
+    ctrl: 0xA0,
+    timing: 0xA4,
+});
+
+/// Representation of an I2S controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct I2s {
+    /// The index of the controller, starting at zero for I2S1.
+    index: u32,
+}
+
+// Definitions for known I2S controllers.
+impl I2s {
+    /// Representation of I2S1, which is attached to the internal codec.
+    pub const I2S1: Self = I2s { index: 0 };
+
+    /// Representation of I2S2.
+    pub const I2S2: Self = I2s { index: 1 };
+
+    /// Representation of I2S3.
+    pub const I2S3: Self = I2s { index: 2 };
+
+    /// Representation of I2S4.
+    pub const I2S4: Self = I2s { index: 3 };
+
+    /// Representation of I2S5.
+    pub const I2S5: Self = I2s { index: 4 };
+
+    /// All I2S controllers.
+    pub const ALL: [Self; 5] = [I2s::I2S1, I2s::I2S2, I2s::I2S3, I2s::I2S4, I2s::I2S5];
+}
+
+impl I2s {
+    /// Gets the registers of the controller.
+    ///
+    /// The APE has to be clocked and out of reset to access them.
+    pub fn registers(&self) -> &'static Registers {
+        unsafe { &*((I2S_BASE + self.index * I2S_STRIDE) as *const Registers) }
+    }
+
+    /// Gets the index of the controller in the crossbar, starting at zero
+    /// for I2S1.
+    pub(super) fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Configures the controller as I²S bus master for 16-bit stereo
+    /// samples.
+    ///
+    /// Only I2S1 has a clock definition, so other controllers must be
+    /// clocked by the caller.
+    pub fn configure(&self) {
+        let registers = self.registers();
+
+        if *self == I2s::I2S1 {
+            let mut clock = Clock::I2S_1;
+            clock.enable();
+        }
+
+        // Disable second-level clock gating while configuring.
+        registers
+            .cg
+            .write(registers.cg.read() & !I2S_CG_SLCG_ENABLE);
+
+        registers
+            .ctrl
+            .write(I2S_CTRL_MASTER_ENABLE | I2S_CTRL_FRAME_FORMAT_LRCK | I2S_CTRL_BIT_SIZE_16);
+        registers.timing.write(I2S_TIMING_CHANNEL_BIT_COUNT_16);
+        registers.rx_ctrl.write(I2S_DATA_OFFSET_I2S);
+        registers.tx_ctrl.write(I2S_DATA_OFFSET_I2S);
+        registers.rx_cif_ctrl.write(CIF_STEREO_16);
+    }
+
+    /// Starts sending samples from the crossbar to the codec.
+    pub fn start(&self) {
+        let registers = self.registers();
+
+        registers.rx_enable.write(I2S_ENABLE);
+        registers.enable.write(I2S_ENABLE);
+    }
+
+    /// Stops sending samples to the codec.
+    pub fn stop(&self) {
+        let registers = self.registers();
+
+        registers.enable.write(0);
+        registers.rx_enable.write(0);
+    }
+}
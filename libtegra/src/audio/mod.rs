@@ -0,0 +1,156 @@
+//! Audio output through the Audio Processing Engine.
+//!
+//! # Description
+//!
+//! Headless setups, where the display is unavailable or not yet up, have
+//! no way to signal errors or boot progress to the user. The internal
+//! speakers of the Switch fill that gap.
+//!
+//! Samples are fetched from memory by the audio DMA controller, routed
+//! through the audio hub to the I2S1 controller and converted to analog
+//! signals by the Realtek ALC5639 codec, which is attached to I2C1.
+//!
+//! # Implementation
+//!
+//! - [`init`] clocks the Audio Processing Engine, configures I2S1 and the
+//! codec on the given I2C1 controller and routes the samples between them.
+//! It has to be called before any other function in this module.
+//!
+//! - [`beep`] plays a square wave of a given frequency for a given
+//! duration, blocking until it is over.
+//!
+//! - The [`i2s`] module exposes the I2S controllers, which also need to
+//! be touched during early hardware initialization.
+//!
+//! - The [`alc5639`] module provides access to the registers of the codec.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{audio, peripherals::Peripherals};
+//!
+//! fn main() {
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!
+//!     audio::init(&mut peripherals.i2c_1).unwrap();
+//!
+//!     // Two short beeps to signal an error.
+//!     audio::beep(880, 100).unwrap();
+//!     audio::beep(880, 100).unwrap();
+//! }
+//! ```
+//!
+//! [`init`]: fn.init.html
+//! [`beep`]: fn.beep.html
+//! [`i2s`]: i2s/index.html
+//! [`alc5639`]: alc5639/index.html
+
+mod ahub;
+pub mod alc5639;
+pub mod i2s;
+
+use self::i2s::I2s;
+use crate::{clock::Clock, i2c::I2c, timer::msleep, Error, Result};
+
+/// The sample rate of the audio output, in Hertz.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// The lowest frequency [`beep`] can play, in Hertz.
+///
+/// [`beep`]: fn.beep.html
+pub const MIN_FREQUENCY: u32 = 20;
+
+/// The amplitude of the square wave played by [`beep`].
+///
+/// [`beep`]: fn.beep.html
+const AMPLITUDE: i16 = 0x2000;
+
+/// The number of stereo frames in the sample buffer.
+///
+/// This fits one period of the lowest supported frequency.
+const BUFFER_FRAMES: usize = (SAMPLE_RATE / MIN_FREQUENCY) as usize;
+
+/// The buffer of stereo frames looped by the DMA controller.
+///
+/// Each frame holds the left sample in the low and the right sample in the
+/// high half-word.
+static mut BUFFER: [u32; BUFFER_FRAMES] = [0; BUFFER_FRAMES];
+
+/// Brings up the audio output path, with the codec on `i2c`.
+pub fn init(i2c: &mut I2c) -> Result<()> {
+    for clock in &mut [Clock::APE, Clock::APB2APE, Clock::D_AUDIO] {
+        clock.enable();
+    }
+
+    I2s::I2S1.configure();
+    ahub::route(I2s::I2S1);
+
+    // The codec clocks itself from the bit clock of I2S1.
+    I2s::I2S1.start();
+
+    i2c.init();
+    alc5639::init(i2c)
+}
+
+/// Fills `buffer` with whole periods of a square wave.
+///
+/// Returns the number of frames that were written.
+fn fill_square_wave(buffer: &mut [u32], frequency: u32) -> usize {
+    let period = (SAMPLE_RATE / frequency) as usize;
+    let frames = buffer.len() / period * period;
+
+    for (i, frame) in buffer[..frames].iter_mut().enumerate() {
+        let sample = if i % period < period / 2 {
+            AMPLITUDE
+        } else {
+            -AMPLITUDE
+        } as u16 as u32;
+
+        *frame = sample << 16 | sample;
+    }
+
+    frames
+}
+
+/// Plays a square wave with the given frequency in Hertz for the given
+/// duration in milliseconds.
+///
+/// Fails with [`Error::InvalidArgument`] if the frequency can't be
+/// represented at [`SAMPLE_RATE`].
+///
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+/// [`SAMPLE_RATE`]: constant.SAMPLE_RATE.html
+pub fn beep(frequency: u32, duration: u32) -> Result<()> {
+    if frequency < MIN_FREQUENCY || frequency > SAMPLE_RATE / 2 {
+        return Err(Error::InvalidArgument);
+    }
+
+    let buffer = unsafe { &mut BUFFER };
+    let frames = fill_square_wave(buffer, frequency);
+
+    ahub::play(&buffer[..frames]);
+    msleep(duration);
+    ahub::stop();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_has_whole_periods() {
+        let mut buffer = [0; BUFFER_FRAMES];
+
+        let frames = fill_square_wave(&mut buffer, 440);
+        let period = (SAMPLE_RATE / 440) as usize;
+        assert_eq!(frames % period, 0);
+
+        let high = AMPLITUDE as u16 as u32;
+        let low = -AMPLITUDE as u16 as u32;
+        assert_eq!(buffer[0], high << 16 | high);
+        assert_eq!(buffer[period - 1], low << 16 | low);
+        assert!(buffer[frames..].iter().all(|&frame| frame == 0));
+    }
+}
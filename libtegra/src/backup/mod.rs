@@ -0,0 +1,276 @@
+//! Dumping and restoring of eMMC partitions.
+//!
+//! # Description
+//!
+//! Recovery and maintenance payloads need to back up the eMMC before they
+//! touch it, and to write such backups back later on. Both directions are
+//! verified with SHA-256 hashes, which are calculated by the Security
+//! Engine while the data is being transferred.
+//!
+//! The data is streamed through two caller-provided buffers, so the SE can
+//! hash one chunk while the next one is being read.
+//!
+//! # Implementation
+//!
+//! - [`Write`] and [`Read`] are the sinks and sources of the data, like a
+//! file on the SD card or a USB endpoint.
+//!
+//! - [`dump_partition`] reads a partition from a [`BlockDevice`] into a
+//! [`Write`] sink and returns the SHA-256 hash of its contents.
+//! [`dump_blocks`] does the same for an arbitrary range of blocks, such as
+//! the whole `BOOT0` hardware partition.
+//!
+//! - [`restore_partition`] and [`restore_blocks`] write the contents of a
+//! [`Read`] source back and check them against an expected hash, both
+//! during the transfer and by reading the written data back through
+//! [`verify_blocks`].
+//!
+//! - All functions report the amount of bytes transferred so far and the
+//! total size to a progress callback after every chunk.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     backup::{self, Write},
+//!     block::BlockDevice,
+//!     gpt::Gpt,
+//!     Result,
+//! };
+//!
+//! fn dump_prodinfo<D: BlockDevice, W: Write>(emmc: &mut D, sink: &mut W) -> Result<[u8; 32]> {
+//!     let mut buffers = [[0; 0x4000]; 2];
+//!     let (a, b) = buffers.split_at_mut(1);
+//!
+//!     let gpt = Gpt::read(emmc)?;
+//!     let prodinfo = gpt.find(emmc, "PRODINFO")?.unwrap();
+//!
+//!     backup::dump_partition(emmc, &prodinfo, sink, [&mut a[0], &mut b[0]], &mut |done, total| {
+//!         println!("{}/{} bytes", done, total);
+//!     })
+//! }
+//! ```
+//!
+//! [`Write`]: trait.Write.html
+//! [`Read`]: trait.Read.html
+//! [`dump_partition`]: fn.dump_partition.html
+//! [`BlockDevice`]: ../block/trait.BlockDevice.html
+//! [`dump_blocks`]: fn.dump_blocks.html
+//! [`restore_partition`]: fn.restore_partition.html
+//! [`restore_blocks`]: fn.restore_blocks.html
+//! [`verify_blocks`]: fn.verify_blocks.html
+
+use crate::{
+    block::{BlockDevice, BLOCK_SIZE},
+    ct,
+    gpt::Partition,
+    sched::yield_now,
+    se::{SecurityEngine, SHA256_HASH_SIZE},
+    Error, Result,
+};
+
+/// A sink for dumped data.
+pub trait Write {
+    /// Writes all of `data` to the sink.
+    fn write_all(&mut self, data: &[u8]) -> Result<()>;
+}
+
+/// A source for data to restore.
+pub trait Read {
+    /// Fills all of `buffer` from the source.
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<()>;
+}
+
+/// A callback that is given the amount of bytes transferred so far and
+/// the total amount of bytes.
+pub type Progress<'a> = &'a mut dyn FnMut(u64, u64);
+
+/// Checks that the transfer buffers can hold whole blocks.
+fn check_buffers(buffers: &[&mut [u8]; 2]) -> Result<()> {
+    if buffers[0].is_empty() || buffers[0].len() % BLOCK_SIZE != 0 {
+        return Err(Error::InvalidArgument);
+    }
+
+    Ok(())
+}
+
+/// Checks that a range of blocks lies within a device and gets its size
+/// in bytes.
+fn range_size<D: BlockDevice + ?Sized>(device: &D, first_lba: u64, count: u64) -> Result<u64> {
+    match first_lba.checked_add(count) {
+        Some(end) if end <= device.block_count() => Ok(count * BLOCK_SIZE as u64),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+/// Dumps `count` blocks starting at `first_lba` into `sink`.
+///
+/// Returns the SHA-256 hash of the dumped data.
+pub fn dump_blocks<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    first_lba: u64,
+    count: u64,
+    sink: &mut dyn Write,
+    buffers: [&mut [u8]; 2],
+    progress: Progress<'_>,
+) -> Result<[u8; SHA256_HASH_SIZE]> {
+    check_buffers(&buffers)?;
+    let size = range_size(device, first_lba, count)?;
+
+    let mut done = 0;
+    SecurityEngine::new().calculate_sha256_pipelined(
+        size,
+        buffers,
+        |offset, chunk| device.read_blocks(first_lba + offset / BLOCK_SIZE as u64, chunk),
+        |chunk| {
+            sink.write_all(chunk)?;
+
+            done += chunk.len() as u64;
+            progress(done, size);
+            yield_now();
+
+            Ok(())
+        },
+    )
+}
+
+/// Dumps a partition into `sink`.
+///
+/// Returns the SHA-256 hash of the dumped data.
+pub fn dump_partition<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    partition: &Partition,
+    sink: &mut dyn Write,
+    buffers: [&mut [u8]; 2],
+    progress: Progress<'_>,
+) -> Result<[u8; SHA256_HASH_SIZE]> {
+    dump_blocks(
+        device,
+        partition.first_lba,
+        partition.block_count(),
+        sink,
+        buffers,
+        progress,
+    )
+}
+
+/// Reads `count` blocks starting at `first_lba` back and checks them
+/// against the SHA-256 hash `expected`.
+///
+/// Fails with [`Error::InvalidData`] if the hashes don't match.
+///
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+pub fn verify_blocks<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    first_lba: u64,
+    count: u64,
+    expected: &[u8; SHA256_HASH_SIZE],
+    buffers: [&mut [u8]; 2],
+    progress: Progress<'_>,
+) -> Result<()> {
+    struct Discard;
+
+    impl Write for Discard {
+        fn write_all(&mut self, _: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let hash = dump_blocks(device, first_lba, count, &mut Discard, buffers, progress)?;
+    if !ct::eq(&hash, expected) {
+        return Err(Error::InvalidData);
+    }
+
+    Ok(())
+}
+
+/// Restores `count` blocks starting at `first_lba` from `source`.
+///
+/// The data is checked against the SHA-256 hash `expected` while it is
+/// being written and read back afterwards, which is reported as a second
+/// pass to `progress`. Fails with [`Error::InvalidData`] if either check
+/// fails, in which case the blocks hold unverified data.
+///
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+pub fn restore_blocks<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    first_lba: u64,
+    count: u64,
+    source: &mut dyn Read,
+    expected: &[u8; SHA256_HASH_SIZE],
+    buffers: [&mut [u8]; 2],
+    progress: Progress<'_>,
+) -> Result<()> {
+    check_buffers(&buffers)?;
+    let size = range_size(device, first_lba, count)?;
+
+    let [a, b] = buffers;
+    let mut lba = first_lba;
+    let mut done = 0;
+
+    let hash = SecurityEngine::new().calculate_sha256_pipelined(
+        size,
+        [&mut *a, &mut *b],
+        |_, chunk| source.read_exact(chunk),
+        |chunk| {
+            device.write_blocks(lba, chunk)?;
+            lba += (chunk.len() / BLOCK_SIZE) as u64;
+
+            done += chunk.len() as u64;
+            progress(done, size);
+            yield_now();
+
+            Ok(())
+        },
+    )?;
+
+    if !ct::eq(&hash, expected) {
+        return Err(Error::InvalidData);
+    }
+
+    verify_blocks(device, first_lba, count, expected, [a, b], progress)
+}
+
+/// Restores a partition from `source`.
+///
+/// See [`restore_blocks`] for how the data is verified.
+///
+/// [`restore_blocks`]: fn.restore_blocks.html
+pub fn restore_partition<D: BlockDevice + ?Sized>(
+    device: &mut D,
+    partition: &Partition,
+    source: &mut dyn Read,
+    expected: &[u8; SHA256_HASH_SIZE],
+    buffers: [&mut [u8]; 2],
+    progress: Progress<'_>,
+) -> Result<()> {
+    restore_blocks(
+        device,
+        partition.first_lba,
+        partition.block_count(),
+        source,
+        expected,
+        buffers,
+        progress,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_ranges() {
+        let mut disk = [0; 4 * BLOCK_SIZE];
+        let disk = &mut disk[..];
+
+        assert_eq!(range_size(disk, 1, 3).unwrap(), 3 * BLOCK_SIZE as u64);
+        assert!(range_size(disk, 2, 3).is_err());
+        assert!(range_size(disk, u64::max_value(), 2).is_err());
+
+        let mut a = [0; BLOCK_SIZE];
+        let mut b = [0; BLOCK_SIZE];
+        assert!(check_buffers(&[&mut a, &mut b]).is_ok());
+        assert!(check_buffers(&[&mut a[..100], &mut b[..100]]).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Abstraction of block storage devices.
+//!
+//! # Description
+//!
+//! SD cards, the eMMC and its partitions are all accessed in blocks of
+//! [`BLOCK_SIZE`] bytes. Code that only cares about the data, like partition
+//! table and filesystem parsers, is written against the [`BlockDevice`]
+//! trait, so it works with any of them.
+//!
+//! # Implementation
+//!
+//! - [`BlockDevice`] reads and writes whole blocks, addressed by their
+//! logical block address (LBA).
+//!
+//! - Slices of bytes implement [`BlockDevice`] as well, which is useful for
+//! disk images in memory and for tests.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::block::{BlockDevice, BLOCK_SIZE};
+//!
+//! fn read_mbr<D: BlockDevice>(device: &mut D) -> [u8; BLOCK_SIZE] {
+//!     let mut mbr = [0; BLOCK_SIZE];
+//!     device.read_blocks(0, &mut mbr).unwrap();
+//!
+//!     mbr
+//! }
+//! ```
+//!
+//! [`BLOCK_SIZE`]: constant.BLOCK_SIZE.html
+//! [`BlockDevice`]: trait.BlockDevice.html
+
+use crate::{Error, Result};
+
+/// The size of a block in bytes.
+pub const BLOCK_SIZE: usize = 0x200;
+
+/// A storage device that is accessed in blocks.
+pub trait BlockDevice {
+    /// Gets the amount of blocks on the device.
+    fn block_count(&self) -> u64;
+
+    /// Reads consecutive blocks, starting at `lba`, into `buffer`.
+    ///
+    /// The length of `buffer` must be a multiple of [`BLOCK_SIZE`].
+    ///
+    /// [`BLOCK_SIZE`]: constant.BLOCK_SIZE.html
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<()>;
+
+    /// Writes `buffer` to consecutive blocks, starting at `lba`.
+    ///
+    /// The length of `buffer` must be a multiple of [`BLOCK_SIZE`].
+    ///
+    /// [`BLOCK_SIZE`]: constant.BLOCK_SIZE.html
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<()>;
+}
+
+/// Gets the byte range of blocks in a device of the given size.
+fn byte_range(size: usize, lba: u64, len: usize) -> Result<core::ops::Range<usize>> {
+    if len % BLOCK_SIZE != 0 || lba > usize::max_value() as u64 {
+        return Err(Error::InvalidArgument);
+    }
+
+    let start = (lba as usize)
+        .checked_mul(BLOCK_SIZE)
+        .ok_or(Error::InvalidArgument)?;
+    let end = start.checked_add(len).ok_or(Error::InvalidArgument)?;
+
+    if end > size {
+        return Err(Error::InvalidArgument);
+    }
+
+    Ok(start..end)
+}
+
+impl BlockDevice for [u8] {
+    fn block_count(&self) -> u64 {
+        (self.len() / BLOCK_SIZE) as u64
+    }
+
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<()> {
+        let range = byte_range(self.len(), lba, buffer.len())?;
+        buffer.copy_from_slice(&self[range]);
+
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<()> {
+        let range = byte_range(self.len(), lba, buffer.len())?;
+        self[range].copy_from_slice(buffer);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,695 @@
+//! The board profiles of the Mariko based Nintendo Switch Lite and OLED model.
+//!
+//! The Switch Lite has its controllers built in, so the pads and GPIOs
+//! of the Joy-Con rails are left untouched. The OLED model keeps the
+//! Joy-Con rails and shares the tables of the original Nintendo Switch.
+
+use super::{erista, BoardProfile, GpioEntry, PadState, PinmuxEntry};
+use crate::{
+    gpio::{Gpio, GpioConfig},
+    pinmux::{Pad, INPUT, PULL_DOWN, PULL_UP},
+};
+
+/// The Pinmux table of the Nintendo Switch Lite.
+const PINMUX: [PinmuxEntry; 162] = [
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Clk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Cmd,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Dat3,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Dat2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Dat1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc1Dat0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Clk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Cmd,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Dat0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Dat1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Dat2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Sdmmc3Dat3,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PexL0RstN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PexL0ClkreqN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PexWakeN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PexL1RstN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PexL1ClkreqN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::SataLedActive,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi1Mosi,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi1Miso,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi1Sck,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi1Cs0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi1Cs1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi2Mosi,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi2Miso,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi2Sck,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi2Cs0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi2Cs1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi4Mosi,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi4Miso,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi4Sck,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Spi4Cs0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiSck,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiCsN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiIo0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiIo1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiIo2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::QspiIo3,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic1Clk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic1Dat,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic2Clk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic2Dat,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic3Clk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dmic3Dat,
+        state: PadState::Keep,
+    },
+    // I²C 1.
+    PinmuxEntry {
+        pad: Pad::Gen1I2cScl,
+        state: PadState::Set(INPUT),
+    },
+    PinmuxEntry {
+        pad: Pad::Gen1I2cSda,
+        state: PadState::Set(INPUT),
+    },
+    PinmuxEntry {
+        pad: Pad::Gen2I2cScl,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Gen2I2cSda,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Gen3I2cScl,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Gen3I2cSda,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CamI2cScl,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CamI2cSda,
+        state: PadState::Keep,
+    },
+    // I²C 5, PMIC and RTC.
+    PinmuxEntry {
+        pad: Pad::PwrI2cScl,
+        state: PadState::Set(INPUT),
+    },
+    PinmuxEntry {
+        pad: Pad::PwrI2cSda,
+        state: PadState::Set(INPUT),
+    },
+    // UART A, debug console.
+    PinmuxEntry {
+        pad: Pad::Uart1Tx,
+        state: PadState::Set(0),
+    },
+    PinmuxEntry {
+        pad: Pad::Uart1Rx,
+        state: PadState::Set(INPUT | PULL_UP),
+    },
+    PinmuxEntry {
+        pad: Pad::Uart1Rts,
+        state: PadState::Set(0),
+    },
+    PinmuxEntry {
+        pad: Pad::Uart1Cts,
+        state: PadState::Set(INPUT | PULL_DOWN),
+    },
+    // UART B/C TX, used as Joy-Con IsAttached sense.
+    PinmuxEntry {
+        pad: Pad::Uart2Tx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart2Rx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart2Rts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart2Cts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart3Tx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart3Rx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart3Rts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart3Cts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart4Tx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart4Rx,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart4Rts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Uart4Cts,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap1Fs,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap1Din,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap1Dout,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap1Sclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap2Fs,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap2Din,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap2Dout,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap2Sclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap4Fs,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap4Din,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap4Dout,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Dap4Sclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Cam1Mclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Cam2Mclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::JtagRtck,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Clk32kIn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Clk32kOut,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::BattBcl,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ClkReq,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CpuPwrReq,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::PwrIntN,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Shutdown,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CorePwrReq,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::AudMclk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::DvfsPwm,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::DvfsClk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::GpioX1Aud,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::GpioX3Aud,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pcc7,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::HdmiCec,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::HdmiIntDpHpd,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::SpdifOut,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::SpdifIn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::UsbVbusEn0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::UsbVbusEn1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::DpHpd0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::WifiEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::WifiRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::WifiWakeAp,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ApWakeBt,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::BtRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::BtWakeAp,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ApWakeNfc,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::NfcEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::NfcInt,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::GpsEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::GpsRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CamRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CamAfEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::CamFlashEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Cam1Pwdn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Cam2Pwdn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Cam1Strobe,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdTe,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdBlPwm,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdBlEn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdGpio1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::LcdGpio2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ApReady,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::TouchRst,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::TouchClk,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ModemWakeAp,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::TouchInt,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::MotionInt,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::AlsProxInt,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::TempAlert,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ButtonPowerOn,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ButtonVolUp,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ButtonVolDown,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ButtonSlideSw,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::ButtonHome,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pa6,
+        state: PadState::Keep,
+    },
+    // Joy-Con IsAttached.
+    PinmuxEntry {
+        pad: Pad::Pe6,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pe7,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Ph6,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk3,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk4,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk5,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk6,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pk7,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pl0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pl1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz0,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz1,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz2,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz3,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz4,
+        state: PadState::Keep,
+    },
+    PinmuxEntry {
+        pad: Pad::Pz5,
+        state: PadState::Keep,
+    },
+];
+
+/// The GPIO table of the Nintendo Switch Lite.
+const GPIOS: [GpioEntry; 2] = [
+    // Configure Volume Up/Down as inputs.
+    GpioEntry {
+        gpio: Gpio::BUTTON_VOL_UP,
+        config: GpioConfig::Input,
+    },
+    GpioEntry {
+        gpio: Gpio::BUTTON_VOL_DOWN,
+        config: GpioConfig::Input,
+    },
+];
+
+/// The board profile of the Nintendo Switch Lite.
+pub const MARIKO_HANDHELD: BoardProfile = BoardProfile {
+    name: "Mariko handheld",
+    pinmux: &PINMUX,
+    gpios: &GPIOS,
+};
+
+/// The board profile of the Nintendo Switch OLED model.
+pub const OLED: BoardProfile = BoardProfile {
+    name: "OLED",
+    pinmux: &erista::PINMUX,
+    gpios: &erista::GPIOS,
+};
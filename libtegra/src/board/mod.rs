@@ -0,0 +1,241 @@
+//! Board profiles describing the default pad and GPIO configuration.
+//!
+//! # Description
+//!
+//! Different boards built around the Tegra X1 route the SoC pads to
+//! different peripherals. During early hardware initialization, the
+//! pads have to be muxed and configured accordingly and some GPIOs
+//! need to be set up before the respective devices can be used.
+//!
+//! Instead of encoding these steps as code, every board describes its
+//! default configuration as const data in a [`BoardProfile`].
+//!
+//! # Implementation
+//!
+//! - A [`BoardProfile`] consists of a Pinmux table and a GPIO table.
+//! The Pinmux table has to list every [`Pad`] exactly once, either
+//! with a configuration to apply through [`PadState::Set`] or with
+//! [`PadState::Keep`] to leave the pad in its current state. This
+//! forces an explicit decision for every pad when adding new boards.
+//!
+//! - [`BoardProfile::validate`] checks this invariant and is verified
+//! for every profile by the crate's unit tests.
+//!
+//! - [`BoardProfile::apply`] writes the Pinmux table and configures
+//! the GPIOs afterwards, in table order.
+//!
+//! - [`ERISTA`] is the profile of the original Nintendo Switch,
+//! [`MARIKO_HANDHELD`] the one of the Switch Lite and [`OLED`] the one of
+//! the OLED model.
+//!
+//! - [`Board`] identifies the board of the running unit from the fuses
+//! through [`Board::detect`]. Besides selecting the profile, it tells
+//! apart the parts of the init sequence that differ between boards, like
+//! the panel backlight and the SD card power GPIO.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::board::Board;
+//!
+//! fn main() {
+//!     let board = Board::detect();
+//!
+//!     board.profile().apply();
+//! }
+//! ```
+//!
+//! [`BoardProfile`]: struct.BoardProfile.html
+//! [`BoardProfile::validate`]: struct.BoardProfile.html#method.validate
+//! [`BoardProfile::apply`]: struct.BoardProfile.html#method.apply
+//! [`Pad`]: ../pinmux/enum.Pad.html
+//! [`PadState::Set`]: enum.PadState.html#variant.Set
+//! [`PadState::Keep`]: enum.PadState.html#variant.Keep
+//! [`ERISTA`]: constant.ERISTA.html
+//! [`MARIKO_HANDHELD`]: constant.MARIKO_HANDHELD.html
+//! [`OLED`]: constant.OLED.html
+//! [`Board`]: enum.Board.html
+//! [`Board::detect`]: enum.Board.html#method.detect
+
+use mirage_mmio::VolatileStorage;
+
+use crate::{
+    gpio::{Gpio, GpioConfig},
+    hw_rev::{self, HardwareType},
+    pinmux::{Pad, Pinmux, PAD_COUNT},
+};
+
+pub use erista::ERISTA;
+pub use mariko::{MARIKO_HANDHELD, OLED};
+
+mod erista;
+mod mariko;
+
+/// The boards of the Switch models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Board {
+    /// The board of the original Switch, which is also used by its
+    /// Mariko revision.
+    Erista,
+    /// The board of the Switch Lite, with built-in controllers.
+    MarikoHandheld,
+    /// The board of the OLED model, whose panel has no LCD backlight.
+    Oled,
+}
+
+impl Board {
+    /// Gets the board of the given hardware type.
+    pub fn from_hardware_type(hardware_type: HardwareType) -> Self {
+        match hardware_type {
+            HardwareType::Icosa | HardwareType::Iowa => Board::Erista,
+            HardwareType::Hoag => Board::MarikoHandheld,
+            HardwareType::Aula => Board::Oled,
+        }
+    }
+
+    /// Determines the board of the unit.
+    ///
+    /// Requires the fuse registers to be visible.
+    pub fn detect() -> Self {
+        Self::from_hardware_type(hw_rev::hardware_type())
+    }
+
+    /// Gets the board profile with the Pinmux and GPIO tables.
+    pub fn profile(self) -> &'static BoardProfile {
+        match self {
+            Board::Erista => &ERISTA,
+            Board::MarikoHandheld => &MARIKO_HANDHELD,
+            Board::Oled => &OLED,
+        }
+    }
+
+    /// Whether the Joy-Con rails are present.
+    pub fn has_joycon_rails(self) -> bool {
+        self != Board::MarikoHandheld
+    }
+
+    /// Whether the panel is lit by an LCD backlight driven through
+    /// the backlight GPIOs.
+    ///
+    /// The OLED panel controls its brightness itself instead.
+    pub fn has_lcd_backlight(self) -> bool {
+        self != Board::Oled
+    }
+
+    /// Gets the GPIO which powers the SD card.
+    ///
+    /// All known boards use the same GPIO, drivers should still query it
+    /// from the board rather than hardcoding it.
+    pub fn sd_power_gpio(self) -> Gpio {
+        Gpio::MICROSD_SUPPLY_ENABLE
+    }
+}
+
+/// The desired state of a pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadState {
+    /// Leaves the pad untouched.
+    Keep,
+    /// Writes the given value to the configuration register of the pad.
+    Set(u32),
+}
+
+/// An entry of a Pinmux table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinmuxEntry {
+    /// The pad to configure.
+    pub pad: Pad,
+    /// The desired state of the pad.
+    pub state: PadState,
+}
+
+/// An entry of a GPIO table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GpioEntry {
+    /// The GPIO to configure.
+    pub gpio: Gpio,
+    /// The desired configuration of the GPIO.
+    pub config: GpioConfig,
+}
+
+/// Possible errors of a board profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    /// The pad isn't listed in the Pinmux table.
+    MissingPad(Pad),
+    /// The pad is listed more than once in the Pinmux table.
+    DuplicatePad(Pad),
+}
+
+/// Representation of the default hardware configuration of a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardProfile {
+    /// The name of the board.
+    pub name: &'static str,
+    /// The Pinmux table, covering every pad exactly once.
+    pub pinmux: &'static [PinmuxEntry],
+    /// The GPIO table.
+    pub gpios: &'static [GpioEntry],
+}
+
+impl BoardProfile {
+    /// Checks that the Pinmux table covers every pad exactly once.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        for pad in Pad::ALL.iter() {
+            match self.pinmux.iter().filter(|entry| entry.pad == *pad).count() {
+                0 => return Err(ProfileError::MissingPad(*pad)),
+                1 => {}
+                _ => return Err(ProfileError::DuplicatePad(*pad)),
+            }
+        }
+
+        debug_assert_eq!(self.pinmux.len(), PAD_COUNT);
+
+        Ok(())
+    }
+
+    /// Applies the Pinmux and GPIO tables of the profile.
+    pub fn apply(&self) {
+        let pinmux = unsafe { Pinmux::get() };
+
+        for entry in self.pinmux.iter() {
+            if let PadState::Set(value) = entry.state {
+                pinmux.pad(entry.pad).write(value);
+            }
+        }
+
+        for entry in self.gpios.iter() {
+            entry.gpio.config(entry.config);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies that the Erista Pinmux table covers every pad exactly once.
+    #[test]
+    fn erista_covers_every_pad_once() {
+        assert_eq!(ERISTA.validate(), Ok(()));
+    }
+
+    /// Verifies that the Mariko Pinmux tables cover every pad exactly once.
+    #[test]
+    fn mariko_covers_every_pad_once() {
+        assert_eq!(MARIKO_HANDHELD.validate(), Ok(()));
+        assert_eq!(OLED.validate(), Ok(()));
+    }
+
+    /// Verifies the board selection from the hardware type.
+    #[test]
+    fn board_from_hardware_type() {
+        assert_eq!(Board::from_hardware_type(HardwareType::Icosa), Board::Erista);
+        assert_eq!(Board::from_hardware_type(HardwareType::Iowa), Board::Erista);
+        assert_eq!(Board::from_hardware_type(HardwareType::Hoag), Board::MarikoHandheld);
+        assert_eq!(Board::from_hardware_type(HardwareType::Aula), Board::Oled);
+
+        assert!(!Board::MarikoHandheld.has_joycon_rails());
+        assert!(!Board::Oled.has_lcd_backlight());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{Error, Result};
+
+/// The size of the Tegra X1 boot configuration table in bytes.
+pub const BCT_SIZE: usize = 0x2800;
+
+/// The distance between the copies of the BCT in `BOOT0` in bytes.
+///
+/// Every copy occupies an erase block of its own.
+pub const BCT_COPY_SPACING: usize = 0x4000;
+
+/// The offset of the RSA public key modulus.
+const KEY_OFFSET: usize = 0x210;
+/// The offset of the AES-CMAC hash of the signed section.
+const HASH_OFFSET: usize = 0x310;
+/// The offset of the RSA-PSS signature of the signed section.
+const SIGNATURE_OFFSET: usize = 0x320;
+/// The size of the RSA key modulus and the signature.
+const RSA_SIZE: usize = 0x100;
+
+/// A boot configuration table.
+#[derive(Clone, Copy, Debug)]
+pub struct Bct<'a> {
+    /// The raw table.
+    data: &'a [u8],
+}
+
+impl<'a> Bct<'a> {
+    /// Wraps a copy of the BCT.
+    ///
+    /// Fails with [`Error::InvalidData`] if the data is shorter than
+    /// [`BCT_SIZE`].
+    ///
+    /// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+    /// [`BCT_SIZE`]: constant.BCT_SIZE.html
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        if data.len() < BCT_SIZE {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Bct {
+            data: &data[..BCT_SIZE],
+        })
+    }
+
+    /// Gets the raw table.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Gets the modulus of the RSA public key the bootloader is signed with.
+    ///
+    /// Retail and development units use different keys, so this tells them
+    /// apart.
+    pub fn public_key(&self) -> &'a [u8] {
+        &self.data[KEY_OFFSET..KEY_OFFSET + RSA_SIZE]
+    }
+
+    /// Gets the AES-CMAC hash of the signed section.
+    pub fn hash(&self) -> &'a [u8] {
+        &self.data[HASH_OFFSET..SIGNATURE_OFFSET]
+    }
+
+    /// Gets the RSA-PSS signature of the signed section.
+    pub fn signature(&self) -> &'a [u8] {
+        &self.data[SIGNATURE_OFFSET..SIGNATURE_OFFSET + RSA_SIZE]
+    }
+}
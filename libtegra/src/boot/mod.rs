@@ -0,0 +1,111 @@
+//! Structures of the eMMC boot partitions.
+//!
+//! # Description
+//!
+//! The Boot ROM loads the first stage bootloader of the Switch OS from the
+//! eMMC `BOOT0` partition. It starts with copies of the boot configuration
+//! table (BCT), which describes where the bootloader is stored and how it is
+//! signed, followed by package1, which contains the bootloader along with
+//! the TSEC firmware, the Secure Monitor and the warmboot firmware.
+//!
+//! The package1 build timestamp identifies the installed firmware, which in
+//! turn determines the key generation and the TSEC firmware revision a
+//! bootloader has to use.
+//!
+//! # Implementation
+//!
+//! - [`Bct`] wraps a copy of the BCT and provides access to the key and the
+//! signature of the table.
+//!
+//! - [`Package1`] parses package1 and detects its [`Version`] from the
+//! known build timestamps. It locates the TSEC firmware and, for plaintext
+//! packages before 6.2.0, the [`Pk11`] container and its sections.
+//!
+//! - [`read_bct`] and [`read_package1`] read the structures from a
+//! [`BlockDevice`] for the `BOOT0` partition.
+//!
+//! - All parsers are read-only and borrow from the caller's buffer.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{block::BlockDevice, boot, Result};
+//!
+//! fn key_generation<D: BlockDevice>(boot0: &mut D) -> Result<u8> {
+//!     let mut buffer = [0; boot::PACKAGE1_SIZE];
+//!     let package1 = boot::read_package1(boot0, &mut buffer)?;
+//!
+//!     Ok(package1.version().key_generation)
+//! }
+//! ```
+//!
+//! [`Bct`]: struct.Bct.html
+//! [`Package1`]: struct.Package1.html
+//! [`Version`]: struct.Version.html
+//! [`Pk11`]: struct.Pk11.html
+//! [`read_bct`]: fn.read_bct.html
+//! [`read_package1`]: fn.read_package1.html
+//! [`BlockDevice`]: ../block/trait.BlockDevice.html
+
+pub use self::bct::{Bct, BCT_COPY_SPACING, BCT_SIZE};
+pub use self::package1::{Package1, Pk11, Section, Version, VERSIONS};
+
+use crate::{
+    block::{BlockDevice, BLOCK_SIZE},
+    Error, Result,
+};
+
+mod bct;
+mod package1;
+
+/// The offset of package1 in the `BOOT0` partition.
+pub const PACKAGE1_OFFSET: usize = 0x10_0000;
+
+/// The space reserved for package1 in the `BOOT0` partition.
+pub const PACKAGE1_SIZE: usize = 0x4_0000;
+
+/// Reads a copy of the BCT from the `BOOT0` partition.
+///
+/// `buffer` has to hold at least [`BCT_SIZE`] bytes, rounded up to whole
+/// blocks. Fails with [`Error::InvalidArgument`] if it is too small.
+///
+/// [`BCT_SIZE`]: constant.BCT_SIZE.html
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+pub fn read_bct<'b, D: BlockDevice + ?Sized>(
+    boot0: &mut D,
+    copy: usize,
+    buffer: &'b mut [u8],
+) -> Result<Bct<'b>> {
+    let len = (BCT_SIZE + BLOCK_SIZE - 1) & !(BLOCK_SIZE - 1);
+    if buffer.len() < len {
+        return Err(Error::InvalidArgument);
+    }
+
+    let lba = (copy * BCT_COPY_SPACING / BLOCK_SIZE) as u64;
+    boot0.read_blocks(lba, &mut buffer[..len])?;
+
+    Bct::new(buffer)
+}
+
+/// Reads package1 from the `BOOT0` partition.
+///
+/// `buffer` has to hold [`PACKAGE1_SIZE`] bytes. Fails with
+/// [`Error::InvalidArgument`] if it is too small and with
+/// [`Error::InvalidData`] if the package is unknown.
+///
+/// [`PACKAGE1_SIZE`]: constant.PACKAGE1_SIZE.html
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+pub fn read_package1<'b, D: BlockDevice + ?Sized>(
+    boot0: &mut D,
+    buffer: &'b mut [u8],
+) -> Result<Package1<'b>> {
+    if buffer.len() < PACKAGE1_SIZE {
+        return Err(Error::InvalidArgument);
+    }
+
+    let lba = (PACKAGE1_OFFSET / BLOCK_SIZE) as u64;
+    boot0.read_blocks(lba, &mut buffer[..PACKAGE1_SIZE])?;
+
+    Package1::new(buffer)
+}
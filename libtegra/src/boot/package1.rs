@@ -0,0 +1,265 @@
+use core::convert::TryInto;
+
+use crate::{Error, Result};
+
+/// The offset of the build timestamp in the package1 header.
+const TIMESTAMP_OFFSET: usize = 0x10;
+/// The length of the build timestamp.
+const TIMESTAMP_LENGTH: usize = 14;
+
+/// The magic of the PK11 container.
+const PK11_MAGIC: &[u8; 4] = b"PK11";
+/// The size of the PK11 header.
+const PK11_HEADER_SIZE: usize = 0x20;
+
+/// A section of the PK11 container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// The warmboot firmware, run by the BPMP on wake from sleep.
+    Warmboot,
+    /// The package1 loader, which loads package2.
+    Loader,
+    /// The Secure Monitor.
+    SecureMonitor,
+}
+
+/// A known package1 revision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    /// The build timestamp identifying the revision.
+    pub timestamp: &'static str,
+    /// The firmware versions shipping the revision.
+    pub name: &'static str,
+    /// The master key generation of the firmware.
+    pub key_generation: u8,
+    /// The offset of the TSEC firmware in package1.
+    pub tsec_offset: usize,
+    /// The offset of the PK11 container in package1.
+    pub pk11_offset: usize,
+    /// The order of the sections in the PK11 container.
+    section_order: [Section; 3],
+}
+
+impl Version {
+    /// Gets the size of the TSEC firmware in package1.
+    pub fn tsec_size(&self) -> usize {
+        match self.key_generation {
+            0..=5 => 0xF00,
+            6 => 0x2900,
+            _ => 0x3000,
+        }
+    }
+
+    /// Whether the PK11 container is encrypted.
+    ///
+    /// Starting with 6.2.0, it is decrypted by the TSEC firmware.
+    pub fn is_encrypted(&self) -> bool {
+        self.key_generation >= 6
+    }
+
+    /// Gets the build date in the form `YYYY-MM-DD`.
+    pub fn date(&self) -> [u8; 10] {
+        let t = self.timestamp.as_bytes();
+
+        [t[0], t[1], t[2], t[3], b'-', t[4], t[5], b'-', t[6], t[7]]
+    }
+}
+
+/// Creates a [`Version`] entry.
+///
+/// [`Version`]: struct.Version.html
+macro_rules! version {
+    ($timestamp:expr, $name:expr, $kg:expr, $tsec:expr, $pk11:expr, $order:expr) => {
+        Version {
+            timestamp: $timestamp,
+            name: $name,
+            key_generation: $kg,
+            tsec_offset: $tsec,
+            pk11_offset: $pk11,
+            section_order: $order,
+        }
+    };
+}
+
+/// The section order of 1.0.0.
+const ORDER_100: [Section; 3] = [Section::SecureMonitor, Section::Loader, Section::Warmboot];
+/// The section order of 2.0.0 and newer.
+const ORDER_200: [Section; 3] = [Section::Warmboot, Section::Loader, Section::SecureMonitor];
+
+/// The known package1 revisions.
+#[rustfmt::skip]
+pub static VERSIONS: [Version; 14] = [
+    version!("20161121183008", "1.0.0", 0, 0x1900, 0x3FE0, ORDER_100),
+    version!("20170210155124", "2.0.0-2.3.0", 0, 0x1900, 0x3FE0, ORDER_200),
+    version!("20170519101410", "3.0.0", 1, 0x1A00, 0x3FE0, ORDER_200),
+    version!("20170710161758", "3.0.1-3.0.2", 2, 0x1A00, 0x3FE0, ORDER_200),
+    version!("20170921172629", "4.0.0-4.1.0", 3, 0x1800, 0x3FE0, ORDER_200),
+    version!("20180220163747", "5.0.0-5.1.0", 4, 0x1900, 0x3FE0, ORDER_200),
+    version!("20180802162753", "6.0.0-6.1.0", 5, 0x1900, 0x3FE0, ORDER_200),
+    version!("20181107105733", "6.2.0", 6, 0x0E00, 0x6FE0, ORDER_200),
+    version!("20181218175730", "7.0.0", 7, 0x0F00, 0x6FE0, ORDER_200),
+    version!("20190208150037", "7.0.1", 7, 0x0F00, 0x6FE0, ORDER_200),
+    version!("20190314172056", "8.0.0-8.0.1", 7, 0x0F00, 0x6FE0, ORDER_200),
+    version!("20190531152432", "8.1.0", 8, 0x0F00, 0x6FE0, ORDER_200),
+    version!("20190809135709", "9.0.0-9.0.1", 9, 0x0F00, 0x6FE0, ORDER_200),
+    version!("20191021113848", "9.1.0", 10, 0x0F00, 0x6FE0, ORDER_200),
+];
+
+/// The first stage bootloader package of the Switch OS.
+#[derive(Clone, Copy, Debug)]
+pub struct Package1<'a> {
+    /// The raw package.
+    data: &'a [u8],
+    /// The detected revision.
+    version: &'static Version,
+}
+
+impl<'a> Package1<'a> {
+    /// Parses package1 and detects its revision.
+    ///
+    /// Fails with [`Error::InvalidData`] if the revision is unknown or the
+    /// data is too short to hold its TSEC firmware and PK11 container.
+    ///
+    /// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let timestamp = data
+            .get(TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + TIMESTAMP_LENGTH)
+            .ok_or(Error::InvalidData)?;
+        let version = VERSIONS
+            .iter()
+            .find(|version| version.timestamp.as_bytes() == timestamp)
+            .ok_or(Error::InvalidData)?;
+
+        if data.len() < version.tsec_offset + version.tsec_size()
+            || data.len() < version.pk11_offset + PK11_HEADER_SIZE
+        {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Package1 { data, version })
+    }
+
+    /// Gets the detected revision.
+    pub fn version(&self) -> &'static Version {
+        self.version
+    }
+
+    /// Gets the build timestamp of the package.
+    pub fn timestamp(&self) -> &'static str {
+        self.version.timestamp
+    }
+
+    /// Gets the TSEC firmware embedded in the package.
+    pub fn tsec_firmware(&self) -> &'a [u8] {
+        &self.data[self.version.tsec_offset..][..self.version.tsec_size()]
+    }
+
+    /// Gets the PK11 container.
+    ///
+    /// Returns `None` if the container is encrypted or malformed.
+    pub fn pk11(&self) -> Option<Pk11<'a>> {
+        if self.version.is_encrypted() {
+            return None;
+        }
+
+        Pk11::new(&self.data[self.version.pk11_offset..], self.version)
+    }
+}
+
+/// The container of the package1 sections.
+#[derive(Clone, Copy, Debug)]
+pub struct Pk11<'a> {
+    /// The container, starting at its header.
+    data: &'a [u8],
+    /// The revision of the surrounding package.
+    version: &'static Version,
+}
+
+impl<'a> Pk11<'a> {
+    /// Parses a plaintext PK11 container.
+    fn new(data: &'a [u8], version: &'static Version) -> Option<Self> {
+        if !data.starts_with(PK11_MAGIC) {
+            return None;
+        }
+
+        let pk11 = Pk11 { data, version };
+        let total = version
+            .section_order
+            .iter()
+            .try_fold(PK11_HEADER_SIZE, |total, &section| {
+                total.checked_add(pk11.section_size(section))
+            })?;
+
+        if total > data.len() {
+            return None;
+        }
+
+        Some(pk11)
+    }
+
+    /// Gets the size of a section from the header.
+    pub fn section_size(&self, section: Section) -> usize {
+        let offset = match section {
+            Section::Warmboot => 0x4,
+            Section::Loader => 0x10,
+            Section::SecureMonitor => 0x18,
+        };
+
+        u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    /// Gets the data of a section.
+    pub fn section(&self, section: Section) -> &'a [u8] {
+        let mut offset = PK11_HEADER_SIZE;
+
+        for &current in self.version.section_order.iter() {
+            let size = self.section_size(current);
+            if current == section {
+                return &self.data[offset..offset + size];
+            }
+
+            offset += size;
+        }
+
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests detecting the revision and locating the sections.
+    #[test]
+    fn parse() {
+        let mut data = [0u8; 0x4100];
+        assert_eq!(Package1::new(&data).err(), Some(Error::InvalidData));
+
+        data[TIMESTAMP_OFFSET..TIMESTAMP_OFFSET + TIMESTAMP_LENGTH]
+            .copy_from_slice(b"20170519101410");
+        data[0x1A00] = 0xAA;
+
+        let pk11 = &mut data[0x3FE0..];
+        pk11[..4].copy_from_slice(PK11_MAGIC);
+        pk11[0x4] = 0x10;
+        pk11[0x10] = 0x20;
+        pk11[0x18] = 0x30;
+        pk11[PK11_HEADER_SIZE + 0x10] = 0xBB;
+
+        let package1 = Package1::new(&data).unwrap();
+        assert_eq!(package1.version().name, "3.0.0");
+        assert_eq!(package1.version().key_generation, 1);
+        assert_eq!(&package1.version().date(), b"2017-05-19");
+        assert_eq!(package1.tsec_firmware().len(), 0xF00);
+        assert_eq!(package1.tsec_firmware()[0], 0xAA);
+
+        let pk11 = package1.pk11().unwrap();
+        assert_eq!(pk11.section(Section::Warmboot).len(), 0x10);
+        assert_eq!(pk11.section(Section::Loader)[0], 0xBB);
+        assert_eq!(pk11.section(Section::SecureMonitor).len(), 0x30);
+
+        // The sections don't fit anymore.
+        data[0x3FE0 + 0x18] = 0xFF;
+        assert!(Package1::new(&data).unwrap().pk11().is_none());
+    }
+}
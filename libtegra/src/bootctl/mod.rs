@@ -0,0 +1,352 @@
+//! A/B boot slot selection.
+//!
+//! # Description
+//!
+//! Updating the stage-2 payload in place leaves the console without a
+//! working bootloader if the new image is broken. With two slots, an update
+//! is written to the slot that is not in use and only made active once it
+//! is complete. If the new image then fails to boot a few times in a row,
+//! the previous one is booted again.
+//!
+//! The boot attempts are tracked in [`Scratch::BOOT_CONTROL`], which keeps
+//! its value across resets, but not across a power cycle. On a cold boot,
+//! the active slot is thus taken from a default the caller provides, e.g.
+//! from a configuration file next to the images.
+//!
+//! # Implementation
+//!
+//! - [`BootControl`] is the state of both slots. [`BootControl::begin_boot`]
+//! counts a boot attempt of the active slot and falls back to the other
+//! slot once the active one is out of attempts and hasn't been marked as
+//! successful.
+//!
+//! - [`select_slot`] loads the state, counts the attempt and stores it again
+//! in one go. It is meant to be called once per boot, before the payload
+//! is loaded.
+//!
+//! - [`mark_successful`] should be called by the payload once it booted far
+//! enough to be able to install another update. Slots that were marked as
+//! successful don't count their attempts anymore.
+//!
+//! - [`BootControl::set_active`] makes a freshly written image active, with
+//! a clean attempt counter.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::bootctl::{self, Slot};
+//!
+//! fn main() {
+//!     let slot = bootctl::select_slot(Slot::A, 3).expect("No bootable slot left");
+//!
+//!     // Load and verify the payload of the slot, then boot it.
+//! }
+//! ```
+//!
+//! [`Scratch::BOOT_CONTROL`]: ../pmc/struct.Scratch.html#associatedconstant.BOOT_CONTROL
+//! [`BootControl`]: struct.BootControl.html
+//! [`BootControl::begin_boot`]: struct.BootControl.html#method.begin_boot
+//! [`BootControl::set_active`]: struct.BootControl.html#method.set_active
+//! [`select_slot`]: fn.select_slot.html
+//! [`mark_successful`]: fn.mark_successful.html
+
+use mirage_mmio::register_bitfields;
+
+use crate::{pmc::Scratch, Error, Result};
+
+register_bitfields! {
+    u32,
+    /// The layout of the [`Scratch::BOOT_CONTROL`] register.
+    ///
+    /// [`Scratch::BOOT_CONTROL`]: ../../pmc/struct.Scratch.html#associatedconstant.BOOT_CONTROL
+    BOOT_CONTROL [
+        /// The active slot.
+        ACTIVE OFFSET(0) NUMBITS(1),
+        /// The boot attempts of the active slot.
+        ATTEMPTS OFFSET(4) NUMBITS(4),
+        /// The slots that booted successfully, one bit per slot.
+        SUCCESSFUL OFFSET(8) NUMBITS(2),
+        /// The slots that ran out of attempts, one bit per slot.
+        UNBOOTABLE OFFSET(12) NUMBITS(2),
+        /// Identifies a valid state.
+        MAGIC OFFSET(16) NUMBITS(16),
+    ],
+}
+
+/// Identifies a valid state in the scratch register ("AB").
+const BOOT_CONTROL_MAGIC: u32 = 0x4142;
+
+/// The maximum amount of boot attempts that can be tracked.
+pub const MAX_ATTEMPTS: u32 = 15;
+
+/// The payload slots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    /// The first slot.
+    A = 0,
+    /// The second slot.
+    B = 1,
+}
+
+impl Slot {
+    /// Gets the slot with the given index.
+    fn from_index(index: u32) -> Self {
+        if index == 0 {
+            Slot::A
+        } else {
+            Slot::B
+        }
+    }
+
+    /// Gets the respective other slot.
+    pub fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+
+    /// Gets the bit of the slot in the per-slot masks.
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// The boot state of both slots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BootControl {
+    /// The slot that is booted.
+    active: Slot,
+    /// The boot attempts of the active slot.
+    attempts: u32,
+    /// The slots that booted successfully.
+    successful: u32,
+    /// The slots that ran out of attempts.
+    unbootable: u32,
+}
+
+impl BootControl {
+    /// Creates the state of a cold boot, with `active` as the active slot.
+    pub const fn new(active: Slot) -> Self {
+        BootControl {
+            active,
+            attempts: 0,
+            successful: 0,
+            unbootable: 0,
+        }
+    }
+
+    /// Loads the state from the scratch register.
+    ///
+    /// Returns `None` if there is no valid state, e.g. after a cold boot.
+    pub fn load() -> Option<Self> {
+        Self::decode(Scratch::BOOT_CONTROL.read())
+    }
+
+    /// Stores the state in the scratch register.
+    pub fn store(&self) {
+        Scratch::BOOT_CONTROL.write(self.encode());
+    }
+
+    /// Decodes the state from a scratch register value.
+    fn decode(value: u32) -> Option<Self> {
+        if BOOT_CONTROL::MAGIC.get(value) != BOOT_CONTROL_MAGIC {
+            return None;
+        }
+
+        Some(BootControl {
+            active: Slot::from_index(BOOT_CONTROL::ACTIVE.get(value)),
+            attempts: BOOT_CONTROL::ATTEMPTS.get(value),
+            successful: BOOT_CONTROL::SUCCESSFUL.get(value),
+            unbootable: BOOT_CONTROL::UNBOOTABLE.get(value),
+        })
+    }
+
+    /// Encodes the state into a scratch register value.
+    fn encode(&self) -> u32 {
+        BOOT_CONTROL::ACTIVE.val(self.active as u32)
+            | BOOT_CONTROL::ATTEMPTS.val(self.attempts)
+            | BOOT_CONTROL::SUCCESSFUL.val(self.successful)
+            | BOOT_CONTROL::UNBOOTABLE.val(self.unbootable)
+            | BOOT_CONTROL::MAGIC.val(BOOT_CONTROL_MAGIC)
+    }
+
+    /// Gets the active slot.
+    pub fn active(&self) -> Slot {
+        self.active
+    }
+
+    /// Gets the boot attempts of the active slot.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Whether a slot has been marked as successful.
+    pub fn is_successful(&self, slot: Slot) -> bool {
+        self.successful & slot.bit() != 0
+    }
+
+    /// Whether a slot can still be booted.
+    pub fn is_bootable(&self, slot: Slot) -> bool {
+        self.unbootable & slot.bit() == 0
+    }
+
+    /// Counts a boot attempt and returns the slot to boot.
+    ///
+    /// A slot that hasn't been marked as successful may be booted up to
+    /// `max_attempts` times, capped at [`MAX_ATTEMPTS`]. After that, it is
+    /// marked as unbootable and the other slot becomes active. Fails with
+    /// [`Error::Exhausted`] if neither slot can be booted anymore.
+    ///
+    /// [`MAX_ATTEMPTS`]: constant.MAX_ATTEMPTS.html
+    /// [`Error::Exhausted`]: ../enum.Error.html#variant.Exhausted
+    pub fn begin_boot(&mut self, max_attempts: u32) -> Result<Slot> {
+        let max_attempts = max_attempts.min(MAX_ATTEMPTS);
+
+        if !self.is_bootable(self.active) {
+            self.fall_back()?;
+        }
+
+        if self.is_successful(self.active) {
+            return Ok(self.active);
+        }
+
+        if self.attempts >= max_attempts {
+            self.mark_failed()?;
+        }
+
+        self.attempts += 1;
+
+        Ok(self.active)
+    }
+
+    /// Marks the active slot as successful, so its attempts don't count
+    /// anymore.
+    pub fn mark_successful(&mut self) {
+        self.successful |= self.active.bit();
+        self.unbootable &= !self.active.bit();
+        self.attempts = 0;
+    }
+
+    /// Marks the active slot as unbootable and makes the other slot active.
+    ///
+    /// Fails with [`Error::Exhausted`] if the other slot can't be booted
+    /// either.
+    ///
+    /// [`Error::Exhausted`]: ../enum.Error.html#variant.Exhausted
+    pub fn mark_failed(&mut self) -> Result<()> {
+        self.unbootable |= self.active.bit();
+        self.successful &= !self.active.bit();
+
+        self.fall_back()
+    }
+
+    /// Makes a slot active after a new image was written to it.
+    pub fn set_active(&mut self, slot: Slot) {
+        self.active = slot;
+        self.attempts = 0;
+        self.successful &= !slot.bit();
+        self.unbootable &= !slot.bit();
+    }
+
+    /// Makes the other slot active, if it can be booted.
+    fn fall_back(&mut self) -> Result<()> {
+        let other = self.active.other();
+
+        if !self.is_bootable(other) {
+            return Err(Error::Exhausted);
+        }
+
+        self.active = other;
+        self.attempts = 0;
+
+        Ok(())
+    }
+}
+
+/// Counts a boot attempt in the scratch register and returns the slot to
+/// boot.
+///
+/// Without a valid state, e.g. on a cold boot, `default` becomes the
+/// active slot. See [`BootControl::begin_boot`] for the selection.
+///
+/// [`BootControl::begin_boot`]: struct.BootControl.html#method.begin_boot
+pub fn select_slot(default: Slot, max_attempts: u32) -> Result<Slot> {
+    let mut state = BootControl::load().unwrap_or_else(|| BootControl::new(default));
+
+    let slot = state.begin_boot(max_attempts);
+    state.store();
+
+    slot
+}
+
+/// Marks the active slot in the scratch register as successful.
+///
+/// Does nothing if there is no valid state.
+pub fn mark_successful() {
+    if let Some(mut state) = BootControl::load() {
+        state.mark_successful();
+        state.store();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the state survives the scratch register encoding.
+    #[test]
+    fn encode_decode() {
+        let mut state = BootControl::new(Slot::B);
+        state.begin_boot(3).unwrap();
+        state.successful = Slot::A.bit();
+
+        assert_eq!(BootControl::decode(state.encode()), Some(state));
+        assert_eq!(BootControl::decode(0), None);
+    }
+
+    /// Tests falling back to the other slot after too many attempts.
+    #[test]
+    fn fall_back() {
+        let mut state = BootControl::new(Slot::B);
+
+        for _ in 0..3 {
+            assert_eq!(state.begin_boot(3), Ok(Slot::B));
+        }
+        assert_eq!(state.begin_boot(3), Ok(Slot::A));
+        assert!(!state.is_bootable(Slot::B));
+
+        state.mark_successful();
+        for _ in 0..5 {
+            assert_eq!(state.begin_boot(3), Ok(Slot::A));
+        }
+
+        state.set_active(Slot::B);
+        assert_eq!(state.begin_boot(3), Ok(Slot::B));
+    }
+
+    /// Tests that booting fails once both slots are out of attempts.
+    #[test]
+    fn exhausted() {
+        let mut state = BootControl::new(Slot::A);
+
+        for _ in 0..2 {
+            assert_eq!(state.begin_boot(2), Ok(Slot::A));
+        }
+        for _ in 0..2 {
+            assert_eq!(state.begin_boot(2), Ok(Slot::B));
+        }
+        assert_eq!(state.begin_boot(2), Err(Error::Exhausted));
+    }
+
+    /// Tests that a cold boot starts with the default slot.
+    #[cfg(feature = "mock")]
+    #[test]
+    fn select_slot_cold_boot() {
+        let _session = mirage_mmio::mock::start();
+
+        assert_eq!(select_slot(Slot::B, 1), Ok(Slot::B));
+        assert_eq!(select_slot(Slot::A, 1), Ok(Slot::A));
+        assert_eq!(BootControl::load().map(|state| state.active()), Some(Slot::A));
+    }
+}
@@ -13,17 +13,26 @@
 //! - [`wait`] waits until a button was pressed and returns the bitmask.
 //!
 //! - [`wait_for`] waits for a given duration to read the provided
-//! bitmask and returns a [`Result`] with the bitmask or `()` in
-//! case the function has timed out.
+//! bitmask and returns a [`Result`] with the bitmask or [`Error::Timeout`]
+//! in case the function has timed out.
+//!
+//! - UI code should poll buttons through the [`Input`] trait, which
+//! [`Buttons`] implements for the physical buttons. This allows feeding
+//! scripted input to the UI on a development host.
+//!
+//! - The power button is read through the PMIC, so all of these take the
+//! I²C 5 controller it is attached to.
 //!
 //! # Example
 //!
 //! ```
-//! use mirage_libtegra::button::*;
+//! use mirage_libtegra::{button::*, peripherals::Peripherals};
 //!
 //! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
 //!     // Wait for 10 seconds to get the key combination for entering RCM.
-//!     let button = wait_for(10, Button::POWER | Button::VOL_UP)
+//!     let button = wait_for(&peripherals.i2c_5, 10, Button::POWER | Button::VOL_UP)
 //!         .unwrap_or_else(|_| panic!("Key combination for entering RCM wasn't pressed in time!"));
 //! }
 //! ```
@@ -32,12 +41,17 @@
 //! [`read`]: fn.read.html
 //! [`wait`]: fn.wait.html
 //! [`wait_for`]: fn.wait_for.html
-//! [`Result`]: https://doc.rust-lang.org/nightly/core/result/enum.Result.html
+//! [`Input`]: trait.Input.html
+//! [`Buttons`]: struct.Buttons.html
+//! [`Result`]: ../type.Result.html
+//! [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
 
 use crate::{
     gpio::{Gpio, GpioLevel},
     i2c::*,
+    sched::yield_now,
     timer::get_seconds,
+    Error, Result,
 };
 
 bitflags! {
@@ -52,8 +66,24 @@ bitflags! {
     }
 }
 
+/// A source of button input.
+pub trait Input {
+    /// Reads the currently pressed buttons.
+    fn read(&mut self) -> Button;
+}
+
+/// The physical buttons of the console, with the PMIC on the given I²C
+/// controller.
+pub struct Buttons<'a>(pub &'a I2c);
+
+impl<'a> Input for Buttons<'a> {
+    fn read(&mut self) -> Button {
+        read(self.0)
+    }
+}
+
 /// Reads a physical button input.
-pub fn read() -> Button {
+pub fn read(i2c: &I2c) -> Button {
     let mut result = Button::empty();
 
     if Gpio::BUTTON_VOL_DOWN.read() == GpioLevel::Low {
@@ -64,7 +94,7 @@ pub fn read() -> Button {
         result |= Button::VOL_UP;
     }
 
-    if I2c::C5.read_byte(Device::Max77620Pwr, 0x15).unwrap() & 0x4 != 0 {
+    if i2c.read_byte(Device::Max77620Pwr, 0x15).unwrap() & 0x4 != 0 {
         result |= Button::POWER;
     }
 
@@ -72,10 +102,10 @@ pub fn read() -> Button {
 }
 
 /// Waits for a physical button input.
-pub fn wait() -> Button {
+pub fn wait(i2c: &I2c) -> Button {
     let mut result = Button::empty();
     let mut pwr = false;
-    let mut btn = read();
+    let mut btn = read(i2c);
 
     if btn.contains(Button::POWER) {
         pwr = true;
@@ -83,7 +113,7 @@ pub fn wait() -> Button {
     }
 
     loop {
-        result = read();
+        result = read(i2c);
 
         if !result.contains(Button::POWER) && pwr {
             pwr = false;
@@ -94,23 +124,27 @@ pub fn wait() -> Button {
         if btn != result {
             break;
         }
+
+        yield_now();
     }
 
     result
 }
 
 /// Waits for physical button input equal to the bitmask for a given time.
-pub fn wait_for(seconds: u32, mask: Button) -> Result<Button, ()> {
+pub fn wait_for(i2c: &I2c, seconds: u32, mask: Button) -> Result<Button> {
     let timeout = get_seconds() + seconds;
 
     let mut result;
     while get_seconds() < timeout {
-        result = read() & mask;
+        result = read(i2c) & mask;
 
         if result.contains(mask) {
             return Ok(result);
         }
+
+        yield_now();
     }
 
-    Err(())
+    Err(Error::Timeout)
 }
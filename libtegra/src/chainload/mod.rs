@@ -0,0 +1,260 @@
+//! Preparation of the hardware for chainloaded payloads.
+//!
+//! # Description
+//!
+//! Payloads launched from RCM expect to find the SoC roughly in the state
+//! the bootrom leaves it in. Some of the changes Mirage makes during init
+//! break that assumption: the memory built-in self test workaround gates
+//! the audio clocks, which hangs payloads that touch the APE, and the AHB
+//! redirect to IRAM is not necessarily active anymore.
+//!
+//! Before jumping to an arbitrary third-party payload, these have to be
+//! reverted, and nothing of Mirage may interrupt the payload or still sit
+//! in a cache.
+//!
+//! Besides the audio clocks, the workaround rewrites all clock enables,
+//! the second level clock gate overrides, PLLD and a few clock sources.
+//! Their prior values depend on the boot path, so a [`clock::snapshot`]
+//! has to be taken before the workaround runs.
+//!
+//! # Implementation
+//!
+//! - [`cleanup`] masks all interrupts on the BPMP and in the interrupt
+//! controllers, restores the clock configuration from before the memory
+//! built-in self test workaround, restores the AHB redirect and finally
+//! cleans and disables the BPMP cache.
+//!
+//! - [`CleanupOptions`] passes the [`ClockState`] to restore. Without one,
+//! only the gated audio clocks are re-enabled. It also selects the optional
+//! steps: reverting the MC security carveouts which aren't locked to their
+//! empty reset state and clearing the SE keyslots.
+//!
+//! - [`cleanup`] doesn't wipe memory, see the [`security`] module for this.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::chainload::{self, CleanupOptions};
+//!
+//! fn main() {
+//!     let options = CleanupOptions {
+//!         revert_carveouts: true,
+//!         ..CleanupOptions::default()
+//!     };
+//!
+//!     chainload::cleanup(&options).unwrap();
+//!
+//!     // Jump to the payload.
+//! }
+//! ```
+//!
+//! [`clock::snapshot`]: ../clock/fn.snapshot.html
+//! [`cleanup`]: fn.cleanup.html
+//! [`CleanupOptions`]: struct.CleanupOptions.html
+//! [`ClockState`]: ../clock/struct.ClockState.html
+//! [`security`]: ../security/index.html
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use crate::{
+    arch,
+    clock::{Car, ClockState},
+    mc::{self, Carveout, CARVEOUT_COUNT},
+    se, Result,
+};
+
+/// Base address for the primary interrupt controller registers.
+const ICTLR_BASE: u32 = 0x6000_4000;
+
+/// The number of primary interrupt controllers, each serving 32 interrupts.
+const ICTLR_COUNT: u32 = 6;
+
+/// The distance between the registers of two interrupt controllers.
+const ICTLR_STRIDE: u32 = 0x100;
+
+/// The `ICTLR_CPU_IER_CLR` register, which masks interrupts to the CCPLEX.
+const ICTLR_CPU_IER_CLR: u32 = 0x28;
+
+/// The `ICTLR_COP_IER_CLR` register, which masks interrupts to the BPMP.
+const ICTLR_COP_IER_CLR: u32 = 0x38;
+
+/// The AHUB clock in `CLK_OUT_ENB_V`, gated by the MBIST workaround.
+const CLK_V_AHUB: u32 = 1 << 10;
+
+/// The APE clock in `CLK_OUT_ENB_Y`, gated by the MBIST workaround.
+const CLK_Y_APE: u32 = 1 << 6;
+
+/// Options for [`cleanup`].
+///
+/// [`cleanup`]: fn.cleanup.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CleanupOptions {
+    /// Enables the AHB redirect to IRAM, as the bootrom does.
+    pub ahb_redirect: bool,
+    /// Resets all MC security carveouts which aren't locked, so the
+    /// payload can set up its own layout.
+    pub revert_carveouts: bool,
+    /// Clears all SE keyslots.
+    pub clear_keyslots: bool,
+    /// The clock configuration from before the MBIST workaround, as taken
+    /// by [`clock::snapshot`].
+    ///
+    /// [`clock::snapshot`]: ../clock/fn.snapshot.html
+    pub clocks: Option<ClockState>,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        CleanupOptions {
+            ahb_redirect: true,
+            revert_carveouts: false,
+            clear_keyslots: false,
+            clocks: None,
+        }
+    }
+}
+
+/// Masks all interrupts to the CCPLEX and the BPMP.
+fn mask_interrupts() {
+    arch::disable_interrupts();
+
+    for index in 0..ICTLR_COUNT {
+        let base = ICTLR_BASE + index * ICTLR_STRIDE;
+
+        unsafe {
+            (*((base + ICTLR_CPU_IER_CLR) as *const Mmio<u32>)).write(0xFFFF_FFFF);
+            (*((base + ICTLR_COP_IER_CLR) as *const Mmio<u32>)).write(0xFFFF_FFFF);
+        }
+    }
+}
+
+/// Restores the clock configuration from before the MBIST workaround.
+///
+/// Without a saved configuration, only the gated audio clocks are
+/// re-enabled, which is enough for payloads that touch the APE.
+fn restore_mbist_clocks(car: &Car, clocks: Option<&ClockState>) {
+    match clocks {
+        Some(state) => state.restore(),
+        None => {
+            car.clk_enb_v_set.write(CLK_V_AHUB);
+            car.clk_enb_y_set.write(CLK_Y_APE);
+        }
+    }
+}
+
+/// Resets all security carveouts which aren't locked.
+fn revert_carveouts() -> Result<()> {
+    for id in 1..=CARVEOUT_COUNT {
+        if !mc::is_carveout_locked(id) {
+            mc::apply(&Carveout::new(id))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reverts the hardware state Mirage leaves behind, so arbitrary payloads
+/// can be launched afterwards.
+///
+/// Interrupts stay masked, so this has to be the last thing done before
+/// jumping to the payload.
+pub fn cleanup(options: &CleanupOptions) -> Result<()> {
+    let car = unsafe { Car::get() };
+
+    mask_interrupts();
+    restore_mbist_clocks(car, options.clocks.as_ref());
+
+    if options.revert_carveouts {
+        revert_carveouts()?;
+    }
+
+    if options.clear_keyslots {
+        se::clear_all_keyslots()?;
+    }
+
+    if options.ahb_redirect {
+        mc::enable_ahb_redirect();
+    }
+
+    // Make sure everything reached memory before the payload takes over.
+    arch::disable_cache();
+    arch::data_barrier();
+    arch::instruction_barrier();
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+    use crate::clock;
+
+    /// Gets the address of a register.
+    fn address(register: &Mmio<u32>) -> usize {
+        register as *const _ as usize
+    }
+
+    /// Tests the default cleanup against mocked registers.
+    #[test]
+    fn cleans_up() {
+        let _session = mock::start();
+        let car = unsafe { Car::get() };
+
+        cleanup(&CleanupOptions::default()).unwrap();
+
+        assert_eq!(mock::writes(address(&car.clk_enb_v_set)), [u64::from(CLK_V_AHUB)]);
+        assert_eq!(mock::writes(address(&car.clk_enb_y_set)), [u64::from(CLK_Y_APE)]);
+        assert_eq!(mock::get(0x6000_4538), 0xFFFF_FFFF);
+        assert_eq!(mock::get(0x7001_965C), 0x4000_0000);
+    }
+
+    /// Tests restoring the clock configuration saved before the MBIST
+    /// workaround.
+    #[test]
+    fn restores_clocks() {
+        let _session = mock::start();
+        let car = unsafe { Car::get() };
+
+        mock::set(address(&car.clk_out_enb_y), 0x340);
+        mock::set(address(&car.lvl2_clk_gate_ovrc), 0x8000_0000);
+        mock::set(address(&car.clk_source_vi), 0x2000_0000);
+        let options = CleanupOptions {
+            clocks: Some(clock::snapshot()),
+            ..CleanupOptions::default()
+        };
+
+        // What the workaround leaves behind.
+        car.clk_out_enb_y.write(0x300);
+        car.lvl2_clk_gate_ovrc.write(0);
+        car.clk_source_vi.write(0x8000_0000);
+        car.plld_base.write(0x4000_0000);
+
+        cleanup(&options).unwrap();
+        assert!(mock::writes(address(&car.clk_enb_y_set)).is_empty());
+        assert_eq!(mock::get(address(&car.clk_out_enb_y)), 0x340);
+        assert_eq!(mock::get(address(&car.lvl2_clk_gate_ovrc)), 0x8000_0000);
+        assert_eq!(mock::get(address(&car.clk_source_vi)), 0x2000_0000);
+        assert_eq!(mock::get(address(&car.plld_base)), 0);
+    }
+
+    /// Tests that locked carveouts are left alone.
+    #[test]
+    fn reverts_unlocked_carveouts() {
+        let _session = mock::start();
+
+        mc::apply(&Carveout::new(1).base(0x8000_0000).size_128kb(1)).unwrap();
+        mc::apply(&Carveout::new(2).size_128kb(1).locked()).unwrap();
+
+        let options = CleanupOptions {
+            revert_carveouts: true,
+            ..CleanupOptions::default()
+        };
+        cleanup(&options).unwrap();
+
+        // The base of carveout 1 was reset, carveout 2 is still locked.
+        assert_eq!(mock::get(0x7001_9C08), 0);
+        assert!(mc::is_carveout_locked(2));
+    }
+}
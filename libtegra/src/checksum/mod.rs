@@ -0,0 +1,197 @@
+//! Checksums for validating data without the Security Engine.
+//!
+//! # Description
+//!
+//! Stage-2 payloads and settings blobs read from the SD card can be damaged
+//! by a bad card or an interrupted write. The SE may already be locked down
+//! by the time they are loaded, so detecting such damage shouldn't depend on
+//! its hash engines. These checksums don't protect against tampering though.
+//!
+//! # Implementation
+//!
+//! - [`crc32`] computes the CRC32 used by zlib, PNG and GPT, processing
+//! eight bytes per step with the slicing-by-8 tables.
+//!
+//! - [`adler32`] computes the zlib Adler-32 checksum, which is faster but
+//! weaker for short inputs.
+//!
+//! - [`Crc32`] and [`Adler32`] compute the checksums incrementally, for data
+//! that is read in chunks.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::checksum::{self, Crc32};
+//!
+//! fn main() {
+//!     assert_eq!(checksum::crc32(b"123456789"), 0xCBF4_3926);
+//!
+//!     let mut crc = Crc32::new();
+//!     crc.update(b"1234");
+//!     crc.update(b"56789");
+//!     assert_eq!(crc.finish(), 0xCBF4_3926);
+//! }
+//! ```
+//!
+//! [`crc32`]: fn.crc32.html
+//! [`adler32`]: fn.adler32.html
+//! [`Crc32`]: struct.Crc32.html
+//! [`Adler32`]: struct.Adler32.html
+
+use self::table::TABLE;
+
+mod table;
+
+/// The modulus of the Adler-32 sums.
+const ADLER_MODULUS: u32 = 65_521;
+
+/// The maximum amount of bytes that can be summed before the Adler-32 sums
+/// have to be reduced to not overflow.
+const ADLER_CHUNK: usize = 5_552;
+
+/// Incremental computation of a CRC32.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32 {
+    /// The inverted CRC of the data so far.
+    state: u32,
+}
+
+impl Crc32 {
+    /// Creates a CRC32 of no data.
+    pub const fn new() -> Self {
+        Crc32 { state: !0 }
+    }
+
+    /// Adds data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.state;
+        let mut chunks = data.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+
+            crc = TABLE[7][low as usize & 0xFF]
+                ^ TABLE[6][(low >> 8) as usize & 0xFF]
+                ^ TABLE[5][(low >> 16) as usize & 0xFF]
+                ^ TABLE[4][(low >> 24) as usize]
+                ^ TABLE[3][chunk[4] as usize]
+                ^ TABLE[2][chunk[5] as usize]
+                ^ TABLE[1][chunk[6] as usize]
+                ^ TABLE[0][chunk[7] as usize];
+        }
+
+        for &byte in chunks.remainder() {
+            crc = (crc >> 8) ^ TABLE[0][(crc as u8 ^ byte) as usize];
+        }
+
+        self.state = crc;
+    }
+
+    /// Gets the checksum of the data added so far.
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental computation of an Adler-32 checksum.
+#[derive(Clone, Copy, Debug)]
+pub struct Adler32 {
+    /// The sum of all bytes plus one.
+    a: u32,
+    /// The sum of all values of `a`.
+    b: u32,
+}
+
+impl Adler32 {
+    /// Creates an Adler-32 checksum of no data.
+    pub const fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    /// Adds data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(ADLER_CHUNK) {
+            for &byte in chunk {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+
+            self.a %= ADLER_MODULUS;
+            self.b %= ADLER_MODULUS;
+        }
+    }
+
+    /// Gets the checksum of the data added so far.
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the CRC32 of the given data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+
+    crc.finish()
+}
+
+/// Computes the Adler-32 checksum of the given data.
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+
+    adler.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the checksums against known values.
+    #[test]
+    fn known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    /// Tests that incremental updates match a single pass.
+    #[test]
+    fn incremental() {
+        let mut data = [0u8; 0x3000];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = (i * 7 + i / 0x100) as u8;
+        }
+
+        let mut crc = Crc32::new();
+        let mut adler = Adler32::new();
+        for chunk in data.chunks(13) {
+            crc.update(chunk);
+            adler.update(chunk);
+        }
+
+        assert_eq!(crc.finish(), crc32(&data));
+        assert_eq!(adler.finish(), adler32(&data));
+
+        // The sums must not overflow for long runs of 0xFF.
+        assert_eq!(adler32(&[0xFF; 0x3000]), 0x59D5_D2C2);
+    }
+}
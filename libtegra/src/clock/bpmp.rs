@@ -0,0 +1,160 @@
+//! Clock profiles of the boot and power management processor.
+//!
+//! # Description
+//!
+//! The BPMP, the ARM7 core Mirage runs on, is clocked by SCLK. After
+//! early init, SCLK runs from PLLP_OUT2 at 204MHz, which leaves flows
+//! like cryptography and SD card I/O CPU-bound.
+//!
+//! SCLK can be moved over to PLLC to run the BPMP faster. As SCLK also
+//! clocks the AHB and APB buses, the APB divider has to be raised to keep
+//! PCLK within its limits, and the SoC rail needs a slightly higher
+//! voltage to run the faster logic reliably.
+//!
+//! # Implementation
+//!
+//! - [`Profile`] enumerates the supported clock profiles of the BPMP.
+//!
+//! - [`bpmp_set_profile`] switches between them. Boosting raises the SoC
+//! voltage before it brings up PLLC and reclocks SCLK; returning to the
+//! default profile does the same in reverse and disables PLLC again. The
+//! SoC voltage is set through the PMIC on the given I²C controller.
+//!
+//! - [`bpmp_profile`] gets the currently active profile.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     clock::{bpmp_set_profile, Profile},
+//!     peripherals::Peripherals,
+//! };
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
+//!     bpmp_set_profile(&peripherals.i2c_5, Profile::Boost).unwrap();
+//!
+//!     // Do some heavy lifting.
+//!
+//!     bpmp_set_profile(&peripherals.i2c_5, Profile::Default).unwrap();
+//! }
+//! ```
+//!
+//! [`Profile`]: enum.Profile.html
+//! [`bpmp_set_profile`]: fn.bpmp_set_profile.html
+//! [`bpmp_profile`]: fn.bpmp_profile.html
+
+use mirage_mmio::VolatileStorage;
+
+use super::{
+    fields::{SclkSource, SysState, CLK_SYS_RATE, SCLK_BRST_POL},
+    pll::{Pll, PllConfig},
+    Car,
+};
+use crate::{i2c::I2c, power::max77620::Regulator, timer::usleep, Error};
+
+/// The PLLC configuration for a 1152MHz VCO from a 38.4MHz reference.
+///
+/// This puts PLLC_OUT0 at 576MHz.
+pub const PLLC_BOOST_CONFIG: PllConfig = PllConfig::new(2, 60, 1);
+
+/// The SoC voltage in the default profile, in microvolts.
+const SOC_VOLTAGE_DEFAULT: u32 = 1_125_000;
+/// The SoC voltage in the boost profile, in microvolts.
+const SOC_VOLTAGE_BOOST: u32 = 1_150_000;
+
+/// The clock profiles of the BPMP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// SCLK runs from PLLP_OUT2 at 204MHz, with PCLK at 68MHz.
+    Default,
+    /// SCLK runs from PLLC_OUT0 at 576MHz, with PCLK at 144MHz.
+    Boost,
+}
+
+impl Profile {
+    /// Gets the source of SCLK in the profile.
+    fn source(self) -> SclkSource {
+        match self {
+            Profile::Default => SclkSource::PllPOut2,
+            Profile::Boost => SclkSource::PllCOut0,
+        }
+    }
+
+    /// Gets the divisor of PCLK from HCLK in the profile, minus one.
+    fn apb_rate(self) -> u32 {
+        match self {
+            Profile::Default => 2,
+            Profile::Boost => 3,
+        }
+    }
+}
+
+/// The currently active profile.
+static mut PROFILE: Profile = Profile::Default;
+
+/// Moves SCLK over to the source of a profile.
+fn set_sclk_source(car: &Car, profile: Profile) {
+    let source = profile.source().value();
+
+    car.sclk_brst_pol.write(
+        SCLK_BRST_POL::SYS_STATE.val(SysState::Run.value())
+            | SCLK_BRST_POL::FIQ_SOURCE.val(source)
+            | SCLK_BRST_POL::IRQ_SOURCE.val(source)
+            | SCLK_BRST_POL::RUN_SOURCE.val(source)
+            | SCLK_BRST_POL::IDLE_SOURCE.val(source),
+    );
+
+    // Wait for the clock source change to settle.
+    usleep(1000);
+}
+
+/// Gets the currently active clock profile of the BPMP.
+pub fn bpmp_profile() -> Profile {
+    unsafe { PROFILE }
+}
+
+/// Switches the BPMP to the given clock profile.
+///
+/// The SoC voltage is set through the PMIC on `i2c`. Nothing happens if
+/// the profile is already active. If PLLC fails to lock, the BPMP stays in
+/// the default profile.
+pub fn bpmp_set_profile(i2c: &I2c, profile: Profile) -> Result<(), Error> {
+    if bpmp_profile() == profile {
+        return Ok(());
+    }
+
+    let car = unsafe { Car::get() };
+
+    match profile {
+        Profile::Boost => {
+            Regulator::SD0.set_voltage(i2c, SOC_VOLTAGE_BOOST)?;
+
+            if let Err(err) = Pll::C.configure(&PLLC_BOOST_CONFIG) {
+                Regulator::SD0.set_voltage(i2c, SOC_VOLTAGE_DEFAULT)?;
+                return Err(err.into());
+            }
+
+            // Lower PCLK before SCLK goes up.
+            car.clk_sys_rate
+                .write(CLK_SYS_RATE::APB_RATE.val(profile.apb_rate()));
+            set_sclk_source(car, profile);
+        }
+        Profile::Default => {
+            // Lower SCLK before PCLK goes up.
+            set_sclk_source(car, profile);
+            car.clk_sys_rate
+                .write(CLK_SYS_RATE::APB_RATE.val(profile.apb_rate()));
+
+            Pll::C.disable();
+            Regulator::SD0.set_voltage(i2c, SOC_VOLTAGE_DEFAULT)?;
+        }
+    }
+
+    unsafe {
+        PROFILE = profile;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! Field definitions for the most-used CAR registers.
+//!
+//! # Description
+//!
+//! Early hardware initialization programs many clock registers at once.
+//! Instead of handwritten masks, this module describes the fields of
+//! these registers and enumerates the values of fields that select
+//! frequencies, parents or states.
+//!
+//! # Implementation
+//!
+//! - Every register is a module holding a [`Field`] per field, so they
+//! can be used with [`Mmio::read_field`], [`Mmio::write_field`] and
+//! [`Field::val`].
+//!
+//! - Enumerations like [`OscFrequency`], [`SysState`] and [`SclkSource`]
+//! list the valid values of fields. Their `value` methods pass them to
+//! the field setters, so raw numbers don't have to be spelled out.
+//!
+//! - [`CLK_SOURCE`] applies to all `CLK_SOURCE_*` registers of peripheral
+//! clocks. Peripherals with an 8-bit divisor ignore the upper bits.
+//! Likewise, [`PLL_BASE`] describes the bits shared by all PLLs.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::clock::{
+//!     fields::{OscFrequency, OSC_CTRL},
+//!     Car,
+//! };
+//! use mirage_mmio::VolatileStorage;
+//!
+//! fn main() {
+//!     let car = unsafe { Car::get() };
+//!
+//!     car.osc_ctrl
+//!         .write_field(OSC_CTRL::OSC_FREQ, OscFrequency::Osc38P4.value());
+//! }
+//! ```
+//!
+//! [`Field`]: ../../../mirage_mmio/struct.Field.html
+//! [`Mmio::read_field`]: ../../../mirage_mmio/struct.Mmio.html#method.read_field
+//! [`Mmio::write_field`]: ../../../mirage_mmio/struct.Mmio.html#method.write_field
+//! [`Field::val`]: ../../../mirage_mmio/struct.Field.html#method.val
+//! [`OscFrequency`]: enum.OscFrequency.html
+//! [`SysState`]: enum.SysState.html
+//! [`SclkSource`]: enum.SclkSource.html
+//! [`CLK_SOURCE`]: CLK_SOURCE/index.html
+//! [`PLL_BASE`]: PLL_BASE/index.html
+
+use mirage_mmio::register_bitfields;
+
+register_bitfields! {
+    u32,
+    /// The `CLK_RST_CONTROLLER_OSC_CTRL_0` register.
+    pub OSC_CTRL [
+        /// Enables the oscillator.
+        XOE OFFSET(0) NUMBITS(1),
+        /// The drive strength of the oscillator.
+        XOFS OFFSET(4) NUMBITS(6),
+        /// The frequency of the oscillator, see `OscFrequency`.
+        OSC_FREQ OFFSET(28) NUMBITS(4),
+    ],
+    /// The `CLK_RST_CONTROLLER_SPARE_REG0_0` register.
+    pub SPARE_REG0 [
+        /// The divisor of CLK_M, minus one.
+        CLK_M_DIVISOR OFFSET(2) NUMBITS(2),
+    ],
+    /// The `CLK_RST_CONTROLLER_SCLK_BURST_POLICY_0` register.
+    pub SCLK_BRST_POL [
+        /// The source in the IDLE state, see `SclkSource`.
+        IDLE_SOURCE OFFSET(0) NUMBITS(4),
+        /// The source in the RUN state, see `SclkSource`.
+        RUN_SOURCE OFFSET(4) NUMBITS(4),
+        /// The source in the IRQ state, see `SclkSource`.
+        IRQ_SOURCE OFFSET(8) NUMBITS(4),
+        /// The source in the FIQ state, see `SclkSource`.
+        FIQ_SOURCE OFFSET(12) NUMBITS(4),
+        /// The current state of the system clock, see `SysState`.
+        SYS_STATE OFFSET(28) NUMBITS(4),
+    ],
+    /// The `CLK_RST_CONTROLLER_SUPER_SCLK_DIVIDER_0` register.
+    pub SUPER_SCLK_DIV [
+        /// The divisor of the skipper, minus one.
+        DIVISOR OFFSET(0) NUMBITS(8),
+        /// The dividend of the skipper, minus one.
+        DIVIDEND OFFSET(8) NUMBITS(8),
+        /// Enables the super clock divider.
+        ENABLE OFFSET(31) NUMBITS(1),
+    ],
+    /// The `CLK_RST_CONTROLLER_CLK_SYSTEM_RATE_0` register.
+    pub CLK_SYS_RATE [
+        /// The divisor of PCLK from HCLK, minus one.
+        APB_RATE OFFSET(0) NUMBITS(2),
+        /// The divisor of HCLK from SCLK, minus one.
+        AHB_RATE OFFSET(4) NUMBITS(2),
+    ],
+    /// The `CLK_RST_CONTROLLER_CLK_SOURCE_*_0` registers.
+    pub CLK_SOURCE [
+        /// The divisor of the clock, a 7.1 fixed-point value for most clocks.
+        DIVISOR OFFSET(0) NUMBITS(16),
+        /// The index of the parent clock in the mux of the clock.
+        SOURCE OFFSET(29) NUMBITS(3),
+    ],
+    /// The `CLK_RST_CONTROLLER_CLK_SOURCE_SOR1_0` register.
+    pub CLK_SOURCE_SOR1 [
+        /// Selects the safe clock over the pad clock.
+        SOR1_CLK_SEL0 OFFSET(14) NUMBITS(1),
+        /// Selects the SOR1 clock over the selection of `SOR1_CLK_SEL0`.
+        SOR1_CLK_SEL1 OFFSET(15) NUMBITS(1),
+    ],
+    /// The `CLK_RST_CONTROLLER_PLL*_BASE_0` registers.
+    pub PLL_BASE [
+        /// Enables the CSI clock output, only present in `PLLD_BASE`.
+        PLLD_CSI_CLK_SRC OFFSET(23) NUMBITS(1),
+        /// Disables the reference clock.
+        REF_DIS OFFSET(29) NUMBITS(1),
+        /// Enables the PLL.
+        ENABLE OFFSET(30) NUMBITS(1),
+        /// Bypasses the PLL.
+        BYPASS OFFSET(31) NUMBITS(1),
+    ]
+}
+
+/// The frequencies of the oscillator in `OSC_CTRL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OscFrequency {
+    /// 13MHz.
+    Osc13 = 0,
+    /// 16.8MHz.
+    Osc16P8 = 1,
+    /// 19.2MHz.
+    Osc19P2 = 4,
+    /// 38.4MHz, as used by the Switch.
+    Osc38P4 = 5,
+    /// 12MHz.
+    Osc12 = 8,
+    /// 48MHz.
+    Osc48 = 9,
+    /// 26MHz.
+    Osc26 = 12,
+}
+
+impl OscFrequency {
+    /// Gets the raw value of the `OSC_FREQ` field.
+    pub const fn value(self) -> u32 {
+        self as u32
+    }
+}
+
+/// The states of the system clock in `SCLK_BRST_POL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SysState {
+    /// The system clock is stopped.
+    Standby = 0,
+    /// The system clock runs from the IDLE source.
+    Idle = 1,
+    /// The system clock runs from the RUN source.
+    Run = 2,
+}
+
+impl SysState {
+    /// Gets the raw value of the `SYS_STATE` field.
+    pub const fn value(self) -> u32 {
+        self as u32
+    }
+}
+
+/// The parents of the system clock in `SCLK_BRST_POL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SclkSource {
+    /// CLK_M, the divided oscillator.
+    ClkM = 0,
+    /// PLLC_OUT0, as configured by the PLLC dividers.
+    PllCOut0 = 2,
+    /// PLLP_OUT0 at 408MHz.
+    PllPOut0 = 3,
+    /// PLLP_OUT2 at 204MHz.
+    PllPOut2 = 4,
+    /// CLK_S, the 32.768kHz clock.
+    ClkS = 6,
+}
+
+impl SclkSource {
+    /// Gets the raw value of a `*_SOURCE` field.
+    pub const fn value(self) -> u32 {
+        self as u32
+    }
+}
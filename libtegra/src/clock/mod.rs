@@ -24,13 +24,38 @@
 //! - [`Clock::enable`], [`Clock::disable`] and [`Clock::is_enabled`] can
 //! be used to check and modify the state of a device.
 //!
+//! - [`Clock::decode_source`] turns a raw `CLK_SOURCE_*` register value
+//! into a [`SourceInfo`], consisting of the parent clock, the divisor and
+//! the resulting frequency given the [`ParentRates`]. [`dump`] prints this
+//! information for all known clocks, which helps with debugging clocking
+//! issues. [`find`] looks up one of them by its name.
+//!
+//! - [`Clock::set_source`] and [`Clock::enable_with`] reclock a device at
+//! runtime. [`Clock::frequency`] computes its current rate using the PLL
+//! state read through [`ParentRates::read`].
+//!
+//! - The [`fields`] module describes the fields of the most-used CAR
+//! registers, together with enumerations of their values.
+//!
+//! - The [`pll`] module takes care of configuring and locking PLLs.
+//!
+//! - The [`bpmp`] module switches the clock profile of the boot processor,
+//! re-exported as [`bpmp_set_profile`] and [`Profile`].
+//!
+//! - The [`sdmmc`] module provides the clock, pad and trim sequences for
+//! the speed modes of the SD/eMMC controllers.
+//!
+//! - The [`state`] module audits which clocks are enabled and which devices
+//! are held in reset. [`snapshot`] reads the state of all devices into a
+//! [`ClockState`], which can be compared against a later one.
+//!
 //! # Example
 //!
 //! ```
 //! use mirage_libtegra::clock::Clock;
 //!
 //! fn main() {
-//!     let se_clock = Clock::SE;
+//!     let mut se_clock = Clock::SE;
 //!
 //!     // Enable Security Engine.
 //!     se_clock.enable();
@@ -43,14 +68,43 @@
 //! ```
 //!
 //! [`Car`]: struct.Car.html
+//! [`fields`]: fields/index.html
 //! [`Clock`]: struct.Clock.html
 //! [`Clock::enable`]: struct.Clock.html#method.enable
 //! [`Clock::disable`]: struct.Clock.html#method.disable
 //! [`Clock::is_enabled`]: struct.Clock.html#method.is_enabled
-
-use mirage_mmio::{Mmio, VolatileStorage};
-
-use crate::timer::usleep;
+//! [`Clock::decode_source`]: struct.Clock.html#method.decode_source
+//! [`SourceInfo`]: struct.SourceInfo.html
+//! [`ParentRates`]: struct.ParentRates.html
+//! [`dump`]: fn.dump.html
+//! [`find`]: fn.find.html
+//! [`Clock::set_source`]: struct.Clock.html#method.set_source
+//! [`Clock::enable_with`]: struct.Clock.html#method.enable_with
+//! [`Clock::frequency`]: struct.Clock.html#method.frequency
+//! [`ParentRates::read`]: struct.ParentRates.html#method.read
+//! [`pll`]: pll/index.html
+//! [`bpmp`]: bpmp/index.html
+//! [`bpmp_set_profile`]: fn.bpmp_set_profile.html
+//! [`Profile`]: enum.Profile.html
+//! [`sdmmc`]: sdmmc/index.html
+//! [`state`]: state/index.html
+//! [`snapshot`]: fn.snapshot.html
+//! [`ClockState`]: struct.ClockState.html
+
+use core::fmt;
+
+use mirage_mmio::{assert_layout, Mmio, VolatileStorage};
+
+use crate::{timer::usleep, Error};
+
+pub mod bpmp;
+pub mod fields;
+pub mod pll;
+pub mod sdmmc;
+pub mod state;
+
+pub use self::bpmp::{bpmp_profile, bpmp_set_profile, Profile};
+pub use self::state::{snapshot, ClockDiff, ClockState};
 
 /// Base address for clock registers.
 pub(crate) const CLOCK_BASE: u32 = 0x6000_6000;
@@ -65,6 +119,11 @@ pub const CLK_SOURCE_FIRST: u32 = (0b000 << 29);
 pub const CLK_DIVIDER_MASK: u32 = (0xff << 0);
 pub const CLK_DIVIDER_UNITY: u32 = (0x00 << 0);
 
+/// Enables a PLL.
+const PLL_BASE_ENABLE: u32 = 1 << 30;
+/// Bypasses a PLL, passing through its reference clock.
+const PLL_BASE_BYPASS: u32 = 1 << 31;
+
 /// Representation of the CAR.
 #[allow(non_snake_case)]
 #[repr(C)]
@@ -488,6 +547,28 @@ impl VolatileStorage for Car {
     }
 }
 
+assert_layout!(car_layout, Car, 0x748, {
+    rst_src: 0x000,
+    rst_dev_l: 0x004,
+    clk_out_enb_l: 0x010,
+    cclk_brst_pol: 0x020,
+    osc_ctrl: 0x050,
+    pllm_base: 0x090,
+    pllx_base: 0x0E0,
+    clk_source_sdmmc1: 0x150,
+    clk_source_uarta: 0x178,
+    clk_source_emc: 0x19C,
+    clk_out_enb_x: 0x280,
+    clk_out_enb_y: 0x298,
+    rst_dev_l_set: 0x300,
+    rst_dev_v: 0x358,
+    clk_source_mselect: 0x3B4,
+    rst_dev_v_set: 0x430,
+    pllmb_base: 0x5E8,
+    clk_source_usb2_hsic_trk: 0x6CC,
+    clk_source_emc_safe: 0x724,
+});
+
 /// Representation of a device clock.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Clock {
@@ -537,8 +618,17 @@ const CLK_RST_CONTROLLER_CLK_SOURCE_SE: u32 = 0x42C;
 const CLK_RST_CONTROLLER_CLK_SOURCE_HOST1X: u32 = 0x180;
 const CLK_RST_CONTROLLER_CLK_SOURCE_TSEC: u32 = 0x1F4;
 const CLK_RST_CONTROLLER_CLK_SOURCE_SOR1: u32 = 0x410;
+const CLK_RST_CONTROLLER_CLK_SOURCE_DISP2: u32 = 0x13C;
 const CLK_RST_CONTROLLER_CLK_SOURCE_CSITE: u32 = 0x1D4;
-const CLK_RST_CONTROLLER_CLK_SOURCE_PWM: u32 = 0x11;
+const CLK_RST_CONTROLLER_CLK_SOURCE_PWM: u32 = 0x110;
+const CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC1: u32 = 0x150;
+const CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC2: u32 = 0x154;
+const CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC4: u32 = 0x164;
+const CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC3: u32 = 0x1BC;
+const CLK_RST_CONTROLLER_CLK_SOURCE_ACTMON: u32 = 0x3E8;
+const CLK_RST_CONTROLLER_CLK_SOURCE_USB2_HSIC_TRK: u32 = 0x6CC;
+const CLK_RST_CONTROLLER_CLK_SOURCE_I2S1: u32 = 0x100;
+const CLK_RST_CONTROLLER_CLK_SOURCE_APE: u32 = 0x6C0;
 
 // Definitions for known devices.
 impl Clock {
@@ -712,6 +802,16 @@ impl Clock {
         clock_divisor: 0,
     };
 
+    /// Representation of the GPU clock.
+    pub const GPU: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_X,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_X,
+        source: CLK_NO_SOURCE,
+        index: 0x18,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
     /// Representation of the SOR1 clock.
     pub const SOR1: Self = Clock {
         reset: CLK_RST_CONTROLLER_RST_DEVICES_X,
@@ -722,6 +822,26 @@ impl Clock {
         clock_divisor: 0x2,
     };
 
+    /// Representation of the DISP2 clock, clocked from PLLD2.
+    pub const DISP2: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_L,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_DISP2,
+        index: 0x1A,
+        clock_source: 0x5,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the DPAUX1 clock.
+    pub const DPAUX1: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_Y,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_Y,
+        source: CLK_NO_SOURCE,
+        index: 0xF,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
     /// Representation of the KFUSE clock.
     pub const KFUSE: Self = Clock {
         reset: CLK_RST_CONTROLLER_RST_DEVICES_H,
@@ -761,11 +881,134 @@ impl Clock {
         clock_source: 0x6,
         clock_divisor: 0x4,
     };
+
+    /// Representation of the AHBDMA clock.
+    pub const AHBDMA: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_H,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_H,
+        source: CLK_NO_SOURCE,
+        index: 0x1,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the APBDMA clock.
+    pub const APBDMA: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_H,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_H,
+        source: CLK_NO_SOURCE,
+        index: 0x2,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the SDMMC1 clock.
+    pub const SDMMC1: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_L,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC1,
+        index: 0xE,
+        clock_source: 0,
+        clock_divisor: 0x1E,
+    };
+
+    /// Representation of the SDMMC2 clock.
+    pub const SDMMC2: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_L,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC2,
+        index: 0x9,
+        clock_source: 0,
+        clock_divisor: 0x1E,
+    };
+
+    /// Representation of the SDMMC3 clock.
+    pub const SDMMC3: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_U,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_U,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC3,
+        index: 0x5,
+        clock_source: 0,
+        clock_divisor: 0x1E,
+    };
+
+    /// Representation of the SDMMC4 clock.
+    pub const SDMMC4: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_L,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC4,
+        index: 0xF,
+        clock_source: 0,
+        clock_divisor: 0x1E,
+    };
+
+    /// Representation of the ACTMON clock, fed from CLK_M.
+    pub const ACTMON: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_V,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_V,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_ACTMON,
+        index: 0x17,
+        clock_source: 6,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the USB2 pad tracking clock.
+    pub const USB2_TRK: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_Y,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_Y,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_USB2_HSIC_TRK,
+        index: 0x12,
+        clock_source: 0,
+        clock_divisor: 0x6,
+    };
+
+    /// Representation of the Audio Processing Engine clock.
+    pub const APE: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_Y,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_Y,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_APE,
+        index: 0x6,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the APB to APE bridge clock.
+    pub const APB2APE: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_V,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_V,
+        source: CLK_NO_SOURCE,
+        index: 0xB,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the audio hub and ADMA clock.
+    pub const D_AUDIO: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_V,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_V,
+        source: CLK_NO_SOURCE,
+        index: 0xA,
+        clock_source: 0,
+        clock_divisor: 0,
+    };
+
+    /// Representation of the I2S 1 clock.
+    ///
+    /// This is the bit clock of the controller, 1.536MHz from CLK_M for
+    /// 16-bit stereo samples at 48kHz.
+    pub const I2S_1: Self = Clock {
+        reset: CLK_RST_CONTROLLER_RST_DEVICES_L,
+        enable: CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+        source: CLK_RST_CONTROLLER_CLK_SOURCE_I2S1,
+        index: 0x1E,
+        clock_source: 0x6,
+        clock_divisor: 0x17,
+    };
 }
 
 impl Clock {
     /// Sets whether the clock should be reset or not.
-    fn set_reset(&self, set_reset: bool) {
+    pub(crate) fn set_reset(&mut self, set_reset: bool) {
         let reset_reg = unsafe { &*((CLOCK_BASE + self.reset) as *const Mmio<u32>) };
 
         let current_value = reset_reg.read();
@@ -781,7 +1024,7 @@ impl Clock {
     }
 
     /// Sets whether the clock should be enabled or disabled.
-    fn set_enable(&self, set_enable: bool) {
+    pub(crate) fn set_enable(&mut self, set_enable: bool) {
         let enable_reg = unsafe { &*((CLOCK_BASE + self.enable) as *const Mmio<u32>) };
 
         let current_value = enable_reg.read();
@@ -796,16 +1039,15 @@ impl Clock {
         enable_reg.write(new_value);
     }
 
-    /// Enables the clock.
-    pub fn enable(&self) {
+    /// Enables the clock after programming the given source register value.
+    fn enable_with_source(&mut self, source: Option<u32>) {
         // Disable clock.
         self.disable();
 
         // Setup clock source if needed.
-        if self.source != 0 {
+        if let Some(value) = source {
             unsafe {
-                (*((CLOCK_BASE + self.source) as *const Mmio<u32>))
-                    .write((self.clock_source << 29) | self.clock_divisor);
+                (*((CLOCK_BASE + self.source) as *const Mmio<u32>)).write(value);
             }
         }
 
@@ -827,8 +1069,33 @@ impl Clock {
         }
     }
 
+    /// Enables the clock.
+    pub fn enable(&mut self) {
+        let source = if self.source != 0 {
+            Some((self.clock_source << 29) | self.clock_divisor)
+        } else {
+            None
+        };
+
+        self.enable_with_source(source);
+    }
+
+    /// Enables the clock, clocked from the given parent with the given
+    /// raw divisor instead of the default configuration.
+    ///
+    /// See [`Clock::set_source`] for the possible errors.
+    ///
+    /// [`Clock::set_source`]: struct.Clock.html#method.set_source
+    pub fn enable_with(&mut self, parent: ClockParent, divisor: u32) -> Result<(), Error> {
+        let value = self.encode_source(parent, divisor)?;
+
+        self.enable_with_source(Some(value));
+
+        Ok(())
+    }
+
     /// Disables the clock.
-    pub fn disable(&self) {
+    pub fn disable(&mut self) {
         // Put clock into reset.
         self.set_reset(true);
         // Disable.
@@ -843,3 +1110,547 @@ impl Clock {
         (enable_reg.read() & mask) == mask
     }
 }
+
+/// Possible parent clocks of device clocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockParent {
+    /// PLLP_OUT0.
+    PllP,
+    /// PLLC_OUT0.
+    PllC,
+    /// PLLC2_OUT0.
+    PllC2,
+    /// PLLC3_OUT0.
+    PllC3,
+    /// PLLM_OUT0.
+    PllM,
+    /// PLLA_OUT0.
+    PllA,
+    /// PLLC4_OUT0.
+    PllC4Out0,
+    /// PLLC4_OUT1, a fixed third of the PLLC4 VCO.
+    PllC4Out1,
+    /// PLLC4_OUT2, a fixed fifth of the PLLC4 VCO.
+    PllC4Out2,
+    /// CLK_M.
+    ClkM,
+    /// A reserved or unknown mux selection.
+    Unknown,
+}
+
+impl ClockParent {
+    /// Gets a human-readable name of the parent clock.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockParent::PllP => "PLLP",
+            ClockParent::PllC => "PLLC",
+            ClockParent::PllC2 => "PLLC2",
+            ClockParent::PllC3 => "PLLC3",
+            ClockParent::PllM => "PLLM",
+            ClockParent::PllA => "PLLA",
+            ClockParent::PllC4Out0 => "PLLC4_OUT0",
+            ClockParent::PllC4Out1 => "PLLC4_OUT1",
+            ClockParent::PllC4Out2 => "PLLC4_OUT2",
+            ClockParent::ClkM => "CLK_M",
+            ClockParent::Unknown => "unknown",
+        }
+    }
+}
+
+/// The parent clock mux of most peripheral clocks.
+const MUX_PLLP_PLLC2_C_C3_PLLM_CLKM: [ClockParent; 8] = [
+    ClockParent::PllP,
+    ClockParent::PllC2,
+    ClockParent::PllC,
+    ClockParent::PllC3,
+    ClockParent::PllM,
+    ClockParent::Unknown,
+    ClockParent::ClkM,
+    ClockParent::Unknown,
+];
+
+/// The parent clock mux of the HOST1X clock.
+const MUX_PLLM_PLLC2_C_C3_PLLP_PLLA: [ClockParent; 8] = [
+    ClockParent::PllM,
+    ClockParent::PllC2,
+    ClockParent::PllC,
+    ClockParent::PllC3,
+    ClockParent::PllP,
+    ClockParent::Unknown,
+    ClockParent::PllA,
+    ClockParent::Unknown,
+];
+
+/// The parent clock mux of the I2S clocks.
+///
+/// The audio sync clocks aren't supported.
+const MUX_PLLA_PLLP_CLKM: [ClockParent; 8] = [
+    ClockParent::PllA,
+    ClockParent::Unknown,
+    ClockParent::Unknown,
+    ClockParent::Unknown,
+    ClockParent::PllP,
+    ClockParent::Unknown,
+    ClockParent::ClkM,
+    ClockParent::Unknown,
+];
+
+/// The parent clock mux of the SDMMC clocks.
+///
+/// SDMMC2 and SDMMC4 can additionally select low-jitter variants
+/// of the PLLC4 outputs, which aren't supported.
+const MUX_PLLP_PLLC4_OUT2_OUT1_CLKM_OUT0: [ClockParent; 8] = [
+    ClockParent::PllP,
+    ClockParent::Unknown,
+    ClockParent::Unknown,
+    ClockParent::PllC4Out2,
+    ClockParent::PllC4Out1,
+    ClockParent::Unknown,
+    ClockParent::ClkM,
+    ClockParent::PllC4Out0,
+];
+
+/// The frequencies of the parent clocks in Hz.
+///
+/// A frequency of `0` denotes an unknown rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParentRates {
+    /// The frequency of PLLP_OUT0.
+    pub pll_p: u32,
+    /// The frequency of PLLC_OUT0.
+    pub pll_c: u32,
+    /// The frequency of PLLC2_OUT0.
+    pub pll_c2: u32,
+    /// The frequency of PLLC3_OUT0.
+    pub pll_c3: u32,
+    /// The frequency of PLLM_OUT0.
+    pub pll_m: u32,
+    /// The frequency of PLLA_OUT0.
+    pub pll_a: u32,
+    /// The frequency of PLLC4_OUT0.
+    pub pll_c4_out0: u32,
+    /// The frequency of PLLC4_OUT1.
+    pub pll_c4_out1: u32,
+    /// The frequency of PLLC4_OUT2.
+    pub pll_c4_out2: u32,
+    /// The frequency of CLK_M.
+    pub clk_m: u32,
+}
+
+impl ParentRates {
+    /// The rates set up by the Boot ROM, with PLLP at 408MHz
+    /// and CLK_M at 19.2MHz.
+    pub const DEFAULT: Self = ParentRates {
+        pll_p: 408_000_000,
+        pll_c: 0,
+        pll_c2: 0,
+        pll_c3: 0,
+        pll_m: 0,
+        pll_a: 0,
+        pll_c4_out0: 0,
+        pll_c4_out1: 0,
+        pll_c4_out2: 0,
+        clk_m: 19_200_000,
+    };
+
+    /// Gets the frequency of a parent clock, if known.
+    pub fn rate(&self, parent: ClockParent) -> Option<u32> {
+        let rate = match parent {
+            ClockParent::PllP => self.pll_p,
+            ClockParent::PllC => self.pll_c,
+            ClockParent::PllC2 => self.pll_c2,
+            ClockParent::PllC3 => self.pll_c3,
+            ClockParent::PllM => self.pll_m,
+            ClockParent::PllA => self.pll_a,
+            ClockParent::PllC4Out0 => self.pll_c4_out0,
+            ClockParent::PllC4Out1 => self.pll_c4_out1,
+            ClockParent::PllC4Out2 => self.pll_c4_out2,
+            ClockParent::ClkM => self.clk_m,
+            ClockParent::Unknown => 0,
+        };
+
+        if rate == 0 {
+            None
+        } else {
+            Some(rate)
+        }
+    }
+}
+
+/// Decodes the output frequency of a PLL from its base register.
+///
+/// `divn_shift` is the offset of the feedback divider and `qlin` selects
+/// the 5-bit post divider table over the 1-bit linear one.
+fn pll_rate(base: u32, reference: u32, divn_shift: u32, qlin: bool) -> u32 {
+    /// Post divider values of PLLs with a "QLIN" post divider.
+    const QLIN_POST_DIVIDERS: [u32; 17] = [
+        1, 2, 3, 4, 5, 6, 8, 9, 10, 12, 15, 16, 18, 20, 24, 30, 32,
+    ];
+
+    // The PLL is disabled.
+    if base & PLL_BASE_ENABLE == 0 {
+        return 0;
+    }
+
+    // The PLL passes the reference clock through.
+    if base & PLL_BASE_BYPASS != 0 {
+        return reference;
+    }
+
+    let divm = base & 0xFF;
+    let divn = (base >> divn_shift) & 0xFF;
+    let divp = if qlin {
+        match QLIN_POST_DIVIDERS.get(((base >> 20) & 0x1F) as usize) {
+            Some(divp) => *divp,
+            None => return 0,
+        }
+    } else {
+        ((base >> 20) & 1) + 1
+    };
+
+    if divm == 0 {
+        return 0;
+    }
+
+    ((u64::from(reference) * u64::from(divn)) / u64::from(divm * divp)) as u32
+}
+
+impl ParentRates {
+    /// Reads the current parent clock frequencies from the
+    /// oscillator and PLL configuration.
+    pub fn read() -> Self {
+        let car = unsafe { Car::get() };
+
+        let osc_ctrl = car.osc_ctrl.read();
+        let osc = match osc_ctrl >> 28 {
+            0 => 13_000_000,
+            1 => 16_800_000,
+            4 => 19_200_000,
+            5 => 38_400_000,
+            8 => 12_000_000,
+            9 => 48_000_000,
+            12 => 26_000_000,
+            _ => 0,
+        };
+
+        let reference = osc >> ((osc_ctrl >> 26) & 3);
+        let clk_m = osc / (((car.spare_reg0.read() >> 2) & 3) + 1);
+
+        // PLLP is fixed to 408MHz by the Boot ROM.
+        let pll_p = if car.pllp_base.read() & PLL_BASE_ENABLE != 0 {
+            408_000_000
+        } else {
+            0
+        };
+
+        // PLLC4 keeps its post divider at bit 19 rather than 20, and its
+        // secondary outputs are fixed fractions of the VCO.
+        let pllc4 = car.pllc4_base.read() & !(0x3F << 19);
+        let pllc4_divp = (car.pllc4_base.read() >> 19) & 0x1F;
+        let pllc4_vco = pll_rate(pllc4, reference, 8, true);
+
+        ParentRates {
+            pll_p,
+            pll_c: pll_rate(car.pllc_base.read(), reference, 10, true),
+            pll_c2: pll_rate(car.pllc2_base.read(), reference, 10, true),
+            pll_c3: pll_rate(car.pllc3_base.read(), reference, 10, true),
+            pll_m: pll_rate(car.pllm_base.read(), reference, 8, false),
+            pll_a: pll_rate(car.plla_base.read(), reference, 8, true),
+            pll_c4_out0: pll_rate(pllc4 | (pllc4_divp << 20), reference, 8, true),
+            pll_c4_out1: pllc4_vco / 3,
+            pll_c4_out2: pllc4_vco / 5,
+            clk_m,
+        }
+    }
+}
+
+impl Default for ParentRates {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A decoded `CLK_SOURCE_*` register value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceInfo {
+    /// The selected parent clock.
+    pub parent: ClockParent,
+    /// The raw value of the divisor field.
+    pub divisor: u32,
+    /// Whether the divisor is an integer divisor rather
+    /// than one in 7.1 fixed-point format.
+    pub integer_divisor: bool,
+    /// The resulting frequency in Hz, if the parent rate is known.
+    pub frequency: Option<u32>,
+}
+
+impl fmt::Display for SourceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / ", self.parent.name())?;
+
+        if self.integer_divisor {
+            write!(f, "{}", self.divisor + 1)?;
+        } else {
+            // Divisors in 7.1 format divide by (n / 2) + 1.
+            write!(
+                f,
+                "{}.{}",
+                self.divisor / 2 + 1,
+                if self.divisor & 1 != 0 { 5 } else { 0 }
+            )?;
+        }
+
+        match self.frequency {
+            Some(frequency) => write!(f, " = {} Hz", frequency),
+            None => write!(f, " = ? Hz"),
+        }
+    }
+}
+
+impl Clock {
+    /// Gets the parent clock mux of the clock.
+    fn parent_mux(&self) -> &'static [ClockParent; 8] {
+        match self.source {
+            CLK_RST_CONTROLLER_CLK_SOURCE_HOST1X => &MUX_PLLM_PLLC2_C_C3_PLLP_PLLA,
+            CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC1
+            | CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC2
+            | CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC3
+            | CLK_RST_CONTROLLER_CLK_SOURCE_SDMMC4 => &MUX_PLLP_PLLC4_OUT2_OUT1_CLKM_OUT0,
+            CLK_RST_CONTROLLER_CLK_SOURCE_I2S1 => &MUX_PLLA_PLLP_CLKM,
+            _ => &MUX_PLLP_PLLC2_C_C3_PLLM_CLKM,
+        }
+    }
+
+    /// Whether the clock uses a 16-bit integer divisor
+    /// instead of the 8-bit fixed-point one.
+    fn has_integer_divisor(&self) -> bool {
+        match self.source {
+            CLK_RST_CONTROLLER_CLK_SOURCE_I2C_1
+            | CLK_RST_CONTROLLER_CLK_SOURCE_I2C_2
+            | CLK_RST_CONTROLLER_CLK_SOURCE_I2C_3
+            | CLK_RST_CONTROLLER_CLK_SOURCE_I2C_4
+            | CLK_RST_CONTROLLER_CLK_SOURCE_I2C_5
+            | CLK_RST_CONTROLLER_CLK_SOURCE_I2C_6 => true,
+            _ => false,
+        }
+    }
+
+    /// Encodes a clock source register value for the given parent and raw divisor.
+    fn encode_source(&self, parent: ClockParent, divisor: u32) -> Result<u32, Error> {
+        if self.source == CLK_NO_SOURCE || parent == ClockParent::Unknown {
+            return Err(Error::Clock);
+        }
+
+        let divisor_mask = if self.has_integer_divisor() {
+            0xFFFF
+        } else {
+            CLK_DIVIDER_MASK
+        };
+
+        if divisor & !divisor_mask != 0 {
+            return Err(Error::Clock);
+        }
+
+        let index = self
+            .parent_mux()
+            .iter()
+            .position(|p| *p == parent)
+            .ok_or(Error::Clock)?;
+
+        Ok(((index as u32) << 29) | divisor)
+    }
+
+    /// Selects the parent clock and the raw divisor of the clock.
+    ///
+    /// The divisor is given in the format of the clock's divisor field,
+    /// which is a 7.1 fixed-point value for most clocks. Fails if the
+    /// clock has no source register, the parent is not available to the
+    /// clock or the divisor doesn't fit.
+    pub fn set_source(&mut self, parent: ClockParent, divisor: u32) -> Result<(), Error> {
+        let value = self.encode_source(parent, divisor)?;
+
+        unsafe {
+            (*((CLOCK_BASE + self.source) as *const Mmio<u32>)).write(value);
+        }
+
+        Ok(())
+    }
+
+    /// Computes the current frequency of the clock in Hz from its source
+    /// configuration and the state of the PLLs.
+    ///
+    /// Returns `0` if the clock has no source register or the frequency
+    /// of its parent clock is unknown.
+    pub fn frequency(&self) -> u32 {
+        self.read_source()
+            .and_then(|value| self.decode_source(value, &ParentRates::read()).frequency)
+            .unwrap_or(0)
+    }
+
+    /// Reads the raw value of the clock source register.
+    ///
+    /// Returns `None` if the clock doesn't have a source register.
+    pub fn read_source(&self) -> Option<u32> {
+        if self.source == CLK_NO_SOURCE {
+            return None;
+        }
+
+        Some(unsafe { (*((CLOCK_BASE + self.source) as *const Mmio<u32>)).read() })
+    }
+
+    /// Decodes a value of the clock source register into the selected
+    /// parent clock, the divisor and the resulting frequency.
+    pub fn decode_source(&self, value: u32, rates: &ParentRates) -> SourceInfo {
+        let parent = self.parent_mux()[(value >> 29) as usize];
+        let integer_divisor = self.has_integer_divisor();
+
+        let divisor = if integer_divisor {
+            value & 0xFFFF
+        } else {
+            value & CLK_DIVIDER_MASK
+        };
+
+        let frequency = rates.rate(parent).map(|rate| {
+            if integer_divisor {
+                rate / (divisor + 1)
+            } else {
+                ((u64::from(rate) * 2) / u64::from(divisor + 2)) as u32
+            }
+        });
+
+        SourceInfo {
+            parent,
+            divisor,
+            integer_divisor,
+            frequency,
+        }
+    }
+}
+
+/// All known clocks along with their names.
+static CLOCKS: [(&str, &Clock); 33] = [
+    ("UART_A", &Clock::UART_A),
+    ("UART_B", &Clock::UART_B),
+    ("UART_C", &Clock::UART_C),
+    ("UART_D", &Clock::UART_D),
+    ("UART_APE", &Clock::UART_APE),
+    ("I2C_1", &Clock::I2C_1),
+    ("I2C_2", &Clock::I2C_2),
+    ("I2C_3", &Clock::I2C_3),
+    ("I2C_4", &Clock::I2C_4),
+    ("I2C_5", &Clock::I2C_5),
+    ("I2C_6", &Clock::I2C_6),
+    ("SE", &Clock::SE),
+    ("TZRAM", &Clock::TZRAM),
+    ("HOST1X", &Clock::HOST1X),
+    ("TSEC", &Clock::TSEC),
+    ("SOR_SAFE", &Clock::SOR_SAFE),
+    ("SOR0", &Clock::SOR0),
+    ("SOR1", &Clock::SOR1),
+    ("GPU", &Clock::GPU),
+    ("KFUSE", &Clock::KFUSE),
+    ("CL_DVFS", &Clock::CL_DVFS),
+    ("CORESIGHT", &Clock::CORESIGHT),
+    ("PWM", &Clock::PWM),
+    ("AHBDMA", &Clock::AHBDMA),
+    ("APBDMA", &Clock::APBDMA),
+    ("SDMMC1", &Clock::SDMMC1),
+    ("SDMMC2", &Clock::SDMMC2),
+    ("SDMMC3", &Clock::SDMMC3),
+    ("SDMMC4", &Clock::SDMMC4),
+    ("ACTMON", &Clock::ACTMON),
+    ("USB2_TRK", &Clock::USB2_TRK),
+    ("APE", &Clock::APE),
+    ("I2S_1", &Clock::I2S_1),
+];
+
+/// Looks up a known clock by its name, e.g. `"SDMMC1"`.
+pub fn find(name: &str) -> Option<&'static Clock> {
+    CLOCKS
+        .iter()
+        .find(|(clock_name, _)| clock_name.eq_ignore_ascii_case(name))
+        .map(|&(_, clock)| clock)
+}
+
+/// Writes the state and decoded source configuration
+/// of all known clocks to the given writer.
+pub fn dump<W: fmt::Write>(writer: &mut W, rates: &ParentRates) -> fmt::Result {
+    for (name, clock) in CLOCKS.iter() {
+        let state = if clock.is_enabled() { "on" } else { "off" };
+
+        match clock.read_source() {
+            Some(value) => writeln!(
+                writer,
+                "{:<10} {:<3} {:08X} {}",
+                name,
+                state,
+                value,
+                clock.decode_source(value, rates)
+            )?,
+            None => writeln!(writer, "{:<10} {:<3}", name, state)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+    use crate::timer;
+
+    const RST_DEVICES_L: usize = (CLOCK_BASE + CLK_RST_CONTROLLER_RST_DEVICES_L) as usize;
+    const CLK_OUT_ENB_L: usize = (CLOCK_BASE + CLK_RST_CONTROLLER_CLK_OUT_ENB_L) as usize;
+
+    /// Tests the reset and enable sequence of a clock.
+    #[test]
+    fn enable_clock() {
+        let _session = mock::start();
+        timer::mock_counter();
+
+        mock::set(RST_DEVICES_L, 0x1);
+        mock::set(CLK_OUT_ENB_L, 0x1 | (1 << 6));
+
+        // The clock is disabled and put into reset first.
+        mock::expect_write(RST_DEVICES_L, 0x1 | (1 << 6));
+        mock::expect_write(CLK_OUT_ENB_L, 0x1);
+        // Then it is enabled and taken out of reset.
+        mock::expect_write(CLK_OUT_ENB_L, 0x1 | (1 << 6));
+        mock::expect_write(RST_DEVICES_L, 0x1);
+
+        let mut clock = Clock::UART_A;
+        clock.enable();
+
+        assert!(clock.is_enabled());
+        // The default source and divisor are programmed as well.
+        assert_eq!(
+            mock::writes((CLOCK_BASE + CLK_RST_CONTROLLER_CLK_SOURCE_UART_A) as usize),
+            [0]
+        );
+    }
+
+    /// Tests that a non-zero default source is programmed and the clock can be disabled again.
+    #[test]
+    fn enable_clock_with_source() {
+        let _session = mock::start();
+        timer::mock_counter();
+
+        mock::expect_write(
+            (CLOCK_BASE + CLK_RST_CONTROLLER_CLK_SOURCE_I2C_1) as usize,
+            6 << 29,
+        );
+
+        let mut clock = Clock::I2C_1;
+        clock.enable();
+
+        assert!(clock.is_enabled());
+        assert_eq!(mock::get(RST_DEVICES_L) & (1 << 0xC), 0);
+
+        clock.disable();
+
+        assert!(!clock.is_enabled());
+        assert_ne!(mock::get(RST_DEVICES_L) & (1 << 0xC), 0);
+    }
+}
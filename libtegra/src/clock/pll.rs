@@ -0,0 +1,394 @@
+//! Phase-Locked Loop configuration.
+//!
+//! # Description
+//!
+//! The CAR derives most clocks of the SoC from a set of PLLs, which
+//! multiply the oscillator frequency. Every PLL is configured through an
+//! input divider (M), a feedback divider (N) and a post divider (P), and
+//! has to be locked before its output can be used.
+//!
+//! Several PLLs additionally have to be taken out of IDDQ (power-down)
+//! before they are enabled and need their lock detection enabled.
+//!
+//! # Implementation
+//!
+//! - [`Pll`] represents the PLLs that can be configured through this
+//! module: PLLC, PLLC4, PLLD, PLLD2, PLLDP, PLLX and PLLE.
+//!
+//! - [`PllConfig`] holds the divider values for a PLL. The range of the
+//! dividers depends on the PLL, [`Pll::configure`] rejects values which
+//! don't fit.
+//!
+//! - [`Pll::configure`] powers up the PLL, programs the dividers, enables
+//! it and waits for it to lock. [`Pll::wait_for_lock`] only does the
+//! latter, for PLLs that were configured through other means.
+//!
+//! - [`Pll::enable_spread_spectrum`] sets up spread-spectrum clocking of
+//! PLLE, which is required for PCIe, SATA and XUSB.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::clock::pll::{Pll, PllConfig};
+//!
+//! fn main() {
+//!     // Bring up PLLX with M = 2, N = 78, P = 4.
+//!     Pll::X.configure(&PllConfig::new(2, 78, 4)).unwrap();
+//! }
+//! ```
+//!
+//! [`Pll`]: enum.Pll.html
+//! [`PllConfig`]: struct.PllConfig.html
+//! [`Pll::configure`]: enum.Pll.html#method.configure
+//! [`Pll::wait_for_lock`]: enum.Pll.html#method.wait_for_lock
+//! [`Pll::enable_spread_spectrum`]: enum.Pll.html#method.enable_spread_spectrum
+
+use mirage_mmio::{register_bitfields, Mmio, VolatileStorage};
+
+use super::Car;
+use crate::{
+    timer::{usleep, wait_for},
+    warn,
+};
+
+/// The default time to wait for a PLL to lock in microseconds.
+pub const PLL_LOCK_TIMEOUT: u32 = 1000;
+
+/// Enables most PLLs.
+const PLL_BASE_ENABLE: u32 = 1 << 30;
+/// Bypasses most PLLs.
+const PLL_BASE_BYPASS: u32 = 1 << 31;
+/// Indicates that most PLLs are locked.
+const PLL_BASE_LOCK: u32 = 1 << 27;
+
+/// Enables PLLE.
+const PLLE_BASE_ENABLE: u32 = 1 << 31;
+/// Indicates that PLLE is locked.
+const PLLE_MISC_LOCK: u32 = 1 << 11;
+/// Enables the PLLE lock detection.
+const PLLE_MISC_LOCK_ENABLE: u32 = 1 << 9;
+/// The software-controlled IDDQ value of PLLE.
+const PLLE_MISC_IDDQ_SW_VALUE: u32 = 1 << 13;
+/// Puts the PLLE IDDQ under software control.
+const PLLE_MISC_IDDQ_SW_CTRL: u32 = 1 << 14;
+
+/// Bypasses the spread-spectrum controller of PLLE.
+const PLLE_SS_CNTL_SSC_BYP: u32 = 1 << 12;
+/// Resets the spread-spectrum interpolator of PLLE.
+const PLLE_SS_CNTL_INTERP_RESET: u32 = 1 << 11;
+/// Bypasses spread-spectrum clocking of PLLE.
+const PLLE_SS_CNTL_BYPASS_SS: u32 = 1 << 10;
+
+register_bitfields! {
+    u32,
+    /// The `CLK_RST_CONTROLLER_PLLE_SS_CNTL_0` register.
+    PLLE_SS_CNTL [
+        /// The maximum frequency deviation.
+        SSC_MAX OFFSET(0) NUMBITS(9),
+        /// The increment per step.
+        SSC_INC OFFSET(16) NUMBITS(8),
+        /// The interval between steps.
+        SSC_INCINTRV OFFSET(24) NUMBITS(6),
+    ]
+}
+
+/// Enumeration of possible errors when configuring a PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The configuration is not supported by the PLL.
+    InvalidConfig,
+    /// The PLL didn't lock in time.
+    LockTimeout,
+}
+
+/// The divider configuration of a PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PllConfig {
+    /// The input divider.
+    pub divm: u32,
+    /// The feedback divider.
+    pub divn: u32,
+    /// The raw post divider value.
+    pub divp: u32,
+}
+
+impl PllConfig {
+    /// Creates a new PLL configuration.
+    pub const fn new(divm: u32, divn: u32, divp: u32) -> Self {
+        PllConfig { divm, divn, divp }
+    }
+}
+
+/// The spread-spectrum parameters of PLLE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpreadSpectrum {
+    /// The maximum frequency deviation.
+    pub max: u32,
+    /// The step size of the modulation.
+    pub increment: u32,
+    /// The interval between two steps.
+    pub interval: u32,
+}
+
+impl SpreadSpectrum {
+    /// The parameters recommended for Tegra210.
+    pub const TEGRA210: Self = SpreadSpectrum {
+        max: 0x21,
+        increment: 0x1,
+        interval: 0x23,
+    };
+}
+
+/// Representation of a PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pll {
+    /// PLLC, a general-purpose PLL.
+    C,
+    /// PLLC4, which clocks the SDMMC controllers in high-speed modes.
+    C4,
+    /// PLLD, which clocks the DSI display.
+    D,
+    /// PLLD2, which clocks the HDMI/DP display.
+    D2,
+    /// PLLDP, which clocks the DisplayPort link.
+    DP,
+    /// PLLX, which clocks the CPU cluster.
+    X,
+    /// PLLE, which clocks PCIe, SATA and XUSB.
+    E,
+}
+
+/// Sets or clears a mask in a register.
+fn update(register: &Mmio<u32>, mask: u32, set: bool) {
+    let value = register.read();
+
+    if set {
+        register.write(value | mask);
+    } else {
+        register.write(value & !mask);
+    }
+}
+
+impl Pll {
+    /// Gets the base register of the PLL.
+    fn base(&self) -> &'static Mmio<u32> {
+        let car = unsafe { Car::get() };
+
+        match self {
+            Pll::C => &car.pllc_base,
+            Pll::C4 => &car.pllc4_base,
+            Pll::D => &car.plld_base,
+            Pll::D2 => &car.plld2_base,
+            Pll::DP => &car.plldp_base,
+            Pll::X => &car.pllx_base,
+            Pll::E => &car.plle_base,
+        }
+    }
+
+    /// Gets the misc register holding the lock enable bit of the PLL.
+    fn misc(&self) -> &'static Mmio<u32> {
+        let car = unsafe { Car::get() };
+
+        match self {
+            Pll::C => &car.pllc_misc0,
+            Pll::C4 => &car.pllc4_misc,
+            Pll::D => &car.plld_misc2,
+            Pll::D2 => &car.plld2_misc,
+            Pll::DP => &car.plldp_misc,
+            Pll::X => &car.pllx_misc,
+            Pll::E => &car.plle_misc,
+        }
+    }
+
+    /// Gets the shift of the N divider and the widths of the N and P dividers.
+    fn divider_layout(&self) -> (u32, u32, u32) {
+        match self {
+            Pll::C => (10, 8, 5),
+            Pll::C4 => (8, 8, 5),
+            Pll::D => (11, 8, 3),
+            Pll::D2 | Pll::DP => (8, 8, 5),
+            Pll::X => (8, 8, 5),
+            Pll::E => (8, 8, 6),
+        }
+    }
+
+    /// Gets the shift of the P divider.
+    fn divp_shift(&self) -> u32 {
+        match self {
+            Pll::C4 | Pll::D2 | Pll::DP => 19,
+            Pll::E => 16,
+            _ => 20,
+        }
+    }
+
+    /// Gets the lock enable bit in the misc register of the PLL.
+    fn lock_enable(&self) -> u32 {
+        match self {
+            Pll::C => 1 << 24,
+            Pll::C4 => 1 << 30,
+            Pll::D => 1 << 18,
+            Pll::D2 | Pll::DP => 1 << 30,
+            Pll::X => 1 << 18,
+            Pll::E => PLLE_MISC_LOCK_ENABLE,
+        }
+    }
+
+    /// Gets the enable bit of the PLL.
+    fn enable_mask(&self) -> u32 {
+        match self {
+            Pll::E => PLLE_BASE_ENABLE,
+            _ => PLL_BASE_ENABLE,
+        }
+    }
+
+    /// Powers the PLL up or down.
+    fn set_iddq(&self, power_down: bool) {
+        let car = unsafe { Car::get() };
+
+        match self {
+            Pll::C => update(&car.pllc_misc1, 1 << 27, power_down),
+            Pll::C4 => update(&car.pllc4_base, 1 << 18, power_down),
+            Pll::D => update(&car.plld_misc2, 1 << 20, power_down),
+            Pll::D2 => update(&car.plld2_base, 1 << 18, power_down),
+            Pll::DP => update(&car.plldp_base, 1 << 18, power_down),
+            Pll::X => update(&car.pllx_misc3, 1 << 3, power_down),
+            Pll::E => {
+                update(&car.plle_misc, PLLE_MISC_IDDQ_SW_CTRL, true);
+                update(&car.plle_misc, PLLE_MISC_IDDQ_SW_VALUE, power_down);
+            }
+        }
+    }
+
+    /// Whether the PLL is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.base().read() & self.enable_mask() != 0
+    }
+
+    /// Whether the PLL is locked.
+    pub fn is_locked(&self) -> bool {
+        match self {
+            Pll::E => self.misc().read() & PLLE_MISC_LOCK != 0,
+            _ => self.base().read() & PLL_BASE_LOCK != 0,
+        }
+    }
+
+    /// Reads the current divider configuration of the PLL.
+    pub fn read_config(&self) -> PllConfig {
+        let (divn_shift, divn_width, divp_width) = self.divider_layout();
+        let value = self.base().read();
+
+        PllConfig {
+            divm: value & 0xFF,
+            divn: (value >> divn_shift) & ((1 << divn_width) - 1),
+            divp: (value >> self.divp_shift()) & ((1 << divp_width) - 1),
+        }
+    }
+
+    /// Waits for the PLL to lock for at most `timeout` microseconds.
+    pub fn wait_for_lock(&self, timeout: u32) -> Result<(), Error> {
+        wait_for(|| self.is_locked(), timeout).map_err(|_| {
+            warn!("PLL{:?} failed to lock within {}us", self, timeout);
+            Error::LockTimeout
+        })
+    }
+
+    /// Configures the PLL with the given dividers, enables it and
+    /// waits for it to lock.
+    pub fn configure(&self, config: &PllConfig) -> Result<(), Error> {
+        let (divn_shift, divn_width, divp_width) = self.divider_layout();
+        let divp_shift = self.divp_shift();
+
+        if config.divm == 0
+            || config.divm > 0xFF
+            || config.divn >> divn_width != 0
+            || config.divp >> divp_width != 0
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        let base = self.base();
+        let enable = self.enable_mask();
+
+        // Disable the PLL, keeping the bits outside of the dividers.
+        let divider_mask = 0xFF
+            | (((1 << divn_width) - 1) << divn_shift)
+            | (((1 << divp_width) - 1) << divp_shift);
+        let preserved = base.read() & !(divider_mask | enable | PLL_BASE_BYPASS);
+        let value = preserved
+            | config.divm
+            | (config.divn << divn_shift)
+            | (config.divp << divp_shift);
+
+        // Power up the PLL.
+        self.set_iddq(false);
+        usleep(2);
+
+        // Program the dividers while the PLL is bypassed.
+        if *self != Pll::E {
+            base.write(value | PLL_BASE_BYPASS);
+        }
+        base.write(value);
+
+        // Enable lock detection and the PLL itself.
+        update(self.misc(), self.lock_enable(), true);
+        base.write(value | enable);
+
+        self.wait_for_lock(PLL_LOCK_TIMEOUT)
+    }
+
+    /// Disables the PLL and powers it down.
+    pub fn disable(&self) {
+        update(self.base(), self.enable_mask(), false);
+        self.set_iddq(true);
+    }
+
+    /// Enables spread-spectrum clocking with the given parameters.
+    ///
+    /// Only PLLE supports spread-spectrum clocking through this interface.
+    /// It must be locked already.
+    pub fn enable_spread_spectrum(&self, parameters: &SpreadSpectrum) -> Result<(), Error> {
+        if *self != Pll::E
+            || parameters.max > 0x1FF
+            || parameters.increment > 0xFF
+            || parameters.interval > 0x3F
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        let car = unsafe { Car::get() };
+        let ss_cntl = &car.plle_ss_cntl;
+
+        // Program the coefficients.
+        let mut value = PLLE_SS_CNTL::SSC_MAX.set(ss_cntl.read(), parameters.max);
+        value = PLLE_SS_CNTL::SSC_INC.set(value, parameters.increment);
+        value = PLLE_SS_CNTL::SSC_INCINTRV.set(value, parameters.interval);
+        ss_cntl.write(value);
+
+        // Enable the spread-spectrum controller.
+        value &= !(PLLE_SS_CNTL_SSC_BYP | PLLE_SS_CNTL_BYPASS_SS);
+        ss_cntl.write(value);
+        usleep(1);
+
+        // Release the interpolator from reset.
+        value &= !PLLE_SS_CNTL_INTERP_RESET;
+        ss_cntl.write(value);
+        usleep(1);
+
+        Ok(())
+    }
+
+    /// Disables spread-spectrum clocking.
+    pub fn disable_spread_spectrum(&self) {
+        if *self != Pll::E {
+            return;
+        }
+
+        let car = unsafe { Car::get() };
+
+        update(
+            &car.plle_ss_cntl,
+            PLLE_SS_CNTL_SSC_BYP | PLLE_SS_CNTL_INTERP_RESET | PLLE_SS_CNTL_BYPASS_SS,
+            true,
+        );
+    }
+}
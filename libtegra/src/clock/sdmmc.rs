@@ -0,0 +1,374 @@
+//! Clock, pad and trim sequences for the SDMMC controllers.
+//!
+//! # Description
+//!
+//! Every speed mode of SD cards and eMMC requires a specific rate of
+//! the controller clock, which is derived from a parent clock in the CAR
+//! and then divided down by the SDHCI clock divider to the card clock.
+//!
+//! Up to SDR104, all rates are derived from PLLP. HS200 and HS400 run
+//! from PLLC4_OUT2 instead, as PLLP can't provide a rate close enough to
+//! 200MHz. Higher modes further need stronger pad drivers and tuned
+//! trimmer and tap values to sample the data lines reliably.
+//!
+//! # Implementation
+//!
+//! - [`Controller`] represents the four SDMMC controllers and gives
+//! access to their [`Clock`].
+//!
+//! - [`BusMode`] enumerates the supported speed modes, each of which maps
+//! to a [`ClockSetting`] through [`BusMode::clock_setting`].
+//!
+//! - [`Controller::set_bus_mode`] reclocks a controller for a speed mode,
+//! bringing up PLLC4 first if needed, and returns the resulting controller
+//! clock rate. The card clock is this rate divided by
+//! [`ClockSetting::sdclk_divider`], which has to be programmed into the
+//! SDHCI clock control register by the driver.
+//!
+//! - [`Controller::configure_pads`] sets the pad drive strengths for the
+//! bus voltage and [`Controller::configure_trim`] applies the trimmer,
+//! tap and DQS trim values of a speed mode.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::clock::sdmmc::{BusMode, Controller, Voltage};
+//!
+//! fn main() {
+//!     let emmc = Controller::Sdmmc4;
+//!
+//!     emmc.configure_pads(Voltage::V1_8).unwrap();
+//!     emmc.configure_trim(BusMode::Hs400);
+//!     let rate = emmc.set_bus_mode(BusMode::Hs400).unwrap();
+//! }
+//! ```
+//!
+//! [`Controller`]: enum.Controller.html
+//! [`Clock`]: ../struct.Clock.html
+//! [`BusMode`]: enum.BusMode.html
+//! [`ClockSetting`]: struct.ClockSetting.html
+//! [`BusMode::clock_setting`]: enum.BusMode.html#method.clock_setting
+//! [`Controller::set_bus_mode`]: enum.Controller.html#method.set_bus_mode
+//! [`ClockSetting::sdclk_divider`]: struct.ClockSetting.html#structfield.sdclk_divider
+//! [`Controller::configure_pads`]: enum.Controller.html#method.configure_pads
+//! [`Controller::configure_trim`]: enum.Controller.html#method.configure_trim
+
+use mirage_mmio::{register_bitfields, Mmio};
+
+use super::{
+    pll::{self, Pll, PllConfig},
+    Clock, ClockParent,
+};
+use crate::{
+    apb_misc::{PadDrive, PadGroup},
+    debug,
+    timer::usleep,
+};
+
+/// Base address for SDMMC registers.
+const SDMMC_BASE: u32 = 0x700B_0000;
+
+/// The offset of the vendor clock control register.
+const SDMMC_VENDOR_CLOCK_CNTRL: u32 = 0x100;
+/// The offset of the vendor capability overrides register.
+const SDMMC_VENDOR_CAP_OVERRIDES: u32 = 0x10C;
+
+/// The DQS trimmer value used in HS400.
+const DQS_TRIM_HS400: u32 = 0x28;
+
+register_bitfields! {
+    u32,
+    /// The `SDMMC_VENDOR_CLOCK_CNTRL_0` register.
+    VENDOR_CLOCK_CNTRL [
+        /// The outbound tap delay.
+        TAP_VAL OFFSET(16) NUMBITS(8),
+        /// The inbound trimmer delay.
+        TRIM_VAL OFFSET(24) NUMBITS(8),
+    ],
+    /// The `SDMMC_VENDOR_CAP_OVERRIDES_0` register.
+    VENDOR_CAP_OVERRIDES [
+        /// The DQS trimmer delay for HS400.
+        DQS_TRIM_VAL OFFSET(8) NUMBITS(6),
+    ],
+}
+
+/// The PLLC4 configuration for a 998.4MHz VCO from a 38.4MHz reference.
+///
+/// This puts PLLC4_OUT2 at 199.68MHz, which clocks HS200 and HS400.
+pub const PLLC4_CONFIG: PllConfig = PllConfig::new(2, 52, 0);
+
+/// Enumeration of possible errors when configuring a SDMMC controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The controller doesn't support the requested mode or voltage.
+    Unsupported,
+    /// PLLC4 failed to come up.
+    Pll(pll::Error),
+}
+
+impl From<pll::Error> for Error {
+    fn from(error: pll::Error) -> Self {
+        Error::Pll(error)
+    }
+}
+
+/// The I/O voltage of a SDMMC bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Voltage {
+    /// 1.8V signaling, used by eMMC and UHS-I SD cards.
+    V1_8,
+    /// 3.3V signaling, used by SD cards before switching to UHS-I.
+    V3_3,
+}
+
+/// The speed modes of a SDMMC bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusMode {
+    /// Card identification at up to 400kHz.
+    Identification,
+    /// SD default speed or UHS-I SDR12 at up to 25MHz.
+    SdDefault,
+    /// eMMC legacy speed at up to 26MHz.
+    MmcLegacy,
+    /// SD high speed or UHS-I SDR25 at up to 50MHz.
+    SdHighSpeed,
+    /// eMMC high speed at up to 52MHz.
+    MmcHighSpeed,
+    /// UHS-I SDR50 at up to 100MHz.
+    Sdr50,
+    /// UHS-I DDR50 at up to 50MHz.
+    Ddr50,
+    /// UHS-I SDR104 at up to 208MHz.
+    Sdr104,
+    /// eMMC HS200 at up to 200MHz.
+    Hs200,
+    /// eMMC HS400 at up to 200MHz with data on both edges.
+    Hs400,
+}
+
+/// The clock configuration for a [`BusMode`].
+///
+/// [`BusMode`]: enum.BusMode.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockSetting {
+    /// The parent clock of the controller.
+    pub parent: ClockParent,
+    /// The raw CAR divisor in 7.1 fixed-point format.
+    pub divisor: u32,
+    /// The divisor of the card clock from the controller clock.
+    pub sdclk_divider: u32,
+}
+
+impl ClockSetting {
+    /// Creates a new clock setting.
+    const fn new(parent: ClockParent, divisor: u32, sdclk_divider: u32) -> Self {
+        ClockSetting {
+            parent,
+            divisor,
+            sdclk_divider,
+        }
+    }
+}
+
+impl BusMode {
+    /// Gets the maximum card clock frequency of the mode in Hz.
+    pub fn max_frequency(&self) -> u32 {
+        match self {
+            BusMode::Identification => 400_000,
+            BusMode::SdDefault => 25_000_000,
+            BusMode::MmcLegacy => 26_000_000,
+            BusMode::SdHighSpeed | BusMode::Ddr50 => 50_000_000,
+            BusMode::MmcHighSpeed => 52_000_000,
+            BusMode::Sdr50 => 100_000_000,
+            BusMode::Sdr104 => 208_000_000,
+            BusMode::Hs200 | BusMode::Hs400 => 200_000_000,
+        }
+    }
+
+    /// Gets the clock configuration of the mode.
+    pub fn clock_setting(&self) -> ClockSetting {
+        match self {
+            // 25.5MHz / 66 = 386kHz.
+            BusMode::Identification => ClockSetting::new(ClockParent::PllP, 30, 66),
+            // 408MHz / 16.5 = 24.7MHz.
+            BusMode::SdDefault => ClockSetting::new(ClockParent::PllP, 31, 1),
+            // 408MHz / 16 = 25.5MHz.
+            BusMode::MmcLegacy => ClockSetting::new(ClockParent::PllP, 30, 1),
+            // 408MHz / 8.5 = 48MHz.
+            BusMode::SdHighSpeed => ClockSetting::new(ClockParent::PllP, 15, 1),
+            // 408MHz / 8 = 51MHz.
+            BusMode::MmcHighSpeed => ClockSetting::new(ClockParent::PllP, 14, 1),
+            // 408MHz / 4.5 = 90.7MHz.
+            BusMode::Sdr50 => ClockSetting::new(ClockParent::PllP, 7, 1),
+            // 408MHz / 10 = 40.8MHz.
+            BusMode::Ddr50 => ClockSetting::new(ClockParent::PllP, 18, 1),
+            // 408MHz / 2 = 204MHz.
+            BusMode::Sdr104 => ClockSetting::new(ClockParent::PllP, 2, 1),
+            // 998.4MHz / 5 = 199.7MHz.
+            BusMode::Hs200 | BusMode::Hs400 => ClockSetting::new(ClockParent::PllC4Out2, 0, 1),
+        }
+    }
+
+    /// Whether the mode requires 1.8V signaling.
+    pub fn requires_1v8(&self) -> bool {
+        match self {
+            BusMode::Sdr50 | BusMode::Ddr50 | BusMode::Sdr104 | BusMode::Hs200 | BusMode::Hs400 => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Representation of a SDMMC controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Controller {
+    /// SDMMC1, connected to the SD card slot.
+    Sdmmc1 = 0,
+    /// SDMMC2.
+    Sdmmc2,
+    /// SDMMC3.
+    Sdmmc3,
+    /// SDMMC4, connected to the eMMC.
+    Sdmmc4,
+}
+
+impl Controller {
+    /// Gets the device clock of the controller.
+    pub fn clock(&self) -> Clock {
+        match self {
+            Controller::Sdmmc1 => Clock::SDMMC1,
+            Controller::Sdmmc2 => Clock::SDMMC2,
+            Controller::Sdmmc3 => Clock::SDMMC3,
+            Controller::Sdmmc4 => Clock::SDMMC4,
+        }
+    }
+
+    /// Whether the controller supports the given speed mode.
+    ///
+    /// HS400 is only wired up for SDMMC2 and SDMMC4, which have a DLL.
+    pub fn supports(&self, mode: BusMode) -> bool {
+        match (self, mode) {
+            (Controller::Sdmmc1, BusMode::Hs400) | (Controller::Sdmmc3, BusMode::Hs400) => false,
+            _ => true,
+        }
+    }
+
+    /// Gets a vendor register of the controller.
+    fn vendor_register(&self, offset: u32) -> &'static Mmio<u32> {
+        unsafe { &*((SDMMC_BASE + *self as u32 * 0x200 + offset) as *const Mmio<u32>) }
+    }
+
+    /// Reclocks the controller for the given speed mode and returns the
+    /// resulting controller clock rate in Hz.
+    ///
+    /// PLLC4 is brought up first if the mode requires it. The clock is
+    /// gated while its source is changed and ungated again afterwards,
+    /// if it was running before.
+    pub fn set_bus_mode(&self, mode: BusMode) -> Result<u32, Error> {
+        if !self.supports(mode) {
+            return Err(Error::Unsupported);
+        }
+
+        let setting = mode.clock_setting();
+
+        match setting.parent {
+            ClockParent::PllC4Out0 | ClockParent::PllC4Out1 | ClockParent::PllC4Out2 => {
+                enable_pllc4()?
+            }
+            _ => {}
+        }
+
+        let mut clock = self.clock();
+        let was_enabled = clock.is_enabled();
+
+        if was_enabled {
+            clock.set_enable(false);
+        }
+
+        clock
+            .set_source(setting.parent, setting.divisor)
+            .map_err(|_| Error::Unsupported)?;
+
+        if was_enabled {
+            clock.set_enable(true);
+        }
+
+        // Wait for the new rate to propagate.
+        usleep(2);
+
+        let frequency = clock.frequency();
+        debug!("{:?}: {:?} at {}Hz", self, mode, frequency);
+
+        Ok(frequency)
+    }
+
+    /// Sets the drive strengths of the controller pads for the given
+    /// bus voltage.
+    ///
+    /// These are the values to fall back on when auto calibration of the
+    /// pads fails. The eMMC controllers only support 1.8V.
+    pub fn configure_pads(&self, voltage: Voltage) -> Result<(), Error> {
+        let (pads, drvup, drvdn) = match (self, voltage) {
+            (Controller::Sdmmc1, Voltage::V3_3) => (PadGroup::Sdmmc1, 0x12, 0x12),
+            (Controller::Sdmmc1, Voltage::V1_8) => (PadGroup::Sdmmc1, 0x11, 0x15),
+            (Controller::Sdmmc3, Voltage::V3_3) => (PadGroup::Sdmmc3, 0x12, 0x12),
+            (Controller::Sdmmc3, Voltage::V1_8) => (PadGroup::Sdmmc3, 0x11, 0x15),
+            (Controller::Sdmmc2, Voltage::V1_8) => (PadGroup::Emmc2, 0x10, 0x10),
+            (Controller::Sdmmc4, Voltage::V1_8) => (PadGroup::Emmc4, 0x10, 0x10),
+            _ => return Err(Error::Unsupported),
+        };
+
+        let drive = PadDrive {
+            drvup,
+            drvdn,
+            ..pads.drive()
+        };
+        pads.set_drive(drive).map_err(|_| Error::Unsupported)
+    }
+
+    /// Gets the recommended trimmer and tap values of the controller.
+    fn trim_values(&self) -> (u32, u32) {
+        match self {
+            Controller::Sdmmc1 => (0x02, 0x04),
+            Controller::Sdmmc2 => (0x08, 0x00),
+            Controller::Sdmmc3 => (0x03, 0x03),
+            Controller::Sdmmc4 => (0x08, 0x00),
+        }
+    }
+
+    /// Applies the trimmer, tap and DQS trimmer values for the
+    /// given speed mode.
+    ///
+    /// The tap value is left untouched in HS200, SDR104 and HS400, where
+    /// it is determined by the tuning procedure instead.
+    pub fn configure_trim(&self, mode: BusMode) {
+        let clock_cntrl = self.vendor_register(SDMMC_VENDOR_CLOCK_CNTRL);
+        let (trim, tap) = self.trim_values();
+
+        clock_cntrl.modify(|mut value| {
+            value = VENDOR_CLOCK_CNTRL::TRIM_VAL.set(value, trim);
+
+            match mode {
+                BusMode::Sdr104 | BusMode::Hs200 | BusMode::Hs400 => value,
+                _ => VENDOR_CLOCK_CNTRL::TAP_VAL.set(value, tap),
+            }
+        });
+
+        if mode == BusMode::Hs400 {
+            self.vendor_register(SDMMC_VENDOR_CAP_OVERRIDES)
+                .write_field(VENDOR_CAP_OVERRIDES::DQS_TRIM_VAL, DQS_TRIM_HS400);
+        }
+    }
+}
+
+/// Brings up PLLC4 with [`PLLC4_CONFIG`], unless it is running already.
+///
+/// [`PLLC4_CONFIG`]: constant.PLLC4_CONFIG.html
+pub fn enable_pllc4() -> Result<(), pll::Error> {
+    if Pll::C4.is_enabled() && Pll::C4.is_locked() {
+        return Ok(());
+    }
+
+    Pll::C4.configure(&PLLC4_CONFIG)
+}
@@ -0,0 +1,480 @@
+//! Audit of the enabled clocks and asserted resets.
+//!
+//! # Description
+//!
+//! Every device clock is gated by a bit in one of the `CLK_OUT_ENB_*`
+//! banks and held in reset by the same bit in the matching `RST_DEVICES_*`
+//! bank. Before chainloading, all resources Mirage brought up should be
+//! cleaned up again, so the next stage finds the SoC in a bootrom-like
+//! state. Comparing the gating and reset state of all devices at two
+//! points in time verifies that this is actually the case.
+//!
+//! # Implementation
+//!
+//! - [`snapshot`] reads all banks into a [`ClockState`].
+//!
+//! - [`ClockState::enabled`] and [`ClockState::in_reset`] iterate over
+//! the [`Device`]s whose clock is enabled or which are held in reset.
+//!
+//! - [`ClockState::diff`] compares two states, [`ClockDiff::changes`]
+//! lists the [`Change`]s between them.
+//!
+//! - A state also holds the second level clock gate overrides, PLLD and
+//! the clock sources the memory built-in self test workaround changes.
+//! [`ClockState::restore`] writes them back along with the clock enables,
+//! which undoes the workaround before chainloading.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::clock::{self, Clock};
+//!
+//! fn main() {
+//!     let before = clock::snapshot();
+//!
+//!     let mut clock = Clock::SE;
+//!     clock.enable();
+//!     clock.disable();
+//!
+//!     assert!(before.diff(&clock::snapshot()).is_empty());
+//! }
+//! ```
+//!
+//! [`snapshot`]: fn.snapshot.html
+//! [`ClockState`]: struct.ClockState.html
+//! [`ClockState::enabled`]: struct.ClockState.html#method.enabled
+//! [`ClockState::in_reset`]: struct.ClockState.html#method.in_reset
+//! [`Device`]: struct.Device.html
+//! [`ClockState::diff`]: struct.ClockState.html#method.diff
+//! [`ClockDiff::changes`]: struct.ClockDiff.html#method.changes
+//! [`Change`]: enum.Change.html
+//! [`ClockState::restore`]: struct.ClockState.html#method.restore
+
+use core::fmt;
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use super::{
+    Car, CLK_RST_CONTROLLER_CLK_OUT_ENB_H, CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+    CLK_RST_CONTROLLER_CLK_OUT_ENB_U, CLK_RST_CONTROLLER_CLK_OUT_ENB_V,
+    CLK_RST_CONTROLLER_CLK_OUT_ENB_W, CLK_RST_CONTROLLER_CLK_OUT_ENB_X,
+    CLK_RST_CONTROLLER_CLK_OUT_ENB_Y, CLK_RST_CONTROLLER_RST_DEVICES_H,
+    CLK_RST_CONTROLLER_RST_DEVICES_L, CLK_RST_CONTROLLER_RST_DEVICES_U,
+    CLK_RST_CONTROLLER_RST_DEVICES_V, CLK_RST_CONTROLLER_RST_DEVICES_W,
+    CLK_RST_CONTROLLER_RST_DEVICES_X, CLK_RST_CONTROLLER_RST_DEVICES_Y, CLOCKS, CLOCK_BASE,
+};
+
+/// The number of `CLK_OUT_ENB_*` and `RST_DEVICES_*` banks.
+const BANK_COUNT: usize = 7;
+
+/// The banks of device clocks and resets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bank {
+    /// The `L` bank.
+    L,
+    /// The `H` bank.
+    H,
+    /// The `U` bank.
+    U,
+    /// The `V` bank.
+    V,
+    /// The `W` bank.
+    W,
+    /// The `X` bank.
+    X,
+    /// The `Y` bank.
+    Y,
+}
+
+impl Bank {
+    /// All banks, in the order of their device indices.
+    pub const ALL: [Bank; BANK_COUNT] = [
+        Bank::L, Bank::H, Bank::U, Bank::V, Bank::W, Bank::X, Bank::Y,
+    ];
+
+    /// Gets the offset of the `CLK_OUT_ENB_*` register of the bank.
+    fn enable_offset(self) -> u32 {
+        match self {
+            Bank::L => CLK_RST_CONTROLLER_CLK_OUT_ENB_L,
+            Bank::H => CLK_RST_CONTROLLER_CLK_OUT_ENB_H,
+            Bank::U => CLK_RST_CONTROLLER_CLK_OUT_ENB_U,
+            Bank::V => CLK_RST_CONTROLLER_CLK_OUT_ENB_V,
+            Bank::W => CLK_RST_CONTROLLER_CLK_OUT_ENB_W,
+            Bank::X => CLK_RST_CONTROLLER_CLK_OUT_ENB_X,
+            Bank::Y => CLK_RST_CONTROLLER_CLK_OUT_ENB_Y,
+        }
+    }
+
+    /// Gets the offset of the `RST_DEVICES_*` register of the bank.
+    fn reset_offset(self) -> u32 {
+        match self {
+            Bank::L => CLK_RST_CONTROLLER_RST_DEVICES_L,
+            Bank::H => CLK_RST_CONTROLLER_RST_DEVICES_H,
+            Bank::U => CLK_RST_CONTROLLER_RST_DEVICES_U,
+            Bank::V => CLK_RST_CONTROLLER_RST_DEVICES_V,
+            Bank::W => CLK_RST_CONTROLLER_RST_DEVICES_W,
+            Bank::X => CLK_RST_CONTROLLER_RST_DEVICES_X,
+            Bank::Y => CLK_RST_CONTROLLER_RST_DEVICES_Y,
+        }
+    }
+
+    /// Gets the name of the bank.
+    pub fn name(self) -> &'static str {
+        match self {
+            Bank::L => "L",
+            Bank::H => "H",
+            Bank::U => "U",
+            Bank::V => "V",
+            Bank::W => "W",
+            Bank::X => "X",
+            Bank::Y => "Y",
+        }
+    }
+}
+
+/// A device, identified by its bit in the clock and reset banks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Device {
+    /// The bank of the device.
+    pub bank: Bank,
+    /// The bit of the device within the bank.
+    pub bit: u8,
+}
+
+impl Device {
+    /// Gets the name of the device, if it is one of the known clocks.
+    pub fn name(&self) -> Option<&'static str> {
+        CLOCKS
+            .iter()
+            .find(|(_, clock)| {
+                clock.enable == self.bank.enable_offset() && clock.index & 0x1F == self.bit
+            })
+            .map(|&(name, _)| name)
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}{}", self.bank.name(), self.bit),
+        }
+    }
+}
+
+/// Gets the register at the given offset from the CAR base.
+fn register(offset: u32) -> &'static Mmio<u32> {
+    unsafe { &*((CLOCK_BASE + offset) as *const Mmio<u32>) }
+}
+
+/// Gets the `LVL2_CLK_GATE_OVR*` registers.
+fn lvl2_clk_gate_ovr_registers(car: &Car) -> [&Mmio<u32>; 5] {
+    [
+        &car.lvl2_clk_gate_ovra,
+        &car.lvl2_clk_gate_ovrb,
+        &car.lvl2_clk_gate_ovrc,
+        &car.lvl2_clk_gate_ovrd,
+        &car.lvl2_clk_gate_ovre,
+    ]
+}
+
+/// Gets the clock source registers changed by the memory built-in self
+/// test workaround.
+fn clk_source_registers(car: &Car) -> [&Mmio<u32>; 4] {
+    [
+        &car.clk_source_sor1,
+        &car.clk_source_vi,
+        &car.clk_source_host1x,
+        &car.clk_source_nvenc,
+    ]
+}
+
+/// The gating and reset state of all devices.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClockState {
+    /// The values of the `CLK_OUT_ENB_*` registers.
+    enabled: [u32; BANK_COUNT],
+    /// The values of the `RST_DEVICES_*` registers.
+    reset: [u32; BANK_COUNT],
+    /// The values of the `LVL2_CLK_GATE_OVR*` registers.
+    lvl2_clk_gate_ovr: [u32; 5],
+    /// The value of `PLLD_BASE`.
+    plld_base: u32,
+    /// The values of `CLK_SOURCE_SOR1`, `_VI`, `_HOST1X` and `_NVENC`.
+    clk_sources: [u32; 4],
+}
+
+impl ClockState {
+    /// Whether the clock of a device is enabled.
+    pub fn is_enabled(&self, device: Device) -> bool {
+        self.enabled[device.bank as usize] & (1 << device.bit) != 0
+    }
+
+    /// Whether a device is held in reset.
+    pub fn is_in_reset(&self, device: Device) -> bool {
+        self.reset[device.bank as usize] & (1 << device.bit) != 0
+    }
+
+    /// Iterates over the devices whose clock is enabled.
+    pub fn enabled(&self) -> Devices {
+        Devices::new(self.enabled)
+    }
+
+    /// Iterates over the devices which are held in reset.
+    pub fn in_reset(&self) -> Devices {
+        Devices::new(self.reset)
+    }
+
+    /// Compares this state with a later one.
+    pub fn diff(&self, other: &ClockState) -> ClockDiff {
+        ClockDiff {
+            before: *self,
+            after: *other,
+        }
+    }
+
+    /// Writes the clock configuration back.
+    ///
+    /// The clock sources and PLLD are restored before the clocks get
+    /// enabled. The resets are left alone, devices which have been brought
+    /// up since are taken care of by their drivers.
+    pub fn restore(&self) {
+        let car = unsafe { Car::get() };
+
+        car.plld_base.write(self.plld_base);
+        for (value, register) in self.clk_sources.iter().zip(&clk_source_registers(car)) {
+            register.write(*value);
+        }
+
+        for (value, register) in self
+            .lvl2_clk_gate_ovr
+            .iter()
+            .zip(&lvl2_clk_gate_ovr_registers(car))
+        {
+            register.write(*value);
+        }
+
+        for (value, bank) in self.enabled.iter().zip(Bank::ALL.iter()) {
+            register(bank.enable_offset()).write(*value);
+        }
+    }
+}
+
+/// Iterator over a set of devices, created by [`ClockState::enabled`] and
+/// [`ClockState::in_reset`].
+///
+/// [`ClockState::enabled`]: struct.ClockState.html#method.enabled
+/// [`ClockState::in_reset`]: struct.ClockState.html#method.in_reset
+#[derive(Clone, Debug)]
+pub struct Devices {
+    /// The remaining devices of each bank.
+    banks: [u32; BANK_COUNT],
+    /// The index of the current bank.
+    bank: usize,
+}
+
+impl Devices {
+    fn new(banks: [u32; BANK_COUNT]) -> Self {
+        Devices { banks, bank: 0 }
+    }
+}
+
+impl Iterator for Devices {
+    type Item = Device;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bank < BANK_COUNT {
+            let remaining = &mut self.banks[self.bank];
+
+            if *remaining != 0 {
+                let bit = remaining.trailing_zeros() as u8;
+                *remaining &= *remaining - 1;
+
+                return Some(Device {
+                    bank: Bank::ALL[self.bank],
+                    bit,
+                });
+            }
+
+            self.bank += 1;
+        }
+
+        None
+    }
+}
+
+/// A change of a device between two [`ClockState`]s.
+///
+/// [`ClockState`]: struct.ClockState.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The clock of the device has been enabled.
+    Enabled(Device),
+    /// The clock of the device has been disabled.
+    Disabled(Device),
+    /// The device has been put into reset.
+    Reset(Device),
+    /// The device has been taken out of reset.
+    Released(Device),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Enabled(device) => write!(f, "{} enabled", device),
+            Change::Disabled(device) => write!(f, "{} disabled", device),
+            Change::Reset(device) => write!(f, "{} reset", device),
+            Change::Released(device) => write!(f, "{} released", device),
+        }
+    }
+}
+
+/// The differences between two [`ClockState`]s, created by
+/// [`ClockState::diff`].
+///
+/// [`ClockState`]: struct.ClockState.html
+/// [`ClockState::diff`]: struct.ClockState.html#method.diff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockDiff {
+    /// The earlier state.
+    before: ClockState,
+    /// The later state.
+    after: ClockState,
+}
+
+impl ClockDiff {
+    /// Whether the gating and reset state of all devices is the same.
+    pub fn is_empty(&self) -> bool {
+        self.before.enabled == self.after.enabled && self.before.reset == self.after.reset
+    }
+
+    /// Iterates over the devices whose clock gating or reset changed.
+    pub fn changes(&self) -> impl Iterator<Item = Change> {
+        let (before, after) = (self.before, self.after);
+        let changed = |old: [u32; BANK_COUNT], new: [u32; BANK_COUNT]| {
+            let mut banks = [0; BANK_COUNT];
+            for (bank, (old, new)) in banks.iter_mut().zip(old.iter().zip(new.iter())) {
+                *bank = old ^ new;
+            }
+            Devices::new(banks)
+        };
+
+        let clocks = changed(before.enabled, after.enabled).map(move |device| {
+            if after.is_enabled(device) {
+                Change::Enabled(device)
+            } else {
+                Change::Disabled(device)
+            }
+        });
+        let resets = changed(before.reset, after.reset).map(move |device| {
+            if after.is_in_reset(device) {
+                Change::Reset(device)
+            } else {
+                Change::Released(device)
+            }
+        });
+
+        clocks.chain(resets)
+    }
+}
+
+/// Reads the clock gating and reset state of all devices.
+pub fn snapshot() -> ClockState {
+    let car = unsafe { Car::get() };
+
+    let mut state = ClockState::default();
+    for (index, bank) in Bank::ALL.iter().enumerate() {
+        state.enabled[index] = register(bank.enable_offset()).read();
+        state.reset[index] = register(bank.reset_offset()).read();
+    }
+
+    for (value, register) in state
+        .lvl2_clk_gate_ovr
+        .iter_mut()
+        .zip(&lvl2_clk_gate_ovr_registers(car))
+    {
+        *value = register.read();
+    }
+
+    state.plld_base = car.plld_base.read();
+    for (value, register) in state.clk_sources.iter_mut().zip(&clk_source_registers(car)) {
+        *value = register.read();
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(bank: Bank, bit: u8) -> Device {
+        Device { bank, bit }
+    }
+
+    /// Tests iterating over the devices of a state.
+    #[test]
+    fn iterates_devices() {
+        let mut state = ClockState::default();
+        state.enabled[0] = (1 << 6) | (1 << 31);
+        state.enabled[6] = 1;
+
+        let mut enabled = state.enabled();
+        assert_eq!(enabled.next(), Some(device(Bank::L, 6)));
+        assert_eq!(enabled.next(), Some(device(Bank::L, 31)));
+        assert_eq!(enabled.next(), Some(device(Bank::Y, 0)));
+        assert_eq!(enabled.next(), None);
+        assert_eq!(state.in_reset().next(), None);
+
+        assert!(state.is_enabled(device(Bank::L, 6)));
+        assert!(!state.is_enabled(device(Bank::H, 6)));
+    }
+
+    /// Tests the changes between two states.
+    #[test]
+    fn diffs_states() {
+        let mut before = ClockState::default();
+        before.enabled[0] = 1 << 6;
+        before.reset[1] = 1 << 3;
+
+        let mut after = before;
+        assert!(before.diff(&after).is_empty());
+
+        after.enabled[0] = 1 << 7;
+        after.reset[1] = 0;
+
+        let diff = before.diff(&after);
+        let mut changes = diff.changes();
+        assert!(!diff.is_empty());
+        assert_eq!(changes.next(), Some(Change::Disabled(device(Bank::L, 6))));
+        assert_eq!(changes.next(), Some(Change::Enabled(device(Bank::L, 7))));
+        assert_eq!(changes.next(), Some(Change::Released(device(Bank::H, 3))));
+        assert_eq!(changes.next(), None);
+    }
+
+    /// Tests naming devices after the known clocks.
+    #[test]
+    fn names_devices() {
+        assert_eq!(device(Bank::L, 6).name(), Some("UART_A"));
+        assert_eq!(device(Bank::Y, 31).name(), None);
+    }
+
+    /// Tests reading a snapshot from mocked registers.
+    #[cfg(feature = "mock")]
+    #[test]
+    fn reads_snapshot() {
+        use mirage_mmio::mock;
+
+        let _session = mock::start();
+        mock::set((CLOCK_BASE + CLK_RST_CONTROLLER_CLK_OUT_ENB_V) as usize, 1 << 2);
+        mock::set((CLOCK_BASE + CLK_RST_CONTROLLER_RST_DEVICES_Y) as usize, 1 << 5);
+
+        let state = snapshot();
+        let mut enabled = state.enabled();
+        let mut in_reset = state.in_reset();
+        assert_eq!(enabled.next(), Some(device(Bank::V, 2)));
+        assert_eq!(enabled.next(), None);
+        assert_eq!(in_reset.next(), Some(device(Bank::Y, 5)));
+        assert_eq!(in_reset.next(), None);
+    }
+}
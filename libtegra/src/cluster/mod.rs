@@ -1,33 +1,60 @@
 //! Nvidia Tegra210 CPU cluster driver.
 
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{register_bitfields, Mmio, VolatileStorage};
 
 use crate::{
-    clock::{Car, Clock},
-    i2c::{Error, I2c, Device},
+    clock::{
+        pll::{Pll, PllConfig, PLL_LOCK_TIMEOUT},
+        Car,
+        Clock,
+    },
+    i2c::{I2cError, I2c, Device},
     pmc::Pmc,
     sysreg::{SbRegisters, EXCEPTION_VECTOR_BASE},
-    timer::usleep,
+    timer::wait_for,
 };
 
 /// Base address for Flow Control registers.
 pub(crate) const FLOW_CTLR_BASE: u32 = 0x6000_7000;
 
-fn try_enable_power() -> Result<(), Error> {
-    let value = I2c::C5.read_byte(Device::Max77620Pwr, 0x40)?;
+/// The MSELECT bit in the V clock enable and reset registers.
+const CLK_V_MSELECT: u32 = 1 << 3;
+
+register_bitfields! {
+    u32,
+    /// The `CLK_RST_CONTROLLER_CLK_SOURCE_MSELECT_0` register.
+    CLK_SOURCE_MSELECT [
+        /// The 7.1 divisor of the clock.
+        DIVISOR OFFSET(0) NUMBITS(8),
+        /// The parent clock, where 0 is PLLP_OUT0.
+        SOURCE OFFSET(29) NUMBITS(3),
+    ]
+}
+
+/// The time in microseconds a power partition may take to toggle.
+const PWRGATE_TIMEOUT: u32 = 5000;
+
+/// The time in microseconds the RAM repair may take.
+const RAM_REPAIR_TIMEOUT: u32 = 10_000;
+
+/// The initial PLLX configuration for booting the CPU.
+const PLLX_BOOT_CONFIG: PllConfig = PllConfig::new(2, 78, 4);
 
-    I2c::C5.write_byte(Device::Max77620Pwr, 0x40, value & 0xDF)?;
-    I2c::C5.write_byte(Device::Max77620Pwr, 0x3B, 0x9)?;
+fn try_enable_power(i2c: &I2c) -> Result<(), I2cError> {
+    let value = i2c.read_byte(Device::Max77620Pwr, 0x40)?;
+
+    i2c.write_byte(Device::Max77620Pwr, 0x40, value & 0xDF)?;
+    i2c.write_byte(Device::Max77620Pwr, 0x3B, 0x9)?;
 
     // Enable power.
-    I2c::C5.write_byte(Device::Max77621Cpu, 0x2, 0x20)?;
-    I2c::C5.write_byte(Device::Max77621Cpu, 0x3, 0x8D)?;
-    I2c::C5.write_byte(Device::Max77621Cpu, 0, 0xB7)?;
-    I2c::C5.write_byte(Device::Max77621Cpu, 0x1, 0xB7)
+    i2c.write_byte(Device::Max77621Cpu, 0x2, 0x20)?;
+    i2c.write_byte(Device::Max77621Cpu, 0x3, 0x8D)?;
+    i2c.write_byte(Device::Max77621Cpu, 0, 0xB7)?;
+    i2c.write_byte(Device::Max77621Cpu, 0x1, 0xB7)
 }
 
-fn enable_power() {
-    try_enable_power().unwrap();
+fn enable_power(i2c: &I2c) {
+    try_enable_power(i2c).unwrap();
 }
 
 fn enable_pmc_partition(partition: u32, toggle: u32) -> Result<(), ()> {
@@ -38,33 +65,20 @@ fn enable_pmc_partition(partition: u32, toggle: u32) -> Result<(), ()> {
         return Ok(());
     }
 
-    let mut i = 5001;
-    while pmc.pwrgate_toggle.read() & 0x100 != 0 {
-        usleep(1);
-        i -= 1;
-
-        if i < 1 {
-            return Err(());
-        }
-    }
+    wait_for(|| pmc.pwrgate_toggle.read() & 0x100 == 0, PWRGATE_TIMEOUT).map_err(|_| ())?;
 
     pmc.pwrgate_toggle.write(toggle | 0x100);
 
-    i = 5001;
-    while i > 0 {
-        if pmc.pwrgate_status.read() & partition != 0 {
-            break;
-        }
-
-        usleep(1);
-        i -= 1;
-    }
+    // The partition may take longer to report its state, carry on regardless.
+    wait_for(|| pmc.pwrgate_status.read() & partition != 0, PWRGATE_TIMEOUT).ok();
 
     Ok(())
 }
 
 /// Boots the CPU0 of the device.
-pub fn boot_cpu0(entry: u32) {
+///
+/// The CPU rail is powered up through the PMIC on `i2c`.
+pub fn boot_cpu0(i2c: &I2c, entry: u32) {
     let car = unsafe { Car::get() };
     let sb = unsafe { SbRegisters::get() };
 
@@ -77,36 +91,35 @@ pub fn boot_cpu0(entry: u32) {
     };
 
     // Set ACTIVE_CLUSTER to FAST.
-    bpmp_cluster_control.write(bpmp_cluster_control.read() & 0xFFFF_FFFE);
+    bpmp_cluster_control.set_bits(0..1, 0);
 
-    enable_power();
-
-    if car.pllx_base.read() & 0x4000_0000 == 0 {
-        car.pllx_misc3.write(car.pllx_misc3.read() & 0xFFFF_FFF7);
-        usleep(2);
-        car.pllx_base.write(0x8040_4E02);
-        car.pllx_base.write(0x404E02);
-        car.pllx_misc.write((car.pllx_misc.read() & 0xFFFB_FFFF) | 0x40000);
-        car.pllx_base.write(0x4040_4E02);
-    }
+    enable_power(i2c);
 
-    while car.pllx_base.read() & 0x8000000 == 0 {
-        // Wait.
-    }
+    // Bring up PLLX, unless it is running already.
+    let pllx = if Pll::X.is_enabled() {
+        Pll::X.wait_for_lock(PLL_LOCK_TIMEOUT)
+    } else {
+        Pll::X.configure(&PLLX_BOOT_CONFIG)
+    };
+    pllx.expect("PLLX failed to lock!");
 
     // Configure MSELECT source and enable clock.
-    car.clk_source_mselect.write((car.clk_source_mselect.read() & 0x1FFF_FF00) | 6);
-    car.clk_out_enb_v.write((car.clk_out_enb_v.read() & 0xFFFF_FFF7) | 8);
+    car.clk_source_mselect.modify(|v| {
+        let v = CLK_SOURCE_MSELECT::SOURCE.set(v, 0);
+        CLK_SOURCE_MSELECT::DIVISOR.set(v, 6)
+    });
+    car.clk_out_enb_v.modify(|v| v | CLK_V_MSELECT);
 
     // Configure initial CPU clock frequency and enable clock.
     car.cclk_brst_pol.write(0x2000_8888);
     car.super_cclk_div.write(0x8000_0000);
     car.clk_enb_v_set.write(1);
 
-    Clock::CORESIGHT.enable();
+    let mut coresight = Clock::CORESIGHT;
+    coresight.enable();
 
     // CAR2PMC_CPU_ACK_WIDTH should be set to 0.
-    car.cpu_softrst_ctrl2.write(car.cpu_softrst_ctrl2.read() & 0xFFFF_F000);
+    car.cpu_softrst_ctrl2.set_bits(0..12, 0);
 
     // Enable CPU rail.
     enable_pmc_partition(1, 0).unwrap();
@@ -119,9 +132,8 @@ pub fn boot_cpu0(entry: u32) {
 
     // Request and wait for RAM repair.
     ram_repair.write(1);
-    while ram_repair.read() & 2 == 0 {
-        // Wait.
-    }
+    wait_for(|| ram_repair.read() & 2 != 0, RAM_REPAIR_TIMEOUT)
+        .expect("RAM repair timed out!");
 
     unsafe {
         (*((EXCEPTION_VECTOR_BASE + 0x100) as *const Mmio<u32>)).write(0);
@@ -139,7 +151,7 @@ pub fn boot_cpu0(entry: u32) {
     // TODO(Vale): Should we do this?
 
     // Clear MSELECT reset.
-    car.rst_dev_v.write(car.rst_dev_v.read() & 0xFFFF_FFF7);
+    car.rst_dev_v.modify(|v| v & !CLK_V_MSELECT);
 
     // Clear NONCPU reset.
     car.rst_cpug_cmplx_clr.write(0x2000_0000);
@@ -0,0 +1,452 @@
+//! Crash records that survive a reboot.
+//!
+//! # Description
+//!
+//! Failures in the field often can't be reproduced with a debugger or a
+//! UART attached. When a panic or a fault occurs, this module saves a
+//! compact record of it to IRAM, which keeps its contents across a reboot.
+//! The next boot can then retrieve the record and show or upload it.
+//!
+//! A record consists of the cause, the registers at the time of a fault,
+//! the fault status of the BPMP MMU, the panic message and the latest log
+//! messages from the [`RingBuffer`].
+//!
+//! # Implementation
+//!
+//! - [`record_panic`] saves a record of a panic and is meant to be called
+//! from the panic handler. [`record_fault`] does the same for exceptions,
+//! given the registers saved by the exception handler.
+//!
+//! - [`take`] retrieves the record of the previous boot and clears it,
+//! [`read`] retrieves it without clearing and [`clear`] discards it.
+//!
+//! - The record is protected by a CRC32, which is also written to
+//! [`Scratch::CRASHLOG`]. As the PMC scratch registers are cleared on a
+//! power cycle, stale data in IRAM isn't mistaken for a new record.
+//!
+//! - [`CrashLog`] represents the storage of a record, so records can be
+//! kept at other locations as well.
+//!
+//! # Example
+//!
+//! ```
+//! use core::panic::PanicInfo;
+//!
+//! use mirage_libtegra::{crashlog, pmc};
+//!
+//! #[panic_handler]
+//! fn panic(info: &PanicInfo<'_>) -> ! {
+//!     crashlog::record_panic(info);
+//!     pmc::reboot_to_rcm()
+//! }
+//!
+//! fn main() {
+//!     if let Some(record) = crashlog::take() {
+//!         println!("Previous boot crashed: {}", record.message());
+//!     }
+//! }
+//! ```
+//!
+//! [`RingBuffer`]: ../log/struct.RingBuffer.html
+//! [`record_panic`]: fn.record_panic.html
+//! [`record_fault`]: fn.record_fault.html
+//! [`take`]: fn.take.html
+//! [`read`]: fn.read.html
+//! [`clear`]: fn.clear.html
+//! [`Scratch::CRASHLOG`]: ../pmc/struct.Scratch.html#associatedconstant.CRASHLOG
+//! [`CrashLog`]: struct.CrashLog.html
+
+use core::{
+    fmt::{self, Write},
+    mem::size_of,
+    panic::PanicInfo,
+    ptr, slice, str,
+};
+
+use crate::{arch, checksum::crc32, log::RingBuffer, pmc::Scratch, timer};
+
+/// The address of the crash record in upper IRAM, below the log buffer.
+pub const CRASHLOG_ADDRESS: u32 = 0x4003_C000;
+
+/// The size reserved for the crash record in bytes.
+pub const CRASHLOG_SIZE: u32 = 0x1000;
+
+/// The maximum length of a panic message.
+pub const MESSAGE_SIZE: usize = 0x100;
+
+/// The maximum amount of log bytes kept in a record.
+pub const LOG_SIZE: usize = 0x800;
+
+/// Identifies a saved crash record ("MCRS").
+const CRASH_MAGIC: u32 = 0x5352_434D;
+
+/// The cause of a crash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Cause {
+    /// A Rust panic.
+    Panic = 1,
+    /// An undefined instruction exception.
+    UndefinedInstruction = 2,
+    /// A prefetch abort exception.
+    PrefetchAbort = 3,
+    /// A data abort exception.
+    DataAbort = 4,
+}
+
+impl Cause {
+    /// Gets the cause for its raw value.
+    fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            1 => Some(Cause::Panic),
+            2 => Some(Cause::UndefinedInstruction),
+            3 => Some(Cause::PrefetchAbort),
+            4 => Some(Cause::DataAbort),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Cause::Panic => "panic",
+            Cause::UndefinedInstruction => "undefined instruction",
+            Cause::PrefetchAbort => "prefetch abort",
+            Cause::DataAbort => "data abort",
+        })
+    }
+}
+
+/// The registers of the program at the time of a fault.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Registers {
+    /// The address of the faulting instruction.
+    pub pc: u32,
+    /// The link register.
+    pub lr: u32,
+    /// The stack pointer.
+    pub sp: u32,
+    /// The program status register.
+    pub cpsr: u32,
+}
+
+/// A record of a crash.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct CrashRecord {
+    /// The raw [`Cause`].
+    ///
+    /// [`Cause`]: enum.Cause.html
+    cause: u32,
+    /// The registers at the time of a fault, zero for panics.
+    pub registers: Registers,
+    /// The `MMU_ABORT_STAT` register of the BPMP MMU.
+    pub fault_status: u32,
+    /// The `MMU_ABORT_ADDR` register of the BPMP MMU.
+    pub fault_address: u32,
+    /// The time since boot in microseconds.
+    pub timestamp: u32,
+    /// The length of `message`.
+    message_length: u32,
+    /// The length of `log`.
+    log_length: u32,
+    /// The panic message.
+    message: [u8; MESSAGE_SIZE],
+    /// The latest log messages.
+    log: [u8; LOG_SIZE],
+}
+
+impl CrashRecord {
+    /// Creates a record of a crash that is happening now.
+    ///
+    /// The fault status is read from the BPMP MMU and the latest messages
+    /// are copied from the log buffer in IRAM.
+    pub fn new(cause: Cause, registers: Registers) -> Self {
+        let (fault_status, fault_address) = arch::last_abort();
+
+        let mut record = CrashRecord {
+            cause: cause as u32,
+            registers,
+            fault_status,
+            fault_address,
+            timestamp: timer::get_microseconds(),
+            message_length: 0,
+            log_length: 0,
+            message: [0; MESSAGE_SIZE],
+            log: [0; LOG_SIZE],
+        };
+
+        let mut ring = RingBuffer::IRAM;
+        if ring.init() {
+            record.log_length = ring.read(&mut record.log) as u32;
+        }
+
+        record
+    }
+
+    /// Gets the cause of the crash.
+    pub fn cause(&self) -> Option<Cause> {
+        Cause::from_raw(self.cause)
+    }
+
+    /// Gets the panic message, which is truncated to [`MESSAGE_SIZE`].
+    ///
+    /// [`MESSAGE_SIZE`]: constant.MESSAGE_SIZE.html
+    pub fn message(&self) -> &str {
+        let message = &self.message[..self.message_length as usize];
+
+        // Truncation may have split a character.
+        match str::from_utf8(message) {
+            Ok(message) => message,
+            Err(error) => unsafe { str::from_utf8_unchecked(&message[..error.valid_up_to()]) },
+        }
+    }
+
+    /// Gets the latest log messages before the crash.
+    pub fn log(&self) -> &[u8] {
+        &self.log[..self.log_length as usize]
+    }
+
+    /// Checks that the lengths are within bounds, as the record might
+    /// come from corrupted memory.
+    fn is_valid(&self) -> bool {
+        self.cause().is_some()
+            && self.message_length as usize <= MESSAGE_SIZE
+            && self.log_length as usize <= LOG_SIZE
+    }
+
+    /// Gets the raw bytes of the record.
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+}
+
+impl fmt::Write for CrashRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let length = self.message_length as usize;
+        let count = s.len().min(MESSAGE_SIZE - length);
+
+        self.message[length..length + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.message_length += count as u32;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for CrashRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cause() {
+            Some(cause) => write!(f, "{}", cause)?,
+            None => write!(f, "unknown crash")?,
+        }
+        write!(f, " at {}us", self.timestamp)?;
+
+        if self.message_length != 0 {
+            write!(f, ": {}", self.message())?;
+        }
+
+        let registers = &self.registers;
+        write!(
+            f,
+            "\npc={:08X} lr={:08X} sp={:08X} cpsr={:08X}",
+            registers.pc, registers.lr, registers.sp, registers.cpsr
+        )?;
+        write!(
+            f,
+            "\nabort status={:08X} address={:08X}",
+            self.fault_status, self.fault_address
+        )
+    }
+}
+
+/// The header in front of a saved record.
+#[repr(C)]
+struct Header {
+    /// Always [`CRASH_MAGIC`] for a saved record.
+    magic: u32,
+    /// The CRC32 of the record.
+    checksum: u32,
+}
+
+/// The storage of a crash record in memory.
+pub struct CrashLog {
+    address: usize,
+    /// The scratch register holding a copy of the checksum.
+    scratch: Option<Scratch>,
+}
+
+impl CrashLog {
+    /// The crash record in upper IRAM, validated by [`Scratch::CRASHLOG`].
+    ///
+    /// [`Scratch::CRASHLOG`]: ../pmc/struct.Scratch.html#associatedconstant.CRASHLOG
+    pub const IRAM: Self = CrashLog {
+        address: CRASHLOG_ADDRESS as usize,
+        scratch: Some(Scratch::CRASHLOG),
+    };
+
+    /// Creates a crash log at the given location in memory, which is only
+    /// validated by its own checksum.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for [`CRASHLOG_SIZE`] bytes, aligned to 4
+    /// bytes and not be used for anything else.
+    ///
+    /// [`CRASHLOG_SIZE`]: constant.CRASHLOG_SIZE.html
+    pub const unsafe fn new(address: usize) -> Self {
+        CrashLog {
+            address,
+            scratch: None,
+        }
+    }
+
+    fn header(&self) -> *mut Header {
+        self.address as *mut Header
+    }
+
+    fn record(&self) -> *mut CrashRecord {
+        (self.address + size_of::<Header>()) as *mut CrashRecord
+    }
+
+    /// Saves a record, replacing any previous one.
+    pub fn save(&self, record: &CrashRecord) {
+        let checksum = crc32(record.as_bytes());
+
+        unsafe {
+            ptr::write_volatile(self.record(), *record);
+            ptr::write_volatile(&mut (*self.header()).checksum, checksum);
+            ptr::write_volatile(&mut (*self.header()).magic, CRASH_MAGIC);
+        }
+
+        if let Some(scratch) = &self.scratch {
+            scratch.write(checksum);
+        }
+
+        // Make sure the record reaches IRAM before the reboot.
+        arch::clean_range(self.address, CRASHLOG_SIZE as usize);
+    }
+
+    /// Loads the saved record, if there is an intact one.
+    pub fn load(&self) -> Option<CrashRecord> {
+        let (magic, checksum) = unsafe {
+            (
+                ptr::read_volatile(&(*self.header()).magic),
+                ptr::read_volatile(&(*self.header()).checksum),
+            )
+        };
+
+        if magic != CRASH_MAGIC {
+            return None;
+        }
+
+        if let Some(scratch) = &self.scratch {
+            if scratch.read() != checksum {
+                return None;
+            }
+        }
+
+        let record = unsafe { ptr::read_volatile(self.record()) };
+        if crc32(record.as_bytes()) != checksum || !record.is_valid() {
+            return None;
+        }
+
+        Some(record)
+    }
+
+    /// Discards the saved record.
+    pub fn clear(&self) {
+        unsafe {
+            ptr::write_volatile(&mut (*self.header()).magic, 0);
+        }
+
+        if let Some(scratch) = &self.scratch {
+            scratch.write(0);
+        }
+    }
+}
+
+/// Saves a record of a panic to IRAM.
+pub fn record_panic(info: &PanicInfo<'_>) {
+    let mut record = CrashRecord::new(Cause::Panic, Registers::default());
+    write!(record, "{}", info).ok();
+
+    CrashLog::IRAM.save(&record);
+}
+
+/// Saves a record of an exception to IRAM, given the registers of the
+/// program at the time of the exception.
+pub fn record_fault(cause: Cause, registers: Registers) {
+    CrashLog::IRAM.save(&CrashRecord::new(cause, registers));
+}
+
+/// Gets the crash record of a previous boot from IRAM, if there is one.
+pub fn read() -> Option<CrashRecord> {
+    CrashLog::IRAM.load()
+}
+
+/// Gets the crash record of a previous boot from IRAM and clears it, so it
+/// is only reported once.
+pub fn take() -> Option<CrashRecord> {
+    let record = read();
+    clear();
+
+    record
+}
+
+/// Discards the crash record in IRAM.
+pub fn clear() {
+    CrashLog::IRAM.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        assert!(size_of::<Header>() + size_of::<CrashRecord>() <= CRASHLOG_SIZE as usize);
+
+        let mut memory = [0u32; CRASHLOG_SIZE as usize / 4];
+        let log = unsafe { CrashLog::new(memory.as_mut_ptr() as usize) };
+        assert!(log.load().is_none());
+
+        let mut record = CrashRecord {
+            cause: Cause::DataAbort as u32,
+            registers: Registers {
+                pc: 0x4001_0000,
+                ..Registers::default()
+            },
+            fault_status: 0,
+            fault_address: 0,
+            timestamp: 0,
+            message_length: 0,
+            log_length: 3,
+            message: [0; MESSAGE_SIZE],
+            log: [b'l'; LOG_SIZE],
+        };
+        write!(record, "a").unwrap();
+        for _ in 0..200 {
+            write!(record, "é").unwrap();
+        }
+
+        log.save(&record);
+        let loaded = log.load().unwrap();
+        assert_eq!(loaded.cause(), Some(Cause::DataAbort));
+        assert_eq!(loaded.registers.pc, 0x4001_0000);
+        assert_eq!(loaded.log(), b"lll");
+
+        // The message is truncated at a character boundary.
+        assert_eq!(loaded.message().len(), MESSAGE_SIZE - 1);
+        assert!(loaded.message().starts_with("aéé"));
+
+        // Corrupted records are rejected.
+        memory[10] ^= 1;
+        assert!(log.load().is_none());
+        memory[10] ^= 1;
+
+        log.clear();
+        assert!(log.load().is_none());
+    }
+}
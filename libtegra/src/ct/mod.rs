@@ -0,0 +1,129 @@
+//! Constant-time utilities for handling secret data.
+//!
+//! # Description
+//!
+//! Comparing key material, MACs or signatures with regular equality
+//! operators leaks timing information, since such comparisons return
+//! early on the first mismatching byte. If the compared data can be
+//! influenced by an attacker, this may be used to recover secrets
+//! byte by byte.
+//!
+//! # Implementation
+//!
+//! - [`eq`] compares two byte slices in time that only depends on
+//! their length.
+//!
+//! - [`is_zero`] checks whether a byte slice only consists of zeroes,
+//! without revealing the position of a non-zero byte.
+//!
+//! - [`select_u8`] and [`select_u32`] select between two values
+//! without branching on the condition.
+//!
+//! - [`cmov`] conditionally copies a buffer into another one without
+//! branching on the condition.
+//!
+//! - Intermediate results are passed through a volatile read to keep
+//! the compiler from introducing early exits.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::ct;
+//!
+//! fn verify(expected: &[u8; 0x10], computed: &[u8; 0x10]) -> bool {
+//!     ct::eq(expected, computed)
+//! }
+//! ```
+//!
+//! [`eq`]: fn.eq.html
+//! [`is_zero`]: fn.is_zero.html
+//! [`select_u8`]: fn.select_u8.html
+//! [`select_u32`]: fn.select_u32.html
+//! [`cmov`]: fn.cmov.html
+
+use core::ptr::read_volatile;
+
+/// Hides a value from the optimizer.
+#[inline(always)]
+fn black_box<T: Copy>(value: T) -> T {
+    unsafe { read_volatile(&value) }
+}
+
+/// Expands a boolean into an all-zeroes or all-ones byte mask.
+#[inline(always)]
+fn mask_u8(condition: bool) -> u8 {
+    black_box(condition as u8).wrapping_neg()
+}
+
+/// Expands a boolean into an all-zeroes or all-ones word mask.
+#[inline(always)]
+fn mask_u32(condition: bool) -> u32 {
+    black_box(condition as u32).wrapping_neg()
+}
+
+/// Compares two byte slices in constant time.
+///
+/// Slices of different lengths are never equal. The length itself
+/// is not considered secret.
+#[inline(never)]
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut difference = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        difference = black_box(difference | (x ^ y));
+    }
+
+    difference == 0
+}
+
+/// Compares two words in constant time.
+#[inline(never)]
+pub fn eq_u32(a: u32, b: u32) -> bool {
+    let difference = black_box(a ^ b);
+
+    // The MSB is set if and only if the difference is non-zero.
+    ((difference | difference.wrapping_neg()) >> 31) == 0
+}
+
+/// Checks whether a byte slice only contains zeroes in constant time.
+#[inline(never)]
+pub fn is_zero(data: &[u8]) -> bool {
+    let mut accumulator = 0;
+    for byte in data.iter() {
+        accumulator = black_box(accumulator | byte);
+    }
+
+    accumulator == 0
+}
+
+/// Returns `a` if `condition` is set, `b` otherwise, without branching.
+#[inline]
+pub fn select_u8(condition: bool, a: u8, b: u8) -> u8 {
+    let mask = mask_u8(condition);
+
+    (a & mask) | (b & !mask)
+}
+
+/// Returns `a` if `condition` is set, `b` otherwise, without branching.
+#[inline]
+pub fn select_u32(condition: bool, a: u32, b: u32) -> u32 {
+    let mask = mask_u32(condition);
+
+    (a & mask) | (b & !mask)
+}
+
+/// Copies `source` into `destination` if `condition` is set, without branching.
+///
+/// Panics if the buffers differ in length.
+#[inline(never)]
+pub fn cmov(condition: bool, destination: &mut [u8], source: &[u8]) {
+    assert_eq!(destination.len(), source.len());
+
+    let mask = mask_u8(condition);
+    for (d, s) in destination.iter_mut().zip(source.iter()) {
+        *d = (*s & mask) | (*d & !mask);
+    }
+}
@@ -0,0 +1,424 @@
+//! BMP image rendering
+//!
+//! # Description
+//!
+//! Bootloaders usually want to show a logo or splash screen. This module
+//! decodes Windows bitmaps directly from a byte slice, without allocating,
+//! and draws them onto a [`Canvas`].
+//!
+//! Uncompressed 24-bit and 32-bit images are supported, as well as 8-bit
+//! images with a palette, either uncompressed or RLE8-compressed. Both
+//! bottom-up and top-down images are handled. The alpha channel of 32-bit
+//! images is ignored, all pixels are drawn opaque.
+//!
+//! [`draw_bmp`] and [`show_splash`] draw onto the framebuffer, while
+//! [`render_bmp`] and [`render_splash`] take any [`Canvas`].
+//!
+//! [`Canvas`]: trait.Canvas.html
+//! [`draw_bmp`]: fn.draw_bmp.html
+//! [`show_splash`]: fn.show_splash.html
+//! [`render_bmp`]: fn.render_bmp.html
+//! [`render_splash`]: fn.render_splash.html
+
+use core::convert::TryInto;
+
+use super::canvas::{Canvas, Framebuffer};
+use crate::{Error, Result};
+
+/// The size of the file header which precedes the info header.
+const FILE_HEADER_SIZE: usize = 14;
+/// The size of the smallest supported info header, `BITMAPINFOHEADER`.
+const INFO_HEADER_SIZE: usize = 40;
+
+/// Uncompressed pixel data.
+const BI_RGB: u32 = 0;
+/// 8-bit run-length encoded pixel data.
+const BI_RLE8: u32 = 1;
+/// Uncompressed pixel data with explicit channel masks.
+const BI_BITFIELDS: u32 = 3;
+
+/// The background color of the splash screen.
+const SPLASH_BACKGROUND: u32 = 0xFF00_0000;
+
+/// Reads a little-endian `u16` at the given offset.
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::InvalidArgument)
+}
+
+/// Reads a little-endian `u32` at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::InvalidArgument)
+}
+
+/// A parsed BMP image which borrows its pixel data.
+#[derive(Clone, Copy, Debug)]
+pub struct Bmp<'a> {
+    /// The width of the image in pixels.
+    width: u32,
+    /// The height of the image in pixels.
+    height: u32,
+    /// Whether the first row of the pixel data is the top one.
+    top_down: bool,
+    /// The bits per pixel, 8, 24 or 32.
+    bits_per_pixel: u16,
+    /// The compression of the pixel data.
+    compression: u32,
+    /// The palette of 8-bit images, as 4-byte BGRX entries.
+    palette: &'a [u8],
+    /// The pixel data.
+    pixels: &'a [u8],
+}
+
+impl<'a> Bmp<'a> {
+    /// Parses the headers of a BMP image.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the data isn't a BMP image,
+    /// is truncated or uses an unsupported format.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.get(..2) != Some(b"BM") {
+            return Err(Error::InvalidArgument);
+        }
+
+        let pixel_offset = read_u32(data, 10)? as usize;
+        let header_size = read_u32(data, FILE_HEADER_SIZE)? as usize;
+        if header_size < INFO_HEADER_SIZE {
+            return Err(Error::InvalidArgument);
+        }
+
+        let width = read_u32(data, FILE_HEADER_SIZE + 4)? as i32;
+        let height = read_u32(data, FILE_HEADER_SIZE + 8)? as i32;
+        let planes = read_u16(data, FILE_HEADER_SIZE + 12)?;
+        let bits_per_pixel = read_u16(data, FILE_HEADER_SIZE + 14)?;
+        let compression = read_u32(data, FILE_HEADER_SIZE + 16)?;
+        let colors_used = read_u32(data, FILE_HEADER_SIZE + 32)? as usize;
+
+        if width <= 0 || height == 0 || height == i32::min_value() || planes != 1 {
+            return Err(Error::InvalidArgument);
+        }
+
+        match (bits_per_pixel, compression) {
+            (24, BI_RGB) | (32, BI_RGB) | (8, BI_RGB) | (8, BI_RLE8) => {}
+            (32, BI_BITFIELDS) => {
+                // Only the standard layout is supported. The masks follow
+                // the info header or are part of the larger header versions.
+                let masks = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+                if read_u32(data, masks)? != 0x00FF_0000
+                    || read_u32(data, masks + 4)? != 0x0000_FF00
+                    || read_u32(data, masks + 8)? != 0x0000_00FF
+                {
+                    return Err(Error::InvalidArgument);
+                }
+            }
+            _ => return Err(Error::InvalidArgument),
+        }
+
+        let palette = if bits_per_pixel == 8 {
+            let entries = if colors_used == 0 { 256 } else { colors_used };
+            let start = FILE_HEADER_SIZE + header_size;
+
+            data.get(start..start + entries.min(256) * 4)
+                .ok_or(Error::InvalidArgument)?
+        } else {
+            &[]
+        };
+
+        let pixels = data.get(pixel_offset..).ok_or(Error::InvalidArgument)?;
+
+        let bmp = Bmp {
+            width: width as u32,
+            height: height.abs() as u32,
+            top_down: height < 0,
+            bits_per_pixel,
+            compression,
+            palette,
+            pixels,
+        };
+
+        // Uncompressed images must contain all rows.
+        if compression != BI_RLE8 && pixels.len() < bmp.stride() * bmp.height as usize {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(bmp)
+    }
+
+    /// Gets the width of the image in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Gets the height of the image in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gets the size of an uncompressed row in bytes, including padding.
+    fn stride(&self) -> usize {
+        ((self.width as usize * self.bits_per_pixel as usize + 31) / 32) * 4
+    }
+
+    /// Looks up a palette entry, unknown entries are black.
+    fn palette_color(&self, index: u8) -> u32 {
+        match self.palette.get(index as usize * 4..index as usize * 4 + 3) {
+            Some(bgr) => rgb(bgr[2], bgr[1], bgr[0]),
+            None => SPLASH_BACKGROUND,
+        }
+    }
+
+    /// Converts a row index of the pixel data into a y coordinate.
+    fn row_to_y(&self, row: u32) -> u32 {
+        if self.top_down {
+            row
+        } else {
+            self.height - 1 - row
+        }
+    }
+
+    /// Draws the image onto a canvas with its top left corner at the
+    /// given coordinates. Parts outside of the canvas are clipped.
+    pub fn draw<C: Canvas>(&self, canvas: &mut C, x: u32, y: u32) {
+        if self.compression == BI_RLE8 {
+            self.draw_rle8(canvas, x, y);
+            return;
+        }
+
+        let bytes_per_pixel = self.bits_per_pixel as usize / 8;
+
+        for row in 0..self.height {
+            let line = &self.pixels[row as usize * self.stride()..];
+            let yy = y + self.row_to_y(row);
+
+            for column in 0..self.width {
+                let pixel = &line[column as usize * bytes_per_pixel..];
+                let color = match self.bits_per_pixel {
+                    8 => self.palette_color(pixel[0]),
+                    _ => rgb(pixel[2], pixel[1], pixel[0]),
+                };
+
+                canvas.put_pixel(x + column, yy, color);
+            }
+        }
+    }
+
+    /// Draws RLE8-compressed pixel data. Decoding stops at the end of the
+    /// bitmap or of the data, whichever comes first.
+    fn draw_rle8<C: Canvas>(&self, canvas: &mut C, x: u32, y: u32) {
+        let data = self.pixels;
+        let (mut column, mut row) = (0u32, 0u32);
+        let mut i = 0;
+
+        while i + 1 < data.len() && row < self.height {
+            let (count, value) = (data[i], data[i + 1]);
+            i += 2;
+
+            match (count, value) {
+                // End of line.
+                (0, 0) => {
+                    column = 0;
+                    row += 1;
+                }
+                // End of bitmap.
+                (0, 1) => break,
+                // Delta, skipping pixels.
+                (0, 2) => {
+                    if i + 1 >= data.len() {
+                        break;
+                    }
+                    column += u32::from(data[i]);
+                    row += u32::from(data[i + 1]);
+                    i += 2;
+                }
+                // Absolute run of literal indices, padded to 16 bits.
+                (0, length) => {
+                    let length = length as usize;
+                    let literal = match data.get(i..i + length) {
+                        Some(literal) => literal,
+                        None => break,
+                    };
+
+                    for &index in literal {
+                        if column < self.width && row < self.height {
+                            let color = self.palette_color(index);
+                            canvas.put_pixel(x + column, y + self.row_to_y(row), color);
+                        }
+                        column += 1;
+                    }
+                    i += (length + 1) & !1;
+                }
+                // Encoded run of a single index.
+                (count, index) => {
+                    let color = self.palette_color(index);
+
+                    for _ in 0..count {
+                        if column < self.width && row < self.height {
+                            canvas.put_pixel(x + column, y + self.row_to_y(row), color);
+                        }
+                        column += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Packs color components into an opaque framebuffer pixel.
+fn rgb(red: u8, green: u8, blue: u8) -> u32 {
+    0xFF00_0000 | (u32::from(red) << 16) | (u32::from(green) << 8) | u32::from(blue)
+}
+
+/// Draws a BMP image onto the framebuffer with its top left corner
+/// at the given coordinates.
+///
+/// See [`Bmp::parse`] for the possible errors.
+///
+/// [`Bmp::parse`]: struct.Bmp.html#method.parse
+pub fn draw_bmp(x: u32, y: u32, data: &[u8]) -> Result<()> {
+    render_bmp(&mut Framebuffer::new(), x, y, data)
+}
+
+/// Draws a BMP image onto the given canvas with its top left corner
+/// at the given coordinates.
+pub fn render_bmp<C: Canvas>(canvas: &mut C, x: u32, y: u32, data: &[u8]) -> Result<()> {
+    Bmp::parse(data)?.draw(canvas, x, y);
+
+    Ok(())
+}
+
+/// Clears the framebuffer and draws a BMP image centered on it.
+pub fn show_splash(data: &[u8]) -> Result<()> {
+    render_splash(&mut Framebuffer::new(), data)
+}
+
+/// Clears the given canvas and draws a BMP image centered on it.
+///
+/// Images larger than the canvas are aligned to its top left corner.
+pub fn render_splash<C: Canvas>(canvas: &mut C, data: &[u8]) -> Result<()> {
+    let bmp = Bmp::parse(data)?;
+
+    let x = canvas.width().saturating_sub(bmp.width()) / 2;
+    let y = canvas.height().saturating_sub(bmp.height()) / 2;
+
+    canvas.clear(SPLASH_BACKGROUND);
+    bmp.draw(canvas, x, y);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 canvas in memory.
+    struct TestCanvas([u32; 16]);
+
+    impl Canvas for TestCanvas {
+        fn width(&self) -> u32 {
+            4
+        }
+
+        fn height(&self) -> u32 {
+            4
+        }
+
+        fn put_pixel(&mut self, x: u32, y: u32, color: u32) {
+            if x < 4 && y < 4 {
+                self.0[(y * 4 + x) as usize] = color;
+            }
+        }
+    }
+
+    /// Builds the headers of an image with the pixel data at offset 54,
+    /// followed by the given payload.
+    fn bmp(width: i32, height: i32, bpp: u16, compression: u32, payload: &[u8]) -> [u8; 128] {
+        let mut data = [0; 128];
+        let pixel_offset = 54 + if bpp == 8 { 8 } else { 0 };
+
+        data[..2].copy_from_slice(b"BM");
+        data[10..14].copy_from_slice(&(pixel_offset as u32).to_le_bytes());
+        data[14..18].copy_from_slice(&40u32.to_le_bytes());
+        data[18..22].copy_from_slice(&width.to_le_bytes());
+        data[22..26].copy_from_slice(&height.to_le_bytes());
+        data[26..28].copy_from_slice(&1u16.to_le_bytes());
+        data[28..30].copy_from_slice(&bpp.to_le_bytes());
+        data[30..34].copy_from_slice(&compression.to_le_bytes());
+        if bpp == 8 {
+            // Two palette entries, red and blue.
+            data[46..50].copy_from_slice(&2u32.to_le_bytes());
+            data[54..62].copy_from_slice(&[0, 0, 0xFF, 0, 0xFF, 0, 0, 0]);
+        }
+        data[pixel_offset..pixel_offset + payload.len()].copy_from_slice(payload);
+
+        data
+    }
+
+    const RED: u32 = 0xFFFF_0000;
+    const BLUE: u32 = 0xFF00_00FF;
+
+    /// Tests drawing a bottom-up 24-bit image with row padding.
+    #[test]
+    fn draw_24bit() {
+        // Rows of 2 pixels are padded from 6 to 8 bytes.
+        let payload = [
+            0xFF, 0, 0, 0xFF, 0, 0, 0, 0, // Bottom row, blue.
+            0, 0, 0xFF, 0, 0, 0xFF, 0, 0, // Top row, red.
+        ];
+        let mut canvas = TestCanvas([0; 16]);
+
+        render_bmp(&mut canvas, 1, 1, &bmp(2, 2, 24, BI_RGB, &payload)).unwrap();
+        assert_eq!(&canvas.0[4..8], &[0, RED, RED, 0]);
+        assert_eq!(&canvas.0[8..12], &[0, BLUE, BLUE, 0]);
+    }
+
+    /// Tests drawing a top-down 32-bit image, clipped at the canvas border.
+    #[test]
+    fn draw_32bit_top_down() {
+        let payload = [0xFF, 0, 0, 0, 0, 0, 0xFF, 0];
+        let mut canvas = TestCanvas([0; 16]);
+
+        render_bmp(&mut canvas, 3, 0, &bmp(1, -2, 32, BI_RGB, &payload)).unwrap();
+        assert_eq!(canvas.0[3], BLUE);
+        assert_eq!(canvas.0[7], RED);
+    }
+
+    /// Tests decoding of RLE8 runs, absolute runs and line ends.
+    #[test]
+    fn draw_rle8() {
+        let payload = [
+            3, 1, 0, 0, // Bottom row, three times blue.
+            0, 3, 0, 1, 0, 0, 0, 0, // Top row, red, blue, red.
+            0, 1,
+        ];
+        let mut canvas = TestCanvas([0; 16]);
+
+        render_bmp(&mut canvas, 0, 0, &bmp(3, 2, 8, BI_RLE8, &payload)).unwrap();
+        assert_eq!(&canvas.0[..4], &[RED, BLUE, RED, 0]);
+        assert_eq!(&canvas.0[4..8], &[BLUE, BLUE, BLUE, 0]);
+    }
+
+    /// Tests centering of splash images.
+    #[test]
+    fn splash() {
+        let payload = [0, 0, 0xFF, 0];
+        let mut canvas = TestCanvas([0; 16]);
+
+        render_splash(&mut canvas, &bmp(1, 1, 24, BI_RGB, &payload)).unwrap();
+        assert_eq!(canvas.0[5], RED);
+        assert_eq!(canvas.0[0], SPLASH_BACKGROUND);
+    }
+
+    /// Tests that malformed images are rejected.
+    #[test]
+    fn reject_invalid() {
+        assert!(Bmp::parse(b"BM").is_err());
+        assert!(Bmp::parse(&bmp(2, 2, 16, BI_RGB, &[])[..]).is_err());
+
+        // The pixel data is cut off.
+        assert!(Bmp::parse(&bmp(16, 16, 24, BI_RGB, &[])[..]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Drawing surfaces
+//!
+//! # Description
+//!
+//! UI code such as the panic screen draws through the [`Canvas`] trait
+//! instead of writing to the framebuffer directly. This allows rendering
+//! the same screens into memory on a development host, see the `sim`
+//! module which is available with the `std` feature.
+//!
+//! [`Framebuffer`] is the [`Canvas`] backed by the display framebuffer.
+//!
+//! [`Canvas`]: trait.Canvas.html
+//! [`Framebuffer`]: struct.Framebuffer.html
+
+use super::{
+    writer::{FRAMEBUFFER_HEIGHT, FRAMEBUFFER_WIDTH, GFX_STRIDE},
+    FRAMEBUFFER_ADDRESS,
+};
+
+/// A surface of ARGB8888 pixels that can be drawn to.
+pub trait Canvas {
+    /// Gets the width of the surface in pixels.
+    fn width(&self) -> u32;
+
+    /// Gets the height of the surface in pixels.
+    fn height(&self) -> u32;
+
+    /// Writes a pixel to the surface. Pixels outside of the
+    /// surface must be ignored.
+    fn put_pixel(&mut self, x: u32, y: u32, color: u32);
+
+    /// Fills a rectangle of the surface.
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: u32) {
+        for yy in y..y + height {
+            for xx in x..x + width {
+                self.put_pixel(xx, yy, color);
+            }
+        }
+    }
+
+    /// Fills the whole surface.
+    fn clear(&mut self, color: u32) {
+        let (width, height) = (self.width(), self.height());
+
+        self.fill_rect(0, 0, width, height, color);
+    }
+}
+
+/// The [`Canvas`] backed by the display framebuffer.
+///
+/// The display must have been initialized before drawing to it.
+///
+/// [`Canvas`]: trait.Canvas.html
+pub struct Framebuffer {
+    /// A pointer to the framebuffer.
+    address: *mut u32,
+}
+
+impl Framebuffer {
+    /// Creates a canvas for the default framebuffer.
+    pub const fn new() -> Self {
+        Framebuffer {
+            address: FRAMEBUFFER_ADDRESS as *mut u32,
+        }
+    }
+}
+
+impl Canvas for Framebuffer {
+    fn width(&self) -> u32 {
+        FRAMEBUFFER_WIDTH
+    }
+
+    fn height(&self) -> u32 {
+        FRAMEBUFFER_HEIGHT
+    }
+
+    #[inline]
+    fn put_pixel(&mut self, x: u32, y: u32, color: u32) {
+        if x >= FRAMEBUFFER_WIDTH || y >= FRAMEBUFFER_HEIGHT {
+            return;
+        }
+
+        unsafe {
+            self.address
+                .offset((x + y * GFX_STRIDE) as isize)
+                .write_volatile(color);
+        }
+    }
+}
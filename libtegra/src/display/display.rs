@@ -1,16 +1,24 @@
 use mirage_mmio::{Mmio, VolatileStorage};
 
-use super::display_config::*;
+use super::{display_config::*, dsi};
 use crate::{
-    clock::{Car, CLOCK_BASE},
+    clock::{
+        pll::{Pll, PLL_LOCK_TIMEOUT},
+        Car,
+        CLOCK_BASE,
+    },
     gpio::{Gpio, GpioDirection, GpioLevel, GpioMode},
     i2c::*,
     pinmux::{Pinmux, TRISTATE},
     pmc::Pmc,
-    timer::{get_microseconds, usleep},
+    timer::usleep,
 };
 
-static mut DISPLAY_VERSION: u32 = 0;
+/// The ID of the panel, as read during initialization.
+static mut PANEL_ID: u32 = 0;
+
+/// The delay between enabling the backlight driver and driving PWM.
+const BACKLIGHT_EN_DELAY: u32 = 1_000;
 
 /// Base address for DI registers.
 pub(crate) const DI_BASE: u32 = 0x5420_0000;
@@ -21,30 +29,85 @@ pub(crate) const DSI_BASE: u32 = 0x5430_0000;
 /// Base address for MIPI CAL registers.
 pub(crate) const MIPI_CAL_BASE: u32 = 0x700E_3000;
 
-/// Waits for DSI to be updated.
-unsafe fn dsi_wait(timeout: u32, offset: u32, mask: u32) {
-    let register = &*((DSI_BASE + offset * 4) as *const Mmio<u32>);
-    let end = get_microseconds() + timeout;
+/// The internal panels known to ship in Switch revisions.
+///
+/// Panels are identified by the vendor in the first byte and the
+/// revision in the third byte of the MIPI_DCS_GET_DISPLAY_ID reply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Panel {
+    /// A JDI 6.2" panel which doesn't report its revision.
+    JdiXxx062m,
+    /// The JDI LAM062M109A 6.2" panel.
+    JdiLam062m109a,
+    /// The JDI LPM062M326A 6.2" panel.
+    JdiLpm062m326a,
+    /// The InnoLux P062CCA-AZ1 6.2" panel.
+    InnoluxP062ccaAz1,
+    /// The AUO A062TAN01 6.2" panel.
+    AuoA062tan01,
+    /// The InnoLux 2J055IA-27A 5.5" panel of the Switch Lite.
+    Innolux2j055ia27a,
+    /// The AUO A055TAN01 5.5" panel of the Switch Lite.
+    AuoA055tan01,
+    /// The Sharp LQ055T1SW10 5.5" panel of the Switch Lite.
+    SharpLq055t1sw10,
+    /// The Samsung AMS699VC01 7" OLED panel.
+    SamsungAms699vc01,
+    /// A panel with an unknown ID, e.g. a spare part.
+    Unknown(u32),
+}
+
+impl Panel {
+    /// Gets the panel with the given ID.
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            0x10 => Panel::JdiXxx062m,
+            0x0910 => Panel::JdiLam062m109a,
+            0x2610 => Panel::JdiLpm062m326a,
+            0x0F20 => Panel::InnoluxP062ccaAz1,
+            0x0F30 => Panel::AuoA062tan01,
+            0x1020 => Panel::Innolux2j055ia27a,
+            0x1030 => Panel::AuoA055tan01,
+            0x1040 => Panel::SharpLq055t1sw10,
+            0x2050 => Panel::SamsungAms699vc01,
+            id => Panel::Unknown(id),
+        }
+    }
+
+    /// Whether the panel is made by JDI.
+    pub fn is_jdi(self) -> bool {
+        match self {
+            Panel::JdiXxx062m | Panel::JdiLam062m109a | Panel::JdiLpm062m326a => true,
+            _ => false,
+        }
+    }
 
-    while get_microseconds() < end && register.read() & mask != 0 {
-        // Wait.
+    /// Gets the time the panel needs to leave sleep mode in microseconds.
+    fn exit_sleep_delay(self) -> u32 {
+        match self {
+            Panel::InnoluxP062ccaAz1 | Panel::AuoA062tan01 => 180_000,
+            panel if panel.is_jdi() => 180_000,
+            _ => 120_000,
+        }
     }
-    usleep(5);
 }
 
-/// Initializes the display.
-pub fn initialize() {
+/// Gets the panel detected by [`initialize`].
+///
+/// [`initialize`]: fn.initialize.html
+pub fn panel() -> Panel {
+    Panel::from_id(unsafe { PANEL_ID })
+}
+
+/// Initializes the display, powering it through the PMIC on `i2c`.
+pub fn initialize(i2c: &I2c) {
     let car = unsafe { Car::get() };
     let pinmux = unsafe { Pinmux::get() };
     let pmc = unsafe { Pmc::get() };
 
     // Power on.
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x23, 0xD0)
-        .unwrap();
-    I2c::C5
-        .write_byte(Device::Max77620Pwr, 0x3D, 0x9)
-        .unwrap();
+    i2c.write_byte(Device::Max77620Pwr, 0x23, 0xD0).unwrap();
+    i2c.write_byte(Device::Max77620Pwr, 0x3D, 0x9).unwrap();
 
     // Enable MIPI CAL, DSI, DISP1, HOST1X, UART_FST_MIPI_CAL, DSIA LP clocks.
     car.rst_dev_h_clr.write(0x1010000);
@@ -101,6 +164,14 @@ pub fn initialize() {
         (*((MIPI_CAL_BASE + 0x60) as *const Mmio<u32>)).write(0);
 
         execute(CLOCK_BASE as *mut u32, &DISPLAY_CONFIG_1);
+    }
+
+    // Wait for PLLD to lock before clocking the display from it.
+    Pll::D
+        .wait_for_lock(PLL_LOCK_TIMEOUT)
+        .expect("PLLD failed to lock!");
+
+    unsafe {
         execute(DI_BASE as *mut u32, &DISPLAY_CONFIG_2);
         execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_3);
     }
@@ -114,39 +185,37 @@ pub fn initialize() {
 
     unsafe {
         (*((DSI_BASE + 0x3F * 4) as *const Mmio<u32>)).write(0x50204);
-        (*((DSI_BASE + 0xA * 4) as *const Mmio<u32>)).write(0x337);
-        (*((DSI_BASE + 0x13 * 4) as *const Mmio<u32>)).write(1 << 1);
-
-        dsi_wait(250_000, 0x13, 0x3);
-
-        (*((DSI_BASE + 0xA * 4) as *const Mmio<u32>)).write(0x406);
-        (*((DSI_BASE + 0x13 * 4) as *const Mmio<u32>)).write(1 << 1);
-        dsi_wait(250_000, 0x13, 0x3);
-
-        (*((DSI_BASE + 0xF * 4) as *const Mmio<u32>)).write(0x200B);
-        dsi_wait(150_000, 0xF, 1 << 3);
 
-        usleep(5_000);
+        // Unknown panels are driven like the ones of the Switch Lite.
+        PANEL_ID = dsi::read_panel_id().unwrap_or(0);
 
-        DISPLAY_VERSION = (*((DSI_BASE + 0x9 * 4) as *const Mmio<u32>)).read();
-
-        if DISPLAY_VERSION == 0x10 {
+        let panel = panel();
+        if panel.is_jdi() {
             execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_4);
         }
 
-        (*((DSI_BASE + 0xA * 4) as *const Mmio<u32>)).write(0x1105);
-        (*((DSI_BASE + 0x13 * 4) as *const Mmio<u32>)).write(1 << 1);
+        dsi::dcs_write(dsi::DCS_EXIT_SLEEP_MODE, None).ok();
+
+        usleep(panel.exit_sleep_delay());
 
-        usleep(180_000);
+        match panel {
+            Panel::InnoluxP062ccaAz1 => execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_INNOLUX_62),
+            Panel::AuoA062tan01 => execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_AUO_62),
+            _ => {}
+        }
 
-        (*((DSI_BASE + 0xA * 4) as *const Mmio<u32>)).write(0x2905);
-        (*((DSI_BASE + 0x13 * 4) as *const Mmio<u32>)).write(1 << 1);
+        dsi::dcs_write(dsi::DCS_SET_DISPLAY_ON, None).ok();
 
         usleep(20_000);
 
         execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_5);
         execute(CLOCK_BASE as *mut u32, &DISPLAY_CONFIG_6);
 
+        // Wait for PLLD to lock onto the new rate.
+        Pll::D
+            .wait_for_lock(PLL_LOCK_TIMEOUT)
+            .expect("PLLD failed to lock!");
+
         (*((DI_BASE + 0x42E * 4) as *const Mmio<u32>)).write(4);
         execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_7);
 
@@ -182,12 +251,11 @@ pub fn finish() {
 
         usleep(10_000);
 
-        if DISPLAY_VERSION == 0x10 {
+        if panel().is_jdi() {
             execute(DSI_BASE as *mut u32, &DISPLAY_CONFIG_14);
         }
 
-        (*((DSI_BASE + 0xA * 4) as *const Mmio<u32>)).write(0x1005);
-        (*((DSI_BASE + 0x13 * 4) as *const Mmio<u32>)).write(1 << 1);
+        dsi::dcs_write(dsi::DCS_ENTER_SLEEP_MODE, None).ok();
     }
 
     usleep(50_000);
@@ -265,15 +333,24 @@ fn set_backlight(enable: bool) {
 }
 
 /// Displays the backlight.
-#[inline]
+///
+/// The panel should show a valid frame already, as anything else becomes
+/// visible otherwise.
 pub fn display_backlight() {
+    // Power up the backlight driver before driving PWM.
+    Gpio::LCD_BL_EN.write(GpioLevel::High);
+    usleep(BACKLIGHT_EN_DELAY);
+
     set_backlight(true);
 }
 
 /// Disables the backlight.
-#[inline]
 pub fn hide_backlight() {
     set_backlight(false);
+    usleep(BACKLIGHT_EN_DELAY);
+
+    // Power down the backlight driver.
+    Gpio::LCD_BL_EN.write(GpioLevel::Low);
 }
 
 /// Initializes display in full 1280x720 resolution.
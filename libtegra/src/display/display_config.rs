@@ -3,6 +3,9 @@ use core::ptr::write_volatile;
 /// The framebuffer memory address.
 pub const FRAMEBUFFER_ADDRESS: u32 = 0xC000_0000;
 
+/// The size of the framebuffer in bytes.
+pub const FRAMEBUFFER_SIZE: u32 = 720 * 1280 * 4;
+
 /// Wrapper around a display configuration value.
 #[derive(Clone, Copy, PartialEq)]
 pub struct ConfigTable {
@@ -26,7 +29,7 @@ impl ConfigTable {
 ///
 /// [`ConfigTable`]: struct.ConfigTable.html
 macro_rules! config_table {
-    ($offset:tt, $value:tt) => {
+    ($offset:expr, $value:expr) => {
         ConfigTable {
             offset: $offset,
             value: $value,
@@ -204,7 +207,7 @@ pub const DISPLAY_CONFIG_3: [ConfigTable; 60] = [
     config_table!(0x1A, 0),
 ];
 
-/// DSI config (if version == 0x10).
+/// DSI config of JDI panels, sent before leaving sleep mode.
 pub const DISPLAY_CONFIG_4: [ConfigTable; 43] = [
     config_table!(0xA, 0x439),
     config_table!(0xA, 0x9483FFB9),
@@ -251,6 +254,28 @@ pub const DISPLAY_CONFIG_4: [ConfigTable; 43] = [
     config_table!(0x13, 0x2),
 ];
 
+/// DSI config of InnoLux 6.2" panels, sent after leaving sleep mode.
+pub const DISPLAY_CONFIG_INNOLUX_62: [ConfigTable; 7] = [
+    config_table!(0xA, 0x439),
+    config_table!(0xA, 0x9483_FFB9),
+    config_table!(0x13, 0x2),
+    config_table!(0xA, 0x739),
+    config_table!(0xA, 0x7515_48B1),
+    config_table!(0xA, 0x14_3209),
+    config_table!(0x13, 0x2),
+];
+
+/// DSI config of AUO 6.2" panels, sent after leaving sleep mode.
+pub const DISPLAY_CONFIG_AUO_62: [ConfigTable; 7] = [
+    config_table!(0xA, 0x439),
+    config_table!(0xA, 0x9483_FFB9),
+    config_table!(0x13, 0x2),
+    config_table!(0xA, 0x739),
+    config_table!(0xA, 0x7111_48B1),
+    config_table!(0xA, 0x14_3209),
+    config_table!(0x13, 0x2),
+];
+
 /// DSI config.
 pub const DISPLAY_CONFIG_5: [ConfigTable; 21] = [
     config_table!(0x4F, 0),
@@ -493,7 +518,7 @@ pub const DISPLAY_CONFIG_13: [ConfigTable; 16] = [
     config_table!(0x1A, 0),
 ];
 
-/// DSI config (if ver == 0x10).
+/// DSI config of JDI panels, sent before entering sleep mode.
 pub const DISPLAY_CONFIG_14: [ConfigTable; 22] = [
     config_table!(0xA, 0x439),
     config_table!(0xA, 0x9483_FFB9),
@@ -0,0 +1,889 @@
+//! DisplayPort output for the dock.
+//!
+//! # Description
+//!
+//! When docked, the Switch drives the external display through the second
+//! display controller, whose pixel stream is encoded by SOR1 and sent over
+//! a DisplayPort link to the dock, which converts it to HDMI. The sink is
+//! configured over the AUX channel of DPAUX1, which also reports whether
+//! a sink is connected at all.
+//!
+//! # Implementation
+//!
+//! - [`is_connected`] checks the hotplug detect line of the dock.
+//!
+//! - [`initialize_external`] brings up the whole path in [`Mode::FULL_HD`].
+//! It picks the most conservative [`LinkConfig`] the sink supports for the
+//! mode, trains the link and scans out the framebuffer used for the
+//! internal panel. The framebuffer is shown as drawn, scaled to the height
+//! of the mode and centered horizontally.
+//!
+//! - [`finish_external`] shuts the path down again, which should be done
+//! before handing over to a payload.
+//!
+//! [`is_connected`]: fn.is_connected.html
+//! [`initialize_external`]: fn.initialize_external.html
+//! [`Mode::FULL_HD`]: struct.Mode.html#associatedconstant.FULL_HD
+//! [`LinkConfig`]: struct.LinkConfig.html
+//! [`finish_external`]: fn.finish_external.html
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use super::{
+    display_config::{execute, ConfigTable},
+    writer::{FRAMEBUFFER_HEIGHT, FRAMEBUFFER_WIDTH},
+};
+use crate::{
+    clock::{
+        fields::CLK_SOURCE_SOR1,
+        pll::{Pll, PllConfig},
+        Car, Clock,
+    },
+    debug,
+    dpaux::Dpaux,
+    sor::Sor,
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// Base address for the registers of the second display controller.
+pub(crate) const DC_B_BASE: u32 = 0x5424_0000;
+
+/// PLLD2 at 148.8MHz, the closest match to the 148.5MHz of [`Mode::FULL_HD`]
+/// and well within the tolerance of sinks (M = 8, N = 155, P = 5).
+///
+/// [`Mode::FULL_HD`]: struct.Mode.html#associatedconstant.FULL_HD
+const PLLD2_FULL_HD: PllConfig = PllConfig::new(8, 155, 4);
+
+/// PLLDP at the 270MHz reference of the SOR pad macro (M = 16, N = 225, P = 2).
+const PLLDP_CONFIG: PllConfig = PllConfig::new(16, 225, 1);
+
+/// The pixel clock PLLD2 actually runs at with [`PLLD2_FULL_HD`], in Hz.
+///
+/// [`PLLD2_FULL_HD`]: constant.PLLD2_FULL_HD.html
+const PIXEL_CLOCK: u32 = 148_800_000;
+
+/// The bits per pixel sent over the link, RGB with 8 bits per component.
+const BITS_PER_PIXEL: u32 = 24;
+
+/// The size of a transfer unit in link symbols.
+const TU_SIZE: u32 = 64;
+
+/// How often clock recovery and channel equalization are attempted.
+const TRAINING_ATTEMPTS: u32 = 5;
+
+/// How long the SOR may take to complete a power sequence, in µs.
+const SOR_TIMEOUT: u32 = 100_000;
+
+// DPCD registers of the sink.
+const DPCD_REV: u32 = 0x000;
+const DPCD_TRAINING_PATTERN_SET: u32 = 0x102;
+const DPCD_TRAINING_LANE0_SET: u32 = 0x103;
+const DPCD_LANE0_1_STATUS: u32 = 0x202;
+const DPCD_LINK_BW_SET: u32 = 0x100;
+const DPCD_SET_POWER: u32 = 0x600;
+
+/// Enables the enhanced framing mode in `LANE_COUNT_SET`.
+const DPCD_ENHANCED_FRAME_EN: u8 = 1 << 7;
+/// Disables scrambling in `TRAINING_PATTERN_SET`.
+const DPCD_SCRAMBLING_DISABLE: u8 = 1 << 5;
+/// Wakes the sink up in `SET_POWER`.
+const DPCD_SET_POWER_D0: u8 = 0x1;
+
+// Bits of the per-lane nibbles in `LANE0_1_STATUS` and `LANE2_3_STATUS`.
+const DPCD_LANE_CR_DONE: u8 = 1 << 0;
+const DPCD_LANE_CHANNEL_EQ_DONE: u8 = 1 << 1;
+const DPCD_LANE_SYMBOL_LOCKED: u8 = 1 << 2;
+/// All lanes are aligned to each other, in `LANE_ALIGN_STATUS_UPDATED`.
+const DPCD_INTERLANE_ALIGN_DONE: u8 = 1 << 0;
+
+// SOR register bits.
+const SOR_STATE_UPDATE: u32 = 1 << 0;
+const SOR_SUPER_STATE1_HEAD_MODE_AWAKE: u32 = 2 << 0;
+const SOR_SUPER_STATE1_ORMODE_NORMAL: u32 = 1 << 2;
+const SOR_SUPER_STATE1_ATTACHED: u32 = 1 << 3;
+const SOR_STATE1_OWNER_HEAD1: u32 = 2 << 0;
+const SOR_STATE1_CRC_MODE_COMPLETE: u32 = 1 << 6;
+const SOR_STATE1_PROTOCOL_DP_A: u32 = 0x8 << 8;
+const SOR_STATE1_PIXELDEPTH_BPP_24_444: u32 = 0x5 << 17;
+const SOR_CLK_CNTRL_DP_CLK_SEL_SINGLE_DPCLK: u32 = 2 << 0;
+const SOR_PWR_NORMAL_STATE_PU: u32 = 1 << 0;
+const SOR_PWR_TRIGGER: u32 = 1 << 31;
+const SOR_PLL0_PWR: u32 = 1 << 0;
+const SOR_PLL0_VCOPD: u32 = 1 << 2;
+const SOR_PLL2_SEQ_PLLCAPPD_ENFORCE: u32 = 1 << 21;
+const SOR_PLL2_PORT_POWERDOWN: u32 = 1 << 23;
+const SOR_PLL2_BANDGAP_POWERDOWN: u32 = 1 << 24;
+const SOR_LANE_SEQ_CTL_SEQUENCE_DOWN: u32 = 1 << 20;
+const SOR_LANE_SEQ_CTL_TRIGGER: u32 = 1 << 31;
+const SOR_DP_LINKCTL_ENABLE: u32 = 1 << 0;
+const SOR_DP_LINKCTL_ENHANCED_FRAME: u32 = 1 << 14;
+const SOR_DP_CONFIG_ACTIVESYM_POLARITY: u32 = 1 << 24;
+const SOR_DP_CONFIG_ACTIVESYM_ENABLE: u32 = 1 << 26;
+const SOR_DP_CONFIG_DISPARITY_NEGATIVE: u32 = 1 << 31;
+const SOR_DP_TPG_CHANNEL_CODING: u32 = 1 << 6;
+const SOR_DP_TPG_SCRAMBLER_GALIOS: u32 = 1 << 4;
+
+/// The power down bits of the lanes in `SOR_DP_PADCTL0`, by lane.
+const SOR_DP_PADCTL_PD_TXD: [u32; 4] = [1 << 22, 1 << 21, 1 << 20, 1 << 23];
+
+/// The lane drive currents, by voltage swing and pre-emphasis level.
+const DRIVE_CURRENT: [[u8; 4]; 4] = [
+    [0x13, 0x19, 0x1E, 0x28],
+    [0x1E, 0x25, 0x2D, 0x00],
+    [0x28, 0x32, 0x00, 0x00],
+    [0x3C, 0x00, 0x00, 0x00],
+];
+
+/// The lane pre-emphasis currents, by voltage swing and pre-emphasis level.
+const PREEMPHASIS: [[u8; 4]; 4] = [
+    [0x00, 0x09, 0x13, 0x25],
+    [0x00, 0x0A, 0x14, 0x00],
+    [0x00, 0x0A, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00],
+];
+
+// Display controller registers, as word offsets.
+const DC_CMD_DISPLAY_COMMAND: u32 = 0x32;
+const DC_CMD_DISPLAY_POWER_CONTROL: u32 = 0x36;
+const DC_CMD_STATE_CONTROL: u32 = 0x41;
+const DC_CMD_DISPLAY_WINDOW_HEADER: u32 = 0x42;
+const DC_DISP_DISP_WIN_OPTIONS: u32 = 0x402;
+const DC_DISP_DISP_TIMING_OPTIONS: u32 = 0x405;
+const DC_DISP_REF_TO_SYNC: u32 = 0x406;
+const DC_DISP_SYNC_WIDTH: u32 = 0x407;
+const DC_DISP_BACK_PORCH: u32 = 0x408;
+const DC_DISP_DISP_ACTIVE: u32 = 0x409;
+const DC_DISP_FRONT_PORCH: u32 = 0x40A;
+const DC_DISP_DISP_CLOCK_CONTROL: u32 = 0x42E;
+const DC_DISP_DISP_INTERFACE_CONTROL: u32 = 0x42F;
+const DC_DISP_DISP_COLOR_CONTROL: u32 = 0x430;
+const DC_DISP_BLEND_BACKGROUND_COLOR: u32 = 0x4E4;
+const DC_WIN_WIN_OPTIONS: u32 = 0x700;
+const DC_WIN_BUFFER_CONTROL: u32 = 0x702;
+const DC_WIN_COLOR_DEPTH: u32 = 0x703;
+const DC_WIN_POSITION: u32 = 0x704;
+const DC_WIN_SIZE: u32 = 0x705;
+const DC_WIN_PRESCALED_SIZE: u32 = 0x706;
+const DC_WIN_H_INITIAL_DDA: u32 = 0x707;
+const DC_WIN_V_INITIAL_DDA: u32 = 0x708;
+const DC_WIN_DDA_INCREMENT: u32 = 0x709;
+const DC_WIN_LINE_STRIDE: u32 = 0x70A;
+const DC_WINBUF_START_ADDR: u32 = 0x800;
+const DC_WINBUF_ADDR_H_OFFSET: u32 = 0x806;
+const DC_WINBUF_ADDR_V_OFFSET: u32 = 0x808;
+const DC_WINBUF_SURFACE_KIND: u32 = 0x80B;
+
+// Display controller register bits.
+const DISP_CTRL_MODE_C_DISPLAY: u32 = 1 << 5;
+const PW0_PW4_PM0_PM1_ENABLE: u32 = 0x5_0155;
+const GENERAL_ACT_REQ: u32 = 1 << 0;
+const WIN_A_ACT_REQ: u32 = 1 << 1;
+const GENERAL_UPDATE: u32 = 1 << 8;
+const WIN_A_UPDATE: u32 = 1 << 9;
+const WINDOW_A_SELECT: u32 = 1 << 4;
+const SOR1_ENABLE: u32 = 1 << 26;
+const BASE_COLOR_SIZE_888: u32 = 0x8;
+const COLOR_DEPTH_B8G8R8A8: u32 = 0xC;
+const WIN_ENABLE: u32 = 1 << 30;
+const H_FILTER_ENABLE: u32 = 1 << 16;
+const V_FILTER_ENABLE: u32 = 1 << 18;
+
+/// The line stride of the framebuffer in bytes.
+const FRAMEBUFFER_STRIDE: u32 = 0xC00;
+
+/// Timings of a display mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mode {
+    /// The nominal pixel clock in Hz.
+    pub pixel_clock: u32,
+    /// The visible width in pixels.
+    pub h_active: u32,
+    /// The horizontal front porch in pixels.
+    pub h_front_porch: u32,
+    /// The width of the horizontal sync pulse in pixels.
+    pub h_sync_width: u32,
+    /// The horizontal back porch in pixels.
+    pub h_back_porch: u32,
+    /// The visible height in lines.
+    pub v_active: u32,
+    /// The vertical front porch in lines.
+    pub v_front_porch: u32,
+    /// The width of the vertical sync pulse in lines.
+    pub v_sync_width: u32,
+    /// The vertical back porch in lines.
+    pub v_back_porch: u32,
+}
+
+impl Mode {
+    /// 1920x1080 at 60Hz, as defined by CEA-861.
+    pub const FULL_HD: Self = Mode {
+        pixel_clock: 148_500_000,
+        h_active: 1920,
+        h_front_porch: 88,
+        h_sync_width: 44,
+        h_back_porch: 148,
+        v_active: 1080,
+        v_front_porch: 4,
+        v_sync_width: 5,
+        v_back_porch: 36,
+    };
+
+    /// Gets the total width of a line, including blanking.
+    pub fn h_total(&self) -> u32 {
+        self.h_active + self.h_front_porch + self.h_sync_width + self.h_back_porch
+    }
+
+    /// Gets the total height of a frame, including blanking.
+    pub fn v_total(&self) -> u32 {
+        self.v_active + self.v_front_porch + self.v_sync_width + self.v_back_porch
+    }
+}
+
+/// The supported link rates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkRate {
+    /// Reduced Bit Rate, 1.62Gbps per lane.
+    Rbr = 0x06,
+    /// High Bit Rate, 2.7Gbps per lane.
+    Hbr = 0x0A,
+}
+
+impl LinkRate {
+    /// Gets the DPCD encoding of the rate, which is also used by the SOR.
+    pub const fn value(self) -> u32 {
+        self as u32
+    }
+
+    /// Gets the symbol rate of a lane in kHz.
+    pub fn symbol_rate(self) -> u32 {
+        match self {
+            LinkRate::Rbr => 162_000,
+            LinkRate::Hbr => 270_000,
+        }
+    }
+}
+
+/// The configuration of a DisplayPort link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkConfig {
+    /// The rate of the link.
+    pub rate: LinkRate,
+    /// The number of lanes, 1, 2 or 4.
+    pub lanes: u32,
+    /// Whether enhanced framing is used.
+    pub enhanced_framing: bool,
+}
+
+impl LinkConfig {
+    /// Selects the link configuration for a mode, given the `MAX_LINK_RATE`
+    /// and `MAX_LANE_COUNT` capabilities of the sink.
+    ///
+    /// The lowest rate is preferred, then the fewest lanes. Returns `None`
+    /// if the sink can't carry the mode.
+    pub fn select(max_link_rate: u8, max_lane_count: u8, mode: &Mode) -> Option<Self> {
+        let max_lanes = u32::from(max_lane_count & 0x1F);
+        let enhanced_framing = max_lane_count & 0x80 != 0;
+
+        for &rate in [LinkRate::Rbr, LinkRate::Hbr].iter() {
+            if rate.value() > u32::from(max_link_rate) {
+                continue;
+            }
+
+            for &lanes in [1, 2, 4].iter() {
+                let config = LinkConfig {
+                    rate,
+                    lanes,
+                    enhanced_framing,
+                };
+
+                if lanes <= max_lanes && TransferUnit::compute(mode, &config).is_some() {
+                    return Some(config);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Gets the mask of the lanes in use.
+    fn lane_mask(&self) -> u32 {
+        (1 << self.lanes) - 1
+    }
+}
+
+/// The fill of a transfer unit with active symbols.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TransferUnit {
+    /// The whole number of active symbols per transfer unit.
+    active_count: u32,
+    /// The inverse of the fractional part, which makes every n-th
+    /// transfer unit carry one symbol more or less.
+    active_frac: u32,
+    /// Whether the fractional part is added instead of subtracted.
+    active_polarity: bool,
+    /// The FIFO watermark, in symbols.
+    watermark: u32,
+}
+
+impl TransferUnit {
+    /// Computes the transfer unit fill for a mode on a link.
+    ///
+    /// Returns `None` if the link is too slow for the mode.
+    fn compute(mode: &Mode, link: &LinkConfig) -> Option<Self> {
+        // The share of the link taken by the pixel data, in thousandths.
+        let payload = u64::from(mode.pixel_clock / 1000) * u64::from(BITS_PER_PIXEL);
+        let capacity = u64::from(link.rate.symbol_rate()) * 8 * u64::from(link.lanes);
+        if payload > capacity {
+            return None;
+        }
+        let ratio = (payload * 1000 / capacity) as u32;
+
+        let active = ratio * TU_SIZE;
+        let mut frac = active % 1000;
+        let active_polarity = frac >= 500;
+        if active_polarity {
+            frac = 1000 - frac;
+        }
+
+        let active_frac = match frac {
+            0 => 0,
+            frac if 1_000_000 / frac > 15_000 => 15,
+            frac => 1_000_000 / frac / 1000,
+        };
+
+        let watermark = ratio * TU_SIZE * (1000 - ratio) / 1_000_000 + BITS_PER_PIXEL / 8 + 2;
+
+        Some(TransferUnit {
+            active_count: active / 1000,
+            active_frac,
+            active_polarity: active_polarity && active_frac > 1,
+            watermark: watermark.min(0x3F),
+        })
+    }
+}
+
+/// Voltage swing and pre-emphasis levels of a lane, from 0 to 3.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct DriveLevel {
+    /// The voltage swing level.
+    swing: u8,
+    /// The pre-emphasis level.
+    preemphasis: u8,
+}
+
+impl DriveLevel {
+    /// Clamps the levels to the combinations the SOR supports.
+    fn clamp(self) -> Self {
+        let swing = self.swing.min(3);
+
+        DriveLevel {
+            swing,
+            preemphasis: self.preemphasis.min(3 - swing),
+        }
+    }
+
+    /// Encodes the levels for the `TRAINING_LANEx_SET` registers.
+    fn training_lane_set(&self) -> u8 {
+        let mut value = self.swing | (self.preemphasis << 3);
+
+        if self.swing == 3 {
+            value |= 1 << 2;
+        }
+        if self.swing + self.preemphasis == 3 {
+            value |= 1 << 5;
+        }
+
+        value
+    }
+}
+
+/// The link status registers of the sink, from `LANE0_1_STATUS`
+/// through `ADJUST_REQUEST_LANE2_3`.
+struct LinkStatus([u8; 6]);
+
+impl LinkStatus {
+    /// Gets the status nibble of a lane.
+    fn lane(&self, lane: u32) -> u8 {
+        (self.0[lane as usize / 2] >> ((lane % 2) * 4)) & 0xF
+    }
+
+    /// Whether clock recovery succeeded on all lanes of the link.
+    fn clock_recovered(&self, link: &LinkConfig) -> bool {
+        (0..link.lanes).all(|lane| self.lane(lane) & DPCD_LANE_CR_DONE != 0)
+    }
+
+    /// Whether all lanes of the link are equalized, locked and aligned.
+    fn channel_equalized(&self, link: &LinkConfig) -> bool {
+        let done = DPCD_LANE_CR_DONE | DPCD_LANE_CHANNEL_EQ_DONE | DPCD_LANE_SYMBOL_LOCKED;
+
+        (0..link.lanes).all(|lane| self.lane(lane) & done == done)
+            && self.0[2] & DPCD_INTERLANE_ALIGN_DONE != 0
+    }
+
+    /// Gets the drive levels the sink requests for a lane.
+    fn adjust_request(&self, lane: u32) -> DriveLevel {
+        let request = self.0[4 + lane as usize / 2] >> ((lane % 2) * 4);
+
+        DriveLevel {
+            swing: request & 0x3,
+            preemphasis: (request >> 2) & 0x3,
+        }
+        .clamp()
+    }
+}
+
+/// Gets a register of the second display controller.
+fn dc_register(offset: u32) -> &'static Mmio<u32> {
+    unsafe { &*((DC_B_BASE + offset * 4) as *const Mmio<u32>) }
+}
+
+/// Waits for the hardware to clear the given bits of a register.
+fn wait_for_clear(register: &Mmio<u32>, mask: u32) -> Result<()> {
+    wait_for(|| register.read() & mask == 0, SOR_TIMEOUT)
+}
+
+/// Whether a sink is connected to the DisplayPort output.
+///
+/// DPAUX1 must have been initialized through [`initialize_external`]
+/// or [`Dpaux::init`] before.
+///
+/// [`initialize_external`]: fn.initialize_external.html
+/// [`Dpaux::init`]: ../dpaux/struct.Dpaux.html#method.init
+pub fn is_connected() -> bool {
+    Dpaux::DPAUX1.is_connected()
+}
+
+/// Enables the clocks of the external display path.
+fn enable_clocks() -> Result<()> {
+    let car = unsafe { Car::get() };
+
+    // HOST1X is shared with the internal panel, don't reset it.
+    let mut host1x = Clock::HOST1X;
+    if !host1x.is_enabled() {
+        host1x.enable();
+    }
+
+    Pll::D2.configure(&PLLD2_FULL_HD)?;
+    Pll::DP.configure(&PLLDP_CONFIG)?;
+
+    for clock in &mut [Clock::DISP2, Clock::SOR_SAFE, Clock::SOR1] {
+        clock.enable();
+    }
+
+    // Run SOR1 from the safe clock until the link is up.
+    car.clk_source_sor1.modify(|v| {
+        let v = CLK_SOURCE_SOR1::SOR1_CLK_SEL1.set(v, 1);
+        CLK_SOURCE_SOR1::SOR1_CLK_SEL0.set(v, 1)
+    });
+
+    Ok(())
+}
+
+/// Powers up the pad macro of SOR1 and the lanes of the link.
+fn power_up_link(link: &LinkConfig) -> Result<()> {
+    let sor = Sor::SOR1.registers();
+
+    sor.SOR_CLK_CNTRL
+        .write((link.rate.value() << 2) | SOR_CLK_CNTRL_DP_CLK_SEL_SINGLE_DPCLK);
+
+    sor.SOR_PLL2
+        .write(sor.SOR_PLL2.read() & !SOR_PLL2_BANDGAP_POWERDOWN);
+    usleep(20);
+
+    sor.SOR_PLL0
+        .write(sor.SOR_PLL0.read() & !(SOR_PLL0_PWR | SOR_PLL0_VCOPD));
+    sor.SOR_PLL2
+        .write(sor.SOR_PLL2.read() & !SOR_PLL2_SEQ_PLLCAPPD_ENFORCE);
+    usleep(200);
+
+    sor.SOR_PLL2
+        .write(sor.SOR_PLL2.read() & !SOR_PLL2_PORT_POWERDOWN);
+    usleep(20);
+
+    // Power up the lanes in use and keep the others down.
+    let mut padctl = sor.SOR_DP_PADCTL0.read();
+    for (lane, &bit) in SOR_DP_PADCTL_PD_TXD.iter().enumerate() {
+        if (lane as u32) < link.lanes {
+            padctl &= !bit;
+        } else {
+            padctl |= bit;
+        }
+    }
+    sor.SOR_DP_PADCTL0.write(padctl);
+
+    sor.SOR_LANE_SEQ_CTL
+        .write(SOR_LANE_SEQ_CTL_TRIGGER | SOR_LANE_SEQ_CTL_SEQUENCE_DOWN);
+    wait_for_clear(&sor.SOR_LANE_SEQ_CTL, SOR_LANE_SEQ_CTL_TRIGGER)?;
+
+    // The pad macro runs now, switch SOR1 over to its clock.
+    let car = unsafe { Car::get() };
+    car.clk_source_sor1
+        .modify(|v| CLK_SOURCE_SOR1::SOR1_CLK_SEL0.set(v, 0));
+
+    Ok(())
+}
+
+/// Sets the training pattern on both ends of the link, 0 disables training.
+fn set_training_pattern(link: &LinkConfig, pattern: u8) -> Result<()> {
+    let sor = Sor::SOR1.registers();
+
+    let tpg = if pattern == 0 {
+        SOR_DP_TPG_CHANNEL_CODING | SOR_DP_TPG_SCRAMBLER_GALIOS
+    } else {
+        SOR_DP_TPG_CHANNEL_CODING | u32::from(pattern)
+    };
+    sor.SOR_DP_TPG
+        .write((0..link.lanes).fold(0, |value, lane| value | (tpg << (lane * 8))));
+
+    let dpcd = if pattern == 0 {
+        0
+    } else {
+        pattern | DPCD_SCRAMBLING_DISABLE
+    };
+    Dpaux::DPAUX1.write_byte(DPCD_TRAINING_PATTERN_SET, dpcd)
+}
+
+/// Applies drive levels on both ends of the link.
+fn set_drive_levels(link: &LinkConfig, levels: &[DriveLevel; 4]) -> Result<()> {
+    let sor = Sor::SOR1.registers();
+
+    let mut current = 0;
+    let mut preemphasis = 0;
+    let mut training_set = [0; 4];
+
+    for lane in 0..link.lanes as usize {
+        let level = levels[lane];
+        let (swing, pre) = (level.swing as usize, level.preemphasis as usize);
+
+        current |= u32::from(DRIVE_CURRENT[swing][pre]) << (lane * 8);
+        preemphasis |= u32::from(PREEMPHASIS[swing][pre]) << (lane * 8);
+        training_set[lane] = level.training_lane_set();
+    }
+
+    sor.SOR_LANE_DRIVE_CURRENT0.write(current);
+    sor.SOR_LANE_PREEMPHASIS0.write(preemphasis);
+    sor.SOR_LANE_POSTCURSOR0.write(0);
+
+    Dpaux::DPAUX1.write(
+        DPCD_TRAINING_LANE0_SET,
+        &training_set[..link.lanes as usize],
+    )
+}
+
+/// Reads the link status of the sink.
+fn read_link_status() -> Result<LinkStatus> {
+    let mut status = [0; 6];
+    Dpaux::DPAUX1.read(DPCD_LANE0_1_STATUS, &mut status)?;
+
+    Ok(LinkStatus(status))
+}
+
+/// Trains the link, first recovering the clock and then equalizing
+/// the channels, adjusting the drive levels as requested by the sink.
+fn train_link(link: &LinkConfig) -> Result<()> {
+    let mut levels = [DriveLevel::default(); 4];
+
+    // Configure the link on the sink.
+    let mut lane_count = link.lanes as u8;
+    if link.enhanced_framing {
+        lane_count |= DPCD_ENHANCED_FRAME_EN;
+    }
+    Dpaux::DPAUX1.write(DPCD_LINK_BW_SET, &[link.rate.value() as u8, lane_count])?;
+
+    for &(pattern, delay) in [(1, 100), (2, 400)].iter() {
+        set_training_pattern(link, pattern)?;
+
+        let mut trained = false;
+        for _ in 0..TRAINING_ATTEMPTS {
+            set_drive_levels(link, &levels)?;
+            usleep(delay);
+
+            let status = read_link_status()?;
+            trained = if pattern == 1 {
+                status.clock_recovered(link)
+            } else {
+                status.channel_equalized(link)
+            };
+
+            if trained {
+                break;
+            }
+
+            for lane in 0..link.lanes {
+                levels[lane as usize] = status.adjust_request(lane);
+            }
+        }
+
+        if !trained {
+            debug!("DP: link training failed with pattern {}", pattern);
+            set_training_pattern(link, 0)?;
+            return Err(Error::Display);
+        }
+    }
+
+    set_training_pattern(link, 0)
+}
+
+/// Configures the link layer of SOR1 for the mode.
+fn configure_link(link: &LinkConfig, tu: &TransferUnit) {
+    let sor = Sor::SOR1.registers();
+
+    let mut linkctl = SOR_DP_LINKCTL_ENABLE | (TU_SIZE << 2) | (link.lane_mask() << 16);
+    if link.enhanced_framing {
+        linkctl |= SOR_DP_LINKCTL_ENHANCED_FRAME;
+    }
+    sor.SOR_DP_LINKCTL0.write(linkctl);
+
+    let mut config = SOR_DP_CONFIG_DISPARITY_NEGATIVE
+        | SOR_DP_CONFIG_ACTIVESYM_ENABLE
+        | (tu.active_frac << 16)
+        | (tu.active_count << 8)
+        | tu.watermark;
+    if tu.active_polarity {
+        config |= SOR_DP_CONFIG_ACTIVESYM_POLARITY;
+    }
+    sor.SOR_DP_CONFIG0.write(config);
+}
+
+/// Programs the timings and the framebuffer window of the second
+/// display controller.
+fn configure_display_controller(mode: &Mode, framebuffer: u32) {
+    // Scale the framebuffer to the height of the mode, keeping its aspect.
+    let width = FRAMEBUFFER_WIDTH * mode.v_active / FRAMEBUFFER_HEIGHT;
+    let dda = (FRAMEBUFFER_HEIGHT << 12) / mode.v_active;
+
+    let config = [
+        config_table!(DC_CMD_DISPLAY_POWER_CONTROL, PW0_PW4_PM0_PM1_ENABLE),
+        config_table!(DC_DISP_DISP_TIMING_OPTIONS, 0),
+        config_table!(DC_DISP_REF_TO_SYNC, (1 << 16) | 1),
+        config_table!(
+            DC_DISP_SYNC_WIDTH,
+            (mode.v_sync_width << 16) | mode.h_sync_width
+        ),
+        config_table!(
+            DC_DISP_BACK_PORCH,
+            (mode.v_back_porch << 16) | mode.h_back_porch
+        ),
+        config_table!(DC_DISP_DISP_ACTIVE, (mode.v_active << 16) | mode.h_active),
+        config_table!(
+            DC_DISP_FRONT_PORCH,
+            (mode.v_front_porch << 16) | mode.h_front_porch
+        ),
+        config_table!(DC_DISP_DISP_CLOCK_CONTROL, 0),
+        config_table!(DC_DISP_DISP_INTERFACE_CONTROL, 0),
+        config_table!(DC_DISP_DISP_COLOR_CONTROL, BASE_COLOR_SIZE_888),
+        config_table!(DC_DISP_BLEND_BACKGROUND_COLOR, 0),
+        config_table!(DC_CMD_DISPLAY_WINDOW_HEADER, WINDOW_A_SELECT),
+        config_table!(DC_WIN_WIN_OPTIONS, 0),
+        config_table!(DC_WIN_COLOR_DEPTH, COLOR_DEPTH_B8G8R8A8),
+        config_table!(DC_WIN_POSITION, (mode.h_active - width) / 2),
+        config_table!(DC_WIN_SIZE, (mode.v_active << 16) | width),
+        config_table!(
+            DC_WIN_PRESCALED_SIZE,
+            (FRAMEBUFFER_HEIGHT << 16) | (FRAMEBUFFER_WIDTH * 4)
+        ),
+        config_table!(DC_WIN_H_INITIAL_DDA, 0),
+        config_table!(DC_WIN_V_INITIAL_DDA, 0),
+        config_table!(DC_WIN_DDA_INCREMENT, (dda << 16) | dda),
+        config_table!(DC_WIN_LINE_STRIDE, FRAMEBUFFER_STRIDE),
+        config_table!(DC_WIN_BUFFER_CONTROL, 0),
+        config_table!(DC_WINBUF_SURFACE_KIND, 0),
+        config_table!(DC_WINBUF_START_ADDR, framebuffer),
+        config_table!(DC_WINBUF_ADDR_H_OFFSET, 0),
+        config_table!(DC_WINBUF_ADDR_V_OFFSET, 0),
+        config_table!(
+            DC_WIN_WIN_OPTIONS,
+            WIN_ENABLE | H_FILTER_ENABLE | V_FILTER_ENABLE
+        ),
+        config_table!(DC_DISP_DISP_WIN_OPTIONS, SOR1_ENABLE),
+        config_table!(DC_CMD_DISPLAY_COMMAND, DISP_CTRL_MODE_C_DISPLAY),
+        config_table!(DC_CMD_STATE_CONTROL, GENERAL_UPDATE | WIN_A_UPDATE),
+        config_table!(DC_CMD_STATE_CONTROL, GENERAL_ACT_REQ | WIN_A_ACT_REQ),
+    ];
+
+    unsafe {
+        execute(DC_B_BASE as *mut u32, &config);
+    }
+}
+
+/// Attaches SOR1 to the second display controller and powers it up.
+fn attach(mode: &Mode) -> Result<()> {
+    let sor = Sor::SOR1.registers();
+
+    // Head 1 is the second display controller.
+    let head = 1;
+    let h_sync_end = mode.h_sync_width - 1;
+    let v_sync_end = mode.v_sync_width - 1;
+    let h_blank_end = h_sync_end + mode.h_back_porch;
+    let v_blank_end = v_sync_end + mode.v_back_porch;
+
+    sor.SOR_STATE1.write(
+        SOR_STATE1_OWNER_HEAD1
+            | SOR_STATE1_CRC_MODE_COMPLETE
+            | SOR_STATE1_PROTOCOL_DP_A
+            | SOR_STATE1_PIXELDEPTH_BPP_24_444,
+    );
+    sor.SOR_HEAD_STATE0[head].write(0);
+    sor.SOR_HEAD_STATE1[head].write((mode.v_total() << 16) | mode.h_total());
+    sor.SOR_HEAD_STATE2[head].write((v_sync_end << 16) | h_sync_end);
+    sor.SOR_HEAD_STATE3[head].write((v_blank_end << 16) | h_blank_end);
+    sor.SOR_HEAD_STATE4[head]
+        .write(((v_blank_end + mode.v_active) << 16) | (h_blank_end + mode.h_active));
+    sor.SOR_HEAD_STATE5[head].write(1);
+    sor.SOR_STATE0.write(SOR_STATE_UPDATE);
+
+    sor.SOR_PWR.write(SOR_PWR_TRIGGER | SOR_PWR_NORMAL_STATE_PU);
+    wait_for_clear(&sor.SOR_PWR, SOR_PWR_TRIGGER)?;
+
+    sor.SOR_SUPER_STATE1.write(
+        SOR_SUPER_STATE1_ATTACHED
+            | SOR_SUPER_STATE1_ORMODE_NORMAL
+            | SOR_SUPER_STATE1_HEAD_MODE_AWAKE,
+    );
+    sor.SOR_SUPER_STATE0.write(SOR_STATE_UPDATE);
+
+    Ok(())
+}
+
+/// Brings up the DisplayPort output in [`Mode::FULL_HD`] and scans out
+/// the framebuffer at the given address.
+///
+/// Fails with [`Error::Display`] if no sink is connected, it can't carry
+/// the mode or link training fails.
+///
+/// [`Mode::FULL_HD`]: struct.Mode.html#associatedconstant.FULL_HD
+/// [`Error::Display`]: ../enum.Error.html#variant.Display
+pub fn initialize_external(framebuffer: u32) -> Result<LinkConfig> {
+    let mut mode = Mode::FULL_HD;
+    mode.pixel_clock = PIXEL_CLOCK;
+
+    let mut dpaux = Dpaux::DPAUX1;
+    dpaux.init();
+    if !dpaux.is_connected() {
+        return Err(Error::Display);
+    }
+
+    // Wake the sink up and read its capabilities.
+    dpaux.write_byte(DPCD_SET_POWER, DPCD_SET_POWER_D0)?;
+    let mut caps = [0; 3];
+    dpaux.read(DPCD_REV, &mut caps)?;
+
+    let link = LinkConfig::select(caps[1], caps[2], &mode).ok_or(Error::Display)?;
+    let tu = TransferUnit::compute(&mode, &link).ok_or(Error::Display)?;
+    debug!(
+        "DP: DPCD {:#X}, {:?} with {} lane(s)",
+        caps[0], link.rate, link.lanes
+    );
+
+    enable_clocks()?;
+    power_up_link(&link)?;
+    train_link(&link)?;
+    configure_link(&link, &tu);
+
+    configure_display_controller(&mode, framebuffer);
+    attach(&mode)?;
+
+    Ok(link)
+}
+
+/// Shuts the DisplayPort output down.
+pub fn finish_external() {
+    let sor = Sor::SOR1.registers();
+
+    // Detach SOR1 and stop the display controller.
+    sor.SOR_SUPER_STATE1.write(0);
+    sor.SOR_SUPER_STATE0.write(SOR_STATE_UPDATE);
+    dc_register(DC_DISP_DISP_WIN_OPTIONS).write(0);
+    dc_register(DC_CMD_DISPLAY_COMMAND).write(0);
+    dc_register(DC_CMD_STATE_CONTROL).write(GENERAL_UPDATE);
+    dc_register(DC_CMD_STATE_CONTROL).write(GENERAL_ACT_REQ);
+
+    // Power the link down.
+    sor.SOR_DP_LINKCTL0.write(0);
+    sor.SOR_PWR.write(SOR_PWR_TRIGGER);
+    sor.SOR_PLL2
+        .write(sor.SOR_PLL2.read() | SOR_PLL2_PORT_POWERDOWN | SOR_PLL2_BANDGAP_POWERDOWN);
+    sor.SOR_PLL0
+        .write(sor.SOR_PLL0.read() | SOR_PLL0_PWR | SOR_PLL0_VCOPD);
+
+    for clock in &mut [Clock::SOR1, Clock::SOR_SAFE, Clock::DISP2] {
+        clock.disable();
+    }
+    Pll::DP.disable();
+    Pll::D2.disable();
+    let mut dpaux = Dpaux::DPAUX1;
+    dpaux.finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the selection of the link configuration.
+    #[test]
+    fn select_link() {
+        let mode = Mode::FULL_HD;
+
+        // 1080p needs all four lanes at RBR, but only two at HBR.
+        assert_eq!(
+            LinkConfig::select(0x0A, 0x84, &mode),
+            Some(LinkConfig {
+                rate: LinkRate::Rbr,
+                lanes: 4,
+                enhanced_framing: true,
+            })
+        );
+        assert_eq!(
+            LinkConfig::select(0x14, 0x02, &mode),
+            Some(LinkConfig {
+                rate: LinkRate::Hbr,
+                lanes: 2,
+                enhanced_framing: false,
+            })
+        );
+        assert_eq!(LinkConfig::select(0x06, 0x02, &mode), None);
+    }
+
+    /// Tests the computation of the transfer unit fill.
+    #[test]
+    fn transfer_unit() {
+        let link = LinkConfig {
+            rate: LinkRate::Rbr,
+            lanes: 4,
+            enhanced_framing: true,
+        };
+        let tu = TransferUnit::compute(&Mode::FULL_HD, &link).unwrap();
+
+        // 148.5MHz * 24 bits on 4 * 1.62Gbps fills 68.7% of the link.
+        assert_eq!(tu.active_count, 43);
+        assert!(tu.active_polarity);
+        assert_eq!(tu.watermark, 18);
+    }
+
+    /// Tests the decoding of the link status and adjust requests.
+    #[test]
+    fn link_status() {
+        let link = LinkConfig {
+            rate: LinkRate::Hbr,
+            lanes: 2,
+            enhanced_framing: true,
+        };
+
+        let status = LinkStatus([0x11, 0x00, 0x00, 0x00, 0x96, 0x00]);
+        assert!(status.clock_recovered(&link));
+        assert!(!status.channel_equalized(&link));
+        assert_eq!(
+            status.adjust_request(0),
+            DriveLevel {
+                swing: 2,
+                preemphasis: 1,
+            }
+        );
+        // Swing 1 with pre-emphasis 2 is the maximum level.
+        assert_eq!(status.adjust_request(1).training_lane_set(), 0x31);
+
+        let status = LinkStatus([0x77, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        assert!(status.channel_equalized(&link));
+    }
+}
@@ -0,0 +1,255 @@
+//! DSI command interface to the internal panel.
+//!
+//! # Description
+//!
+//! Besides streaming pixels, the DSI host controller can send MIPI Display
+//! Command Set (DCS) commands to the panel and read back its replies. This
+//! is how the panel is identified and brought out of sleep, and how the
+//! vendor-specific initialization tables are applied.
+//!
+//! # Implementation
+//!
+//! - [`dcs_write`] sends a short DCS command with at most one parameter,
+//! [`dcs_write_long`] sends a command with an arbitrary payload.
+//!
+//! - [`dcs_read`] sends a DCS read command, hands the bus over to the
+//! panel and parses its reply into a buffer.
+//!
+//! - [`read_panel_id`] reads the ID of the panel through
+//! [`DCS_GET_DISPLAY_ID`], which maps to a [`Panel`].
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::display::{dsi, Panel};
+//!
+//! fn main() {
+//!     let panel = Panel::from_id(dsi::read_panel_id().unwrap());
+//!
+//!     dsi::dcs_write(dsi::DCS_EXIT_SLEEP_MODE, None).unwrap();
+//! }
+//! ```
+//!
+//! [`dcs_write`]: fn.dcs_write.html
+//! [`dcs_write_long`]: fn.dcs_write_long.html
+//! [`dcs_read`]: fn.dcs_read.html
+//! [`read_panel_id`]: fn.read_panel_id.html
+//! [`DCS_GET_DISPLAY_ID`]: constant.DCS_GET_DISPLAY_ID.html
+//! [`Panel`]: ../enum.Panel.html
+
+use mirage_mmio::Mmio;
+
+use super::display::DSI_BASE;
+use crate::{
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// Reads the ID of the panel.
+pub const DCS_GET_DISPLAY_ID: u8 = 0x04;
+/// Puts the panel into sleep mode.
+pub const DCS_ENTER_SLEEP_MODE: u8 = 0x10;
+/// Brings the panel out of sleep mode.
+pub const DCS_EXIT_SLEEP_MODE: u8 = 0x11;
+/// Stops showing the frame data.
+pub const DCS_SET_DISPLAY_OFF: u8 = 0x28;
+/// Starts showing the frame data.
+pub const DCS_SET_DISPLAY_ON: u8 = 0x29;
+
+/// The data type of a short DCS write without parameter.
+const DSI_DCS_SHORT_WRITE: u32 = 0x05;
+/// The data type of a short DCS write with one parameter.
+const DSI_DCS_SHORT_WRITE_PARAM: u32 = 0x15;
+/// The data type of a DCS read request.
+const DSI_DCS_READ: u32 = 0x06;
+/// The data type of a maximum return packet size request.
+const DSI_SET_MAXIMUM_RETURN_PACKET_SIZE: u32 = 0x37;
+/// The data type of a long DCS write.
+const DSI_DCS_LONG_WRITE: u32 = 0x39;
+
+/// The data type of an acknowledgement from the panel.
+const DSI_ACK: u32 = 0x84;
+/// The data type of the trigger message ending a reply.
+const DSI_ESCAPE: u32 = 0x87;
+/// The data type of a long DCS read response.
+const DSI_DCS_LONG_READ_RESPONSE: u32 = 0x1C;
+/// The data type of a short DCS read response with one byte.
+const DSI_DCS_SHORT_READ_RESPONSE_1: u32 = 0x21;
+/// The data type of a short DCS read response with two bytes.
+const DSI_DCS_SHORT_READ_RESPONSE_2: u32 = 0x22;
+
+/// The `DSI_RD_DATA` register.
+const DSI_RD_DATA: u32 = 0x9;
+/// The `DSI_WR_DATA` register.
+const DSI_WR_DATA: u32 = 0xA;
+/// The `DSI_HOST_CONTROL` register.
+const DSI_HOST_CONTROL: u32 = 0xF;
+/// The `DSI_TRIGGER` register.
+const DSI_TRIGGER: u32 = 0x13;
+/// The `DSI_STATUS` register.
+const DSI_STATUS: u32 = 0x15;
+
+/// Transmits the pending video frame.
+const DSI_TRIGGER_VIDEO: u32 = 1 << 0;
+/// Transmits the packets in the host FIFO.
+const DSI_TRIGGER_HOST: u32 = 1 << 1;
+/// Hands the bus over to the panel immediately, in `DSI_HOST_CONTROL`.
+const DSI_HOST_CONTROL_IMM_BTA: u32 = 1 << 3;
+/// The host control value requesting a reply from the panel.
+const DSI_HOST_CONTROL_READ: u32 = 0x2003 | DSI_HOST_CONTROL_IMM_BTA;
+/// The mask of the word count of the read FIFO in `DSI_STATUS`.
+const DSI_STATUS_RD_FIFO_COUNT: u32 = 0x1F;
+
+/// The time a packet may take to be sent in microseconds.
+const WRITE_TIMEOUT: u32 = 250_000;
+/// The time the panel may take to reply in microseconds.
+const READ_TIMEOUT: u32 = 150_000;
+
+/// The maximum number of bytes that can be read at once.
+pub const MAX_READ_SIZE: usize = 64;
+
+/// Gets a DSI register.
+fn register(offset: u32) -> &'static Mmio<u32> {
+    unsafe { &*((DSI_BASE + offset * 4) as *const Mmio<u32>) }
+}
+
+/// Waits for the bits in `mask` of a DSI register to clear.
+fn wait(timeout: u32, offset: u32, mask: u32) -> Result<()> {
+    let register = register(offset);
+
+    wait_for(|| register.read() & mask == 0, timeout)?;
+    usleep(5);
+
+    Ok(())
+}
+
+/// Sends the packets written to the host FIFO.
+fn trigger() -> Result<()> {
+    register(DSI_TRIGGER).write(DSI_TRIGGER_HOST);
+
+    wait(
+        WRITE_TIMEOUT,
+        DSI_TRIGGER,
+        DSI_TRIGGER_HOST | DSI_TRIGGER_VIDEO,
+    )
+}
+
+/// Sends a short packet with a two byte payload.
+fn write_short(data_type: u32, payload: u16) -> Result<()> {
+    register(DSI_WR_DATA).write(u32::from(payload) << 8 | data_type);
+
+    trigger()
+}
+
+/// Sends a short DCS command with an optional parameter.
+pub fn dcs_write(command: u8, parameter: Option<u8>) -> Result<()> {
+    match parameter {
+        Some(parameter) => write_short(
+            DSI_DCS_SHORT_WRITE_PARAM,
+            u16::from(command) | u16::from(parameter) << 8,
+        ),
+        None => write_short(DSI_DCS_SHORT_WRITE, u16::from(command)),
+    }
+}
+
+/// Sends a long DCS command with the given parameters.
+pub fn dcs_write_long(command: u8, parameters: &[u8]) -> Result<()> {
+    if parameters.len() >= 0xFFFF {
+        return Err(Error::InvalidArgument);
+    }
+
+    let wr_data = register(DSI_WR_DATA);
+    let length = parameters.len() as u32 + 1;
+    wr_data.write(length << 8 | DSI_DCS_LONG_WRITE);
+
+    // The command is the first byte of the payload.
+    let mut word = u32::from(command);
+    for (i, &byte) in parameters.iter().enumerate() {
+        let shift = ((i + 1) % 4) * 8;
+        if shift == 0 {
+            wr_data.write(word);
+            word = 0;
+        }
+
+        word |= u32::from(byte) << shift;
+    }
+    wr_data.write(word);
+
+    trigger()
+}
+
+/// Sends a DCS read command and reads the reply of the panel into `buffer`.
+///
+/// Returns the number of bytes the panel replied with. Fails with
+/// [`Error::Timeout`] if the panel doesn't reply and with
+/// [`Error::Display`] if the reply is malformed.
+///
+/// [`Error::Timeout`]: ../../enum.Error.html#variant.Timeout
+/// [`Error::Display`]: ../../enum.Error.html#variant.Display
+pub fn dcs_read(command: u8, buffer: &mut [u8]) -> Result<usize> {
+    if buffer.is_empty() || buffer.len() > MAX_READ_SIZE {
+        return Err(Error::InvalidArgument);
+    }
+
+    write_short(DSI_SET_MAXIMUM_RETURN_PACKET_SIZE, buffer.len() as u16)?;
+    write_short(DSI_DCS_READ, u16::from(command))?;
+
+    // Hand the bus over to the panel for its reply.
+    register(DSI_HOST_CONTROL).write(DSI_HOST_CONTROL_READ);
+    wait(READ_TIMEOUT, DSI_HOST_CONTROL, DSI_HOST_CONTROL_IMM_BTA)?;
+
+    usleep(5_000);
+
+    let rd_data = register(DSI_RD_DATA);
+    let mut count = register(DSI_STATUS).read() & DSI_STATUS_RD_FIFO_COUNT;
+
+    while count > 0 {
+        let header = rd_data.read();
+        count -= 1;
+
+        match header & 0xFF {
+            DSI_ACK | DSI_ESCAPE => continue,
+            DSI_DCS_SHORT_READ_RESPONSE_1 | DSI_DCS_SHORT_READ_RESPONSE_2 => {
+                let size = if header & 0xFF == DSI_DCS_SHORT_READ_RESPONSE_1 {
+                    1
+                } else {
+                    2
+                };
+                let size = size.min(buffer.len());
+                buffer[..size].copy_from_slice(&header.to_le_bytes()[1..1 + size]);
+
+                return Ok(size);
+            }
+            DSI_DCS_LONG_READ_RESPONSE => {
+                let size = ((header >> 8) & 0xFFFF) as usize;
+                if size > buffer.len() || (size + 3) / 4 > count as usize {
+                    return Err(Error::Display);
+                }
+
+                for chunk in buffer[..size].chunks_mut(4) {
+                    let word = rd_data.read().to_le_bytes();
+                    chunk.copy_from_slice(&word[..chunk.len()]);
+                }
+
+                return Ok(size);
+            }
+            _ => return Err(Error::Display),
+        }
+    }
+
+    Err(Error::Display)
+}
+
+/// Reads the ID of the panel.
+///
+/// The ID consists of the vendor in the low byte and the panel revision
+/// in the high byte.
+pub fn read_panel_id() -> Result<u32> {
+    let mut id = [0; 3];
+
+    if dcs_read(DCS_GET_DISPLAY_ID, &mut id)? != id.len() {
+        return Err(Error::Display);
+    }
+
+    Ok(u32::from(id[2]) << 8 | u32::from(id[0]))
+}
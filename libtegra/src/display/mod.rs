@@ -7,11 +7,37 @@
 //! panel and an external HDMI TV or DP monitor. Other configurations are possible
 //! such as two local panels. Each display controller can run at a different clock
 //! rate and drive a different resolution panel.
+//!
+//! On the Switch, the first display controller drives the internal panel over
+//! DSI while the second one drives the dock over DisplayPort, see the `dp`
+//! functions like [`initialize_external`].
+//!
+//! The internal panel is made by one of several vendors depending on the
+//! revision of the Switch. [`initialize`] identifies it over DSI and follows
+//! its power sequence, the result is available through [`panel`]. The
+//! [`dsi`] module provides the underlying DCS command interface.
+//!
+//! [`initialize_external`]: fn.initialize_external.html
+//! [`initialize`]: fn.initialize.html
+//! [`panel`]: fn.panel.html
+//! [`dsi`]: dsi/index.html
 
+pub use bmp::{draw_bmp, render_bmp, render_splash, show_splash, Bmp};
+pub use canvas::{Canvas, Framebuffer};
 pub use display::*;
+pub use dp::{finish_external, initialize_external, is_connected, LinkConfig, LinkRate, Mode};
 pub use writer::*;
-pub use display_config::FRAMEBUFFER_ADDRESS;
+pub use display_config::{FRAMEBUFFER_ADDRESS, FRAMEBUFFER_SIZE};
+pub use panic_screen::{render_panic_screen, show_panic_screen};
+pub use qr::{QrCode, QR_CAPACITY, QR_SIZE};
 
+mod bmp;
+mod canvas;
 mod display;
+#[macro_use]
 mod display_config;
+mod dp;
+pub mod dsi;
+mod panic_screen;
+mod qr;
 mod writer;
@@ -0,0 +1,153 @@
+//! Panic screen
+//!
+//! # Description
+//!
+//! Renders a fatal error report onto the framebuffer, consisting of
+//! an error code, the panic message, a QR code with diagnostic data
+//! and instructions on how to reboot the console.
+//!
+//! The screen is drawn directly into the framebuffer without relying
+//! on any global state, so it can be used from within a panic handler.
+//! The display must have been initialized beforehand.
+//!
+//! [`render_panic_screen`] draws the same screen onto any [`Canvas`],
+//! which allows previewing it on a development host.
+//!
+//! [`render_panic_screen`]: fn.render_panic_screen.html
+//! [`Canvas`]: trait.Canvas.html
+
+use core::fmt;
+
+use super::{
+    canvas::{Canvas, Framebuffer},
+    qr::{QrCode, QR_SIZE},
+    writer::GFX_FONT,
+};
+
+/// The background color of the panic screen.
+const PANIC_BACKGROUND: u32 = 0xFF8B_2A12;
+/// The foreground color of the panic screen.
+const PANIC_FOREGROUND: u32 = 0xFFFF_FFFF;
+
+/// The horizontal and vertical margin of the screen contents.
+const MARGIN: u32 = 32;
+/// The size of a single QR module in pixels.
+const QR_SCALE: u32 = 8;
+/// The width of the quiet zone around the QR code in modules.
+const QR_QUIET_ZONE: u32 = 4;
+
+/// A text cursor that draws scaled glyphs into a bounded area.
+struct Console<'a, C: Canvas> {
+    canvas: &'a mut C,
+    x: u32,
+    y: u32,
+    scale: u32,
+    color: u32,
+}
+
+impl<'a, C: Canvas> Console<'a, C> {
+    /// Draws a single character at the cursor and advances it.
+    fn draw_char(&mut self, character: char) {
+        let glyph_size = 8 * self.scale;
+
+        if character == '\n' || self.x + glyph_size > self.canvas.width() - MARGIN {
+            self.x = MARGIN;
+            self.y += glyph_size + self.scale * 2;
+
+            if character == '\n' {
+                return;
+            }
+        }
+
+        let index = match character as u32 {
+            c @ 32..=126 => (c - 32) as usize,
+            _ => ('?' as u32 - 32) as usize,
+        };
+
+        for (row, byte) in GFX_FONT[index].iter().enumerate() {
+            for column in 0..8 {
+                if (byte >> column) & 1 != 0 {
+                    self.canvas.fill_rect(
+                        self.x + column * self.scale,
+                        self.y + row as u32 * self.scale,
+                        self.scale,
+                        self.scale,
+                        self.color,
+                    );
+                }
+            }
+        }
+
+        self.x += glyph_size;
+    }
+}
+
+impl<'a, C: Canvas> fmt::Write for Console<'a, C> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.draw_char(c);
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders a panic screen with the given error code, message and
+/// diagnostic data, which is encoded into a QR code.
+pub fn show_panic_screen(code: u32, message: fmt::Arguments<'_>, diagnostics: &[u8]) {
+    render_panic_screen(&mut Framebuffer::new(), code, message, diagnostics);
+}
+
+/// Renders a panic screen onto the given canvas.
+pub fn render_panic_screen<C: Canvas>(
+    canvas: &mut C,
+    code: u32,
+    message: fmt::Arguments<'_>,
+    diagnostics: &[u8],
+) {
+    use core::fmt::Write;
+
+    canvas.clear(PANIC_BACKGROUND);
+
+    let mut console = Console {
+        canvas,
+        x: MARGIN,
+        y: MARGIN,
+        scale: 4,
+        color: PANIC_FOREGROUND,
+    };
+    write!(&mut console, ":(\n").ok();
+
+    console.scale = 3;
+    write!(&mut console, "Mirage crashed.\nError {:08X}\n\n", code).ok();
+
+    console.scale = 2;
+    write!(&mut console, "{}\n\n", message).ok();
+
+    // Draw the QR code including its quiet zone.
+    let qr = QrCode::encode(diagnostics);
+    let qr_x = MARGIN;
+    let qr_y = console.y + MARGIN;
+    let qr_extent = (QR_SIZE as u32 + 2 * QR_QUIET_ZONE) * QR_SCALE;
+
+    console
+        .canvas
+        .fill_rect(qr_x, qr_y, qr_extent, qr_extent, 0xFFFF_FFFF);
+    for y in 0..QR_SIZE {
+        for x in 0..QR_SIZE {
+            if qr.is_dark(x, y) {
+                console.canvas.fill_rect(
+                    qr_x + (x as u32 + QR_QUIET_ZONE) * QR_SCALE,
+                    qr_y + (y as u32 + QR_QUIET_ZONE) * QR_SCALE,
+                    QR_SCALE,
+                    QR_SCALE,
+                    0xFF00_0000,
+                );
+            }
+        }
+    }
+
+    console.x = MARGIN;
+    console.y = qr_y + qr_extent + MARGIN;
+    write!(&mut console, "Press POWER to reboot to RCM.").ok();
+}
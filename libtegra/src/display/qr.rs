@@ -0,0 +1,259 @@
+//! Minimal QR code encoder
+//!
+//! # Description
+//!
+//! Encodes short binary payloads into version 2 QR codes (25x25 modules)
+//! with error correction level L, which is enough to carry 32 bytes of
+//! diagnostic data. Masking always uses pattern 0, since the code is only
+//! meant to be scanned off a display and doesn't need an optimal penalty
+//! score.
+
+/// The amount of modules per side of a version 2 QR code.
+pub const QR_SIZE: usize = 25;
+/// The maximum amount of bytes that can be encoded.
+pub const QR_CAPACITY: usize = 32;
+
+/// The amount of data codewords for version 2-L.
+const DATA_CODEWORDS: usize = 34;
+/// The amount of error correction codewords for version 2-L.
+const ECC_CODEWORDS: usize = 10;
+
+/// The BCH-encoded and masked format bits for error correction level L
+/// with mask pattern 0.
+const FORMAT_BITS: u32 = 0b111_0111_1100_0100;
+
+/// Multiplies two values in GF(2^8) modulo `x^8 + x^4 + x^3 + x^2 + 1`.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= u32::from((y >> i) & 1) * u32::from(x);
+    }
+
+    z as u8
+}
+
+/// Computes the Reed-Solomon error correction codewords for the data.
+fn compute_ecc(data: &[u8; DATA_CODEWORDS]) -> [u8; ECC_CODEWORDS] {
+    // Compute the generator polynomial, without the leading term.
+    let mut divisor = [0; ECC_CODEWORDS];
+    divisor[ECC_CODEWORDS - 1] = 1;
+
+    let mut root = 1;
+    for _ in 0..ECC_CODEWORDS {
+        for j in 0..ECC_CODEWORDS {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < ECC_CODEWORDS {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+
+        root = gf_mul(root, 0x02);
+    }
+
+    // Polynomial division.
+    let mut remainder = [0; ECC_CODEWORDS];
+    for byte in data.iter() {
+        let factor = byte ^ remainder[0];
+
+        remainder.copy_within(1.., 0);
+        remainder[ECC_CODEWORDS - 1] = 0;
+
+        for (r, d) in remainder.iter_mut().zip(divisor.iter()) {
+            *r ^= gf_mul(*d, factor);
+        }
+    }
+
+    remainder
+}
+
+/// Representation of an encoded QR code.
+pub struct QrCode {
+    /// The module colors, indexed by row and column. `true` is dark.
+    modules: [[bool; QR_SIZE]; QR_SIZE],
+    /// Whether a module belongs to a function pattern.
+    function: [[bool; QR_SIZE]; QR_SIZE],
+}
+
+impl QrCode {
+    /// Encodes the given data in byte mode.
+    ///
+    /// Data exceeding the [`QR_CAPACITY`] is truncated.
+    ///
+    /// [`QR_CAPACITY`]: constant.QR_CAPACITY.html
+    pub fn encode(data: &[u8]) -> Self {
+        let data = &data[..data.len().min(QR_CAPACITY)];
+
+        let mut code = QrCode {
+            modules: [[false; QR_SIZE]; QR_SIZE],
+            function: [[false; QR_SIZE]; QR_SIZE],
+        };
+
+        code.draw_function_patterns();
+
+        // Build the data codewords: mode, length, data, terminator and padding.
+        let mut codewords = [0; DATA_CODEWORDS + ECC_CODEWORDS];
+        let mut bits = BitWriter::new(&mut codewords[..DATA_CODEWORDS]);
+
+        bits.append(0b0100, 4);
+        bits.append(data.len() as u32, 8);
+        for byte in data.iter() {
+            bits.append(u32::from(*byte), 8);
+        }
+        bits.append(0, 4_usize.min(DATA_CODEWORDS * 8 - bits.position));
+
+        let mut position = (bits.position + 7) / 8;
+        let mut pad = 0xEC;
+        while position < DATA_CODEWORDS {
+            codewords[position] = pad;
+            pad ^= 0xEC ^ 0x11;
+            position += 1;
+        }
+
+        let mut data_codewords = [0; DATA_CODEWORDS];
+        data_codewords.copy_from_slice(&codewords[..DATA_CODEWORDS]);
+        codewords[DATA_CODEWORDS..].copy_from_slice(&compute_ecc(&data_codewords));
+
+        code.draw_codewords(&codewords);
+        code.apply_mask();
+
+        code
+    }
+
+    /// Checks whether the module at the given position is dark.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y][x]
+    }
+
+    /// Sets a module that belongs to a function pattern.
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y][x] = dark;
+        self.function[y][x] = true;
+    }
+
+    /// Draws the finder, timing and alignment patterns and format bits.
+    fn draw_function_patterns(&mut self) {
+        // Timing patterns.
+        for i in 0..QR_SIZE {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        // Finder patterns, including separators.
+        for &(x, y) in [(3, 3), (QR_SIZE - 4, 3), (3, QR_SIZE - 4)].iter() {
+            for dy in -4..=4_isize {
+                for dx in -4..=4_isize {
+                    let xx = x as isize + dx;
+                    let yy = y as isize + dy;
+
+                    if xx >= 0 && xx < QR_SIZE as isize && yy >= 0 && yy < QR_SIZE as isize {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        // The single alignment pattern of version 2.
+        for dy in -2..=2_isize {
+            for dx in -2..=2_isize {
+                self.set_function(
+                    (18 + dx) as usize,
+                    (18 + dy) as usize,
+                    dx.abs().max(dy.abs()) != 1,
+                );
+            }
+        }
+
+        // Format bits.
+        let bit = |i: usize| (FORMAT_BITS >> i) & 1 != 0;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(QR_SIZE - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, QR_SIZE - 15 + i, bit(i));
+        }
+
+        // The dark module.
+        self.set_function(8, QR_SIZE - 8, true);
+    }
+
+    /// Places the codewords in the zig-zag pattern.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut i = 0;
+        let mut right = QR_SIZE as isize - 1;
+
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+
+            for vertical in 0..QR_SIZE {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        QR_SIZE - 1 - vertical
+                    } else {
+                        vertical
+                    };
+
+                    if !self.function[y][x] && i < codewords.len() * 8 {
+                        self.modules[y][x] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+
+            right -= 2;
+        }
+    }
+
+    /// Applies mask pattern 0 to all data modules.
+    fn apply_mask(&mut self) {
+        for y in 0..QR_SIZE {
+            for x in 0..QR_SIZE {
+                if !self.function[y][x] && (x + y) % 2 == 0 {
+                    self.modules[y][x] = !self.modules[y][x];
+                }
+            }
+        }
+    }
+}
+
+/// Helper to append bit sequences to a codeword buffer.
+struct BitWriter<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        BitWriter {
+            buffer,
+            position: 0,
+        }
+    }
+
+    /// Appends the lowest `length` bits of `value`, MSB first.
+    fn append(&mut self, value: u32, length: usize) {
+        for i in (0..length).rev() {
+            if (value >> i) & 1 != 0 {
+                self.buffer[self.position >> 3] |= 1 << (7 - (self.position & 7));
+            }
+
+            self.position += 1;
+        }
+    }
+}
@@ -23,9 +23,10 @@
 use core::{convert::TryFrom, fmt};
 
 use super::FRAMEBUFFER_ADDRESS;
+use crate::{Error, Result};
 
 /// Representations of printable characters.
-const GFX_FONT: [[u8; 8]; 95] = [
+pub(super) const GFX_FONT: [[u8; 8]; 95] = [
     [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // Char 032 ( )
     [0x00, 0x30, 0x30, 0x18, 0x18, 0x00, 0x0C, 0x00], // Char 033 (!)
     [0x00, 0x22, 0x22, 0x22, 0x00, 0x00, 0x00, 0x00], // Char 034 (")
@@ -129,14 +130,14 @@ const GFX_FONT: [[u8; 8]; 95] = [
 const WRITER: Writer = Writer::new();
 
 /// The display height supported by the framebuffer.
-const FRAMEBUFFER_HEIGHT: u32 = 1280;
+pub(super) const FRAMEBUFFER_HEIGHT: u32 = 1280;
 /// The display width supported by the framebuffer.
-const FRAMEBUFFER_WIDTH: u32 = 720;
+pub(super) const FRAMEBUFFER_WIDTH: u32 = 720;
 /// The GFX stride for the framebuffer area.
-const GFX_STRIDE: u32 = 720;
+pub(super) const GFX_STRIDE: u32 = 720;
 
 /// Interface to the framebuffer for drawing contents to the screen.
-struct Writer {
+pub(crate) struct Writer {
     /// A mutable pointer to the framebuffer.
     framebuffer: *mut u32,
     /// The foreground color of the framebuffer area.
@@ -154,7 +155,7 @@ impl Writer {
     /// Creates a new instance of the [`Writer`] with default values.
     ///
     /// [`Writer`]: struct.Writer.html
-    const fn new() -> Self {
+    pub(crate) const fn new() -> Self {
         Self {
             framebuffer: FRAMEBUFFER_ADDRESS as *mut u32,
             foreground_color: 0xFFCC_CCCC,
@@ -167,7 +168,7 @@ impl Writer {
 
     /// Writes a single character into the framebuffer at the current position.
     /// **Warning:** The character must be in a range between 32 and 126.
-    pub fn write_char(&mut self, character: char) -> Result<char, ()> {
+    pub fn write_char(&mut self, character: char) -> Result<char> {
         if character == '\n' {
             self.new_line();
             return Ok(character);
@@ -177,10 +178,10 @@ impl Writer {
 
         // Check if the character is in the allowed range and thus printable.
         if char_num < 32 || char_num > 126 {
-            return Err(());
+            return Err(Error::Display);
         }
 
-        let char_buf = &GFX_FONT[8 * (char_num as usize - 32)];
+        let char_buf = &GFX_FONT[char_num as usize - 32];
         let mut framebuffer =
             self.framebuffer.wrapping_offset((self.x + self.y * GFX_STRIDE) as isize);
 
@@ -209,6 +210,11 @@ impl Writer {
         Ok(character)
     }
 
+    /// Sets the color of the characters written from now on.
+    pub fn set_foreground_color(&mut self, color: u32) {
+        self.foreground_color = color;
+    }
+
     /// Puts a line break at the current position and continues in the next line.
     pub fn new_line(&mut self) {
         self.x = 0;
@@ -0,0 +1,275 @@
+//! Accelerated memory-to-memory copies.
+//!
+//! # Description
+//!
+//! Payloads like a stage-2 bootloader are several megabytes in size and
+//! copying them around byte by byte on the BPMP takes a noticeable amount
+//! of time. The AHB DMA controller can move memory in the background at
+//! a much higher rate, while the CPU is free to update the UI.
+//!
+//! # Implementation
+//!
+//! - [`memcpy`] copies through an AHB DMA channel whenever possible and
+//! falls back to a word-wise CPU copy otherwise, e.g. for small or
+//! unaligned buffers or memory outside of IRAM and DRAM.
+//!
+//! - Before a DMA transfer, the source and destination ranges are cleaned
+//! from the data cache, so the engine sees up-to-date data and no dirty
+//! lines are written back over the result. The destination is
+//! invalidated afterwards.
+//!
+//! - Transfers are split into chunks of at most [`MAX_CHUNK_SIZE`] bytes.
+//! Should the engine stall, the remainder is copied by the CPU.
+//!
+//! - [`copy`] is a safe wrapper around [`memcpy`] for slices.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::dma;
+//!
+//! fn main() {
+//!     let source = [0xAAu8; 0x4000];
+//!     let mut destination = [0u8; 0x4000];
+//!
+//!     dma::copy(&mut destination, &source);
+//! }
+//! ```
+//!
+//! [`memcpy`]: fn.memcpy.html
+//! [`MAX_CHUNK_SIZE`]: constant.MAX_CHUNK_SIZE.html
+//! [`copy`]: fn.copy.html
+
+use core::{mem::size_of, ptr};
+
+use mirage_mmio::Mmio;
+
+use crate::{
+    arch,
+    clock::Clock,
+    memory_map::{self, Region, DMA_MEMORIES},
+    timer::wait_for,
+    warn, Result,
+};
+
+/// Base address for the AHB DMA registers.
+pub(crate) const AHBDMA_BASE: u32 = 0x6000_8000;
+
+/// Base address for the AHB DMA channel used for copies.
+const AHBDMA_CHANNEL_BASE: u32 = AHBDMA_BASE + 0x1000;
+
+/// The minimum size of a copy to be worth setting up a DMA transfer.
+pub const DMA_THRESHOLD: usize = 0x400;
+
+/// The maximum size of a single DMA transfer in bytes.
+pub const MAX_CHUNK_SIZE: usize = 0x4000 * size_of::<u32>();
+
+/// The time to wait for a chunk to be copied in microseconds.
+const CHUNK_TIMEOUT: u32 = 100_000;
+
+/// Enables the AHB DMA controller in `COMMAND`.
+const COMMAND_GEN: u32 = 1 << 31;
+
+/// Enables the channel.
+const CSR_ENB: u32 = 1 << 31;
+/// Signals the end of the transfer.
+const CSR_IE_EOC: u32 = 1 << 30;
+/// Transfers from the XMB to the AHB side.
+const CSR_DIR_AHB_WRITE: u32 = 1 << 28;
+/// Stops the channel after a single transfer.
+const CSR_ONCE: u32 = 1 << 27;
+/// The shift of the word count.
+const CSR_WCOUNT_SHIFT: u32 = 2;
+
+/// The transfer has completed.
+const STA_IS_EOC: u32 = 1 << 30;
+
+/// 32-bit accesses on the AHB side.
+const AHB_SEQ_BUS_WIDTH_32: u32 = 2 << 28;
+/// Bursts of 4 words on the AHB side.
+const AHB_SEQ_BURST_4: u32 = 5 << 24;
+
+/// Representation of the registers of an AHB DMA channel.
+#[allow(non_snake_case)]
+#[repr(C)]
+struct ChannelRegisters {
+    CSR: Mmio<u32>,
+    STA: Mmio<u32>,
+    _0x8: [Mmio<u32>; 2],
+    AHB_PTR: Mmio<u32>,
+    AHB_SEQ: Mmio<u32>,
+    XMB_PTR: Mmio<u32>,
+    XMB_SEQ: Mmio<u32>,
+}
+
+/// Whether the given range lies within IRAM or DRAM.
+fn is_dma_capable(address: usize, len: usize) -> bool {
+    if address > u32::max_value() as usize || len > u32::max_value() as usize {
+        return false;
+    }
+
+    let region = Region::new("copy", address as u32, len as u32);
+
+    memory_map::check_bounds(&region, &DMA_MEMORIES).is_ok()
+}
+
+/// Copies memory through the CPU, word by word where alignment allows.
+unsafe fn cpu_copy(dst: *mut u8, src: *const u8, len: usize) {
+    let mut offset = 0;
+
+    // Words can only be used if both pointers can be aligned at once.
+    if (dst as usize ^ src as usize) & (size_of::<u32>() - 1) == 0 {
+        while offset < len && (dst as usize + offset) & (size_of::<u32>() - 1) != 0 {
+            *dst.add(offset) = *src.add(offset);
+            offset += 1;
+        }
+
+        while offset + size_of::<u32>() <= len {
+            ptr::write(
+                dst.add(offset) as *mut u32,
+                ptr::read(src.add(offset) as *const u32),
+            );
+            offset += size_of::<u32>();
+        }
+    }
+
+    while offset < len {
+        *dst.add(offset) = *src.add(offset);
+        offset += 1;
+    }
+}
+
+/// Copies a chunk of word-aligned memory through the AHB DMA.
+///
+/// Fails with [`Error::Timeout`] if the engine doesn't complete the chunk.
+///
+/// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+unsafe fn dma_copy_chunk(dst: u32, src: u32, len: usize) -> Result<()> {
+    let channel = &*(AHBDMA_CHANNEL_BASE as *const ChannelRegisters);
+    let words = (len / size_of::<u32>()) as u32;
+
+    // Disable the channel and clear a stale completion.
+    channel.CSR.write(0);
+    channel.STA.write(STA_IS_EOC);
+
+    // The source is read through the XMB side and written to the AHB side.
+    channel.AHB_PTR.write(dst);
+    channel.AHB_SEQ.write(AHB_SEQ_BUS_WIDTH_32 | AHB_SEQ_BURST_4);
+    channel.XMB_PTR.write(src);
+    channel.XMB_SEQ.write(0);
+
+    let csr = CSR_IE_EOC | CSR_DIR_AHB_WRITE | CSR_ONCE | ((words - 1) << CSR_WCOUNT_SHIFT);
+    channel.CSR.write(csr);
+    channel.CSR.write(csr | CSR_ENB);
+
+    if let Err(error) = wait_for(|| channel.STA.read() & STA_IS_EOC != 0, CHUNK_TIMEOUT) {
+        channel.CSR.write(0);
+        return Err(error);
+    }
+
+    channel.STA.write(STA_IS_EOC);
+
+    Ok(())
+}
+
+/// Copies `len` bytes from `src` to `dst`, using the AHB DMA if possible.
+///
+/// # Safety
+///
+/// Both pointers must be valid for `len` bytes and the ranges must
+/// not overlap.
+pub unsafe fn memcpy(dst: *mut u8, src: *const u8, len: usize) {
+    let aligned = (dst as usize | src as usize | len) & (size_of::<u32>() - 1) == 0;
+
+    if cfg!(feature = "std")
+        || !aligned
+        || len < DMA_THRESHOLD
+        || !is_dma_capable(dst as usize, len)
+        || !is_dma_capable(src as usize, len)
+    {
+        cpu_copy(dst, src, len);
+        return;
+    }
+
+    // Bring up the controller.
+    let mut clock = Clock::AHBDMA;
+    if !clock.is_enabled() {
+        clock.enable();
+    }
+    (*(AHBDMA_BASE as *const Mmio<u32>)).write(COMMAND_GEN);
+
+    arch::clean_range(src as usize, len);
+    arch::clean_invalidate_range(dst as usize, len);
+
+    let mut offset = 0;
+    while offset < len {
+        let chunk = (len - offset).min(MAX_CHUNK_SIZE);
+
+        if dma_copy_chunk(
+            dst as u32 + offset as u32,
+            src as u32 + offset as u32,
+            chunk,
+        )
+        .is_err()
+        {
+            // The engine stalled, finish the copy on the CPU.
+            warn!("AHB DMA stalled at offset {:#X}, copying the rest by CPU", offset);
+            break;
+        }
+
+        offset += chunk;
+    }
+
+    arch::invalidate_range(dst as usize, len);
+
+    if offset < len {
+        cpu_copy(dst.add(offset), src.add(offset), len - offset);
+    }
+}
+
+/// Copies a slice into another one of the same length,
+/// using the AHB DMA if possible.
+///
+/// # Panics
+///
+/// Panics if the slices have different lengths.
+pub fn copy(dst: &mut [u8], src: &[u8]) {
+    assert_eq!(dst.len(), src.len(), "Slices must have the same length");
+
+    unsafe {
+        memcpy(dst.as_mut_ptr(), src.as_ptr(), src.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_with_any_alignment() {
+        let mut source = [0u8; 64];
+        for (i, byte) in source.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        for src_offset in 0..4 {
+            for dst_offset in 0..4 {
+                let mut destination = [0u8; 64];
+                let len = 64 - src_offset.max(dst_offset);
+
+                unsafe {
+                    cpu_copy(
+                        destination.as_mut_ptr().add(dst_offset),
+                        source.as_ptr().add(src_offset),
+                        len,
+                    );
+                }
+
+                assert_eq!(
+                    &destination[dst_offset..dst_offset + len],
+                    &source[src_offset..src_offset + len]
+                );
+            }
+        }
+    }
+}
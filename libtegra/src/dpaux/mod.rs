@@ -0,0 +1,361 @@
+//! Tegra210 DisplayPort AUX channel driver.
+//!
+//! # Description
+//!
+//! The DPAUX controllers implement the auxiliary channel of DisplayPort,
+//! a half-duplex side band over which the source reads and configures
+//! the DPCD registers of the sink, for example during link training.
+//! They also report the state of the hotplug detect (HPD) line.
+//!
+//! Tegra X1 devices have two instances, DPAUX for SOR0 and DPAUX1 for
+//! SOR1. On the Switch, DPAUX1 talks to the DisplayPort sink in the dock.
+//!
+//! # Implementation
+//!
+//! - The [`Registers`] struct represents the DPAUX registers.
+//!
+//! - The [`Dpaux`] struct represents a DPAUX controller and holds the
+//! [`Clock`] and the respective [`Registers`] block pointer.
+//!
+//! - [`Dpaux::init`] has to be called before the controller can be used.
+//!
+//! - [`Dpaux::is_connected`] reports the state of the HPD line.
+//!
+//! - [`Dpaux::read`] and [`Dpaux::write`] perform native AUX transfers
+//! of up to [`AUX_MAX_TRANSFER`] bytes. Deferred transfers are retried.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::dpaux::Dpaux;
+//!
+//! fn main() {
+//!     let mut dpaux = Dpaux::DPAUX1;
+//!     dpaux.init();
+//!
+//!     if dpaux.is_connected() {
+//!         // Read the DPCD revision of the sink.
+//!         let revision = dpaux.read_byte(0x0).unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! [`Registers`]: struct.Registers.html
+//! [`Dpaux`]: struct.Dpaux.html
+//! [`Clock`]: ../clock/struct.Clock.html
+//! [`Dpaux::init`]: struct.Dpaux.html#method.init
+//! [`Dpaux::is_connected`]: struct.Dpaux.html#method.is_connected
+//! [`Dpaux::read`]: struct.Dpaux.html#method.read
+//! [`Dpaux::write`]: struct.Dpaux.html#method.write
+//! [`AUX_MAX_TRANSFER`]: constant.AUX_MAX_TRANSFER.html
+
+use mirage_mmio::{BlockMmio, Mmio};
+
+use crate::{
+    clock::Clock,
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// Base address for DPAUX1 registers.
+const DPAUX1_BASE: u32 = 0x5404_0000;
+
+/// The maximum amount of bytes in a single AUX transfer.
+pub const AUX_MAX_TRANSFER: usize = 16;
+
+/// How often a deferred transfer is retried.
+const AUX_DEFER_RETRIES: u32 = 7;
+
+/// How long a single transfer may take, in µs.
+const AUX_TIMEOUT: u32 = 10_000;
+
+/// Native AUX write.
+const AUXCTL_CMD_AUX_WR: u32 = 0x8 << 12;
+/// Native AUX read.
+const AUXCTL_CMD_AUX_RD: u32 = 0x9 << 12;
+/// Starts a transfer, cleared by the hardware once it has completed.
+const AUXCTL_TRANSACTREQ: u32 = 1 << 16;
+
+/// The sink acknowledged the transfer.
+const AUXSTAT_REPLY_ACK: u32 = 0x0;
+/// The sink asked to retry the transfer later.
+const AUXSTAT_REPLY_DEFER: u32 = 0x2;
+/// The errors a transfer may end with.
+const AUXSTAT_ERRORS: u32 = 0xF << 8;
+/// The transfer timed out on the wire.
+const AUXSTAT_TIMEOUT_ERROR: u32 = 1 << 8;
+/// The HPD line is asserted.
+const AUXSTAT_HPD_STATUS: u32 = 1 << 28;
+
+/// Powers down the pads.
+const HYBRID_SPARE_PAD_POWER_DOWN: u32 = 1 << 0;
+
+/// The pad configuration for AUX mode, with the drive strength
+/// and common mode settings recommended for Tegra210.
+const HYBRID_PADCTL_AUX: u32 = (2 << 12) | (4 << 8) | (0x18 << 2) | (1 << 1);
+
+/// Representation of the DPAUX registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct Registers {
+    pub DPAUX_CTXSW: Mmio<u32>,
+    pub DPAUX_INTR_EN_AUX: Mmio<u32>,
+    _reserved0: [Mmio<u32>; 0x3],
+    pub DPAUX_INTR_AUX: Mmio<u32>,
+    _reserved1: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_WRITE_W0: Mmio<u32>,
+    _reserved2: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_WRITE_W1: Mmio<u32>,
+    _reserved3: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_WRITE_W2: Mmio<u32>,
+    _reserved4: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_WRITE_W3: Mmio<u32>,
+    _reserved5: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_READ_W0: Mmio<u32>,
+    _reserved6: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_READ_W1: Mmio<u32>,
+    _reserved7: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_READ_W2: Mmio<u32>,
+    _reserved8: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXDATA_READ_W3: Mmio<u32>,
+    _reserved9: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXADDR: Mmio<u32>,
+    _reserved10: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXCTL: Mmio<u32>,
+    _reserved11: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUXSTAT: Mmio<u32>,
+    _reserved12: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUX_SINKSTAT_LO: Mmio<u32>,
+    _reserved13: [Mmio<u32>; 0x3],
+    pub DPAUX_DP_AUX_SINKSTAT_HI: Mmio<u32>,
+    _reserved14: [Mmio<u32>; 0x3],
+    pub DPAUX_HPD_CONFIG: Mmio<u32>,
+    pub DPAUX_HPD_IRQ_CONFIG: Mmio<u32>,
+    pub DPAUX_DP_AUX_CONFIG: Mmio<u32>,
+    pub DPAUX_HYBRID_PADCTL: Mmio<u32>,
+    pub DPAUX_HYBRID_SPARE: Mmio<u32>,
+}
+
+impl Registers {
+    fn write_data(&self) -> [&Mmio<u32>; 4] {
+        [
+            &self.DPAUX_DP_AUXDATA_WRITE_W0,
+            &self.DPAUX_DP_AUXDATA_WRITE_W1,
+            &self.DPAUX_DP_AUXDATA_WRITE_W2,
+            &self.DPAUX_DP_AUXDATA_WRITE_W3,
+        ]
+    }
+
+    fn read_data(&self) -> [&Mmio<u32>; 4] {
+        [
+            &self.DPAUX_DP_AUXDATA_READ_W0,
+            &self.DPAUX_DP_AUXDATA_READ_W1,
+            &self.DPAUX_DP_AUXDATA_READ_W2,
+            &self.DPAUX_DP_AUXDATA_READ_W3,
+        ]
+    }
+}
+
+/// Representation of a DisplayPort AUX controller.
+pub struct Dpaux {
+    /// The respective registers of the controller.
+    registers: BlockMmio<Registers>,
+    /// The respective device clock of the controller.
+    clock: Clock,
+}
+
+// Definitions of known DPAUX controllers.
+impl Dpaux {
+    /// Representation of DPAUX1, which belongs to SOR1.
+    pub const DPAUX1: Self = Dpaux {
+        registers: unsafe { BlockMmio::new(DPAUX1_BASE as usize) },
+        clock: Clock::DPAUX1,
+    };
+}
+
+impl Dpaux {
+    /// Enables the controller and configures its pads for AUX transfers.
+    pub fn init(&mut self) {
+        let registers = &*self.registers;
+
+        self.clock.enable();
+
+        registers.DPAUX_HYBRID_PADCTL.write(HYBRID_PADCTL_AUX);
+        registers
+            .DPAUX_HYBRID_SPARE
+            .write(registers.DPAUX_HYBRID_SPARE.read() & !HYBRID_SPARE_PAD_POWER_DOWN);
+    }
+
+    /// Powers down the pads and disables the controller.
+    pub fn finish(&mut self) {
+        let registers = &*self.registers;
+
+        registers
+            .DPAUX_HYBRID_SPARE
+            .write(registers.DPAUX_HYBRID_SPARE.read() | HYBRID_SPARE_PAD_POWER_DOWN);
+
+        self.clock.disable();
+    }
+
+    /// Whether a sink is connected, as reported by the HPD line.
+    pub fn is_connected(&self) -> bool {
+        self.registers.DPAUX_DP_AUXSTAT.read() & AUXSTAT_HPD_STATUS != 0
+    }
+
+    /// Performs a single transfer and returns the status of it.
+    fn transfer_once(&self, command: u32, address: u32, length: usize) -> Result<u32> {
+        let registers = &*self.registers;
+
+        registers.DPAUX_DP_AUXADDR.write(address);
+        registers
+            .DPAUX_DP_AUXCTL
+            .write(command | (length as u32 - 1) | AUXCTL_TRANSACTREQ);
+
+        wait_for(
+            || registers.DPAUX_DP_AUXCTL.read() & AUXCTL_TRANSACTREQ == 0,
+            AUX_TIMEOUT,
+        )?;
+
+        let status = registers.DPAUX_DP_AUXSTAT.read();
+
+        // Clear the errors for the next transfer.
+        registers.DPAUX_DP_AUXSTAT.write(status & AUXSTAT_ERRORS);
+
+        Ok(status)
+    }
+
+    /// Performs a transfer, retrying it while the sink defers it.
+    fn transfer(&self, command: u32, address: u32, length: usize) -> Result<u32> {
+        if length == 0 || length > AUX_MAX_TRANSFER {
+            return Err(Error::InvalidArgument);
+        }
+
+        for _ in 0..AUX_DEFER_RETRIES {
+            let status = self.transfer_once(command, address, length)?;
+
+            if status & AUXSTAT_TIMEOUT_ERROR != 0 {
+                return Err(Error::Timeout);
+            }
+
+            if status & AUXSTAT_ERRORS != 0 {
+                return Err(Error::Display);
+            }
+
+            match (status >> 16) & 0xF {
+                AUXSTAT_REPLY_ACK => return Ok(status),
+                AUXSTAT_REPLY_DEFER => usleep(500),
+                _ => return Err(Error::Display),
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+
+    /// Reads DPCD registers of the sink, starting at the given address.
+    ///
+    /// Fails with [`Error::Display`] if the sink doesn't acknowledge
+    /// the transfer or returns less data than requested.
+    ///
+    /// [`Error::Display`]: ../enum.Error.html#variant.Display
+    pub fn read(&self, address: u32, buffer: &mut [u8]) -> Result<()> {
+        let status = self.transfer(AUXCTL_CMD_AUX_RD, address, buffer.len())?;
+
+        if (status & 0xFF) as usize != buffer.len() {
+            return Err(Error::Display);
+        }
+
+        for (chunk, register) in buffer.chunks_mut(4).zip(self.registers.read_data().iter()) {
+            let word = register.read().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+
+    /// Writes DPCD registers of the sink, starting at the given address.
+    ///
+    /// Fails with [`Error::Display`] if the sink doesn't acknowledge
+    /// the transfer.
+    ///
+    /// [`Error::Display`]: ../enum.Error.html#variant.Display
+    pub fn write(&self, address: u32, data: &[u8]) -> Result<()> {
+        if data.len() > AUX_MAX_TRANSFER {
+            return Err(Error::InvalidArgument);
+        }
+
+        for (chunk, register) in data.chunks(4).zip(self.registers.write_data().iter()) {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            register.write(u32::from_le_bytes(word));
+        }
+
+        self.transfer(AUXCTL_CMD_AUX_WR, address, data.len())?;
+
+        Ok(())
+    }
+
+    /// Reads a single DPCD register of the sink.
+    pub fn read_byte(&self, address: u32) -> Result<u8> {
+        let mut buffer = [0; 1];
+        self.read(address, &mut buffer)?;
+
+        Ok(buffer[0])
+    }
+
+    /// Writes a single DPCD register of the sink.
+    pub fn write_byte(&self, address: u32, value: u8) -> Result<()> {
+        self.write(address, &[value])
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    const AUXDATA_WRITE_W0: usize = 0x5404_0024;
+    const AUXDATA_READ_W0: usize = 0x5404_0064;
+    const AUXDATA_READ_W1: usize = 0x5404_0074;
+    const AUXADDR: usize = 0x5404_00A4;
+    const AUXCTL: usize = 0x5404_00B4;
+    const AUXSTAT: usize = 0x5404_00C4;
+
+    /// Tests native AUX reads and writes, including deferred ones.
+    #[test]
+    fn transfers() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        // Every transfer completes right away.
+        for _ in 0..4 {
+            mock::expect_read(AUXCTL, 0);
+        }
+
+        // A deferred reply followed by an ACK of 6 bytes.
+        mock::expect_read(AUXSTAT, 0x2 << 16);
+        mock::expect_read(AUXSTAT, 0x6);
+        mock::set(AUXDATA_READ_W0, 0x0484_0A12);
+        mock::set(AUXDATA_READ_W1, 0x0000_0101);
+
+        let mut caps = [0; 6];
+        Dpaux::DPAUX1.read(0x0, &mut caps).unwrap();
+        assert_eq!(caps, [0x12, 0x0A, 0x84, 0x04, 0x01, 0x01]);
+        assert_eq!(
+            mock::writes(AUXCTL),
+            [u64::from(0x9005 | AUXCTL_TRANSACTREQ); 2]
+        );
+
+        Dpaux::DPAUX1.write(0x100, &[0x0A, 0x84]).unwrap();
+        assert_eq!(mock::get(AUXADDR), 0x100);
+        assert_eq!(mock::get(AUXDATA_WRITE_W0), 0x840A);
+        assert_eq!(
+            mock::writes(AUXCTL)[2],
+            u64::from(0x8001 | AUXCTL_TRANSACTREQ)
+        );
+
+        // A NACK fails the transfer.
+        mock::expect_read(AUXSTAT, 0x1 << 16);
+        assert_eq!(Dpaux::DPAUX1.read_byte(0x0), Err(Error::Display));
+    }
+}
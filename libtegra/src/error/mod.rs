@@ -0,0 +1,237 @@
+//! The common error type of libtegra.
+//!
+//! # Description
+//!
+//! Drivers report failures in many different shapes. To let bootloaders
+//! propagate and log them coherently, the public APIs return a [`Result`]
+//! with the crate-wide [`Error`] type.
+//!
+//! # Implementation
+//!
+//! - [`Error`] has a variant per subsystem. Drivers with detailed error
+//! enums, like [`I2cError`] or [`TsecError`], keep them and wrap them
+//! into the respective variant.
+//!
+//! - All driver-specific errors convert into [`Error`] through `From`,
+//! so they can be propagated with the `?` operator.
+//!
+//! - [`Error`] implements `Display` with a short description for logs.
+//!
+//! - `core::fmt` adds tens of kilobytes to a binary, which is a lot for the
+//! IRAM bootstrap. [`Error::as_str`] and [`Error::code`] describe an error
+//! without it, and [`hex`] renders numbers, e.g. addresses, for output that
+//! is written byte by byte.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{button::{self, Button}, i2c::I2c, Result};
+//!
+//! fn wait_for_confirmation(i2c: &I2c) -> Result<()> {
+//!     button::wait_for(i2c, 10, Button::POWER)?;
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`Result`]: type.Result.html
+//! [`Error`]: enum.Error.html
+//! [`Error::as_str`]: enum.Error.html#method.as_str
+//! [`Error::code`]: enum.Error.html#method.code
+//! [`hex`]: fn.hex.html
+//! [`I2cError`]: ../i2c/enum.I2cError.html
+//! [`TsecError`]: ../tsec/enum.TsecError.html
+
+use core::fmt;
+
+use crate::{
+    apbdma,
+    clock::{pll, sdmmc},
+    falcon,
+    i2c::I2cError,
+    memory_map,
+    tsec::TsecError,
+};
+
+/// Enumeration of errors reported by the libtegra drivers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The Security Engine reported an error.
+    Se,
+    /// The SD/MMC controller or the card couldn't be configured.
+    Sdmmc,
+    /// An I²C transfer failed.
+    I2c(I2cError),
+    /// Operating the TSEC failed.
+    Tsec(TsecError),
+    /// The display can't render the given data.
+    Display,
+    /// A clock or PLL couldn't be configured.
+    Clock,
+    /// An APB DMA transfer failed.
+    Dma(apbdma::Error),
+    /// The fuses couldn't be read or failed their integrity check.
+    Fuse,
+    /// The Memory Controller rejected a configuration.
+    Mc,
+    /// The touch panel controller isn't operational.
+    Touch,
+    /// A parameter is out of the range supported by the hardware.
+    InvalidArgument,
+    /// Data read from storage is malformed or failed its integrity check.
+    InvalidData,
+    /// All instances of a limited resource are in use.
+    Exhausted,
+    /// The hardware didn't respond in time.
+    Timeout,
+    /// The operation isn't supported on this hardware revision.
+    UnsupportedHardware,
+}
+
+/// A specialized `Result` type for libtegra operations.
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// Gets a short description of the error.
+    ///
+    /// Unlike the `Display` implementation, this omits the details of
+    /// driver-specific errors.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Error::Se => "Security Engine error",
+            Error::Sdmmc => "SD/MMC error",
+            Error::I2c(_) => "I2C error",
+            Error::Tsec(_) => "TSEC error",
+            Error::Display => "display error",
+            Error::Clock => "clock configuration error",
+            Error::Dma(_) => "DMA error",
+            Error::Fuse => "fuse error",
+            Error::Mc => "memory controller error",
+            Error::Touch => "touch controller error",
+            Error::InvalidArgument => "invalid argument",
+            Error::InvalidData => "invalid or corrupted data",
+            Error::Exhausted => "resources exhausted",
+            Error::Timeout => "timed out",
+            Error::UnsupportedHardware => "unsupported hardware revision",
+        }
+    }
+
+    /// Gets a numeric code of the error, which stays the same across
+    /// versions of libtegra.
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Se => 1,
+            Error::Sdmmc => 2,
+            Error::I2c(_) => 3,
+            Error::Tsec(_) => 4,
+            Error::Display => 5,
+            Error::Clock => 6,
+            Error::Dma(_) => 7,
+            Error::Fuse => 8,
+            Error::Mc => 9,
+            Error::Touch => 10,
+            Error::InvalidArgument => 11,
+            Error::InvalidData => 12,
+            Error::Exhausted => 13,
+            Error::Timeout => 14,
+            Error::UnsupportedHardware => 15,
+        }
+    }
+}
+
+/// Renders a number as eight uppercase hexadecimal digits.
+pub fn hex(value: u32) -> [u8; 8] {
+    let mut digits = [0; 8];
+
+    for (index, digit) in digits.iter_mut().enumerate() {
+        let nibble = (value >> (28 - 4 * index)) & 0xF;
+
+        *digit = b"0123456789ABCDEF"[nibble as usize];
+    }
+
+    digits
+}
+
+impl From<I2cError> for Error {
+    fn from(error: I2cError) -> Self {
+        Error::I2c(error)
+    }
+}
+
+impl From<TsecError> for Error {
+    fn from(error: TsecError) -> Self {
+        Error::Tsec(error)
+    }
+}
+
+impl From<falcon::Error> for Error {
+    fn from(error: falcon::Error) -> Self {
+        Error::Tsec(TsecError::from(error))
+    }
+}
+
+impl From<apbdma::Error> for Error {
+    fn from(error: apbdma::Error) -> Self {
+        match error {
+            apbdma::Error::Timeout => Error::Timeout,
+            error => Error::Dma(error),
+        }
+    }
+}
+
+impl From<memory_map::Error> for Error {
+    fn from(_: memory_map::Error) -> Self {
+        Error::InvalidArgument
+    }
+}
+
+impl From<pll::Error> for Error {
+    fn from(error: pll::Error) -> Self {
+        match error {
+            pll::Error::LockTimeout => Error::Timeout,
+            _ => Error::Clock,
+        }
+    }
+}
+
+impl From<sdmmc::Error> for Error {
+    fn from(error: sdmmc::Error) -> Self {
+        match error {
+            sdmmc::Error::Unsupported => Error::Sdmmc,
+            sdmmc::Error::Pll(error) => error.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::I2c(error) => write!(f, "{}: {:?}", self.as_str(), error),
+            Error::Tsec(error) => write!(f, "{}: {:?}", self.as_str(), error),
+            Error::Dma(error) => write!(f, "{}: {:?}", self.as_str(), error),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests rendering numbers as hexadecimal digits.
+    #[test]
+    fn hex_digits() {
+        assert_eq!(&hex(0), b"00000000");
+        assert_eq!(&hex(0x4001_0ABC), b"40010ABC");
+        assert_eq!(&hex(u32::max_value()), b"FFFFFFFF");
+    }
+
+    /// Tests that the codes tell the errors apart.
+    #[test]
+    fn codes() {
+        assert_eq!(Error::Se.code(), 1);
+        assert_eq!(Error::Timeout.code(), 14);
+        assert_eq!(Error::I2c(I2cError::Nack).code(), 3);
+        assert_eq!(Error::I2c(I2cError::Nack).as_str(), "I2C error");
+    }
+}
@@ -0,0 +1,497 @@
+//! NVIDIA Falcon microprocessor driver.
+//!
+//! # Description
+//!
+//! Falcon (FAst Logic CONtroller) is a general-purpose microprocessor
+//! which NVIDIA embeds into various engines of the Tegra X1, such as
+//! TSEC, NVDEC, NVENC and VIC. Each engine maps its own copy of the
+//! Falcon register interface at offset `0x1000` of its register
+//! aperture.
+//!
+//! All Falcon units share the same mechanisms for loading code into
+//! their instruction memory (IMEM), exchanging data through their
+//! data memory (DMEM), booting and communicating with the host
+//! through mailbox registers.
+//!
+//! # Implementation
+//!
+//! - The [`Registers`] struct represents the Falcon registers of a
+//! single engine.
+//!
+//! - The [`Falcon`] struct holds a pointer to the [`Registers`] of a
+//! unit and provides abstractions for loading, booting and talking to
+//! firmware running on it.
+//!
+//! - [`Falcon`] holds pre-defined constants which represent the known
+//! Falcon units and should be preferred over creating instances of the
+//! [`Falcon`] struct manually.
+//!
+//! - [`Falcon`] doesn't manage clocks or resets of the engine it belongs
+//! to. These must be configured by the engine driver, e.g. [`Tsec`],
+//! before the unit is accessed.
+//!
+//! - Falcon operations may fail for various reasons. All of them return
+//! an [`Error`] on failure which describes the cause.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::falcon::Falcon;
+//!
+//! fn run(firmware: &[u8]) {
+//!     let nvdec = Falcon::NVDEC;
+//!
+//!     // Load and boot the firmware.
+//!     nvdec.load(firmware, firmware.len()).unwrap();
+//!     nvdec.dma_wait_idle().unwrap();
+//!     nvdec.boot(0);
+//!
+//!     // Wait for the firmware to report back.
+//!     let result = nvdec.wait_for_mailbox1(2000).unwrap();
+//! }
+//! ```
+//!
+//! [`Registers`]: struct.Registers.html
+//! [`Falcon`]: struct.Falcon.html
+//! [`Error`]: enum.Error.html
+//! [`Tsec`]: ../tsec/struct.Tsec.html
+
+use mirage_mmio::Mmio;
+
+use crate::timer::wait_for;
+
+/// Base address for the TSEC Falcon registers.
+pub(crate) const FALCON_TSEC_BASE: u32 = 0x5450_1000;
+
+/// Base address for the NVDEC Falcon registers.
+pub(crate) const FALCON_NVDEC_BASE: u32 = 0x5448_1000;
+
+/// Base address for the NVENC Falcon registers.
+pub(crate) const FALCON_NVENC_BASE: u32 = 0x544C_1000;
+
+/// Base address for the VIC Falcon registers.
+pub(crate) const FALCON_VIC_BASE: u32 = 0x5434_1000;
+
+/// Auto-increments the IMEM/DMEM port address on writes.
+const FALCON_MEMC_AINCW: u32 = 1 << 24;
+/// Auto-increments the IMEM/DMEM port address on reads.
+const FALCON_MEMC_AINCR: u32 = 1 << 25;
+/// Marks uploaded IMEM pages as secure.
+const FALCON_IMEMC_SECURE: u32 = 1 << 28;
+
+/// The timeout for DMA transfers in milliseconds.
+const FALCON_DMA_TIMEOUT: u32 = 10000;
+
+/// Enumeration of possible errors when operating a Falcon unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The DMA engine didn't become idle in time.
+    DmaTimeout,
+    /// The firmware didn't report back through the mailbox in time.
+    MailboxTimeout,
+    /// The access exceeds the size of the Falcon memory.
+    OutOfBounds,
+    /// The data isn't properly aligned or located outside
+    /// the 32-bit address space.
+    AlignmentError,
+}
+
+/// Representation of the Falcon registers.
+#[repr(C)]
+pub struct Registers {
+    pub irqsset: Mmio<u32>,   // 0x0
+    pub irqsclr: Mmio<u32>,   // 0x4
+    pub irqstat: Mmio<u32>,   // 0x8
+    pub irqmode: Mmio<u32>,   // 0xc
+    pub irqmset: Mmio<u32>,   // 0x10
+    pub irqmclr: Mmio<u32>,   // 0x14
+    pub irqmask: Mmio<u32>,   // 0x18
+    pub irqdest: Mmio<u32>,   // 0x1c
+    pub gptmrint: Mmio<u32>,  // 0x20
+    pub gptmrval: Mmio<u32>,  // 0x24
+    pub gptmrctl: Mmio<u32>,  // 0x28
+    pub ptimer0: Mmio<u32>,   // 0x2c
+    pub ptimer1: Mmio<u32>,   // 0x30
+    pub wdtmrval: Mmio<u32>,  // 0x34
+    pub wdtmrctl: Mmio<u32>,  // 0x38
+    pub irqdest2: Mmio<u32>,  // 0x3c
+    pub mailbox0: Mmio<u32>,  // 0x40
+    pub mailbox1: Mmio<u32>,  // 0x44
+    pub itfen: Mmio<u32>,     // 0x48
+    pub idlestate: Mmio<u32>, // 0x4c
+    pub curctx: Mmio<u32>,    // 0x50
+    pub nxtctx: Mmio<u32>,    // 0x54
+    pub ctxack: Mmio<u32>,    // 0x58
+    pub fhstate: Mmio<u32>,   // 0x5c
+    pub privstate: Mmio<u32>, // 0x60
+    pub mthddata: Mmio<u32>,  // 0x64
+    pub mthdid: Mmio<u32>,    // 0x68
+    pub mthdwdat: Mmio<u32>,  // 0x6c
+    pub mthdcount: Mmio<u32>, // 0x70
+    pub mthdpop: Mmio<u32>,   // 0x74
+    pub mthdramsz: Mmio<u32>, // 0x78
+    pub sftreset: Mmio<u32>,  // 0x7c
+    pub os: Mmio<u32>,        // 0x80
+    pub rm: Mmio<u32>,        // 0x84
+    pub soft_pm: Mmio<u32>,   // 0x88
+    pub soft_mode: Mmio<u32>, // 0x8c
+    pub debug1: Mmio<u32>,    // 0x90
+    pub debuginfo: Mmio<u32>, // 0x94
+    pub ibrkpt1: Mmio<u32>,   // 0x98
+    pub ibrkpt2: Mmio<u32>,   // 0x9c
+    pub cgctl: Mmio<u32>,     // 0xa0
+    pub engctl: Mmio<u32>,    // 0xa4
+    pub pmm: Mmio<u32>,       // 0xa8
+    pub addr: Mmio<u32>,      // 0xac
+    pub ibrkpt3: Mmio<u32>,   // 0xb0
+    pub ibrkpt4: Mmio<u32>,   // 0xb4
+    pub ibrkpt5: Mmio<u32>,   // 0xb8
+    _reserved68: [Mmio<u8>; 0x14],
+    pub exci: Mmio<u32>,     // 0xd0
+    pub svec_spr: Mmio<u32>, // 0xd4
+    pub rstat0: Mmio<u32>,   // 0xd8
+    pub rstat3: Mmio<u32>,   // 0xdc
+    pub unk_e0: Mmio<u32>,   // 0xe0
+    _reserved73: [Mmio<u8>; 0x1C],
+    pub cpuctl: Mmio<u32>,       // 0x100
+    pub bootvec: Mmio<u32>,      // 0x104
+    pub hwcfg: Mmio<u32>,        // 0x108
+    pub dmactl: Mmio<u32>,       // 0x10c
+    pub dmatrfbase: Mmio<u32>,   // 0x110
+    pub dmatrfmoffs: Mmio<u32>,  // 0x114
+    pub dmatrfcmd: Mmio<u32>,    // 0x118
+    pub dmatrffboffs: Mmio<u32>, // 0x11c
+    pub dmapoll_fb: Mmio<u32>,   // 0x120
+    pub dmapoll_cp: Mmio<u32>,   // 0x124
+    pub dbg_state: Mmio<u32>,    // 0x128
+    pub hwcfg1: Mmio<u32>,       // 0x12c
+    pub cpuctl_alias: Mmio<u32>, // 0x130
+    _reserved86: [Mmio<u8>; 0x4],
+    pub stackcfg: Mmio<u32>, // 0x138
+    _reserved87: [Mmio<u8>; 0x4],
+    pub imctl: Mmio<u32>,       // 0x140
+    pub imstat: Mmio<u32>,      // 0x144
+    pub traceidx: Mmio<u32>,    // 0x148
+    pub tracepc: Mmio<u32>,     // 0x14c
+    pub imfillrng0: Mmio<u32>,  // 0x150
+    pub imfillrng1: Mmio<u32>,  // 0x154
+    pub imfillctl: Mmio<u32>,   // 0x158
+    pub imctl_debug: Mmio<u32>, // 0x15c
+    pub cmembase: Mmio<u32>,    // 0x160
+    pub dmemapert: Mmio<u32>,   // 0x164
+    pub exterraddr: Mmio<u32>,  // 0x168
+    pub exterrstat: Mmio<u32>,  // 0x16c
+    _reserved99: [Mmio<u8>; 0xC],
+    pub cg2: Mmio<u32>,    // 0x17c
+    pub imemc0: Mmio<u32>, // 0x180
+    pub imemd0: Mmio<u32>, // 0x184
+    pub imemt0: Mmio<u32>, // 0x188
+    _reserved103: [Mmio<u8>; 0x4],
+    pub imemc1: Mmio<u32>, // 0x190
+    pub imemd1: Mmio<u32>, // 0x194
+    pub imemt1: Mmio<u32>, // 0x198
+    _reserved106: [Mmio<u8>; 0x4],
+    pub imemc2: Mmio<u32>, // 0x1a0
+    pub imemd2: Mmio<u32>, // 0x1a4
+    pub imemt2: Mmio<u32>, // 0x1a8
+    _reserved109: [Mmio<u8>; 0x4],
+    pub imemc3: Mmio<u32>, // 0x1b0
+    pub imemd3: Mmio<u32>, // 0x1b4
+    pub imemt3: Mmio<u32>, // 0x1b8
+    _reserved112: [Mmio<u8>; 0x4],
+    pub dmemc0: Mmio<u32>,    // 0x1c0
+    pub dmemd0: Mmio<u32>,    // 0x1c4
+    pub dmemc1: Mmio<u32>,    // 0x1c8
+    pub dmemd1: Mmio<u32>,    // 0x1cc
+    pub dmemc2: Mmio<u32>,    // 0x1d0
+    pub dmemd2: Mmio<u32>,    // 0x1d4
+    pub dmemc3: Mmio<u32>,    // 0x1d8
+    pub dmemd3: Mmio<u32>,    // 0x1dc
+    pub dmemc4: Mmio<u32>,    // 0x1e0
+    pub dmemd4: Mmio<u32>,    // 0x1e4
+    pub dmemc5: Mmio<u32>,    // 0x1e8
+    pub dmemd5: Mmio<u32>,    // 0x1ec
+    pub dmemc6: Mmio<u32>,    // 0x1f0
+    pub dmemd6: Mmio<u32>,    // 0x1f4
+    pub dmemc7: Mmio<u32>,    // 0x1f8
+    pub dmemd7: Mmio<u32>,    // 0x1fc
+    pub icd_cmd: Mmio<u32>,   // 0x200
+    pub icd_addr: Mmio<u32>,  // 0x204
+    pub icd_wdata: Mmio<u32>, // 0x208
+    pub icd_rdata: Mmio<u32>, // 0x20c
+    _reserved132: [Mmio<u8>; 0x30],
+    pub sctl: Mmio<u32>,    // 0x240
+    pub sstat: Mmio<u32>,   // 0x244
+    pub unk_248: Mmio<u32>, // 0x248
+    pub unk_24c: Mmio<u32>, // 0x24c
+    pub unk_250: Mmio<u32>, // 0x250
+    _reserved137: [Mmio<u8>; 0xC],
+    pub unk_260: Mmio<u32>, // 0x260
+    _reserved138: [Mmio<u8>; 0x1C],
+    pub sprot_imem: Mmio<u32>,   // 0x280
+    pub sprot_dmem: Mmio<u32>,   // 0x284
+    pub sprot_cpuctl: Mmio<u32>, // 0x288
+    pub sprot_misc: Mmio<u32>,   // 0x28c
+    pub sprot_irq: Mmio<u32>,    // 0x290
+    pub sprot_mthd: Mmio<u32>,   // 0x294
+    pub sprot_sctl: Mmio<u32>,   // 0x298
+    pub sprot_wdtmr: Mmio<u32>,  // 0x29c
+    _reserved146: [Mmio<u8>; 0x20],
+    pub dmainfo_finished_fbrd_low: Mmio<u32>, // 0x2c0
+    pub dmainfo_finished_fbrd_high: Mmio<u32>, // 0x2c4
+    pub dmainfo_finished_fbwr_low: Mmio<u32>, // 0x2c8
+    pub dmainfo_finished_fbwr_high: Mmio<u32>, // 0x2cc
+    pub dmainfo_current_fbrd_low: Mmio<u32>,  // 0x2d0
+    pub dmainfo_current_fbrd_high: Mmio<u32>, // 0x2d4
+    pub dmainfo_current_fbwr_low: Mmio<u32>,  // 0x2d8
+    pub dmainfo_current_fbwr_high: Mmio<u32>, // 0x2dc
+    pub dmainfo_ctl: Mmio<u32>,               // 0x2e0
+}
+
+/// Representation of a Falcon unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Falcon {
+    /// A pointer to the respective registers of the unit.
+    registers: *const Registers,
+}
+
+// Definitions of known Falcon units.
+impl Falcon {
+    /// Representation of the TSEC Falcon.
+    pub const TSEC: Self = Falcon {
+        registers: FALCON_TSEC_BASE as *const _,
+    };
+
+    /// Representation of the NVDEC Falcon.
+    pub const NVDEC: Self = Falcon {
+        registers: FALCON_NVDEC_BASE as *const _,
+    };
+
+    /// Representation of the NVENC Falcon.
+    pub const NVENC: Self = Falcon {
+        registers: FALCON_NVENC_BASE as *const _,
+    };
+
+    /// Representation of the VIC Falcon.
+    pub const VIC: Self = Falcon {
+        registers: FALCON_VIC_BASE as *const _,
+    };
+}
+
+impl Falcon {
+    /// Validates a word-aligned access to a Falcon memory of a given size.
+    fn check_memory_access(offset: u32, length: usize, size: usize) -> Result<(), Error> {
+        if offset & 3 != 0 || length & 3 != 0 {
+            return Err(Error::AlignmentError);
+        }
+
+        if offset as usize + length > size {
+            return Err(Error::OutOfBounds);
+        }
+
+        Ok(())
+    }
+
+    /// Configures a physical DMA transfer of a 256 byte block to Falcon.
+    fn dma_phys_to_flcn(
+        &self,
+        is_imem: bool,
+        flcn_offset: u32,
+        phys_offset: u32,
+    ) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        let cmd = if is_imem { 0x10 } else { 0x600 };
+
+        register_base.dmatrfmoffs.write(flcn_offset);
+        register_base.dmatrffboffs.write(phys_offset);
+        register_base.dmatrfcmd.write(cmd);
+
+        self.dma_wait_idle()
+    }
+
+    /// Gets a reference to the registers of the unit.
+    pub fn registers(&self) -> &'static Registers {
+        unsafe { &*self.registers }
+    }
+
+    /// Waits until DMA has entered an idle state.
+    pub fn dma_wait_idle(&self) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        wait_for(
+            || (register_base.dmatrfcmd.read() & (1 << 1)) != 0,
+            FALCON_DMA_TIMEOUT * 1000,
+        )
+        .map_err(|_| Error::DmaTimeout)
+    }
+
+    /// Gets the size of the IMEM in bytes.
+    pub fn imem_size(&self) -> usize {
+        let register_base = unsafe { &*self.registers };
+
+        // The size is reported in 256 byte blocks.
+        ((register_base.hwcfg.read() & 0x1FF) as usize) << 8
+    }
+
+    /// Gets the size of the DMEM in bytes.
+    pub fn dmem_size(&self) -> usize {
+        let register_base = unsafe { &*self.registers };
+
+        // The size is reported in 256 byte blocks.
+        (((register_base.hwcfg.read() >> 9) & 0x1FF) as usize) << 8
+    }
+
+    /// Transfers the first `length` bytes of the code to IMEM through DMA.
+    ///
+    /// The code must be aligned to a 256 byte boundary and located
+    /// within the 32-bit address space.
+    pub fn load(&self, code: &[u8], length: usize) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        // Validate the code location.
+        let address = code.as_ptr() as usize;
+        if address & 0xFF != 0 || address > u32::max_value() as usize {
+            return Err(Error::AlignmentError);
+        }
+
+        // Configure Falcon.
+        register_base.dmactl.write(0);
+        register_base.irqmset.write(0xFFF2);
+        register_base.irqdest.write(0xFFF0);
+        register_base.itfen.write(3);
+
+        self.dma_wait_idle()?;
+
+        // Validate the code size against the IMEM size.
+        if length > code.len() || length > self.imem_size() {
+            return Err(Error::OutOfBounds);
+        }
+
+        // Load the code.
+        register_base.dmatrfbase.write(address as u32 >> 8);
+
+        let mut addr = 0;
+        while addr < length {
+            self.dma_phys_to_flcn(true, addr as u32, addr as u32)?;
+
+            addr += 0x100;
+        }
+
+        Ok(())
+    }
+
+    /// Reads data from the DMEM at a given word-aligned offset.
+    pub fn read_dmem(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        Self::check_memory_access(offset, buffer.len(), self.dmem_size())?;
+
+        register_base.dmemc0.write(offset | FALCON_MEMC_AINCR);
+        for chunk in buffer.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&register_base.dmemd0.read().to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Writes data to the DMEM at a given word-aligned offset.
+    pub fn write_dmem(&self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        Self::check_memory_access(offset, data.len(), self.dmem_size())?;
+
+        register_base.dmemc0.write(offset | FALCON_MEMC_AINCW);
+        for chunk in data.chunks_exact(4) {
+            register_base
+                .dmemd0
+                .write(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        }
+
+        Ok(())
+    }
+
+    /// Uploads code to the IMEM at a given word-aligned offset
+    /// through the IMEM port, as an alternative to DMA transfers.
+    ///
+    /// Every 256 byte page is tagged with its virtual page number.
+    pub fn upload_imem(&self, offset: u32, code: &[u8], secure: bool) -> Result<(), Error> {
+        let register_base = unsafe { &*self.registers };
+
+        Self::check_memory_access(offset, code.len(), self.imem_size())?;
+
+        let mut control = offset | FALCON_MEMC_AINCW;
+        if secure {
+            control |= FALCON_IMEMC_SECURE;
+        }
+        register_base.imemc0.write(control);
+
+        for (i, chunk) in code.chunks_exact(4).enumerate() {
+            let address = offset + (i as u32) * 4;
+
+            // Tag each page before writing its first word.
+            if i == 0 || address & 0xFF == 0 {
+                register_base.imemt0.write(address >> 8);
+            }
+
+            register_base
+                .imemd0
+                .write(u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        }
+
+        Ok(())
+    }
+
+    /// Starts the CPU at boot vector 0, passing an argument in `MAILBOX0`.
+    ///
+    /// `MAILBOX1` is cleared beforehand so that the firmware's
+    /// response can be detected.
+    pub fn boot(&self, argument: u32) {
+        let register_base = unsafe { &*self.registers };
+
+        register_base.mailbox1.write(0);
+        register_base.mailbox0.write(argument);
+        register_base.bootvec.write(0);
+        register_base.cpuctl.write(2);
+    }
+
+    /// Reads the value of `MAILBOX0`.
+    pub fn mailbox0(&self) -> u32 {
+        unsafe { (*self.registers).mailbox0.read() }
+    }
+
+    /// Reads the value of `MAILBOX1`.
+    pub fn mailbox1(&self) -> u32 {
+        unsafe { (*self.registers).mailbox1.read() }
+    }
+
+    /// Writes a value to `MAILBOX0`.
+    pub fn set_mailbox0(&self, value: u32) {
+        unsafe { (*self.registers).mailbox0.write(value) }
+    }
+
+    /// Writes a value to `MAILBOX1`.
+    pub fn set_mailbox1(&self, value: u32) {
+        unsafe { (*self.registers).mailbox1.write(value) }
+    }
+
+    /// Waits up to `timeout` milliseconds for the firmware to write a
+    /// non-zero value to `MAILBOX1` and returns it.
+    pub fn wait_for_mailbox1(&self, timeout: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        wait_for(
+            || {
+                value = self.mailbox1();
+                value != 0
+            },
+            timeout * 1000,
+        )
+        .map_err(|_| Error::MailboxTimeout)?;
+
+        Ok(value)
+    }
+}
+
+unsafe impl Send for Falcon {}
+
+unsafe impl Sync for Falcon {}
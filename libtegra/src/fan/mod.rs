@@ -0,0 +1,206 @@
+//! Control of the cooling fan.
+//!
+//! # Description
+//!
+//! Both Erista and Mariko units cool the SoC with a fan, which is powered
+//! from the 5V rail of the battery regulator. Its speed is controlled by
+//! the first channel of the PWM controller with inverted polarity, and it
+//! reports two tachometer pulses per revolution on GPIO S7.
+//!
+//! Maintenance payloads doing sustained work, such as NAND dumps or memory
+//! tests, should spin up the fan instead of relying on passive cooling.
+//!
+//! # Implementation
+//!
+//! - [`set_speed`] powers the fan and sets its duty cycle in percent. A
+//! speed of zero stops the fan and cuts its supply again.
+//!
+//! - [`speed`] gets the currently configured duty cycle.
+//!
+//! - [`rpm`] counts tachometer pulses over a given window to calculate
+//! the actual speed of the fan in revolutions per minute.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::fan;
+//!
+//! fn main() {
+//!     fan::set_speed(80);
+//!
+//!     // Dump the NAND.
+//!
+//!     println!("The fan spins at {} RPM", fan::rpm(1000));
+//!     fan::set_speed(0);
+//! }
+//! ```
+//!
+//! [`set_speed`]: fn.set_speed.html
+//! [`speed`]: fn.speed.html
+//! [`rpm`]: fn.rpm.html
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use crate::{
+    clock::Clock,
+    gpio::{Gpio, GpioConfig, GpioLevel, GpioMode, GpioPin, GpioPort},
+    pinmux::{Pad, Pinmux, INPUT, PULL_UP, TRISTATE},
+    timer::{get_microseconds, get_time_since},
+};
+
+/// Base address for the PWM controller registers.
+const PWM_BASE: u32 = 0x7000_A000;
+
+/// The `PWM_CONTROLLER_PWM_CSR_1` register.
+const PWM_CSR_1: u32 = 0x10;
+
+/// Enables the PWM channel in `PWM_CSR`.
+const PWM_CSR_ENABLE: u32 = 1 << 31;
+/// The shift of the pulse width in `PWM_CSR`.
+const PWM_CSR_PULSE_WIDTH_SHIFT: u32 = 16;
+/// The mask of the pulse width in `PWM_CSR`.
+const PWM_CSR_PULSE_WIDTH_MASK: u32 = 0x1FF;
+
+/// The pulse width the fan reaches full speed at.
+///
+/// The fan is driven with inverted polarity, so this is the pulse width
+/// that stops it.
+const FAN_MAX_PULSE_WIDTH: u32 = 236;
+/// The pulse width that keeps the PWM output high all the time.
+const FAN_OFF_PULSE_WIDTH: u32 = 0x100;
+
+/// The pad muxing the PWM output to the fan.
+const FAN_PWM_PAD: Pad = Pad::LcdGpio2;
+/// The function that routes PWM1 to `FAN_PWM_PAD`.
+const FAN_PWM_FUNCTION: u32 = 1;
+/// The pad of the tachometer input.
+const FAN_TACH_PAD: Pad = Pad::Cam1Pwdn;
+
+/// The GPIO that enables the 5V supply of the fan.
+const FAN_5V_EN: Gpio = Gpio::new(GpioPort::A, GpioPin::P5);
+/// The GPIO that is driven by the PWM output.
+const FAN_PWM: Gpio = Gpio::new(GpioPort::V, GpioPin::P4);
+/// The GPIO that receives the tachometer pulses.
+const FAN_TACH: Gpio = Gpio::new(GpioPort::S, GpioPin::P7);
+
+/// The number of tachometer pulses per revolution.
+const PULSES_PER_REVOLUTION: u32 = 2;
+
+/// Gets the PWM register controlling the fan.
+fn csr() -> &'static Mmio<u32> {
+    unsafe { &*((PWM_BASE + PWM_CSR_1) as *const Mmio<u32>) }
+}
+
+/// Converts a speed in percent into a PWM pulse width.
+fn pulse_width(percent: u32) -> u32 {
+    FAN_MAX_PULSE_WIDTH - percent.min(100) * FAN_MAX_PULSE_WIDTH / 100
+}
+
+/// Converts the number of tachometer pulses over a window in milliseconds
+/// into revolutions per minute.
+fn pulses_to_rpm(pulses: u32, window: u32) -> u32 {
+    if window == 0 {
+        return 0;
+    }
+
+    pulses * 60_000 / (PULSES_PER_REVOLUTION * window)
+}
+
+/// Powers the fan up and routes the PWM output to it.
+fn power_on() {
+    let pinmux = unsafe { Pinmux::get() };
+
+    let mut clock = Clock::PWM;
+    clock.enable();
+
+    FAN_5V_EN.config(GpioConfig::OutputHigh);
+
+    pinmux
+        .pad(FAN_TACH_PAD)
+        .write(TRISTATE | INPUT | PULL_UP | 1);
+    FAN_TACH.config(GpioConfig::Input);
+
+    // Keep the fan stopped until the duty cycle is set.
+    csr().write(PWM_CSR_ENABLE | FAN_OFF_PULSE_WIDTH << PWM_CSR_PULSE_WIDTH_SHIFT);
+    pinmux.pad(FAN_PWM_PAD).write(FAN_PWM_FUNCTION);
+    FAN_PWM.set_mode(GpioMode::SFIO);
+}
+
+/// Stops the fan and cuts its supply.
+fn power_off() {
+    csr().write(PWM_CSR_ENABLE | FAN_OFF_PULSE_WIDTH << PWM_CSR_PULSE_WIDTH_SHIFT);
+    FAN_PWM.config(GpioConfig::OutputLow);
+    FAN_5V_EN.config(GpioConfig::OutputLow);
+}
+
+/// Sets the speed of the fan in percent.
+///
+/// Speeds above 100 are clamped, a speed of zero turns the fan off.
+pub fn set_speed(percent: u32) {
+    if percent == 0 {
+        power_off();
+        return;
+    }
+
+    if speed() == 0 {
+        power_on();
+    }
+
+    csr().write(PWM_CSR_ENABLE | pulse_width(percent) << PWM_CSR_PULSE_WIDTH_SHIFT);
+}
+
+/// Gets the configured speed of the fan in percent.
+pub fn speed() -> u32 {
+    let csr = csr().read();
+    if csr & PWM_CSR_ENABLE == 0 || FAN_5V_EN.get_output() == GpioLevel::Low {
+        return 0;
+    }
+
+    let width = (csr >> PWM_CSR_PULSE_WIDTH_SHIFT) & PWM_CSR_PULSE_WIDTH_MASK;
+    if width >= FAN_MAX_PULSE_WIDTH {
+        return 0;
+    }
+
+    ((FAN_MAX_PULSE_WIDTH - width) * 100 + FAN_MAX_PULSE_WIDTH - 1) / FAN_MAX_PULSE_WIDTH
+}
+
+/// Measures the speed of the fan in revolutions per minute.
+///
+/// This blocks for `window` milliseconds to count tachometer pulses.
+/// Longer windows give more accurate results.
+pub fn rpm(window: u32) -> u32 {
+    if speed() == 0 {
+        return 0;
+    }
+
+    let start = get_microseconds();
+    let mut pulses = 0;
+    let mut level = FAN_TACH.read();
+
+    while get_time_since(start) < window * 1000 {
+        let current = FAN_TACH.read();
+        if current != level && current == GpioLevel::High {
+            pulses += 1;
+        }
+
+        level = current;
+    }
+
+    pulses_to_rpm(pulses, window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_speeds() {
+        assert_eq!(pulse_width(0), FAN_MAX_PULSE_WIDTH);
+        assert_eq!(pulse_width(100), 0);
+        assert_eq!(pulse_width(150), 0);
+        assert_eq!(pulse_width(50), FAN_MAX_PULSE_WIDTH / 2);
+
+        assert_eq!(pulses_to_rpm(100, 1000), 3000);
+        assert_eq!(pulses_to_rpm(100, 0), 0);
+    }
+}
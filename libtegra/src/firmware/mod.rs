@@ -0,0 +1,138 @@
+//! Embedding of firmware blobs and other binary assets.
+//!
+//! # Description
+//!
+//! Engines like the TSEC fetch their firmware through DMA, which requires
+//! the blob to be placed at a suitably aligned address. `include_bytes!`
+//! provides no guarantees about the alignment of the data, so blobs had to
+//! be converted into source files with hand-written alignment wrappers.
+//!
+//! # Implementation
+//!
+//! - The [`include_firmware!`] macro embeds a file into a static with the
+//! requested alignment, placed into a dedicated linker section, and
+//! evaluates to a `&'static` [`AlignedBlob`].
+//!
+//! - The alignment defaults to `0x100` bytes, which satisfies the DMA
+//! requirements of the Falcon engines. The section defaults to
+//! `.rodata.firmware`.
+//!
+//! - [`AlignedBlob`] dereferences to the raw bytes of the blob and thus can
+//! be passed to anything accepting a byte slice.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{firmware::AlignedBlob, include_firmware, tsec::Tsec};
+//!
+//! static TSEC_FIRMWARE: &AlignedBlob = include_firmware!("tsec_fw.bin", align = 0x100);
+//!
+//! fn main() {
+//!     Tsec::new().load_firmware(TSEC_FIRMWARE).unwrap();
+//! }
+//! ```
+//!
+//! [`include_firmware!`]: ../macro.include_firmware.html
+//! [`AlignedBlob`]: struct.AlignedBlob.html
+
+use core::ops::Deref;
+
+/// A blob of binary data which is guaranteed to be aligned.
+#[derive(Debug)]
+pub struct AlignedBlob {
+    /// The data of the blob.
+    data: &'static [u8],
+    /// The guaranteed alignment of the data.
+    align: usize,
+}
+
+impl AlignedBlob {
+    /// Creates a blob from data that is aligned to `align` bytes.
+    ///
+    /// Use the [`include_firmware!`] macro rather than calling this
+    /// directly.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the data is actually aligned.
+    ///
+    /// [`include_firmware!`]: ../macro.include_firmware.html
+    pub const unsafe fn new_unchecked(data: &'static [u8], align: usize) -> Self {
+        AlignedBlob { data, align }
+    }
+
+    /// Creates a blob from the given data, if it is aligned to `align` bytes.
+    pub fn new(data: &'static [u8], align: usize) -> Option<Self> {
+        if align.is_power_of_two() && data.as_ptr() as usize % align == 0 {
+            Some(AlignedBlob { data, align })
+        } else {
+            None
+        }
+    }
+
+    /// Gets the raw bytes of the blob.
+    pub fn as_bytes(&self) -> &'static [u8] {
+        self.data
+    }
+
+    /// Gets the guaranteed alignment of the blob.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// Gets the physical address of the blob.
+    pub fn address(&self) -> u32 {
+        self.data.as_ptr() as usize as u32
+    }
+}
+
+impl Deref for AlignedBlob {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl AsRef<[u8]> for AlignedBlob {
+    fn as_ref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+/// Embeds a file as an aligned firmware blob.
+///
+/// Evaluates to a `&'static AlignedBlob`, with the data aligned to `align`
+/// bytes and placed into the given linker section. The path is resolved
+/// like with `include_bytes!`.
+///
+/// # Example
+///
+/// ```
+/// use mirage_libtegra::{firmware::AlignedBlob, include_firmware};
+///
+/// static SPLASH: &AlignedBlob = include_firmware!("splash.bmp", align = 0x40, section = ".rodata.splash");
+/// ```
+#[macro_export]
+macro_rules! include_firmware {
+    ($path:literal) => {
+        $crate::include_firmware!($path, align = 0x100)
+    };
+    ($path:literal, align = $align:literal) => {
+        $crate::include_firmware!($path, align = $align, section = ".rodata.firmware")
+    };
+    ($path:literal, align = $align:literal, section = $section:literal) => {{
+        #[repr(C, align($align))]
+        struct Aligned<T: ?Sized>(T);
+
+        const DATA: &[u8] = include_bytes!($path);
+
+        #[link_section = $section]
+        static ALIGNED: Aligned<[u8; DATA.len()]> = Aligned(*include_bytes!($path));
+
+        static BLOB: $crate::firmware::AlignedBlob =
+            unsafe { $crate::firmware::AlignedBlob::new_unchecked(&ALIGNED.0, $align) };
+
+        &BLOB
+    }};
+}
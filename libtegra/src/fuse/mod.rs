@@ -1,15 +1,31 @@
 //! Tegra210 Fuse implementation.
 
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{Mmio, ReadOnly, VolatileStorage};
 
-use crate::{clock::Car, timer::usleep};
+use crate::{
+    clock::Car,
+    keys::wipe,
+    se::{SecurityEngine, SHA256_HASH_SIZE},
+    timer::wait_for,
+    Error, Result,
+};
+
+/// The time in microseconds the fuse controller may take to finish an
+/// operation.
+const IDLE_TIMEOUT: u32 = 10_000;
+
+/// The size of the Secure Boot Key in bytes.
+pub const SBK_SIZE: usize = 0x10;
+
+/// Hides the private key fuses from reads in `FUSE_PRIVATEKEYDISABLE`.
+const PRIVATEKEYDISABLE: u32 = 1 << 0;
 
 /// Representation of the Fuse registers.
 #[repr(C)]
 pub struct Fuse {
     pub ctrl: Mmio<u32>,
     pub reg_addr: Mmio<u32>,
-    pub reg_read: Mmio<u32>,
+    pub reg_read: ReadOnly<u32>,
     pub reg_write: Mmio<u32>,
     pub time_rd1: Mmio<u32>,
     pub time_rd2: Mmio<u32>,
@@ -31,148 +47,150 @@ impl VolatileStorage for Fuse {
 }
 
 /// Representation of the Fuse chip.
+///
+/// These are the cached fuse values, which can only be read.
 #[repr(C)]
 pub struct FuseChip {
-    pub production_mode: Mmio<u32>,
-    _0x4: Mmio<u32>,
-    _0x8: Mmio<u32>,
-    _0xc: Mmio<u32>,
-    pub sku_info: Mmio<u32>,
-    pub cpu_speedo_0: Mmio<u32>,
-    pub cpu_iddq: Mmio<u32>,
-    _0x1c: Mmio<u32>,
-    _0x20: Mmio<u32>,
-    _0x24: Mmio<u32>,
-    pub ft_rev: Mmio<u32>,
-    pub cpu_speedo_1: Mmio<u32>,
-    pub cpu_speedo_2: Mmio<u32>,
-    pub soc_speedo_0: Mmio<u32>,
-    pub soc_speedo_1: Mmio<u32>,
-    pub soc_speedo_2: Mmio<u32>,
-    pub soc_iddq: Mmio<u32>,
-    _0x44: Mmio<u32>,
-    pub fa: Mmio<u32>,
-    _0x4c: Mmio<u32>,
-    _0x50: Mmio<u32>,
-    _0x54: Mmio<u32>,
-    _0x58: Mmio<u32>,
-    _0x5c: Mmio<u32>,
-    _0x60: Mmio<u32>,
-    pub public_key: [Mmio<u32>; 0x8],
-    pub tsensor_1: Mmio<u32>,
-    pub tsensor_2: Mmio<u32>,
-    _0x8c: Mmio<u32>,
-    pub cp_rev: Mmio<u32>,
-    _0x84: Mmio<u32>,
-    pub tsensor_0: Mmio<u32>,
-    pub first_bootrom_patch_size_reg: Mmio<u32>,
-    pub security_mode: Mmio<u32>,
-    pub private_key: [Mmio<u32>; 0x4],
-    device_key: Mmio<u32>,
-    _0xb8: Mmio<u32>,
-    _0xbc: Mmio<u32>,
-    pub reserved_sw: Mmio<u32>,
-    pub vp8_enable: Mmio<u32>,
-    pub reserved_odm: [Mmio<u32>; 0x8],
-    _0xe8: Mmio<u32>,
-    _0xec: Mmio<u32>,
-    pub sku_usb_calib: Mmio<u32>,
-    pub sku_direct_config: Mmio<u32>,
-    _0xf8: Mmio<u32>,
-    _0xfc: Mmio<u32>,
-    pub vendor_code: Mmio<u32>,
-    pub fab_code: Mmio<u32>,
-    pub lot_code_0: Mmio<u32>,
-    pub lot_code_1: Mmio<u32>,
-    pub wafer_id: Mmio<u32>,
-    pub x_coordinate: Mmio<u32>,
-    pub y_coordinate: Mmio<u32>,
-    _0x11c: Mmio<u32>,
-    _0x120: Mmio<u32>,
-    pub sata_calib: Mmio<u32>,
-    pub gpu_iddq: Mmio<u32>,
-    pub tsensor_3: Mmio<u32>,
-    _0x130: Mmio<u32>,
-    _0x134: Mmio<u32>,
-    _0x138: Mmio<u32>,
-    _0x13c: Mmio<u32>,
-    _0x140: Mmio<u32>,
-    _0x144: Mmio<u32>,
-    pub opt_subrevision: Mmio<u32>,
-    _0x14c: Mmio<u32>,
-    _0x150: Mmio<u32>,
-    pub tsensor_4: Mmio<u32>,
-    pub tsensor_5: Mmio<u32>,
-    pub tsensor_6: Mmio<u32>,
-    pub tsensor_7: Mmio<u32>,
-    pub opt_priv_sec_dis: Mmio<u32>,
-    pub pkc_disable: Mmio<u32>,
-    _0x16c: Mmio<u32>,
-    _0x170: Mmio<u32>,
-    _0x174: Mmio<u32>,
-    _0x178: Mmio<u32>,
-    _0x17c: Mmio<u32>,
-    pub tsensor_common: Mmio<u32>,
-    _0x184: Mmio<u32>,
-    _0x188: Mmio<u32>,
-    _0x18c: Mmio<u32>,
-    _0x190: Mmio<u32>,
-    _0x194: Mmio<u32>,
-    _0x198: Mmio<u32>,
-    pub debug_auth_override: Mmio<u32>,
-    _0x1a0: Mmio<u32>,
-    _0x1a4: Mmio<u32>,
-    _0x1a8: Mmio<u32>,
-    _0x1ac: Mmio<u32>,
-    _0x1b0: Mmio<u32>,
-    _0x1b4: Mmio<u32>,
-    _0x1b8: Mmio<u32>,
-    _0x1bc: Mmio<u32>,
-    _0x1d0: Mmio<u32>,
-    pub tsensor_8: Mmio<u32>,
-    _0x1d8: Mmio<u32>,
-    _0x1dc: Mmio<u32>,
-    _0x1e0: Mmio<u32>,
-    _0x1e4: Mmio<u32>,
-    _0x1e8: Mmio<u32>,
-    _0x1ec: Mmio<u32>,
-    _0x1f0: Mmio<u32>,
-    _0x1f4: Mmio<u32>,
-    _0x1f8: Mmio<u32>,
-    _0x1fc: Mmio<u32>,
-    _0x200: Mmio<u32>,
-    pub reserved_calib: Mmio<u32>,
-    _0x208: Mmio<u32>,
-    _0x20c: Mmio<u32>,
-    _0x210: Mmio<u32>,
-    _0x214: Mmio<u32>,
-    _0x218: Mmio<u32>,
-    pub tsensor_9: Mmio<u32>,
-    _0x220: Mmio<u32>,
-    _0x224: Mmio<u32>,
-    _0x228: Mmio<u32>,
-    _0x22c: Mmio<u32>,
-    _0x230: Mmio<u32>,
-    _0x234: Mmio<u32>,
-    _0x238: Mmio<u32>,
-    _0x23c: Mmio<u32>,
-    _0x240: Mmio<u32>,
-    _0x244: Mmio<u32>,
-    _0x248: Mmio<u32>,
-    _0x24c: Mmio<u32>,
-    pub usb_calib_ext: Mmio<u32>,
-    _0x254: Mmio<u32>,
-    _0x258: Mmio<u32>,
-    _0x25c: Mmio<u32>,
-    _0x260: Mmio<u32>,
-    _0x264: Mmio<u32>,
-    _0x268: Mmio<u32>,
-    _0x26c: Mmio<u32>,
-    _0x270: Mmio<u32>,
-    _0x274: Mmio<u32>,
-    _0x278: Mmio<u32>,
-    _0x27c: Mmio<u32>,
-    pub spare_bit: [Mmio<u32>; 0x20],
+    pub production_mode: ReadOnly<u32>,
+    _0x4: ReadOnly<u32>,
+    _0x8: ReadOnly<u32>,
+    _0xc: ReadOnly<u32>,
+    pub sku_info: ReadOnly<u32>,
+    pub cpu_speedo_0: ReadOnly<u32>,
+    pub cpu_iddq: ReadOnly<u32>,
+    _0x1c: ReadOnly<u32>,
+    _0x20: ReadOnly<u32>,
+    _0x24: ReadOnly<u32>,
+    pub ft_rev: ReadOnly<u32>,
+    pub cpu_speedo_1: ReadOnly<u32>,
+    pub cpu_speedo_2: ReadOnly<u32>,
+    pub soc_speedo_0: ReadOnly<u32>,
+    pub soc_speedo_1: ReadOnly<u32>,
+    pub soc_speedo_2: ReadOnly<u32>,
+    pub soc_iddq: ReadOnly<u32>,
+    _0x44: ReadOnly<u32>,
+    pub fa: ReadOnly<u32>,
+    _0x4c: ReadOnly<u32>,
+    _0x50: ReadOnly<u32>,
+    _0x54: ReadOnly<u32>,
+    _0x58: ReadOnly<u32>,
+    _0x5c: ReadOnly<u32>,
+    _0x60: ReadOnly<u32>,
+    pub public_key: [ReadOnly<u32>; 0x8],
+    pub tsensor_1: ReadOnly<u32>,
+    pub tsensor_2: ReadOnly<u32>,
+    _0x8c: ReadOnly<u32>,
+    pub cp_rev: ReadOnly<u32>,
+    _0x84: ReadOnly<u32>,
+    pub tsensor_0: ReadOnly<u32>,
+    pub first_bootrom_patch_size_reg: ReadOnly<u32>,
+    pub security_mode: ReadOnly<u32>,
+    pub private_key: [ReadOnly<u32>; 0x4],
+    device_key: ReadOnly<u32>,
+    _0xb8: ReadOnly<u32>,
+    _0xbc: ReadOnly<u32>,
+    pub reserved_sw: ReadOnly<u32>,
+    pub vp8_enable: ReadOnly<u32>,
+    pub reserved_odm: [ReadOnly<u32>; 0x8],
+    _0xe8: ReadOnly<u32>,
+    _0xec: ReadOnly<u32>,
+    pub sku_usb_calib: ReadOnly<u32>,
+    pub sku_direct_config: ReadOnly<u32>,
+    _0xf8: ReadOnly<u32>,
+    _0xfc: ReadOnly<u32>,
+    pub vendor_code: ReadOnly<u32>,
+    pub fab_code: ReadOnly<u32>,
+    pub lot_code_0: ReadOnly<u32>,
+    pub lot_code_1: ReadOnly<u32>,
+    pub wafer_id: ReadOnly<u32>,
+    pub x_coordinate: ReadOnly<u32>,
+    pub y_coordinate: ReadOnly<u32>,
+    _0x11c: ReadOnly<u32>,
+    _0x120: ReadOnly<u32>,
+    pub sata_calib: ReadOnly<u32>,
+    pub gpu_iddq: ReadOnly<u32>,
+    pub tsensor_3: ReadOnly<u32>,
+    _0x130: ReadOnly<u32>,
+    _0x134: ReadOnly<u32>,
+    _0x138: ReadOnly<u32>,
+    _0x13c: ReadOnly<u32>,
+    _0x140: ReadOnly<u32>,
+    _0x144: ReadOnly<u32>,
+    pub opt_subrevision: ReadOnly<u32>,
+    _0x14c: ReadOnly<u32>,
+    _0x150: ReadOnly<u32>,
+    pub tsensor_4: ReadOnly<u32>,
+    pub tsensor_5: ReadOnly<u32>,
+    pub tsensor_6: ReadOnly<u32>,
+    pub tsensor_7: ReadOnly<u32>,
+    pub opt_priv_sec_dis: ReadOnly<u32>,
+    pub pkc_disable: ReadOnly<u32>,
+    _0x16c: ReadOnly<u32>,
+    _0x170: ReadOnly<u32>,
+    _0x174: ReadOnly<u32>,
+    _0x178: ReadOnly<u32>,
+    _0x17c: ReadOnly<u32>,
+    pub tsensor_common: ReadOnly<u32>,
+    _0x184: ReadOnly<u32>,
+    _0x188: ReadOnly<u32>,
+    _0x18c: ReadOnly<u32>,
+    _0x190: ReadOnly<u32>,
+    _0x194: ReadOnly<u32>,
+    _0x198: ReadOnly<u32>,
+    pub debug_auth_override: ReadOnly<u32>,
+    _0x1a0: ReadOnly<u32>,
+    _0x1a4: ReadOnly<u32>,
+    _0x1a8: ReadOnly<u32>,
+    _0x1ac: ReadOnly<u32>,
+    _0x1b0: ReadOnly<u32>,
+    _0x1b4: ReadOnly<u32>,
+    _0x1b8: ReadOnly<u32>,
+    _0x1bc: ReadOnly<u32>,
+    _0x1d0: ReadOnly<u32>,
+    pub tsensor_8: ReadOnly<u32>,
+    _0x1d8: ReadOnly<u32>,
+    _0x1dc: ReadOnly<u32>,
+    _0x1e0: ReadOnly<u32>,
+    _0x1e4: ReadOnly<u32>,
+    _0x1e8: ReadOnly<u32>,
+    _0x1ec: ReadOnly<u32>,
+    _0x1f0: ReadOnly<u32>,
+    _0x1f4: ReadOnly<u32>,
+    _0x1f8: ReadOnly<u32>,
+    _0x1fc: ReadOnly<u32>,
+    _0x200: ReadOnly<u32>,
+    pub reserved_calib: ReadOnly<u32>,
+    _0x208: ReadOnly<u32>,
+    _0x20c: ReadOnly<u32>,
+    _0x210: ReadOnly<u32>,
+    _0x214: ReadOnly<u32>,
+    _0x218: ReadOnly<u32>,
+    pub tsensor_9: ReadOnly<u32>,
+    _0x220: ReadOnly<u32>,
+    _0x224: ReadOnly<u32>,
+    _0x228: ReadOnly<u32>,
+    _0x22c: ReadOnly<u32>,
+    _0x230: ReadOnly<u32>,
+    _0x234: ReadOnly<u32>,
+    _0x238: ReadOnly<u32>,
+    _0x23c: ReadOnly<u32>,
+    _0x240: ReadOnly<u32>,
+    _0x244: ReadOnly<u32>,
+    _0x248: ReadOnly<u32>,
+    _0x24c: ReadOnly<u32>,
+    pub usb_calib_ext: ReadOnly<u32>,
+    _0x254: ReadOnly<u32>,
+    _0x258: ReadOnly<u32>,
+    _0x25c: ReadOnly<u32>,
+    _0x260: ReadOnly<u32>,
+    _0x264: ReadOnly<u32>,
+    _0x268: ReadOnly<u32>,
+    _0x26c: ReadOnly<u32>,
+    _0x270: ReadOnly<u32>,
+    _0x274: ReadOnly<u32>,
+    _0x278: ReadOnly<u32>,
+    _0x27c: ReadOnly<u32>,
+    pub spare_bit: [ReadOnly<u32>; 0x20],
 }
 
 impl VolatileStorage for FuseChip {
@@ -211,21 +229,17 @@ pub fn disable_secondary_private_key() {
 }
 
 /// Wait for the fuse driver to enter an idle state.
-pub fn wait_idle() {
+pub fn wait_idle() -> Result<()> {
     let fuse = unsafe { Fuse::get() };
 
     // Wait for STATE_IDLE.
-    let mut ctrl = 0;
-    while (ctrl & 0xF0000) != 0x40000 {
-        usleep(1);
-        ctrl = fuse.ctrl.read();
-    }
+    wait_for(|| (fuse.ctrl.read() & 0xF0000) == 0x40000, IDLE_TIMEOUT)
 }
 
 /// Reads a fuse from the hardware array.
-pub fn hardware_read(address: u32) -> u32 {
+pub fn hardware_read(address: u32) -> Result<u32> {
     let fuse = unsafe { Fuse::get() };
-    wait_idle();
+    wait_idle()?;
 
     // Program the target address.
     fuse.reg_addr.write(address);
@@ -236,15 +250,15 @@ pub fn hardware_read(address: u32) -> u32 {
     ctrl |= 0x1; // Set FUSE_READ command.
     fuse.ctrl.write(ctrl);
 
-    wait_idle();
+    wait_idle()?;
 
-    fuse.reg_read.read()
+    Ok(fuse.reg_read.read())
 }
 
 /// Writes a fuse to the hardware array.
-pub fn hardware_write(address: u32, value: u32) {
+pub fn hardware_write(address: u32, value: u32) -> Result<()> {
     let fuse = unsafe { Fuse::get() };
-    wait_idle();
+    wait_idle()?;
 
     // Program the target address and value.
     fuse.reg_addr.write(address);
@@ -256,13 +270,13 @@ pub fn hardware_write(address: u32, value: u32) {
     ctrl |= 0x2; // Set FUSE_WRITE command.
     fuse.ctrl.write(ctrl);
 
-    wait_idle();
+    wait_idle()
 }
 
 /// Senses the fuse hardware array into the shadow cache.
-pub fn hardware_sense() {
+pub fn hardware_sense() -> Result<()> {
     let fuse = unsafe { Fuse::get() };
-    wait_idle();
+    wait_idle()?;
 
     // Enable sense operation in control register.
     let mut ctrl = fuse.ctrl.read();
@@ -270,7 +284,7 @@ pub fn hardware_sense() {
     ctrl |= 0x3; // Set FUSE_SENSE command.
     fuse.ctrl.write(ctrl);
 
-    wait_idle();
+    wait_idle()
 }
 
 /// Reads the SKU info register from the shadow cache.
@@ -309,8 +323,78 @@ pub fn read_reserved_odm(index: usize) -> u32 {
     }
 }
 
+/// Whether the private key fuses, i.e. the SBK and the device key, can
+/// still be read.
+///
+/// The bootrom hides them before it runs the bootloader, unless the
+/// console was booted through RCM.
+pub fn is_private_key_visible() -> bool {
+    let fuse = unsafe { Fuse::get() };
+
+    fuse.privatekeydisable.read() & PRIVATEKEYDISABLE == 0
+}
+
+/// Reads the Secure Boot Key from the shadow cache.
+///
+/// The fuse words are laid out in little-endian order, which is what the
+/// SE expects when the key is loaded into a keyslot. Returns `None` if the
+/// private key fuses are hidden, as they read as zeros then.
+pub fn secure_boot_key() -> Option<[u8; SBK_SIZE]> {
+    if !is_private_key_visible() {
+        return None;
+    }
+
+    let fuse_chip = unsafe { FuseChip::get() };
+    let mut key = [0; SBK_SIZE];
+
+    for (bytes, word) in key.chunks_exact_mut(4).zip(fuse_chip.private_key.iter()) {
+        bytes.copy_from_slice(&word.read().to_le_bytes());
+    }
+
+    Some(key)
+}
+
+/// Reads the 32-bit device key from the shadow cache.
+///
+/// Returns `None` if the private key fuses are hidden.
+pub fn device_key() -> Option<u32> {
+    if !is_private_key_visible() {
+        return None;
+    }
+
+    let fuse_chip = unsafe { FuseChip::get() };
+
+    Some(fuse_chip.device_key.read())
+}
+
+/// Computes the SHA-256 hash of the SBK followed by the device key.
+///
+/// The hash identifies a console without revealing its keys, e.g. to tell
+/// backups of different consoles apart. Fails with [`Error::Fuse`] if the
+/// private key fuses are hidden.
+///
+/// [`Error::Fuse`]: ../enum.Error.html#variant.Fuse
+pub fn private_key_hash() -> Result<[u8; SHA256_HASH_SIZE]> {
+    let mut key = secure_boot_key().ok_or(Error::Fuse)?;
+    let device_key = device_key().ok_or(Error::Fuse)?;
+
+    let mut data = [0; SBK_SIZE + 4];
+    data[..SBK_SIZE].copy_from_slice(&key);
+    data[SBK_SIZE..].copy_from_slice(&device_key.to_le_bytes());
+
+    let hash = SecurityEngine::new().calculate_sha256(&data);
+
+    wipe(&mut key);
+    wipe(&mut data);
+
+    hash
+}
+
 /// Retrieves the Device ID from the shadow cache.
-pub fn get_device_id() -> u64 {
+///
+/// The ID is assembled from the fab, lot, wafer and die coordinates of
+/// the SoC and is unique per console, but not secret.
+pub fn device_id() -> u64 {
     let fuse_chip = unsafe { FuseChip::get() };
     let mut device_id = 0;
 
@@ -334,3 +418,40 @@ pub fn get_device_id() -> u64 {
 
     device_id
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    fn address<T>(register: &T) -> usize {
+        register as *const T as usize
+    }
+
+    /// Tests the byte order of the SBK and hiding the private key.
+    #[test]
+    fn secure_boot_key() {
+        let _session = mock::start();
+
+        let fuse = unsafe { Fuse::get() };
+        let fuse_chip = unsafe { FuseChip::get() };
+
+        for (i, word) in fuse_chip.private_key.iter().enumerate() {
+            mock::set(address(word), 0x0302_0100 + 0x0404_0404 * i as u64);
+        }
+        mock::set(address(&fuse_chip.device_key), 0xDEAD_BEEF);
+
+        let mut expected = [0; SBK_SIZE];
+        for (i, byte) in expected.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        assert_eq!(super::secure_boot_key(), Some(expected));
+        assert_eq!(device_key(), Some(0xDEAD_BEEF));
+
+        mock::set(address(&fuse.privatekeydisable), u64::from(PRIVATEKEYDISABLE));
+        assert!(!is_private_key_visible());
+        assert_eq!(super::secure_boot_key(), None);
+        assert_eq!(device_key(), None);
+    }
+}
@@ -0,0 +1,553 @@
+//! GDB Remote Serial Protocol stub for the BPMP.
+//!
+//! # Description
+//!
+//! The stub lets GDB debug code running on the BPMP through a UART, without
+//! any JTAG hardware. It supports reading and writing registers and memory,
+//! software breakpoints and single-stepping.
+//!
+//! Breakpoints are undefined instructions that trap into the stub. As the
+//! ARM7TDMI can't single-step in hardware, steps are emulated by placing a
+//! temporary breakpoint on the instruction that executes next.
+//!
+//! The stub is only entered through traps, so GDB can't interrupt a running
+//! program with `Ctrl-C`. Call [`breakpoint`] at interesting places instead.
+//!
+//! # Implementation
+//!
+//! - The stub is only available with the `gdbstub` feature and when building
+//! for the BPMP, i.e. an ARM target.
+//!
+//! - [`init`] installs the undefined instruction handler into the
+//! exception vectors of the BPMP and sets up a stack for it. The UART has to
+//! be initialized beforehand and is then owned by the stub.
+//!
+//! - [`breakpoint`] stops the program and hands control to GDB.
+//!
+//! - The handler saves the state of the program as a [`Frame`], which is
+//! described to GDB through a target description of the
+//! `org.gnu.gdb.arm.core` feature.
+//!
+//! - Undefined instructions other than breakpoints are reported to GDB as
+//! `SIGILL`.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{gdbstub, peripherals::Peripherals};
+//!
+//! fn main() {
+//!     let mut uart = Peripherals::take().unwrap().uart_b;
+//!
+//!     uart.init(115_200).unwrap();
+//!     gdbstub::init(uart);
+//!
+//!     // Waits for GDB to connect, e.g. with `target remote /dev/ttyUSB0`.
+//!     gdbstub::breakpoint();
+//! }
+//! ```
+//!
+//! [`breakpoint`]: fn.breakpoint.html
+//! [`init`]: fn.init.html
+//! [`Frame`]: struct.Frame.html
+
+use core::{
+    fmt::Write,
+    ptr::{read_volatile, write_volatile},
+};
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use crate::{arch, sysreg::EXCEPTION_VECTOR_BASE, uart::Uart, Result};
+
+use packet::{Connection, Response, PACKET_SIZE};
+use step::{Memory, CPSR_THUMB};
+
+mod packet;
+mod step;
+
+global_asm!(include_str!("entry.S"));
+
+/// The undefined instruction vector of the BPMP.
+const EVP_COP_UNDEF_VECTOR: u32 = EXCEPTION_VECTOR_BASE + 0x204;
+
+/// The permanently undefined ARM instruction used as a breakpoint.
+const ARM_BREAKPOINT: u32 = 0xE7FF_DEFE;
+
+/// The permanently undefined Thumb instruction used as a breakpoint.
+const THUMB_BREAKPOINT: u16 = 0xDEFE;
+
+/// The maximum amount of breakpoints that can be set by GDB at once.
+const MAX_BREAKPOINTS: usize = 16;
+
+/// The size of the stack of the undefined instruction handler.
+const STACK_SIZE: usize = 0x800;
+
+/// The `SIGILL` signal, reported for undefined instructions.
+const SIGILL: u8 = 4;
+
+/// The `SIGTRAP` signal, reported for breakpoints and steps.
+const SIGTRAP: u8 = 5;
+
+/// The target description, which lists the registers in the order of a
+/// [`Frame`].
+///
+/// [`Frame`]: struct.Frame.html
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target>
+<architecture>arm</architecture>
+<feature name="org.gnu.gdb.arm.core">
+<reg name="r0" bitsize="32"/>
+<reg name="r1" bitsize="32"/>
+<reg name="r2" bitsize="32"/>
+<reg name="r3" bitsize="32"/>
+<reg name="r4" bitsize="32"/>
+<reg name="r5" bitsize="32"/>
+<reg name="r6" bitsize="32"/>
+<reg name="r7" bitsize="32"/>
+<reg name="r8" bitsize="32"/>
+<reg name="r9" bitsize="32"/>
+<reg name="r10" bitsize="32"/>
+<reg name="r11" bitsize="32"/>
+<reg name="r12" bitsize="32"/>
+<reg name="sp" bitsize="32" type="data_ptr"/>
+<reg name="lr" bitsize="32"/>
+<reg name="pc" bitsize="32" type="code_ptr"/>
+<reg name="cpsr" bitsize="32"/>
+</feature>
+</target>
+"#;
+
+/// The register state of the debugged program.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Frame {
+    /// The general purpose registers `r0` to `r15`, with `sp`, `lr` and
+    /// `pc` as seen by the interrupted mode.
+    pub r: [u32; 16],
+    /// The saved program status register.
+    pub cpsr: u32,
+}
+
+impl Frame {
+    /// The amount of registers known to GDB.
+    const REGISTER_COUNT: usize = 17;
+
+    /// Gets a register by its GDB number.
+    fn register(&mut self, index: usize) -> Option<&mut u32> {
+        match index {
+            0..=15 => Some(&mut self.r[index]),
+            16 => Some(&mut self.cpsr),
+            _ => None,
+        }
+    }
+}
+
+/// A breakpoint instruction patched into the program.
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    /// The address of the breakpoint.
+    address: u32,
+    /// Whether the breakpoint is a Thumb instruction.
+    thumb: bool,
+    /// The instruction that was replaced.
+    original: u32,
+}
+
+impl Breakpoint {
+    /// Patches a breakpoint into the program.
+    unsafe fn insert(address: u32, thumb: bool) -> Self {
+        let original = if thumb {
+            let original = read_volatile(address as *const u16);
+            write_volatile(address as *mut u16, THUMB_BREAKPOINT);
+            u32::from(original)
+        } else {
+            let original = read_volatile(address as *const u32);
+            write_volatile(address as *mut u32, ARM_BREAKPOINT);
+            original
+        };
+        arch::clean_invalidate_range(address as usize, 4);
+
+        Breakpoint {
+            address,
+            thumb,
+            original,
+        }
+    }
+
+    /// Restores the original instruction.
+    unsafe fn remove(&self) {
+        if self.thumb {
+            write_volatile(self.address as *mut u16, self.original as u16);
+        } else {
+            write_volatile(self.address as *mut u32, self.original);
+        }
+        arch::clean_invalidate_range(self.address as usize, 4);
+    }
+}
+
+/// The state of the stub.
+struct State {
+    /// The UART connected to GDB.
+    uart: Option<Uart>,
+    /// The breakpoints set by GDB.
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    /// The temporary breakpoint of a single step.
+    step: Option<Breakpoint>,
+}
+
+static mut STATE: State = State {
+    uart: None,
+    breakpoints: [None; MAX_BREAKPOINTS],
+    step: None,
+};
+
+/// The stack of the undefined instruction handler.
+static mut STACK: [u64; STACK_SIZE / 8] = [0; STACK_SIZE / 8];
+
+extern "C" {
+    fn gdbstub_undef_handler();
+
+    fn gdbstub_set_undef_stack(top: *mut u64);
+}
+
+impl Connection for Uart {
+    fn read_byte(&mut self) -> u8 {
+        Uart::read_byte(self)
+    }
+
+    fn read_byte_timeout(&mut self, timeout: u32) -> Result<u8> {
+        Uart::read_byte_timeout(self, timeout)
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        Uart::write_byte(self, byte)
+    }
+}
+
+/// Direct access to the memory of the program.
+struct Target;
+
+impl Memory for Target {
+    fn read_u32(&self, address: u32) -> u32 {
+        unsafe { read_volatile(address as *const u32) }
+    }
+
+    fn read_u16(&self, address: u32) -> u16 {
+        unsafe { read_volatile(address as *const u16) }
+    }
+}
+
+/// Installs the stub, which then communicates with GDB over `uart`.
+///
+/// The UART has to be initialized already.
+pub fn init(uart: Uart) {
+    unsafe {
+        STATE.uart = Some(uart);
+        install_handler();
+    }
+}
+
+/// Sets up the undefined instruction handler and its stack.
+unsafe fn install_handler() {
+    gdbstub_set_undef_stack(STACK.as_mut_ptr().add(STACK.len()));
+
+    let vector = &*(EVP_COP_UNDEF_VECTOR as *const Mmio<u32>);
+    vector.write(gdbstub_undef_handler as usize as u32);
+}
+
+/// Stops the program and hands control to GDB.
+///
+/// Does nothing unless the stub was installed through [`init`].
+///
+/// [`init`]: fn.init.html
+#[inline(always)]
+pub fn breakpoint() {
+    unsafe {
+        if STATE.uart.is_some() {
+            trap();
+        }
+    }
+}
+
+/// Executes a breakpoint instruction.
+#[inline(always)]
+unsafe fn trap() {
+    asm!(".inst 0xE7FFDEFE" ::: "memory" : "volatile");
+}
+
+/// Checks whether the program is stopped at a breakpoint instruction.
+fn is_breakpoint(frame: &Frame) -> bool {
+    let pc = frame.r[15];
+
+    if frame.cpsr & CPSR_THUMB != 0 {
+        Target.read_u16(pc) == THUMB_BREAKPOINT
+    } else {
+        Target.read_u32(pc) == ARM_BREAKPOINT
+    }
+}
+
+/// Splits `data` at the first occurrence of `separator`.
+fn split(data: &[u8], separator: u8) -> (&[u8], &[u8]) {
+    match data.iter().position(|&byte| byte == separator) {
+        Some(position) => (&data[..position], &data[position + 1..]),
+        None => (data, &[]),
+    }
+}
+
+/// Parses an `address,length` pair.
+fn parse_range(data: &[u8]) -> Option<(u32, u32)> {
+    let (address, length) = split(data, b',');
+    Some((packet::parse_hex(address)?, packet::parse_hex(length)?))
+}
+
+/// Handles the arguments of a `Z0`/`z0` packet, which inserts or removes a
+/// software breakpoint.
+fn set_breakpoint(state: &mut State, insert: bool, data: &[u8]) -> Option<()> {
+    let (address, size) = parse_range(data)?;
+
+    // Only ARM and 16-bit Thumb breakpoints exist on the ARM7TDMI.
+    let thumb = match size {
+        4 => false,
+        2 => true,
+        _ => return None,
+    };
+
+    let existing = state
+        .breakpoints
+        .iter_mut()
+        .find(|slot| slot.map_or(false, |breakpoint| breakpoint.address == address));
+
+    match (insert, existing) {
+        (true, Some(_)) => Some(()),
+        (true, None) => {
+            let slot = state.breakpoints.iter_mut().find(|slot| slot.is_none())?;
+            *slot = Some(unsafe { Breakpoint::insert(address, thumb) });
+            Some(())
+        }
+        (false, Some(slot)) => {
+            unsafe { slot.take()?.remove() };
+            Some(())
+        }
+        (false, None) => Some(()),
+    }
+}
+
+/// Handles a `qXfer:features:read` packet with the given annex.
+fn read_features(response: &mut Response, data: &[u8]) -> Option<()> {
+    let (annex, range) = split(data, b':');
+    if annex != b"target.xml" {
+        return None;
+    }
+
+    let (offset, length) = parse_range(range)?;
+    let xml = TARGET_XML.as_bytes();
+    let start = (offset as usize).min(xml.len());
+    let end = start
+        + (length as usize)
+            .min(xml.len() - start)
+            .min(PACKET_SIZE - 1);
+
+    response
+        .push(if end == xml.len() { b"l" } else { b"m" })
+        .push(&xml[start..end]);
+    Some(())
+}
+
+/// Reads `length` bytes of memory into the response.
+fn read_memory(response: &mut Response, address: u32, length: u32) {
+    let length = length.min(PACKET_SIZE as u32 / 2);
+
+    for offset in 0..length {
+        let byte = unsafe { read_volatile(address.wrapping_add(offset) as *const u8) };
+        response.push_hex(&[byte]);
+    }
+}
+
+/// Writes hex encoded bytes to memory.
+fn write_memory(address: u32, length: u32, data: &[u8]) -> Option<()> {
+    let mut buffer = [0; PACKET_SIZE / 2];
+    if packet::decode_hex(data, &mut buffer)? != length as usize {
+        return None;
+    }
+
+    for (offset, &byte) in buffer[..length as usize].iter().enumerate() {
+        unsafe { write_volatile(address.wrapping_add(offset as u32) as *mut u8, byte) };
+    }
+    arch::clean_invalidate_range(address as usize, length as usize);
+
+    Some(())
+}
+
+/// Writes `OK` or an error to the response.
+fn reply_ok(response: &mut Response, result: Option<()>) {
+    response.push(if result.is_some() { b"OK" } else { b"E01" });
+}
+
+/// Handles an exception in the debugged program.
+///
+/// Called by the undefined instruction handler in `entry.S`. Talks to GDB
+/// until it resumes the program, with the frame as modified by GDB.
+#[no_mangle]
+unsafe extern "C" fn gdbstub_handle_exception(frame: &mut Frame) {
+    let state = &mut STATE;
+
+    // Hold the UART for the session, which also keeps breakpoints in the
+    // stub itself from recursing.
+    if let Some(mut uart) = state.uart.take() {
+        serve(state, frame, &mut uart);
+        state.uart = Some(uart);
+    }
+}
+
+/// Reports the exception in `frame` to GDB and handles its requests until
+/// it resumes the program.
+unsafe fn serve(state: &mut State, frame: &mut Frame, uart: &mut Uart) {
+    let stepped = match state.step.take() {
+        Some(step) => {
+            step.remove();
+            step.address == frame.r[15]
+        }
+        None => false,
+    };
+    let signal = if stepped || is_breakpoint(frame) {
+        SIGTRAP
+    } else {
+        SIGILL
+    };
+
+    let mut buffer = [0; PACKET_SIZE];
+    let mut response = Response::new();
+    write!(response, "S{:02x}", signal).ok();
+    response.send(uart);
+
+    loop {
+        let length = packet::receive(uart, &mut buffer);
+        let (&command, data) = match buffer[..length].split_first() {
+            Some(packet) => packet,
+            None => continue,
+        };
+
+        let mut response = Response::new();
+        match command {
+            b'?' => {
+                write!(response, "S{:02x}", signal).ok();
+            }
+            b'g' => {
+                for index in 0..Frame::REGISTER_COUNT {
+                    let value = *frame.register(index).unwrap();
+                    response.push_hex(&value.to_le_bytes());
+                }
+            }
+            b'G' => {
+                let mut registers = [0; Frame::REGISTER_COUNT * 4];
+                let result = packet::decode_hex(data, &mut registers)
+                    .filter(|&count| count == registers.len())
+                    .map(|_| {
+                        for (index, bytes) in registers.chunks(4).enumerate() {
+                            let value =
+                                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                            *frame.register(index).unwrap() = value;
+                        }
+                    });
+                reply_ok(&mut response, result);
+            }
+            b'p' => {
+                match packet::parse_hex(data).and_then(|index| frame.register(index as usize)) {
+                    Some(value) => response.push_hex(&value.to_le_bytes()),
+                    None => response.push(b"E01"),
+                };
+            }
+            b'P' => {
+                let (index, value) = split(data, b'=');
+                let mut bytes = [0; 4];
+                let result = packet::parse_hex(index)
+                    .and_then(|index| frame.register(index as usize))
+                    .and_then(|register| {
+                        if packet::decode_hex(value, &mut bytes)? != 4 {
+                            return None;
+                        }
+                        *register = u32::from_le_bytes(bytes);
+                        Some(())
+                    });
+                reply_ok(&mut response, result);
+            }
+            b'm' => match parse_range(data) {
+                Some((address, length)) => read_memory(&mut response, address, length),
+                None => {
+                    response.push(b"E01");
+                }
+            },
+            b'M' => {
+                let (range, bytes) = split(data, b':');
+                let result = parse_range(range)
+                    .and_then(|(address, length)| write_memory(address, length, bytes));
+                reply_ok(&mut response, result);
+            }
+            b'c' | b's' => {
+                if let Some(address) = packet::parse_hex(data) {
+                    frame.r[15] = address;
+                }
+
+                if command == b's' {
+                    let next = step::next_pc(frame, &Target);
+                    state.step = Some(Breakpoint::insert(next & !1, next & 1 != 0));
+                }
+                return;
+            }
+            b'Z' | b'z' if data.starts_with(b"0,") => {
+                let result = set_breakpoint(state, command == b'Z', &data[2..]);
+                reply_ok(&mut response, result);
+            }
+            b'q' => {
+                if data.starts_with(b"Supported") {
+                    write!(
+                        response,
+                        "PacketSize={:x};qXfer:features:read+",
+                        PACKET_SIZE
+                    )
+                    .ok();
+                } else if data.starts_with(b"Xfer:features:read:") {
+                    if read_features(&mut response, &data[19..]).is_none() {
+                        response.push(b"E00");
+                    }
+                } else if data == b"Attached" {
+                    response.push(b"1");
+                }
+            }
+            b'D' => {
+                response.push(b"OK").send(uart);
+                return;
+            }
+            b'k' => return,
+            _ => {}
+        }
+
+        response.send(uart);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_arguments() {
+        assert_eq!(parse_range(b"40010000,10"), Some((0x4001_0000, 0x10)));
+        assert_eq!(parse_range(b"40010000"), None);
+
+        let (annex, range) = split(b"target.xml:0,ffb", b':');
+        assert_eq!(annex, b"target.xml");
+        assert_eq!(parse_range(range), Some((0, 0xFFB)));
+
+        let mut response = Response::new();
+        read_features(&mut response, b"target.xml:0,8").unwrap();
+        assert_eq!(response.data(), b"m<?xml ve");
+
+        let mut response = Response::new();
+        read_features(&mut response, b"target.xml:2a8,ffb").unwrap();
+        assert_eq!(response.data()[0], b'l');
+        assert!(read_features(&mut Response::new(), b"other.xml:0,8").is_none());
+    }
+}
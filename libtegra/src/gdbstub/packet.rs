@@ -0,0 +1,303 @@
+//! Framing of GDB Remote Serial Protocol packets.
+//!
+//! Packets are sent as `$<data>#<checksum>`, where the checksum is the sum
+//! of all data bytes modulo 256 in two hex digits. The receiver replies
+//! with `+` to acknowledge a packet or with `-` to request a retransmit.
+//!
+//! Waiting for the next packet is an intentionally blocking read, as GDB
+//! may stay idle for arbitrarily long. Once a packet has started, every
+//! byte and acknowledgement has to arrive within [`BYTE_TIMEOUT`].
+//!
+//! [`BYTE_TIMEOUT`]: constant.BYTE_TIMEOUT.html
+
+use core::fmt;
+
+use crate::Result;
+
+/// The maximum size of packet data, as announced to GDB.
+pub const PACKET_SIZE: usize = 0x200;
+
+/// The time in microseconds GDB may take to send the next byte of a packet
+/// or an acknowledgement.
+pub const BYTE_TIMEOUT: u32 = 1_000_000;
+
+/// A byte-oriented connection to GDB.
+pub trait Connection {
+    /// Reads a byte, blocking until one is available.
+    fn read_byte(&mut self) -> u8;
+
+    /// Reads a byte, waiting at most `timeout` microseconds for it.
+    fn read_byte_timeout(&mut self, timeout: u32) -> Result<u8>;
+
+    /// Writes a byte.
+    fn write_byte(&mut self, byte: u8);
+}
+
+/// Gets the value of a hex digit.
+pub fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Gets the lowercase hex digit for the low nibble of `value`.
+pub fn hex_digit(value: u8) -> u8 {
+    b"0123456789abcdef"[(value & 0xF) as usize]
+}
+
+/// Parses a hex number, as used for addresses and lengths.
+pub fn parse_hex(data: &[u8]) -> Option<u32> {
+    if data.is_empty() || data.len() > 8 {
+        return None;
+    }
+
+    data.iter().try_fold(0, |value, &digit| {
+        Some(value << 4 | u32::from(hex_value(digit)?))
+    })
+}
+
+/// Decodes pairs of hex digits into `buffer`.
+///
+/// Returns the amount of bytes decoded.
+pub fn decode_hex(data: &[u8], buffer: &mut [u8]) -> Option<usize> {
+    if data.len() % 2 != 0 || data.len() / 2 > buffer.len() {
+        return None;
+    }
+
+    for (byte, pair) in buffer.iter_mut().zip(data.chunks(2)) {
+        *byte = hex_value(pair[0])? << 4 | hex_value(pair[1])?;
+    }
+
+    Some(data.len() / 2)
+}
+
+/// Receives a packet into `buffer`, acknowledging it.
+///
+/// Packets with a bad checksum are rejected and received again, packets
+/// which GDB stops sending midway are dropped. Returns the length of the
+/// packet data.
+pub fn receive<C: Connection>(connection: &mut C, buffer: &mut [u8]) -> usize {
+    loop {
+        // Skip everything up to the start of a packet, like stray acks. This
+        // blocks until GDB sends the next command, however long it takes.
+        while connection.read_byte() != b'$' {}
+
+        match receive_body(connection, buffer) {
+            Ok(Some(length)) => {
+                connection.write_byte(b'+');
+                return length;
+            }
+            Ok(None) => connection.write_byte(b'-'),
+            Err(_) => (),
+        }
+    }
+}
+
+/// Receives the data and checksum of a packet, after its `$`.
+///
+/// Returns the length of the packet data, or `None` if the checksum doesn't
+/// match. Fails with [`Error::Timeout`] if GDB stops sending.
+///
+/// [`Error::Timeout`]: ../../enum.Error.html#variant.Timeout
+fn receive_body<C: Connection>(connection: &mut C, buffer: &mut [u8]) -> Result<Option<usize>> {
+    let mut length = 0;
+    let mut checksum = 0u8;
+    let mut overflow = false;
+
+    loop {
+        let byte = connection.read_byte_timeout(BYTE_TIMEOUT)?;
+        if byte == b'#' {
+            break;
+        }
+
+        if length < buffer.len() {
+            buffer[length] = byte;
+            length += 1;
+        } else {
+            overflow = true;
+        }
+        checksum = checksum.wrapping_add(byte);
+    }
+
+    let high = hex_value(connection.read_byte_timeout(BYTE_TIMEOUT)?);
+    let low = hex_value(connection.read_byte_timeout(BYTE_TIMEOUT)?);
+
+    match (high, low) {
+        (Some(high), Some(low)) if !overflow && high << 4 | low == checksum => Ok(Some(length)),
+        _ => Ok(None),
+    }
+}
+
+/// Builds a packet in a buffer and sends it.
+pub struct Response {
+    /// The packet data.
+    data: [u8; PACKET_SIZE],
+    /// The amount of bytes in `data`.
+    length: usize,
+}
+
+impl Response {
+    /// Creates an empty response, which tells GDB that a command is
+    /// unsupported.
+    pub fn new() -> Self {
+        Response {
+            data: [0; PACKET_SIZE],
+            length: 0,
+        }
+    }
+
+    /// Gets the data of the response.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+
+    /// Appends raw bytes, dropping what doesn't fit.
+    pub fn push(&mut self, bytes: &[u8]) -> &mut Self {
+        let count = bytes.len().min(PACKET_SIZE - self.length);
+        self.data[self.length..self.length + count].copy_from_slice(&bytes[..count]);
+        self.length += count;
+        self
+    }
+
+    /// Appends bytes as pairs of hex digits.
+    pub fn push_hex(&mut self, bytes: &[u8]) -> &mut Self {
+        for &byte in bytes {
+            self.push(&[hex_digit(byte >> 4), hex_digit(byte)]);
+        }
+        self
+    }
+
+    /// Sends the response as a packet, until GDB acknowledges it.
+    ///
+    /// The packet is sent again if GDB rejects it or doesn't acknowledge it
+    /// within [`BYTE_TIMEOUT`].
+    ///
+    /// [`BYTE_TIMEOUT`]: constant.BYTE_TIMEOUT.html
+    pub fn send<C: Connection>(&self, connection: &mut C) {
+        let checksum = self
+            .data()
+            .iter()
+            .fold(0u8, |checksum, &byte| checksum.wrapping_add(byte));
+
+        loop {
+            connection.write_byte(b'$');
+            for &byte in self.data() {
+                connection.write_byte(byte);
+            }
+            connection.write_byte(b'#');
+            connection.write_byte(hex_digit(checksum >> 4));
+            connection.write_byte(hex_digit(checksum));
+
+            if connection.read_byte_timeout(BYTE_TIMEOUT) == Ok(b'+') {
+                break;
+            }
+        }
+    }
+}
+
+impl fmt::Write for Response {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    /// A connection replaying input and recording output.
+    ///
+    /// A `~` in the input stands for a byte that didn't arrive in time.
+    struct Loopback<'a> {
+        input: &'a [u8],
+        output: [u8; 64],
+        written: usize,
+    }
+
+    impl Connection for Loopback<'_> {
+        fn read_byte(&mut self) -> u8 {
+            let (&byte, rest) = self.input.split_first().unwrap();
+            self.input = rest;
+            byte
+        }
+
+        fn read_byte_timeout(&mut self, _: u32) -> Result<u8> {
+            match self.read_byte() {
+                b'~' => Err(Error::Timeout),
+                byte => Ok(byte),
+            }
+        }
+
+        fn write_byte(&mut self, byte: u8) {
+            self.output[self.written] = byte;
+            self.written += 1;
+        }
+    }
+
+    #[test]
+    fn frame_packets() {
+        let mut connection = Loopback {
+            input: b"+$m0,4#00$m0,4#fd",
+            output: [0; 64],
+            written: 0,
+        };
+
+        let mut buffer = [0; 16];
+        let length = receive(&mut connection, &mut buffer);
+        assert_eq!(&buffer[..length], b"m0,4");
+        assert_eq!(&connection.output[..connection.written], b"-+");
+
+        let mut connection = Loopback {
+            input: b"+",
+            output: [0; 64],
+            written: 0,
+        };
+        Response::new().push(b"OK").send(&mut connection);
+        assert_eq!(&connection.output[..connection.written], b"$OK#9a");
+    }
+
+    /// Tests that packets and acknowledgements which don't arrive in time
+    /// are dropped and sent again respectively.
+    #[test]
+    fn time_out_packets() {
+        let mut connection = Loopback {
+            input: b"$m0~$m0,4#fd",
+            output: [0; 64],
+            written: 0,
+        };
+
+        let mut buffer = [0; 16];
+        let length = receive(&mut connection, &mut buffer);
+        assert_eq!(&buffer[..length], b"m0,4");
+        assert_eq!(&connection.output[..connection.written], b"+");
+
+        let mut connection = Loopback {
+            input: b"~+",
+            output: [0; 64],
+            written: 0,
+        };
+        Response::new().push(b"OK").send(&mut connection);
+        assert_eq!(&connection.output[..connection.written], b"$OK#9a$OK#9a");
+    }
+
+    #[test]
+    fn convert_hex() {
+        assert_eq!(parse_hex(b"4001fF00"), Some(0x4001_FF00));
+        assert_eq!(parse_hex(b""), None);
+        assert_eq!(parse_hex(b"12g"), None);
+
+        let mut buffer = [0; 4];
+        assert_eq!(decode_hex(b"deadBEEF", &mut buffer), Some(4));
+        assert_eq!(buffer, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(decode_hex(b"abc", &mut buffer), None);
+
+        let mut response = Response::new();
+        response.push_hex(&[0x01, 0xAB]);
+        assert_eq!(response.data(), b"01ab");
+    }
+}
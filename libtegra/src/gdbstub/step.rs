@@ -0,0 +1,322 @@
+//! Single-step emulation for the ARM7TDMI.
+//!
+//! The BPMP has no hardware single-stepping, so stepping is done by placing
+//! a temporary breakpoint on the instruction that executes next. This
+//! decodes the current instruction to find it.
+//!
+//! All instructions that can change the flow of execution in common code
+//! are handled: branches, `BX`, loads of `pc` and `MOV`/`ADD`-like data
+//! processing into `pc`. Writes to `pc` through more exotic forms, like
+//! shifted register operands, step to the next sequential instruction.
+
+use super::Frame;
+
+/// The Thumb state bit in the CPSR.
+pub const CPSR_THUMB: u32 = 1 << 5;
+
+/// Read access to the memory of the debugged program.
+pub trait Memory {
+    /// Reads a word from an aligned address.
+    fn read_u32(&self, address: u32) -> u32;
+
+    /// Reads a halfword from an aligned address.
+    fn read_u16(&self, address: u32) -> u16;
+}
+
+/// Checks whether an instruction with the given condition executes.
+fn condition_passed(condition: u32, cpsr: u32) -> bool {
+    let n = cpsr & (1 << 31) != 0;
+    let z = cpsr & (1 << 30) != 0;
+    let c = cpsr & (1 << 29) != 0;
+    let v = cpsr & (1 << 28) != 0;
+
+    match condition {
+        0x0 => z,
+        0x1 => !z,
+        0x2 => c,
+        0x3 => !c,
+        0x4 => n,
+        0x5 => !n,
+        0x6 => v,
+        0x7 => !v,
+        0x8 => c && !z,
+        0x9 => !c || z,
+        0xA => n == v,
+        0xB => n != v,
+        0xC => !z && n == v,
+        0xD => z || n != v,
+        _ => true,
+    }
+}
+
+/// Sign-extends the low `bits` bits of `value`.
+fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32 >> shift) as u32
+}
+
+/// Reads a register as seen by the current instruction.
+fn register(frame: &Frame, index: u32, pc_offset: u32) -> u32 {
+    if index == 15 {
+        frame.r[15].wrapping_add(pc_offset)
+    } else {
+        frame.r[index as usize]
+    }
+}
+
+/// Gets the address of the next ARM instruction.
+fn next_arm<M: Memory + ?Sized>(frame: &Frame, memory: &M) -> u32 {
+    let pc = frame.r[15];
+    let insn = memory.read_u32(pc);
+    let sequential = pc.wrapping_add(4);
+    let reg = |index: u32| register(frame, index & 0xF, 8);
+
+    if !condition_passed(insn >> 28, frame.cpsr) {
+        return sequential;
+    }
+
+    // BX Rm, which may switch to Thumb state.
+    if insn & 0x0FFF_FFF0 == 0x012F_FF10 {
+        return reg(insn);
+    }
+
+    // B and BL.
+    if insn & 0x0E00_0000 == 0x0A00_0000 {
+        let offset = sign_extend(insn & 0x00FF_FFFF, 24) << 2;
+        return pc.wrapping_add(8).wrapping_add(offset);
+    }
+
+    let rd = (insn >> 12) & 0xF;
+    let rn = insn >> 16;
+    let up = insn & (1 << 23) != 0;
+    let pre = insn & (1 << 24) != 0;
+
+    // LDR pc, [Rn, offset].
+    if insn & 0x0C50_0000 == 0x0410_0000 && rd == 15 {
+        let offset = if insn & (1 << 25) == 0 {
+            insn & 0xFFF
+        } else if insn & 0x70 == 0 {
+            // Register offset, shifted left by an immediate.
+            reg(insn) << ((insn >> 7) & 0x1F)
+        } else {
+            return sequential;
+        };
+
+        let base = reg(rn);
+        let offset_base = if up {
+            base.wrapping_add(offset)
+        } else {
+            base.wrapping_sub(offset)
+        };
+
+        let address = if pre { offset_base } else { base };
+        return memory.read_u32(address) & !3;
+    }
+
+    // LDM Rn, {..., pc}.
+    if insn & 0x0E10_8000 == 0x0810_8000 {
+        let count = (insn & 0xFFFF).count_ones();
+        let base = reg(rn);
+
+        // pc is the highest register and thus loaded from the highest address.
+        let address = match (up, pre) {
+            (true, false) => base.wrapping_add(4 * (count - 1)),
+            (true, true) => base.wrapping_add(4 * count),
+            (false, false) => base,
+            (false, true) => base.wrapping_sub(4),
+        };
+        return memory.read_u32(address) & !3;
+    }
+
+    // Data processing into pc.
+    if insn & 0x0C00_0000 == 0 && rd == 15 {
+        let operand = if insn & (1 << 25) != 0 {
+            (insn & 0xFF).rotate_right(((insn >> 8) & 0xF) * 2)
+        } else if insn & 0xFF0 == 0 {
+            reg(insn)
+        } else {
+            return sequential;
+        };
+        let first = reg(rn);
+
+        let result = match (insn >> 21) & 0xF {
+            0x0 => first & operand,
+            0x1 => first ^ operand,
+            0x2 => first.wrapping_sub(operand),
+            0x3 => operand.wrapping_sub(first),
+            0x4 => first.wrapping_add(operand),
+            0xC => first | operand,
+            0xD => operand,
+            0xE => first & !operand,
+            0xF => !operand,
+            _ => return sequential,
+        };
+        return result & !3;
+    }
+
+    sequential
+}
+
+/// Gets the address of the next Thumb instruction, with bit 0 set for
+/// Thumb state.
+fn next_thumb<M: Memory + ?Sized>(frame: &Frame, memory: &M) -> u32 {
+    let pc = frame.r[15];
+    let insn = u32::from(memory.read_u16(pc));
+    let sequential = pc.wrapping_add(2) | 1;
+    let reg = |index: u32| register(frame, index & 0xF, 4);
+
+    // Conditional branch.
+    if insn & 0xF000 == 0xD000 && (insn >> 8) & 0xF < 0xE {
+        if !condition_passed((insn >> 8) & 0xF, frame.cpsr) {
+            return sequential;
+        }
+
+        let offset = sign_extend(insn & 0xFF, 8) << 1;
+        return pc.wrapping_add(4).wrapping_add(offset) | 1;
+    }
+
+    // Unconditional branch.
+    if insn & 0xF800 == 0xE000 {
+        let offset = sign_extend(insn & 0x7FF, 11) << 1;
+        return pc.wrapping_add(4).wrapping_add(offset) | 1;
+    }
+
+    // BL, split into a prefix and a suffix.
+    if insn & 0xF800 == 0xF000 {
+        let suffix = u32::from(memory.read_u16(pc.wrapping_add(2)));
+        if suffix & 0xF800 != 0xF800 {
+            return sequential;
+        }
+
+        let offset = sign_extend(insn & 0x7FF, 11) << 12 | (suffix & 0x7FF) << 1;
+        return pc.wrapping_add(4).wrapping_add(offset) | 1;
+    }
+
+    // BX Rm, which may switch to ARM state.
+    if insn & 0xFF87 == 0x4700 {
+        let target = reg(insn >> 3);
+        return if target & 1 != 0 { target } else { target & !3 };
+    }
+
+    // POP {..., pc}.
+    if insn & 0xFF00 == 0xBD00 {
+        let count = (insn & 0xFF).count_ones();
+        return memory.read_u32(frame.r[13].wrapping_add(4 * count)) | 1;
+    }
+
+    // MOV pc, Rm.
+    if insn & 0xFF87 == 0x4687 {
+        return reg(insn >> 3) | 1;
+    }
+
+    // ADD pc, Rm.
+    if insn & 0xFF87 == 0x4487 {
+        return pc.wrapping_add(4).wrapping_add(reg(insn >> 3)) | 1;
+    }
+
+    sequential
+}
+
+/// Gets the address of the instruction that executes after the current
+/// one. Bit 0 is set if it executes in Thumb state.
+pub fn next_pc<M: Memory + ?Sized>(frame: &Frame, memory: &M) -> u32 {
+    if frame.cpsr & CPSR_THUMB != 0 {
+        next_thumb(frame, memory)
+    } else {
+        next_arm(frame, memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory holding a single instruction at 0x1000 and a stack at 0x2000.
+    struct Program {
+        code: [u8; 8],
+        stack: [u32; 4],
+    }
+
+    impl Memory for Program {
+        fn read_u32(&self, address: u32) -> u32 {
+            if address >= 0x2000 {
+                self.stack[(address as usize - 0x2000) / 4]
+            } else {
+                u32::from(self.read_u16(address)) | u32::from(self.read_u16(address + 2)) << 16
+            }
+        }
+
+        fn read_u16(&self, address: u32) -> u16 {
+            let offset = address as usize - 0x1000;
+            u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+        }
+    }
+
+    /// Runs the step emulation on one ARM or Thumb instruction.
+    fn step(code: u32, thumb: bool, cpsr: u32) -> u32 {
+        let program = Program {
+            code: [
+                code as u8,
+                (code >> 8) as u8,
+                (code >> 16) as u8,
+                (code >> 24) as u8,
+                0,
+                0,
+                0,
+                0,
+            ],
+            stack: [0x10, 0x20, 0x4000_0101, 0x30],
+        };
+
+        let mut frame = Frame {
+            r: [0; 16],
+            cpsr: cpsr | if thumb { CPSR_THUMB } else { 0 },
+        };
+        frame.r[1] = 0x4001_0000;
+        frame.r[2] = 0x4002_0001;
+        frame.r[13] = 0x2000;
+        frame.r[14] = 0x4003_0000;
+        frame.r[15] = 0x1000;
+
+        next_pc(&frame, &program)
+    }
+
+    #[test]
+    fn step_arm() {
+        // nop (mov r0, r0)
+        assert_eq!(step(0xE1A0_0000, false, 0), 0x1004);
+        // b .+0x10
+        assert_eq!(step(0xEA00_0002, false, 0), 0x1010);
+        // beq .-8, not taken and taken.
+        assert_eq!(step(0x0AFF_FFFC, false, 0), 0x1004);
+        assert_eq!(step(0x0AFF_FFFC, false, 1 << 30), 0x0FF8);
+        // bx r2
+        assert_eq!(step(0xE12F_FF12, false, 0), 0x4002_0001);
+        // mov pc, lr
+        assert_eq!(step(0xE1A0_F00E, false, 0), 0x4003_0000);
+        // ldmia sp!, {r0, r1, pc}
+        assert_eq!(step(0xE8BD_8003, false, 0), 0x4000_0100);
+        // ldr pc, [sp, #4]
+        assert_eq!(step(0xE59D_F004, false, 0), 0x20);
+        // add pc, pc, #8
+        assert_eq!(step(0xE28F_F008, false, 0), 0x1010);
+    }
+
+    #[test]
+    fn step_thumb() {
+        // movs r0, #0
+        assert_eq!(step(0x2000, true, 0), 0x1003);
+        // b .+4
+        assert_eq!(step(0xE000, true, 0), 0x1005);
+        // bne .-4, taken.
+        assert_eq!(step(0xD1FC, true, 0), 0x0FFD);
+        // bl .+0x1004
+        assert_eq!(step(0xF800_F001, true, 0), 0x2005);
+        // bx r1, switching to ARM.
+        assert_eq!(step(0x4708, true, 0), 0x4001_0000);
+        // pop {r0, r1, pc}
+        assert_eq!(step(0xBD03, true, 0), 0x4000_0101);
+        // mov pc, lr
+        assert_eq!(step(0x46F7, true, 0), 0x4003_0001);
+    }
+}
@@ -47,7 +47,13 @@
 //! - GPIOs are represented by the [`Gpio`] struct, which holds a [`GpioPort`]
 //! and a [`GpioPin`] to calculate the absolute in value, the bank the
 //! GPIO is located at and the mask that is used for reads writes to the
-//! registers.
+//! registers. Every pin of every port is reachable, either by name through
+//! [`Gpio::new`] or by its absolute number through [`Gpio::from_number`].
+//!
+//! - [`Gpio::set_mode`] selects whether a pin is driven by the GPIO
+//! controller or by its special function (SFIO). [`Gpio::get_output`]
+//! reads back the level a pin is driving, as opposed to [`Gpio::read`],
+//! which samples the level on the pad.
 //!
 //! - [`Gpio`] holds pre-defined constants which represent known GPIOs.
 //! These can be used for convenience.
@@ -59,6 +65,11 @@
 //! - The [`gpio!`] macro is a convenience method for creating [`Gpio`] objects
 //! which reduces boilerplate to a minimum.
 //!
+//! - [`GpioController::snapshot`] captures the configuration, direction, output
+//! and interrupt state of all ports into a [`GpioSnapshot`], which can be written
+//! back with [`GpioController::restore`], e.g. to hand over the original state to
+//! a chainloaded payload.
+//!
 //! # Example
 //!
 //! ```
@@ -89,11 +100,19 @@
 //! [`GpioLevel`]: enum.GpioLevel.html
 //! [`GpioConfig`]: enum.GpioConfig.html
 //! [`gpio!`]: macro.gpio.html
+//! [`Gpio::new`]: struct.Gpio.html#method.new
+//! [`Gpio::from_number`]: struct.Gpio.html#method.from_number
+//! [`Gpio::set_mode`]: struct.Gpio.html#method.set_mode
+//! [`Gpio::get_output`]: struct.Gpio.html#method.get_output
+//! [`Gpio::read`]: struct.Gpio.html#method.read
+//! [`GpioController::snapshot`]: struct.GpioController.html#method.snapshot
+//! [`GpioController::restore`]: struct.GpioController.html#method.restore
+//! [`GpioSnapshot`]: struct.GpioSnapshot.html
 
 pub use paste::expr;
 
 use enum_primitive::FromPrimitive;
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{Mmio, ReadOnly, VolatileStorage, WriteOnly};
 
 /// Base address for the GPIO registers.
 pub(crate) const GPIO_BASE: u32 = 0x6000_D000;
@@ -102,6 +121,8 @@ pub(crate) const GPIO_BASE: u32 = 0x6000_D000;
 const GPIO_PORTS_COUNT: usize = 4;
 /// The total amount of GPIO banks.
 const GPIO_BANKS_COUNT: usize = 8;
+/// The total amount of pins per GPIO port.
+const GPIO_PINS_COUNT: usize = 8;
 
 /// The GPIO ports.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -140,6 +161,56 @@ pub enum GpioPort {
     FF,
 }
 
+impl GpioPort {
+    /// All GPIO ports, in order.
+    pub const ALL: [GpioPort; GPIO_BANKS_COUNT * GPIO_PORTS_COUNT] = [
+        GpioPort::A,
+        GpioPort::B,
+        GpioPort::C,
+        GpioPort::D,
+        GpioPort::E,
+        GpioPort::F,
+        GpioPort::G,
+        GpioPort::H,
+        GpioPort::I,
+        GpioPort::J,
+        GpioPort::K,
+        GpioPort::L,
+        GpioPort::M,
+        GpioPort::N,
+        GpioPort::O,
+        GpioPort::P,
+        GpioPort::Q,
+        GpioPort::R,
+        GpioPort::S,
+        GpioPort::T,
+        GpioPort::U,
+        GpioPort::V,
+        GpioPort::W,
+        GpioPort::X,
+        GpioPort::Y,
+        GpioPort::Z,
+        GpioPort::AA,
+        GpioPort::BB,
+        GpioPort::CC,
+        GpioPort::DD,
+        GpioPort::EE,
+        GpioPort::FF,
+    ];
+
+    /// Gets the bank the port is located in.
+    #[inline]
+    pub const fn bank(self) -> usize {
+        self as usize / GPIO_PORTS_COUNT
+    }
+
+    /// Gets the index of the port within its bank.
+    #[inline]
+    pub const fn index(self) -> usize {
+        self as usize % GPIO_PORTS_COUNT
+    }
+}
+
 /// Representation of the GPIO pins for each port.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GpioPin {
@@ -153,6 +224,20 @@ pub enum GpioPin {
     P7,
 }
 
+impl GpioPin {
+    /// All pins of a GPIO port, in order.
+    pub const ALL: [GpioPin; GPIO_PINS_COUNT] = [
+        GpioPin::P0,
+        GpioPin::P1,
+        GpioPin::P2,
+        GpioPin::P3,
+        GpioPin::P4,
+        GpioPin::P5,
+        GpioPin::P6,
+        GpioPin::P7,
+    ];
+}
+
 enum_from_primitive! {
     /// Possible GPIO modes.
     #[derive(Debug, PartialEq, Eq)]
@@ -187,7 +272,7 @@ enum_from_primitive! {
 }
 
 /// Supported GPIO configurations.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GpioConfig {
     Input,
     OutputLow,
@@ -195,24 +280,42 @@ pub enum GpioConfig {
 }
 
 /// Representation of a GPIO bank.
+///
+/// The masked registers take a mask of the bits to update in
+/// bits 15:8 and their new values in bits 7:0.
+#[allow(dead_code)]
 #[repr(C)]
 struct GpioBank {
+    /// The `GPIO_CNF` registers, selecting GPIO over SFIO per pin.
     gpio_config: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_OE` registers, enabling the output drivers.
     gpio_direction_out: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_OUT` registers, holding the driven levels.
     gpio_out: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_in: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_IN` registers, sampling the levels on the pads.
+    gpio_in: [ReadOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_INT_STA` registers.
     gpio_int_status: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_INT_ENB` registers.
     gpio_int_enable: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_INT_LVL` registers.
     gpio_int_level: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_int_clear: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_config: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_dir_out: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_out: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_in: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_int_status: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_int_enable: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_int_level: [Mmio<u32>; GPIO_PORTS_COUNT],
-    gpio_masked_int_clear: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_INT_CLR` registers.
+    gpio_int_clear: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_CNF` registers.
+    gpio_masked_config: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_OE` registers.
+    gpio_masked_dir_out: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_OUT` registers.
+    gpio_masked_out: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    _reserved: [Mmio<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_INT_STA` registers.
+    gpio_masked_int_status: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_INT_ENB` registers.
+    gpio_masked_int_enable: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    /// The `GPIO_MSK_INT_LVL` registers.
+    gpio_masked_int_level: [WriteOnly<u32>; GPIO_PORTS_COUNT],
+    _reserved1: [Mmio<u32>; GPIO_PORTS_COUNT],
 }
 
 /// Representation of the GPIO controller.
@@ -228,6 +331,76 @@ impl VolatileStorage for GpioController {
     }
 }
 
+/// The total amount of GPIO ports.
+const GPIO_TOTAL_PORTS: usize = GPIO_BANKS_COUNT * GPIO_PORTS_COUNT;
+
+/// A register-level snapshot of the GPIO controller state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GpioSnapshot {
+    /// The `GPIO_CNF` values of each port.
+    pub config: [u32; GPIO_TOTAL_PORTS],
+    /// The `GPIO_OE` values of each port.
+    pub direction_out: [u32; GPIO_TOTAL_PORTS],
+    /// The `GPIO_OUT` values of each port.
+    pub out: [u32; GPIO_TOTAL_PORTS],
+    /// The `GPIO_INT_ENB` values of each port.
+    pub int_enable: [u32; GPIO_TOTAL_PORTS],
+    /// The `GPIO_INT_LVL` values of each port.
+    pub int_level: [u32; GPIO_TOTAL_PORTS],
+}
+
+impl GpioController {
+    /// Captures the state of all GPIO ports.
+    pub fn snapshot(&self) -> GpioSnapshot {
+        let mut snapshot = GpioSnapshot {
+            config: [0; GPIO_TOTAL_PORTS],
+            direction_out: [0; GPIO_TOTAL_PORTS],
+            out: [0; GPIO_TOTAL_PORTS],
+            int_enable: [0; GPIO_TOTAL_PORTS],
+            int_level: [0; GPIO_TOTAL_PORTS],
+        };
+
+        for (i, bank) in self.banks.iter().enumerate() {
+            for port in 0..GPIO_PORTS_COUNT {
+                let index = i * GPIO_PORTS_COUNT + port;
+
+                snapshot.config[index] = bank.gpio_config[port].read();
+                snapshot.direction_out[index] = bank.gpio_direction_out[port].read();
+                snapshot.out[index] = bank.gpio_out[port].read();
+                snapshot.int_enable[index] = bank.gpio_int_enable[port].read();
+                snapshot.int_level[index] = bank.gpio_int_level[port].read();
+            }
+        }
+
+        snapshot
+    }
+
+    /// Restores the state of all GPIO ports from a snapshot.
+    pub fn restore(&self, snapshot: &GpioSnapshot) {
+        for (i, bank) in self.banks.iter().enumerate() {
+            for port in 0..GPIO_PORTS_COUNT {
+                let index = i * GPIO_PORTS_COUNT + port;
+
+                // Mask interrupts while reconfiguring the port.
+                bank.gpio_int_enable[port].write(0);
+
+                // Latch output levels before enabling the drivers to avoid glitches.
+                bank.gpio_out[port].write(snapshot.out[index]);
+                bank.gpio_direction_out[port].write(snapshot.direction_out[index]);
+                bank.gpio_config[port].write(snapshot.config[index]);
+
+                // Restore interrupts, discarding everything that was raised in between.
+                bank.gpio_int_level[port].write(snapshot.int_level[index]);
+                bank.gpio_int_clear[port].write(0xFF);
+                bank.gpio_int_enable[port].write(snapshot.int_enable[index]);
+
+                // Dummy read.
+                bank.gpio_int_enable[port].read();
+            }
+        }
+    }
+}
+
 /// Representation of a GPIO
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Gpio {
@@ -261,6 +434,41 @@ macro_rules! gpio {
     }
 }
 
+impl Gpio {
+    /// The total amount of GPIOs, including ones that aren't bonded out.
+    pub const COUNT: usize = GPIO_BANKS_COUNT * GPIO_PORTS_COUNT * GPIO_PINS_COUNT;
+
+    /// Creates a GPIO given its port and pin.
+    pub const fn new(port: GpioPort, pin: GpioPin) -> Self {
+        Gpio { port, pin }
+    }
+
+    /// Creates a GPIO given its absolute number, as used in the TRM
+    /// and by Linux, i.e. `port * 8 + pin`.
+    pub fn from_number(number: usize) -> Option<Self> {
+        if number >= Self::COUNT {
+            return None;
+        }
+
+        Some(Gpio {
+            port: GpioPort::ALL[number / GPIO_PINS_COUNT],
+            pin: GpioPin::ALL[number % GPIO_PINS_COUNT],
+        })
+    }
+
+    /// Gets the absolute number of the GPIO.
+    #[inline]
+    pub const fn number(&self) -> usize {
+        self.port as usize * GPIO_PINS_COUNT + self.pin as usize
+    }
+
+    /// Gets the bank the GPIO is located in.
+    #[inline]
+    pub const fn bank(&self) -> usize {
+        self.port.bank()
+    }
+}
+
 impl Gpio {
     pub const BUTTON_VOL_DOWN: Self = Gpio {
         port: GpioPort::X,
@@ -314,22 +522,16 @@ impl Gpio {
 }
 
 impl Gpio {
-    /// Calculates the value of the wrapped GPIO.
-    #[inline]
-    fn get_gpio_value(&self) -> usize {
-        self.port as usize * 8 + self.pin as usize
-    }
-
     /// Calculates the value of the wrapped GPIO port.
     #[inline]
     fn get_port_value(&self) -> usize {
-        self.port as usize & 3
+        self.port.index()
     }
 
     /// Calculates the bank where the GPIO is located.
     #[inline]
     fn get_bank(&self) -> usize {
-        self.get_gpio_value() >> 5
+        self.bank()
     }
 
     /// Calculates the GPIO mask.
@@ -340,8 +542,8 @@ impl Gpio {
 
     /// Reads the flag of a GPIO register.
     #[inline]
-    fn read_flag(&self, reg: &Mmio<u32>) -> u32 {
-        (reg.read() >> self.pin as u32) & 1
+    fn read_flag(&self, value: u32) -> u32 {
+        (value >> self.pin as u32) & 1
     }
 
     /// Gets the GPIO mode the pin is currently set to.
@@ -352,7 +554,7 @@ impl Gpio {
         let config_reg = &controller.banks[self.get_bank()].gpio_config[self.get_port_value()];
 
         // Read the flag and wrap it into the corresponding enum.
-        GpioMode::from_u32(self.read_flag(config_reg)).unwrap()
+        GpioMode::from_u32(self.read_flag(config_reg.read())).unwrap()
     }
 
     /// Sets the GPIO mode for the pin.
@@ -392,7 +594,7 @@ impl Gpio {
             &controller.banks[self.get_bank()].gpio_direction_out[self.get_port_value()];
 
         // Read the flag and wrap it into the corresponding enum.
-        GpioDirection::from_u32(self.read_flag(direction_reg)).unwrap()
+        GpioDirection::from_u32(self.read_flag(direction_reg.read())).unwrap()
     }
 
     /// Sets the direction of the pin.
@@ -471,6 +673,28 @@ impl Gpio {
         out_reg.read();
     }
 
+    /// Whether the pin is controlled by the GPIO controller
+    /// rather than its special function.
+    pub fn is_gpio(&self) -> bool {
+        self.get_mode() == GpioMode::GPIO
+    }
+
+    /// Gets the level the pin is driving when configured as output.
+    pub fn get_output(&self) -> GpioLevel {
+        let controller = unsafe { GpioController::get() };
+
+        // Figure out the register to read from.
+        let out_reg = &controller.banks[self.get_bank()].gpio_out[self.get_port_value()];
+
+        // Read the flag and wrap it into the corresponding enum.
+        GpioLevel::from_u32(self.read_flag(out_reg.read())).unwrap()
+    }
+
+    /// Whether the pin is currently driven, i.e. configured as output.
+    pub fn is_driven(&self) -> bool {
+        self.get_direction() == GpioDirection::Output
+    }
+
     /// Reads the GPIO level of the pin.
     pub fn read(&self) -> GpioLevel {
         let controller = unsafe { GpioController::get() };
@@ -479,6 +703,52 @@ impl Gpio {
         let in_reg = &controller.banks[self.get_bank()].gpio_in[self.get_port_value()];
 
         // Read the flag and wrap it into the corresponding enum.
-        GpioLevel::from_u32(self.read_flag(in_reg)).unwrap()
+        GpioLevel::from_u32(self.read_flag(in_reg.read())).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the mapping between GPIOs and their absolute numbers.
+    #[test]
+    fn gpio_numbers() {
+        assert_eq!(Gpio::BUTTON_VOL_DOWN.number(), 191);
+        assert_eq!(Gpio::from_number(191), Some(Gpio::BUTTON_VOL_DOWN));
+        assert_eq!(Gpio::from_number(Gpio::COUNT), None);
+
+        let last = Gpio::new(GpioPort::FF, GpioPin::P7);
+        assert_eq!(last.number(), Gpio::COUNT - 1);
+        assert_eq!(last.bank(), 7);
+        assert_eq!(GpioPort::FF.index(), 3);
+
+        for number in 0..Gpio::COUNT {
+            assert_eq!(Gpio::from_number(number).unwrap().number(), number);
+        }
+    }
+
+    /// Tests pin configuration against mocked registers.
+    #[cfg(feature = "mock")]
+    #[test]
+    fn configure_pin() {
+        use mirage_mmio::mock;
+
+        let _session = mock::start();
+        let controller = unsafe { GpioController::get() };
+        let bank = &controller.banks[Gpio::LCD_BL_PWM.bank()];
+        let address = |register: &Mmio<u32>| register as *const _ as usize;
+
+        Gpio::LCD_BL_PWM.config(GpioConfig::OutputHigh);
+
+        assert!(Gpio::LCD_BL_PWM.is_gpio());
+        assert!(Gpio::LCD_BL_PWM.is_driven());
+        assert_eq!(Gpio::LCD_BL_PWM.get_output(), GpioLevel::High);
+        assert_eq!(mock::get(address(&bank.gpio_out[1])), 1);
+
+        Gpio::LCD_BL_PWM.set_mode(GpioMode::SFIO);
+
+        assert!(!Gpio::LCD_BL_PWM.is_gpio());
+        assert_eq!(mock::get(address(&bank.gpio_config[1])), 0);
     }
 }
@@ -0,0 +1,463 @@
+//! GUID Partition Table parser.
+//!
+//! # Description
+//!
+//! Both the eMMC user area and most SD cards are partitioned with a GUID
+//! Partition Table (GPT). On the eMMC, it holds the partitions of the
+//! Switch OS, like `PRODINFO`, `BCPKG2-1-Normal-Main`, `SAFE`, `SYSTEM`
+//! and `USER`, whose offsets differ between eMMC sizes and vendors.
+//!
+//! # Implementation
+//!
+//! - [`Gpt::read`] reads and validates the header at LBA 1 of a
+//! [`BlockDevice`], including the CRC32 of the header and the partition
+//! entry array. If the primary header is damaged, the backup header in the
+//! last block of the device is used instead.
+//!
+//! - [`Gpt::partitions`] iterates over the used entries of the partition
+//! array, reading it block by block. [`Gpt::find`] looks up a partition by
+//! its name.
+//!
+//! - [`Partition`] holds the type and unique [`Guid`], the LBA range and
+//! the attributes of a partition. The UTF-16 name is decoded on demand.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{block::BlockDevice, gpt::Gpt, Result};
+//!
+//! fn find_user<D: BlockDevice>(emmc: &mut D) -> Result<Option<u64>> {
+//!     let gpt = Gpt::read(emmc)?;
+//!
+//!     Ok(gpt.find(emmc, "USER")?.map(|user| user.first_lba))
+//! }
+//! ```
+//!
+//! [`Gpt::read`]: struct.Gpt.html#method.read
+//! [`BlockDevice`]: ../block/trait.BlockDevice.html
+//! [`Gpt::partitions`]: struct.Gpt.html#method.partitions
+//! [`Gpt::find`]: struct.Gpt.html#method.find
+//! [`Partition`]: struct.Partition.html
+//! [`Guid`]: struct.Guid.html
+
+use core::{char, convert::TryInto, fmt};
+
+use crate::{
+    block::{BlockDevice, BLOCK_SIZE},
+    checksum::{crc32, Crc32},
+    Error, Result,
+};
+
+/// The signature at the start of a GPT header.
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// The minimal size of a GPT header in bytes.
+const HEADER_SIZE: usize = 92;
+
+/// The minimal size of a partition entry in bytes.
+const ENTRY_SIZE: usize = 128;
+
+/// The amount of UTF-16 code units in a partition name.
+const NAME_LENGTH: usize = 36;
+
+/// Reads a little endian halfword at the given offset.
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+/// Reads a little endian word at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a little endian doubleword at the given offset.
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// A globally unique identifier, in its on-disk byte order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// The type of unused partition entries.
+    pub const UNUSED: Self = Guid([0; 16]);
+
+    /// The Microsoft basic data partition type, used by the Switch OS.
+    pub const BASIC_DATA: Self = Guid::from_fields(
+        0xEBD0_A0A2,
+        0xB9E5,
+        0x4433,
+        [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7],
+    );
+
+    /// Creates a GUID from the fields of its textual representation.
+    pub const fn from_fields(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Guid([
+            data1 as u8,
+            (data1 >> 8) as u8,
+            (data1 >> 16) as u8,
+            (data1 >> 24) as u8,
+            data2 as u8,
+            (data2 >> 8) as u8,
+            data3 as u8,
+            (data3 >> 8) as u8,
+            data4[0],
+            data4[1],
+            data4[2],
+            data4[3],
+            data4[4],
+            data4[5],
+            data4[6],
+            data4[7],
+        ])
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            read_u32(b, 0),
+            read_u16(b, 4),
+            read_u16(b, 6),
+            b[8],
+            b[9]
+        )?;
+        for byte in &b[10..] {
+            write!(f, "{:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An entry of the partition table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Partition {
+    /// The index of the entry in the partition array.
+    pub index: u32,
+    /// The type of the partition.
+    pub type_guid: Guid,
+    /// The unique identifier of the partition.
+    pub unique_guid: Guid,
+    /// The first block of the partition.
+    pub first_lba: u64,
+    /// The last block of the partition, inclusive.
+    pub last_lba: u64,
+    /// The attribute flags of the partition.
+    pub attributes: u64,
+    /// The UTF-16 name of the partition, padded with zeroes.
+    name: [u16; NAME_LENGTH],
+}
+
+impl Partition {
+    /// Parses a partition entry.
+    fn parse(index: u32, entry: &[u8]) -> Self {
+        let mut name = [0; NAME_LENGTH];
+        for (i, unit) in name.iter_mut().enumerate() {
+            *unit = read_u16(entry, 56 + i * 2);
+        }
+
+        Partition {
+            index,
+            type_guid: Guid(entry[0..16].try_into().unwrap()),
+            unique_guid: Guid(entry[16..32].try_into().unwrap()),
+            first_lba: read_u64(entry, 32),
+            last_lba: read_u64(entry, 40),
+            attributes: read_u64(entry, 48),
+            name,
+        }
+    }
+
+    /// Gets the amount of blocks in the partition.
+    pub fn block_count(&self) -> u64 {
+        self.last_lba.saturating_sub(self.first_lba) + 1
+    }
+
+    /// Decodes the name of the partition.
+    ///
+    /// Invalid UTF-16 is replaced by `U+FFFD`.
+    pub fn name(&self) -> impl Iterator<Item = char> + '_ {
+        let len = self
+            .name
+            .iter()
+            .position(|&unit| unit == 0)
+            .unwrap_or(NAME_LENGTH);
+
+        char::decode_utf16(self.name[..len].iter().cloned())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Checks whether the partition has the given name.
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name().eq(name.chars())
+    }
+}
+
+/// A validated GPT header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gpt {
+    /// The identifier of the disk.
+    pub disk_guid: Guid,
+    /// The first block usable by partitions.
+    pub first_usable_lba: u64,
+    /// The last block usable by partitions, inclusive.
+    pub last_usable_lba: u64,
+    /// The first block of the partition entry array.
+    entries_lba: u64,
+    /// The amount of entries in the array.
+    entry_count: u32,
+    /// The size of an entry in bytes.
+    entry_size: usize,
+    /// The CRC32 of the entry array.
+    entries_crc: u32,
+}
+
+impl Gpt {
+    /// Reads and validates the partition table of a device.
+    ///
+    /// Fails with [`Error::InvalidData`] if neither the primary nor the
+    /// backup table is valid.
+    ///
+    /// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+    pub fn read<D: BlockDevice + ?Sized>(device: &mut D) -> Result<Self> {
+        match Gpt::read_at(device, 1) {
+            Err(Error::InvalidData) => {
+                let backup_lba = device.block_count().checked_sub(1);
+                Gpt::read_at(device, backup_lba.ok_or(Error::InvalidData)?)
+            }
+            result => result,
+        }
+    }
+
+    /// Reads and validates the header at the given LBA.
+    fn read_at<D: BlockDevice + ?Sized>(device: &mut D, lba: u64) -> Result<Self> {
+        let mut block = [0; BLOCK_SIZE];
+        device.read_blocks(lba, &mut block)?;
+
+        let gpt = Gpt::parse_header(&mut block, lba)?;
+        let mut crc = Crc32::new();
+        let mut remaining = gpt.entry_count as usize * gpt.entry_size;
+        let mut entries_lba = gpt.entries_lba;
+
+        while remaining > 0 {
+            device.read_blocks(entries_lba, &mut block)?;
+
+            let len = remaining.min(BLOCK_SIZE);
+            crc.update(&block[..len]);
+            remaining -= len;
+            entries_lba += 1;
+        }
+
+        if crc.finish() != gpt.entries_crc {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(gpt)
+    }
+
+    /// Parses and checks a header block.
+    fn parse_header(block: &mut [u8; BLOCK_SIZE], lba: u64) -> Result<Self> {
+        let header_size = read_u32(block, 12) as usize;
+
+        if &block[0..8] != SIGNATURE
+            || header_size < HEADER_SIZE
+            || header_size > BLOCK_SIZE
+            || read_u64(block, 24) != lba
+        {
+            return Err(Error::InvalidData);
+        }
+
+        // The header CRC is computed with its own field zeroed.
+        let header_crc = read_u32(block, 16);
+        block[16..20].copy_from_slice(&[0; 4]);
+        if crc32(&block[..header_size]) != header_crc {
+            return Err(Error::InvalidData);
+        }
+
+        let entry_size = read_u32(block, 84) as usize;
+        if entry_size < ENTRY_SIZE || BLOCK_SIZE % entry_size != 0 {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Gpt {
+            disk_guid: Guid(block[56..72].try_into().unwrap()),
+            first_usable_lba: read_u64(block, 40),
+            last_usable_lba: read_u64(block, 48),
+            entries_lba: read_u64(block, 72),
+            entry_count: read_u32(block, 80),
+            entry_size,
+            entries_crc: read_u32(block, 88),
+        })
+    }
+
+    /// Iterates over the used partition entries.
+    pub fn partitions<'d, D: BlockDevice + ?Sized>(&self, device: &'d mut D) -> Partitions<'d, D> {
+        Partitions {
+            device,
+            gpt: *self,
+            index: 0,
+            block: [0; BLOCK_SIZE],
+        }
+    }
+
+    /// Finds a partition by its name.
+    pub fn find<D: BlockDevice + ?Sized>(
+        &self,
+        device: &mut D,
+        name: &str,
+    ) -> Result<Option<Partition>> {
+        for partition in self.partitions(device) {
+            let partition = partition?;
+
+            if partition.has_name(name) {
+                return Ok(Some(partition));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Iterator over the used entries of a partition table.
+///
+/// Created by [`Gpt::partitions`].
+///
+/// [`Gpt::partitions`]: struct.Gpt.html#method.partitions
+pub struct Partitions<'d, D: ?Sized> {
+    /// The device holding the table.
+    device: &'d mut D,
+    /// The table header.
+    gpt: Gpt,
+    /// The index of the next entry.
+    index: u32,
+    /// The block of the entry array holding the next entry.
+    block: [u8; BLOCK_SIZE],
+}
+
+impl<'d, D: BlockDevice + ?Sized> Iterator for Partitions<'d, D> {
+    type Item = Result<Partition>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let per_block = (BLOCK_SIZE / self.gpt.entry_size) as u32;
+
+        while self.index < self.gpt.entry_count {
+            let index = self.index;
+            self.index += 1;
+
+            if index % per_block == 0 {
+                let lba = self.gpt.entries_lba + u64::from(index / per_block);
+                if let Err(error) = self.device.read_blocks(lba, &mut self.block) {
+                    self.index = self.gpt.entry_count;
+                    return Some(Err(error));
+                }
+            }
+
+            let offset = (index % per_block) as usize * self.gpt.entry_size;
+            let partition = Partition::parse(index, &self.block[offset..offset + ENTRY_SIZE]);
+
+            if partition.type_guid != Guid::UNUSED {
+                return Some(Ok(partition));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The amount of blocks in the test image.
+    const BLOCKS: usize = 16;
+
+    /// Writes an entry into the partition array of an image.
+    fn write_entry(image: &mut [u8], index: usize, first_lba: u64, last_lba: u64, name: &str) {
+        let entry = &mut image[2 * BLOCK_SIZE + index * ENTRY_SIZE..][..ENTRY_SIZE];
+
+        entry[0..16].copy_from_slice(&Guid::BASIC_DATA.0);
+        entry[16] = index as u8 + 1;
+        entry[32..40].copy_from_slice(&first_lba.to_le_bytes());
+        entry[40..48].copy_from_slice(&last_lba.to_le_bytes());
+        for (i, unit) in name.encode_utf16().enumerate() {
+            entry[56 + i * 2..58 + i * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    /// Builds an image with a primary GPT of four entries in one block.
+    fn build_image(image: &mut [u8]) {
+        write_entry(image, 0, 4, 7, "SAFE");
+        write_entry(image, 2, 8, 14, "USER");
+
+        let entries_crc = crc32(&image[2 * BLOCK_SIZE..2 * BLOCK_SIZE + 4 * ENTRY_SIZE]);
+        let header = &mut image[BLOCK_SIZE..2 * BLOCK_SIZE];
+        header[0..8].copy_from_slice(SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&1u64.to_le_bytes());
+        header[32..40].copy_from_slice(&(BLOCKS as u64 - 1).to_le_bytes());
+        header[40..48].copy_from_slice(&4u64.to_le_bytes());
+        header[48..56].copy_from_slice(&(BLOCKS as u64 - 2).to_le_bytes());
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&4u32.to_le_bytes());
+        header[84..88].copy_from_slice(&(ENTRY_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+
+        let header_crc = crc32(&header[..HEADER_SIZE]);
+        header[16..20].copy_from_slice(&header_crc.to_le_bytes());
+    }
+
+    /// Tests parsing a partition table from an image.
+    #[test]
+    fn partitions() {
+        let mut image = [0u8; BLOCKS * BLOCK_SIZE];
+        build_image(&mut image);
+
+        let device = &mut image[..];
+        let gpt = Gpt::read(device).unwrap();
+        assert_eq!(gpt.first_usable_lba, 4);
+        assert_eq!(gpt.last_usable_lba, BLOCKS as u64 - 2);
+
+        let mut partitions = gpt.partitions(device);
+        let safe = partitions.next().unwrap().unwrap();
+        assert!(safe.has_name("SAFE"));
+        assert_eq!(safe.index, 0);
+        assert_eq!(safe.block_count(), 4);
+        assert_eq!(safe.type_guid, Guid::BASIC_DATA);
+        assert_eq!(partitions.next().unwrap().unwrap().index, 2);
+        assert!(partitions.next().is_none());
+
+        let user = gpt.find(device, "USER").unwrap().unwrap();
+        assert_eq!((user.first_lba, user.last_lba), (8, 14));
+        assert_eq!(gpt.find(device, "SYSTEM"), Ok(None));
+
+        // Corrupting an entry invalidates the table.
+        image[2 * BLOCK_SIZE + 32] ^= 1;
+        assert_eq!(Gpt::read(&mut image[..]), Err(Error::InvalidData));
+    }
+
+    /// Tests the textual representation of GUIDs.
+    #[test]
+    fn guid() {
+        use core::fmt::Write;
+
+        struct Buffer([u8; 36], usize);
+
+        impl Write for Buffer {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0[self.1..self.1 + s.len()].copy_from_slice(s.as_bytes());
+                self.1 += s.len();
+                Ok(())
+            }
+        }
+
+        let mut buffer = Buffer([0; 36], 0);
+        write!(buffer, "{}", Guid::BASIC_DATA).unwrap();
+        assert_eq!(&buffer.0[..], &b"EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"[..]);
+    }
+}
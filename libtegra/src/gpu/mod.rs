@@ -0,0 +1,300 @@
+//! Minimal GM20B GPU bring-up.
+//!
+//! # Description
+//!
+//! The GPU of the Tegra X1 is a Maxwell GM20B, which sits on its own power
+//! rail supplied by a MAX77621 regulator. It is isolated from the rest of the
+//! SoC by clamps in the PMC until it has been powered up.
+//!
+//! Original firmware expects the GPU to have fetched the Video Protection
+//! Region (VPR) configuration from the Memory Controller when it takes over.
+//! The GPU's Write Protected Regions (WPR) for its secure falcons are backed
+//! by the GPU security carveouts of the MC, which [`mc::config_carveout`]
+//! sets up.
+//!
+//! # Implementation
+//!
+//! - [`power_on`] powers the rail, ungates the clocks, removes the clamps
+//! and takes the GPU out of reset. It then enables the graphics and FIFO
+//! units, loads the non-context register state of a [`Netlist`] and
+//! performs the VPR fetch handshake.
+//!
+//! - [`power_off`] reverses this, leaving the GPU rail-gated.
+//!
+//! - [`Netlist`] parses the netlist firmware images that come with the GPU
+//! driver. They are chip-specific and aren't shipped with Mirage.
+//!
+//! - Booting the context-switch falcons (FECS/GPCCS) from the netlist and
+//! using the GPU engines, e.g. for clearing memory, is out of scope. This
+//! is left to the GPU driver of the next stage.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{gpu, peripherals::Peripherals};
+//!
+//! static NETLIST: &[u8] = include_bytes!("gm20b_netlist.bin");
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
+//!     gpu::power_on(&peripherals.i2c_5, NETLIST).unwrap();
+//! }
+//! ```
+//!
+//! [`mc::config_carveout`]: ../mc/fn.config_carveout.html
+//! [`power_on`]: fn.power_on.html
+//! [`power_off`]: fn.power_off.html
+//! [`Netlist`]: struct.Netlist.html
+
+use core::convert::TryInto;
+
+use mirage_mmio::{BlockMmio, Mmio, VolatileStorage};
+
+use crate::{
+    clock::Clock,
+    i2c::{Device, I2c},
+    pmc::Pmc,
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// Base address for GPU registers.
+pub(crate) const GPU_BASE: u32 = 0x5700_0000;
+
+/// Offset of the `NV_PMC_BOOT_0` register.
+const NV_PMC_BOOT_0: u32 = 0x0;
+/// Offset of the `NV_PMC_ENABLE` register.
+const NV_PMC_ENABLE: u32 = 0x200;
+/// Offset of the `NV_PFB_PRI_MMU_VPR_INFO` register.
+const NV_PFB_PRI_MMU_VPR_INFO: u32 = 0x10_0CD0;
+
+/// The architecture and implementation of the GM20B in `NV_PMC_BOOT_0`.
+const GM20B_CHIP_ID: u32 = 0x12B;
+
+/// Enables the FIFO unit, in `NV_PMC_ENABLE`.
+const NV_PMC_ENABLE_PFIFO: u32 = 1 << 8;
+/// Enables the graphics unit, in `NV_PMC_ENABLE`.
+const NV_PMC_ENABLE_PGRAPH: u32 = 1 << 12;
+
+/// Requests a fetch of the VPR configuration from the MC.
+const NV_PFB_PRI_MMU_VPR_INFO_FETCH: u32 = 1 << 2;
+
+/// The `VOUT` register of the MAX77621.
+const MAX77621_REG_VOUT: u8 = 0x00;
+/// The `VOUT_DVC` register of the MAX77621.
+const MAX77621_REG_VOUT_DVC: u8 = 0x01;
+/// The `CONTROL1` register of the MAX77621.
+const MAX77621_REG_CONTROL1: u8 = 0x02;
+/// The `CONTROL2` register of the MAX77621.
+const MAX77621_REG_CONTROL2: u8 = 0x03;
+
+/// Enables the output of the MAX77621, in `VOUT`.
+const MAX77621_VOUT_ENABLE: u8 = 1 << 7;
+/// The boot voltage of the GPU rail, 950mV in 6.25mV steps above 606.25mV.
+const MAX77621_VOUT_BOOT: u8 = 0x37;
+
+/// The maximum time for the VPR fetch in microseconds.
+const VPR_FETCH_TIMEOUT: u32 = 1000;
+
+/// Regions of a GPU netlist image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// The data segment of the FECS ucode.
+    FecsData = 0,
+    /// The code segment of the FECS ucode.
+    FecsCode = 1,
+    /// The data segment of the GPCCS ucode.
+    GpccsData = 2,
+    /// The code segment of the GPCCS ucode.
+    GpccsCode = 3,
+    /// The initial bundle of the graphics engine.
+    BundleInit = 4,
+    /// Registers which are part of the graphics context.
+    ContextLoad = 5,
+    /// Registers which are not part of the graphics context.
+    NonContextLoad = 6,
+    /// The initial methods of the graphics engine.
+    MethodInit = 7,
+}
+
+/// A parsed GPU netlist image.
+///
+/// The image starts with the amount of regions, followed by a table with
+/// the ID, size and offset of each region. All fields are 32-bit little
+/// endian.
+#[derive(Clone, Copy, Debug)]
+pub struct Netlist<'a> {
+    /// The raw image.
+    data: &'a [u8],
+    /// The amount of regions in the image.
+    regions: usize,
+}
+
+/// Reads a little endian word at the given offset.
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+impl<'a> Netlist<'a> {
+    /// Parses a netlist image, validating its region table.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let regions = read_u32(data, 0).ok_or(Error::InvalidArgument)? as usize;
+        let netlist = Netlist { data, regions };
+
+        for index in 0..regions {
+            netlist.entry(index).ok_or(Error::InvalidArgument)?;
+        }
+
+        Ok(netlist)
+    }
+
+    /// Gets the ID and the data of a region table entry.
+    fn entry(&self, index: usize) -> Option<(u32, &'a [u8])> {
+        let offset = 4 + index.checked_mul(12)?;
+        let id = read_u32(self.data, offset)?;
+        let size = read_u32(self.data, offset + 4)? as usize;
+        let start = read_u32(self.data, offset + 8)? as usize;
+
+        Some((id, self.data.get(start..start.checked_add(size)?)?))
+    }
+
+    /// Gets the data of a region, if the image contains it.
+    pub fn region(&self, region: Region) -> Option<&'a [u8]> {
+        (0..self.regions)
+            .filter_map(|index| self.entry(index))
+            .find(|(id, _)| *id == region as u32)
+            .map(|(_, data)| data)
+    }
+
+    /// Gets the `(address, value)` pairs of a register list region.
+    pub fn registers(&self, region: Region) -> impl Iterator<Item = (u32, u32)> + 'a {
+        self.region(region)
+            .unwrap_or(&[])
+            .chunks_exact(8)
+            .map(|pair| (read_u32(pair, 0).unwrap(), read_u32(pair, 4).unwrap()))
+    }
+}
+
+/// Gets a GPU register.
+fn register(offset: u32) -> BlockMmio<Mmio<u32>> {
+    unsafe { BlockMmio::new((GPU_BASE + offset) as usize) }
+}
+
+/// Enables or disables the GPU rail.
+fn set_rail(i2c: &I2c, enabled: bool) -> Result<()> {
+    if enabled {
+        i2c.write_byte(Device::Max77621Gpu, MAX77621_REG_CONTROL1, 0x20)?;
+        i2c.write_byte(Device::Max77621Gpu, MAX77621_REG_CONTROL2, 0x8D)?;
+        i2c.write_byte(
+            Device::Max77621Gpu,
+            MAX77621_REG_VOUT_DVC,
+            MAX77621_VOUT_ENABLE | MAX77621_VOUT_BOOT,
+        )?;
+        i2c.write_byte(
+            Device::Max77621Gpu,
+            MAX77621_REG_VOUT,
+            MAX77621_VOUT_ENABLE | MAX77621_VOUT_BOOT,
+        )?;
+    } else {
+        i2c.write_byte(Device::Max77621Gpu, MAX77621_REG_VOUT, MAX77621_VOUT_BOOT)?;
+    }
+
+    Ok(())
+}
+
+/// Requests the GPU to fetch the VPR configuration from the MC and waits
+/// for it to complete.
+pub fn fetch_vpr_info() -> Result<()> {
+    let vpr_info = register(NV_PFB_PRI_MMU_VPR_INFO);
+
+    vpr_info.write(NV_PFB_PRI_MMU_VPR_INFO_FETCH);
+
+    wait_for(
+        || vpr_info.read() & NV_PFB_PRI_MMU_VPR_INFO_FETCH == 0,
+        VPR_FETCH_TIMEOUT,
+    )
+}
+
+/// Powers on the GPU and brings it into the state original firmware expects.
+///
+/// The register state is taken from the given netlist image. The GPU rail
+/// is controlled through `i2c`, which has to be the initialized I²C 5.
+pub fn power_on(i2c: &I2c, netlist: &[u8]) -> Result<()> {
+    let pmc = unsafe { Pmc::get() };
+    let netlist = Netlist::parse(netlist)?;
+
+    set_rail(i2c, true)?;
+    usleep(10);
+
+    // Ungate the clock while the GPU is held in reset.
+    let mut clock = Clock::GPU;
+    clock.set_reset(true);
+    clock.set_enable(true);
+    usleep(10);
+
+    // Remove the clamps and take the GPU out of reset.
+    pmc.gpu_rg_cntrl.write(0);
+    usleep(10);
+    clock.set_reset(false);
+
+    if register(NV_PMC_BOOT_0).read() >> 20 != GM20B_CHIP_ID {
+        power_off(i2c)?;
+        return Err(Error::UnsupportedHardware);
+    }
+
+    register(NV_PMC_ENABLE).write(NV_PMC_ENABLE_PFIFO | NV_PMC_ENABLE_PGRAPH);
+
+    for (address, value) in netlist.registers(Region::NonContextLoad) {
+        register(address).write(value);
+    }
+
+    fetch_vpr_info()
+}
+
+/// Puts the GPU into reset, clamps it and disables its rail through `i2c`.
+pub fn power_off(i2c: &I2c) -> Result<()> {
+    let pmc = unsafe { Pmc::get() };
+    let mut clock = Clock::GPU;
+
+    clock.set_reset(true);
+    pmc.gpu_rg_cntrl.write(1);
+    usleep(10);
+    clock.set_enable(false);
+
+    set_rail(i2c, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests parsing of the netlist region table.
+    #[test]
+    fn netlist() {
+        let mut image = [0u8; 0x30];
+        let words: [u32; 7] = [2, 6, 0x10, 0x1C, 4, 4, 0x2C];
+        for (i, word) in words.iter().enumerate() {
+            image[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        image[0x1C..0x20].copy_from_slice(&0x0040_9000u32.to_le_bytes());
+        image[0x20..0x24].copy_from_slice(&0x1234u32.to_le_bytes());
+
+        {
+            let netlist = Netlist::parse(&image).unwrap();
+            assert_eq!(netlist.region(Region::BundleInit).map(|r| r.len()), Some(4));
+            assert_eq!(netlist.region(Region::MethodInit), None);
+
+            let mut registers = netlist.registers(Region::NonContextLoad);
+            assert_eq!(registers.next(), Some((0x0040_9000, 0x1234)));
+            assert_eq!(registers.next(), Some((0, 0)));
+            assert_eq!(registers.next(), None);
+        }
+
+        // A region beyond the end of the image is rejected.
+        image[0x14..0x18].copy_from_slice(&0x10u32.to_le_bytes());
+        assert!(Netlist::parse(&image).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Implementations of the `embedded-hal` traits for the drivers.
+//!
+//! # Description
+//!
+//! Device drivers from the embedded Rust ecosystem, e.g. for sensors or
+//! displays, are written against the [`embedded-hal`] and [`embedded-io`]
+//! traits. With the `hal` feature enabled, the libtegra drivers implement
+//! them, so such drivers can be used on the Switch without glue code.
+//!
+//! This covers GPIO, I²C, UART and delays. SPI is left out, as libtegra
+//! has no driver for the SPI controllers yet.
+//!
+//! # Implementation
+//!
+//! - [`Gpio`] implements the `digital` traits for input and output pins.
+//! The pin has to be configured as GPIO with the right direction first,
+//! e.g. through [`Gpio::config`].
+//!
+//! - [`I2c`] implements the `i2c::I2c` trait with 7-bit addresses. The
+//! controller runs in normal mode, which transfers at most 4 bytes when
+//! writing and 8 bytes when reading. Longer operations are split into
+//! multiple transfers, each with its own START and STOP condition.
+//!
+//! - [`Uart`] implements the `embedded-io` `Read` and `Write` traits, as
+//! `embedded-hal` 1.0 leaves serial communication to them. Reading blocks
+//! until at least one byte has arrived.
+//!
+//! - [`Delay`] implements `DelayNs` on top of the microsecond timer, so
+//! delays are rounded up to whole microseconds.
+//!
+//! - The `spi` traits aren't implemented, see above.
+//!
+//! # Example
+//!
+//! ```
+//! use embedded_hal::{delay::DelayNs, digital::OutputPin};
+//! use mirage_libtegra::{gpio::{Gpio, GpioConfig}, hal::Delay};
+//!
+//! fn main() {
+//!     let mut led = Gpio::LCD_BL_EN;
+//!     led.config(GpioConfig::OutputLow);
+//!
+//!     led.set_high().unwrap();
+//!     Delay.delay_ms(500);
+//!     led.set_low().unwrap();
+//! }
+//! ```
+//!
+//! [`embedded-hal`]: https://docs.rs/embedded-hal/1.0.0/embedded_hal/
+//! [`embedded-io`]: https://docs.rs/embedded-io/0.6.1/embedded_io/
+//! [`Gpio`]: ../gpio/struct.Gpio.html
+//! [`Gpio::config`]: ../gpio/struct.Gpio.html#method.config
+//! [`I2c`]: ../i2c/struct.I2c.html
+//! [`Uart`]: ../uart/struct.Uart.html
+//! [`Delay`]: struct.Delay.html
+
+use core::convert::Infallible;
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital,
+    i2c::{self, ErrorKind, NoAcknowledgeSource, Operation},
+};
+use embedded_io as io;
+
+use crate::{
+    gpio::{Gpio, GpioLevel},
+    i2c::{I2c, I2cError},
+    timer::{msleep, usleep},
+    uart::{Uart, VendorStatus},
+    Error,
+};
+
+/// The maximum length of a write transfer in normal mode.
+const I2C_MAX_WRITE: usize = 4;
+/// The maximum length of a read transfer in normal mode.
+const I2C_MAX_READ: usize = 8;
+
+/// A delay provider based on the microsecond timer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Delay;
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        usleep(ns / 1000 + (ns % 1000 != 0) as u32);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        usleep(us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        msleep(ms);
+    }
+}
+
+impl digital::ErrorType for Gpio {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for Gpio {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.write(GpioLevel::Low);
+
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.write(GpioLevel::High);
+
+        Ok(())
+    }
+}
+
+impl digital::StatefulOutputPin for Gpio {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.get_output() == GpioLevel::High)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(self.get_output() == GpioLevel::Low)
+    }
+}
+
+impl digital::InputPin for Gpio {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.read() == GpioLevel::High)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(self.read() == GpioLevel::Low)
+    }
+}
+
+impl i2c::Error for I2cError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            I2cError::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            I2cError::ArbitrationLost => ErrorKind::ArbitrationLoss,
+            I2cError::FifoOverrun => ErrorKind::Overrun,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl i2c::ErrorType for I2c {
+    type Error = I2cError;
+}
+
+impl i2c::I2c for I2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2cError> {
+        let address = u32::from(address);
+
+        for operation in operations {
+            match operation {
+                Operation::Write(data) => {
+                    for chunk in data.chunks(I2C_MAX_WRITE) {
+                        self.write_packet(address, chunk)?;
+                    }
+                }
+                Operation::Read(buffer) => {
+                    for chunk in buffer.chunks_mut(I2C_MAX_READ) {
+                        self.read_packet(address, chunk)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl io::Error for Error {
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Timeout => io::ErrorKind::TimedOut,
+            Error::InvalidArgument => io::ErrorKind::InvalidInput,
+            Error::InvalidData => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl io::ErrorType for Uart {
+    type Error = Error;
+}
+
+impl io::Read for Uart {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let (first, rest) = match buffer.split_first_mut() {
+            Some(split) => split,
+            None => return Ok(0),
+        };
+
+        // Block for the first byte, then take what has arrived already.
+        *first = self.read_byte();
+
+        let mut count = 1;
+        for byte in rest {
+            match self.read_byte_timeout(0) {
+                Ok(value) => *byte = value,
+                Err(_) => break,
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+impl io::Write for Uart {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        for (count, &byte) in data.iter().enumerate() {
+            if let Err(error) = self.try_write_byte(byte) {
+                // Report the bytes that made it, the error shows up on retry.
+                return if count == 0 { Err(error) } else { Ok(count) };
+            }
+        }
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.wait_idle(VendorStatus::UART_TX_IDLE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal::i2c::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn i2c_error_kinds() {
+        assert_eq!(
+            I2cError::Nack.kind(),
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+        );
+        assert_eq!(I2cError::ArbitrationLost.kind(), ErrorKind::ArbitrationLoss);
+        assert_eq!(I2cError::Timeout.kind(), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,277 @@
+//! Heap allocator for the `alloc` crate.
+//!
+//! # Description
+//!
+//! Mirage itself doesn't allocate, but higher-level code like filesystem
+//! drivers or menus is much simpler to write with `Vec`, `String` and `Box`.
+//! With the `heap` feature, libtegra provides the global allocator for this,
+//! serving memory from a region the payload reserves for it, e.g. a part of
+//! DRAM.
+//!
+//! # Implementation
+//!
+//! - [`init`] hands the memory region to the global [`Heap`]. Allocations
+//! fail until it has been called.
+//!
+//! - [`Heap`] is a first-fit allocator over a list of free blocks, sorted by
+//! address. Freed blocks are merged with their neighbours, so the heap
+//! doesn't fragment over time when the same sizes are reused.
+//!
+//! - All blocks are multiples of two words, which is also the minimal
+//! alignment, so every free block can hold its own list node.
+//!
+//! - Like the rest of libtegra, the allocator isn't synchronized and must
+//! only be used from a single core.
+//!
+//! # Example
+//!
+//! ```
+//! extern crate alloc;
+//!
+//! use alloc::vec::Vec;
+//! use mirage_libtegra::heap;
+//!
+//! fn main() {
+//!     unsafe { heap::init(0xF000_0000, 0x1000_0000) };
+//!
+//!     let mut entries = Vec::new();
+//!     entries.push("Stock");
+//! }
+//! ```
+//!
+//! [`init`]: fn.init.html
+//! [`Heap`]: struct.Heap.html
+
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    mem::size_of,
+    ptr::null_mut,
+};
+
+/// The granularity and minimal alignment of blocks in bytes.
+const UNIT: usize = size_of::<Node>();
+
+/// The global heap, backing the `alloc` crate on the target.
+#[cfg_attr(not(any(test, feature = "std")), global_allocator)]
+static HEAP: Heap = Heap::empty();
+
+/// A free block of memory, stored at its start.
+struct Node {
+    /// The size of the block in bytes.
+    size: usize,
+    /// The next free block at a higher address.
+    next: *mut Node,
+}
+
+/// The list of free blocks.
+struct FreeList {
+    /// The free block at the lowest address.
+    head: *mut Node,
+}
+
+/// Rounds an address up to the given power of two.
+fn align_up(address: usize, align: usize) -> usize {
+    (address + align - 1) & !(align - 1)
+}
+
+/// Gets the size and alignment of the block for an allocation.
+fn block_layout(layout: Layout) -> (usize, usize) {
+    let align = layout.align().max(UNIT);
+    let size = align_up(layout.size().max(1), UNIT);
+
+    (size, align)
+}
+
+/// Writes a free block node and returns a pointer to it.
+unsafe fn write_node(address: usize, size: usize, next: *mut Node) -> *mut Node {
+    let node = address as *mut Node;
+    node.write(Node { size, next });
+
+    node
+}
+
+impl FreeList {
+    /// Takes a block for the given layout from the first free block that
+    /// fits it.
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = block_layout(layout);
+        let mut link: *mut *mut Node = &mut self.head;
+
+        while !(*link).is_null() {
+            let node = *link;
+            let start = node as usize;
+            let end = start + (*node).size;
+            let block = align_up(start, align);
+
+            match block.checked_add(size) {
+                Some(block_end) if block_end <= end => {
+                    // Keep the space after the block on the list.
+                    let mut rest = (*node).next;
+                    if block_end < end {
+                        rest = write_node(block_end, end - block_end, rest);
+                    }
+
+                    // Keep the space before the block, if alignment left any.
+                    if block > start {
+                        (*node).size = block - start;
+                        (*node).next = rest;
+                    } else {
+                        *link = rest;
+                    }
+
+                    return block as *mut u8;
+                }
+                _ => link = &mut (*node).next,
+            }
+        }
+
+        null_mut()
+    }
+
+    /// Puts a block back on the list, merging it with adjacent free blocks.
+    unsafe fn free(&mut self, address: usize, size: usize) {
+        let mut prev: *mut Node = null_mut();
+        let mut next = self.head;
+
+        while !next.is_null() && (next as usize) < address {
+            prev = next;
+            next = (*next).next;
+        }
+
+        let block = write_node(address, size, next);
+        if !next.is_null() && address + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        if prev.is_null() {
+            self.head = block;
+        } else if prev as usize + (*prev).size == address {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+}
+
+/// A first-fit heap allocator.
+pub struct Heap {
+    /// The free blocks of the heap.
+    free: UnsafeCell<FreeList>,
+}
+
+// The heap is only ever used from a single core.
+unsafe impl Sync for Heap {}
+
+impl Heap {
+    /// Creates a heap without any memory.
+    pub const fn empty() -> Self {
+        Heap {
+            free: UnsafeCell::new(FreeList { head: null_mut() }),
+        }
+    }
+
+    /// Adds a region of memory to the heap.
+    ///
+    /// The region is shrunk to whole blocks. It must be unused and must not
+    /// overlap memory that was added before.
+    pub unsafe fn add_region(&self, start: usize, size: usize) {
+        let aligned_start = align_up(start, UNIT);
+        let end = start.saturating_add(size) & !(UNIT - 1);
+
+        if end > aligned_start {
+            (*self.free.get()).free(aligned_start, end - aligned_start);
+        }
+    }
+
+    /// Gets the total amount of free memory in bytes.
+    ///
+    /// Due to fragmentation, the largest possible allocation may be smaller.
+    pub fn free_bytes(&self) -> usize {
+        let mut node = unsafe { (*self.free.get()).head };
+        let mut total = 0;
+
+        while !node.is_null() {
+            unsafe {
+                total += (*node).size;
+                node = (*node).next;
+            }
+        }
+
+        total
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        (*self.free.get()).allocate(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block_layout(layout);
+
+        (*self.free.get()).free(ptr as usize, size);
+    }
+}
+
+/// Initializes the global heap with the given memory region.
+///
+/// The region must be unused for the rest of the program's lifetime.
+pub unsafe fn init(start: usize, size: usize) {
+    HEAP.add_region(start, size);
+}
+
+/// Gets the total amount of free memory on the global heap in bytes.
+pub fn free_bytes() -> usize {
+    HEAP.free_bytes()
+}
+
+/// Handles failed allocations on the target by panicking.
+#[cfg(not(any(test, feature = "std")))]
+#[alloc_error_handler]
+fn out_of_memory(layout: Layout) -> ! {
+    panic!("Out of heap memory allocating {} bytes", layout.size());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests allocation, alignment and merging of freed blocks.
+    #[test]
+    fn allocate_and_free() {
+        #[repr(align(16))]
+        struct Memory([u8; 0x200]);
+
+        let mut memory = Memory([0; 0x200]);
+        let heap = Heap::empty();
+        let start = memory.0.as_mut_ptr() as usize;
+        unsafe { heap.add_region(start + 1, 0x200 - 1) };
+        assert_eq!(heap.free_bytes(), 0x200 - UNIT);
+
+        let small = Layout::from_size_align(3, 1).unwrap();
+        let aligned = Layout::from_size_align(0x40, 0x40).unwrap();
+        unsafe {
+            let a = heap.alloc(small);
+            let b = heap.alloc(aligned);
+            assert_eq!(a as usize, start + UNIT);
+            assert_eq!(b as usize % 0x40, 0);
+            assert_eq!(heap.free_bytes(), 0x200 - 2 * UNIT - 0x40);
+
+            // Too large for what is left.
+            assert!(heap
+                .alloc(Layout::from_size_align(0x200, 8).unwrap())
+                .is_null());
+
+            heap.dealloc(a, small);
+            heap.dealloc(b, aligned);
+            assert_eq!(heap.free_bytes(), 0x200 - UNIT);
+
+            // Everything got merged back into a single block.
+            let all = Layout::from_size_align(0x200 - UNIT, 8).unwrap();
+            assert_eq!(heap.alloc(all) as usize, start + UNIT);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Tegra210 HOST1X interface.
+//!
+//! # Description
+//!
+//! HOST1X is the DMA engine and command processor that feeds the graphics
+//! and multimedia engines, like the display controllers, VI or the TSEC.
+//! Clients synchronize with each other and with the CPU through syncpoints,
+//! 32-bit counters which are incremented when work has completed.
+//!
+//! # Implementation
+//!
+//! - The [`SyncRegisters`] struct can be used to access the syncpoint
+//! registers of the HOST1X sync block.
+//!
+//! - [`Syncpoint`] represents a single syncpoint. Its value can be read
+//! with [`Syncpoint::read`] and awaited with [`Syncpoint::wait`], which
+//! handles wrap-around of the counter.
+//!
+//! - [`Syncpoint::TSEC`] is the syncpoint the TSEC firmware uses for its
+//! handshake with the bootloader, see [`TSEC_SYNCPT_MAGIC`].
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::host1x::Syncpoint;
+//!
+//! fn main() {
+//!     let syncpt = Syncpoint::new(18).unwrap();
+//!     let target = syncpt.read().wrapping_add(1);
+//!
+//!     // Wait up to 100ms for an engine to signal completion.
+//!     syncpt.wait(target, 100_000).unwrap();
+//! }
+//! ```
+//!
+//! [`SyncRegisters`]: struct.SyncRegisters.html
+//! [`Syncpoint`]: struct.Syncpoint.html
+//! [`Syncpoint::read`]: struct.Syncpoint.html#method.read
+//! [`Syncpoint::wait`]: struct.Syncpoint.html#method.wait
+//! [`Syncpoint::TSEC`]: struct.Syncpoint.html#associatedconstant.TSEC
+//! [`TSEC_SYNCPT_MAGIC`]: constant.TSEC_SYNCPT_MAGIC.html
+
+use mirage_mmio::{Mmio, VolatileStorage};
+
+use crate::{timer::wait_for, Result};
+
+/// Base address for the HOST1X registers.
+pub(crate) const HOST1X_BASE: u32 = 0x5000_0000;
+
+/// Offset of the sync block within the HOST1X aperture.
+const HOST1X_SYNC_OFFSET: u32 = 0x2100;
+
+/// The number of syncpoints supported by the Tegra210 HOST1X.
+pub const SYNCPT_COUNT: usize = 192;
+
+/// The value written to [`Syncpoint::TSEC`] before booting the TSEC
+/// firmware. The syncpoint is cleared again once the firmware is done.
+///
+/// [`Syncpoint::TSEC`]: struct.Syncpoint.html#associatedconstant.TSEC
+pub const TSEC_SYNCPT_MAGIC: u32 = 0x34C2_E1DA;
+
+/// Representation of the HOST1X sync registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct SyncRegisters {
+    _reserved0: [Mmio<u32>; 0x3E0],
+    /// The `HOST1X_SYNC_SYNCPT_*` registers, holding the syncpoint values.
+    pub SYNCPT: [Mmio<u32>; SYNCPT_COUNT],
+}
+
+impl VolatileStorage for SyncRegisters {
+    unsafe fn make_ptr() -> *const Self {
+        (HOST1X_BASE + HOST1X_SYNC_OFFSET) as *const _
+    }
+}
+
+/// Representation of a HOST1X syncpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Syncpoint {
+    /// The index of the syncpoint.
+    id: usize,
+}
+
+impl Syncpoint {
+    /// The syncpoint the TSEC firmware uses for its handshake.
+    pub const TSEC: Self = Syncpoint { id: 160 };
+
+    /// Creates a syncpoint given its index.
+    ///
+    /// Returns `None` if the index exceeds [`SYNCPT_COUNT`].
+    ///
+    /// [`SYNCPT_COUNT`]: constant.SYNCPT_COUNT.html
+    pub fn new(id: usize) -> Option<Self> {
+        if id < SYNCPT_COUNT {
+            Some(Syncpoint { id })
+        } else {
+            None
+        }
+    }
+
+    /// Gets the index of the syncpoint.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    fn register(&self) -> &'static Mmio<u32> {
+        unsafe { &SyncRegisters::get().SYNCPT[self.id] }
+    }
+
+    /// Reads the current value of the syncpoint.
+    pub fn read(&self) -> u32 {
+        self.register().read()
+    }
+
+    /// Overwrites the value of the syncpoint.
+    ///
+    /// This bypasses the regular increment semantics and should only be
+    /// used for handshakes with firmware, like the one of the TSEC.
+    pub fn write(&self, value: u32) {
+        self.register().write(value);
+    }
+
+    /// Whether the syncpoint has reached the given threshold.
+    ///
+    /// The comparison accounts for the counter wrapping around.
+    pub fn has_reached(&self, threshold: u32) -> bool {
+        self.read().wrapping_sub(threshold) as i32 >= 0
+    }
+
+    /// Waits until the syncpoint reaches the given threshold and
+    /// returns its value.
+    ///
+    /// Fails with [`Error::Timeout`] if the threshold isn't reached
+    /// within the given amount of microseconds.
+    ///
+    /// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+    pub fn wait(&self, threshold: u32, timeout: u32) -> Result<u32> {
+        wait_for(|| self.has_reached(threshold), timeout)?;
+
+        Ok(self.read())
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// Tests syncpoint addressing and wrap-around of the counter.
+    #[test]
+    fn syncpoints() {
+        let _session = mock::start();
+
+        // The TSEC handshake register is located at HOST1X + 0x3300.
+        Syncpoint::TSEC.write(TSEC_SYNCPT_MAGIC);
+        assert_eq!(mock::get(0x5000_3300), u64::from(TSEC_SYNCPT_MAGIC));
+
+        let syncpt = Syncpoint::new(SYNCPT_COUNT - 1).unwrap();
+        assert!(Syncpoint::new(SYNCPT_COUNT).is_none());
+
+        syncpt.write(0xFFFF_FFFE);
+        assert!(!syncpt.has_reached(1));
+
+        syncpt.write(2);
+        assert!(syncpt.has_reached(1));
+        assert!(syncpt.has_reached(0xFFFF_FFFE));
+        assert_eq!(syncpt.wait(2, 0), Ok(2));
+    }
+}
@@ -0,0 +1,146 @@
+//! Runtime detection of the SoC revision and the Switch hardware type.
+//!
+//! # Description
+//!
+//! Switch units ship with two revisions of the Tegra X1: the original
+//! T210 (Erista) and the die-shrunk T210B01 (Mariko). Many low-level
+//! initialization sequences, like the memory built-in self test workaround
+//! or the SDRAM parameter tables, only apply to one of them and can leave
+//! the other one in a broken state when applied blindly.
+//!
+//! # Implementation
+//!
+//! - [`SocRevision`] is read from the major revision of the `HIDREV`
+//! register in the APB MISC block, see [`soc_revision`].
+//!
+//! - [`HardwareType`] identifies the product the SoC is built into. It is
+//! derived from the SoC revision and an ODM fuse, see [`hardware_type`].
+//!
+//! - [`require`] lets code paths that only support a single revision bail
+//! out with [`Error::UnsupportedHardware`] instead of writing registers.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{hw_rev::{self, SocRevision}, Result};
+//!
+//! fn erista_only() -> Result<()> {
+//!     hw_rev::require(SocRevision::Erista)?;
+//!
+//!     // ...
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`SocRevision`]: enum.SocRevision.html
+//! [`soc_revision`]: fn.soc_revision.html
+//! [`HardwareType`]: enum.HardwareType.html
+//! [`hardware_type`]: fn.hardware_type.html
+//! [`require`]: fn.require.html
+//! [`Error::UnsupportedHardware`]: ../enum.Error.html#variant.UnsupportedHardware
+
+use mirage_mmio::Mmio;
+
+use crate::{fuse, Error, Result};
+
+/// The `APB_MISC_GP_HIDREV` register which identifies the chip.
+const APB_MISC_GP_HIDREV: u32 = 0x7000_0804;
+
+/// The major revision of T210B01 chips in `HIDREV`.
+const HIDREV_MAJOR_T210B01: u32 = 2;
+
+/// Revisions of the Tegra X1 SoC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocRevision {
+    /// The original Tegra X1, T210.
+    Erista,
+    /// The revised Tegra X1, T210B01.
+    Mariko,
+}
+
+/// Switch hardware models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HardwareType {
+    /// The original Switch, the only model with an Erista SoC.
+    Icosa,
+    /// The revised Switch with a Mariko SoC.
+    Iowa,
+    /// The Switch Lite.
+    Hoag,
+    /// The Switch OLED model.
+    Aula,
+}
+
+impl SocRevision {
+    /// Decodes the revision from a `HIDREV` register value.
+    fn from_hidrev(hidrev: u32) -> Self {
+        if (hidrev >> 4) & 0xF >= HIDREV_MAJOR_T210B01 {
+            SocRevision::Mariko
+        } else {
+            SocRevision::Erista
+        }
+    }
+}
+
+impl HardwareType {
+    /// Decodes the hardware type of a Mariko unit from its ODM fuse 4.
+    ///
+    /// Unknown values are treated as [`HardwareType::Iowa`].
+    ///
+    /// [`HardwareType::Iowa`]: enum.HardwareType.html#variant.Iowa
+    fn from_odm4(odm4: u32) -> Self {
+        match (odm4 >> 16) & 0xF {
+            2 => HardwareType::Hoag,
+            4 => HardwareType::Aula,
+            _ => HardwareType::Iowa,
+        }
+    }
+}
+
+/// Reads the revision of the SoC.
+pub fn soc_revision() -> SocRevision {
+    let hidrev = unsafe { &*(APB_MISC_GP_HIDREV as *const Mmio<u32>) };
+
+    SocRevision::from_hidrev(hidrev.read())
+}
+
+/// Determines the hardware type of the unit.
+///
+/// Requires the fuse registers to be visible.
+pub fn hardware_type() -> HardwareType {
+    match soc_revision() {
+        SocRevision::Erista => HardwareType::Icosa,
+        SocRevision::Mariko => HardwareType::from_odm4(fuse::read_reserved_odm(4)),
+    }
+}
+
+/// Ensures that the code is running on the given SoC revision.
+///
+/// Fails with [`Error::UnsupportedHardware`] on any other revision.
+///
+/// [`Error::UnsupportedHardware`]: ../enum.Error.html#variant.UnsupportedHardware
+pub fn require(revision: SocRevision) -> Result<()> {
+    if soc_revision() == revision {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedHardware)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests decoding of the known revisions and hardware types.
+    #[test]
+    fn decode() {
+        assert_eq!(SocRevision::from_hidrev(0x0000_2102), SocRevision::Erista);
+        assert_eq!(SocRevision::from_hidrev(0x0000_2112), SocRevision::Erista);
+        assert_eq!(SocRevision::from_hidrev(0x0000_2122), SocRevision::Mariko);
+
+        assert_eq!(HardwareType::from_odm4(0x0001_0000), HardwareType::Iowa);
+        assert_eq!(HardwareType::from_odm4(0x0002_0000), HardwareType::Hoag);
+        assert_eq!(HardwareType::from_odm4(0x0004_0000), HardwareType::Aula);
+    }
+}
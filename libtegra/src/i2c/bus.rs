@@ -0,0 +1,149 @@
+//! Exclusive access to shared I²C buses.
+//!
+//! # Description
+//!
+//! Several devices share the same I²C controller, e.g. the PMIC, the RTC
+//! and the fuel gauge on I2C5. A single register access consists of
+//! multiple packets, so conversations from IRQ-driven code and the main
+//! loop may interleave and corrupt each other's transactions.
+//!
+//! # Implementation
+//!
+//! - [`I2c::lock`] returns a [`BusGuard`] which masks interrupts while it
+//! is alive, so all transactions issued through it are atomic. The guard
+//! dereferences to the [`I2c`] controller it was created for.
+//!
+//! - Acquiring a bus which is already held, e.g. from a scheduler callback
+//! that runs while a driver is using the bus, fails with [`I2cError::Busy`]
+//! instead of corrupting the transfer in progress.
+//!
+//! - Every controller keeps [`BusStats`] about how often it was acquired,
+//! how often acquisition failed and the longest time it was held, to help
+//! spotting badly-behaved callers.
+//!
+//! [`I2c::lock`]: struct.I2c.html#method.lock
+//! [`BusGuard`]: struct.BusGuard.html
+//! [`I2c`]: struct.I2c.html
+//! [`I2cError::Busy`]: enum.I2cError.html#variant.Busy
+//! [`BusStats`]: struct.BusStats.html
+
+use core::ops::Deref;
+
+use super::{I2c, I2cError};
+use crate::{
+    arch::{disable_interrupts, restore_interrupts},
+    timer::{get_microseconds, get_time_since},
+};
+
+/// The number of I²C controllers.
+const I2C_CONTROLLERS: usize = 6;
+
+/// Statistics about the usage of an I²C bus.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BusStats {
+    /// The number of times the bus was acquired.
+    pub acquisitions: u32,
+    /// The number of times acquiring the bus failed because it was held.
+    pub contentions: u32,
+    /// The longest time the bus was held in microseconds.
+    pub max_hold_time: u32,
+}
+
+impl BusStats {
+    const fn new() -> Self {
+        BusStats {
+            acquisitions: 0,
+            contentions: 0,
+            max_hold_time: 0,
+        }
+    }
+}
+
+static mut BUS_HELD: [bool; I2C_CONTROLLERS] = [false; I2C_CONTROLLERS];
+
+static mut BUS_STATS: [BusStats; I2C_CONTROLLERS] = [BusStats::new(); I2C_CONTROLLERS];
+
+/// Exclusive access to an I²C bus.
+///
+/// Interrupts stay masked until the guard is dropped, thus it should
+/// only be held for the duration of a transaction.
+pub struct BusGuard<'a> {
+    bus: &'a I2c,
+    index: usize,
+    interrupts: u32,
+    acquired: u32,
+}
+
+impl<'a> Deref for BusGuard<'a> {
+    type Target = I2c;
+
+    fn deref(&self) -> &I2c {
+        self.bus
+    }
+}
+
+impl<'a> Drop for BusGuard<'a> {
+    fn drop(&mut self) {
+        let hold_time = get_time_since(self.acquired);
+
+        unsafe {
+            let stats = &mut BUS_STATS[self.index];
+            stats.max_hold_time = stats.max_hold_time.max(hold_time);
+
+            BUS_HELD[self.index] = false;
+        }
+
+        restore_interrupts(self.interrupts);
+    }
+}
+
+impl I2c {
+    /// Gets the index of the controller, ranging from 0 to 5.
+    pub(crate) fn index(&self) -> usize {
+        [I2c::C1, I2c::C2, I2c::C3, I2c::C4, I2c::C5, I2c::C6]
+            .iter()
+            .position(|c| c.registers == self.registers)
+            .unwrap()
+    }
+
+    /// Acquires exclusive access to the bus.
+    ///
+    /// Fails with [`I2cError::Busy`] if the bus is already held.
+    ///
+    /// [`I2cError::Busy`]: enum.I2cError.html#variant.Busy
+    pub fn lock(&self) -> Result<BusGuard<'_>, I2cError> {
+        let index = self.index();
+        let interrupts = disable_interrupts();
+
+        unsafe {
+            if BUS_HELD[index] {
+                BUS_STATS[index].contentions += 1;
+                restore_interrupts(interrupts);
+
+                return Err(I2cError::Busy);
+            }
+
+            BUS_HELD[index] = true;
+            BUS_STATS[index].acquisitions += 1;
+        }
+
+        Ok(BusGuard {
+            bus: self,
+            index,
+            interrupts,
+            acquired: get_microseconds(),
+        })
+    }
+
+    /// Gets the usage statistics of the bus.
+    pub fn stats(&self) -> BusStats {
+        unsafe { BUS_STATS[self.index()] }
+    }
+
+    /// Resets the usage statistics of the bus.
+    pub fn reset_stats(&self) {
+        unsafe {
+            BUS_STATS[self.index()] = BusStats::new();
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! DMA-driven I²C transfers in packet mode.
+//!
+//! # Description
+//!
+//! In normal mode, the controller can only transfer up to 4 bytes per
+//! transaction and the CPU has to poll for every single one. For larger
+//! writes, e.g. when uploading configuration blocks to a device, packet
+//! mode lets the controller consume a packet from its TX FIFO, which can
+//! be fed by an APB DMA channel.
+//!
+//! # Implementation
+//!
+//! - [`I2c::write_dma`] assembles a packet consisting of the generic
+//! header, the I²C protocol header and the payload, hands it to the DMA
+//! and waits until the controller has sent it.
+//!
+//! - The payload is limited to [`I2C_DMA_MAX_PAYLOAD`] bytes, including
+//! the device register.
+//!
+//! [`I2c::write_dma`]: struct.I2c.html#method.write_dma
+//! [`I2C_DMA_MAX_PAYLOAD`]: constant.I2C_DMA_MAX_PAYLOAD.html
+
+use super::*;
+use crate::apbdma::{BusWidth, Channel, Request};
+
+/// The maximum payload of a DMA transfer in bytes.
+pub const I2C_DMA_MAX_PAYLOAD: usize = 256;
+
+/// The number of header words preceding the payload of a packet.
+const PACKET_HEADER_WORDS: usize = 3;
+
+/// Selects the I²C protocol in the generic packet header.
+const PACKET_HEADER0_PROTOCOL_I2C: u32 = 1 << 4;
+/// The shift of the controller ID in the generic packet header.
+const PACKET_HEADER0_CONT_ID_SHIFT: u32 = 12;
+/// The shift of the packet ID in the generic packet header.
+const PACKET_HEADER0_PACKET_ID_SHIFT: u32 = 16;
+
+/// Enables packet mode in `I2C_CNFG`.
+const I2C_CNFG_PACKET_MODE_EN: u32 = 1 << 10;
+
+/// Flushes the RX FIFO.
+const FIFO_CONTROL_RX_FLUSH: u32 = 1 << 0;
+/// Flushes the TX FIFO.
+const FIFO_CONTROL_TX_FLUSH: u32 = 1 << 1;
+
+/// The packet has been transferred.
+const I2C_INT_PACKET_XFER_COMPLETE: u32 = 1 << 7;
+
+impl I2c {
+    /// Gets the DMA request of the controller.
+    fn dma_request(&self) -> Request {
+        match self.index() {
+            0 => Request::I2c1,
+            1 => Request::I2c2,
+            2 => Request::I2c3,
+            3 => Request::I2c4,
+            4 => Request::I2c5,
+            _ => Request::I2c6,
+        }
+    }
+
+    /// Writes a buffer of data to a register from a device over I²C,
+    /// using the given APB DMA channel to feed the controller.
+    ///
+    /// This supports payloads of up to [`I2C_DMA_MAX_PAYLOAD`] bytes,
+    /// including the register.
+    ///
+    /// [`I2C_DMA_MAX_PAYLOAD`]: constant.I2C_DMA_MAX_PAYLOAD.html
+    pub fn write_dma(
+        &self,
+        channel: &mut Channel,
+        device: Device,
+        register: u8,
+        data: &[u8],
+    ) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
+        let payload_size = data.len() + 1;
+
+        if payload_size > I2C_DMA_MAX_PAYLOAD {
+            return Err(I2cError::MemoryError);
+        }
+
+        // Assemble the generic header, the I²C header and the payload.
+        let mut packet = [0u32; PACKET_HEADER_WORDS + I2C_DMA_MAX_PAYLOAD / 4];
+        packet[0] = PACKET_HEADER0_PROTOCOL_I2C
+            | ((self.index() as u32) << PACKET_HEADER0_CONT_ID_SHIFT)
+            | (1 << PACKET_HEADER0_PACKET_ID_SHIFT);
+        packet[1] = (payload_size - 1) as u32;
+        packet[2] = device.address() << 1;
+
+        for (i, byte) in core::iter::once(&register).chain(data).enumerate() {
+            packet[PACKET_HEADER_WORDS + i / 4] |= u32::from(*byte) << ((i % 4) * 8);
+        }
+
+        let words = PACKET_HEADER_WORDS + (payload_size + 3) / 4;
+
+        // Flush the FIFOs and request data as soon as a word is free.
+        register_base
+            .FIFO_CONTROL
+            .write(FIFO_CONTROL_TX_FLUSH | FIFO_CONTROL_RX_FLUSH);
+        wait_for(
+            || register_base.FIFO_CONTROL.read() & (FIFO_CONTROL_TX_FLUSH | FIFO_CONTROL_RX_FLUSH) == 0,
+            I2C_TRANSFER_TIMEOUT,
+        )
+        .map_err(|_| I2cError::Timeout)?;
+
+        // Clear stale interrupts so errors can be attributed to this transfer.
+        register_base.INTERRUPT_STATUS.write(register_base.INTERRUPT_STATUS.read());
+
+        // Set config with PACKET_MODE_EN, NEW_MASTER_FSM, DEBOUNCE_CNT = 4T.
+        register_base.I2C_CNFG.write(I2C_CNFG_PACKET_MODE_EN | 0x2800);
+        self.load_config();
+
+        // Feed the packet to the controller, which starts sending right away.
+        channel
+            .write(
+                self.dma_request(),
+                &register_base.I2C_TX_PACKET_FIFO as *const _ as u32,
+                BusWidth::Bits32,
+                &packet[..words],
+            )
+            .map_err(|_| I2cError::Timeout)?;
+
+        // Wait for the packet to be transferred or the transfer to fail.
+        let done = I2C_INT_PACKET_XFER_COMPLETE
+            | I2C_INT_ARBITRATION_LOST
+            | I2C_INT_NO_ACK
+            | I2C_INT_TX_FIFO_OVERFLOW;
+        wait_for(
+            || register_base.INTERRUPT_STATUS.read() & done != 0,
+            I2C_TRANSFER_TIMEOUT,
+        )
+        .map_err(|_| I2cError::Timeout)?;
+
+        self.check_interrupts()
+    }
+}
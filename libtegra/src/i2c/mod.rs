@@ -31,22 +31,34 @@
 //! the device and the respective [`Registers`] block pointer to communicate over
 //! I²C.
 //!
-//! - [`I2c`] holds pre-defined constants which represent the I2C
-//! controllers 1 through 6 and should be preferred over creating
-//! instances of the [`I2c`] struct manually.
+//! - The I2C controllers 1 through 6 are handed out by
+//! [`Peripherals::take`], so each of them has a single owner. Drivers
+//! of devices on a bus take a reference to its controller.
 //!
 //! - [`I2c::init`] has to be called for each device before it can
 //! be used.
 //!
-//! - [`I2c::read`] and [`I2c::write`] take buffers as arguments.
+//! - [`I2c::read`] and [`I2c::write`] take buffers as arguments. Reads
+//! transfer up to 8 bytes, writes up to 3 bytes following the register.
 //! For write operations, this buffer must contain the byte
 //! representation of the number to send in little-endian byte order.
 //! For read operations, the buffer wis filled with little-endian-ordered
 //! bytes.
 //!
 //! - I2C operations may fail for various reasons. Thus we return a [`Result`]
-//! which, in case of failure, provides access to a member of [`Error`], which
-//! can give more detailed information about the cause.
+//! which, in case of failure, provides access to a member of [`I2cError`],
+//! which tells whether the slave didn't acknowledge, another master took
+//! over the bus, the transfer timed out or the FIFOs overflowed.
+//!
+//! - A slave may get stuck holding SDA low when a transfer is interrupted,
+//! which wedges the whole bus. [`I2c::recover_bus`] clocks SCL until the
+//! slave lets go.
+//!
+//! - [`I2c::lock`] provides exclusive access to a bus shared between
+//! multiple users through a [`BusGuard`].
+//!
+//! - [`I2c::write_dma`] writes larger buffers in packet mode, with the
+//! packet fed to the controller by an APB DMA channel.
 //!
 //! - The [`Sync`] and [`Send`] traits are implemented for [`I2c`], it is
 //! considered thread-safe.
@@ -55,11 +67,16 @@
 //! [`Registers`]: struct.Registers.html
 //! [`I2c`]: struct.I2c.html
 //! [`Clock`]: ../clock/struct.Clock.html
+//! [`Peripherals::take`]: ../peripherals/struct.Peripherals.html#method.take
 //! [`I2c::init`]: struct.I2c.html#method.init
 //! [`I2c::read`]: struct.I2c.html#method.read
 //! [`I2c::write`]: struct.I2c.html#method.write
+//! [`I2c::lock`]: struct.I2c.html#method.lock
+//! [`BusGuard`]: struct.BusGuard.html
+//! [`I2c::write_dma`]: struct.I2c.html#method.write_dma
 //! [`Result`]: https://doc.rust-lang.org/core/result/enum.Result.html
-//! [`Error`]: enum.Error.html
+//! [`I2cError`]: enum.I2cError.html
+//! [`I2c::recover_bus`]: struct.I2c.html#method.recover_bus
 //! [`Sync`]: https://doc.rust-lang.org/nightly/core/marker/trait.Sync.html
 //! [`Send`]: https://doc.rust-lang.org/nightly/core/marker/trait.Send.html
 
@@ -68,9 +85,20 @@ use core::{
     marker::{Send, Sync},
 };
 
-use mirage_mmio::Mmio;
+use mirage_mmio::{BlockMmio, Mmio, ReadOnly, WriteOnly};
 
-use crate::{clock::Clock, timer::usleep};
+pub use bus::*;
+pub use dma::*;
+
+use crate::{
+    clock::Clock,
+    debug,
+    retry::RetryPolicy,
+    timer::{usleep, wait_for},
+};
+
+mod bus;
+mod dma;
 
 /// Base address for the I²C 1 controller.
 pub(crate) const I2C_1_BASE: u32 = 0x7000_C000;
@@ -90,10 +118,45 @@ pub(crate) const I2C_5_BASE: u32 = 0x7000_D000;
 /// Base address for the I²C 6 controller.
 pub(crate) const I2C_6_BASE: u32 = 0x7000_D100;
 
+/// The time to wait for a transfer to complete in microseconds.
+const I2C_TRANSFER_TIMEOUT: u32 = 100_000;
+
+/// The time to wait for a bus clear to complete in microseconds.
+const I2C_BUS_CLEAR_TIMEOUT: u32 = 200_000;
+
+/// The transfer status of the first slave in `I2C_STATUS`.
+const I2C_STATUS_CMD1_STAT: u32 = 0xF;
+/// Indicates that the controller is busy in `I2C_STATUS`.
+const I2C_STATUS_BUSY: u32 = 1 << 8;
+
+/// The RX FIFO underflowed.
+const I2C_INT_RX_FIFO_UNDERFLOW: u32 = 1 << 4;
+/// The TX FIFO overflowed.
+const I2C_INT_TX_FIFO_OVERFLOW: u32 = 1 << 5;
+/// The slave didn't acknowledge.
+const I2C_INT_NO_ACK: u32 = 1 << 3;
+/// Another master took over the bus.
+const I2C_INT_ARBITRATION_LOST: u32 = 1 << 2;
+/// The bus clear operation has finished.
+const I2C_INT_BUS_CLEAR_DONE: u32 = 1 << 11;
+
+/// Starts the bus clear operation.
+const I2C_BC_ENABLE: u32 = 1 << 0;
+/// Terminates the bus clear operation as soon as SDA is released.
+const I2C_BC_TERMINATE: u32 = 1 << 1;
+/// Sends a STOP condition after the bus clear operation.
+const I2C_BC_STOP_COND: u32 = 1 << 2;
+/// Toggles SCL up to nine times, enough to finish any byte in flight.
+const I2C_BC_SCLK_THRESHOLD: u32 = 9 << 16;
+/// Indicates that SDA was released in `I2C_BUS_CLEAR_STATUS`.
+const I2C_BC_STATUS: u32 = 1 << 0;
+
 /// Enumeration of I²C devices the controller can access.
 #[derive(Clone, Copy, Debug)]
 #[repr(u32)]
 pub enum Device {
+    /// The ROHM BM92T USB type-C and Power Delivery controller.
+    Bm92t = 0x18,
     /// The Maxim 77621 CPU device.
     Max77621Cpu = 0x1B,
     /// The Maxim 77621 GPU device.
@@ -102,23 +165,59 @@ pub enum Device {
     Max17050 = 0x36,
     /// The Maxim 77620 PWR device.
     Max77620Pwr = 0x3C,
+    /// The TI TMP451 temperature sensor.
+    Tmp451 = 0x4C,
     /// The Maxim 77620 RTC device.
     Max77620Rtc = 0x68,
     /// The TI BQ24193 device.
     Bq24193 = 0x6B,
+    /// The STMicroelectronics FTM4 touch panel controller.
+    Ftm4Touch = 0x49,
+    /// The Realtek ALC5639 audio codec.
+    ///
+    /// Its address 0x1C clashes with the one of the Maxim 77621 GPU device,
+    /// which is on a different bus, see [`Device::address`].
+    ///
+    /// [`Device::address`]: enum.Device.html#method.address
+    Alc5639,
+}
+
+impl Device {
+    /// Gets the 7-bit slave address of the device.
+    pub fn address(self) -> u32 {
+        match self {
+            Device::Alc5639 => 0x1C,
+            device => device as u32,
+        }
+    }
 }
 
 /// Enumeration of possible errors when communicating over the I²C protocol.
-#[derive(Clone, Copy, Debug)]
-pub enum Error {
-    /// Generic I²C error. Not closer specified.
-    Generic,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum I2cError {
+    /// The slave didn't acknowledge its address or a data byte.
+    Nack,
+    /// Another master took over the bus during the transfer.
+    ArbitrationLost,
+    /// The transfer didn't complete in time, the bus may be stuck.
+    Timeout,
+    /// The TX FIFO overflowed or the RX FIFO underflowed.
+    FifoOverrun,
     /// An issue with memory organization, e.g. a
     /// buffer is too large to fit an I2C register.
     MemoryError,
-    /// An I/O error that occurred during communication
-    /// over I²C. Indicated through the MMIOs.
-    IOError,
+    /// The bus is already held by another user.
+    Busy,
+}
+
+impl I2cError {
+    /// Whether the error may disappear when the operation is repeated.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            I2cError::MemoryError | I2cError::Busy => false,
+            _ => true,
+        }
+    }
 }
 
 /// Representation of the I²C registers.
@@ -131,10 +230,10 @@ pub struct Registers {
     pub I2C_CMD_DATA1: Mmio<u32>,
     pub I2C_CMD_DATA2: Mmio<u32>,
     _reserved0: [Mmio<u8>; 0x8],
-    pub I2C_STATUS: Mmio<u32>,
+    pub I2C_STATUS: ReadOnly<u32>,
     pub I2C_SL_CNFG: Mmio<u32>,
     pub I2C_SL_RCVD: Mmio<u32>,
-    pub I2C_SL_STATUS: Mmio<u32>,
+    pub I2C_SL_STATUS: ReadOnly<u32>,
     pub I2C_SL_ADDR1: Mmio<u32>,
     pub I2C_SL_ADDR2: Mmio<u32>,
     pub I2C_TLOW_SEXT: Mmio<u32>,
@@ -144,21 +243,21 @@ pub struct Registers {
     pub I2C_SL_INT_SOURCE: Mmio<u32>,
     pub I2C_SL_INT_SET: Mmio<u32>,
     _reserved2: [Mmio<u8>; 0x4],
-    pub I2C_TX_PACKET_FIFO: Mmio<u32>,
-    pub I2C_RX_FIFO: Mmio<u32>,
-    pub PACKET_TRANSFER_STATUS: Mmio<u32>,
+    pub I2C_TX_PACKET_FIFO: WriteOnly<u32>,
+    pub I2C_RX_FIFO: ReadOnly<u32>,
+    pub PACKET_TRANSFER_STATUS: ReadOnly<u32>,
     pub FIFO_CONTROL: Mmio<u32>,
-    pub FIFO_STATUS: Mmio<u32>,
+    pub FIFO_STATUS: ReadOnly<u32>,
     pub INTERRUPT_MASK: Mmio<u32>,
     pub INTERRUPT_STATUS: Mmio<u32>,
     pub I2C_CLK_DIVISOR: Mmio<u32>,
     pub I2C_INTERRUPT_SOURCE: Mmio<u32>,
     pub I2C_INTERRUPT_SET: Mmio<u32>,
-    pub I2C_SLV_TX_PACKET_FIFO: Mmio<u32>,
-    pub I2C_SLV_RX_FIFO: Mmio<u32>,
+    pub I2C_SLV_TX_PACKET_FIFO: WriteOnly<u32>,
+    pub I2C_SLV_RX_FIFO: ReadOnly<u32>,
     pub I2C_SLV_PACKET_STATUS: Mmio<u32>,
     pub I2C_BUS_CLEAR_CONFIG: Mmio<u32>,
-    pub I2C_BUS_CLEAR_STATUS: Mmio<u32>,
+    pub I2C_BUS_CLEAR_STATUS: ReadOnly<u32>,
     pub I2C_CONFIG_LOAD: Mmio<u32>,
     _reserved3: [Mmio<u8>; 0x4],
     pub I2C_INTERFACE_TIMING_0: Mmio<u32>,
@@ -168,57 +267,57 @@ pub struct Registers {
 }
 
 /// Representation of an I²C controller.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct I2c {
-    /// A pointer to the respective registers, used for communication.
-    registers: *const Registers,
+    /// The respective registers, used for communication.
+    registers: BlockMmio<Registers>,
     /// The respective device clock for the controller.
-    clock: &'static Clock,
+    clock: Clock,
 }
 
 // Definitions of known I²C controllers.
 impl I2c {
     /// Representation of the I²C 1 controller.
-    pub const C1: Self = I2c {
-        registers: I2C_1_BASE as *const _,
-        clock: &Clock::I2C_1,
+    pub(crate) const C1: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_1_BASE as usize) },
+        clock: Clock::I2C_1,
     };
 
     /// Representation of the I²C 2 controller.
-    pub const C2: Self = I2c {
-        registers: I2C_2_BASE as *const _,
-        clock: &Clock::I2C_2,
+    pub(crate) const C2: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_2_BASE as usize) },
+        clock: Clock::I2C_2,
     };
 
     /// Representation of the I²C 3 controller.
-    pub const C3: Self = I2c {
-        registers: I2C_3_BASE as *const _,
-        clock: &Clock::I2C_3,
+    pub(crate) const C3: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_3_BASE as usize) },
+        clock: Clock::I2C_3,
     };
 
     /// Representation of the I²C 4 controller.
-    pub const C4: Self = I2c {
-        registers: I2C_4_BASE as *const _,
-        clock: &Clock::I2C_4,
+    pub(crate) const C4: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_4_BASE as usize) },
+        clock: Clock::I2C_4,
     };
 
     /// Representation of the I²C 5 controller.
-    pub const C5: Self = I2c {
-        registers: I2C_5_BASE as *const _,
-        clock: &Clock::I2C_5,
+    pub(crate) const C5: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_5_BASE as usize) },
+        clock: Clock::I2C_5,
     };
 
     /// Representation of the I²C 6 controller.
-    pub const C6: Self = I2c {
-        registers: I2C_6_BASE as *const _,
-        clock: &Clock::I2C_6,
+    pub(crate) const C6: Self = I2c {
+        registers: unsafe { BlockMmio::new(I2C_6_BASE as usize) },
+        clock: Clock::I2C_6,
     };
 }
 
 impl I2c {
     /// Loads the hardware configuration for the controller.
     fn load_config(&self) {
-        let register_base = unsafe { &*self.registers };
+        let register_base = &*self.registers;
 
         // Set MSTR_CONFIG_LOAD, TIMEOUT_CONFIG_LOAD, undocumented bit.
         register_base.I2C_CONFIG_LOAD.write(0x25);
@@ -233,19 +332,15 @@ impl I2c {
         }
     }
 
-    /// Transmits a packet of data to a given device over I²C.
-    fn write_packet(&self, device: Device, packet: &[u8]) -> Result<(), Error> {
-        let register_base = unsafe { &*self.registers };
-
-        // Set device for 7-bit write mode.
-        register_base.I2C_CMD_ADDR0.write((device as u32) << 1);
+    /// Kicks off a transfer with the given configuration and
+    /// waits for it to complete.
+    fn transfer(&self, config: u32) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
 
-        // Load in data to write.
-        let data = u32::from_le_bytes(packet.try_into().unwrap());
-        register_base.I2C_CMD_DATA1.write(data);
+        // Clear stale interrupts so errors can be attributed to this transfer.
+        register_base.INTERRUPT_STATUS.write(register_base.INTERRUPT_STATUS.read());
 
-        // Set config with LENGTH = packet.len(), NEW_MASTER_FSM, DEBOUNCE_CNT = 4T.
-        register_base.I2C_CNFG.write((((packet.len() - 1) << 1) | 0x2800) as u32);
+        register_base.I2C_CNFG.write(config);
 
         // Kick off the transaction.
         self.load_config();
@@ -253,87 +348,154 @@ impl I2c {
         // CONFIG |= SEND
         register_base.I2C_CNFG.write((register_base.I2C_CNFG.read() & 0xFFFF_FDFF) | 0x200);
 
-        while (register_base.I2C_STATUS.read() & 0x100) != 0 {
-            // Wait until not busy.
-        }
+        // Wait until not busy.
+        wait_for(
+            || (register_base.I2C_STATUS.read() & I2C_STATUS_BUSY) == 0,
+            I2C_TRANSFER_TIMEOUT,
+        )
+        .map_err(|_| I2cError::Timeout)?;
+
+        self.check_interrupts()?;
 
-        // Check whether the translation was successful and determine the appropriate Result.
-        // CMD1_STAT == SL1_XFER_SUCCESSFUL
-        if (register_base.I2C_STATUS.read() & 0xF) == 0 {
+        // CMD1_STAT != SL1_XFER_SUCCESSFUL
+        if register_base.I2C_STATUS.read() & I2C_STATUS_CMD1_STAT != 0 {
+            Err(I2cError::Nack)
+        } else {
             Ok(())
+        }
+    }
+
+    /// Acknowledges all pending interrupts and determines
+    /// the cause of a failed transfer from them.
+    fn check_interrupts(&self) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
+
+        let interrupts = register_base.INTERRUPT_STATUS.read();
+        register_base.INTERRUPT_STATUS.write(interrupts);
+
+        let result = if interrupts & I2C_INT_ARBITRATION_LOST != 0 {
+            Err(I2cError::ArbitrationLost)
+        } else if interrupts & (I2C_INT_TX_FIFO_OVERFLOW | I2C_INT_RX_FIFO_UNDERFLOW) != 0 {
+            Err(I2cError::FifoOverrun)
+        } else if interrupts & I2C_INT_NO_ACK != 0 {
+            Err(I2cError::Nack)
         } else {
-            Err(Error::IOError)
+            Ok(())
+        };
+
+        if let Err(error) = result {
+            debug!(
+                "I2C{}: transfer failed with {:?} (interrupts {:#X})",
+                self.index() + 1,
+                error,
+                interrupts
+            );
         }
+
+        result
     }
 
-    /// Reads a packet of data from a given device over I²C.
-    fn read_packet(&self, device: Device, packet: &mut [u8]) -> Result<(), Error> {
-        let register_base = unsafe { &*self.registers };
+    /// Transmits a packet of up to 4 bytes to the device with the given
+    /// 7-bit address over I²C.
+    pub(crate) fn write_packet(&self, address: u32, packet: &[u8]) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
+
+        // Set device for 7-bit write mode.
+        register_base.I2C_CMD_ADDR0.write(address << 1);
+
+        // Load in data to write.
+        let mut data = [0; 4];
+        data[..packet.len()].copy_from_slice(packet);
+        register_base.I2C_CMD_DATA1.write(u32::from_le_bytes(data));
+
+        // Set config with LENGTH = packet.len(), NEW_MASTER_FSM, DEBOUNCE_CNT = 4T.
+        self.transfer((((packet.len() - 1) << 1) | 0x2800) as u32)
+    }
+
+    /// Reads a packet of up to 8 bytes from the device with the given
+    /// 7-bit address over I²C.
+    pub(crate) fn read_packet(&self, address: u32, packet: &mut [u8]) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
 
         // Set device for 7-bit read mode.
-        register_base.I2C_CMD_ADDR0.write(((device as u32) << 1) | 1);
+        register_base.I2C_CMD_ADDR0.write((address << 1) | 1);
 
         // Set config with LENGTH = packet.len(), NEW_MASTER_FSM, DEBOUNCE_CNT = 4T.
-        register_base.I2C_CNFG.write((((packet.len() - 1) << 1) | 0x2840) as u32);
+        self.transfer((((packet.len() - 1) << 1) | 0x2840) as u32)?;
 
-        // Kick off the transaction.
-        self.load_config();
+        // Read and copy back the result.
+        let mut result = [0; 8];
+        result[..4].copy_from_slice(&register_base.I2C_CMD_DATA1.read().to_le_bytes());
+        result[4..].copy_from_slice(&register_base.I2C_CMD_DATA2.read().to_le_bytes());
+        packet.copy_from_slice(&result[..packet.len()]);
 
-        // CONFIG |= SEND
-        register_base.I2C_CNFG.write((register_base.I2C_CNFG.read() & 0xFFFF_FDFF) | 0x200);
+        Ok(())
+    }
+
+    /// Frees the bus from a slave which holds SDA low, e.g. because a
+    /// transfer was interrupted in the middle of a byte.
+    ///
+    /// The controller toggles SCL until the slave releases SDA, at most
+    /// nine times, and then sends a STOP condition.
+    pub fn recover_bus(&self) -> Result<(), I2cError> {
+        let register_base = &*self.registers;
+
+        // Clear a stale BUS_CLEAR_DONE.
+        register_base.INTERRUPT_STATUS.write(register_base.INTERRUPT_STATUS.read());
 
-        while (register_base.I2C_STATUS.read() & 0x100) != 0 {
-            // Wait until not busy.
+        // Configure the bus clear operation and load the configuration.
+        register_base
+            .I2C_BUS_CLEAR_CONFIG
+            .write(I2C_BC_SCLK_THRESHOLD | I2C_BC_STOP_COND | I2C_BC_TERMINATE);
+        self.load_config();
+
+        // Start clocking SCL.
+        register_base.I2C_BUS_CLEAR_CONFIG.write(
+            register_base.I2C_BUS_CLEAR_CONFIG.read() | I2C_BC_ENABLE,
+        );
+
+        // Wait until BUS_CLEAR_DONE is set.
+        if wait_for(
+            || register_base.INTERRUPT_STATUS.read() & I2C_INT_BUS_CLEAR_DONE != 0,
+            I2C_BUS_CLEAR_TIMEOUT,
+        )
+        .is_err()
+        {
+            debug!("I2C{}: bus clear timed out", self.index() + 1);
+            return Err(I2cError::Timeout);
         }
 
-        // Check whether the translation was successful and determine the appropriate Result.
-        // CMD1_STAT == SL1_XFER_SUCCESSFUL
-        if (register_base.I2C_STATUS.read() & 0xF) == 0 {
-            // Read and copy back the result.
-            let result = register_base.I2C_CMD_DATA1.read();
-            packet.copy_from_slice(&result.to_le_bytes()[..packet.len()]);
+        // Acknowledge the interrupt.
+        register_base.INTERRUPT_STATUS.write(register_base.INTERRUPT_STATUS.read());
 
+        // Check whether the slave actually released SDA.
+        if register_base.I2C_BUS_CLEAR_STATUS.read() & I2C_BC_STATUS != 0 {
             Ok(())
         } else {
-            Err(Error::IOError)
+            Err(I2cError::Nack)
         }
     }
 
     /// Initializes the I²C controller.
-    pub fn init(&self) {
-        let register_base = unsafe { &*self.registers };
+    pub fn init(&mut self) {
+        let register_base = &*self.registers;
 
         // Enable the device clock.
         self.clock.enable();
 
         // Setup divisor, and clear the bus.
         register_base.I2C_CLK_DIVISOR.write(0x50001);
-        register_base.I2C_BUS_CLEAR_CONFIG.write(0x90003);
-
-        // Load hardware configuration.
-        self.load_config();
-
-        // Wait a while until BUS_CLEAR_DONE is set.
-        for _ in 0..10 {
-            usleep(20_000);
-
-            if (register_base.INTERRUPT_STATUS.read() & 0x800) != 0 {
-                break;
-            }
-        }
 
-        // Dummy read.
-        register_base.I2C_BUS_CLEAR_STATUS.read();
-
-        // Read and set the Interrupt Status.
-        register_base.INTERRUPT_STATUS.write(register_base.INTERRUPT_STATUS.read());
+        // A bus without a stuck slave doesn't need to be cleared, so a
+        // failure here isn't fatal. Transfers will report a wedged bus.
+        self.recover_bus().ok();
     }
 
     /// Writes a buffer of data to a register from a device over I²C.
-    pub fn write(&self, device: Device, register: u8, data: &[u8]) -> Result<(), Error> {
+    pub fn write(&self, device: Device, register: u8, data: &[u8]) -> Result<(), I2cError> {
         // Limit input size to 24 bits. One byte is reserved for the device register.
         if data.len() > 3 {
-            return Err(Error::MemoryError);
+            return Err(I2cError::MemoryError);
         }
 
         // Prepare an I²C packet, composed from the device register and the provided data.
@@ -343,37 +505,67 @@ impl I2c {
         packet[1..=data.len()].copy_from_slice(data);
 
         // Write the packet to the device.
-        self.write_packet(device, &packet[..])
+        self.write_packet(device.address(), &packet[..=data.len()])
     }
 
     /// Writes a byte to a register of a device over I²C.
     #[inline(always)]
-    pub fn write_byte(&self, device: Device, register: u8, byte: u8) -> Result<(), Error> {
+    pub fn write_byte(&self, device: Device, register: u8, byte: u8) -> Result<(), I2cError> {
         self.write(device, register, &byte.to_le_bytes())
     }
 
     /// Reads the contents of a register from a device over I²C into a given buffer.
-    pub fn read(&self, device: Device, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
-        // Limit output buffer size to 32 bits.
-        if buffer.len() > 4 {
-            return Err(Error::MemoryError);
+    pub fn read(&self, device: Device, register: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        // Limit output buffer size to the 64 bits of the data registers.
+        if buffer.is_empty() || buffer.len() > 8 {
+            return Err(I2cError::MemoryError);
         }
 
         // Write single byte register ID to device.
-        self.write_packet(device, &[register])?;
+        self.write_packet(device.address(), &[register])?;
 
         // Receive data and write them to the buffer.
-        self.read_packet(device, buffer)
+        self.read_packet(device.address(), buffer)
     }
 
     /// Reads a byte from a register of a device over I²C.
     #[inline(always)]
-    pub fn read_byte(&self, device: Device, register: u8) -> Result<u8, Error> {
+    pub fn read_byte(&self, device: Device, register: u8) -> Result<u8, I2cError> {
         let mut buffer = [0; 1];
         self.read(device, register, &mut buffer)?;
 
         Ok(u8::from_le_bytes(buffer.try_into().unwrap()))
     }
+
+    /// Writes a buffer of data to a register from a device over I²C,
+    /// retrying I/O errors according to the given policy.
+    pub fn write_with(
+        &self,
+        policy: &RetryPolicy,
+        device: Device,
+        register: u8,
+        data: &[u8],
+    ) -> Result<(), I2cError> {
+        policy.run_if(
+            || self.write(device, register, data),
+            |e| e.is_transient(),
+        )
+    }
+
+    /// Reads the contents of a register from a device over I²C into a given buffer,
+    /// retrying I/O errors according to the given policy.
+    pub fn read_with(
+        &self,
+        policy: &RetryPolicy,
+        device: Device,
+        register: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        policy.run_if(
+            || self.read(device, register, buffer),
+            |e| e.is_transient(),
+        )
+    }
 }
 
 unsafe impl Send for I2c {}
@@ -0,0 +1,274 @@
+//! Parser for INI configuration files.
+//!
+//! # Description
+//!
+//! Bootloaders built on Mirage are usually configured through an INI file on
+//! the SD card, similar to `bootloader.ini`:
+//!
+//! ```ini
+//! ; Global settings.
+//! [config]
+//! autoboot = 1
+//! timeout = 0x3
+//!
+//! [Stock]
+//! payload = bootloader/payloads/stock.bin
+//! ```
+//!
+//! # Implementation
+//!
+//! - [`Ini`] parses a file in place, without allocating. Keys and values
+//! borrow from the input.
+//!
+//! - [`Ini::items`] iterates over the sections and properties in file order,
+//! which allows multiple sections with the same name, e.g. boot entries.
+//!
+//! - [`Ini::get`], [`Ini::get_bool`] and [`Ini::get_u32`] look up the last
+//! value of a key in a section.
+//!
+//! - Lines starting with `;` or `#` are comments. Whitespace around section
+//! names, keys and values is ignored. Properties before the first section
+//! belong to a section with an empty name.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::ini::Ini;
+//!
+//! fn main() {
+//!     let ini = Ini::new(b"[config]\nautoboot = 1\n");
+//!
+//!     assert_eq!(ini.get_bool("config", "autoboot"), Some(true));
+//! }
+//! ```
+//!
+//! [`Ini`]: struct.Ini.html
+//! [`Ini::items`]: struct.Ini.html#method.items
+//! [`Ini::get`]: struct.Ini.html#method.get
+//! [`Ini::get_bool`]: struct.Ini.html#method.get_bool
+//! [`Ini::get_u32`]: struct.Ini.html#method.get_u32
+
+use core::str;
+
+/// An element of an INI file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item<'a> {
+    /// The start of a section with the given name.
+    Section(&'a str),
+    /// A `key = value` pair in the current section.
+    Property {
+        /// The section the property belongs to.
+        section: &'a str,
+        /// The key of the property.
+        key: &'a str,
+        /// The value of the property.
+        value: &'a str,
+    },
+}
+
+/// A malformed line in an INI file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The number of the line, starting at 1.
+    pub line: usize,
+}
+
+/// An INI file.
+#[derive(Clone, Copy, Debug)]
+pub struct Ini<'a> {
+    /// The contents of the file.
+    data: &'a [u8],
+}
+
+impl<'a> Ini<'a> {
+    /// Wraps the contents of an INI file.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Ini { data }
+    }
+
+    /// Iterates over the sections and properties of the file.
+    pub fn items(&self) -> Items<'a> {
+        Items {
+            lines: self.data.split(is_newline as fn(&u8) -> bool),
+            line: 0,
+            section: "",
+        }
+    }
+
+    /// Gets the value of a key in a section.
+    ///
+    /// If the key occurs more than once, the last value is returned.
+    /// Malformed lines are skipped.
+    pub fn get(&self, section: &str, key: &str) -> Option<&'a str> {
+        self.items()
+            .filter_map(|item| match item {
+                Ok(Item::Property {
+                    section: s,
+                    key: k,
+                    value,
+                }) if s == section && k == key => Some(value),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Gets the value of a key in a section as a string.
+    pub fn get_str(&self, section: &str, key: &str) -> Option<&'a str> {
+        self.get(section, key)
+    }
+
+    /// Gets the value of a key in a section as a boolean.
+    ///
+    /// `1`, `true`, `yes` and `on` are true, `0`, `false`, `no` and `off`
+    /// are false.
+    pub fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        self.get(section, key).and_then(parse_bool)
+    }
+
+    /// Gets the value of a key in a section as an integer.
+    ///
+    /// Values may be decimal or hexadecimal with a `0x` prefix.
+    pub fn get_u32(&self, section: &str, key: &str) -> Option<u32> {
+        self.get(section, key).and_then(parse_u32)
+    }
+}
+
+/// Iterator over the items of an INI file.
+///
+/// Created by [`Ini::items`].
+///
+/// [`Ini::items`]: struct.Ini.html#method.items
+#[derive(Clone)]
+pub struct Items<'a> {
+    /// The remaining lines of the file.
+    lines: core::slice::Split<'a, u8, fn(&u8) -> bool>,
+    /// The number of the current line.
+    line: usize,
+    /// The name of the current section.
+    section: &'a str,
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = Result<Item<'a>, SyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = self.lines.next()?;
+            self.line += 1;
+
+            let error = SyntaxError { line: self.line };
+            let line = match str::from_utf8(line) {
+                Ok(line) => line.trim(),
+                Err(_) => return Some(Err(error)),
+            };
+
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Some(Err(error));
+                }
+
+                self.section = line[1..line.len() - 1].trim();
+                return Some(Ok(Item::Section(self.section)));
+            }
+
+            let separator = match line.find('=') {
+                Some(separator) => separator,
+                None => return Some(Err(error)),
+            };
+
+            return Some(Ok(Item::Property {
+                section: self.section,
+                key: line[..separator].trim(),
+                value: line[separator + 1..].trim(),
+            }));
+        }
+    }
+}
+
+/// Checks whether a byte ends a line.
+fn is_newline(byte: &u8) -> bool {
+    *byte == b'\n'
+}
+
+/// Parses a boolean value.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a decimal or hexadecimal integer value.
+fn parse_u32(value: &str) -> Option<u32> {
+    if value.starts_with("0x") || value.starts_with("0X") {
+        u32::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &[u8] = b"; Comment\r\n\
+        verbose = on\n\
+        [config]\n\
+        autoboot = 1\n\
+        timeout = 0x1F\n\
+        \n\
+        [ Stock ]\n\
+        payload = bootloader/payloads/stock.bin\n\
+        broken line\n\
+        [config]\n\
+        autoboot=0\n";
+
+    /// Tests iterating over the items of a file.
+    #[test]
+    fn items() {
+        let ini = Ini::new(CONFIG);
+        let mut items = ini.items();
+
+        assert_eq!(
+            items.next(),
+            Some(Ok(Item::Property {
+                section: "",
+                key: "verbose",
+                value: "on",
+            }))
+        );
+        assert_eq!(items.next(), Some(Ok(Item::Section("config"))));
+        assert_eq!(items.nth(2), Some(Ok(Item::Section("Stock"))));
+        assert_eq!(
+            items.next(),
+            Some(Ok(Item::Property {
+                section: "Stock",
+                key: "payload",
+                value: "bootloader/payloads/stock.bin",
+            }))
+        );
+        assert_eq!(items.next(), Some(Err(SyntaxError { line: 9 })));
+        assert_eq!(items.count(), 2);
+    }
+
+    /// Tests the typed getters.
+    #[test]
+    fn getters() {
+        let ini = Ini::new(CONFIG);
+
+        assert_eq!(ini.get_bool("", "verbose"), Some(true));
+        assert_eq!(ini.get_bool("config", "autoboot"), Some(false));
+        assert_eq!(ini.get_u32("config", "timeout"), Some(0x1F));
+        assert_eq!(
+            ini.get_str("Stock", "payload"),
+            Some("bootloader/payloads/stock.bin")
+        );
+        assert_eq!(ini.get_u32("Stock", "payload"), None);
+        assert_eq!(ini.get("Stock", "timeout"), None);
+    }
+}
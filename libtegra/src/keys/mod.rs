@@ -0,0 +1,399 @@
+//! Keyblob and master key derivation.
+//!
+//! # Description
+//!
+//! Up to firmware 6.0.0, the root keys of the Switch OS are stored in
+//! encrypted keyblobs in the `BOOT0` partition, one per key generation.
+//! Decrypting them requires the TSEC key and the Secure Boot Key (SBK),
+//! which the bootrom leaves in the SE. The decrypted keyblob contains the
+//! master key encryption key (KEK) of its generation and the key package1
+//! is encrypted with.
+//!
+//! # Implementation
+//!
+//! - [`KeySources`] holds the public seeds of the derivation. They are
+//! provided by the caller rather than embedded into libtegra.
+//!
+//! - [`derive_keys`] runs the whole pipeline for a key generation: it
+//! derives the keyblob key from the TSEC key and the SBK, verifies the
+//! keyblob CMAC, decrypts it and derives the master key and the device key
+//! from it.
+//!
+//! - The resulting keys are left in the keyslots [`KEYSLOT_DEVICE_KEY`],
+//! [`KEYSLOT_PACKAGE1_KEY`] and [`KEYSLOT_MASTER_KEY`], which are locked
+//! against being read back. The TSEC key is cleared from the SE afterwards.
+//!
+//! - All derivation steps happen inside the SE. Intermediate keys that pass
+//! through memory are wiped before returning.
+//!
+//! - Key generations after [`MAX_KEYBLOB_GENERATION`] derive their keys
+//! through the TSEC firmware instead and aren't supported here.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     keys::{self, KeySources, KEYBLOB_SIZE},
+//!     tsec::Tsec,
+//!     Result,
+//! };
+//!
+//! fn derive(
+//!     sources: &KeySources,
+//!     tsec_firmware: &[u8],
+//!     keyblob: &[u8; KEYBLOB_SIZE],
+//! ) -> Result<()> {
+//!     let words = Tsec::new().get_key(1, tsec_firmware)?;
+//!
+//!     let mut tsec_key = [0; 0x10];
+//!     for (chunk, word) in tsec_key.chunks_mut(4).zip(words.iter()) {
+//!         chunk.copy_from_slice(&word.to_le_bytes());
+//!     }
+//!
+//!     keys::derive_keys(sources, &tsec_key, keyblob, 0)
+//! }
+//! ```
+//!
+//! [`KeySources`]: struct.KeySources.html
+//! [`derive_keys`]: fn.derive_keys.html
+//! [`KEYSLOT_DEVICE_KEY`]: constant.KEYSLOT_DEVICE_KEY.html
+//! [`KEYSLOT_PACKAGE1_KEY`]: constant.KEYSLOT_PACKAGE1_KEY.html
+//! [`KEYSLOT_MASTER_KEY`]: constant.KEYSLOT_MASTER_KEY.html
+//! [`MAX_KEYBLOB_GENERATION`]: constant.MAX_KEYBLOB_GENERATION.html
+
+use core::{convert::TryInto, ptr};
+
+use crate::{
+    ct,
+    se::{KeySlot, SecurityEngine, AES_BLOCK_SIZE},
+    Error, Result,
+};
+
+/// The offset of the keyblobs in the `BOOT0` partition.
+pub const KEYBLOB_OFFSET: usize = 0x18_0000;
+
+/// The distance between two keyblobs in `BOOT0` in bytes.
+pub const KEYBLOB_SPACING: usize = 0x200;
+
+/// The size of an encrypted keyblob in bytes.
+pub const KEYBLOB_SIZE: usize = 0xB0;
+
+/// The last key generation that is stored in a keyblob.
+pub const MAX_KEYBLOB_GENERATION: u8 = 5;
+
+/// The keyslot receiving the device key.
+pub const KEYSLOT_DEVICE_KEY: usize = 0xA;
+
+/// The keyslot receiving the package1 key.
+pub const KEYSLOT_PACKAGE1_KEY: usize = 0xB;
+
+/// The keyslot receiving the master key.
+pub const KEYSLOT_MASTER_KEY: usize = 0xC;
+
+/// The keyslot holding the TSEC key during derivation.
+pub const KEYSLOT_TSEC_KEY: usize = 0xD;
+
+/// The keyslot holding the SBK, as left by the bootrom.
+const KEYSLOT_SBK: usize = 0xE;
+
+/// The offset of the CMAC in a keyblob.
+const KEYBLOB_MAC_OFFSET: usize = 0x00;
+/// The offset of the CTR in a keyblob.
+const KEYBLOB_CTR_OFFSET: usize = 0x10;
+/// The offset of the encrypted data in a keyblob.
+const KEYBLOB_DATA_OFFSET: usize = 0x20;
+/// The offset of the package1 key in the decrypted data.
+const PACKAGE1_KEY_OFFSET: usize = 0x80;
+
+/// The seeds of the key derivation.
+#[derive(Clone, Debug)]
+pub struct KeySources {
+    /// The keyblob key seeds, indexed by key generation.
+    pub keyblob_keys: [[u8; AES_BLOCK_SIZE]; MAX_KEYBLOB_GENERATION as usize + 1],
+    /// The seed of the keyblob MAC keys.
+    pub keyblob_mac_key: [u8; AES_BLOCK_SIZE],
+    /// The seed of the master keys.
+    pub master_key: [u8; AES_BLOCK_SIZE],
+    /// The seed of the device key.
+    pub device_key: [u8; AES_BLOCK_SIZE],
+}
+
+/// A single AES block.
+type Block = [u8; AES_BLOCK_SIZE];
+
+/// Overwrites key material in memory.
+pub(crate) fn wipe(data: &mut [u8]) {
+    for byte in data.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+}
+
+/// Multiplies a CMAC subkey by `x` in GF(2^128).
+fn double(block: &Block) -> Block {
+    let mut result = [0; AES_BLOCK_SIZE];
+
+    let mut carry = 0;
+    for (out, &byte) in result.iter_mut().zip(block.iter()).rev() {
+        *out = (byte << 1) | carry;
+        carry = byte >> 7;
+    }
+    if block[0] & 0x80 != 0 {
+        result[AES_BLOCK_SIZE - 1] ^= 0x87;
+    }
+
+    result
+}
+
+/// Computes the AES-CMAC of `data` with the given block cipher.
+fn cmac<F>(mut encrypt: F, data: &[u8]) -> Result<Block>
+where
+    F: FnMut(&mut Block) -> Result<()>,
+{
+    let mut subkey = [0; AES_BLOCK_SIZE];
+    encrypt(&mut subkey)?;
+    subkey = double(&subkey);
+
+    let mut mac = [0; AES_BLOCK_SIZE];
+    let blocks = (data.len() + AES_BLOCK_SIZE - 1) / AES_BLOCK_SIZE;
+
+    for (i, chunk) in data.chunks(AES_BLOCK_SIZE).enumerate() {
+        for (m, byte) in mac.iter_mut().zip(chunk) {
+            *m ^= byte;
+        }
+
+        if i + 1 == blocks {
+            // Incomplete final blocks are padded and use the second subkey.
+            if chunk.len() < AES_BLOCK_SIZE {
+                mac[chunk.len()] ^= 0x80;
+                subkey = double(&subkey);
+            }
+            for (m, k) in mac.iter_mut().zip(subkey.iter()) {
+                *m ^= k;
+            }
+        }
+
+        encrypt(&mut mac)?;
+    }
+
+    if data.is_empty() {
+        mac[0] ^= 0x80;
+        for (m, k) in mac.iter_mut().zip(double(&subkey).iter()) {
+            *m ^= k;
+        }
+        encrypt(&mut mac)?;
+    }
+
+    wipe(&mut subkey);
+
+    Ok(mac)
+}
+
+/// Increments a big endian counter block.
+fn increment(counter: &mut Block) {
+    for byte in counter.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+/// Decrypts `data` in place with AES-CTR and the given block cipher.
+fn ctr<F>(mut encrypt: F, counter: &Block, data: &mut [u8]) -> Result<()>
+where
+    F: FnMut(&mut Block) -> Result<()>,
+{
+    let mut counter = *counter;
+
+    for chunk in data.chunks_mut(AES_BLOCK_SIZE) {
+        let mut keystream = counter;
+        encrypt(&mut keystream)?;
+
+        for (byte, key) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= key;
+        }
+
+        wipe(&mut keystream);
+        increment(&mut counter);
+    }
+
+    Ok(())
+}
+
+/// Derives a key from a seed into a keyslot.
+///
+/// The seed is decrypted with the TSEC key first, then unwrapped into
+/// `destination` with the SBK.
+fn derive_keyblob_key(se: &mut SecurityEngine, destination: usize, seed: &Block) -> Result<()> {
+    let mut key = *seed;
+
+    let result = se.aes_ecb_decrypt_block(KEYSLOT_TSEC_KEY, &mut key);
+    let result = result.and_then(|_| unwrap_key(se, destination, KEYSLOT_SBK, &key));
+    wipe(&mut key);
+
+    result
+}
+
+/// Decrypts a wrapped key with the key in `source` into `destination`.
+fn unwrap_key(
+    se: &mut SecurityEngine,
+    destination: usize,
+    source: usize,
+    wrapped_key: &Block,
+) -> Result<()> {
+    let mut key = *wrapped_key;
+
+    let result = se.decrypt_data_into_keyslot(destination, source, &mut key);
+    wipe(&mut key);
+
+    result
+}
+
+/// Decrypts a keyblob and derives the keys of its generation.
+///
+/// `tsec_key` is the key retrieved from the TSEC firmware of the installed
+/// package1, `keyblob` the keyblob of the given key generation. Fails with
+/// [`Error::InvalidArgument`] for generations that aren't stored in
+/// keyblobs and with [`Error::InvalidData`] if the keyblob CMAC doesn't
+/// match, which usually means a wrong TSEC key.
+///
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+pub fn derive_keys(
+    sources: &KeySources,
+    tsec_key: &[u8; AES_BLOCK_SIZE],
+    keyblob: &[u8; KEYBLOB_SIZE],
+    generation: u8,
+) -> Result<()> {
+    if generation > MAX_KEYBLOB_GENERATION {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut se = SecurityEngine::new();
+    let result = se
+        .set_aes_keyslot(KEYSLOT_TSEC_KEY, tsec_key)
+        .and_then(|_| derive_with_tsec_key(&mut se, sources, keyblob, generation as usize));
+
+    // The TSEC key is not needed anymore, whatever happened.
+    se.clear_aes_keyslot(KEYSLOT_TSEC_KEY)?;
+
+    result
+}
+
+/// Runs the derivation with the TSEC key in its keyslot.
+fn derive_with_tsec_key(
+    se: &mut SecurityEngine,
+    sources: &KeySources,
+    keyblob: &[u8; KEYBLOB_SIZE],
+    generation: usize,
+) -> Result<()> {
+    // The keyblob key temporarily lives in the package1 keyslot.
+    derive_keyblob_key(se, KEYSLOT_PACKAGE1_KEY, &sources.keyblob_keys[generation])?;
+
+    // Verify the keyblob with the MAC key, temporarily in the master keyslot.
+    unwrap_key(se, KEYSLOT_MASTER_KEY, KEYSLOT_PACKAGE1_KEY, &sources.keyblob_mac_key)?;
+
+    let mac = cmac(
+        |block| se.aes_ecb_encrypt_block(KEYSLOT_MASTER_KEY, block),
+        &keyblob[KEYBLOB_CTR_OFFSET..],
+    )?;
+    if !ct::eq(&mac, &keyblob[KEYBLOB_MAC_OFFSET..KEYBLOB_CTR_OFFSET]) {
+        se.clear_aes_keyslot(KEYSLOT_PACKAGE1_KEY)?;
+        se.clear_aes_keyslot(KEYSLOT_MASTER_KEY)?;
+        return Err(Error::InvalidData);
+    }
+
+    // Decrypt the keyblob.
+    let mut counter = [0; AES_BLOCK_SIZE];
+    counter.copy_from_slice(&keyblob[KEYBLOB_CTR_OFFSET..KEYBLOB_DATA_OFFSET]);
+    let mut data = [0; KEYBLOB_SIZE - KEYBLOB_DATA_OFFSET];
+    data.copy_from_slice(&keyblob[KEYBLOB_DATA_OFFSET..]);
+
+    let result = ctr(
+        |block| se.aes_ecb_encrypt_block(KEYSLOT_PACKAGE1_KEY, block),
+        &counter,
+        &mut data,
+    );
+    let result = result.and_then(|_| {
+        // The master key is derived from the master KEK.
+        se.set_aes_keyslot(KEYSLOT_MASTER_KEY, (&data[..AES_BLOCK_SIZE]).try_into().unwrap())?;
+        unwrap_key(se, KEYSLOT_MASTER_KEY, KEYSLOT_MASTER_KEY, &sources.master_key)?;
+
+        se.set_aes_keyslot(
+            KEYSLOT_PACKAGE1_KEY,
+            (&data[PACKAGE1_KEY_OFFSET..PACKAGE1_KEY_OFFSET + AES_BLOCK_SIZE])
+                .try_into()
+                .unwrap(),
+        )
+    });
+    wipe(&mut data);
+    result?;
+
+    // The device key is derived from the first keyblob key.
+    derive_keyblob_key(se, KEYSLOT_DEVICE_KEY, &sources.keyblob_keys[0])?;
+    unwrap_key(se, KEYSLOT_DEVICE_KEY, KEYSLOT_DEVICE_KEY, &sources.device_key)?;
+
+    for &keyslot in &[KEYSLOT_DEVICE_KEY, KEYSLOT_PACKAGE1_KEY, KEYSLOT_MASTER_KEY] {
+        KeySlot::aes(keyslot).unwrap().lock_read();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the CMAC subkey generation against RFC 4493.
+    #[test]
+    fn subkeys() {
+        let l = [
+            0x7D, 0xF7, 0x6B, 0x0C, 0x1A, 0xB8, 0x99, 0xB3, 0x3E, 0x42, 0xF0, 0x47, 0xB9, 0x1B,
+            0x54, 0x6F,
+        ];
+        let k1 = double(&l);
+        let k2 = double(&k1);
+
+        assert_eq!(
+            k1,
+            [
+                0xFB, 0xEE, 0xD6, 0x18, 0x35, 0x71, 0x33, 0x66, 0x7C, 0x85, 0xE0, 0x8F, 0x72, 0x36,
+                0xA8, 0xDE
+            ]
+        );
+        assert_eq!(
+            k2,
+            [
+                0xF7, 0xDD, 0xAC, 0x30, 0x6A, 0xE2, 0x66, 0xCC, 0xF9, 0x0B, 0xC1, 0x1E, 0xE4, 0x6D,
+                0x51, 0x3B
+            ]
+        );
+    }
+
+    /// Tests the CTR keystream with a counter that carries.
+    #[test]
+    fn counter() {
+        let mut counter = [0xFF; AES_BLOCK_SIZE];
+        counter[0] = 0;
+        let mut seen = [[0; AES_BLOCK_SIZE]; 2];
+        let mut calls = 0;
+
+        let mut data = [0u8; 0x18];
+        ctr(
+            |block| {
+                seen[calls] = *block;
+                calls += 1;
+                Ok(())
+            },
+            &counter,
+            &mut data,
+        )
+        .unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(seen[0], counter);
+        assert_eq!(seen[1][0], 1);
+        assert!(seen[1][1..].iter().all(|&b| b == 0));
+        assert_eq!(&data[..AES_BLOCK_SIZE], &counter[..]);
+    }
+}
@@ -1,8 +1,8 @@
 //! Tegra210 KFUSE implementation.
 
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{Mmio, ReadOnly, VolatileStorage};
 
-use crate::clock::Clock;
+use crate::{clock::Clock, error, timer::wait_for, Error, Result};
 
 pub(crate) const KFUSE_BASE: u32 = 0x7000_FC00;
 
@@ -13,18 +13,21 @@ pub(crate) const KFUSE_KEYADDR_AUTOINC: u32 = (1 << 16);
 
 pub const KFUSE_NUM_WORDS: u32 = 144;
 
+/// The time in microseconds the KFUSE may take to read its contents.
+const READ_TIMEOUT: u32 = 10_000;
+
 /// Representation of the HDCP KFUSE registers.
 #[allow(non_snake_case)]
 #[repr(C)]
 pub struct KfuseRegisters {
     /// The `KFUSE_STATE_0` register.
-    pub STATE: Mmio<u32>,
+    pub STATE: ReadOnly<u32>,
     /// The `KFUSE_ERRCOUNT_0` register.
-    pub ERRCOUNT: Mmio<u32>,
+    pub ERRCOUNT: ReadOnly<u32>,
     /// The `KFUSE_KEYADDR_0` register.
     pub KEYADDR: Mmio<u32>,
     /// The `KFUSE_KEYS_0` register.
-    pub KEYS: Mmio<u32>,
+    pub KEYS: ReadOnly<u32>,
     _unk: [Mmio<u32>; 25],
     /// The `KFUSE_PD_0` register.
     pub PD: Mmio<u32>,
@@ -38,18 +41,25 @@ impl VolatileStorage for KfuseRegisters {
 
 /// Reads the KFuse contents into a buffer.
 #[optimize(size)]
-pub fn read(buffer: &mut [u32]) -> Result<(), ()> {
+pub fn read(buffer: &mut [u32]) -> Result<()> {
     let registers = unsafe { KfuseRegisters::get() };
+    let mut clock = Clock::KFUSE;
 
-    Clock::KFUSE.enable();
+    clock.enable();
 
-    while (registers.STATE.read() & KFUSE_STATE_DONE) == 0 {
-        // Wait.
+    if let Err(error) = wait_for(
+        || (registers.STATE.read() & KFUSE_STATE_DONE) != 0,
+        READ_TIMEOUT,
+    ) {
+        error!("KFuse didn't finish reading");
+        clock.disable();
+        return Err(error);
     }
 
     if (registers.STATE.read() & KFUSE_STATE_CRCPASS) == 0 {
-        Clock::KFUSE.disable();
-        return Err(());
+        error!("KFuse CRC check failed");
+        clock.disable();
+        return Err(Error::Fuse);
     }
 
     registers.KEYADDR.write(KFUSE_KEYADDR_AUTOINC);
@@ -58,7 +68,7 @@ pub fn read(buffer: &mut [u32]) -> Result<(), ()> {
         buffer[i as usize] = registers.KEYS.read();
     }
 
-    Clock::KFUSE.disable();
+    clock.disable();
 
     Ok(())
 }
@@ -4,9 +4,13 @@
 //! If you decide to use it for other Tegra210 platforms, use
 //! at own risk.
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![feature(asm)]
 #![feature(const_fn)]
 #![feature(optimize_attribute)]
+#![recursion_limit = "256"]
+#![cfg_attr(all(feature = "heap", not(feature = "std")), feature(alloc_error_handler))]
+#![cfg_attr(feature = "gdbstub", feature(global_asm))]
 
 #[macro_use]
 extern crate bitflags;
@@ -18,25 +22,76 @@ extern crate mirage_mmio;
 
 extern crate paste;
 
+pub use error::{Error, Result};
+
 pub mod apb_misc;
+pub mod actmon;
+pub mod apbdma;
+pub mod arch;
+pub mod audio;
+pub mod backup;
+pub mod block;
+pub mod board;
+pub mod boot;
+pub mod bootctl;
 pub mod button;
+pub mod chainload;
+pub mod checksum;
 pub mod clock;
 pub mod cluster;
+pub mod crashlog;
+pub mod ct;
 pub mod display;
+pub mod dpaux;
+pub mod dma;
+pub mod error;
+pub mod falcon;
+pub mod fan;
+pub mod firmware;
 pub mod fuse;
+#[cfg(all(feature = "gdbstub", target_arch = "arm"))]
+pub mod gdbstub;
 pub mod gpio;
+pub mod gpt;
+pub mod gpu;
+#[cfg(feature = "hal")]
+pub mod hal;
+#[cfg(feature = "heap")]
+pub mod heap;
+pub mod host1x;
+pub mod hw_rev;
 pub mod i2c;
+pub mod ini;
+pub mod keys;
 pub mod kfuse;
+pub mod log;
+pub mod lz4;
 pub mod mc;
+pub mod memory_map;
+pub mod monitor;
+pub mod payload;
+pub mod peripherals;
 pub mod pinmux;
 pub mod pmc;
 pub mod power;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod retry;
 pub mod rtc;
+pub mod sched;
 pub mod sdmmc;
 pub mod sdram;
 pub mod se;
+pub mod security;
+#[cfg(feature = "std")]
+pub mod sim;
+pub mod sor;
 pub mod sysctr0;
 pub mod sysreg;
+pub mod thermal;
 pub mod timer;
+pub mod touch;
 pub mod tsec;
 pub mod uart;
+pub mod usb;
+pub mod xusb_padctl;
@@ -0,0 +1,51 @@
+use core::fmt::{self, Write};
+
+use super::{Level, Sink};
+use crate::display::Writer;
+
+/// Foreground colors of the levels on screen.
+const LEVEL_COLORS: [u32; 5] = [
+    0xFF00_00FF, // Error
+    0xFF00_CCFF, // Warn
+    0xFFCC_CCCC, // Info
+    0xFF99_9999, // Debug
+    0xFF66_6666, // Trace
+];
+
+/// Writes to the framebuffer, skipping characters the font can't render.
+struct Printable<'a>(&'a mut Writer);
+
+impl Write for Printable<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.0.write_char(c).ok();
+        }
+
+        Ok(())
+    }
+}
+
+/// A sink that draws log messages onto the framebuffer.
+///
+/// The display must have been initialized before messages are logged.
+pub struct ConsoleSink {
+    writer: Writer,
+}
+
+impl ConsoleSink {
+    /// Creates a new sink that starts drawing at the top of the screen.
+    pub const fn new() -> Self {
+        ConsoleSink {
+            writer: Writer::new(),
+        }
+    }
+}
+
+impl Sink for ConsoleSink {
+    fn write(&mut self, level: Level, args: fmt::Arguments<'_>) {
+        self.writer
+            .set_foreground_color(LEVEL_COLORS[level as usize - 1]);
+
+        write!(Printable(&mut self.writer), "[{}] {}\n", level, args).ok();
+    }
+}
@@ -0,0 +1,266 @@
+//! Logging facade with pluggable sinks.
+//!
+//! # Description
+//!
+//! Drivers report what they are doing through the [`error!`], [`warn!`],
+//! [`info!`], [`debug!`] and [`trace!`] macros. Messages are dispatched to
+//! every registered [`Sink`], which can be a serial console, the screen or
+//! a buffer in memory that survives a reboot.
+//!
+//! # Implementation
+//!
+//! - Up to [`MAX_SINKS`] sinks can be registered through [`add_sink`].
+//! Each message is written to all of them, prefixed with its [`Level`].
+//!
+//! - Messages above the level set through [`set_level`] are discarded
+//! before they are formatted. The default level is [`Level::Info`].
+//!
+//! - [`UartSink`] writes messages to a UART, [`ConsoleSink`] draws them
+//! onto the framebuffer and [`RingBuffer`] keeps the latest messages in
+//! IRAM, where they can be retrieved after a reboot.
+//!
+//! - With the `log` feature, [`init_log_crate`] installs a logger for the
+//! `log` crate that forwards records to the registered sinks.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{info, log::{self, UartSink}, peripherals::Peripherals};
+//!
+//! static mut SINK: Option<UartSink> = None;
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
+//!     unsafe {
+//!         SINK = Some(UartSink::new(peripherals.uart_a));
+//!         log::add_sink(SINK.as_mut().unwrap()).unwrap();
+//!     }
+//!
+//!     info!("Hello from {}!", "Mirage");
+//! }
+//! ```
+//!
+//! [`error!`]: ../macro.error.html
+//! [`warn!`]: ../macro.warn.html
+//! [`info!`]: ../macro.info.html
+//! [`debug!`]: ../macro.debug.html
+//! [`trace!`]: ../macro.trace.html
+//! [`Sink`]: trait.Sink.html
+//! [`MAX_SINKS`]: constant.MAX_SINKS.html
+//! [`add_sink`]: fn.add_sink.html
+//! [`Level`]: enum.Level.html
+//! [`set_level`]: fn.set_level.html
+//! [`Level::Info`]: enum.Level.html#variant.Info
+//! [`UartSink`]: struct.UartSink.html
+//! [`ConsoleSink`]: struct.ConsoleSink.html
+//! [`RingBuffer`]: struct.RingBuffer.html
+//! [`init_log_crate`]: fn.init_log_crate.html
+
+use core::fmt;
+
+use crate::{Error, Result};
+
+pub use console::*;
+pub use ring::*;
+pub use uart::*;
+
+mod console;
+mod ring;
+mod uart;
+
+/// The maximum number of sinks that can be registered at once.
+pub const MAX_SINKS: usize = 4;
+
+/// The severity of a log message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Operations that failed.
+    Error = 1,
+    /// Unexpected conditions that could be recovered from.
+    Warn,
+    /// Progress of the boot process.
+    Info,
+    /// Details that help to diagnose problems.
+    Debug,
+    /// Very verbose details, e.g. about every single transfer.
+    Trace,
+}
+
+impl Level {
+    /// Gets the name of the level as it appears in front of messages.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A destination for log messages.
+pub trait Sink {
+    /// Writes a single log message.
+    ///
+    /// The message is not terminated by a line break.
+    fn write(&mut self, level: Level, args: fmt::Arguments<'_>);
+}
+
+/// A handle to a registered sink, used to remove it again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SinkHandle(usize);
+
+static mut SINKS: [Option<&'static mut dyn Sink>; MAX_SINKS] = [None, None, None, None];
+
+static mut LEVEL: Level = Level::Info;
+
+/// Registers a sink that receives all future log messages.
+///
+/// Fails with [`Error::Exhausted`] if all sink slots are in use.
+///
+/// [`Error::Exhausted`]: ../enum.Error.html#variant.Exhausted
+pub fn add_sink(sink: &'static mut dyn Sink) -> Result<SinkHandle> {
+    let sinks = unsafe { &mut SINKS };
+
+    for (index, slot) in sinks.iter_mut().enumerate() {
+        if slot.is_none() {
+            *slot = Some(sink);
+            return Ok(SinkHandle(index));
+        }
+    }
+
+    Err(Error::Exhausted)
+}
+
+/// Removes a previously registered sink.
+pub fn remove_sink(handle: SinkHandle) {
+    unsafe {
+        SINKS[handle.0] = None;
+    }
+}
+
+/// Sets the most verbose level of messages that are written to the sinks.
+pub fn set_level(level: Level) {
+    unsafe {
+        LEVEL = level;
+    }
+}
+
+/// Gets the most verbose level of messages that are written to the sinks.
+pub fn level() -> Level {
+    unsafe { LEVEL }
+}
+
+/// Whether messages of the given level are written to the sinks.
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+/// Writes a message to all registered sinks.
+///
+/// This is usually called through the logging macros.
+pub fn log(level: Level, args: fmt::Arguments<'_>) {
+    if !enabled(level) {
+        return;
+    }
+
+    let sinks = unsafe { &mut SINKS };
+
+    for sink in sinks.iter_mut().filter_map(|s| s.as_mut()) {
+        sink.write(level, args);
+    }
+}
+
+/// Logs a message at the given level.
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => ($crate::log::log($level, format_args!($($arg)*)));
+}
+
+/// Logs a message at the error level.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Error, $($arg)*));
+}
+
+/// Logs a message at the warning level.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Warn, $($arg)*));
+}
+
+/// Logs a message at the info level.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Info, $($arg)*));
+}
+
+/// Logs a message at the debug level.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Debug, $($arg)*));
+}
+
+/// Logs a message at the trace level.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Trace, $($arg)*));
+}
+
+/// A logger for the `log` crate that forwards records to the sinks.
+#[cfg(feature = "log")]
+struct Logger;
+
+#[cfg(feature = "log")]
+impl ::log::Log for Logger {
+    fn enabled(&self, metadata: &::log::Metadata<'_>) -> bool {
+        enabled(Level::from(metadata.level()))
+    }
+
+    fn log(&self, record: &::log::Record<'_>) {
+        log(
+            Level::from(record.level()),
+            format_args!("{}: {}", record.target(), record.args()),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+impl From<::log::Level> for Level {
+    fn from(level: ::log::Level) -> Self {
+        match level {
+            ::log::Level::Error => Level::Error,
+            ::log::Level::Warn => Level::Warn,
+            ::log::Level::Info => Level::Info,
+            ::log::Level::Debug => Level::Debug,
+            ::log::Level::Trace => Level::Trace,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+static LOGGER: Logger = Logger;
+
+/// Installs a logger for the `log` crate, which forwards all records
+/// to the registered sinks.
+///
+/// Filtering is left to [`set_level`], so all records are passed on.
+///
+/// [`set_level`]: fn.set_level.html
+#[cfg(feature = "log")]
+pub fn init_log_crate() -> core::result::Result<(), ::log::SetLoggerError> {
+    ::log::set_logger(&LOGGER)?;
+    ::log::set_max_level(::log::LevelFilter::Trace);
+
+    Ok(())
+}
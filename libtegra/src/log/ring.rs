@@ -0,0 +1,202 @@
+use core::{
+    fmt::{self, Write},
+    mem::size_of,
+    ptr,
+};
+
+use super::{Level, Sink};
+
+/// The address of the log buffer in upper IRAM.
+pub const LOG_BUFFER_ADDRESS: u32 = 0x4003_D000;
+
+/// The size of the log buffer in bytes, including its header.
+pub const LOG_BUFFER_SIZE: u32 = 0x2000;
+
+/// Identifies an initialized log buffer ("MLOG").
+const RING_MAGIC: u32 = 0x474F_4C4D;
+
+/// The size of the buffer header.
+const HEADER_SIZE: usize = size_of::<Header>();
+
+/// The header in front of the buffered messages.
+#[repr(C)]
+struct Header {
+    /// Always [`RING_MAGIC`] for an initialized buffer.
+    magic: u32,
+    /// The capacity of the buffer in bytes.
+    capacity: u32,
+    /// The total number of bytes ever written.
+    written: u32,
+}
+
+/// A sink that keeps the latest log messages in a buffer in memory.
+///
+/// IRAM keeps its contents across a reboot, so the messages of a failed
+/// boot attempt can be retrieved afterwards through [`RingBuffer::read`].
+///
+/// [`RingBuffer::read`]: struct.RingBuffer.html#method.read
+pub struct RingBuffer {
+    address: usize,
+    size: usize,
+}
+
+impl RingBuffer {
+    /// The log buffer in upper IRAM.
+    pub const IRAM: Self = RingBuffer {
+        address: LOG_BUFFER_ADDRESS as usize,
+        size: LOG_BUFFER_SIZE as usize,
+    };
+
+    /// Creates a ring buffer at the given location in memory.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for `size` bytes, aligned to 4 bytes
+    /// and not be used for anything else.
+    pub const unsafe fn new(address: usize, size: usize) -> Self {
+        RingBuffer { address, size }
+    }
+
+    fn header(&self) -> *mut Header {
+        self.address as *mut Header
+    }
+
+    fn data(&self) -> *mut u8 {
+        (self.address + HEADER_SIZE) as *mut u8
+    }
+
+    /// Gets the number of bytes the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.size - HEADER_SIZE
+    }
+
+    fn written(&self) -> usize {
+        unsafe { ptr::read_volatile(&(*self.header()).written) as usize }
+    }
+
+    /// Prepares the buffer for use.
+    ///
+    /// Messages left by a previous boot are retained if the buffer
+    /// is intact, in which case `true` is returned.
+    pub fn init(&mut self) -> bool {
+        let header = self.header();
+
+        let intact = unsafe {
+            ptr::read_volatile(&(*header).magic) == RING_MAGIC
+                && ptr::read_volatile(&(*header).capacity) as usize == self.capacity()
+                && self.written() < self.capacity() * 2
+        };
+
+        if !intact {
+            self.clear();
+        }
+
+        intact
+    }
+
+    /// Discards all messages in the buffer.
+    pub fn clear(&mut self) {
+        let header = self.header();
+
+        unsafe {
+            ptr::write_volatile(&mut (*header).magic, RING_MAGIC);
+            ptr::write_volatile(&mut (*header).capacity, self.capacity() as u32);
+            ptr::write_volatile(&mut (*header).written, 0);
+        }
+    }
+
+    /// Gets the number of bytes currently held by the buffer.
+    pub fn len(&self) -> usize {
+        self.written().min(self.capacity())
+    }
+
+    /// Whether the buffer holds no messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the latest messages that fit into the given buffer,
+    /// oldest first, and returns the number of bytes copied.
+    pub fn read(&self, buffer: &mut [u8]) -> usize {
+        let written = self.written();
+        let count = self.len().min(buffer.len());
+        let start = written - count;
+
+        for (i, byte) in buffer[..count].iter_mut().enumerate() {
+            let index = (start + i) % self.capacity();
+            *byte = unsafe { ptr::read_volatile(self.data().add(index)) };
+        }
+
+        count
+    }
+
+    /// Appends bytes to the buffer, overwriting the oldest ones when full.
+    pub fn push(&mut self, bytes: &[u8]) {
+        let mut written = self.written();
+
+        for byte in bytes {
+            let index = written % self.capacity();
+            unsafe {
+                ptr::write_volatile(self.data().add(index), *byte);
+            }
+
+            // Wrap around at a multiple of the capacity to keep the index valid.
+            written = (written + 1) % (self.capacity() * 2);
+            if written == 0 {
+                written = self.capacity();
+            }
+        }
+
+        unsafe {
+            ptr::write_volatile(&mut (*self.header()).written, written as u32);
+        }
+    }
+}
+
+impl Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+impl Sink for RingBuffer {
+    fn write(&mut self, level: Level, args: fmt::Arguments<'_>) {
+        write!(self, "[{}] {}\n", level, args).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_latest_messages() {
+        let mut memory = [0u32; 8];
+        let mut ring = unsafe { RingBuffer::new(memory.as_mut_ptr() as usize, 32) };
+        assert_eq!(ring.capacity(), 20);
+
+        assert!(!ring.init());
+        ring.push(b"0123456789");
+        assert_eq!(ring.len(), 10);
+
+        ring.push(b"abcdefghijklmnop");
+        assert_eq!(ring.len(), 20);
+
+        let mut buffer = [0u8; 32];
+        let count = ring.read(&mut buffer);
+        assert_eq!(&buffer[..count], b"6789abcdefghijklmnop");
+
+        // Only the newest messages are returned if the buffer is too small.
+        let count = ring.read(&mut buffer[..4]);
+        assert_eq!(&buffer[..count], b"mnop");
+
+        // The contents survive a re-initialization.
+        assert!(ring.init());
+        assert_eq!(ring.len(), 20);
+
+        ring.clear();
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+use core::fmt::{self, Write};
+
+use super::{Level, Sink};
+use crate::uart::Uart;
+
+/// A sink that writes log messages to a serial console.
+///
+/// The UART must have been initialized before messages are logged.
+pub struct UartSink {
+    uart: Uart,
+}
+
+impl UartSink {
+    /// Creates a new sink that writes to the given UART.
+    pub const fn new(uart: Uart) -> Self {
+        UartSink { uart }
+    }
+}
+
+impl Sink for UartSink {
+    fn write(&mut self, level: Level, args: fmt::Arguments<'_>) {
+        write!(self.uart, "[{}] {}\r\n", level, args).ok();
+    }
+}
@@ -0,0 +1,182 @@
+//! LZ4 block decompression.
+//!
+//! # Description
+//!
+//! Reading from the SD card is one of the slowest parts of booting. Storing
+//! payloads and splash screens compressed cuts the amount of data to read,
+//! and LZ4 decompresses fast enough to make up for it even on the BPMP.
+//!
+//! # Implementation
+//!
+//! - [`decompress`] decodes a raw LZ4 block, as produced by `LZ4_compress`
+//! or `lz4 -B` without the frame. The LZ4 frame format with its headers and
+//! checksums isn't supported, as payloads carry their own hash.
+//!
+//! - Decoding works on slices only and never allocates. Every length and
+//! offset is checked against the buffers, so malformed input results in
+//! an error rather than reading or writing out of bounds.
+//!
+//! - The size of the decompressed data has to be known in advance, e.g.
+//! from the [`payload`] header, to provide a large enough output buffer.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::lz4;
+//!
+//! fn main() {
+//!     let block = [0x35, b'a', b'b', b'c', 0x03, 0x00, 0x10, b'!'];
+//!     let mut output = [0; 13];
+//!
+//!     assert_eq!(lz4::decompress(&block, &mut output), Ok(13));
+//!     assert_eq!(&output, b"abcabcabcabc!");
+//! }
+//! ```
+//!
+//! [`decompress`]: fn.decompress.html
+//! [`payload`]: ../payload/index.html
+
+use crate::{Error, Result};
+
+/// The length stored in a token nibble that is continued by extra bytes.
+const LENGTH_CONTINUED: usize = 0xF;
+
+/// The shortest possible match.
+const MIN_MATCH: usize = 4;
+
+/// Reads a byte from the input.
+fn read_byte(input: &[u8], position: &mut usize) -> Result<u8> {
+    let byte = *input.get(*position).ok_or(Error::InvalidData)?;
+    *position += 1;
+
+    Ok(byte)
+}
+
+/// Reads a length from the token nibble and the extra bytes following it.
+fn read_length(input: &[u8], position: &mut usize, nibble: u8) -> Result<usize> {
+    let mut length = nibble as usize;
+
+    if length == LENGTH_CONTINUED {
+        loop {
+            let byte = read_byte(input, position)?;
+            length = length
+                .checked_add(byte as usize)
+                .ok_or(Error::InvalidData)?;
+
+            if byte != 0xFF {
+                break;
+            }
+        }
+    }
+
+    Ok(length)
+}
+
+/// Decompresses an LZ4 block into `output` and returns the amount of bytes
+/// written.
+///
+/// Fails with [`Error::InvalidData`] if the block is malformed and with
+/// [`Error::InvalidArgument`] if it doesn't fit into `output`.
+///
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+pub fn decompress(input: &[u8], output: &mut [u8]) -> Result<usize> {
+    let mut position = 0;
+    let mut written = 0;
+
+    while position < input.len() {
+        let token = read_byte(input, &mut position)?;
+
+        // Copy the literals.
+        let literals = read_length(input, &mut position, token >> 4)?;
+        if literals > input.len() - position {
+            return Err(Error::InvalidData);
+        }
+        if literals > output.len() - written {
+            return Err(Error::InvalidArgument);
+        }
+
+        output[written..written + literals].copy_from_slice(&input[position..position + literals]);
+        position += literals;
+        written += literals;
+
+        // The last sequence consists of literals only.
+        if position == input.len() {
+            break;
+        }
+
+        // Copy the match from the output so far, which may overlap with
+        // the bytes it produces.
+        let offset = u16::from_le_bytes([
+            read_byte(input, &mut position)?,
+            read_byte(input, &mut position)?,
+        ]) as usize;
+        if offset == 0 || offset > written {
+            return Err(Error::InvalidData);
+        }
+
+        let length = read_length(input, &mut position, token & 0xF)? + MIN_MATCH;
+        if length > output.len() - written {
+            return Err(Error::InvalidArgument);
+        }
+
+        for index in written..written + length {
+            output[index] = output[index - offset];
+        }
+        written += length;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests decoding literals and overlapping matches.
+    #[test]
+    fn sequences() {
+        let block = [0x35, b'a', b'b', b'c', 0x03, 0x00, 0x50, b'x', b'y', b'z', b'0', b'1'];
+        let mut output = [0; 32];
+
+        assert_eq!(decompress(&block, &mut output), Ok(17));
+        assert_eq!(&output[..17], b"abcabcabcabcxyz01");
+    }
+
+    /// Tests lengths continued by extra bytes.
+    #[test]
+    fn long_lengths() {
+        let mut block = [b'l'; 2 + 20];
+        block[0] = 0xF0;
+        block[1] = 5;
+        let mut output = [0; 20];
+        assert_eq!(decompress(&block, &mut output), Ok(20));
+        assert!(output.iter().all(|&byte| byte == b'l'));
+
+        let block = [0x1F, b'a', 0x01, 0x00, 0xFF, 0x02, 0x10, b'b'];
+        let mut output = [0; 0x200];
+        assert_eq!(decompress(&block, &mut output), Ok(1 + 15 + 0xFF + 2 + 4 + 1));
+        assert!(output[..277].iter().all(|&byte| byte == b'a'));
+        assert_eq!(output[277], b'b');
+    }
+
+    /// Tests rejecting malformed blocks and short output buffers.
+    #[test]
+    fn invalid() {
+        let mut output = [0; 8];
+
+        assert_eq!(decompress(&[0x20, b'a'], &mut output), Err(Error::InvalidData));
+        assert_eq!(
+            decompress(&[0x10, b'a', 0x02, 0x00, 0x00], &mut output),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            decompress(&[0x10, b'a', 0x00, 0x00, 0x00], &mut output),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            decompress(&[0x15, b'a', 0x01, 0x00, 0x00], &mut output),
+            Err(Error::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Memory Controller security carveouts.
+//!
+//! # Description
+//!
+//! The MC provides five general-purpose security carveouts which restrict
+//! access to a region of DRAM to a configurable set of memory clients.
+//! Each carveout is described by its base address, its size in units of
+//! 128KiB, a bitmask of clients that are allowed to access it and a
+//! configuration word selecting the protection mode.
+//!
+//! Once a carveout has been locked, its configuration can no longer be
+//! changed until the next reset.
+//!
+//! # Implementation
+//!
+//! - [`Carveout::new`] creates a [`CarveoutConfig`] for one of the
+//! carveouts, which is then filled in through builder methods. All of
+//! them are `const fn`s, so layouts can be defined as constants.
+//!
+//! - [`Client`] represents a memory client, e.g. an engine like TSEC.
+//! Clients which issue requests under multiple MC client IDs grant all
+//! of them access at once.
+//!
+//! - [`apply`] writes a [`CarveoutConfig`] to the hardware.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::mc::{self, Carveout, CarveoutConfig, Client};
+//!
+//! const TSEC_CARVEOUT: CarveoutConfig = Carveout::new(4)
+//!     .base(0x8FE0_0000)
+//!     .size_128kb(0x10)
+//!     .allow_clients(&[Client::Tsec])
+//!     .locked();
+//!
+//! fn protect() {
+//!     mc::apply(&TSEC_CARVEOUT).unwrap();
+//! }
+//! ```
+//!
+//! [`Carveout::new`]: struct.Carveout.html#method.new
+//! [`CarveoutConfig`]: struct.CarveoutConfig.html
+//! [`Client`]: enum.Client.html
+//! [`apply`]: fn.apply.html
+
+use mirage_mmio::Mmio;
+
+use super::MC_BASE;
+use crate::{Error, Result};
+
+/// Base address of the registers of the first security carveout.
+const MC_SECURITY_CARVEOUT_BASE: u32 = MC_BASE + 0xC08;
+/// The distance between the registers of two consecutive carveouts.
+const MC_SECURITY_CARVEOUT_STRIDE: u32 = 0x50;
+
+/// The number of general-purpose security carveouts.
+pub const CARVEOUT_COUNT: u32 = 5;
+/// The granularity of carveout bases and sizes in bytes.
+pub const CARVEOUT_ALIGNMENT: u64 = 0x20000;
+
+/// Locks the carveout configuration until the next reset.
+const CFG0_LOCKED: u32 = 1 << 0;
+/// Enforces the carveout through TrustZone instead of the lock bit.
+const CFG0_TZ_SECURE: u32 = 1 << 1;
+/// Only applies the carveout to untranslated addresses.
+const CFG0_UNTRANSLATED_ONLY: u32 = 1 << 2;
+/// Shift of the per-level read access enable bits.
+const CFG0_READ_ACCESS_LEVEL_SHIFT: u32 = 3;
+/// Shift of the per-level write access enable bits.
+const CFG0_WRITE_ACCESS_LEVEL_SHIFT: u32 = 7;
+
+/// Representation of the registers of a single security carveout.
+#[allow(non_snake_case)]
+#[repr(C)]
+struct Registers {
+    pub CFG0: Mmio<u32>,
+    pub BOM: Mmio<u32>,
+    pub BOM_HI: Mmio<u32>,
+    pub SIZE_128KB: Mmio<u32>,
+    pub CLIENT_ACCESS: [Mmio<u32>; 5],
+    pub CLIENT_FORCE_INTERNAL_ACCESS: [Mmio<u32>; 5],
+}
+
+/// Memory clients which can be granted access to a carveout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Client {
+    /// The CPU complex.
+    Cpu,
+    /// The BPMP (AVP) coprocessor.
+    Bpmp,
+    /// The display controllers.
+    Display,
+    /// The host1x DMA engine.
+    Host1x,
+    /// The Security Engine.
+    Se,
+    /// The TSEC engine.
+    Tsec,
+    /// The GPU.
+    Gpu,
+    /// The NVDEC engine.
+    Nvdec,
+    /// The NVENC engine.
+    Nvenc,
+    /// The VIC engine.
+    Vic,
+    /// The SDMMC controllers.
+    Sdmmc,
+    /// The XUSB host and device controllers.
+    Xusb,
+}
+
+impl Client {
+    /// Gets the MC client IDs used by the client.
+    pub fn ids(&self) -> &'static [u8] {
+        match self {
+            Client::Cpu => &[0x27, 0x39],
+            Client::Bpmp => &[0x0F, 0x32],
+            Client::Display => &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x10, 0x11, 0x5A, 0x73],
+            Client::Host1x => &[0x16, 0x17, 0x36],
+            Client::Se => &[0x80, 0x81],
+            Client::Tsec => &[0x54, 0x55, 0x86, 0x87],
+            Client::Gpu => &[0x58, 0x59, 0x88, 0x89],
+            Client::Nvdec => &[0x78, 0x79],
+            Client::Nvenc => &[0x1C, 0x2B],
+            Client::Vic => &[0x6C, 0x6D],
+            Client::Sdmmc => &[0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67],
+            Client::Xusb => &[0x4A, 0x4B, 0x4C, 0x4D],
+        }
+    }
+}
+
+/// Computes the client access bitmasks for the given clients.
+fn client_masks(clients: &[Client]) -> [u32; 5] {
+    let mut masks = [0; 5];
+
+    for id in clients.iter().flat_map(|c| c.ids().iter()) {
+        masks[(id / 32) as usize] |= 1 << (id % 32);
+    }
+
+    masks
+}
+
+/// Representation of a general-purpose security carveout.
+pub struct Carveout;
+
+impl Carveout {
+    /// Starts building a configuration for the carveout with the given
+    /// ID, ranging from 1 to [`CARVEOUT_COUNT`].
+    ///
+    /// The carveout is empty, inaccessible to all clients and unlocked
+    /// by default.
+    ///
+    /// [`CARVEOUT_COUNT`]: constant.CARVEOUT_COUNT.html
+    pub const fn new(id: u32) -> CarveoutConfig {
+        CarveoutConfig {
+            id,
+            base: 0,
+            size_128kb: 0,
+            clients: &[],
+            force_internal: &[],
+            config: 0,
+        }
+    }
+}
+
+/// The configuration of a security carveout.
+#[derive(Clone, Copy, Debug)]
+pub struct CarveoutConfig {
+    id: u32,
+    base: u64,
+    size_128kb: u32,
+    clients: &'static [Client],
+    force_internal: &'static [Client],
+    config: u32,
+}
+
+impl CarveoutConfig {
+    /// Sets the physical base address of the carveout.
+    ///
+    /// The address must be aligned to [`CARVEOUT_ALIGNMENT`].
+    ///
+    /// [`CARVEOUT_ALIGNMENT`]: constant.CARVEOUT_ALIGNMENT.html
+    pub const fn base(mut self, address: u64) -> Self {
+        self.base = address;
+        self
+    }
+
+    /// Sets the size of the carveout in units of 128KiB.
+    pub const fn size_128kb(mut self, size: u32) -> Self {
+        self.size_128kb = size;
+        self
+    }
+
+    /// Sets the clients which are allowed to access the carveout,
+    /// replacing any previously set ones.
+    pub const fn allow_clients(mut self, clients: &'static [Client]) -> Self {
+        self.clients = clients;
+        self
+    }
+
+    /// Sets the clients whose accesses to the carveout are forced to be
+    /// treated as internal, replacing any previously set ones.
+    pub const fn force_internal_clients(mut self, clients: &'static [Client]) -> Self {
+        self.force_internal = clients;
+        self
+    }
+
+    /// Enforces the carveout through TrustZone.
+    pub const fn tz_secure(mut self) -> Self {
+        self.config |= CFG0_TZ_SECURE;
+        self
+    }
+
+    /// Only applies the carveout to untranslated addresses.
+    pub const fn untranslated_only(mut self) -> Self {
+        self.config |= CFG0_UNTRANSLATED_ONLY;
+        self
+    }
+
+    /// Allows reads from the given TrustZone access level (0-3).
+    pub const fn read_access_level(mut self, level: u32) -> Self {
+        self.config |= 1 << (CFG0_READ_ACCESS_LEVEL_SHIFT + (level & 3));
+        self
+    }
+
+    /// Allows writes from the given TrustZone access level (0-3).
+    pub const fn write_access_level(mut self, level: u32) -> Self {
+        self.config |= 1 << (CFG0_WRITE_ACCESS_LEVEL_SHIFT + (level & 3));
+        self
+    }
+
+    /// Sets additional raw bits in the `CFG0` register of the carveout.
+    pub const fn config(mut self, bits: u32) -> Self {
+        self.config |= bits;
+        self
+    }
+
+    /// Locks the carveout configuration once it has been applied.
+    pub const fn locked(mut self) -> Self {
+        self.config |= CFG0_LOCKED;
+        self
+    }
+
+    /// Gets the ID of the carveout.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Gets the physical base address of the carveout.
+    pub fn address(&self) -> u64 {
+        self.base
+    }
+
+    /// Gets the size of the carveout in bytes.
+    pub fn size(&self) -> u64 {
+        u64::from(self.size_128kb) * CARVEOUT_ALIGNMENT
+    }
+
+    /// Gets the raw value for the `CFG0` register of the carveout.
+    pub fn config_bits(&self) -> u32 {
+        self.config
+    }
+
+    /// Whether the configuration locks the carveout.
+    pub fn is_locked(&self) -> bool {
+        self.config & CFG0_LOCKED != 0
+    }
+}
+
+/// Gets the registers of the carveout with the given ID.
+fn registers(id: u32) -> Option<&'static Registers> {
+    if id == 0 || id > CARVEOUT_COUNT {
+        return None;
+    }
+
+    let address = MC_SECURITY_CARVEOUT_BASE + (id - 1) * MC_SECURITY_CARVEOUT_STRIDE;
+
+    Some(unsafe { &*(address as *const Registers) })
+}
+
+/// Whether the carveout with the given ID has been locked.
+pub fn is_carveout_locked(id: u32) -> bool {
+    registers(id).map_or(false, |r| r.CFG0.read() & CFG0_LOCKED != 0)
+}
+
+/// Writes the given carveout configuration to the Memory Controller.
+///
+/// Fails if the carveout ID is invalid, the base address is misaligned
+/// or the carveout has already been locked.
+pub fn apply(config: &CarveoutConfig) -> Result<()> {
+    let register_base = registers(config.id).ok_or(Error::InvalidArgument)?;
+
+    if config.base % CARVEOUT_ALIGNMENT != 0 {
+        return Err(Error::InvalidArgument);
+    }
+
+    if is_carveout_locked(config.id) {
+        return Err(Error::Mc);
+    }
+
+    let access = client_masks(config.clients);
+    let force_internal = client_masks(config.force_internal);
+
+    register_base.BOM.write(config.base as u32);
+    register_base.BOM_HI.write((config.base >> 32) as u32);
+    register_base.SIZE_128KB.write(config.size_128kb);
+
+    for (register, mask) in register_base.CLIENT_ACCESS.iter().zip(access.iter()) {
+        register.write(*mask);
+    }
+
+    for (register, mask) in register_base
+        .CLIENT_FORCE_INTERNAL_ACCESS
+        .iter()
+        .zip(force_internal.iter())
+    {
+        register.write(*mask);
+    }
+
+    // The configuration must be written last, as it may lock the carveout.
+    register_base.CFG0.write(config.config);
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Per-channel Memory Controller and External Memory Controller registers.
+//!
+//! # Description
+//!
+//! The Tegra X1 has two memory channels, each with its own MC and EMC.
+//! Writes to the regular MC and EMC apertures are broadcast to both
+//! channels, reads return the state of channel 0. Whether the second
+//! channel is actually in use depends on the SDRAM configuration, which
+//! enables the channels through `EMC_FBIO_CFG7`.
+//!
+//! Status like a pending timing update or a completed clock change has to
+//! be checked on every enabled channel, through the per-channel apertures.
+//!
+//! # Implementation
+//!
+//! - [`Channel::mc`] and [`Channel::emc`] get the [`McChannelRegisters`]
+//! and [`EmcChannelRegisters`] of a channel.
+//!
+//! - [`Channel::enabled`] iterates over the channels the EMC is configured
+//! for, [`Channel::enabled_in`] over the ones enabled in an `EMC_FBIO_CFG7`
+//! value which hasn't been written yet.
+//!
+//! [`Channel::mc`]: enum.Channel.html#method.mc
+//! [`Channel::emc`]: enum.Channel.html#method.emc
+//! [`McChannelRegisters`]: struct.McChannelRegisters.html
+//! [`EmcChannelRegisters`]: struct.EmcChannelRegisters.html
+//! [`Channel::enabled`]: enum.Channel.html#method.enabled
+//! [`Channel::enabled_in`]: enum.Channel.html#method.enabled_in
+
+use mirage_mmio::{register_block, Mmio};
+
+/// Base address for the MC registers of channel 0.
+pub(crate) const MC0_BASE: u32 = 0x7001_C000;
+
+/// Base address for the MC registers of channel 1.
+pub(crate) const MC1_BASE: u32 = 0x7001_D000;
+
+/// Base address for the EMC registers, writes are broadcast to both channels.
+pub(crate) const EMC_BASE: u32 = 0x7001_B000;
+
+/// Base address for the EMC registers of channel 0.
+pub(crate) const EMC0_BASE: u32 = 0x7001_E000;
+
+/// Base address for the EMC registers of channel 1.
+pub(crate) const EMC1_BASE: u32 = 0x7001_F000;
+
+/// Channel 0 is in use, in `EMC_FBIO_CFG7`.
+pub const EMC_FBIO_CFG7_CH0_ENABLE: u32 = 1 << 1;
+
+/// Channel 1 is in use, in `EMC_FBIO_CFG7`.
+pub const EMC_FBIO_CFG7_CH1_ENABLE: u32 = 1 << 2;
+
+/// A timing update is pending, in `EMC_EMC_STATUS`.
+pub const EMC_STATUS_TIMING_UPDATE_STALLED: u32 = 1 << 23;
+
+register_block! {
+    /// Representation of the MC registers of a single channel.
+    pub struct McChannelRegisters {
+        (0x000 => pub intstatus: Mmio<u32>),
+        (0x004 => pub intmask: Mmio<u32>),
+        (0x008 => pub err_status: Mmio<u32>),
+        (0x00C => pub err_adr: Mmio<u32>),
+        (0x010 => _reserved0),
+        (0x050 => pub emem_cfg: Mmio<u32>),
+        (0x054 => pub emem_adr_cfg: Mmio<u32>),
+        (0x058 => pub emem_adr_cfg_dev0: Mmio<u32>),
+        (0x05C => pub emem_adr_cfg_dev1: Mmio<u32>),
+        (0x060 => pub emem_adr_cfg_channel_mask: Mmio<u32>),
+        (0x064 => _reserved1),
+        (0x0FC => pub timing_control: Mmio<u32>),
+        (0x100 => @END),
+    }
+}
+
+register_block! {
+    /// Representation of the EMC registers of a single channel.
+    pub struct EmcChannelRegisters {
+        (0x000 => pub intstatus: Mmio<u32>),
+        (0x004 => pub intmask: Mmio<u32>),
+        (0x008 => pub dbg: Mmio<u32>),
+        (0x00C => pub cfg: Mmio<u32>),
+        (0x010 => _reserved0),
+        (0x028 => pub timing_control: Mmio<u32>),
+        (0x02C => _reserved1),
+        (0x2B4 => pub emc_status: Mmio<u32>),
+        (0x2B8 => _reserved2),
+        (0x2EC => pub zq_cal: Mmio<u32>),
+        (0x2F0 => _reserved3),
+        (0x584 => pub fbio_cfg7: Mmio<u32>),
+        (0x588 => @END),
+    }
+}
+
+/// The memory channels, each served by its own MC and EMC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Channel 0.
+    Ch0,
+    /// Channel 1.
+    Ch1,
+}
+
+impl Channel {
+    /// All channels.
+    pub const ALL: [Channel; 2] = [Channel::Ch0, Channel::Ch1];
+
+    /// Gets the MC registers of the channel.
+    pub fn mc(self) -> &'static McChannelRegisters {
+        let base = match self {
+            Channel::Ch0 => MC0_BASE,
+            Channel::Ch1 => MC1_BASE,
+        };
+
+        unsafe { &*(base as *const McChannelRegisters) }
+    }
+
+    /// Gets the EMC registers of the channel.
+    pub fn emc(self) -> &'static EmcChannelRegisters {
+        let base = match self {
+            Channel::Ch0 => EMC0_BASE,
+            Channel::Ch1 => EMC1_BASE,
+        };
+
+        unsafe { &*(base as *const EmcChannelRegisters) }
+    }
+
+    /// Gets the enable bit of the channel in `EMC_FBIO_CFG7`.
+    fn enable_mask(self) -> u32 {
+        match self {
+            Channel::Ch0 => EMC_FBIO_CFG7_CH0_ENABLE,
+            Channel::Ch1 => EMC_FBIO_CFG7_CH1_ENABLE,
+        }
+    }
+
+    /// Iterates over the channels enabled in an `EMC_FBIO_CFG7` value, e.g.
+    /// the one of the SDRAM parameters.
+    ///
+    /// A value without any channel bits yields channel 0, which the EMC
+    /// keeps running in that case.
+    pub fn enabled_in(fbio_cfg7: u32) -> impl Iterator<Item = Channel> {
+        let channel_mask = EMC_FBIO_CFG7_CH0_ENABLE | EMC_FBIO_CFG7_CH1_ENABLE;
+        let fbio_cfg7 = if fbio_cfg7 & channel_mask == 0 {
+            EMC_FBIO_CFG7_CH0_ENABLE
+        } else {
+            fbio_cfg7
+        };
+
+        Channel::ALL
+            .iter()
+            .copied()
+            .filter(move |channel| fbio_cfg7 & channel.enable_mask() != 0)
+    }
+
+    /// Iterates over the channels the EMC is currently configured for.
+    pub fn enabled() -> impl Iterator<Item = Channel> {
+        Channel::enabled_in(Channel::Ch0.emc().fbio_cfg7.read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the registers of both channels match the offsets of the TRM.
+    #[test]
+    fn layout() {
+        let address = |register: &Mmio<u32>| register as *const _ as u32;
+
+        assert_eq!(address(&Channel::Ch0.mc().timing_control), 0x7001_C0FC);
+        assert_eq!(address(&Channel::Ch1.mc().emem_adr_cfg), 0x7001_D054);
+        assert_eq!(address(&Channel::Ch0.emc().emc_status), 0x7001_E2B4);
+        assert_eq!(address(&Channel::Ch1.emc().fbio_cfg7), 0x7001_F584);
+    }
+
+    /// Tests decoding the enabled channels.
+    #[test]
+    fn enabled_channels() {
+        let mut channels = Channel::enabled_in(0x6);
+        assert_eq!(channels.next(), Some(Channel::Ch0));
+        assert_eq!(channels.next(), Some(Channel::Ch1));
+        assert_eq!(channels.next(), None);
+
+        let mut channels = Channel::enabled_in(EMC_FBIO_CFG7_CH0_ENABLE);
+        assert_eq!(channels.next(), Some(Channel::Ch0));
+        assert_eq!(channels.next(), None);
+
+        let mut channels = Channel::enabled_in(0);
+        assert_eq!(channels.next(), Some(Channel::Ch0));
+        assert_eq!(channels.next(), None);
+    }
+}
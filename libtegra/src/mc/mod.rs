@@ -10,14 +10,77 @@
 //! bandwidth for DDR3L, LPDDR3, and LPDDR4 SDRAMs. The external
 //! memory controller (EMC) communicates with external DDR3L,
 //! LPDDR3, and LPDDR4 devices.
+//!
+//! # Implementation
+//!
+//! - [`config_carveout`] and [`config_carveout_finalize`] set up the
+//! default carveout layout used by Mirage.
+//!
+//! - Custom layouts of the general-purpose security carveouts can be
+//! described through [`CarveoutConfig`] and written using [`apply`].
+//!
+//! - [`enable_ahb_redirect_window`] redirects AHB accesses to a custom
+//! window. [`AhbRedirect`] does the same for the lifetime of a guard and
+//! restores the previous configuration afterwards.
+//!
+//! - [`Channel`] gives access to the MC and EMC registers of a single
+//! channel, which is needed to check the status of both channels.
+//!
+//! [`config_carveout`]: fn.config_carveout.html
+//! [`config_carveout_finalize`]: fn.config_carveout_finalize.html
+//! [`CarveoutConfig`]: struct.CarveoutConfig.html
+//! [`apply`]: fn.apply.html
+//! [`enable_ahb_redirect_window`]: fn.enable_ahb_redirect_window.html
+//! [`AhbRedirect`]: struct.AhbRedirect.html
+//! [`Channel`]: enum.Channel.html
 
 use mirage_mmio::{Mmio, VolatileStorage};
 
+pub use carveout::*;
+pub use channel::*;
+
 use crate::{clock::Car, timer::usleep};
 
+mod carveout;
+mod channel;
+
 /// Base address for the MC registers.
 pub(crate) const MC_BASE: u32 = 0x7001_9000;
 
+/// The default configuration of security carveout 1, left unused.
+const CARVEOUT1: CarveoutConfig = Carveout::new(1)
+    .tz_secure()
+    .untranslated_only()
+    .config(0x400_0000);
+
+/// The default configuration of security carveout 2, reserved for the GPU.
+const CARVEOUT2: CarveoutConfig = Carveout::new(2)
+    .base(0x8002_0000)
+    .size_128kb(2)
+    .allow_clients(&[Client::Gpu])
+    .config(0x440_167E);
+
+/// The default configuration of security carveout 3, reserved for the GPU.
+const CARVEOUT3: CarveoutConfig = Carveout::new(3)
+    .allow_clients(&[Client::Gpu])
+    .config(0x440_1E7E);
+
+/// The default configuration of security carveout 4, locked as unused.
+const CARVEOUT4: CarveoutConfig = Carveout::new(4)
+    .tz_secure()
+    .untranslated_only()
+    .read_access_level(0)
+    .write_access_level(0)
+    .locked();
+
+/// The default configuration of security carveout 5, locked as unused.
+const CARVEOUT5: CarveoutConfig = Carveout::new(5)
+    .tz_secure()
+    .untranslated_only()
+    .read_access_level(0)
+    .write_access_level(0)
+    .locked();
+
 pub fn config_tsec_carveout(bom: u32, size_mb: u32, lock: bool) {
     let sec_carveout_bom = unsafe { &*((MC_BASE + 0x670) as *const Mmio<u32>) };
 
@@ -61,172 +124,6 @@ pub fn config_carveout() {
     let mts_carveout_reg_ctrl =
         unsafe { &*((MC_BASE + 0x9ac) as *const Mmio<u32>) };
 
-    let security_carveout1_bom =
-        unsafe { &*((MC_BASE + 0xC0C) as *const Mmio<u32>) };
-
-    let security_carveout1_bom_hi =
-        unsafe { &*((MC_BASE + 0xC10) as *const Mmio<u32>) };
-
-    let security_carveout1_size_128kb =
-        unsafe { &*((MC_BASE + 0xC14) as *const Mmio<u32>) };
-
-    let security_carveout1_client_access0 =
-        unsafe { &*((MC_BASE + 0xC18) as *const Mmio<u32>) };
-
-    let security_carveout1_client_access1 =
-        unsafe { &*((MC_BASE + 0xC1C) as *const Mmio<u32>) };
-
-    let security_carveout1_client_access2 =
-        unsafe { &*((MC_BASE + 0xC20) as *const Mmio<u32>) };
-
-    let security_carveout1_client_access3 =
-        unsafe { &*((MC_BASE + 0xC24) as *const Mmio<u32>) };
-
-    let security_carveout1_client_access4 =
-        unsafe { &*((MC_BASE + 0xC28) as *const Mmio<u32>) };
-
-    let security_carveout1_client_force_internal_access0 =
-        unsafe { &*((MC_BASE + 0xC2C) as *const Mmio<u32>) };
-
-    let security_carveout1_client_force_internal_access1 =
-        unsafe { &*((MC_BASE + 0xC30) as *const Mmio<u32>) };
-
-    let security_carveout1_client_force_internal_access2 =
-        unsafe { &*((MC_BASE + 0xC34) as *const Mmio<u32>) };
-
-    let security_carveout1_client_force_internal_access3 =
-        unsafe { &*((MC_BASE + 0xC38) as *const Mmio<u32>) };
-
-    let security_carveout1_client_force_internal_access4 =
-        unsafe { &*((MC_BASE + 0xC3C) as *const Mmio<u32>) };
-
-    let security_carveout1_cfg0 =
-        unsafe { &*((MC_BASE + 0xC08) as *const Mmio<u32>) };
-
-    let security_carveout3_bom =
-        unsafe { &*((MC_BASE + 0xCAC) as *const Mmio<u32>) };
-
-    let security_carveout3_bom_hi =
-        unsafe { &*((MC_BASE + 0xCB0) as *const Mmio<u32>) };
-
-    let security_carveout3_size_128kb =
-        unsafe { &*((MC_BASE + 0xCB4) as *const Mmio<u32>) };
-
-    let security_carveout3_client_access0 =
-        unsafe { &*((MC_BASE + 0xCB8) as *const Mmio<u32>) };
-
-    let security_carveout3_client_access1 =
-        unsafe { &*((MC_BASE + 0xCBC) as *const Mmio<u32>) };
-
-    let security_carveout3_client_access2 =
-        unsafe { &*((MC_BASE + 0xCC0) as *const Mmio<u32>) };
-
-    let security_carveout3_client_access3 =
-        unsafe { &*((MC_BASE + 0xCC4) as *const Mmio<u32>) };
-
-    let security_carveout3_client_access4 =
-        unsafe { &*((MC_BASE + 0xCC8) as *const Mmio<u32>) };
-
-    let security_carveout3_client_force_internal_access0 =
-        unsafe { &*((MC_BASE + 0xCCC) as *const Mmio<u32>) };
-
-    let security_carveout3_client_force_internal_access1 =
-        unsafe { &*((MC_BASE + 0xCD0) as *const Mmio<u32>) };
-
-    let security_carveout3_client_force_internal_access2 =
-        unsafe { &*((MC_BASE + 0xCD4) as *const Mmio<u32>) };
-
-    let security_carveout3_client_force_internal_access3 =
-        unsafe { &*((MC_BASE + 0xCD8) as *const Mmio<u32>) };
-
-    let security_carveout3_client_force_internal_access4 =
-        unsafe { &*((MC_BASE + 0xCDC) as *const Mmio<u32>) };
-
-    let security_carveout3_cfg0 =
-        unsafe { &*((MC_BASE + 0xCA8) as *const Mmio<u32>) };
-
-    let security_carveout4_bom = unsafe { &*((MC_BASE + 0xCFC) as *const Mmio<u32>) };
-
-    let security_carveout4_bom_hi =
-        unsafe { &*((MC_BASE + 0xD00) as *const Mmio<u32>) };
-
-    let security_carveout4_size_128kb =
-        unsafe { &*((MC_BASE + 0xD04) as *const Mmio<u32>) };
-
-    let security_carveout4_client_access0 =
-        unsafe { &*((MC_BASE + 0xD08) as *const Mmio<u32>) };
-
-    let security_carveout4_client_access1 =
-        unsafe { &*((MC_BASE + 0xD0C) as *const Mmio<u32>) };
-
-    let security_carveout4_client_access2 =
-        unsafe { &*((MC_BASE + 0xD10) as *const Mmio<u32>) };
-
-    let security_carveout4_client_access3 =
-        unsafe { &*((MC_BASE + 0xD14) as *const Mmio<u32>) };
-
-    let security_carveout4_client_access4 =
-        unsafe { &*((MC_BASE + 0xD18) as *const Mmio<u32>) };
-
-    let security_carveout4_client_force_internal_access0 =
-        unsafe { &*((MC_BASE + 0xD1C) as *const Mmio<u32>) };
-
-    let security_carveout4_client_force_internal_access1 =
-        unsafe { &*((MC_BASE + 0xD20) as *const Mmio<u32>) };
-
-    let security_carveout4_client_force_internal_access2 =
-        unsafe { &*((MC_BASE + 0xD24) as *const Mmio<u32>) };
-
-    let security_carveout4_client_force_internal_access3 =
-        unsafe { &*((MC_BASE + 0xD28) as *const Mmio<u32>) };
-
-    let security_carveout4_client_force_internal_access4 =
-        unsafe { &*((MC_BASE + 0xD2C) as *const Mmio<u32>) };
-
-    let security_carveout4_cfg0 =
-        unsafe { &*((MC_BASE + 0xCF8) as *const Mmio<u32>) };
-
-    let security_carveout5_bom = unsafe { &*((MC_BASE + 0xD4C) as *const Mmio<u32>) };
-
-    let security_carveout5_bom_hi =
-        unsafe { &*((MC_BASE + 0xD50) as *const Mmio<u32>) };
-
-    let security_carveout5_size_128kb =
-        unsafe { &*((MC_BASE + 0xD54) as *const Mmio<u32>) };
-
-    let security_carveout5_client_access0 =
-        unsafe { &*((MC_BASE + 0xD58) as *const Mmio<u32>) };
-
-    let security_carveout5_client_access1 =
-        unsafe { &*((MC_BASE + 0xD5C) as *const Mmio<u32>) };
-
-    let security_carveout5_client_access2 =
-        unsafe { &*((MC_BASE + 0xD60) as *const Mmio<u32>) };
-
-    let security_carveout5_client_access3 =
-        unsafe { &*((MC_BASE + 0xD64) as *const Mmio<u32>) };
-
-    let security_carveout5_client_access4 =
-        unsafe { &*((MC_BASE + 0xD68) as *const Mmio<u32>) };
-
-    let security_carveout5_client_force_internal_access0 =
-        unsafe { &*((MC_BASE + 0xD6C) as *const Mmio<u32>) };
-
-    let security_carveout5_client_force_internal_access1 =
-        unsafe { &*((MC_BASE + 0xD70) as *const Mmio<u32>) };
-
-    let security_carveout5_client_force_internal_access2 =
-        unsafe { &*((MC_BASE + 0xD74) as *const Mmio<u32>) };
-
-    let security_carveout5_client_force_internal_access3 =
-        unsafe { &*((MC_BASE + 0xD78) as *const Mmio<u32>) };
-
-    let security_carveout5_client_force_internal_access4 =
-        unsafe { &*((MC_BASE + 0xD7C) as *const Mmio<u32>) };
-
-    let security_carveout5_cfg0 =
-        unsafe { &*((MC_BASE + 0xD48) as *const Mmio<u32>) };
-
     unsafe {
         (*(0x8005_FFFC as *const Mmio<u32>)).write(0xC0ED_BBCC);
     }
@@ -244,149 +141,119 @@ pub fn config_carveout() {
     mts_carveout_adr_hi.write(0);
     mts_carveout_reg_ctrl.write(1);
 
-    security_carveout1_bom.write(0);
-    security_carveout1_bom_hi.write(0);
-    security_carveout1_size_128kb.write(0);
-    security_carveout1_client_access0.write(0);
-    security_carveout1_client_access1.write(0);
-    security_carveout1_client_access2.write(0);
-    security_carveout1_client_access3.write(0);
-    security_carveout1_client_access4.write(0);
-    security_carveout1_client_force_internal_access0.write(0);
-    security_carveout1_client_force_internal_access1.write(0);
-    security_carveout1_client_force_internal_access2.write(0);
-    security_carveout1_client_force_internal_access3.write(0);
-    security_carveout1_client_force_internal_access4.write(0);
-    security_carveout1_cfg0.write(0x4000006);
-
-    security_carveout3_bom.write(0);
-    security_carveout3_bom_hi.write(0);
-    security_carveout3_size_128kb.write(0);
-    security_carveout3_client_access0.write(0);
-    security_carveout3_client_access1.write(0);
-    security_carveout3_client_access2.write(0x3000000);
-    security_carveout3_client_access3.write(0);
-    security_carveout3_client_access4.write(0x300);
-    security_carveout3_client_force_internal_access0.write(0);
-    security_carveout3_client_force_internal_access1.write(0);
-    security_carveout3_client_force_internal_access2.write(0);
-    security_carveout3_client_force_internal_access3.write(0);
-    security_carveout3_client_force_internal_access4.write(0);
-    security_carveout3_cfg0.write(0x4401E7E);
-
-    security_carveout4_bom.write(0);
-    security_carveout4_bom_hi.write(0);
-    security_carveout4_size_128kb.write(0);
-    security_carveout4_client_access0.write(0);
-    security_carveout4_client_access1.write(0);
-    security_carveout4_client_access2.write(0);
-    security_carveout4_client_access3.write(0);
-    security_carveout4_client_access4.write(0);
-    security_carveout4_client_force_internal_access0.write(0);
-    security_carveout4_client_force_internal_access1.write(0);
-    security_carveout4_client_force_internal_access2.write(0);
-    security_carveout4_client_force_internal_access3.write(0);
-    security_carveout4_client_force_internal_access4.write(0);
-    security_carveout4_cfg0.write(0x8F);
-
-    security_carveout5_bom.write(0);
-    security_carveout5_bom_hi.write(0);
-    security_carveout5_size_128kb.write(0);
-    security_carveout5_client_access0.write(0);
-    security_carveout5_client_access1.write(0);
-    security_carveout5_client_access2.write(0);
-    security_carveout5_client_access3.write(0);
-    security_carveout5_client_access4.write(0);
-    security_carveout5_client_force_internal_access0.write(0);
-    security_carveout5_client_force_internal_access1.write(0);
-    security_carveout5_client_force_internal_access2.write(0);
-    security_carveout5_client_force_internal_access3.write(0);
-    security_carveout5_client_force_internal_access4.write(0);
-    security_carveout5_cfg0.write(0x8F);
+    apply(&CARVEOUT1).ok();
+    apply(&CARVEOUT3).ok();
+    apply(&CARVEOUT4).ok();
+    apply(&CARVEOUT5).ok();
 }
 
 pub fn config_carveout_finalize() {
-    let security_carveout2_bom = unsafe { &*((MC_BASE + 0xC5C) as *const Mmio<u32>) };
+    apply(&CARVEOUT2).ok();
+}
 
-    let security_carveout2_bom_hi =
-        unsafe { &*((MC_BASE + 0xC60) as *const Mmio<u32>) };
+/// The default AHB redirect window, covering IRAM.
+pub const IRAM_REDIRECT_WINDOW: RedirectWindow = RedirectWindow {
+    start: 0x4000_0000,
+    end: 0x4003_F000,
+};
+
+/// Mask of the level 2 clock gate override for the AHB redirect.
+const AHB_REDIRECT_CLK_GATE_OVRD: u32 = 1 << 19;
+
+/// A range of physical addresses which AHB accesses are redirected to.
+///
+/// Both addresses are aligned down to 4KiB by the hardware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RedirectWindow {
+    /// The start address of the window.
+    pub start: u32,
+    /// The end address of the window.
+    pub end: u32,
+}
 
-    let security_carveout2_size_128kb =
-        unsafe { &*((MC_BASE + 0xC64) as *const Mmio<u32>) };
+/// Gets the IRAM_BOM and IRAM_TOM registers.
+fn iram_registers() -> (&'static Mmio<u32>, &'static Mmio<u32>) {
+    let iram_bom = unsafe { &*((MC_BASE + 0x65C) as *const Mmio<u32>) };
 
-    let security_carveout2_client_access0 =
-        unsafe { &*((MC_BASE + 0xC68) as *const Mmio<u32>) };
+    let iram_tom = unsafe { &*((MC_BASE + 0x660) as *const Mmio<u32>) };
 
-    let security_carveout2_client_access1 =
-        unsafe { &*((MC_BASE + 0xC6C) as *const Mmio<u32>) };
+    (iram_bom, iram_tom)
+}
 
-    let security_carveout2_client_access2 =
-        unsafe { &*((MC_BASE + 0xC70) as *const Mmio<u32>) };
+/// Enables the AHB redirect for the default IRAM window.
+pub fn enable_ahb_redirect() {
+    enable_ahb_redirect_window(IRAM_REDIRECT_WINDOW);
+}
 
-    let security_carveout2_client_access3 =
-        unsafe { &*((MC_BASE + 0xC74) as *const Mmio<u32>) };
+/// Enables the AHB redirect for the given window.
+pub fn enable_ahb_redirect_window(window: RedirectWindow) {
+    let (iram_bom, iram_tom) = iram_registers();
 
-    let security_carveout2_client_access4 =
-        unsafe { &*((MC_BASE + 0xC78) as *const Mmio<u32>) };
+    let car = unsafe { Car::get() };
 
-    let security_carveout2_client_force_internal_access0 =
-        unsafe { &*((MC_BASE + 0xC7C) as *const Mmio<u32>) };
+    car.lvl2_clk_gate_ovrd
+        .write(car.lvl2_clk_gate_ovrd.read() | AHB_REDIRECT_CLK_GATE_OVRD);
 
-    let security_carveout2_client_force_internal_access1 =
-        unsafe { &*((MC_BASE + 0xC80) as *const Mmio<u32>) };
+    iram_bom.write(window.start & !0xFFF);
+    iram_tom.write(window.end & !0xFFF);
+}
 
-    let security_carveout2_client_force_internal_access2 =
-        unsafe { &*((MC_BASE + 0xC84) as *const Mmio<u32>) };
+pub fn disable_ahb_redirect() {
+    let (iram_bom, iram_tom) = iram_registers();
 
-    let security_carveout2_client_force_internal_access3 =
-        unsafe { &*((MC_BASE + 0xC88) as *const Mmio<u32>) };
+    let car = unsafe { Car::get() };
 
-    let security_carveout2_client_force_internal_access4 =
-        unsafe { &*((MC_BASE + 0xC8C) as *const Mmio<u32>) };
+    iram_bom.write(0xFFFF_F000);
+    iram_tom.write(0);
 
-    let security_carveout2_cfg0 =
-        unsafe { &*((MC_BASE + 0xC58) as *const Mmio<u32>) };
+    car.lvl2_clk_gate_ovrd
+        .write(car.lvl2_clk_gate_ovrd.read() & !AHB_REDIRECT_CLK_GATE_OVRD);
+}
 
-    security_carveout2_bom.write(0x8002_0000);
-    security_carveout2_bom_hi.write(0);
-    security_carveout2_size_128kb.write(2);
-    security_carveout2_client_access0.write(0);
-    security_carveout2_client_access1.write(0);
-    security_carveout2_client_access2.write(0x3000000);
-    security_carveout2_client_access3.write(0);
-    security_carveout2_client_access4.write(0x300);
-    security_carveout2_client_force_internal_access0.write(0);
-    security_carveout2_client_force_internal_access1.write(0);
-    security_carveout2_client_force_internal_access2.write(0);
-    security_carveout2_client_force_internal_access3.write(0);
-    security_carveout2_client_force_internal_access4.write(0);
-    security_carveout2_cfg0.write(0x440167E);
+/// An active AHB redirect, which restores the previous redirect
+/// configuration when dropped.
+pub struct AhbRedirect {
+    bom: u32,
+    tom: u32,
+    clk_gate_ovrd: bool,
 }
 
-pub fn enable_ahb_redirect() {
-    let iram_bom = unsafe { &*((MC_BASE + 0x65C) as *const Mmio<u32>) };
+impl AhbRedirect {
+    /// Enables the AHB redirect for the given window until the returned
+    /// guard goes out of scope.
+    pub fn new(window: RedirectWindow) -> Self {
+        let (iram_bom, iram_tom) = iram_registers();
 
-    let iram_tom = unsafe { &*((MC_BASE + 0x660) as *const Mmio<u32>) };
+        let car = unsafe { Car::get() };
 
-    let car = unsafe { Car::get() };
+        let guard = AhbRedirect {
+            bom: iram_bom.read(),
+            tom: iram_tom.read(),
+            clk_gate_ovrd: car.lvl2_clk_gate_ovrd.read() & AHB_REDIRECT_CLK_GATE_OVRD != 0,
+        };
 
-    car.lvl2_clk_gate_ovrd.write((car.lvl2_clk_gate_ovrd.read() & 0xFFF7_FFFF) | 0x80000);
+        enable_ahb_redirect_window(window);
 
-    iram_bom.write(0x4000_0000);
-    iram_tom.write(0x4003_F000);
+        guard
+    }
 }
 
-pub fn disable_ahb_redirect() {
-    let iram_bom = unsafe { &*((MC_BASE + 0x65C) as *const Mmio<u32>) };
+impl Drop for AhbRedirect {
+    fn drop(&mut self) {
+        let (iram_bom, iram_tom) = iram_registers();
 
-    let iram_tom = unsafe { &*((MC_BASE + 0x660) as *const Mmio<u32>) };
+        let car = unsafe { Car::get() };
 
-    let car = unsafe { Car::get() };
+        iram_bom.write(self.bom);
+        iram_tom.write(self.tom);
 
-    iram_bom.write(0xFFFF_F000);
-    iram_tom.write(0);
-
-    car.lvl2_clk_gate_ovrd.write(car.lvl2_clk_gate_ovrd.read() & 0xFFF7_FFFF);
+        let ovrd = car.lvl2_clk_gate_ovrd.read() & !AHB_REDIRECT_CLK_GATE_OVRD;
+        if self.clk_gate_ovrd {
+            car.lvl2_clk_gate_ovrd.write(ovrd | AHB_REDIRECT_CLK_GATE_OVRD);
+        } else {
+            car.lvl2_clk_gate_ovrd.write(ovrd);
+        }
+    }
 }
 
 pub fn enable_mc() {
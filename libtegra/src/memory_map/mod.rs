@@ -0,0 +1,297 @@
+//! Validation of the physical memory layout.
+//!
+//! # Description
+//!
+//! Mirage places its stack, payloads, framebuffer and DMA buffers at fixed
+//! physical addresses in IRAM and DRAM. As these become configurable, a
+//! misconfiguration would silently corrupt memory at some later point,
+//! which is hard to debug.
+//!
+//! This module describes memory regions and validates that they neither
+//! overlap each other nor exceed the bounds of the physical memories, so
+//! such problems can be caught early during startup.
+//!
+//! # Implementation
+//!
+//! - A [`Region`] is a named range of physical memory.
+//!
+//! - [`IRAM`], [`TZRAM`] and [`DRAM`] describe the physical memories.
+//! [`IRAM_LOW`], [`PAYLOAD_STAGING`], [`STACK`] and [`RCM_PAYLOAD`] describe
+//! the IRAM layout of an RCM payload, where the BPMP starts execution at
+//! the start of [`RCM_PAYLOAD`] and the stack grows down from there.
+//!
+//! - [`is_in_iram`], [`is_in_tzram`] and [`is_in_dram`] classify single
+//! addresses, [`check_bounds`] checks that a whole region lies within one
+//! of a set of memories, e.g. [`DMA_MEMORIES`] for DMA buffers.
+//!
+//! - [`LIBTEGRA_REGIONS`] lists the regions used by libtegra itself,
+//! applications are expected to add their own regions to it.
+//!
+//! - [`validate`] checks a list of regions and reports the first problem
+//! it finds as an [`Error`], which implements `Display` with a message
+//! naming the offending regions. [`assert_valid`] panics with it instead.
+//!
+//! - [`dump`] writes a list of regions to a [`fmt::Write`] implementor.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::memory_map::{self, Region};
+//!
+//! fn main() {
+//!     let regions = [memory_map::STACK, memory_map::RCM_PAYLOAD];
+//!
+//!     memory_map::assert_valid(&regions);
+//!     assert!(memory_map::is_in_iram(0x4000_3000));
+//! }
+//! ```
+//!
+//! [`Region`]: struct.Region.html
+//! [`IRAM`]: constant.IRAM.html
+//! [`TZRAM`]: constant.TZRAM.html
+//! [`DRAM`]: constant.DRAM.html
+//! [`IRAM_LOW`]: constant.IRAM_LOW.html
+//! [`PAYLOAD_STAGING`]: constant.PAYLOAD_STAGING.html
+//! [`STACK`]: constant.STACK.html
+//! [`RCM_PAYLOAD`]: constant.RCM_PAYLOAD.html
+//! [`is_in_iram`]: fn.is_in_iram.html
+//! [`is_in_tzram`]: fn.is_in_tzram.html
+//! [`is_in_dram`]: fn.is_in_dram.html
+//! [`check_bounds`]: fn.check_bounds.html
+//! [`DMA_MEMORIES`]: constant.DMA_MEMORIES.html
+//! [`LIBTEGRA_REGIONS`]: constant.LIBTEGRA_REGIONS.html
+//! [`validate`]: fn.validate.html
+//! [`Error`]: enum.Error.html
+//! [`assert_valid`]: fn.assert_valid.html
+//! [`dump`]: fn.dump.html
+//! [`fmt::Write`]: https://doc.rust-lang.org/core/fmt/trait.Write.html
+
+use core::fmt;
+
+use crate::{
+    crashlog::{CRASHLOG_ADDRESS, CRASHLOG_SIZE},
+    display::{FRAMEBUFFER_ADDRESS, FRAMEBUFFER_SIZE},
+    log::{LOG_BUFFER_ADDRESS, LOG_BUFFER_SIZE},
+};
+
+/// A named range of physical memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// A human-readable name of the region.
+    pub name: &'static str,
+    /// The physical start address of the region.
+    pub start: u32,
+    /// The size of the region in bytes.
+    pub size: u32,
+}
+
+impl Region {
+    /// Creates a new region.
+    pub const fn new(name: &'static str, start: u32, size: u32) -> Self {
+        Region { name, start, size }
+    }
+
+    /// Gets the exclusive end address of the region.
+    pub fn end(&self) -> u64 {
+        u64::from(self.start) + u64::from(self.size)
+    }
+
+    /// Gets the inclusive last address of the region.
+    pub fn last(&self) -> u32 {
+        (self.end() - 1) as u32
+    }
+
+    /// Whether the region overlaps with another one.
+    pub fn overlaps(&self, other: &Region) -> bool {
+        u64::from(self.start) < other.end() && u64::from(other.start) < self.end()
+    }
+
+    /// Whether the region contains the given address.
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.start && u64::from(address) < self.end()
+    }
+
+    /// Whether the region lies entirely within another one.
+    pub fn is_within(&self, other: &Region) -> bool {
+        self.start >= other.start && self.end() <= other.end()
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{:08X}-{:08X}]", self.name, self.start, self.last())
+    }
+}
+
+/// The internal RAM.
+pub const IRAM: Region = Region::new("IRAM", 0x4000_0000, 0x4_0000);
+
+/// The TrustZone RAM.
+pub const TZRAM: Region = Region::new("TZRAM", 0x7C01_0000, 0x1_0000);
+
+/// The start address of the external DRAM.
+pub const DRAM_START: u32 = 0x8000_0000;
+
+/// The external DRAM, up to the end of the 32-bit address space.
+pub const DRAM: Region = Region::new("DRAM", DRAM_START, 0x8000_0000);
+
+/// The physical memories that regions may be placed in.
+pub const MEMORIES: [Region; 3] = [IRAM, TZRAM, DRAM];
+
+/// The physical memories that the DMA engines can access.
+pub const DMA_MEMORIES: [Region; 2] = [IRAM, DRAM];
+
+/// The low IRAM below the RCM payload, holding the staging area and the stack.
+pub const IRAM_LOW: Region = Region::new("low IRAM", 0x4000_0000, 0x1_0000);
+
+/// The area in low IRAM where raw RCM payloads are placed for chainloading.
+pub const PAYLOAD_STAGING: Region = Region::new("payload staging", 0x4000_3000, 0x8000);
+
+/// The stack of the BPMP, which grows down from the start of the RCM payload
+/// until it reaches the staging area.
+pub const STACK: Region = Region::new("stack", 0x4000_B000, 0x5000);
+
+/// The area an RCM payload is loaded to and executed from.
+pub const RCM_PAYLOAD: Region = Region::new("RCM payload", 0x4001_0000, 0x2_0000);
+
+/// The regions used by libtegra.
+pub const LIBTEGRA_REGIONS: [Region; 3] = [
+    Region::new("framebuffer", FRAMEBUFFER_ADDRESS, FRAMEBUFFER_SIZE),
+    Region::new("log", LOG_BUFFER_ADDRESS, LOG_BUFFER_SIZE),
+    Region::new("crashlog", CRASHLOG_ADDRESS, CRASHLOG_SIZE),
+];
+
+/// Enumeration of possible problems with a memory map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The region has a size of zero.
+    Empty(Region),
+    /// The region doesn't fit into any physical memory.
+    OutOfBounds(Region),
+    /// The regions overlap each other.
+    Overlap(Region, Region),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty(region) => write!(f, "memory region {} is empty", region),
+            Error::OutOfBounds(region) => {
+                write!(f, "memory region {} exceeds IRAM/DRAM bounds", region)
+            }
+            Error::Overlap(a, b) => write!(f, "memory regions {} and {} overlap", a, b),
+        }
+    }
+}
+
+/// Whether the given address lies within IRAM.
+pub fn is_in_iram(address: u32) -> bool {
+    IRAM.contains(address)
+}
+
+/// Whether the given address lies within TZRAM.
+pub fn is_in_tzram(address: u32) -> bool {
+    TZRAM.contains(address)
+}
+
+/// Whether the given address lies within DRAM.
+pub fn is_in_dram(address: u32) -> bool {
+    DRAM.contains(address)
+}
+
+/// Validates that a region isn't empty and lies entirely within
+/// one of the given memories.
+pub fn check_bounds(region: &Region, memories: &[Region]) -> Result<(), Error> {
+    if region.size == 0 {
+        return Err(Error::Empty(*region));
+    }
+
+    if !memories.iter().any(|memory| region.is_within(memory)) {
+        return Err(Error::OutOfBounds(*region));
+    }
+
+    Ok(())
+}
+
+/// Validates that the given regions don't overlap and all fit
+/// into one of the physical [`MEMORIES`].
+///
+/// [`MEMORIES`]: constant.MEMORIES.html
+pub fn validate(regions: &[Region]) -> Result<(), Error> {
+    for (index, region) in regions.iter().enumerate() {
+        check_bounds(region, &MEMORIES)?;
+
+        if let Some(other) = regions[index + 1..].iter().find(|o| region.overlaps(o)) {
+            return Err(Error::Overlap(*region, *other));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the given regions and panics with a description
+/// of the problem if they are invalid.
+pub fn assert_valid(regions: &[Region]) {
+    if let Err(error) = validate(regions) {
+        panic!("Invalid memory map: {}", error);
+    }
+}
+
+/// Writes the given regions to the given writer, one per line.
+pub fn dump<W: fmt::Write>(writer: &mut W, regions: &[Region]) -> fmt::Result {
+    for region in regions {
+        writeln!(writer, "{:08X}-{:08X} {}", region.start, region.last(), region.name)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_overlaps_and_bounds() {
+        let stack = Region::new("stack", 0x4000_B000, 0x5000);
+        let payload = Region::new("payload", 0x4001_0000, 0x2_0000);
+
+        assert_eq!(validate(&[stack, payload]), Ok(()));
+        assert_eq!(validate(&LIBTEGRA_REGIONS), Ok(()));
+
+        let overlapping = Region::new("overlapping", 0x4000_F000, 0x2000);
+        assert_eq!(
+            validate(&[stack, payload, overlapping]),
+            Err(Error::Overlap(stack, overlapping))
+        );
+
+        let straddling = Region::new("straddling", 0x4003_F000, 0x2000);
+        assert_eq!(validate(&[straddling]), Err(Error::OutOfBounds(straddling)));
+
+        let top = Region::new("top", 0xFFFF_F000, 0x1000);
+        assert_eq!(validate(&[top]), Ok(()));
+    }
+
+    #[test]
+    fn iram_layout() {
+        assert_eq!(validate(&[PAYLOAD_STAGING, STACK, RCM_PAYLOAD]), Ok(()));
+        assert_eq!(PAYLOAD_STAGING.end(), u64::from(STACK.start));
+        assert_eq!(STACK.end(), u64::from(RCM_PAYLOAD.start));
+        assert!(PAYLOAD_STAGING.is_within(&IRAM_LOW) && STACK.is_within(&IRAM_LOW));
+        assert!(RCM_PAYLOAD.is_within(&IRAM));
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        assert!(is_in_iram(0x4000_0000) && is_in_iram(0x4003_FFFF));
+        assert!(!is_in_iram(0x4004_0000));
+        assert!(is_in_tzram(0x7C01_0000) && !is_in_dram(0x7C01_0000));
+        assert!(is_in_dram(DRAM_START) && is_in_dram(0xFFFF_FFFF));
+
+        let buffer = Region::new("buffer", 0x7C01_0000, 0x100);
+        assert_eq!(check_bounds(&buffer, &DMA_MEMORIES), Err(Error::OutOfBounds(buffer)));
+        assert_eq!(check_bounds(&buffer, &MEMORIES), Ok(()));
+
+        let empty = Region::new("empty", DRAM_START, 0);
+        assert_eq!(check_bounds(&empty, &MEMORIES), Err(Error::Empty(empty)));
+    }
+}
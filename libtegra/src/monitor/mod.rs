@@ -0,0 +1,417 @@
+//! Interactive debug monitor over UART.
+//!
+//! # Description
+//!
+//! Bringing up a driver usually takes many experiments with register
+//! values. Instead of rebuilding and injecting a payload for each of them,
+//! the monitor provides a shell on a UART to poke at the hardware directly.
+//!
+//! | Command                    | Description                                  |
+//! |----------------------------|----------------------------------------------|
+//! | `peek <addr>`              | Reads a word from an address.                |
+//! | `poke <addr> <value>`      | Writes a word to an address.                 |
+//! | `dump <addr> <len>`        | Dumps a memory range as hex and ASCII.       |
+//! | `fuse <addr>`              | Reads a word from the fuse array.            |
+//! | `clk <name> [on\|off]`     | Shows or toggles a clock.                    |
+//! | `gpio <num> [0\|1\|in]`    | Reads, drives or releases a GPIO.            |
+//! | `jump <addr>`              | Jumps to an address, never returning.        |
+//!
+//! Numbers are hexadecimal with a `0x` prefix or decimal otherwise.
+//!
+//! # Implementation
+//!
+//! - [`run`] prints a prompt, reads a line with basic editing support and
+//! executes it, forever.
+//!
+//! - [`Command::parse`] parses a line into a [`Command`], which
+//! [`Command::execute`] carries out, writing its output to any
+//! [`fmt::Write`] implementor.
+//!
+//! - The UART has to be initialized beforehand.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{monitor, peripherals::Peripherals};
+//!
+//! fn main() {
+//!     let mut uart = Peripherals::take().unwrap().uart_b;
+//!
+//!     uart.init(115_200).unwrap();
+//!     monitor::run(&mut uart);
+//! }
+//! ```
+//!
+//! [`run`]: fn.run.html
+//! [`Command::parse`]: enum.Command.html#method.parse
+//! [`Command`]: enum.Command.html
+//! [`Command::execute`]: enum.Command.html#method.execute
+//! [`fmt::Write`]: https://doc.rust-lang.org/core/fmt/trait.Write.html
+
+use core::{
+    fmt::{self, Write},
+    str,
+};
+
+use mirage_mmio::Mmio;
+
+use crate::{
+    clock::{self, Clock},
+    fuse,
+    gpio::{Gpio, GpioConfig, GpioLevel},
+    uart::Uart,
+};
+
+/// The maximum length of a command line.
+const LINE_LENGTH: usize = 128;
+
+/// The amount of bytes per line of a memory dump.
+const DUMP_LINE_LENGTH: usize = 16;
+
+/// The help text listing all commands.
+const HELP: &str = "\
+peek <addr>            read a word
+poke <addr> <value>    write a word
+dump <addr> <len>      hex dump a memory range
+fuse <addr>            read a word from the fuse array
+clk <name> [on|off]    show or toggle a clock
+gpio <num> [0|1|in]    read, drive or release a GPIO
+jump <addr>            jump to an address
+";
+
+/// The ways a command line can be invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The command isn't known.
+    UnknownCommand,
+    /// An argument is missing.
+    MissingArgument,
+    /// An argument is malformed.
+    InvalidArgument,
+    /// More arguments were given than the command takes.
+    TooManyArguments,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::UnknownCommand => "unknown command, try `help`",
+            ParseError::MissingArgument => "missing argument",
+            ParseError::InvalidArgument => "invalid argument",
+            ParseError::TooManyArguments => "too many arguments",
+        })
+    }
+}
+
+/// The actions that can be applied to a GPIO.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpioAction {
+    /// Reads the level of the GPIO.
+    Read,
+    /// Configures the GPIO as an input.
+    Release,
+    /// Drives the GPIO low or high.
+    Drive(bool),
+}
+
+/// A parsed monitor command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    /// Prints the help text.
+    Help,
+    /// Reads a word from an address.
+    Peek(u32),
+    /// Writes a word to an address.
+    Poke(u32, u32),
+    /// Dumps a range of memory.
+    Dump(u32, u32),
+    /// Reads a word from the fuse array.
+    Fuse(u32),
+    /// Shows the state of a clock or turns it on or off.
+    Clock(&'a str, Option<bool>),
+    /// Applies an action to a GPIO.
+    Gpio(usize, GpioAction),
+    /// Jumps to an address.
+    Jump(u32),
+}
+
+/// Parses a number, either hexadecimal with a `0x` prefix or decimal.
+fn parse_number(s: &str) -> Result<u32, ParseError> {
+    let result = if s.starts_with("0x") || s.starts_with("0X") {
+        u32::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse()
+    };
+
+    result.map_err(|_| ParseError::InvalidArgument)
+}
+
+/// Parses a word-aligned address.
+fn parse_address(s: &str) -> Result<u32, ParseError> {
+    let address = parse_number(s)?;
+    if address % 4 != 0 {
+        return Err(ParseError::InvalidArgument);
+    }
+
+    Ok(address)
+}
+
+impl<'a> Command<'a> {
+    /// Parses a command line.
+    ///
+    /// Returns `Ok(None)` for empty lines.
+    pub fn parse(line: &'a str) -> Result<Option<Self>, ParseError> {
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        let mut argument = || words.next().ok_or(ParseError::MissingArgument);
+
+        let command = match name {
+            "help" | "?" => Command::Help,
+            "peek" => Command::Peek(parse_address(argument()?)?),
+            "poke" => Command::Poke(parse_address(argument()?)?, parse_number(argument()?)?),
+            "dump" => Command::Dump(parse_number(argument()?)?, parse_number(argument()?)?),
+            "fuse" => Command::Fuse(parse_number(argument()?)?),
+            "clk" => {
+                let name = argument()?;
+                let state = match argument() {
+                    Ok("on") => Some(true),
+                    Ok("off") => Some(false),
+                    Ok(_) => return Err(ParseError::InvalidArgument),
+                    Err(_) => None,
+                };
+
+                Command::Clock(name, state)
+            }
+            "gpio" => {
+                let number = parse_number(argument()?)? as usize;
+                if number >= Gpio::COUNT {
+                    return Err(ParseError::InvalidArgument);
+                }
+
+                let action = match argument() {
+                    Ok("0") => GpioAction::Drive(false),
+                    Ok("1") => GpioAction::Drive(true),
+                    Ok("in") => GpioAction::Release,
+                    Ok(_) => return Err(ParseError::InvalidArgument),
+                    Err(_) => GpioAction::Read,
+                };
+
+                Command::Gpio(number, action)
+            }
+            "jump" => Command::Jump(parse_address(argument()?)?),
+            _ => return Err(ParseError::UnknownCommand),
+        };
+
+        if words.next().is_some() {
+            return Err(ParseError::TooManyArguments);
+        }
+
+        Ok(Some(command))
+    }
+
+    /// Executes the command, writing its output to `writer`.
+    pub fn execute<W: fmt::Write>(&self, writer: &mut W) -> fmt::Result {
+        match *self {
+            Command::Help => writer.write_str(HELP),
+            Command::Peek(address) => {
+                let value = unsafe { (*(address as *const Mmio<u32>)).read() };
+                writeln!(writer, "{:08X}: {:08X}", address, value)
+            }
+            Command::Poke(address, value) => {
+                unsafe { (*(address as *const Mmio<u32>)).write(value) };
+                Ok(())
+            }
+            Command::Dump(address, length) => {
+                let data =
+                    unsafe { core::slice::from_raw_parts(address as *const u8, length as usize) };
+                hexdump(writer, address, data)
+            }
+            Command::Fuse(address) => match fuse::hardware_read(address) {
+                Ok(value) => writeln!(writer, "fuse {:#X}: {:08X}", address, value),
+                Err(error) => writeln!(writer, "fuse {:#X}: {}", address, error),
+            },
+            Command::Clock(name, state) => {
+                let mut clock: Clock = match clock::find(name) {
+                    Some(clock) => *clock,
+                    None => return writeln!(writer, "unknown clock {}", name),
+                };
+
+                match state {
+                    Some(true) => clock.enable(),
+                    Some(false) => clock.disable(),
+                    None => (),
+                }
+
+                let state = if clock.is_enabled() { "on" } else { "off" };
+                writeln!(writer, "{} {}", name, state)
+            }
+            Command::Gpio(number, action) => {
+                let gpio = Gpio::from_number(number).unwrap();
+
+                match action {
+                    GpioAction::Read => (),
+                    GpioAction::Release => gpio.config(GpioConfig::Input),
+                    GpioAction::Drive(false) => gpio.config(GpioConfig::OutputLow),
+                    GpioAction::Drive(true) => gpio.config(GpioConfig::OutputHigh),
+                }
+
+                let level = if gpio.read() == GpioLevel::High { 1 } else { 0 };
+                writeln!(writer, "gpio {:?}{}: {}", gpio.port, gpio.pin as u32, level)
+            }
+            Command::Jump(address) => {
+                let entry: extern "C" fn() -> ! = unsafe { core::mem::transmute(address as usize) };
+                entry()
+            }
+        }
+    }
+}
+
+/// Writes a hex dump of `data`, which is located at `address`.
+fn hexdump<W: fmt::Write>(writer: &mut W, address: u32, data: &[u8]) -> fmt::Result {
+    for (i, line) in data.chunks(DUMP_LINE_LENGTH).enumerate() {
+        write!(writer, "{:08X}:", address as usize + i * DUMP_LINE_LENGTH)?;
+
+        for j in 0..DUMP_LINE_LENGTH {
+            match line.get(j) {
+                Some(byte) => write!(writer, " {:02X}", byte)?,
+                None => writer.write_str("   ")?,
+            }
+        }
+
+        writer.write_str("  ")?;
+        for &byte in line {
+            let c = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            writer.write_char(c)?;
+        }
+
+        writer.write_char('\n')?;
+    }
+
+    Ok(())
+}
+
+/// Reads a line from the UART into `buffer`, echoing the input.
+///
+/// Returns the length of the line.
+fn read_line(uart: &mut Uart, buffer: &mut [u8]) -> usize {
+    let mut length = 0;
+
+    loop {
+        match uart.read_byte() {
+            b'\r' | b'\n' => {
+                uart.write_byte(b'\r');
+                uart.write_byte(b'\n');
+                return length;
+            }
+            // Backspace and delete.
+            0x08 | 0x7F if length > 0 => {
+                length -= 1;
+                for &byte in b"\x08 \x08" {
+                    uart.write_byte(byte);
+                }
+            }
+            byte @ 0x20..=0x7E if length < buffer.len() => {
+                buffer[length] = byte;
+                length += 1;
+                uart.write_byte(byte);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Runs the monitor on the given UART.
+pub fn run(uart: &mut Uart) -> ! {
+    let mut line = [0; LINE_LENGTH];
+
+    loop {
+        uart.write_str("> ").ok();
+
+        let length = read_line(uart, &mut line);
+        // Only printable ASCII makes it into the line.
+        let line = str::from_utf8(&line[..length]).unwrap();
+
+        match Command::parse(line) {
+            Ok(Some(command)) => command.execute(uart),
+            Ok(None) => Ok(()),
+            Err(error) => writeln!(uart, "error: {}", error),
+        }
+        .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("   "), Ok(None));
+        assert_eq!(
+            Command::parse("peek 0x7000F800"),
+            Ok(Some(Command::Peek(0x7000_F800)))
+        );
+        assert_eq!(
+            Command::parse("poke 0x6000_6000 1"),
+            Err(ParseError::InvalidArgument)
+        );
+        assert_eq!(
+            Command::parse("poke 0x60006000 0x10"),
+            Ok(Some(Command::Poke(0x6000_6000, 0x10)))
+        );
+        assert_eq!(
+            Command::parse("peek 0x7000F801"),
+            Err(ParseError::InvalidArgument)
+        );
+        assert_eq!(
+            Command::parse("dump 0x40000000"),
+            Err(ParseError::MissingArgument)
+        );
+        assert_eq!(
+            Command::parse("clk sdmmc1 on"),
+            Ok(Some(Command::Clock("sdmmc1", Some(true))))
+        );
+        assert_eq!(
+            Command::parse("gpio 189 0"),
+            Ok(Some(Command::Gpio(189, GpioAction::Drive(false))))
+        );
+        assert_eq!(
+            Command::parse("gpio 4096"),
+            Err(ParseError::InvalidArgument)
+        );
+        assert_eq!(
+            Command::parse("fuse 1 2"),
+            Err(ParseError::TooManyArguments)
+        );
+        assert_eq!(Command::parse("reboot"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn dump_memory() {
+        struct Buffer([u8; 128], usize);
+
+        impl fmt::Write for Buffer {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0[self.1..self.1 + s.len()].copy_from_slice(s.as_bytes());
+                self.1 += s.len();
+                Ok(())
+            }
+        }
+
+        let mut buffer = Buffer([0; 128], 0);
+        hexdump(&mut buffer, 0x4000_0000, b"Mirage\x00!").unwrap();
+
+        assert_eq!(
+            str::from_utf8(&buffer.0[..buffer.1]).unwrap(),
+            "40000000: 4D 69 72 61 67 65 00 21                          Mirage.!\n"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! The Mirage payload container format.
+//!
+//! # Description
+//!
+//! Raw RCM payloads are plain binaries that have to be placed at a fixed
+//! address, usually the start of the low IRAM, and are entered at their
+//! first byte. Nothing tells whether a file actually is such a payload or
+//! whether it was damaged on the SD card.
+//!
+//! Mirage payloads are wrapped in a container with a header describing
+//! where the image has to be loaded and entered, and a SHA-256 hash of the
+//! stored data. The header is 0x40 bytes long, all fields are little-endian:
+//!
+//! | Offset | Size | Field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | 0x00   | 4    | Magic, `MPAY`                                 |
+//! | 0x04   | 2    | Format version, currently 1                   |
+//! | 0x06   | 1    | Compression of the data, see [`Compression`] |
+//! | 0x07   | 1    | Reserved                                      |
+//! | 0x08   | 4    | Load address of the image                     |
+//! | 0x0C   | 4    | Entry point, within the image                 |
+//! | 0x10   | 4    | Size of the stored data                       |
+//! | 0x14   | 4    | Size of the image once loaded                 |
+//! | 0x18   | 8    | Reserved                                      |
+//! | 0x20   | 32   | SHA-256 hash of the stored data               |
+//!
+//! The stored data directly follows the header.
+//!
+//! # Implementation
+//!
+//! - [`parse`] validates the header and returns a [`PayloadInfo`]. This
+//! doesn't touch any hardware, so it can be used to inspect files.
+//!
+//! - [`load_and_verify`] additionally checks the hash with the SE and
+//! copies the image to its load address, decompressing it with [`lz4`] if
+//! necessary. The hash covers the stored data, so the decompressor only
+//! ever sees data that is known to be intact.
+//!
+//! - [`launch`] jumps to the entry point of a loaded payload.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::payload;
+//!
+//! fn boot(file: &[u8]) -> mirage_libtegra::Result<()> {
+//!     let info = unsafe { payload::load_and_verify(file)? };
+//!
+//!     unsafe { payload::launch(&info) }
+//! }
+//! ```
+//!
+//! [`Compression`]: enum.Compression.html
+//! [`parse`]: fn.parse.html
+//! [`PayloadInfo`]: struct.PayloadInfo.html
+//! [`load_and_verify`]: fn.load_and_verify.html
+//! [`lz4`]: ../lz4/index.html
+//! [`launch`]: fn.launch.html
+
+use core::{convert::TryInto, mem, ptr, slice};
+
+use crate::{
+    arch, ct, lz4,
+    memory_map::{self, Region, LIBTEGRA_REGIONS, MEMORIES},
+    se::{SecurityEngine, SHA256_HASH_SIZE},
+    Error, Result,
+};
+
+/// The magic of the payload header.
+pub const PAYLOAD_MAGIC: &[u8; 4] = b"MPAY";
+
+/// The supported version of the payload format.
+pub const PAYLOAD_VERSION: u16 = 1;
+
+/// The size of the payload header.
+pub const PAYLOAD_HEADER_SIZE: usize = 0x40;
+
+/// The offset of the hash in the header.
+const HASH_OFFSET: usize = 0x20;
+
+/// Compression methods of the stored data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// The data is stored as is.
+    None = 0,
+    /// The data is an LZ4 block.
+    Lz4 = 1,
+}
+
+impl Compression {
+    /// Gets the compression method stored in the header.
+    fn from_raw(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+}
+
+/// The information of a parsed payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayloadInfo<'a> {
+    /// The version of the payload format.
+    pub version: u16,
+    /// The compression of the stored data.
+    pub compression: Compression,
+    /// The address the image is loaded to.
+    pub load_address: u32,
+    /// The address of the entry point.
+    pub entry: u32,
+    /// The size of the image once loaded.
+    pub image_size: u32,
+    /// The SHA-256 hash of the stored data.
+    pub hash: [u8; SHA256_HASH_SIZE],
+    /// The stored data.
+    data: &'a [u8],
+}
+
+impl<'a> PayloadInfo<'a> {
+    /// Gets the stored data, following the header.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Gets the memory the image occupies once loaded.
+    pub fn target(&self) -> Region {
+        Region::new("payload", self.load_address, self.image_size)
+    }
+}
+
+/// Reads a little-endian 32-bit field of the header.
+fn read_u32(header: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap())
+}
+
+/// Parses the header of a payload.
+///
+/// Fails with [`Error::InvalidData`] if the header is malformed, has an
+/// unsupported version, or describes an image that can't be loaded, e.g.
+/// with the entry point outside of it.
+///
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+pub fn parse(payload: &[u8]) -> Result<PayloadInfo<'_>> {
+    if payload.len() < PAYLOAD_HEADER_SIZE || !payload.starts_with(PAYLOAD_MAGIC) {
+        return Err(Error::InvalidData);
+    }
+
+    let (header, body) = payload.split_at(PAYLOAD_HEADER_SIZE);
+
+    let version = u16::from_le_bytes([header[0x4], header[0x5]]);
+    if version != PAYLOAD_VERSION {
+        return Err(Error::InvalidData);
+    }
+
+    let compression = Compression::from_raw(header[0x6]).ok_or(Error::InvalidData)?;
+    let load_address = read_u32(header, 0x8);
+    let entry = read_u32(header, 0xC);
+    let data_size = read_u32(header, 0x10) as usize;
+    let image_size = read_u32(header, 0x14);
+
+    let data = body.get(..data_size).ok_or(Error::InvalidData)?;
+
+    if compression == Compression::None && data_size != image_size as usize {
+        return Err(Error::InvalidData);
+    }
+
+    // The image has to fit the address space and contain the entry point,
+    // which may have the Thumb bit set.
+    let image_end = u64::from(load_address) + u64::from(image_size);
+    let entry_address = u64::from(entry & !1);
+    if image_size == 0
+        || image_end > 1 << 32
+        || entry_address < u64::from(load_address)
+        || entry_address >= image_end
+    {
+        return Err(Error::InvalidData);
+    }
+
+    let mut hash = [0; SHA256_HASH_SIZE];
+    hash.copy_from_slice(&header[HASH_OFFSET..HASH_OFFSET + SHA256_HASH_SIZE]);
+
+    Ok(PayloadInfo {
+        version,
+        compression,
+        load_address,
+        entry,
+        image_size,
+        hash,
+        data,
+    })
+}
+
+/// Parses a payload, verifies its hash and loads the image to its load
+/// address.
+///
+/// Fails with [`Error::InvalidData`] if the payload is malformed, the hash
+/// doesn't match or the data doesn't decompress to the image size, and
+/// with [`Error::InvalidArgument`] if the image doesn't lie within IRAM,
+/// TZRAM or DRAM or overlaps the payload itself or a region used by
+/// libtegra.
+///
+/// # Safety
+///
+/// The memory at the load address must not hold anything that is still in
+/// use, including the stack and the code of the caller.
+///
+/// [`Error::InvalidData`]: ../enum.Error.html#variant.InvalidData
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+pub unsafe fn load_and_verify(payload: &[u8]) -> Result<PayloadInfo<'_>> {
+    let info = parse(payload)?;
+    let target = info.target();
+
+    let source = Region::new("source", payload.as_ptr() as u32, payload.len() as u32);
+    memory_map::check_bounds(&target, &MEMORIES)?;
+    if target.overlaps(&source) || LIBTEGRA_REGIONS.iter().any(|region| target.overlaps(region)) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let hash = SecurityEngine::new().calculate_sha256(info.data)?;
+    if !ct::eq(&hash, &info.hash) {
+        return Err(Error::InvalidData);
+    }
+
+    match info.compression {
+        Compression::None => {
+            ptr::copy_nonoverlapping(
+                info.data.as_ptr(),
+                info.load_address as *mut u8,
+                info.data.len(),
+            );
+        }
+        Compression::Lz4 => {
+            let image = slice::from_raw_parts_mut(
+                info.load_address as *mut u8,
+                info.image_size as usize,
+            );
+
+            if lz4::decompress(info.data, image) != Ok(image.len()) {
+                return Err(Error::InvalidData);
+            }
+        }
+    }
+
+    // Make sure the image is in memory before it gets executed.
+    arch::clean_invalidate_range(info.load_address as usize, info.image_size as usize);
+    arch::data_barrier();
+    arch::instruction_barrier();
+
+    Ok(info)
+}
+
+/// Jumps to the entry point of a loaded payload.
+///
+/// # Safety
+///
+/// The payload must have been loaded with [`load_and_verify`], and the
+/// environment has to be prepared for it, e.g. by wiping sensitive state.
+///
+/// [`load_and_verify`]: fn.load_and_verify.html
+pub unsafe fn launch(info: &PayloadInfo<'_>) -> ! {
+    let entry: extern "C" fn() -> ! = mem::transmute(info.entry as usize);
+
+    entry()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a payload around `data`, which is loaded to `0x4001_0000`.
+    fn build(data: &[u8], entry: u32) -> [u8; PAYLOAD_HEADER_SIZE + 0x10] {
+        let mut payload = [0; PAYLOAD_HEADER_SIZE + 0x10];
+
+        payload[..4].copy_from_slice(PAYLOAD_MAGIC);
+        payload[0x4..0x6].copy_from_slice(&PAYLOAD_VERSION.to_le_bytes());
+        payload[0x8..0xC].copy_from_slice(&0x4001_0000u32.to_le_bytes());
+        payload[0xC..0x10].copy_from_slice(&entry.to_le_bytes());
+        payload[0x10..0x14].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        payload[0x14..0x18].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        payload[HASH_OFFSET] = 0xAA;
+        payload[PAYLOAD_HEADER_SIZE..][..data.len()].copy_from_slice(data);
+
+        payload
+    }
+
+    /// Tests parsing a valid header.
+    #[test]
+    fn parse_valid() {
+        let payload = build(&[1, 2, 3, 4, 5, 6, 7, 8], 0x4001_0005);
+
+        let info = parse(&payload).unwrap();
+        assert_eq!(info.version, PAYLOAD_VERSION);
+        assert_eq!(info.compression, Compression::None);
+        assert_eq!(info.entry, 0x4001_0005);
+        assert_eq!(info.data(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(info.hash[0], 0xAA);
+        assert_eq!(info.target(), Region::new("payload", 0x4001_0000, 8));
+    }
+
+    /// Tests rejecting malformed headers.
+    #[test]
+    fn parse_invalid() {
+        let data = [0; 8];
+
+        assert_eq!(parse(&[0; 0x20]), Err(Error::InvalidData));
+        assert_eq!(parse(&build(&data, 0x4001_0008)), Err(Error::InvalidData));
+        assert_eq!(parse(&build(&data, 0x4000_FFFF)), Err(Error::InvalidData));
+
+        let mut payload = build(&data, 0x4001_0000);
+        payload[0x4] = 2;
+        assert_eq!(parse(&payload), Err(Error::InvalidData));
+
+        let mut payload = build(&data, 0x4001_0000);
+        payload[0x10] = 0x11;
+        assert_eq!(parse(&payload), Err(Error::InvalidData));
+
+        let mut payload = build(&data, 0x4001_0000);
+        payload[0x6] = 7;
+        assert_eq!(parse(&payload), Err(Error::InvalidData));
+    }
+}
@@ -0,0 +1,155 @@
+//! Exclusive ownership of the hardware blocks.
+//!
+//! # Description
+//!
+//! If every part of a payload could name any controller, two of them could
+//! reinitialize the same one without noticing, e.g. change the baud rate
+//! of the UART another part logs to.
+//!
+//! [`Peripherals`] gathers a handle for every controller in a struct that
+//! can be obtained only once. Code that receives a handle from it owns the
+//! controller, and methods which change the state of the hardware taking
+//! `&mut self` make the borrow checker enforce that. Outside of this crate,
+//! the handles can't be obtained in any other way.
+//!
+//! Drivers of devices behind a controller, e.g. the PMIC on I²C 5, take a
+//! reference to its handle instead of naming the controller themselves.
+//!
+//! # Implementation
+//!
+//! - [`Peripherals::take`] returns the handles on the first call and
+//! `None` afterwards. Interrupts are masked while checking, so a handler
+//! can't take them at the same time.
+//!
+//! - [`Peripherals::steal`] returns the handles regardless, for code like
+//! panic handlers that has to get hold of the hardware in any case.
+//!
+//! - The register blocks of the CAR, the PMC and the pinmux are handed out
+//! as shared references, as their registers are accessed individually.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::peripherals::Peripherals;
+//!
+//! fn main() {
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!
+//!     peripherals.pinmux.configure_uart(&peripherals.uart_a);
+//!     peripherals.uart_a.init(115_200).unwrap();
+//!
+//!     // The handles are gone now.
+//!     assert!(Peripherals::take().is_none());
+//! }
+//! ```
+//!
+//! [`Peripherals`]: struct.Peripherals.html
+//! [`Peripherals::take`]: struct.Peripherals.html#method.take
+//! [`Peripherals::steal`]: struct.Peripherals.html#method.steal
+
+use mirage_mmio::VolatileStorage;
+
+use crate::{
+    arch::{disable_interrupts, restore_interrupts},
+    clock::Car,
+    i2c::I2c,
+    pinmux::Pinmux,
+    pmc::Pmc,
+    se::SecurityEngine,
+    uart::Uart,
+};
+
+/// Whether the peripherals have been taken.
+static mut TAKEN: bool = false;
+
+/// The handles to all hardware blocks.
+pub struct Peripherals {
+    /// The Clock and Reset Controller.
+    pub car: &'static Car,
+    /// The Power Management Controller.
+    pub pmc: &'static Pmc,
+    /// The pinmux controller.
+    pub pinmux: &'static Pinmux,
+    /// The UART A.
+    pub uart_a: Uart,
+    /// The UART B.
+    pub uart_b: Uart,
+    /// The UART C.
+    pub uart_c: Uart,
+    /// The UART D.
+    pub uart_d: Uart,
+    /// The UART APE.
+    pub uart_e: Uart,
+    /// The I²C controller 1.
+    pub i2c_1: I2c,
+    /// The I²C controller 2.
+    pub i2c_2: I2c,
+    /// The I²C controller 3.
+    pub i2c_3: I2c,
+    /// The I²C controller 4.
+    pub i2c_4: I2c,
+    /// The I²C controller 5.
+    pub i2c_5: I2c,
+    /// The I²C controller 6.
+    pub i2c_6: I2c,
+    /// The Security Engine.
+    pub se: SecurityEngine,
+}
+
+impl Peripherals {
+    /// Takes the handles to all hardware blocks.
+    ///
+    /// Returns `None` if they have been taken before.
+    pub fn take() -> Option<Self> {
+        let interrupts = disable_interrupts();
+
+        let peripherals = unsafe {
+            if TAKEN {
+                None
+            } else {
+                Some(Self::steal())
+            }
+        };
+
+        restore_interrupts(interrupts);
+
+        peripherals
+    }
+
+    /// Takes the handles to all hardware blocks, even if they have been
+    /// taken before.
+    ///
+    /// This is unsafe because the handles may alias ones that are in use.
+    pub unsafe fn steal() -> Self {
+        TAKEN = true;
+
+        Peripherals {
+            car: Car::get(),
+            pmc: Pmc::get(),
+            pinmux: Pinmux::get(),
+            uart_a: Uart::A,
+            uart_b: Uart::B,
+            uart_c: Uart::C,
+            uart_d: Uart::D,
+            uart_e: Uart::E,
+            i2c_1: I2c::C1,
+            i2c_2: I2c::C2,
+            i2c_3: I2c::C3,
+            i2c_4: I2c::C4,
+            i2c_5: I2c::C5,
+            i2c_6: I2c::C6,
+            se: SecurityEngine::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_once() {
+        assert!(Peripherals::take().is_some());
+        assert!(Peripherals::take().is_none());
+    }
+}
@@ -45,14 +45,18 @@
 //! - The functions [`configure_uart`] and [`configure_i2c`] can be used
 //! to configure UART and I²C devices for use.
 //!
+//! - The [`Pad`] enum identifies every pad controlled by the Pinmux. The
+//! configuration register of a pad can be obtained through [`Pinmux::pad`],
+//! which allows for describing pad configurations as data.
+//!
 //! # Example
 //!
 //! ```
-//! use mirage_libtegra::{pinmux::configure_uart, uart::Uart};
+//! use mirage_libtegra::peripherals::Peripherals;
 //!
 //! fn main() {
-//!     let device = Uart::A;
-//!     configure_uart(&device);
+//!     let peripherals = Peripherals::take().unwrap();
+//!     peripherals.pinmux.configure_uart(&peripherals.uart_a);
 //! }
 //! ```
 //!
@@ -64,6 +68,8 @@
 //! [`Pinmux::new`]: struct.Pinmux.html#method.new
 //! [`configure_uart`]: fn.configure_uart.html
 //! [`configure_i2c`]: fn.configure_i2c.html
+//! [`Pad`]: enum.Pad.html
+//! [`Pinmux::pad`]: struct.Pinmux.html#method.pad
 
 use mirage_mmio::{Mmio, VolatileStorage};
 
@@ -133,6 +139,345 @@ pub const IO_HV: u32 = (1 << 10);
 /// Applicable to all pads.
 pub const SCHMT: u32 = (1 << 12);
 
+/// The total amount of pads controlled by the Pinmux.
+pub const PAD_COUNT: usize = 162;
+
+/// The Pinmux pads, represented by the offsets of their
+/// configuration registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pad {
+    Sdmmc1Clk = 0x0,
+    Sdmmc1Cmd = 0x4,
+    Sdmmc1Dat3 = 0x8,
+    Sdmmc1Dat2 = 0xC,
+    Sdmmc1Dat1 = 0x10,
+    Sdmmc1Dat0 = 0x14,
+    Sdmmc3Clk = 0x1C,
+    Sdmmc3Cmd = 0x20,
+    Sdmmc3Dat0 = 0x24,
+    Sdmmc3Dat1 = 0x28,
+    Sdmmc3Dat2 = 0x2C,
+    Sdmmc3Dat3 = 0x30,
+    PexL0RstN = 0x38,
+    PexL0ClkreqN = 0x3C,
+    PexWakeN = 0x40,
+    PexL1RstN = 0x44,
+    PexL1ClkreqN = 0x48,
+    SataLedActive = 0x4C,
+    Spi1Mosi = 0x50,
+    Spi1Miso = 0x54,
+    Spi1Sck = 0x58,
+    Spi1Cs0 = 0x5C,
+    Spi1Cs1 = 0x60,
+    Spi2Mosi = 0x64,
+    Spi2Miso = 0x68,
+    Spi2Sck = 0x6C,
+    Spi2Cs0 = 0x70,
+    Spi2Cs1 = 0x74,
+    Spi4Mosi = 0x78,
+    Spi4Miso = 0x7C,
+    Spi4Sck = 0x80,
+    Spi4Cs0 = 0x84,
+    QspiSck = 0x88,
+    QspiCsN = 0x8C,
+    QspiIo0 = 0x90,
+    QspiIo1 = 0x94,
+    QspiIo2 = 0x98,
+    QspiIo3 = 0x9C,
+    Dmic1Clk = 0xA4,
+    Dmic1Dat = 0xA8,
+    Dmic2Clk = 0xAC,
+    Dmic2Dat = 0xB0,
+    Dmic3Clk = 0xB4,
+    Dmic3Dat = 0xB8,
+    Gen1I2cScl = 0xBC,
+    Gen1I2cSda = 0xC0,
+    Gen2I2cScl = 0xC4,
+    Gen2I2cSda = 0xC8,
+    Gen3I2cScl = 0xCC,
+    Gen3I2cSda = 0xD0,
+    CamI2cScl = 0xD4,
+    CamI2cSda = 0xD8,
+    PwrI2cScl = 0xDC,
+    PwrI2cSda = 0xE0,
+    Uart1Tx = 0xE4,
+    Uart1Rx = 0xE8,
+    Uart1Rts = 0xEC,
+    Uart1Cts = 0xF0,
+    Uart2Tx = 0xF4,
+    Uart2Rx = 0xF8,
+    Uart2Rts = 0xFC,
+    Uart2Cts = 0x100,
+    Uart3Tx = 0x104,
+    Uart3Rx = 0x108,
+    Uart3Rts = 0x10C,
+    Uart3Cts = 0x110,
+    Uart4Tx = 0x114,
+    Uart4Rx = 0x118,
+    Uart4Rts = 0x11C,
+    Uart4Cts = 0x120,
+    Dap1Fs = 0x124,
+    Dap1Din = 0x128,
+    Dap1Dout = 0x12C,
+    Dap1Sclk = 0x130,
+    Dap2Fs = 0x134,
+    Dap2Din = 0x138,
+    Dap2Dout = 0x13C,
+    Dap2Sclk = 0x140,
+    Dap4Fs = 0x144,
+    Dap4Din = 0x148,
+    Dap4Dout = 0x14C,
+    Dap4Sclk = 0x150,
+    Cam1Mclk = 0x154,
+    Cam2Mclk = 0x158,
+    JtagRtck = 0x15C,
+    Clk32kIn = 0x160,
+    Clk32kOut = 0x164,
+    BattBcl = 0x168,
+    ClkReq = 0x16C,
+    CpuPwrReq = 0x170,
+    PwrIntN = 0x174,
+    Shutdown = 0x178,
+    CorePwrReq = 0x17C,
+    AudMclk = 0x180,
+    DvfsPwm = 0x184,
+    DvfsClk = 0x188,
+    GpioX1Aud = 0x18C,
+    GpioX3Aud = 0x190,
+    Pcc7 = 0x194,
+    HdmiCec = 0x198,
+    HdmiIntDpHpd = 0x19C,
+    SpdifOut = 0x1A0,
+    SpdifIn = 0x1A4,
+    UsbVbusEn0 = 0x1A8,
+    UsbVbusEn1 = 0x1AC,
+    DpHpd0 = 0x1B0,
+    WifiEn = 0x1B4,
+    WifiRst = 0x1B8,
+    WifiWakeAp = 0x1BC,
+    ApWakeBt = 0x1C0,
+    BtRst = 0x1C4,
+    BtWakeAp = 0x1C8,
+    ApWakeNfc = 0x1CC,
+    NfcEn = 0x1D0,
+    NfcInt = 0x1D4,
+    GpsEn = 0x1D8,
+    GpsRst = 0x1DC,
+    CamRst = 0x1E0,
+    CamAfEn = 0x1E4,
+    CamFlashEn = 0x1E8,
+    Cam1Pwdn = 0x1EC,
+    Cam2Pwdn = 0x1F0,
+    Cam1Strobe = 0x1F4,
+    LcdTe = 0x1F8,
+    LcdBlPwm = 0x1FC,
+    LcdBlEn = 0x200,
+    LcdRst = 0x204,
+    LcdGpio1 = 0x208,
+    LcdGpio2 = 0x20C,
+    ApReady = 0x210,
+    TouchRst = 0x214,
+    TouchClk = 0x218,
+    ModemWakeAp = 0x21C,
+    TouchInt = 0x220,
+    MotionInt = 0x224,
+    AlsProxInt = 0x228,
+    TempAlert = 0x22C,
+    ButtonPowerOn = 0x230,
+    ButtonVolUp = 0x234,
+    ButtonVolDown = 0x238,
+    ButtonSlideSw = 0x23C,
+    ButtonHome = 0x240,
+    Pa6 = 0x244,
+    Pe6 = 0x248,
+    Pe7 = 0x24C,
+    Ph6 = 0x250,
+    Pk0 = 0x254,
+    Pk1 = 0x258,
+    Pk2 = 0x25C,
+    Pk3 = 0x260,
+    Pk4 = 0x264,
+    Pk5 = 0x268,
+    Pk6 = 0x26C,
+    Pk7 = 0x270,
+    Pl0 = 0x274,
+    Pl1 = 0x278,
+    Pz0 = 0x27C,
+    Pz1 = 0x280,
+    Pz2 = 0x284,
+    Pz3 = 0x288,
+    Pz4 = 0x28C,
+    Pz5 = 0x290,
+}
+
+impl Pad {
+    /// All pads, in register order.
+    pub const ALL: [Pad; PAD_COUNT] = [
+        Pad::Sdmmc1Clk,
+        Pad::Sdmmc1Cmd,
+        Pad::Sdmmc1Dat3,
+        Pad::Sdmmc1Dat2,
+        Pad::Sdmmc1Dat1,
+        Pad::Sdmmc1Dat0,
+        Pad::Sdmmc3Clk,
+        Pad::Sdmmc3Cmd,
+        Pad::Sdmmc3Dat0,
+        Pad::Sdmmc3Dat1,
+        Pad::Sdmmc3Dat2,
+        Pad::Sdmmc3Dat3,
+        Pad::PexL0RstN,
+        Pad::PexL0ClkreqN,
+        Pad::PexWakeN,
+        Pad::PexL1RstN,
+        Pad::PexL1ClkreqN,
+        Pad::SataLedActive,
+        Pad::Spi1Mosi,
+        Pad::Spi1Miso,
+        Pad::Spi1Sck,
+        Pad::Spi1Cs0,
+        Pad::Spi1Cs1,
+        Pad::Spi2Mosi,
+        Pad::Spi2Miso,
+        Pad::Spi2Sck,
+        Pad::Spi2Cs0,
+        Pad::Spi2Cs1,
+        Pad::Spi4Mosi,
+        Pad::Spi4Miso,
+        Pad::Spi4Sck,
+        Pad::Spi4Cs0,
+        Pad::QspiSck,
+        Pad::QspiCsN,
+        Pad::QspiIo0,
+        Pad::QspiIo1,
+        Pad::QspiIo2,
+        Pad::QspiIo3,
+        Pad::Dmic1Clk,
+        Pad::Dmic1Dat,
+        Pad::Dmic2Clk,
+        Pad::Dmic2Dat,
+        Pad::Dmic3Clk,
+        Pad::Dmic3Dat,
+        Pad::Gen1I2cScl,
+        Pad::Gen1I2cSda,
+        Pad::Gen2I2cScl,
+        Pad::Gen2I2cSda,
+        Pad::Gen3I2cScl,
+        Pad::Gen3I2cSda,
+        Pad::CamI2cScl,
+        Pad::CamI2cSda,
+        Pad::PwrI2cScl,
+        Pad::PwrI2cSda,
+        Pad::Uart1Tx,
+        Pad::Uart1Rx,
+        Pad::Uart1Rts,
+        Pad::Uart1Cts,
+        Pad::Uart2Tx,
+        Pad::Uart2Rx,
+        Pad::Uart2Rts,
+        Pad::Uart2Cts,
+        Pad::Uart3Tx,
+        Pad::Uart3Rx,
+        Pad::Uart3Rts,
+        Pad::Uart3Cts,
+        Pad::Uart4Tx,
+        Pad::Uart4Rx,
+        Pad::Uart4Rts,
+        Pad::Uart4Cts,
+        Pad::Dap1Fs,
+        Pad::Dap1Din,
+        Pad::Dap1Dout,
+        Pad::Dap1Sclk,
+        Pad::Dap2Fs,
+        Pad::Dap2Din,
+        Pad::Dap2Dout,
+        Pad::Dap2Sclk,
+        Pad::Dap4Fs,
+        Pad::Dap4Din,
+        Pad::Dap4Dout,
+        Pad::Dap4Sclk,
+        Pad::Cam1Mclk,
+        Pad::Cam2Mclk,
+        Pad::JtagRtck,
+        Pad::Clk32kIn,
+        Pad::Clk32kOut,
+        Pad::BattBcl,
+        Pad::ClkReq,
+        Pad::CpuPwrReq,
+        Pad::PwrIntN,
+        Pad::Shutdown,
+        Pad::CorePwrReq,
+        Pad::AudMclk,
+        Pad::DvfsPwm,
+        Pad::DvfsClk,
+        Pad::GpioX1Aud,
+        Pad::GpioX3Aud,
+        Pad::Pcc7,
+        Pad::HdmiCec,
+        Pad::HdmiIntDpHpd,
+        Pad::SpdifOut,
+        Pad::SpdifIn,
+        Pad::UsbVbusEn0,
+        Pad::UsbVbusEn1,
+        Pad::DpHpd0,
+        Pad::WifiEn,
+        Pad::WifiRst,
+        Pad::WifiWakeAp,
+        Pad::ApWakeBt,
+        Pad::BtRst,
+        Pad::BtWakeAp,
+        Pad::ApWakeNfc,
+        Pad::NfcEn,
+        Pad::NfcInt,
+        Pad::GpsEn,
+        Pad::GpsRst,
+        Pad::CamRst,
+        Pad::CamAfEn,
+        Pad::CamFlashEn,
+        Pad::Cam1Pwdn,
+        Pad::Cam2Pwdn,
+        Pad::Cam1Strobe,
+        Pad::LcdTe,
+        Pad::LcdBlPwm,
+        Pad::LcdBlEn,
+        Pad::LcdRst,
+        Pad::LcdGpio1,
+        Pad::LcdGpio2,
+        Pad::ApReady,
+        Pad::TouchRst,
+        Pad::TouchClk,
+        Pad::ModemWakeAp,
+        Pad::TouchInt,
+        Pad::MotionInt,
+        Pad::AlsProxInt,
+        Pad::TempAlert,
+        Pad::ButtonPowerOn,
+        Pad::ButtonVolUp,
+        Pad::ButtonVolDown,
+        Pad::ButtonSlideSw,
+        Pad::ButtonHome,
+        Pad::Pa6,
+        Pad::Pe6,
+        Pad::Pe7,
+        Pad::Ph6,
+        Pad::Pk0,
+        Pad::Pk1,
+        Pad::Pk2,
+        Pad::Pk3,
+        Pad::Pk4,
+        Pad::Pk5,
+        Pad::Pk6,
+        Pad::Pk7,
+        Pad::Pl0,
+        Pad::Pl1,
+        Pad::Pz0,
+        Pad::Pz1,
+        Pad::Pz2,
+        Pad::Pz3,
+        Pad::Pz4,
+        Pad::Pz5,
+    ];
+}
+
 /// Representation of the Pinmux registers.
 #[repr(C)]
 pub struct Pinmux {
@@ -310,6 +655,11 @@ impl VolatileStorage for Pinmux {
 }
 
 impl Pinmux {
+    /// Gets the configuration register of a pad.
+    pub fn pad(&self, pad: Pad) -> &Mmio<u32> {
+        unsafe { &*((self as *const _ as usize + pad as usize) as *const Mmio<u32>) }
+    }
+
     /// Configures an UART device.
     pub fn configure_uart(&self, uart: &Uart) {
         match uart {
@@ -347,28 +697,28 @@ impl Pinmux {
 
     /// Configures an I²C device.
     pub fn configure_i2c(&self, device: &I2c) {
-        match device {
-            &I2c::C1 => {
+        match device.index() {
+            0 => {
                 self.gen1_i2c_scl.write(INPUT);
                 self.gen1_i2c_sda.write(INPUT);
             }
-            &I2c::C2 => {
+            1 => {
                 self.gen2_i2c_scl.write(INPUT);
                 self.gen2_i2c_sda.write(INPUT);
             }
-            &I2c::C3 => {
+            2 => {
                 self.gen3_i2c_scl.write(INPUT);
                 self.gen3_i2c_sda.write(INPUT);
             }
-            &I2c::C4 => {
+            3 => {
                 self.cam_i2c_scl.write(INPUT);
                 self.cam_i2c_sda.write(INPUT);
             }
-            &I2c::C5 => {
+            4 => {
                 self.pwr_i2c_scl.write(INPUT);
                 self.pwr_i2c_sda.write(INPUT);
             }
-            &I2c::C6 => {
+            5 => {
                 // Unused on the Switch.
                 // TODO(Vale): Nonetheless, figure this out.
             }
@@ -376,3 +726,55 @@ impl Pinmux {
         }
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    fn address(register: &Mmio<u32>) -> usize {
+        register as *const _ as usize
+    }
+
+    /// Tests the pad configuration of the UARTs.
+    #[test]
+    fn configure_uart() {
+        let _session = mock::start();
+        let pinmux = unsafe { Pinmux::get() };
+
+        pinmux.configure_uart(&Uart::B);
+
+        assert_eq!(mock::writes(address(&pinmux.uart2_tx)), [0]);
+        assert_eq!(mock::writes(address(&pinmux.uart2_rx)), [u64::from(INPUT | PULL_UP)]);
+        assert_eq!(mock::writes(address(&pinmux.uart2_rts)), [0]);
+        assert_eq!(mock::writes(address(&pinmux.uart2_cts)), [u64::from(INPUT | PULL_DOWN)]);
+
+        // Other UARTs are left alone.
+        assert!(mock::writes(address(&pinmux.uart1_tx)).is_empty());
+    }
+
+    /// Tests the pad configuration of the I²C controllers.
+    #[test]
+    fn configure_i2c() {
+        let _session = mock::start();
+        let pinmux = unsafe { Pinmux::get() };
+
+        pinmux.configure_i2c(&I2c::C5);
+
+        assert_eq!(mock::writes(address(&pinmux.pwr_i2c_scl)), [u64::from(INPUT)]);
+        assert_eq!(mock::writes(address(&pinmux.pwr_i2c_sda)), [u64::from(INPUT)]);
+        assert!(mock::writes(address(&pinmux.gen1_i2c_scl)).is_empty());
+    }
+
+    /// Tests that pad registers are addressed by their offset.
+    #[test]
+    fn pad_register() {
+        let pinmux = unsafe { Pinmux::get() };
+
+        assert_eq!(
+            address(pinmux.pad(Pad::Uart2Tx)),
+            address(&pinmux.uart2_tx)
+        );
+    }
+}
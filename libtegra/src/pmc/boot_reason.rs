@@ -0,0 +1,219 @@
+//! Boot reason decoding.
+//!
+//! # Description
+//!
+//! Bootloaders often need to know why the system came up, e.g. to enter
+//! a recovery menu after a watchdog reset or to resume from LP0. The
+//! information is spread over the PMC reset status, the reboot reason
+//! Mirage leaves in the scratch registers and the power-on events of the
+//! MAX77620 PMIC.
+//!
+//! # Implementation
+//!
+//! - [`boot_reason`] combines all sources into a [`BootReason`].
+//!
+//! - [`set_reboot_reason`] records the reason of a software reset in
+//! [`Scratch::REBOOT_REASON`] right before resetting. [`reboot_to_rcm`]
+//! does so automatically.
+//!
+//! - The reboot reason and the PMIC event registers are cleared when they
+//! are read, so [`boot_reason`] should be called once early during boot.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     peripherals::Peripherals,
+//!     pmc::{self, BootReason},
+//! };
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
+//!     if pmc::boot_reason(&peripherals.i2c_5) == BootReason::WatchdogReset {
+//!         // Enter recovery.
+//!     }
+//! }
+//! ```
+//!
+//! [`boot_reason`]: fn.boot_reason.html
+//! [`BootReason`]: enum.BootReason.html
+//! [`set_reboot_reason`]: fn.set_reboot_reason.html
+//! [`Scratch::REBOOT_REASON`]: struct.Scratch.html#associatedconstant.REBOOT_REASON
+//! [`reboot_to_rcm`]: fn.reboot_to_rcm.html
+
+use mirage_mmio::{register_bitfields, VolatileStorage};
+
+use super::{Pmc, Scratch};
+use crate::i2c::{Device, I2c};
+
+register_bitfields! {
+    u32,
+    /// The `APBDEV_PMC_RST_STATUS_0` register.
+    pub RST_STATUS [
+        /// The source of the last reset, zero after power-on.
+        RST_SOURCE OFFSET(0) NUMBITS(3),
+    ],
+}
+
+/// The `ONOFFIRQ` register of the MAX77620.
+const MAX77620_REG_ONOFFIRQ: u8 = 0x0B;
+/// The `NVERC` register of the MAX77620.
+const MAX77620_REG_NVERC: u8 = 0x0C;
+
+/// Rising edge of the power button in `ONOFFIRQ`.
+const MAX77620_ONOFFIRQ_EN0_R: u8 = 1 << 3;
+/// Rising edge of the charger detection in `ONOFFIRQ`.
+const MAX77620_ONOFFIRQ_ACOK_R: u8 = 1 << 7;
+/// The PMIC watchdog expired, recorded in `NVERC`.
+const MAX77620_NVERC_WTCHDG: u8 = 1 << 1;
+
+/// Magic value in the upper half of the reboot reason scratch register.
+const REBOOT_REASON_MAGIC: u32 = 0x5242;
+
+/// `RST_SOURCE` of a reset by the PMC watchdog.
+const RST_SOURCE_WATCHDOG: u32 = 1;
+/// `RST_SOURCE` of a reset by a thermal sensor.
+const RST_SOURCE_SENSOR: u32 = 2;
+/// `RST_SOURCE` of a reset through `APBDEV_PMC_CNTRL_0`.
+const RST_SOURCE_SW_MAIN: u32 = 3;
+/// `RST_SOURCE` of a wake from LP0.
+const RST_SOURCE_LP0: u32 = 4;
+
+/// Reasons for software resets, recorded with [`set_reboot_reason`].
+///
+/// [`set_reboot_reason`]: fn.set_reboot_reason.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebootReason {
+    /// A regular reboot.
+    Reboot = 1,
+    /// A reboot into RCM.
+    Rcm = 2,
+}
+
+/// Events that powered up the PMIC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerOnCause {
+    /// The power button was pressed.
+    PowerButton,
+    /// A charger was connected.
+    Charger,
+    /// The cause couldn't be determined.
+    Unknown,
+}
+
+/// Reasons why the system booted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BootReason {
+    /// The system was powered on.
+    ColdBoot(PowerOnCause),
+    /// The PMC or the PMIC watchdog reset the system.
+    WatchdogReset,
+    /// A thermal sensor reset the system.
+    ThermalReset,
+    /// Software reset the system.
+    SoftwareReset,
+    /// The system resumes from the LP0 sleep state.
+    Lp0Resume,
+    /// Software reset the system to enter RCM.
+    RcmEntry,
+}
+
+impl BootReason {
+    /// Decodes the boot reason from the register values of all sources.
+    fn decode(rst_status: u32, reboot_reason: u32, onoffirq: u8, nverc: u8) -> Self {
+        let source = RST_STATUS::RST_SOURCE.get(rst_status);
+        let reboot_reason = if reboot_reason >> 16 == REBOOT_REASON_MAGIC {
+            Some(reboot_reason & 0xFFFF)
+        } else {
+            None
+        };
+
+        match source {
+            RST_SOURCE_WATCHDOG => return BootReason::WatchdogReset,
+            RST_SOURCE_SENSOR => return BootReason::ThermalReset,
+            RST_SOURCE_LP0 => return BootReason::Lp0Resume,
+            RST_SOURCE_SW_MAIN if reboot_reason == Some(RebootReason::Rcm as u32) => {
+                return BootReason::RcmEntry;
+            }
+            RST_SOURCE_SW_MAIN => return BootReason::SoftwareReset,
+            _ => {}
+        }
+
+        // Otherwise the system was powered on, the PMIC events tell the cause.
+        if nverc & MAX77620_NVERC_WTCHDG != 0 {
+            BootReason::WatchdogReset
+        } else if onoffirq & MAX77620_ONOFFIRQ_EN0_R != 0 {
+            BootReason::ColdBoot(PowerOnCause::PowerButton)
+        } else if onoffirq & MAX77620_ONOFFIRQ_ACOK_R != 0 {
+            BootReason::ColdBoot(PowerOnCause::Charger)
+        } else {
+            BootReason::ColdBoot(PowerOnCause::Unknown)
+        }
+    }
+}
+
+/// Records the reason for an upcoming software reset.
+pub fn set_reboot_reason(reason: RebootReason) {
+    Scratch::REBOOT_REASON.write((REBOOT_REASON_MAGIC << 16) | reason as u32);
+}
+
+/// Determines why the system booted.
+///
+/// The PMIC is queried over `i2c`, which has to be the initialized I²C 5.
+/// If that fails, the power-on cause of a cold boot is reported as unknown.
+pub fn boot_reason(i2c: &I2c) -> BootReason {
+    let pmc = unsafe { Pmc::get() };
+
+    let rst_status = pmc.reset_status.read();
+    let reboot_reason = Scratch::REBOOT_REASON.read();
+    Scratch::REBOOT_REASON.write(0);
+
+    // Both PMIC registers are cleared by reading them.
+    let onoffirq = i2c
+        .read_byte(Device::Max77620Pwr, MAX77620_REG_ONOFFIRQ)
+        .unwrap_or(0);
+    let nverc = i2c
+        .read_byte(Device::Max77620Pwr, MAX77620_REG_NVERC)
+        .unwrap_or(0);
+
+    BootReason::decode(rst_status, reboot_reason, onoffirq, nverc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests decoding of all boot reasons.
+    #[test]
+    fn decode() {
+        let rcm = (REBOOT_REASON_MAGIC << 16) | RebootReason::Rcm as u32;
+
+        assert_eq!(BootReason::decode(1, 0, 0, 0), BootReason::WatchdogReset);
+        assert_eq!(BootReason::decode(2, 0, 0, 0), BootReason::ThermalReset);
+        assert_eq!(BootReason::decode(4, 0, 0, 0), BootReason::Lp0Resume);
+        assert_eq!(BootReason::decode(3, 0, 0, 0), BootReason::SoftwareReset);
+        assert_eq!(BootReason::decode(3, rcm, 0, 0), BootReason::RcmEntry);
+        assert_eq!(
+            BootReason::decode(3, rcm & 0xFFFF, 0, 0),
+            BootReason::SoftwareReset
+        );
+
+        assert_eq!(
+            BootReason::decode(0, 0, 0, MAX77620_NVERC_WTCHDG),
+            BootReason::WatchdogReset
+        );
+        assert_eq!(
+            BootReason::decode(0, 0, MAX77620_ONOFFIRQ_EN0_R, 0),
+            BootReason::ColdBoot(PowerOnCause::PowerButton)
+        );
+        assert_eq!(
+            BootReason::decode(0, 0, MAX77620_ONOFFIRQ_ACOK_R, 0),
+            BootReason::ColdBoot(PowerOnCause::Charger)
+        );
+        assert_eq!(
+            BootReason::decode(0, 0, 0, 0),
+            BootReason::ColdBoot(PowerOnCause::Unknown)
+        );
+    }
+}
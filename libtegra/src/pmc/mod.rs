@@ -1,6 +1,12 @@
 //! Power Management Controller registers.
 
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{assert_layout, Mmio, VolatileStorage};
+
+pub use boot_reason::*;
+pub use scratch::*;
+
+mod boot_reason;
+mod scratch;
 
 /// Base address for the PMC registers.
 pub(crate) const PMC_BASE: u32 = 0x7000_E400;
@@ -175,7 +181,7 @@ pub struct Pmc {
     pub utmip_master2_config: Mmio<u32>,
     pub utmip_uhsic_rpd_cfg: Mmio<u32>,
     pub pg_mask_ce0: Mmio<u32>,
-    pub pg_mask3: [Mmio<u32>; 3],
+    pub pg_mask3: [Mmio<u32>; 2],
     pub pllm_wb0_override2: Mmio<u32>,
     pub tsc_mult: Mmio<u32>,
     pub cpu_vsense_override: Mmio<u32>,
@@ -185,7 +191,9 @@ pub struct Pmc {
     pub weak_bias: Mmio<u32>,
     pub reg_short: Mmio<u32>,
     pub pg_mask_andor: Mmio<u32>,
-    _reserved1: [Mmio<u32>; 11],
+    pub gpu_rg_cntrl: Mmio<u32>,
+    pub sec_disable3: Mmio<u32>,
+    _reserved1: [Mmio<u32>; 9],
     pub secure_scratch8: Mmio<u32>,
     pub secure_scratch9: Mmio<u32>,
     pub secure_scratch10: Mmio<u32>,
@@ -271,7 +279,13 @@ pub struct Pmc {
     pub io_dpd4_status: Mmio<u32>,
     _reserved5: [Mmio<u32>; 30],
     pub ddr_cntrl: Mmio<u32>,
-    _reserved6: [Mmio<u32>; 70],
+    _reserved6: [Mmio<u32>; 50],
+    pub sec_disable4: Mmio<u32>,
+    pub sec_disable5: Mmio<u32>,
+    pub sec_disable6: Mmio<u32>,
+    pub sec_disable7: Mmio<u32>,
+    pub sec_disable8: Mmio<u32>,
+    _reserved7: [Mmio<u32>; 15],
     pub scratch56: Mmio<u32>,
     pub scratch57: Mmio<u32>,
     pub scratch58: Mmio<u32>,
@@ -516,7 +530,7 @@ pub struct Pmc {
     pub scratch297: Mmio<u32>,
     pub scratch298: Mmio<u32>,
     pub scratch299: Mmio<u32>,
-    _reserved7: [Mmio<u32>; 50],
+    _reserved8: [Mmio<u32>; 50],
     pub secure_scratch80: Mmio<u32>,
     pub secure_scratch81: Mmio<u32>,
     pub secure_scratch82: Mmio<u32>,
@@ -564,3 +578,39 @@ impl VolatileStorage for Pmc {
         PMC_BASE as *const _
     }
 }
+
+assert_layout!(pmc_layout, Pmc, 0xB38, {
+    cntrl: 0x000,
+    pwrgate_toggle: 0x030,
+    pwrgate_status: 0x038,
+    scratch0: 0x050,
+    secure_scratch0: 0x0B0,
+    crypto_op: 0x0F4,
+    reset_status: 0x1B4,
+    io_dpd_req: 0x1B8,
+    tsc_mult: 0x2B4,
+    sec_disable2: 0x2C4,
+    gpu_rg_cntrl: 0x2D4,
+    sec_disable3: 0x2D8,
+    secure_scratch32: 0x360,
+    fuse_control: 0x450,
+    ddr_cntrl: 0x4E4,
+    sec_disable4: 0x5B0,
+    sec_disable8: 0x5C0,
+    scratch200: 0x840,
+    secure_scratch117: 0xB2C,
+});
+
+/// Triggers a main reset in `APBDEV_PMC_CNTRL_0`.
+const PMC_CNTRL_MAIN_RST: u32 = 1 << 4;
+
+/// Reboots the system into RCM.
+pub fn reboot_to_rcm() -> ! {
+    let pmc = unsafe { Pmc::get() };
+
+    set_reboot_reason(RebootReason::Rcm);
+    Scratch::BOOT_MODE.write_field(SCRATCH0::MODE_RCM, 1);
+    pmc.cntrl.write(pmc.cntrl.read() | PMC_CNTRL_MAIN_RST);
+
+    loop {}
+}
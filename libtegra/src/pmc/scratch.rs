@@ -0,0 +1,333 @@
+//! PMC scratch register map.
+//!
+//! # Description
+//!
+//! The PMC scratch registers survive resets and low-power states, which
+//! makes them the place where the bootrom, Horizon and bootloaders leave
+//! information for each other. Many of them have a meaning that is fixed
+//! by the bootrom, others are claimed by Mirage components.
+//!
+//! # Implementation
+//!
+//! - The bitfields of registers with known semantics are defined with
+//! `register_bitfields!`, e.g. [`SCRATCH0`] for the boot mode flags.
+//!
+//! - [`Scratch`] represents a single scratch register with an assigned
+//! meaning. All registers that are used by Mirage are listed as constants
+//! of it, so that components don't clobber each other. New users should
+//! claim a register there instead of accessing the [`Pmc`] fields.
+//!
+//! - [`Scratch::COMMS`] is a small area for bootloaders to pass data to the
+//! next stage or to themselves across a reboot. Its layout is up to the user.
+//!
+//! - [`SecureScratch`] lists the secure scratch registers with a known
+//! meaning. [`Pmc::lock_secure_scratch`] revokes write or read access to a
+//! range of them until the next reset, through the `SEC_DISABLE2` to
+//! `SEC_DISABLE8` registers. Secure scratch registers 0 to 7 are locked
+//! through `SEC_DISABLE` with a different layout and aren't supported.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::pmc::{Scratch, SCRATCH0};
+//!
+//! fn main() {
+//!     // Enter RCM on the next reboot.
+//!     Scratch::BOOT_MODE.write_field(SCRATCH0::MODE_RCM, 1);
+//! }
+//! ```
+//!
+//! [`SCRATCH0`]: SCRATCH0/index.html
+//! [`Scratch`]: struct.Scratch.html
+//! [`Pmc`]: struct.Pmc.html
+//! [`Scratch::COMMS`]: struct.Scratch.html#associatedconstant.COMMS
+//! [`SecureScratch`]: struct.SecureScratch.html
+//! [`Pmc::lock_secure_scratch`]: struct.Pmc.html#method.lock_secure_scratch
+
+use core::ops::Range;
+
+use mirage_mmio::{register_bitfields, BlockMmio, Field, Mmio};
+
+use super::{Pmc, PMC_BASE};
+use crate::{Error, Result};
+
+register_bitfields! {
+    u32,
+    /// The `APBDEV_PMC_SCRATCH0_0` register, evaluated by the bootrom.
+    pub SCRATCH0 [
+        /// Resume from LP0 through the warmboot firmware.
+        WARMBOOT0 OFFSET(0) NUMBITS(1),
+        /// Enter RCM instead of booting.
+        MODE_RCM OFFSET(1) NUMBITS(1),
+        /// Stay in the bootloader.
+        MODE_BOOTLOADER OFFSET(30) NUMBITS(1),
+        /// Boot into recovery.
+        MODE_RECOVERY OFFSET(31) NUMBITS(1),
+    ],
+    /// The `APBDEV_PMC_SCRATCH20_0` register.
+    pub SCRATCH20 [
+        /// The debug console selection of the customer option.
+        DEBUG_CONSOLE OFFSET(18) NUMBITS(2),
+    ],
+    /// The `APBDEV_PMC_SCRATCH49_0` register.
+    pub SCRATCH49 [
+        /// Undocumented flags which the bootrom clears on a regular boot.
+        BOOTROM_FLAGS OFFSET(0) NUMBITS(2),
+    ],
+    /// The `APBDEV_PMC_SCRATCH188_0` register, restored on LP0 resume.
+    pub SCRATCH188 [
+        /// `EMC2TMC_CFG_XM2COMP_PU_VREF_SEL_RANGE` of the EMC.
+        XM2COMP_PU_VREF_SEL_RANGE OFFSET(24) NUMBITS(2),
+    ],
+    /// The `APBDEV_PMC_SCRATCH190_0` register, restored on LP0 resume.
+    pub SCRATCH190 [
+        /// `DATA_DQ_E_IVREF` of `EMC_PMACRO_DATA_PAD_TX_CTRL`.
+        DATA_DQ_E_IVREF OFFSET(0) NUMBITS(1),
+    ],
+    /// The `APBDEV_PMC_SECURE_SCRATCH21_0` register.
+    pub SECURE_SCRATCH21 [
+        /// Keeps the TrustZone private key disabled across LP0.
+        PRIVATEKEYDISABLE_TZ_STICKY OFFSET(4) NUMBITS(1),
+    ],
+}
+
+/// Representation of a scratch register with an assigned meaning.
+pub struct Scratch {
+    /// The scratch register.
+    register: BlockMmio<Mmio<u32>>,
+}
+
+// Definitions of the scratch registers used by Mirage.
+impl Scratch {
+    /// The boot mode flags in `SCRATCH0`, see [`SCRATCH0`].
+    ///
+    /// [`SCRATCH0`]: SCRATCH0/index.html
+    pub const BOOT_MODE: Self = Scratch::new(0x50);
+
+    /// The address of the warmboot firmware in `SCRATCH1`, which the
+    /// bootrom jumps to when resuming from LP0.
+    pub const WARMBOOT_ADDRESS: Self = Scratch::new(0x54);
+
+    /// The reason of the last software-initiated reset in `SCRATCH200`.
+    pub const REBOOT_REASON: Self = Scratch::new(0x840);
+
+    /// The checksum of the crash record in IRAM in `SCRATCH201`, which
+    /// tells a record of the previous boot apart from stale memory.
+    pub const CRASHLOG: Self = Scratch::new(0x844);
+
+    /// The A/B boot slot state of [`bootctl`] in `SCRATCH202`.
+    ///
+    /// [`bootctl`]: ../bootctl/index.html
+    pub const BOOT_CONTROL: Self = Scratch::new(0x848);
+
+    /// The RTC drift in ppm and a magic value in `SCRATCH250`.
+    pub const RTC_DRIFT: Self = Scratch::new(0x908);
+
+    /// The reference time of the RTC drift correction in `SCRATCH251`.
+    pub const RTC_DRIFT_REFERENCE: Self = Scratch::new(0x90C);
+
+    /// The bootloader communication area in `SCRATCH252` to `SCRATCH255`.
+    pub const COMMS: [Self; 4] = [
+        Scratch::new(0x910),
+        Scratch::new(0x914),
+        Scratch::new(0x918),
+        Scratch::new(0x91C),
+    ];
+}
+
+impl Scratch {
+    /// Creates a scratch register at the given offset into the PMC.
+    const fn new(offset: u32) -> Self {
+        Scratch {
+            register: unsafe { BlockMmio::new((PMC_BASE + offset) as usize) },
+        }
+    }
+
+    /// Reads the value of the register.
+    pub fn read(&self) -> u32 {
+        self.register.read()
+    }
+
+    /// Writes a value to the register.
+    pub fn write(&self, value: u32) {
+        self.register.write(value)
+    }
+
+    /// Reads a field of the register.
+    pub fn read_field(&self, field: Field<u32>) -> u32 {
+        self.register.read_field(field)
+    }
+
+    /// Writes a field of the register, leaving the other bits intact.
+    pub fn write_field(&self, field: Field<u32>, value: u32) {
+        self.register.write_field(field, value)
+    }
+}
+
+bitflags! {
+    /// Access to a secure scratch register that can be revoked.
+    pub struct SecureScratchLock: u32 {
+        /// The register can no longer be written.
+        const WRITE = 1 << 0;
+        /// The register can no longer be read.
+        const READ = 1 << 1;
+    }
+}
+
+/// The first secure scratch register that can be locked.
+const LOCKABLE_SECURE_SCRATCH_START: usize = 8;
+
+/// The secure scratch registers covered by one `SEC_DISABLE` register.
+const SECURE_SCRATCH_PER_SEC_DISABLE: usize = 16;
+
+/// A range of secure scratch registers with an assigned meaning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecureScratch {
+    /// The index of the first register.
+    pub first: usize,
+    /// The amount of registers.
+    pub count: usize,
+}
+
+// Definitions of the known secure scratch registers.
+impl SecureScratch {
+    /// The Secure Rekeying Key of the saved SE context in `SECURE_SCRATCH4`
+    /// to `SECURE_SCRATCH7`, written by the SE itself.
+    pub const SE_SRK: Self = SecureScratch::new(4, 4);
+
+    /// The sticky TrustZone private key disable in `SECURE_SCRATCH21`, see
+    /// [`SECURE_SCRATCH21`].
+    ///
+    /// [`SECURE_SCRATCH21`]: SECURE_SCRATCH21/index.html
+    pub const PRIVATE_KEY_DISABLE: Self = SecureScratch::new(21, 1);
+
+    /// The address the CPU resumes at after warmboot in `SECURE_SCRATCH34`
+    /// and `SECURE_SCRATCH35`.
+    pub const WARMBOOT_RESUME_ADDRESS: Self = SecureScratch::new(34, 2);
+}
+
+impl SecureScratch {
+    /// Creates a range of `count` registers starting at `first`.
+    pub const fn new(first: usize, count: usize) -> Self {
+        SecureScratch { first, count }
+    }
+
+    /// Gets the indices of the registers.
+    pub fn range(&self) -> Range<usize> {
+        self.first..self.first + self.count
+    }
+}
+
+impl Pmc {
+    /// Gets the `SEC_DISABLE` register and bit covering a secure scratch
+    /// register.
+    fn sec_disable(&self, index: usize) -> Result<(&Mmio<u32>, u32)> {
+        let registers = [
+            &self.sec_disable2,
+            &self.sec_disable3,
+            &self.sec_disable4,
+            &self.sec_disable5,
+            &self.sec_disable6,
+            &self.sec_disable7,
+            &self.sec_disable8,
+        ];
+
+        let relative = index
+            .checked_sub(LOCKABLE_SECURE_SCRATCH_START)
+            .ok_or(Error::InvalidArgument)?;
+        let register = registers
+            .get(relative / SECURE_SCRATCH_PER_SEC_DISABLE)
+            .ok_or(Error::InvalidArgument)?;
+
+        Ok((
+            register,
+            (relative % SECURE_SCRATCH_PER_SEC_DISABLE) as u32 * 2,
+        ))
+    }
+
+    /// Revokes access to a range of secure scratch registers.
+    ///
+    /// The lock is sticky until the next reset. Fails with
+    /// [`Error::InvalidArgument`] if the range includes registers that
+    /// can't be locked, in which case nothing is changed.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn lock_secure_scratch(&self, range: Range<usize>, lock: SecureScratchLock) -> Result<()> {
+        for index in range.clone() {
+            self.sec_disable(index)?;
+        }
+
+        for index in range {
+            let (register, shift) = self.sec_disable(index)?;
+            register.write(register.read() | (lock.bits() << shift));
+        }
+
+        Ok(())
+    }
+
+    /// Gets the revoked access to a secure scratch register.
+    pub fn secure_scratch_lock(&self, index: usize) -> Result<SecureScratchLock> {
+        let (register, shift) = self.sec_disable(index)?;
+
+        Ok(SecureScratchLock::from_bits_truncate(
+            register.read() >> shift,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::{mock, VolatileStorage};
+
+    use super::*;
+
+    /// Tests that the named registers match the PMC register block.
+    #[test]
+    fn layout() {
+        let _session = mock::start();
+        let pmc = unsafe { Pmc::get() };
+
+        Scratch::WARMBOOT_ADDRESS.write(0x4000_0000);
+        Scratch::REBOOT_REASON.write(0x1234);
+        Scratch::BOOT_CONTROL.write(0x9ABC);
+        Scratch::COMMS[3].write(0x5678);
+        assert_eq!(pmc.scratch1.read(), 0x4000_0000);
+        assert_eq!(pmc.scratch200.read(), 0x1234);
+        assert_eq!(pmc.scratch202.read(), 0x9ABC);
+        assert_eq!(pmc.scratch255.read(), 0x5678);
+
+        pmc.scratch0.write(0x8000_0001);
+        Scratch::BOOT_MODE.write_field(SCRATCH0::MODE_RCM, 1);
+        assert_eq!(pmc.scratch0.read(), 0x8000_0003);
+        assert_eq!(Scratch::BOOT_MODE.read_field(SCRATCH0::WARMBOOT0), 1);
+    }
+
+    /// Tests locking ranges of secure scratch registers.
+    #[test]
+    fn lock_secure_scratch() {
+        let _session = mock::start();
+        let pmc = unsafe { Pmc::get() };
+
+        pmc.lock_secure_scratch(
+            SecureScratch::PRIVATE_KEY_DISABLE.range(),
+            SecureScratchLock::WRITE,
+        )
+        .unwrap();
+        pmc.lock_secure_scratch(22..25, SecureScratchLock::all())
+            .unwrap();
+        assert_eq!(pmc.sec_disable2.read(), 0xF400_0000);
+        assert_eq!(pmc.sec_disable3.read(), 0x3);
+        assert_eq!(pmc.secure_scratch_lock(24), Ok(SecureScratchLock::all()));
+
+        assert_eq!(
+            pmc.lock_secure_scratch(118..121, SecureScratchLock::READ),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            pmc.lock_secure_scratch(SecureScratch::SE_SRK.range(), SecureScratchLock::WRITE),
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(pmc.sec_disable8.read(), 0);
+        assert_eq!(pmc.secure_scratch_lock(119), Ok(SecureScratchLock::empty()));
+    }
+}
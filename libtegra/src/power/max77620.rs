@@ -36,14 +36,19 @@
 //! and [`Regulator::disable`]. Voltage and FPS may be configured with
 //! [`Regulator::set_voltage`] and [`Regulator::config_fps`].
 //!
+//! - All of these take the I²C controller the PMIC is attached to, which is
+//! I²C 5 on the Switch.
+//!
 //! # Example
 //!
 //! ```
-//! use mirage_libtegra::power::max77620::Regulator;
+//! use mirage_libtegra::{peripherals::Peripherals, power::max77620::Regulator};
 //!
 //! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
 //!     // Configure all regulators.
-//!     Regulator::config_default();
+//!     Regulator::config_default(&peripherals.i2c_5);
 //! }
 //! ```
 //!
@@ -58,6 +63,7 @@
 use crate::{
     i2c::{I2c, Device},
     timer::usleep,
+    Error, Result,
 };
 
 const REGULATOR_SD: u8 = 0;
@@ -385,108 +391,89 @@ impl From<u8> for Regulator<'_> {
 
 impl<'a> Regulator<'a> {
     /// Configures all regulators with the default configuration options.
-    pub fn config_default() {
+    pub fn config_default(i2c: &I2c) {
         for _ in 1..13 {
-            match I2c::C5.read_byte(Device::Max77620Pwr, 0x5C) {
+            match i2c.read_byte(Device::Max77620Pwr, 0x5C) {
                 Ok(value) => {
                     let regulator = Regulator::from(value);
-                    regulator.config_fps().unwrap();
-                    regulator.set_voltage(regulator.mv_default).unwrap();
+                    regulator.config_fps(i2c).unwrap();
+                    regulator.set_voltage(i2c, regulator.mv_default).unwrap();
 
                     if regulator.fps_src != 0x3 {
-                        regulator.enable();
+                        regulator.enable(i2c);
                     }
                 },
                 Err(_) => {},
             };
         }
 
-        I2c::C5.write_byte(Device::Max77620Pwr, 0x22, 4).unwrap();
+        i2c.write_byte(Device::Max77620Pwr, 0x22, 4).unwrap();
     }
 
     /// Configures all regulators for low battery monitoring.
-    pub fn low_battery_monitor_config() {
-        I2c::C5.write_byte(Device::Max77620Pwr, 0, 0x92).unwrap();
+    pub fn low_battery_monitor_config(i2c: &I2c) {
+        i2c.write_byte(Device::Max77620Pwr, 0, 0x92).unwrap();
     }
 
     /// Enables or disables the regulator.
-    fn set_enable(&self, set_enable: bool) -> Result<(), ()> {
+    fn set_enable(&self, i2c: &I2c, set_enable: bool) -> Result<()> {
         let addr = if self.regulator_type == REGULATOR_SD {
             self.cfg_addr
         } else {
             self.volt_addr
         };
 
-        match I2c::C5.read_byte(Device::Max77620Pwr, addr) {
-            Ok(mut value) => {
-                if set_enable {
-                    value =
-                        (value & !self.enable_mask) | ((3 << self.enable_shift) & self.enable_mask);
-                } else {
-                    value &= !self.enable_mask;
-                }
-
-                if I2c::C5
-                    .write_byte(Device::Max77620Pwr, addr, value)
-                    .is_ok()
-                {
-                    usleep(1000);
-                    return Ok(());
-                }
-
-                Err(())
-            },
-            Err(_) => Err(()),
+        let mut value = i2c.read_byte(Device::Max77620Pwr, addr)?;
+        if set_enable {
+            value = (value & !self.enable_mask) | ((3 << self.enable_shift) & self.enable_mask);
+        } else {
+            value &= !self.enable_mask;
         }
+
+        i2c.write_byte(Device::Max77620Pwr, addr, value)?;
+        usleep(1000);
+
+        Ok(())
     }
 
     /// Enables the regulator.
-    pub fn enable(&self) {
-        self.set_enable(true).unwrap();
+    pub fn enable(&self, i2c: &I2c) {
+        self.set_enable(i2c, true).unwrap();
     }
 
     /// Disables the regulator.
-    pub fn disable(&self) {
-        self.set_enable(false).unwrap();
+    pub fn disable(&self, i2c: &I2c) {
+        self.set_enable(i2c, false).unwrap();
     }
 
     /// Configures the FPS value of the regulator.
-    pub fn config_fps(&self) -> Result<(), ()> {
+    pub fn config_fps(&self, i2c: &I2c) -> Result<()> {
         let value = (self.fps_src << 6) | (self.pu_period << 3) | self.pd_period;
 
-        if I2c::C5
-            .write_byte(Device::Max77620Pwr, self.fps_addr, value)
-            .is_ok()
-        {
-            Ok(())
-        } else {
-            Err(())
-        }
+        i2c.write_byte(Device::Max77620Pwr, self.fps_addr, value)?;
+
+        Ok(())
     }
 
     /// Sets the voltage of the regulator.
-    pub fn set_voltage(&self, mv: u32) -> Result<(), ()> {
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the voltage is out of
+    /// the range supported by the regulator.
+    ///
+    /// [`Error::InvalidArgument`]: ../../enum.Error.html#variant.InvalidArgument
+    pub fn set_voltage(&self, i2c: &I2c, mv: u32) -> Result<()> {
         if mv < self.mv_default || mv > self.mv_max {
-            return Err(());
+            return Err(Error::InvalidArgument);
         }
 
         let mult = (mv + self.mv_step - 1 - self.mv_min) / self.mv_step;
 
-        match I2c::C5.read_byte(Device::Max77620Pwr, self.volt_addr) {
-            Ok(mut value) => {
-                value = (value & !self.volt_mask) | (mult & self.volt_mask as u32) as u8;
-
-                if I2c::C5
-                    .write_byte(Device::Max77620Pwr, self.volt_addr, value)
-                    .is_ok()
-                {
-                    usleep(1000);
-                    return Ok(());
-                }
-
-                Err(())
-            },
-            Err(_) => Err(()),
-        }
+        let mut value = i2c.read_byte(Device::Max77620Pwr, self.volt_addr)?;
+        value = (value & !self.volt_mask) | (mult & self.volt_mask as u32) as u8;
+
+        i2c.write_byte(Device::Max77620Pwr, self.volt_addr, value)?;
+        usleep(1000);
+
+        Ok(())
     }
 }
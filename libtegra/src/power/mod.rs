@@ -1,41 +1,44 @@
 //! Drivers for Nintendo Switch power components.
 
-use crate::i2c::{I2c, Error, Device};
+use crate::i2c::{I2c, I2cError, Device};
 
 pub mod max77620;
 
 /// Sets a bit in a PMIC register over I²C during CPU shutdown.
+///
+/// The PMIC is attached to I²C 5, the TI charger used by the other
+/// functions to I²C 1.
 #[inline]
-pub fn send_pmic_cpu_shutdown_cmd() -> Result<(), Error> {
+pub fn send_pmic_cpu_shutdown_cmd(i2c: &I2c) -> Result<(), I2cError> {
     // PMIC == Device 4:3C.
-    let value = I2c::C5.read_byte(Device::Max77620Pwr, 0x41)?;
+    let value = i2c.read_byte(Device::Max77620Pwr, 0x41)?;
 
-    I2c::C5.write_byte(Device::Max77620Pwr, 0x41, value | 4)
+    i2c.write_byte(Device::Max77620Pwr, 0x41, value | 4)
 }
 
 /// Reads the value of TI charger bit over I²C.
 #[inline]
-pub fn read_ti_charger_bit_7() -> Result<bool, Error> {
+pub fn read_ti_charger_bit_7(i2c: &I2c) -> Result<bool, I2cError> {
     // TI Charger = Device 0:6B.
-    let value = I2c::C1.read_byte(Device::Bq24193, 0).unwrap();
+    let value = i2c.read_byte(Device::Bq24193, 0)?;
 
     Ok((value & 0x80) != 0)
 }
 
 /// Clears TI charger bit over I²C.
 #[inline]
-pub fn clear_ti_charger_bit_7() -> Result<(), Error> {
+pub fn clear_ti_charger_bit_7(i2c: &I2c) -> Result<(), I2cError> {
     // TI Charger = Device 0:6B.
-    let value = I2c::C1.read_byte(Device::Bq24193, 0)?;
+    let value = i2c.read_byte(Device::Bq24193, 0)?;
 
-    I2c::C1.write_byte(Device::Bq24193, 0, value & 0x7F)
+    i2c.write_byte(Device::Bq24193, 0, value & 0x7F)
 }
 
 /// Sets TI charger bit over I²C.
 #[inline]
-pub fn set_ti_charger_bit_7() -> Result<(), Error> {
+pub fn set_ti_charger_bit_7(i2c: &I2c) -> Result<(), I2cError> {
     // TI Charger = Device 0:6B.
-    let value = I2c::C1.read_byte(Device::Bq24193, 0)?;
+    let value = i2c.read_byte(Device::Bq24193, 0)?;
 
-    I2c::C1.write_byte(Device::Bq24193, 0, value | 0x80)
+    i2c.write_byte(Device::Bq24193, 0, value | 0x80)
 }
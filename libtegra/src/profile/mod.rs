@@ -0,0 +1,268 @@
+//! Boot-time profiling of init stages and driver operations.
+//!
+//! # Description
+//!
+//! Regressions like slow SDRAM training or a long SD card initialization
+//! only show as a longer boot. To quantify them, init stages and major
+//! driver operations record how long they took, using the microsecond
+//! timer. The resulting breakdown can be printed over UART or on the
+//! display.
+//!
+//! This is only built with the `profile` feature, so regular builds don't
+//! pay for the instrumentation.
+//!
+//! # Implementation
+//!
+//! - [`begin`] starts a [`Span`], which records its duration once it is
+//! finished or dropped. [`measure`] does the same around a closure.
+//!
+//! - Spans started while another one is running are nested into it, which
+//! is reflected by the depth of their [`Record`].
+//!
+//! - Records are kept in a fixed-size table of [`MAX_RECORDS`] entries,
+//! no allocations are involved. Spans beyond that are counted, but not
+//! recorded.
+//!
+//! - [`records`] iterates over all records in the order they were started,
+//! [`dump`] writes them as a boot-time report. [`reset`] clears them.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{peripherals::Peripherals, profile};
+//!
+//! fn main() {
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!
+//!     {
+//!         let _span = profile::begin("sdram");
+//!
+//!         // Initialize SDRAM.
+//!     }
+//!
+//!     profile::dump(&mut peripherals.uart_a).unwrap();
+//! }
+//! ```
+//!
+//! [`begin`]: fn.begin.html
+//! [`Span`]: struct.Span.html
+//! [`measure`]: fn.measure.html
+//! [`Record`]: struct.Record.html
+//! [`MAX_RECORDS`]: constant.MAX_RECORDS.html
+//! [`records`]: fn.records.html
+//! [`dump`]: fn.dump.html
+//! [`reset`]: fn.reset.html
+
+use core::fmt;
+
+use crate::timer::{get_microseconds, get_time_since};
+
+/// The maximum number of records that can be kept at once.
+pub const MAX_RECORDS: usize = 32;
+
+/// The width of the name column in the report.
+const NAME_WIDTH: usize = 24;
+
+/// The time a span took.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The name of the span.
+    pub name: &'static str,
+    /// The start of the span, in microseconds since the timer was started.
+    pub start: u32,
+    /// The duration of the span in microseconds, if it has finished.
+    pub duration: Option<u32>,
+    /// The number of spans this one is nested in.
+    pub depth: u8,
+}
+
+static mut RECORDS: [Option<Record>; MAX_RECORDS] = [None; MAX_RECORDS];
+
+static mut DEPTH: u8 = 0;
+
+static mut DROPPED: u32 = 0;
+
+/// A running span, which records its duration when it is finished.
+#[must_use = "the span is finished as soon as it is dropped"]
+pub struct Span {
+    /// The slot of the record, if one was free.
+    slot: Option<usize>,
+}
+
+impl Span {
+    /// Finishes the span, which is the same as dropping it.
+    pub fn finish(self) {}
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        unsafe {
+            DEPTH = DEPTH.saturating_sub(1);
+
+            if let Some(Some(record)) = self.slot.map(|slot| RECORDS[slot].as_mut()) {
+                record.duration = Some(get_time_since(record.start));
+            }
+        }
+    }
+}
+
+/// Starts a span with the given name.
+pub fn begin(name: &'static str) -> Span {
+    unsafe {
+        let slot = RECORDS.iter().position(Option::is_none);
+
+        match slot {
+            Some(slot) => {
+                RECORDS[slot] = Some(Record {
+                    name,
+                    start: get_microseconds(),
+                    duration: None,
+                    depth: DEPTH,
+                });
+            }
+            None => DROPPED += 1,
+        }
+
+        DEPTH = DEPTH.saturating_add(1);
+
+        Span { slot }
+    }
+}
+
+/// Runs a closure inside of a span with the given name.
+pub fn measure<T, F: FnOnce() -> T>(name: &'static str, f: F) -> T {
+    let _span = begin(name);
+
+    f()
+}
+
+/// Iterates over all records, in the order their spans were started.
+pub fn records() -> impl Iterator<Item = Record> {
+    unsafe { RECORDS.iter().filter_map(|record| *record) }
+}
+
+/// Gets the number of spans which were not recorded, as all slots were in use.
+pub fn dropped() -> u32 {
+    unsafe { DROPPED }
+}
+
+/// Clears all records.
+///
+/// This must not be called while spans are running.
+pub fn reset() {
+    unsafe {
+        RECORDS = [None; MAX_RECORDS];
+        DEPTH = 0;
+        DROPPED = 0;
+    }
+}
+
+/// Writes a milliseconds value with three decimals.
+fn write_micros<W: fmt::Write>(writer: &mut W, micros: u32) -> fmt::Result {
+    write!(writer, "{:>6}.{:03}ms", micros / 1000, micros % 1000)
+}
+
+/// Writes a report of all records to the given writer.
+///
+/// Each line holds the start of a span, its name indented by its depth and
+/// its duration. The sum of all top-level spans is written last.
+pub fn dump<W: fmt::Write>(writer: &mut W) -> fmt::Result {
+    let mut total = 0u32;
+
+    for record in records() {
+        let indent = 2 * record.depth as usize;
+
+        write_micros(writer, record.start)?;
+        write!(
+            writer,
+            " {:indent$}{:<width$}",
+            "",
+            record.name,
+            indent = indent,
+            width = NAME_WIDTH.saturating_sub(indent)
+        )?;
+
+        match record.duration {
+            Some(duration) => {
+                if record.depth == 0 {
+                    total = total.saturating_add(duration);
+                }
+
+                write_micros(writer, duration)?;
+                writeln!(writer)?;
+            }
+            None => writeln!(writer, "   running")?,
+        }
+    }
+
+    write!(writer, "{:>12} {:<width$}", "", "total", width = NAME_WIDTH)?;
+    write_micros(writer, total)?;
+    writeln!(writer)?;
+
+    if dropped() != 0 {
+        writeln!(writer, "{} spans were not recorded", dropped())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use core::str;
+
+    use mirage_mmio::{mock, VolatileStorage};
+
+    use super::*;
+    use crate::timer::TimerRegisters;
+
+    struct Buffer([u8; 256], usize);
+
+    impl fmt::Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0[self.1..self.1 + s.len()].copy_from_slice(s.as_bytes());
+            self.1 += s.len();
+            Ok(())
+        }
+    }
+
+    fn record(name: &'static str, start: u32, duration: u32, depth: u8) -> Option<Record> {
+        Some(Record {
+            name,
+            start,
+            duration: Some(duration),
+            depth,
+        })
+    }
+
+    /// Tests recording nested spans and writing the report.
+    #[test]
+    fn records_spans() {
+        let _session = mock::start();
+        let counter = unsafe { &TimerRegisters::get().TIMERUS_CNTR_1US as *const _ as usize };
+
+        reset();
+
+        mock::set(counter, 1_000);
+        let stage = begin("stage");
+        mock::set(counter, 1_500);
+        measure("driver", || mock::set(counter, 3_750));
+        mock::set(counter, 4_000);
+        stage.finish();
+
+        let mut records = records();
+        assert_eq!(records.next(), record("stage", 1_000, 3_000, 0));
+        assert_eq!(records.next(), record("driver", 1_500, 2_250, 1));
+        assert_eq!(records.next(), None);
+
+        let mut buffer = Buffer([0; 256], 0);
+        dump(&mut buffer).unwrap();
+
+        let mut lines = str::from_utf8(&buffer.0[..buffer.1]).unwrap().lines();
+        assert_eq!(lines.next(), Some("     1.000ms stage                        3.000ms"));
+        assert_eq!(lines.next(), Some("     1.500ms   driver                     2.250ms"));
+        assert_eq!(lines.next(), Some("             total                        3.000ms"));
+        assert_eq!(lines.next(), None);
+
+        reset();
+    }
+}
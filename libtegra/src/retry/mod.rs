@@ -0,0 +1,165 @@
+//! Retry policies for transient hardware errors.
+//!
+//! # Description
+//!
+//! Some hardware operations may fail spuriously on marginal hardware,
+//! e.g. I²C transfers that aren't acknowledged or crypto operations
+//! that report transient errors. Long-running jobs like dumps should
+//! be resilient to such errors, while interactive paths should rather
+//! fail fast.
+//!
+//! # Implementation
+//!
+//! - The [`RetryPolicy`] struct describes how often an operation should
+//! be attempted and how long to back off between attempts.
+//!
+//! - [`RetryPolicy::DEFAULT`] is a conservative policy for general use,
+//! [`RetryPolicy::FAIL_FAST`] doesn't retry at all and
+//! [`RetryPolicy::RESILIENT`] is meant for long-running jobs.
+//!
+//! - The backoff doubles after every failed attempt, up to a configurable
+//! maximum. A pseudo-random jitter derived from the microsecond timer is
+//! added on top to avoid lockstep retries.
+//!
+//! - [`RetryPolicy::run`] retries on every error, [`RetryPolicy::run_if`]
+//! takes a predicate to decide whether an error is transient.
+//!
+//! - [`I2c::read_with`], [`I2c::write_with`],
+//! [`SecurityEngine::perform_aes_block_operation_with`] and
+//! [`Sdmmc::read_blocks_with`] accept a policy directly.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{i2c::Device, peripherals::Peripherals, retry::RetryPolicy};
+//!
+//! fn main() {
+//!     let i2c = Peripherals::take().unwrap().i2c_5;
+//!
+//!     let value = RetryPolicy::DEFAULT
+//!         .run(|| i2c.read_byte(Device::Max77620Pwr, 0x15))
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! [`RetryPolicy`]: struct.RetryPolicy.html
+//! [`RetryPolicy::DEFAULT`]: struct.RetryPolicy.html#associatedconstant.DEFAULT
+//! [`RetryPolicy::FAIL_FAST`]: struct.RetryPolicy.html#associatedconstant.FAIL_FAST
+//! [`RetryPolicy::RESILIENT`]: struct.RetryPolicy.html#associatedconstant.RESILIENT
+//! [`RetryPolicy::run`]: struct.RetryPolicy.html#method.run
+//! [`RetryPolicy::run_if`]: struct.RetryPolicy.html#method.run_if
+//! [`I2c::read_with`]: ../i2c/struct.I2c.html#method.read_with
+//! [`I2c::write_with`]: ../i2c/struct.I2c.html#method.write_with
+//! [`SecurityEngine::perform_aes_block_operation_with`]: ../se/struct.SecurityEngine.html#method.perform_aes_block_operation_with
+//! [`Sdmmc::read_blocks_with`]: ../sdmmc/struct.Sdmmc.html#method.read_blocks_with
+
+use crate::timer::{get_microseconds, usleep};
+
+/// A policy describing how to retry failed operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// The delay in microseconds before the first retry.
+    pub backoff: u32,
+    /// The upper bound for the delay in microseconds between retries.
+    pub max_backoff: u32,
+    /// The maximum random delay in microseconds added to each backoff.
+    pub jitter: u32,
+}
+
+// Definitions for common policies.
+impl RetryPolicy {
+    /// A conservative policy for general use.
+    pub const DEFAULT: Self = RetryPolicy {
+        max_attempts: 3,
+        backoff: 100,
+        max_backoff: 1_000,
+        jitter: 50,
+    };
+
+    /// A policy that doesn't retry, meant for interactive paths.
+    pub const FAIL_FAST: Self = RetryPolicy {
+        max_attempts: 1,
+        backoff: 0,
+        max_backoff: 0,
+        jitter: 0,
+    };
+
+    /// A policy for long-running jobs on marginal hardware.
+    pub const RESILIENT: Self = RetryPolicy {
+        max_attempts: 10,
+        backoff: 1_000,
+        max_backoff: 100_000,
+        jitter: 500,
+    };
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::DEFAULT
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy.
+    pub const fn new(max_attempts: u32, backoff: u32, max_backoff: u32, jitter: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff,
+            max_backoff,
+            jitter,
+        }
+    }
+
+    /// Calculates the delay in microseconds before the given retry.
+    fn delay(&self, retry: u32) -> u32 {
+        let backoff = self
+            .backoff
+            .checked_shl(retry)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        let jitter = if self.jitter != 0 {
+            // Scramble the timer bits to get a cheap pseudo-random value.
+            get_microseconds().wrapping_mul(0x9E37_79B9).rotate_left(16) % (self.jitter + 1)
+        } else {
+            0
+        };
+
+        backoff.saturating_add(jitter)
+    }
+
+    /// Runs an operation, retrying it whenever it fails.
+    pub fn run<T, E, F>(&self, operation: F) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        self.run_if(operation, |_| true)
+    }
+
+    /// Runs an operation, retrying it as long as the returned error is
+    /// considered transient by the given predicate.
+    pub fn run_if<T, E, F, P>(&self, mut operation: F, is_transient: P) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&E) -> bool,
+    {
+        let mut attempt = 0;
+
+        loop {
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) => {
+                    attempt += 1;
+
+                    if attempt >= self.max_attempts || !is_transient(&error) {
+                        return Err(error);
+                    }
+
+                    usleep(self.delay(attempt - 1));
+                }
+            }
+        }
+    }
+}
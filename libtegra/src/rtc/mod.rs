@@ -5,14 +5,39 @@
 //! The Maxim77620 exposes an additional interface to the RTC over I²C
 //! for reading current time as accurate as possible.
 //!
+//! Besides the current time, the RTC provides two alarms which can
+//! raise interrupts and wake the system from deep sleep.
+//!
 //! # Implementation
 //!
 //! - The [`RtcTime`] is a representation of such an RTC time.
 //! All the attributes are publicly accessible for people who
 //! want to do formatting or comparisons.
 //!
-//! - [`RtcTime::new`] crates a new instance of this struct, using the
-//! RTC values that were read over the I2C 5 controller.
+//! - [`init`] configures the RTC for binary and 24-hour mode,
+//! which is what this driver operates on.
+//!
+//! - [`RtcTime::read`] reads the current time over the I2C 5 controller,
+//! [`RtcTime::now`] does the same, but panics on I²C errors. Like all
+//! functions accessing the PMIC, they take the handle of that controller.
+//!
+//! - [`RtcTime::write`] sets the RTC to a given point in time.
+//!
+//! - [`RtcTime::to_unix`] and [`RtcTime::from_unix`] convert from and
+//! to seconds since the Unix epoch which makes calculations easier.
+//!
+//! - [`set_alarm`], [`clear_alarm`] and [`alarm_pending`] control the
+//! first RTC alarm. If requested, the PMIC is configured to wake up the
+//! system once the alarm fires.
+//!
+//! - [`monotonic_seconds`] provides a 64-bit seconds counter based on the
+//! always-on Tegra RTC, which is suitable for timeouts that outlive the
+//! wrapping microsecond timer.
+//!
+//! - [`DriftCorrection`] compensates the drift of the PMIC RTC. The factor
+//! in ppm and the point in time the RTC was last calibrated are kept in PMC
+//! scratch registers, which survive reboots. [`RtcTime::read_corrected`]
+//! returns the RTC time with the correction applied.
 //!
 //! - The [`Display`] trait is implemented for a human-readable
 //! representation of the current point in time.
@@ -20,22 +45,79 @@
 //! # Example
 //!
 //! ```
-//! use mirage_libtegra::rtc::RtcTime;
+//! use mirage_libtegra::{
+//!     peripherals::Peripherals,
+//!     rtc::{self, RtcTime},
+//! };
 //!
 //! fn main() {
-//!     let time = RtcTime::new();
+//!     let i2c = Peripherals::take().unwrap().i2c_5;
+//!     let time = RtcTime::now(&i2c);
 //!
-//!     println!("{:?}", time); // Saturday, November 09, 2019 17:39:36
+//!     println!("{}", time); // Saturday, November 09, 2019 17:39:36
+//!
+//!     // Wake up in one minute.
+//!     let now = time.to_unix().unwrap();
+//!     rtc::set_alarm(&i2c, &RtcTime::from_unix(now + 60), true).unwrap();
 //! }
 //! ```
 //!
 //! [`RtcTime`]: struct.RtcTime.html
-//! [`RtcTime::new`]: struct.RtcTime.html#method.new
+//! [`init`]: fn.init.html
+//! [`RtcTime::read`]: struct.RtcTime.html#method.read
+//! [`RtcTime::now`]: struct.RtcTime.html#method.now
+//! [`RtcTime::write`]: struct.RtcTime.html#method.write
+//! [`RtcTime::to_unix`]: struct.RtcTime.html#method.to_unix
+//! [`RtcTime::from_unix`]: struct.RtcTime.html#method.from_unix
+//! [`set_alarm`]: fn.set_alarm.html
+//! [`clear_alarm`]: fn.clear_alarm.html
+//! [`alarm_pending`]: fn.alarm_pending.html
+//! [`monotonic_seconds`]: fn.monotonic_seconds.html
+//! [`DriftCorrection`]: struct.DriftCorrection.html
+//! [`RtcTime::read_corrected`]: struct.RtcTime.html#method.read_corrected
 //! [`Display`]: https://doc.rust-lang.org/core/fmt/trait.Display.html
 
 use core::fmt;
 
-use crate::i2c::{I2c, Device};
+use crate::{
+    i2c::{Device, I2c, I2cError},
+    pmc::Scratch,
+    timer::{get_seconds, msleep},
+};
+
+/// The `RTCINT` register.
+const MAX77620_RTC_INT_REG: u8 = 0x00;
+/// The `RTCINTM` register.
+const MAX77620_RTC_INTM_REG: u8 = 0x01;
+/// The `RTCCNTLM` register.
+const MAX77620_RTC_CONTROLM_REG: u8 = 0x02;
+/// The `RTCCNTL` register.
+const MAX77620_RTC_CONTROL_REG: u8 = 0x03;
+/// The `RTCUPDATE0` register.
+const MAX77620_RTC_UPDATE0_REG: u8 = 0x04;
+/// The first register of the time block.
+const MAX77620_RTC_SEC_REG: u8 = 0x07;
+/// The first register of the alarm 1 block.
+const MAX77620_ALARM1_SEC_REG: u8 = 0x0E;
+/// The `ONOFFCNFG2` register of the PMIC.
+const MAX77620_REG_ONOFFCNFG2: u8 = 0x42;
+
+/// Binary format instead of BCD.
+const MAX77620_RTC_BIN_FORMAT: u8 = 1 << 0;
+/// 24-hour mode instead of 12-hour mode.
+const MAX77620_RTC_24H: u8 = 1 << 1;
+/// Transfers the buffered time registers to the RTC counters.
+const MAX77620_RTC_WRITE_UPDATE: u8 = 1 << 0;
+/// Transfers the RTC counters to the time registers.
+const MAX77620_RTC_READ_UPDATE: u8 = 1 << 4;
+/// The PM flag in the hour register in 12-hour mode.
+const MAX77620_RTC_PM: u8 = 1 << 6;
+/// Enables comparison of an alarm register.
+const MAX77620_RTC_ALARM_EN: u8 = 1 << 7;
+/// The alarm 1 interrupt.
+const MAX77620_RTC_ALARM1: u8 = 1 << 1;
+/// Wake up the system on alarm 1.
+const MAX77620_ONOFFCNFG2_WK_ALARM1: u8 = 1 << 2;
 
 /// Representation of a point in time as provided by the RTC.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -56,30 +138,247 @@ pub struct RtcTime {
     pub weekday: u8,
 }
 
+/// Reads a block of RTC registers starting at a given register.
+fn read_block(i2c: &I2c, start: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+    for (i, value) in buffer.iter_mut().enumerate() {
+        *value = i2c.read_byte(Device::Max77620Rtc, start + i as u8)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a block of RTC registers starting at a given register.
+fn write_block(i2c: &I2c, start: u8, buffer: &[u8]) -> Result<(), I2cError> {
+    for (i, value) in buffer.iter().enumerate() {
+        i2c.write_byte(Device::Max77620Rtc, start + i as u8, *value)?;
+    }
+
+    Ok(())
+}
+
+/// Commits buffered writes to the RTC counters.
+fn write_update(i2c: &I2c) -> Result<(), I2cError> {
+    i2c.write_byte(
+        Device::Max77620Rtc,
+        MAX77620_RTC_UPDATE0_REG,
+        MAX77620_RTC_WRITE_UPDATE,
+    )?;
+
+    // Wait for the update to be synchronized with the RTC clock.
+    msleep(16);
+
+    Ok(())
+}
+
+/// Configures the RTC for binary and 24-hour mode.
+pub fn init(i2c: &I2c) -> Result<(), I2cError> {
+    i2c.write_byte(
+        Device::Max77620Rtc,
+        MAX77620_RTC_CONTROLM_REG,
+        MAX77620_RTC_BIN_FORMAT | MAX77620_RTC_24H,
+    )?;
+    i2c.write_byte(
+        Device::Max77620Rtc,
+        MAX77620_RTC_CONTROL_REG,
+        MAX77620_RTC_BIN_FORMAT | MAX77620_RTC_24H,
+    )?;
+
+    write_update(i2c)
+}
+
+/// Sets alarm 1 to a given point in time.
+///
+/// If `wake` is set, the PMIC will power up the system once the alarm fires.
+pub fn set_alarm(i2c: &I2c, time: &RtcTime, wake: bool) -> Result<(), I2cError> {
+    let mut registers = time.to_registers();
+    for register in registers.iter_mut() {
+        *register |= MAX77620_RTC_ALARM_EN;
+    }
+
+    write_block(i2c, MAX77620_ALARM1_SEC_REG, &registers)?;
+    write_update(i2c)?;
+
+    // Clear stale interrupts and unmask the alarm.
+    i2c.read_byte(Device::Max77620Rtc, MAX77620_RTC_INT_REG)?;
+    let mask = i2c.read_byte(Device::Max77620Rtc, MAX77620_RTC_INTM_REG)?;
+    i2c.write_byte(
+        Device::Max77620Rtc,
+        MAX77620_RTC_INTM_REG,
+        mask & !MAX77620_RTC_ALARM1,
+    )?;
+
+    // Configure the alarm as a wake source.
+    let config = i2c.read_byte(Device::Max77620Pwr, MAX77620_REG_ONOFFCNFG2)?;
+    let config = if wake {
+        config | MAX77620_ONOFFCNFG2_WK_ALARM1
+    } else {
+        config & !MAX77620_ONOFFCNFG2_WK_ALARM1
+    };
+
+    i2c.write_byte(Device::Max77620Pwr, MAX77620_REG_ONOFFCNFG2, config)
+}
+
+/// Disables alarm 1 and masks its interrupt.
+pub fn clear_alarm(i2c: &I2c) -> Result<(), I2cError> {
+    write_block(i2c, MAX77620_ALARM1_SEC_REG, &[0; 7])?;
+    write_update(i2c)?;
+
+    let mask = i2c.read_byte(Device::Max77620Rtc, MAX77620_RTC_INTM_REG)?;
+    i2c.write_byte(
+        Device::Max77620Rtc,
+        MAX77620_RTC_INTM_REG,
+        mask | MAX77620_RTC_ALARM1,
+    )?;
+
+    let config = i2c.read_byte(Device::Max77620Pwr, MAX77620_REG_ONOFFCNFG2)?;
+    i2c.write_byte(
+        Device::Max77620Pwr,
+        MAX77620_REG_ONOFFCNFG2,
+        config & !MAX77620_ONOFFCNFG2_WK_ALARM1,
+    )
+}
+
+/// Checks whether alarm 1 has fired since the last check.
+///
+/// **Note:** Reading the interrupt register clears all RTC interrupts.
+pub fn alarm_pending(i2c: &I2c) -> Result<bool, I2cError> {
+    let status = i2c.read_byte(Device::Max77620Rtc, MAX77620_RTC_INT_REG)?;
+
+    Ok(status & MAX77620_RTC_ALARM1 != 0)
+}
+
+static mut MONOTONIC_LAST: u32 = 0;
+
+static mut MONOTONIC_EPOCH: u64 = 0;
+
+/// Returns a 64-bit seconds counter that never wraps.
+///
+/// This is based on the always-on Tegra RTC and has to be polled at
+/// least once per wrap of the 32-bit seconds counter to stay monotonic.
+pub fn monotonic_seconds() -> u64 {
+    let seconds = get_seconds();
+
+    unsafe {
+        if seconds < MONOTONIC_LAST {
+            MONOTONIC_EPOCH += 1 << 32;
+        }
+
+        MONOTONIC_LAST = seconds;
+
+        MONOTONIC_EPOCH + u64::from(seconds)
+    }
+}
+
+/// Magic value in the upper half of the scratch register that holds the drift factor.
+const DRIFT_CORRECTION_MAGIC: u32 = 0x4D52;
+
+/// Correction of the PMIC RTC drift.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DriftCorrection {
+    /// The drift of the RTC in parts per million. Positive values
+    /// indicate that the RTC runs slow.
+    pub ppm: i16,
+    /// The Unix time at which the RTC was last set to the exact time.
+    pub reference: u32,
+}
+
+impl DriftCorrection {
+    /// Loads the drift correction from the PMC scratch registers.
+    ///
+    /// Returns `None` if no correction has been stored yet.
+    pub fn load() -> Option<Self> {
+        let value = Scratch::RTC_DRIFT.read();
+        if value >> 16 != DRIFT_CORRECTION_MAGIC {
+            return None;
+        }
+
+        Some(DriftCorrection {
+            ppm: value as u16 as i16,
+            reference: Scratch::RTC_DRIFT_REFERENCE.read(),
+        })
+    }
+
+    /// Stores the drift correction in the PMC scratch registers.
+    pub fn store(&self) {
+        Scratch::RTC_DRIFT.write((DRIFT_CORRECTION_MAGIC << 16) | u32::from(self.ppm as u16));
+        Scratch::RTC_DRIFT_REFERENCE.write(self.reference);
+    }
+
+    /// Removes the drift correction from the PMC scratch registers.
+    pub fn clear() {
+        Scratch::RTC_DRIFT.write(0);
+        Scratch::RTC_DRIFT_REFERENCE.write(0);
+    }
+
+    /// Applies the correction to a raw Unix timestamp read from the RTC.
+    pub fn apply(&self, timestamp: u64) -> u64 {
+        let elapsed = timestamp.saturating_sub(u64::from(self.reference)) as i64;
+        let offset = elapsed * i64::from(self.ppm) / 1_000_000;
+
+        (timestamp as i64 + offset).max(0) as u64
+    }
+}
+
 impl RtcTime {
     /// Constructor which reads the time from the RTC.
-    pub fn now() -> Self {
+    ///
+    /// Panics if the RTC cannot be accessed.
+    pub fn now(i2c: &I2c) -> Self {
+        Self::read(i2c).unwrap()
+    }
+
+    /// Reads the current time from the RTC.
+    pub fn read(i2c: &I2c) -> Result<Self, I2cError> {
         // Update RTC registers from RTC clock.
-        I2c::C5
-            .write_byte(Device::Max77620Rtc, 0x04, 0x10)
-            .unwrap();
+        i2c.write_byte(
+            Device::Max77620Rtc,
+            MAX77620_RTC_UPDATE0_REG,
+            MAX77620_RTC_READ_UPDATE,
+        )?;
 
         // Get control register config.
-        let control_config = I2c::C5.read_byte(Device::Max77620Rtc, 0x03).unwrap();
+        let control_config = i2c.read_byte(Device::Max77620Rtc, MAX77620_RTC_CONTROL_REG)?;
 
-        // Get time.
-        let mut hour = I2c::C5.read_byte(Device::Max77620Rtc, 0x09).unwrap() & 0x1F;
+        let mut registers = [0; 7];
+        read_block(i2c, MAX77620_RTC_SEC_REG, &mut registers)?;
 
-        if control_config & 0x02 == 0 && hour & 0x40 != 0 {
-            hour = (hour & 0xF) + 12;
+        let mut time = Self::from_registers(&registers);
+
+        // Convert from 12-hour mode.
+        if control_config & MAX77620_RTC_24H == 0 {
+            time.hour = registers[2] & 0xF;
+
+            if registers[2] & MAX77620_RTC_PM != 0 {
+                time.hour += 12;
+            }
         }
 
-        let minute = I2c::C5.read_byte(Device::Max77620Rtc, 0x08).unwrap() & 0x7F;
-        let second = I2c::C5.read_byte(Device::Max77620Rtc, 0x07).unwrap() & 0x7F;
+        Ok(time)
+    }
+
+    /// Reads the current time from the RTC and applies the stored drift correction.
+    pub fn read_corrected(i2c: &I2c) -> Result<Self, I2cError> {
+        let time = Self::read(i2c)?;
+
+        Ok(match (DriftCorrection::load(), time.to_unix()) {
+            (Some(correction), Some(timestamp)) => Self::from_unix(correction.apply(timestamp)),
+            _ => time,
+        })
+    }
+
+    /// Sets the RTC to this point in time.
+    pub fn write(&self, i2c: &I2c) -> Result<(), I2cError> {
+        init(i2c)?;
+
+        write_block(i2c, MAX77620_RTC_SEC_REG, &self.to_registers())?;
+        write_update(i2c)
+    }
 
+    /// Decodes the time and alarm register layout.
+    fn from_registers(registers: &[u8; 7]) -> Self {
         // Get day of week.
         let mut weekday = 0;
-        let mut remainder = I2c::C5.read_byte(Device::Max77620Rtc, 0x0A).unwrap();
+        let mut remainder = registers[3];
         for _ in 0..8 {
             weekday += 1;
 
@@ -90,24 +389,85 @@ impl RtcTime {
             remainder >>= 1;
         }
 
-        // Get date.
-        let mut year = [0; 2];
-        I2c::C5
-            .read(Device::Max77620Rtc, 0x0C, &mut year)
-            .unwrap();
-        let year = (u16::from_le_bytes(year) & 0x7F) + 2000;
+        RtcTime {
+            year: u16::from(registers[5] & 0x7F) + 2000,
+            month: registers[4] & 0xF,
+            day: registers[6] & 0x1F,
+            hour: registers[2] & 0x1F,
+            minute: registers[1] & 0x7F,
+            second: registers[0] & 0x7F,
+            weekday,
+        }
+    }
+
+    /// Encodes the time into the time and alarm register layout.
+    fn to_registers(&self) -> [u8; 7] {
+        [
+            self.second & 0x7F,
+            self.minute & 0x7F,
+            self.hour & 0x1F,
+            1 << ((self.weekday.max(1) - 1) & 7),
+            self.month & 0xF,
+            (self.year.saturating_sub(2000) & 0x7F) as u8,
+            self.day & 0x1F,
+        ]
+    }
+
+    /// Converts the time to seconds since the Unix epoch.
+    ///
+    /// Returns `None` if the date is invalid or lies before the epoch.
+    pub fn to_unix(&self) -> Option<u64> {
+        if self.year < 1970 || !(1..=12).contains(&self.month) || !(1..=31).contains(&self.day) {
+            return None;
+        }
+
+        // Days from civil, see http://howardhinnant.github.io/date_algorithms.html.
+        let month = u64::from(self.month);
+        let year = u64::from(self.year) - if month <= 2 { 1 } else { 0 };
+
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year =
+            (153 * ((month + 9) % 12) + 2) / 5 + u64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        Some(
+            days * 86400
+                + u64::from(self.hour) * 3600
+                + u64::from(self.minute) * 60
+                + u64::from(self.second),
+        )
+    }
+
+    /// Creates a point in time from seconds since the Unix epoch.
+    pub fn from_unix(timestamp: u64) -> Self {
+        let days = timestamp / 86400;
+        let seconds = timestamp % 86400;
+
+        // Civil from days, see http://howardhinnant.github.io/date_algorithms.html.
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let day_of_era = z - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
 
-        let month = (I2c::C5.read_byte(Device::Max77620Rtc, 0x0B).unwrap() & 0xF) - 1;
-        let day = I2c::C5.read_byte(Device::Max77620Rtc, 0x0D).unwrap() & 0x1F;
+        // 1970-01-01 was a Thursday.
+        let weekday = ((days + 3) % 7) + 1;
 
         RtcTime {
-            year,
-            month,
-            day,
-            hour,
-            minute,
-            second,
-            weekday,
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: ((seconds % 3600) / 60) as u8,
+            second: (seconds % 60) as u8,
+            weekday: weekday as u8,
         }
     }
 
@@ -149,7 +509,7 @@ impl fmt::Display for RtcTime {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}, {} {}, {} {}:{}:{}",
+            "{}, {} {:02}, {} {:02}:{:02}:{:02}",
             self.get_weekday(),
             self.get_month(),
             self.day,
@@ -160,3 +520,43 @@ impl fmt::Display for RtcTime {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(year: u16, month: u8, day: u8) -> RtcTime {
+        RtcTime {
+            year,
+            month,
+            day,
+            hour: 17,
+            minute: 39,
+            second: 36,
+            weekday: 0,
+        }
+    }
+
+    /// Tests converting valid dates to and from Unix time.
+    #[test]
+    fn unix_time() {
+        assert_eq!(time(1970, 1, 1).to_unix(), Some(63_576));
+        assert_eq!(time(2019, 11, 9).to_unix(), Some(1_573_321_176));
+
+        let converted = RtcTime::from_unix(1_573_321_176);
+        assert_eq!(
+            (converted.year, converted.month, converted.day),
+            (2019, 11, 9)
+        );
+        assert_eq!(converted.weekday, 6);
+    }
+
+    /// Tests rejecting invalid dates and dates before the epoch.
+    #[test]
+    fn unix_time_invalid() {
+        assert_eq!(time(2019, 11, 0).to_unix(), None);
+        assert_eq!(time(2019, 0, 9).to_unix(), None);
+        assert_eq!(time(1969, 12, 31).to_unix(), None);
+        assert_eq!(time(0, 1, 1).to_unix(), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Cooperative time-slicing between long-running driver work and UI tasks.
+//!
+//! # Description
+//!
+//! Mirage runs on a single core without preemption. Long operations,
+//! such as storage transfers or waiting on crypto engines, would otherwise
+//! freeze progress bars and make emergency button combinations unresponsive
+//! until they finish.
+//!
+//! To work around this, drivers periodically call [`yield_now`] from within
+//! their polling loops. Whenever a time slice has elapsed since callbacks
+//! were last run, all registered callbacks are executed before control is
+//! returned to the driver.
+//!
+//! # Implementation
+//!
+//! - Callbacks are plain function pointers that are stored in a fixed-size
+//! table of [`MAX_CALLBACKS`] entries, no allocations are involved.
+//!
+//! - [`register`] adds a callback to the table and returns a [`Handle`]
+//! which can later be passed to [`unregister`] to remove it again.
+//!
+//! - [`yield_now`] runs pending callbacks if the time slice configured
+//! through [`set_time_slice`] has passed. Nested calls from within
+//! callbacks are ignored, so callbacks may safely use drivers which
+//! yield themselves.
+//!
+//! - Callbacks are executed in the middle of driver operations. They
+//! must not access the hardware the yielding driver is currently using.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::sched;
+//!
+//! fn update_progress() {
+//!     // Redraw the progress bar.
+//! }
+//!
+//! fn main() {
+//!     let handle = sched::register(update_progress).unwrap();
+//!
+//!     // Long-running work that calls `sched::yield_now()`...
+//!
+//!     sched::unregister(handle);
+//! }
+//! ```
+//!
+//! [`yield_now`]: fn.yield_now.html
+//! [`MAX_CALLBACKS`]: constant.MAX_CALLBACKS.html
+//! [`register`]: fn.register.html
+//! [`Handle`]: struct.Handle.html
+//! [`unregister`]: fn.unregister.html
+//! [`set_time_slice`]: fn.set_time_slice.html
+
+use crate::{
+    timer::{get_microseconds, get_time_since},
+    Error,
+};
+
+/// The maximum number of callbacks that can be registered at once.
+pub const MAX_CALLBACKS: usize = 8;
+
+/// The default time slice in microseconds.
+pub const DEFAULT_TIME_SLICE: u32 = 10_000;
+
+/// A callback to be run by the scheduler.
+pub type Callback = fn();
+
+/// A handle to a registered callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handle(usize);
+
+static mut CALLBACKS: [Option<Callback>; MAX_CALLBACKS] = [None; MAX_CALLBACKS];
+
+static mut TIME_SLICE: u32 = DEFAULT_TIME_SLICE;
+
+static mut LAST_RUN: u32 = 0;
+
+static mut RUNNING: bool = false;
+
+/// Registers a callback to be run on [`yield_now`].
+///
+/// Fails if all callback slots are in use.
+///
+/// [`yield_now`]: fn.yield_now.html
+pub fn register(callback: Callback) -> Result<Handle, Error> {
+    let callbacks = unsafe { &mut CALLBACKS };
+
+    for (index, slot) in callbacks.iter_mut().enumerate() {
+        if slot.is_none() {
+            *slot = Some(callback);
+            return Ok(Handle(index));
+        }
+    }
+
+    Err(Error::Exhausted)
+}
+
+/// Removes a previously registered callback.
+pub fn unregister(handle: Handle) {
+    unsafe {
+        CALLBACKS[handle.0] = None;
+    }
+}
+
+/// Sets the minimum amount of microseconds between two callback runs.
+pub fn set_time_slice(microseconds: u32) {
+    unsafe {
+        TIME_SLICE = microseconds;
+    }
+}
+
+/// Runs all registered callbacks immediately, regardless of the time slice.
+pub fn run_pending() {
+    unsafe {
+        if RUNNING {
+            return;
+        }
+
+        RUNNING = true;
+
+        for callback in CALLBACKS.iter().filter_map(|c| *c) {
+            callback();
+        }
+
+        LAST_RUN = get_microseconds();
+        RUNNING = false;
+    }
+}
+
+/// Gives registered callbacks a chance to run.
+///
+/// This should be called by drivers from within long polling loops.
+/// It is cheap when the current time slice hasn't elapsed yet.
+#[inline]
+pub fn yield_now() {
+    if get_time_since(unsafe { LAST_RUN }) >= unsafe { TIME_SLICE } {
+        run_pending();
+    }
+}
@@ -0,0 +1,300 @@
+//! ADMA2 scatter-gather transfers.
+//!
+//! # Description
+//!
+//! In PIO mode, the CPU has to move every word of a transfer through the
+//! buffer register of the SDMMC controller, which is far too slow to load
+//! payloads of several megabytes. With ADMA2, the controller fetches a table
+//! of descriptors from memory instead, each pointing to a segment of the
+//! transfer, and moves the data on its own.
+//!
+//! # Implementation
+//!
+//! - [`Descriptor`] is the 128-bit descriptor format used in SDHCI 4.0 mode
+//! with 64-bit addressing, which the controllers are configured for.
+//!
+//! - [`DescriptorTable`] builds the descriptor chain for a list of segments,
+//! splitting them into chunks of at most [`MAX_DESCRIPTOR_LENGTH`] bytes.
+//! [`DescriptorTable::finish`] terminates the chain and cleans it from the
+//! cache, so the controller sees it.
+//!
+//! - ADMA bypasses the data cache. [`prepare_read`] and [`complete_read`]
+//! maintain the cache around reads from the card, [`prepare_write`] before
+//! writes to it.
+//!
+//! - Invalidating a destination that shares cache lines with other data
+//! would discard those. [`read_bounced`] therefore reads into such buffers
+//! through an aligned bounce buffer.
+//!
+//! - [`Sdmmc::read_blocks`] puts these together to read from the card,
+//! [`Sdmmc::transfer`] runs a transfer through any prepared table.
+//!
+//! [`Descriptor`]: struct.Descriptor.html
+//! [`DescriptorTable`]: struct.DescriptorTable.html
+//! [`MAX_DESCRIPTOR_LENGTH`]: constant.MAX_DESCRIPTOR_LENGTH.html
+//! [`DescriptorTable::finish`]: struct.DescriptorTable.html#method.finish
+//! [`prepare_read`]: fn.prepare_read.html
+//! [`complete_read`]: fn.complete_read.html
+//! [`prepare_write`]: fn.prepare_write.html
+//! [`read_bounced`]: fn.read_bounced.html
+//! [`Sdmmc::read_blocks`]: struct.Sdmmc.html#method.read_blocks
+//! [`Sdmmc::transfer`]: struct.Sdmmc.html#method.transfer
+
+use core::mem::size_of;
+
+use crate::{
+    arch::{self, CACHE_LINE_SIZE},
+    Error, Result,
+};
+
+/// The maximum amount of bytes a single descriptor transfers.
+///
+/// This is the largest multiple of the 512 byte block size that fits into
+/// the 16-bit length field.
+pub const MAX_DESCRIPTOR_LENGTH: usize = 0xFE00;
+
+/// The required alignment of data addresses.
+const DATA_ALIGNMENT: usize = 4;
+
+/// The descriptor is valid, in [`Descriptor`] attributes.
+const ATTRIBUTE_VALID: u16 = 1 << 0;
+/// The descriptor is the last one of the chain.
+const ATTRIBUTE_END: u16 = 1 << 1;
+/// The descriptor transfers data.
+const ATTRIBUTE_ACT_TRAN: u16 = 0b100 << 3;
+
+/// An ADMA2 descriptor in the 128-bit format.
+#[repr(C, align(8))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Descriptor {
+    /// The attributes of the descriptor.
+    pub attributes: u16,
+    /// The length of the segment in bytes.
+    pub length: u16,
+    /// The lower 32 bits of the segment address.
+    pub address: u32,
+    /// The upper 32 bits of the segment address.
+    pub address_high: u32,
+    _reserved: u32,
+}
+
+/// A chain of ADMA2 descriptors under construction.
+pub struct DescriptorTable<'a> {
+    /// The memory for the descriptors.
+    descriptors: &'a mut [Descriptor],
+    /// The amount of descriptors in use.
+    used: usize,
+}
+
+impl<'a> DescriptorTable<'a> {
+    /// Creates an empty table in the given memory.
+    pub fn new(descriptors: &'a mut [Descriptor]) -> Self {
+        DescriptorTable {
+            descriptors,
+            used: 0,
+        }
+    }
+
+    /// Gets the descriptors in use.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors[..self.used]
+    }
+
+    /// Gets the address of the table for the ADMA address registers.
+    pub fn address(&self) -> usize {
+        self.descriptors.as_ptr() as usize
+    }
+
+    /// Removes all descriptors from the table.
+    pub fn clear(&mut self) {
+        self.used = 0;
+    }
+
+    /// Appends a segment of memory to the transfer.
+    ///
+    /// Fails with [`Error::InvalidArgument`] for unaligned segments and with
+    /// [`Error::Exhausted`] if the table is too small.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    /// [`Error::Exhausted`]: ../enum.Error.html#variant.Exhausted
+    pub fn push(&mut self, mut address: usize, mut len: usize) -> Result<()> {
+        if address % DATA_ALIGNMENT != 0 || len % DATA_ALIGNMENT != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let needed = (len + MAX_DESCRIPTOR_LENGTH - 1) / MAX_DESCRIPTOR_LENGTH;
+        if self.used + needed > self.descriptors.len() {
+            return Err(Error::Exhausted);
+        }
+
+        while len > 0 {
+            let length = len.min(MAX_DESCRIPTOR_LENGTH);
+
+            self.descriptors[self.used] = Descriptor {
+                attributes: ATTRIBUTE_VALID | ATTRIBUTE_ACT_TRAN,
+                length: length as u16,
+                address: address as u32,
+                address_high: (address as u64 >> 32) as u32,
+                _reserved: 0,
+            };
+            self.used += 1;
+
+            address += length;
+            len -= length;
+        }
+
+        Ok(())
+    }
+
+    /// Terminates the chain and makes it visible to the controller.
+    ///
+    /// Returns the address of the table. Fails with
+    /// [`Error::InvalidArgument`] if the table is empty.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn finish(&mut self) -> Result<usize> {
+        if self.used == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.descriptors[self.used - 1].attributes |= ATTRIBUTE_END;
+        arch::clean_range(self.address(), self.used * size_of::<Descriptor>());
+        arch::data_barrier();
+
+        Ok(self.address())
+    }
+}
+
+/// Checks whether a read destination has to go through a bounce buffer.
+///
+/// This is the case if the buffer shares cache lines with other data or
+/// isn't aligned for ADMA.
+pub fn needs_bounce(address: usize, len: usize) -> bool {
+    address % CACHE_LINE_SIZE != 0 || len % CACHE_LINE_SIZE != 0
+}
+
+/// Prepares a buffer for being written to by the controller.
+///
+/// Dirty lines are written back first, so they can't be evicted over the
+/// data of the transfer later.
+pub fn prepare_read(address: usize, len: usize) {
+    arch::clean_invalidate_range(address, len);
+    arch::data_barrier();
+}
+
+/// Makes the data written by the controller visible to the CPU.
+pub fn complete_read(address: usize, len: usize) {
+    arch::data_barrier();
+    arch::invalidate_range(address, len);
+}
+
+/// Prepares a buffer for being read by the controller.
+pub fn prepare_write(address: usize, len: usize) {
+    arch::clean_range(address, len);
+    arch::data_barrier();
+}
+
+/// Reads into `destination`, bouncing through `bounce` if required.
+///
+/// `read` is called with the buffers to transfer into and the offset into
+/// the destination each belongs to, and is expected to do the cache
+/// maintenance of [`prepare_read`] and [`complete_read`]. Unless the
+/// destination [`needs_bounce`], it is read in a single call. Otherwise,
+/// it is read in chunks of the bounce buffer size, which has to be a
+/// multiple of the block size. `read` should call [`yield_now`] between
+/// transfers, so callbacks keep running during long reads either way.
+///
+/// [`prepare_read`]: fn.prepare_read.html
+/// [`complete_read`]: fn.complete_read.html
+/// [`needs_bounce`]: fn.needs_bounce.html
+/// [`yield_now`]: ../sched/fn.yield_now.html
+pub fn read_bounced<F>(destination: &mut [u8], bounce: &mut [u8], mut read: F) -> Result<()>
+where
+    F: FnMut(&mut [u8], usize) -> Result<()>,
+{
+    if !needs_bounce(destination.as_ptr() as usize, destination.len()) {
+        return read(destination, 0);
+    }
+
+    if bounce.is_empty() || needs_bounce(bounce.as_ptr() as usize, bounce.len()) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut offset = 0;
+    for chunk in destination.chunks_mut(bounce.len()) {
+        // Transfers cover whole blocks, so the last chunk is rounded up.
+        let len = (chunk.len() + CACHE_LINE_SIZE - 1) & !(CACHE_LINE_SIZE - 1);
+        let len = len.min(bounce.len());
+
+        read(&mut bounce[..len], offset)?;
+        chunk.copy_from_slice(&bounce[..chunk.len()]);
+
+        offset += chunk.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests splitting segments into descriptors.
+    #[test]
+    fn descriptor_table() {
+        let mut memory = [Descriptor::default(); 4];
+        let mut table = DescriptorTable::new(&mut memory);
+
+        assert_eq!(table.finish(), Err(Error::InvalidArgument));
+        assert_eq!(table.push(0x8000_0002, 0x200), Err(Error::InvalidArgument));
+
+        table
+            .push(0x8000_0000, MAX_DESCRIPTOR_LENGTH + 0x200)
+            .unwrap();
+        table.push(0x9000_0000, 0x400).unwrap();
+        assert_eq!(
+            table.push(0xA000_0000, 2 * MAX_DESCRIPTOR_LENGTH),
+            Err(Error::Exhausted)
+        );
+        table.finish().unwrap();
+
+        let descriptors = table.descriptors();
+        assert_eq!(descriptors.len(), 3);
+        assert_eq!(descriptors[0].length as usize, MAX_DESCRIPTOR_LENGTH);
+        assert_eq!(
+            descriptors[1].address as usize,
+            0x8000_0000 + MAX_DESCRIPTOR_LENGTH
+        );
+        assert_eq!(descriptors[1].length, 0x200);
+        assert_eq!(descriptors[1].attributes, 0x21);
+        assert_eq!(descriptors[2].address, 0x9000_0000);
+        assert_eq!(descriptors[2].attributes, 0x23);
+    }
+
+    /// Tests reading into an unaligned buffer through a bounce buffer.
+    #[test]
+    fn bounce() {
+        #[repr(align(64))]
+        struct Aligned([u8; 0x40]);
+
+        let mut bounce = Aligned([0; 0x40]);
+        let mut destination = [0u8; 0x51];
+        let mut calls = 0;
+
+        read_bounced(&mut destination[1..], &mut bounce.0, |buffer, offset| {
+            calls += 1;
+            for (i, byte) in buffer.iter_mut().enumerate() {
+                *byte = (offset + i) as u8;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(destination[0], 0);
+        assert!(destination[1..]
+            .iter()
+            .enumerate()
+            .all(|(i, &b)| b == i as u8));
+    }
+}
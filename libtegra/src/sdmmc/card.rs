@@ -0,0 +1,243 @@
+//! SD card presence detection and power cycling.
+//!
+//! # Description
+//!
+//! The microSD slot reports whether a card is inserted through a card
+//! detect GPIO, which is pulled low by an inserted card. A card that was
+//! removed and inserted again, or one that stopped responding, has to go
+//! through a full power cycle before it can be identified again.
+//!
+//! This allows menus to ask for a card to be inserted and retry without
+//! rebooting the console.
+//!
+//! # Implementation
+//!
+//! - [`SdCard::is_inserted`] samples the card detect GPIO, [`SdCard::poll`]
+//! reports debounced insertion and removal as [`CardEvent`]s.
+//!
+//! - [`SdCard::power_cycle`] disables the card supply through the power
+//! GPIO of the [`Board`] and the SDMMC1 I/O rail through the PMIC on the
+//! given I²C controller, waits for the card to discharge, powers it up
+//! again at 3.3V and resets the SDMMC1 controller.
+//!
+//! - [`SdCard::reinsert`] power cycles an inserted card. The card is in its
+//! power-on state afterwards and has to be identified again by the driver.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{board::Board, peripherals::Peripherals, sdmmc::SdCard};
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!     let mut card = SdCard::new(Board::detect());
+//!
+//!     // "Insert an SD card and press A to retry."
+//!     while card.reinsert(&peripherals.i2c_5).is_err() {}
+//! }
+//! ```
+//!
+//! [`SdCard::is_inserted`]: struct.SdCard.html#method.is_inserted
+//! [`SdCard::poll`]: struct.SdCard.html#method.poll
+//! [`CardEvent`]: enum.CardEvent.html
+//! [`SdCard::power_cycle`]: struct.SdCard.html#method.power_cycle
+//! [`Board`]: ../board/enum.Board.html
+//! [`SdCard::reinsert`]: struct.SdCard.html#method.reinsert
+
+use mirage_mmio::Mmio;
+
+use crate::{
+    board::Board,
+    clock::Clock,
+    gpio::{Gpio, GpioConfig, GpioLevel},
+    i2c::I2c,
+    power::max77620::Regulator,
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// The `SOFTWARE_RESET` register of the SDMMC1 controller.
+const SDMMC1_SOFTWARE_RESET: u32 = 0x700B_002F;
+
+/// Resets the whole controller, in `SOFTWARE_RESET`.
+const SOFTWARE_RESET_FOR_ALL: u8 = 1 << 0;
+
+/// The time a changed card detect level has to be stable, in microseconds.
+const DEBOUNCE_DELAY: u32 = 10_000;
+
+/// The time the card is kept unpowered to discharge, in microseconds.
+const POWER_OFF_DELAY: u32 = 100_000;
+
+/// The time the supply needs to ramp up, in microseconds.
+const POWER_ON_DELAY: u32 = 1_000;
+
+/// The maximum time the controller reset may take, in microseconds.
+const RESET_TIMEOUT: u32 = 100_000;
+
+/// The voltage of the SDMMC1 I/O rail at power-on, in µV.
+const IO_VOLTAGE: u32 = 3_300_000;
+
+/// Changes of the card presence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardEvent {
+    /// A card has been inserted.
+    Inserted,
+    /// The card has been removed.
+    Removed,
+}
+
+/// The microSD card slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SdCard {
+    /// The board the slot is wired on.
+    board: Board,
+    /// Whether a card was inserted when last polled.
+    inserted: bool,
+    /// The device clock of the SDMMC1 controller.
+    clock: Clock,
+}
+
+impl SdCard {
+    /// Creates the slot of the given board, sampling the current card
+    /// presence.
+    pub fn new(board: Board) -> Self {
+        Gpio::MICROSD_CARD_DETECT.config(GpioConfig::Input);
+
+        SdCard {
+            board,
+            inserted: Self::sample(),
+            clock: Clock::SDMMC1,
+        }
+    }
+
+    /// Samples the card detect GPIO.
+    fn sample() -> bool {
+        Gpio::MICROSD_CARD_DETECT.read() == GpioLevel::Low
+    }
+
+    /// Whether a card is inserted.
+    pub fn is_inserted(&self) -> bool {
+        Self::sample()
+    }
+
+    /// Checks for a change of the card presence since the last call.
+    ///
+    /// Changes are only reported once they are stable, so this blocks
+    /// for a short time while a card is being inserted or removed.
+    pub fn poll(&mut self) -> Option<CardEvent> {
+        if Self::sample() == self.inserted {
+            return None;
+        }
+
+        usleep(DEBOUNCE_DELAY);
+
+        let inserted = Self::sample();
+        if inserted == self.inserted {
+            return None;
+        }
+
+        self.inserted = inserted;
+
+        if inserted {
+            Some(CardEvent::Inserted)
+        } else {
+            Some(CardEvent::Removed)
+        }
+    }
+
+    /// Cuts the power of the card and the SDMMC1 I/O rail, the latter
+    /// through the PMIC on `i2c`.
+    pub fn power_off(&mut self, i2c: &I2c) {
+        self.clock.disable();
+
+        self.board.sd_power_gpio().config(GpioConfig::OutputLow);
+        Regulator::LDO2.disable(i2c);
+    }
+
+    /// Powers the card and the SDMMC1 I/O rail at 3.3V and resets the
+    /// SDMMC1 controller. The I/O rail is set through the PMIC on `i2c`.
+    ///
+    /// Fails with [`Error::Timeout`] if the controller doesn't leave reset.
+    ///
+    /// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+    pub fn power_on(&mut self, i2c: &I2c) -> Result<()> {
+        Regulator::LDO2.set_voltage(i2c, IO_VOLTAGE)?;
+        Regulator::LDO2.enable(i2c);
+
+        self.board.sd_power_gpio().config(GpioConfig::OutputHigh);
+        usleep(POWER_ON_DELAY);
+
+        self.clock.enable();
+
+        let software_reset = unsafe { &*(SDMMC1_SOFTWARE_RESET as *const Mmio<u8>) };
+        software_reset.write(SOFTWARE_RESET_FOR_ALL);
+
+        wait_for(|| software_reset.read() & SOFTWARE_RESET_FOR_ALL == 0, RESET_TIMEOUT)
+    }
+
+    /// Power cycles the card and resets the SDMMC1 controller.
+    ///
+    /// See [`SdCard::power_on`] for the possible errors.
+    ///
+    /// [`SdCard::power_on`]: struct.SdCard.html#method.power_on
+    pub fn power_cycle(&mut self, i2c: &I2c) -> Result<()> {
+        #[cfg(feature = "profile")]
+        let _span = crate::profile::begin("sd power cycle");
+
+        self.power_off(i2c);
+        usleep(POWER_OFF_DELAY);
+
+        self.power_on(i2c)
+    }
+
+    /// Power cycles a freshly inserted card, so it can be identified.
+    ///
+    /// Fails with [`Error::Sdmmc`] if no card is inserted, otherwise see
+    /// [`SdCard::power_on`] for the possible errors.
+    ///
+    /// [`Error::Sdmmc`]: ../enum.Error.html#variant.Sdmmc
+    /// [`SdCard::power_on`]: struct.SdCard.html#method.power_on
+    pub fn reinsert(&mut self, i2c: &I2c) -> Result<()> {
+        self.inserted = Self::sample();
+        if !self.inserted {
+            return Err(Error::Sdmmc);
+        }
+
+        self.power_cycle(i2c)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// Tests the card detection against mocked registers.
+    #[test]
+    fn detects_card() {
+        let _session = mock::start();
+
+        // The card detect line reads low, i.e. a card is inserted.
+        let mut card = SdCard::new(Board::Erista);
+
+        assert!(card.is_inserted());
+        assert_eq!(card.poll(), None);
+    }
+
+    /// Tests powering the card and the I/O rail up at 3.3V.
+    #[test]
+    fn powers_on() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        // The controller leaves reset right away.
+        mock::expect_read(SDMMC1_SOFTWARE_RESET as usize, 0);
+
+        let mut card = SdCard::new(Board::Erista);
+        card.power_on(&I2c::C5).unwrap();
+
+        // LDO2 is set to 3.3V through `I2C_CMD_DATA1` of I²C 5.
+        assert!(mock::writes(0x7000_D00C).contains(&0x3227));
+    }
+}
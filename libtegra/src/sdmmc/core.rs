@@ -1,17 +1,39 @@
-use mirage_mmio::{Mmio, VolatileStorage};
+use mirage_mmio::{Mmio, ReadOnly, VolatileStorage, WriteOnly};
 
 use crate::{
     apb_misc::Padctl,
     clock::{Car, CLK_L_SDMMC1, CLK_L_SDMMC2, CLK_L_SDMMC4, CLK_SOURCE_FIRST, CLK_U_SDMMC3},
-    timer::{get_microseconds, get_time_since, usleep},
+    i2c::I2c,
+    pmc::Pmc,
+    power::max77620::Regulator,
+    retry::RetryPolicy,
+    sched::yield_now,
+    sdmmc::{
+        complete_read, prepare_read, read_bounced, Descriptor, DescriptorTable,
+        MAX_DESCRIPTOR_LENGTH,
+    },
+    timer::{get_microseconds, get_time_since, usleep, wait_for},
+    Error, Result,
 };
 
 /// Base address for SDMMC registers.
 pub(crate) const SDMMC_BASE: u32 = 0x700B_0000;
 
-const CLK_SOURCES: [u32; 4] = [0; 4];
+/// The size of a data block in bytes.
+pub const BLOCK_SIZE: usize = 512;
 
-const CLK_DIVIDERS: [u32; 4] = [0; 4];
+/// READ_SINGLE_BLOCK command.
+const MMC_READ_SINGLE_BLOCK: u32 = 17;
+/// READ_MULTIPLE_BLOCK command.
+const MMC_READ_MULTIPLE_BLOCK: u32 = 18;
+
+/// The SDMMC1 pads run at 3.3V, in `APBDEV_PMC_PWR_DET_VAL_0`.
+const PMC_PWR_DET_SDMMC1_IO_EN: u32 = 1 << 12;
+
+/// The voltage of the SD card I/O rail (LDO2) in 3.3V signaling mode, in µV.
+const SD_IO_VOLTAGE_3V3: u32 = 3_300_000;
+/// The voltage of the SD card I/O rail (LDO2) in 1.8V signaling mode, in µV.
+const SD_IO_VOLTAGE_1V8: u32 = 1_800_000;
 
 bitflags! {
     /// Present state flags.
@@ -105,6 +127,52 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// SDHCI interrupt status flags.
+    pub struct InterruptStatus: u32 {
+        const SDHCI_INT_RESPONSE = 0x0000_0001;
+        const SDHCI_INT_DATA_END = 0x0000_0002;
+        const SDHCI_INT_DMA_END = 0x0000_0008;
+        const SDHCI_INT_ERROR = 0x0000_8000;
+        const SDHCI_INT_TIMEOUT = 0x0001_0000;
+        const SDHCI_INT_DATA_TIMEOUT = 0x0010_0000;
+        const SDHCI_INT_ADMA_ERROR = 0x0200_0000;
+    }
+}
+
+bitflags! {
+    /// SDHCI transfer mode flags.
+    pub struct TransferMode: u16 {
+        const SDHCI_TRNS_DMA = 0x01;
+        const SDHCI_TRNS_BLK_CNT_EN = 0x02;
+        const SDHCI_TRNS_AUTO_CMD12 = 0x04;
+        const SDHCI_TRNS_READ = 0x10;
+        const SDHCI_TRNS_MULTI = 0x20;
+    }
+}
+
+bitflags! {
+    /// SDHCI command flags.
+    pub struct CommandFlags: u16 {
+        const SDHCI_CMD_RESP_NONE = 0x00;
+        const SDHCI_CMD_RESP_LONG = 0x01;
+        const SDHCI_CMD_RESP_SHORT = 0x02;
+        const SDHCI_CMD_RESP_SHORT_BUSY = 0x03;
+        const SDHCI_CMD_CRC = 0x08;
+        const SDHCI_CMD_INDEX = 0x10;
+        const SDHCI_CMD_DATA = 0x20;
+    }
+}
+
+bitflags! {
+    /// SDHCI software reset flags.
+    pub struct SoftwareReset: u8 {
+        const SDHCI_RESET_ALL = 0x01;
+        const SDHCI_RESET_CMD = 0x02;
+        const SDHCI_RESET_DATA = 0x04;
+    }
+}
+
 bitflags! {
     /// Vendor clock control flags.
     pub struct VendorClockControl: u32 {
@@ -156,7 +224,7 @@ bitflags! {
 bitflags! {
     /// Vendor tuning control 1 flags.
     pub struct VendorTuningControl1: u32 {
-        const SDMMC_VENDOR_TUNING_STEP_SIZE_SDR50_DEFAULT = (0 << 0);
+        const SDMMC_VENDOR_TUNING_STEP_SIZE_SDR50_DEFAULT = 0;
         const SDMMC_VENDOR_TUNING_STEP_SIZE_SDR104_DEFAULT = (0 << 4);
     }
 }
@@ -174,6 +242,9 @@ bitflags! {
     pub struct Timeouts: u32 {
         const SDMMC_AUTOCAL_TIMEOUT = (10 * 1000);
         const SDMMC_TUNING_TIMEOUT = (150 * 1000);
+        const SDMMC_COMMAND_TIMEOUT = (2000 * 1000);
+        const SDMMC_TRANSFER_TIMEOUT = (10_000 * 1000);
+        const SDMMC_RESET_TIMEOUT = (100 * 1000);
     }
 }
 
@@ -211,44 +282,63 @@ bitflags! {
 
 // Native response types for commands.
 pub const SDMMC_RSP_NONE: CommandResponse = CommandResponse::empty();
-pub const SDMMC_RSP_R1: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_CRC
-    | CommandResponse::SDMMC_RSP_OPCODE;
-pub const SDMMC_RSP_R1B: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_CRC
-    | CommandResponse::SDMMC_RSP_OPCODE
-    | CommandResponse::SDMMC_RSP_BUSY;
-pub const SDMMC_RSP_R2: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_136
-    | CommandResponse::SDMMC_RSP_CRC;
+pub const SDMMC_RSP_R1: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits()
+        | CommandResponse::SDMMC_RSP_OPCODE.bits(),
+);
+pub const SDMMC_RSP_R1B: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits()
+        | CommandResponse::SDMMC_RSP_OPCODE.bits()
+        | CommandResponse::SDMMC_RSP_BUSY.bits(),
+);
+pub const SDMMC_RSP_R2: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_136.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits(),
+);
 pub const SDMMC_RSP_R3: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT;
 pub const SDMMC_RSP_R4: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT;
-pub const SDMMC_RSP_R5: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_CRC
-    | CommandResponse::SDMMC_RSP_OPCODE;
-pub const SDMMC_RSP_R6: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_CRC
-    | CommandResponse::SDMMC_RSP_OPCODE;
-pub const SDMMC_RSP_R7: CommandResponse = CommandResponse::SDMMC_RSP_PRESENT
-    | CommandResponse::SDMMC_RSP_CRC
-    | CommandResponse::SDMMC_RSP_OPCODE;
-pub const SDMMC_RSP_R1_NO_CRC: CommandResponse =
-    CommandResponse::SDMMC_RSP_PRESENT | CommandResponse::SDMMC_RSP_OPCODE;
+pub const SDMMC_RSP_R5: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits()
+        | CommandResponse::SDMMC_RSP_OPCODE.bits(),
+);
+pub const SDMMC_RSP_R6: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits()
+        | CommandResponse::SDMMC_RSP_OPCODE.bits(),
+);
+pub const SDMMC_RSP_R7: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits()
+        | CommandResponse::SDMMC_RSP_CRC.bits()
+        | CommandResponse::SDMMC_RSP_OPCODE.bits(),
+);
+pub const SDMMC_RSP_R1_NO_CRC: CommandResponse = CommandResponse::from_bits_truncate(
+    CommandResponse::SDMMC_RSP_PRESENT.bits() | CommandResponse::SDMMC_RSP_OPCODE.bits(),
+);
 
 // SPI response types for commands.
 pub const SDMMC_RSP_SPI_R1: SpiCommandResponse = SpiCommandResponse::SDMMC_RSP_SPI_S1;
-pub const SDMMC_RSP_SPI_R1B: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_BUSY;
-pub const SDMMC_RSP_SPI_R2: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_S2;
-pub const SDMMC_RSP_SPI_R3: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_B4;
-pub const SDMMC_RSP_SPI_R4: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_B4;
-pub const SDMMC_RSP_SPI_R5: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_S2;
-pub const SDMMC_RSP_SPI_R7: SpiCommandResponse =
-    SpiCommandResponse::SDMMC_RSP_SPI_S1 | SpiCommandResponse::SDMMC_RSP_SPI_B4;
+pub const SDMMC_RSP_SPI_R1B: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_BUSY.bits(),
+);
+pub const SDMMC_RSP_SPI_R2: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_S2.bits(),
+);
+pub const SDMMC_RSP_SPI_R3: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_B4.bits(),
+);
+pub const SDMMC_RSP_SPI_R4: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_B4.bits(),
+);
+pub const SDMMC_RSP_SPI_R5: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_S2.bits(),
+);
+pub const SDMMC_RSP_SPI_R7: SpiCommandResponse = SpiCommandResponse::from_bits_truncate(
+    SpiCommandResponse::SDMMC_RSP_SPI_S1.bits() | SpiCommandResponse::SDMMC_RSP_SPI_B4.bits(),
+);
 
 /// Representation of the SDMMC controllers.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -262,11 +352,11 @@ pub enum SdmmcController {
 enum_from_primitive! {
     /// SDMMC partition types.
     pub enum SdmmcPartition {
-        InvalidPartition = -1,
-        UserPartition = 0,
-        Boot0Partition = 1,
-        Boot1Partition = 2,
-        RpmbPartition = 3,
+        Invalid = -1,
+        User = 0,
+        Boot0 = 1,
+        Boot1 = 2,
+        Rpmb = 3,
     }
 }
 
@@ -334,9 +424,9 @@ struct Registers {
     pub argument: Mmio<u32>,
     pub transfer_mode: Mmio<u16>,
     pub command: Mmio<u16>,
-    pub response: [Mmio<u32>; 0x4],
+    pub response: [ReadOnly<u32>; 0x4],
     pub buffer: Mmio<u32>,
-    pub present_state: Mmio<u32>,
+    pub present_state: ReadOnly<u32>,
     pub host_control: Mmio<u8>,
     pub power_control: Mmio<u8>,
     pub block_gap_control: Mmio<u8>,
@@ -349,13 +439,13 @@ struct Registers {
     pub signal_enable: Mmio<u32>,
     pub acmd12_err: Mmio<u16>,
     pub host_control2: Mmio<u16>,
-    pub capabilities: Mmio<u32>,
-    pub capabilities_1: Mmio<u32>,
-    pub max_current: Mmio<u32>,
+    pub capabilities: ReadOnly<u32>,
+    pub capabilities_1: ReadOnly<u32>,
+    pub max_current: ReadOnly<u32>,
     _0x4C: Mmio<u32>,
-    pub set_acmd12_error: Mmio<u16>,
-    pub set_int_error: Mmio<u16>,
-    pub adma_error: Mmio<u8>,
+    pub set_acmd12_error: WriteOnly<u16>,
+    pub set_int_error: WriteOnly<u16>,
+    pub adma_error: ReadOnly<u8>,
     _0x56: [Mmio<u8>; 0x3],
     pub adma_address: Mmio<u32>,
     pub upper_adma_address: Mmio<u32>,
@@ -368,8 +458,8 @@ struct Registers {
     pub preset_for_sdr104: Mmio<u16>,
     pub preset_for_ddr50: Mmio<u16>,
     _0x70: [Mmio<u32>; 0x23],
-    pub slot_int_status: Mmio<u16>,
-    pub host_version: Mmio<u16>,
+    pub slot_int_status: ReadOnly<u16>,
+    pub host_version: ReadOnly<u16>,
 
     // Vendor-specific registers.
     pub vendor_clock_cntrl: Mmio<u32>,
@@ -390,12 +480,12 @@ struct Registers {
     pub vendor_dllcal_cfg: Mmio<u32>,
     pub vendor_dll_ctrl0: Mmio<u32>,
     pub vendor_dll_ctrl1: Mmio<u32>,
-    pub vendor_dllcal_cfg_sta: Mmio<u32>,
+    pub vendor_dllcal_cfg_sta: ReadOnly<u32>,
 
     pub vendor_tuning_cntrl0: Mmio<u32>,
     pub vendor_tuning_cntrl1: Mmio<u32>,
-    pub vendor_tuning_status0: Mmio<u32>,
-    pub vendor_tuning_status1: Mmio<u32>,
+    pub vendor_tuning_status0: ReadOnly<u32>,
+    pub vendor_tuning_status1: ReadOnly<u32>,
     pub vendor_clk_gate_hysteresis_count: Mmio<u32>,
     pub vendor_preset_val0: Mmio<u32>,
     pub vendor_preset_val1: Mmio<u32>,
@@ -403,7 +493,7 @@ struct Registers {
     pub sdmemcomppadctrl: Mmio<u32>,
     pub auto_cal_config: Mmio<u32>,
     pub auto_cal_interval: Mmio<u32>,
-    pub auto_cal_status: Mmio<u32>,
+    pub auto_cal_status: ReadOnly<u32>,
     pub io_spare: Mmio<u32>,
     pub sdmmca_mccif_fifoctrl: Mmio<u32>,
     pub timeout_wcoal_sdmmca: Mmio<u32>,
@@ -427,8 +517,10 @@ pub struct Command {
 }
 
 /// Representation of a SDMMC request.
-pub struct Request<'a> {
-    pub data: &'a str,
+///
+/// The data itself is described by the ADMA2 descriptor table of the transfer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Request {
     pub blksz: u32,
     pub num_blocks: u32,
     pub is_multi_block: bool,
@@ -454,6 +546,22 @@ pub struct Sdmmc<'a> {
     pub next_dma_addr: u32,
     bus_voltage: SdmmcBusVoltage,
     bus_width: SdmmcBusWidth,
+    /// The frequency the device clock source was last configured for.
+    clk_source: u32,
+    /// The actual frequency of the device clock source.
+    clk_frequency: u32,
+}
+
+/// Checks the buffers of a block read.
+fn check_read_buffers(destination: &[u8], descriptors: &[Descriptor], bounce: &[u8]) -> Result<()> {
+    if destination.len() % BLOCK_SIZE != 0
+        || bounce.len() % BLOCK_SIZE != 0
+        || descriptors.is_empty()
+    {
+        return Err(Error::InvalidArgument);
+    }
+
+    Ok(())
 }
 
 /// Gets the appropriate maximum clock frequency for the SDCLK.
@@ -499,6 +607,34 @@ fn get_sdclk_divider(bus_speed: SdmmcBusSpeed) -> u32 {
 }
 
 impl<'a> Sdmmc<'a> {
+    /// Creates a driver for the given controller.
+    ///
+    /// The controller has to be brought up through [`Sdmmc::init`] before
+    /// it can be used.
+    ///
+    /// [`Sdmmc::init`]: struct.Sdmmc.html#method.init
+    pub fn new(controller: SdmmcController, name: &'a str) -> Self {
+        Sdmmc {
+            controller,
+            registers: unsafe { &*Registers::get(controller) },
+            name,
+            has_sd: false,
+            is_clk_running: false,
+            is_sd_clk_enabled: false,
+            is_tuning_tap_val_set: false,
+            use_adma: false,
+            tap_val: 0,
+            internal_divider: 0,
+            resp: [0; 0x4],
+            resp_auto_cmd12: 0,
+            next_dma_addr: 0,
+            bus_voltage: SdmmcBusVoltage::VoltageNone,
+            bus_width: SdmmcBusWidth::Width1Bit,
+            clk_source: 0,
+            clk_frequency: 0,
+        }
+    }
+
     /// Checks if the SDMMC device clock is held in reset.
     fn is_clk_reset(&self) -> bool {
         let car = unsafe { Car::get() };
@@ -604,11 +740,11 @@ impl<'a> Sdmmc<'a> {
     }
 
     /// Sets the device clock source and CAR divider.
-    fn clk_set_source(&self, frequency: u32) -> Result<u32, ()> {
+    fn clk_set_source(&mut self, frequency: u32) -> Result<u32> {
         let car = unsafe { Car::get() };
 
-        let mut car_divider;
-        let mut out_frequency;
+        let car_divider;
+        let out_frequency;
 
         match frequency {
             25_000 => {
@@ -644,12 +780,12 @@ impl<'a> Sdmmc<'a> {
                 car_divider = SdmmcCarDivider::UhsSdr104;
             },
             _ => {
-                return Err(());
+                return Err(Error::InvalidArgument);
             },
         }
 
-        CLK_SOURCES[self.controller as usize] = frequency;
-        CLK_DIVIDERS[self.controller as usize] = out_frequency;
+        self.clk_source = frequency;
+        self.clk_frequency = out_frequency;
 
         match self.controller {
             SdmmcController::Sdmmc1 => {
@@ -674,11 +810,11 @@ impl<'a> Sdmmc<'a> {
     }
 
     /// Adjusts the device clock source value.
-    fn clk_adjust_source(&self, source: u32) -> u32 {
-        let mut value = 0;
+    fn clk_adjust_source(&mut self, source: u32) -> Result<u32> {
+        let value;
 
-        if CLK_SOURCES[self.controller as usize] == source {
-            value = CLK_DIVIDERS[self.controller as usize];
+        if self.clk_source == source {
+            value = self.clk_frequency;
         } else {
             let was_already_enabled = self.is_clk_enabled();
 
@@ -687,7 +823,7 @@ impl<'a> Sdmmc<'a> {
                 self.clk_set_disabled();
             }
 
-            value = self.clk_set_source(source).unwrap();
+            value = self.clk_set_source(source)?;
 
             // Clock was already enabled, enable it back.
             if was_already_enabled {
@@ -698,7 +834,7 @@ impl<'a> Sdmmc<'a> {
             self.is_clk_reset();
         }
 
-        value
+        Ok(value)
     }
 
     /// Enables the SD clock, if possible.
@@ -721,22 +857,13 @@ impl<'a> Sdmmc<'a> {
         self.is_sd_clk_enabled = false;
     }
 
-    /// Automatically enables or disables the SD clock.
-    fn adjust_sd_clock(&mut self) {
-        if !self.has_sd && self.registers.clock_control.read() & (1 << 2) != 0 {
-            self.disable_sd_clock();
-        } else if self.is_sd_clk_enabled && self.registers.clock_control.read() & (1 << 2) == 0 {
-            self.enable_sd_clock();
-        }
-    }
-
     /// Returns the clock control value. Used for dummy reads.
     fn read_sd_clock_control(&self) -> u16 {
         self.registers.clock_control.read()
     }
 
     /// Starts the SDMMC clock.
-    fn clk_start(&self, source: u32) {
+    fn clk_start(&mut self, source: u32) -> Result<()> {
         // Clock was already enabled. Disable it.
         if self.is_clk_enabled() {
             self.clk_set_disabled();
@@ -746,7 +873,7 @@ impl<'a> Sdmmc<'a> {
         self.clk_set_reset();
 
         // Configure the device clock source.
-        let clk_divider = self.clk_set_source(source).unwrap();
+        let clk_divider = self.clk_set_source(source)?;
 
         // Enable the device clock.
         self.clk_set_enabled();
@@ -762,10 +889,14 @@ impl<'a> Sdmmc<'a> {
 
         // Dummy read for value refreshing.
         self.is_clk_reset();
+
+        self.is_clk_running = true;
+
+        Ok(())
     }
 
     /// Stops the SDMMC clock.
-    fn clk_stop(&self) {
+    fn clk_stop(&mut self) {
         // Put the device clock in reset.
         self.clk_set_reset();
 
@@ -774,6 +905,8 @@ impl<'a> Sdmmc<'a> {
 
         // Dummy read for value refreshing.
         self.is_clk_reset();
+
+        self.is_clk_running = false;
     }
 
     /// Configures the clock trimming.
@@ -814,7 +947,7 @@ impl<'a> Sdmmc<'a> {
     }
 
     /// Configures automatic calibration.
-    fn autocal_config(&self, voltage: SdmmcBusVoltage) -> Result<(), ()> {
+    fn autocal_config(&self, voltage: SdmmcBusVoltage) -> Result<()> {
         match self.controller {
             SdmmcController::Sdmmc1 | SdmmcController::Sdmmc3 => match voltage {
                 SdmmcBusVoltage::Voltage1V8 => {
@@ -835,13 +968,13 @@ impl<'a> Sdmmc<'a> {
                 },
                 _ => {
                     // uSD does not support requested voltage.
-                    return Err(());
+                    return Err(Error::InvalidArgument);
                 },
             },
             SdmmcController::Sdmmc2 | SdmmcController::Sdmmc4 => {
                 if voltage != SdmmcBusVoltage::Voltage1V8 {
                     // eMMC can only run at 1V8.
-                    return Err(());
+                    return Err(Error::InvalidArgument);
                 }
 
                 self.registers
@@ -857,9 +990,13 @@ impl<'a> Sdmmc<'a> {
     }
 
     /// Runs automatic calibration.
-    fn autocal_run(&mut self, voltage: SdmmcBusVoltage) {
+    ///
+    /// If the calibration doesn't finish in time, the pad drive strengths
+    /// fall back to fixed values for the voltage and an error is returned.
+    fn autocal_run(&mut self, voltage: SdmmcBusVoltage) -> Result<()> {
         let padctl = unsafe { Padctl::get() };
         let mut restart_sd_clock = false;
+        let mut result = Ok(());
 
         // SD clock is enabled, disable it and restart later.
         if self.is_sd_clk_enabled {
@@ -910,8 +1047,8 @@ impl<'a> Sdmmc<'a> {
                 // Upon timeout, fall back to the standard values.
                 match self.controller {
                     SdmmcController::Sdmmc1 => {
-                        let mut drvup;
-                        let mut drvdn;
+                        let drvup;
+                        let drvdn;
 
                         if voltage == SdmmcBusVoltage::Voltage3V3 {
                             drvup = 0x12;
@@ -946,6 +1083,9 @@ impl<'a> Sdmmc<'a> {
                     self.registers.auto_cal_config.read()
                         & !AutocalConfiguration::SDMMC_AUTOCAL_ENABLE.bits(),
                 );
+
+                result = Err(Error::Timeout);
+                break;
             }
         }
 
@@ -958,10 +1098,101 @@ impl<'a> Sdmmc<'a> {
         if restart_sd_clock {
             self.enable_sd_clock();
         }
+
+        result
+    }
+
+    /// Sets the signaling voltage of the SD card I/O rail and pads.
+    fn set_sd_io_voltage(&mut self, i2c: &I2c, voltage: SdmmcBusVoltage) -> Result<()> {
+        let pmc = unsafe { Pmc::get() };
+
+        match voltage {
+            SdmmcBusVoltage::Voltage1V8 => {
+                Regulator::LDO2.set_voltage(i2c, SD_IO_VOLTAGE_1V8)?;
+                pmc.pwr_det_val
+                    .write(pmc.pwr_det_val.read() & !PMC_PWR_DET_SDMMC1_IO_EN);
+            },
+            SdmmcBusVoltage::Voltage3V3 => {
+                Regulator::LDO2.set_voltage(i2c, SD_IO_VOLTAGE_3V3)?;
+                pmc.pwr_det_val
+                    .write(pmc.pwr_det_val.read() | PMC_PWR_DET_SDMMC1_IO_EN);
+            },
+            _ => return Err(Error::InvalidArgument),
+        }
+
+        // Recalibrate the pad drive strengths for the new voltage. A timeout
+        // leaves the fixed fallback values in place, which are good enough.
+        self.autocal_config(voltage)?;
+        let _ = self.autocal_run(voltage);
+
+        Ok(())
+    }
+
+    /// Checks whether the card drives all DAT[3:0] lines high.
+    fn data_lines_high(&self) -> bool {
+        let mask = PresentState::SDHCI_DATA_LVL_MASK.bits();
+
+        self.registers.present_state.read() & mask == mask
+    }
+
+    /// Switches the SD card to 1.8V signaling for the UHS-I modes.
+    ///
+    /// The card must have accepted the switch through CMD11 (VOLTAGE_SWITCH)
+    /// beforehand. The SD clock is stopped while the I/O rail (LDO2 of the
+    /// MAX77620 on `i2c`) and the pads are switched and recalibrated. If the
+    /// card doesn't signal completion by releasing its DAT lines, the
+    /// controller falls back to 3.3V and [`Error::Sdmmc`] is returned, so the
+    /// card has to be power-cycled and used in a 3.3V mode.
+    ///
+    /// [`Error::Sdmmc`]: ../enum.Error.html#variant.Sdmmc
+    pub fn switch_to_1v8(&mut self, i2c: &I2c) -> Result<()> {
+        // Only the SD card slot can switch voltages, eMMC always runs at 1.8V.
+        if self.controller != SdmmcController::Sdmmc1 {
+            return Err(Error::InvalidArgument);
+        }
+
+        if self.registers.capabilities.read() & Capabilities::SDHCI_CAN_VDD_180.bits() == 0 {
+            return Err(Error::Sdmmc);
+        }
+
+        // Stop the SD clock while the voltage changes.
+        self.disable_sd_clock();
+
+        if self.set_sd_io_voltage(i2c, SdmmcBusVoltage::Voltage1V8).is_ok() {
+            self.registers.host_control2.write(
+                self.registers.host_control2.read() | HostControl2::SDHCI_CTRL_VDD_180.bits(),
+            );
+
+            // Give the regulator 5ms to settle.
+            usleep(5000);
+
+            if self.registers.host_control2.read() & HostControl2::SDHCI_CTRL_VDD_180.bits() != 0 {
+                // Restart the SD clock, the card releases the DAT lines within 1ms.
+                self.enable_sd_clock();
+                usleep(1000);
+
+                if self.data_lines_high() {
+                    self.bus_voltage = SdmmcBusVoltage::Voltage1V8;
+                    return Ok(());
+                }
+
+                self.disable_sd_clock();
+            }
+        }
+
+        // The switch failed, fall back to 3.3V signaling.
+        self.registers.host_control2.write(
+            self.registers.host_control2.read() & !HostControl2::SDHCI_CTRL_VDD_180.bits(),
+        );
+        let _ = self.set_sd_io_voltage(i2c, SdmmcBusVoltage::Voltage3V3);
+        self.bus_voltage = SdmmcBusVoltage::Voltage3V3;
+        self.enable_sd_clock();
+
+        Err(Error::Sdmmc)
     }
 
     /// Enables the internal clock.
-    fn internal_clk_enable(&mut self) -> Result<(), ()> {
+    fn internal_clk_enable(&mut self) -> Result<()> {
         // Enable the internal clock.
         self.registers
             .clock_control
@@ -970,21 +1201,11 @@ impl<'a> Sdmmc<'a> {
         // Force a register read to refresh the clock control value.
         self.read_sd_clock_control();
 
-        // Program a timeout of 2000 milliseconds.
-        let timebase = get_microseconds();
-        let mut is_timeout = false;
-
-        // Wait for the clock to stabilize.
-        while !is_timeout && self.registers.clock_control.read() & (1 << 1) == 0 {
-            // Keep checking if timeout expired.
-            is_timeout = get_time_since(timebase) > 2_000_000;
-        }
-
-        // Clock failed to stabilize.
-        if is_timeout {
-            // Clock never stabilized.
-            return Err(());
-        }
+        // Wait up to 2000 milliseconds for the clock to stabilize.
+        wait_for(
+            || self.registers.clock_control.read() & (1 << 1) != 0,
+            2_000_000,
+        )?;
 
         // Configure clock control and host control 2.
         self.registers.host_control2.write(
@@ -1000,7 +1221,7 @@ impl<'a> Sdmmc<'a> {
         // Ensure 64-bit addressing is supported.
         if self.registers.capabilities.read() & Capabilities::SDHCI_CAN_64BIT.bits() == 0 {
             // 64-bit addressing is unsupported.
-            return Err(());
+            return Err(Error::Sdmmc);
         }
 
         // Enable 64-bit addressing.
@@ -1029,6 +1250,384 @@ impl<'a> Sdmmc<'a> {
         Ok(())
     }
 
+    /// Brings up the controller for the given bus configuration.
+    ///
+    /// Starts the device clock, calibrates the pads for the voltage and
+    /// powers the bus, after which the SD clock runs at the frequency of the
+    /// bus speed. Calibration timeouts leave the fixed fallback drive
+    /// strengths in place and aren't treated as errors.
+    pub fn init(
+        &mut self,
+        voltage: SdmmcBusVoltage,
+        bus_width: SdmmcBusWidth,
+        bus_speed: SdmmcBusSpeed,
+    ) -> Result<()> {
+        // Start the device clock at the frequency of the bus speed.
+        self.clk_start(get_sdclk_frequency(bus_speed))?;
+
+        // Configure the clock trimming.
+        self.vendor_clock_cntrl_config();
+
+        // Force a register read to refresh the clock control value.
+        self.read_sd_clock_control();
+
+        // Calibrate the pads for the bus voltage.
+        self.autocal_config(voltage)?;
+        let _ = self.autocal_run(voltage);
+
+        // Bring up the internal clock.
+        self.internal_clk_enable()?;
+
+        // Power the bus and configure it.
+        self.set_voltage(voltage);
+        self.set_bus_width(bus_width);
+        self.set_bus_speed(bus_speed)?;
+
+        // Start driving the SD clock.
+        self.has_sd = true;
+        self.enable_sd_clock();
+
+        Ok(())
+    }
+
+    /// Shuts the controller down.
+    ///
+    /// Stops the SD clock, removes the bus power and stops the device clock.
+    pub fn shutdown(&mut self) {
+        self.disable_sd_clock();
+        self.has_sd = false;
+
+        self.set_voltage(SdmmcBusVoltage::VoltageNone);
+        self.clk_stop();
+    }
+
+    /// Points the controller to an ADMA2 descriptor table for the next
+    /// data transfer.
+    ///
+    /// The table must have been terminated through [`DescriptorTable::finish`]
+    /// and the data buffers prepared for the transfer direction.
+    ///
+    /// Fails with [`Error::InvalidArgument`] for an empty table and with
+    /// [`Error::Sdmmc`] if the controller can't do ADMA2, in which case the
+    /// transfer has to fall back to PIO.
+    ///
+    /// [`DescriptorTable::finish`]: struct.DescriptorTable.html#method.finish
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    /// [`Error::Sdmmc`]: ../enum.Error.html#variant.Sdmmc
+    pub fn set_adma_table(&mut self, table: &DescriptorTable<'_>) -> Result<()> {
+        if table.descriptors().is_empty() {
+            return Err(Error::InvalidArgument);
+        }
+
+        if !self.use_adma {
+            return Err(Error::Sdmmc);
+        }
+
+        let address = table.address() as u64;
+
+        self.registers.adma_address.write(address as u32);
+        self.registers.upper_adma_address.write((address >> 32) as u32);
+
+        // Select ADMA2 with 64-bit addressing.
+        self.registers
+            .host_control
+            .write(self.registers.host_control.read() & !HostControl::SDHCI_CTRL_DMA_MASK.bits());
+        self.registers
+            .host_control
+            .write(self.registers.host_control.read() | HostControl::SDHCI_CTRL_ADMA64.bits());
+
+        Ok(())
+    }
+
+    /// Resets the given parts of the controller.
+    fn reset(&self, parts: SoftwareReset) -> Result<()> {
+        self.registers.software_reset.write(parts.bits());
+
+        wait_for(
+            || self.registers.software_reset.read() & parts.bits() == 0,
+            Timeouts::SDMMC_RESET_TIMEOUT.bits(),
+        )
+    }
+
+    /// Resets the command and data lines after a failed command.
+    fn reset_lines(&self) {
+        // The reason of the failure is more relevant than a failed reset.
+        let _ = self.reset(SoftwareReset::SDHCI_RESET_CMD | SoftwareReset::SDHCI_RESET_DATA);
+    }
+
+    /// Waits for any of the given interrupts and acknowledges them.
+    ///
+    /// On errors, the command and data lines are reset, so the next
+    /// command can be issued.
+    fn wait_for_interrupt(&self, interrupts: InterruptStatus, timeout: u32) -> Result<()> {
+        let timebase = get_microseconds();
+
+        loop {
+            let status = self.registers.int_status.read();
+
+            if status & InterruptStatus::SDHCI_INT_ERROR.bits() != 0 {
+                // Acknowledge the errors.
+                self.registers.int_status.write(status);
+                self.reset_lines();
+
+                let timeouts =
+                    InterruptStatus::SDHCI_INT_TIMEOUT | InterruptStatus::SDHCI_INT_DATA_TIMEOUT;
+                if status & timeouts.bits() != 0 {
+                    return Err(Error::Timeout);
+                }
+
+                return Err(Error::Sdmmc);
+            }
+
+            if status & interrupts.bits() != 0 {
+                self.registers.int_status.write(status & interrupts.bits());
+
+                return Ok(());
+            }
+
+            if get_time_since(timebase) > timeout {
+                self.reset_lines();
+
+                return Err(Error::Timeout);
+            }
+
+            yield_now();
+        }
+    }
+
+    /// Issues a command and waits for it to complete.
+    ///
+    /// With a request, the data is transferred through the ADMA2 table that
+    /// has been set up before.
+    fn execute(&mut self, command: &mut Command, request: Option<&Request>) -> Result<()> {
+        let response = CommandResponse::from_bits_truncate(command.flags);
+        let is_busy = response.contains(CommandResponse::SDMMC_RSP_BUSY);
+
+        // Wait for the command and, if needed, the data lines to be free.
+        let mut inhibit = PresentState::SDHCI_CMD_INHIBIT;
+        if request.is_some() || is_busy {
+            inhibit |= PresentState::SDHCI_DATA_INHIBIT;
+        }
+        wait_for(
+            || self.registers.present_state.read() & inhibit.bits() == 0,
+            Timeouts::SDMMC_COMMAND_TIMEOUT.bits(),
+        )?;
+
+        // Determine the response type.
+        let mut flags = if !response.contains(CommandResponse::SDMMC_RSP_PRESENT) {
+            CommandFlags::SDHCI_CMD_RESP_NONE
+        } else if response.contains(CommandResponse::SDMMC_RSP_136) {
+            CommandFlags::SDHCI_CMD_RESP_LONG
+        } else if is_busy {
+            CommandFlags::SDHCI_CMD_RESP_SHORT_BUSY
+        } else {
+            CommandFlags::SDHCI_CMD_RESP_SHORT
+        };
+
+        if response.contains(CommandResponse::SDMMC_RSP_CRC) {
+            flags |= CommandFlags::SDHCI_CMD_CRC;
+        }
+
+        if response.contains(CommandResponse::SDMMC_RSP_OPCODE) {
+            flags |= CommandFlags::SDHCI_CMD_INDEX;
+        }
+
+        // Configure the data transfer.
+        if let Some(request) = request {
+            let mut mode = TransferMode::SDHCI_TRNS_DMA;
+
+            if request.is_multi_block {
+                mode |= TransferMode::SDHCI_TRNS_BLK_CNT_EN | TransferMode::SDHCI_TRNS_MULTI;
+            }
+
+            if request.is_auto_cmd12 {
+                mode |= TransferMode::SDHCI_TRNS_AUTO_CMD12;
+            }
+
+            if request.is_read {
+                mode |= TransferMode::SDHCI_TRNS_READ;
+            }
+
+            self.registers.block_size.write((request.blksz & 0xFFF) as u16);
+            self.registers.block_count.write(request.num_blocks as u16);
+            self.registers.transfer_mode.write(mode.bits());
+
+            flags |= CommandFlags::SDHCI_CMD_DATA;
+        }
+
+        // Issue the command.
+        self.registers.argument.write(command.arg);
+        self.registers
+            .command
+            .write(((command.opcode as u16) << 8) | flags.bits());
+
+        self.wait_for_interrupt(
+            InterruptStatus::SDHCI_INT_RESPONSE,
+            Timeouts::SDMMC_COMMAND_TIMEOUT.bits(),
+        )?;
+
+        // Fetch the response.
+        if response.contains(CommandResponse::SDMMC_RSP_136) {
+            // The controller strips the CRC, shift the response back into place.
+            for i in 0..4 {
+                command.resp[i] = self.registers.response[3 - i].read() << 8;
+
+                if i != 3 {
+                    command.resp[i] |= self.registers.response[2 - i].read() >> 24;
+                }
+            }
+        } else if response.contains(CommandResponse::SDMMC_RSP_PRESENT) {
+            command.resp[0] = self.registers.response[0].read();
+        }
+
+        self.resp = command.resp;
+
+        // Wait for the data transfer or the card to leave the busy state.
+        if let Some(request) = request {
+            self.wait_for_interrupt(
+                InterruptStatus::SDHCI_INT_DATA_END,
+                Timeouts::SDMMC_TRANSFER_TIMEOUT.bits(),
+            )?;
+
+            if request.is_auto_cmd12 {
+                self.resp_auto_cmd12 = self.registers.response[3].read();
+            }
+        } else if is_busy {
+            self.wait_for_interrupt(
+                InterruptStatus::SDHCI_INT_DATA_END,
+                Timeouts::SDMMC_COMMAND_TIMEOUT.bits(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a command without data.
+    ///
+    /// The response is stored in the command.
+    pub fn send_command(&mut self, command: &mut Command) -> Result<()> {
+        self.execute(command, None)
+    }
+
+    /// Sends a command that transfers data through an ADMA2 descriptor table.
+    ///
+    /// The table must have been terminated through [`DescriptorTable::finish`]
+    /// and the cache maintenance for the data buffers is up to the caller.
+    /// See [`Sdmmc::set_adma_table`] for the possible errors besides those of
+    /// the transfer.
+    ///
+    /// [`DescriptorTable::finish`]: struct.DescriptorTable.html#method.finish
+    /// [`Sdmmc::set_adma_table`]: struct.Sdmmc.html#method.set_adma_table
+    pub fn transfer(
+        &mut self,
+        command: &mut Command,
+        request: &Request,
+        table: &DescriptorTable<'_>,
+    ) -> Result<()> {
+        self.set_adma_table(table)?;
+        self.execute(command, Some(request))
+    }
+
+    /// Reads a buffer of whole blocks in a single transfer.
+    fn read_transfer(
+        &mut self,
+        address: u32,
+        buffer: &mut [u8],
+        descriptors: &mut [Descriptor],
+    ) -> Result<()> {
+        let num_blocks = (buffer.len() / BLOCK_SIZE) as u32;
+        let is_multi_block = num_blocks > 1;
+
+        let mut table = DescriptorTable::new(descriptors);
+        table.push(buffer.as_ptr() as usize, buffer.len())?;
+        table.finish()?;
+
+        let mut command = Command {
+            opcode: if is_multi_block {
+                MMC_READ_MULTIPLE_BLOCK
+            } else {
+                MMC_READ_SINGLE_BLOCK
+            },
+            arg: address,
+            resp: [0; 0x4],
+            flags: SDMMC_RSP_R1.bits(),
+        };
+        let request = Request {
+            blksz: BLOCK_SIZE as u32,
+            num_blocks,
+            is_multi_block,
+            is_read: true,
+            is_auto_cmd12: is_multi_block,
+        };
+
+        prepare_read(buffer.as_ptr() as usize, buffer.len());
+        let result = self.transfer(&mut command, &request, &table);
+        complete_read(buffer.as_ptr() as usize, buffer.len());
+
+        result
+    }
+
+    /// Reads blocks into `destination` through ADMA2.
+    ///
+    /// `address` is the argument of the read commands, i.e. the block
+    /// address for high capacity cards. The read is split into transfers
+    /// as large as the `descriptors` can describe. Destinations that share
+    /// cache lines with other data are read through `bounce`, which has to
+    /// be cache line aligned and a multiple of [`BLOCK_SIZE`].
+    ///
+    /// Fails with [`Error::InvalidArgument`] if `destination` isn't made up
+    /// of whole blocks or no descriptors are given.
+    ///
+    /// [`BLOCK_SIZE`]: constant.BLOCK_SIZE.html
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn read_blocks(
+        &mut self,
+        address: u32,
+        destination: &mut [u8],
+        descriptors: &mut [Descriptor],
+        bounce: &mut [u8],
+    ) -> Result<()> {
+        check_read_buffers(destination, descriptors, bounce)?;
+
+        // The block count register limits transfers to 65535 blocks.
+        let max_blocks = (descriptors.len() * (MAX_DESCRIPTOR_LENGTH / BLOCK_SIZE))
+            .min(u16::max_value() as usize);
+
+        read_bounced(destination, bounce, |buffer, offset| {
+            let mut address = address + (offset / BLOCK_SIZE) as u32;
+
+            for chunk in buffer.chunks_mut(max_blocks * BLOCK_SIZE) {
+                self.read_transfer(address, chunk, descriptors)?;
+                address += (chunk.len() / BLOCK_SIZE) as u32;
+
+                yield_now();
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Reads blocks into `destination` through ADMA2, retrying failed reads
+    /// according to the given policy.
+    ///
+    /// Every attempt starts over at `address`. See [`Sdmmc::read_blocks`]
+    /// for the meaning of the arguments and the possible errors.
+    ///
+    /// [`Sdmmc::read_blocks`]: struct.Sdmmc.html#method.read_blocks
+    pub fn read_blocks_with(
+        &mut self,
+        policy: &RetryPolicy,
+        address: u32,
+        destination: &mut [u8],
+        descriptors: &mut [Descriptor],
+        bounce: &mut [u8],
+    ) -> Result<()> {
+        // Invalid buffers won't get any better by retrying.
+        check_read_buffers(destination, descriptors, bounce)?;
+
+        policy.run(|| self.read_blocks(address, destination, descriptors, bounce))
+    }
+
     /// Gets the bus width.
     pub fn get_bus_width(&self) -> SdmmcBusWidth {
         self.bus_width
@@ -1147,7 +1746,7 @@ impl<'a> Sdmmc<'a> {
         }
     }
 
-    fn dllcal_run(&mut self) -> Result<(), ()> {
+    fn dllcal_run(&mut self) -> Result<()> {
         let mut shutdown_sd_clock = false;
 
         // SD clock is disabled, enable it.
@@ -1164,37 +1763,17 @@ impl<'a> Sdmmc<'a> {
         // Force a register read to refresh the clock control value.
         self.read_sd_clock_control();
 
-        // Program a timeout of 5 milliseconds.
-        let mut timebase = get_microseconds();
-        let mut is_timeout = false;
+        // Wait up to 5 milliseconds for CALIBRATE to be cleared.
+        wait_for(
+            || self.registers.vendor_dllcal_cfg.read() & 0x8000_0000 == 0,
+            5_000,
+        )?;
 
-        // Wait for CALIBRATE to be cleared.
-        while !is_timeout && self.registers.vendor_dllcal_cfg.read() & 0x8000_0000 != 0 {
-            // Keep checking if timeout expired.
-            is_timeout = get_time_since(timebase) > 5_000;
-        }
-
-        // Calibration failed.
-        if is_timeout {
-            // DLLCAL failed.
-            return Err(());
-        }
-
-        // Program a timeout of 10 milliseconds.
-        timebase = get_microseconds();
-        is_timeout = false;
-
-        // Wait for DLL_CAL_ACTIVE to be cleared.
-        while !is_timeout && self.registers.vendor_dllcal_cfg_sta.read() & 0x8000_0000 != 0 {
-            // Keep checking if timeout expired.
-            is_timeout = get_time_since(timebase) > 10_000;
-        }
-
-        // Calibration failed.
-        if is_timeout {
-            // DLLCAL failed.
-            return Err(());
-        }
+        // Wait up to 10 milliseconds for DLL_CAL_ACTIVE to be cleared.
+        wait_for(
+            || self.registers.vendor_dllcal_cfg_sta.read() & 0x8000_0000 == 0,
+            10_000,
+        )?;
 
         // If requested, disable the SD clock.
         if shutdown_sd_clock {
@@ -1205,7 +1784,12 @@ impl<'a> Sdmmc<'a> {
     }
 
     /// Sets the bus speed.
-    pub fn set_bus_speed(&mut self, bus_speed: SdmmcBusSpeed) -> Result<(), ()> {
+    ///
+    /// Fails with [`Error::Sdmmc`] for bus speeds the controller can't be
+    /// configured for.
+    ///
+    /// [`Error::Sdmmc`]: ../enum.Error.html#variant.Sdmmc
+    pub fn set_bus_speed(&mut self, bus_speed: SdmmcBusSpeed) -> Result<()> {
         let mut restart_sd_clock = false;
 
         // SD clock is enabled, disable it and restart later.
@@ -1287,7 +1871,7 @@ impl<'a> Sdmmc<'a> {
 
             _ => {
                 // Switching to unsupported speed.
-                return Err(());
+                return Err(Error::Sdmmc);
             },
         }
 
@@ -1299,7 +1883,7 @@ impl<'a> Sdmmc<'a> {
         let divider = get_sdclk_divider(bus_speed);
 
         // Adjust the CAR side of the clock.
-        let out_frequency = self.clk_adjust_source(frequency);
+        let out_frequency = self.clk_adjust_source(frequency)?;
 
         // Save the internal divider value.
         self.internal_divider = (out_frequency + divider - 1) / divider;
@@ -1332,3 +1916,159 @@ impl<'a> Sdmmc<'a> {
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// A cache line aligned buffer of two blocks.
+    #[repr(align(64))]
+    struct Aligned([u8; 2 * BLOCK_SIZE]);
+
+    /// Gets the address of a register.
+    fn address<T>(register: &T) -> usize {
+        register as *const _ as usize
+    }
+
+    /// Tests a successful switch of the SD card to 1.8V signaling.
+    #[test]
+    fn switches_to_1v8() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        let mut sdmmc = Sdmmc::new(SdmmcController::Sdmmc1, "SD");
+        mock::set(
+            address(&sdmmc.registers.capabilities),
+            u64::from(Capabilities::SDHCI_CAN_VDD_180.bits()),
+        );
+        // The card releases its DAT lines.
+        mock::set(
+            address(&sdmmc.registers.present_state),
+            u64::from(PresentState::SDHCI_DATA_LVL_MASK.bits()),
+        );
+
+        sdmmc.switch_to_1v8(&I2c::C5).unwrap();
+
+        assert!(sdmmc.get_voltage() == SdmmcBusVoltage::Voltage1V8);
+        // LDO2 is set to 1.8V through `I2C_CMD_DATA1` of I²C 5.
+        assert!(mock::writes(0x7000_D00C).contains(&0x1427));
+    }
+
+    /// Tests reading blocks through ADMA2.
+    #[test]
+    fn reads_blocks() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        let mut sdmmc = Sdmmc::new(SdmmcController::Sdmmc1, "SD");
+        sdmmc.use_adma = true;
+
+        let int_status = address(&sdmmc.registers.int_status);
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_RESPONSE.bits()));
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_DATA_END.bits()));
+
+        let mut destination = Aligned([0; 2 * BLOCK_SIZE]);
+        let mut descriptors = [Descriptor::default(); 2];
+        let table = descriptors.as_ptr() as u64;
+
+        sdmmc
+            .read_blocks(0x800, &mut destination.0, &mut descriptors, &mut [])
+            .unwrap();
+
+        assert_eq!(descriptors[0].length as usize, 2 * BLOCK_SIZE);
+        assert_eq!(
+            mock::writes(address(&sdmmc.registers.adma_address)),
+            [table & 0xFFFF_FFFF]
+        );
+        assert_eq!(mock::writes(address(&sdmmc.registers.argument)), [0x800]);
+        assert_eq!(mock::writes(address(&sdmmc.registers.block_count)), [2]);
+        // READ_MULTIPLE_BLOCK transferring data, with an R1 response.
+        assert_eq!(mock::writes(address(&sdmmc.registers.command)), [0x123A]);
+    }
+
+    /// Tests failing a read on an ADMA error.
+    #[test]
+    fn fails_read_on_error() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        let mut sdmmc = Sdmmc::new(SdmmcController::Sdmmc1, "SD");
+        sdmmc.use_adma = true;
+
+        let int_status = address(&sdmmc.registers.int_status);
+        let error = InterruptStatus::SDHCI_INT_ERROR | InterruptStatus::SDHCI_INT_ADMA_ERROR;
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_RESPONSE.bits()));
+        mock::expect_read(int_status, u64::from(error.bits()));
+
+        let mut destination = Aligned([0; 2 * BLOCK_SIZE]);
+        let mut descriptors = [Descriptor::default(); 1];
+
+        assert_eq!(
+            sdmmc.read_blocks(0, &mut destination.0[..BLOCK_SIZE], &mut descriptors, &mut []),
+            Err(Error::Sdmmc)
+        );
+        // The command and data lines are reset.
+        assert_eq!(
+            mock::writes(address(&sdmmc.registers.software_reset)),
+            [0x06]
+        );
+    }
+
+    /// Tests retrying a read after an ADMA error.
+    #[test]
+    fn retries_read_on_error() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        let mut sdmmc = Sdmmc::new(SdmmcController::Sdmmc1, "SD");
+        sdmmc.use_adma = true;
+
+        let int_status = address(&sdmmc.registers.int_status);
+        let error = InterruptStatus::SDHCI_INT_ERROR | InterruptStatus::SDHCI_INT_ADMA_ERROR;
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_RESPONSE.bits()));
+        mock::expect_read(int_status, u64::from(error.bits()));
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_RESPONSE.bits()));
+        mock::expect_read(int_status, u64::from(InterruptStatus::SDHCI_INT_DATA_END.bits()));
+
+        let mut destination = Aligned([0; 2 * BLOCK_SIZE]);
+        let mut descriptors = [Descriptor::default(); 1];
+
+        sdmmc
+            .read_blocks_with(
+                &RetryPolicy::DEFAULT,
+                0x800,
+                &mut destination.0[..BLOCK_SIZE],
+                &mut descriptors,
+                &mut [],
+            )
+            .unwrap();
+
+        // Both attempts read from the same block.
+        assert_eq!(mock::writes(address(&sdmmc.registers.argument)), [0x800, 0x800]);
+    }
+
+    /// Tests falling back to 3.3V if the card doesn't complete the switch.
+    #[test]
+    fn falls_back_to_3v3() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        let mut sdmmc = Sdmmc::new(SdmmcController::Sdmmc1, "SD");
+        mock::set(
+            address(&sdmmc.registers.capabilities),
+            u64::from(Capabilities::SDHCI_CAN_VDD_180.bits()),
+        );
+
+        assert_eq!(sdmmc.switch_to_1v8(&I2c::C5), Err(Error::Sdmmc));
+
+        assert!(sdmmc.get_voltage() == SdmmcBusVoltage::Voltage3V3);
+        assert_eq!(
+            mock::get(address(&sdmmc.registers.host_control2))
+                & u64::from(HostControl2::SDHCI_CTRL_VDD_180.bits()),
+            0
+        );
+        assert!(mock::writes(0x7000_D00C).ends_with(&[0x3227]));
+    }
+}
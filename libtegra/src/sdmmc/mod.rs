@@ -1 +1,16 @@
-//mod core;
+pub use self::adma::{
+    complete_read, needs_bounce, prepare_read, prepare_write, read_bounced, Descriptor,
+    DescriptorTable, MAX_DESCRIPTOR_LENGTH,
+};
+pub use self::card::{CardEvent, SdCard};
+pub use self::core::{
+    Command, CommandResponse, Request, Sdmmc, SdmmcBusSpeed, SdmmcBusVoltage, SdmmcBusWidth,
+    SdmmcController, SpiCommandResponse, BLOCK_SIZE, SDMMC_RSP_NONE, SDMMC_RSP_R1, SDMMC_RSP_R1B,
+    SDMMC_RSP_R1_NO_CRC, SDMMC_RSP_R2, SDMMC_RSP_R3, SDMMC_RSP_R4, SDMMC_RSP_R5, SDMMC_RSP_R6,
+    SDMMC_RSP_R7, SDMMC_RSP_SPI_R1, SDMMC_RSP_SPI_R1B, SDMMC_RSP_SPI_R2, SDMMC_RSP_SPI_R3,
+    SDMMC_RSP_SPI_R4, SDMMC_RSP_SPI_R5, SDMMC_RSP_SPI_R7,
+};
+
+mod adma;
+mod card;
+mod core;
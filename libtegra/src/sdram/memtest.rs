@@ -0,0 +1,263 @@
+//! DRAM testing.
+//!
+//! # Description
+//!
+//! SDRAM parameters which don't match the memory chips usually don't cause
+//! initialization to fail, but result in random corruption and hangs later
+//! on. A memory test helps to tell such faults apart from software bugs.
+//!
+//! # Implementation
+//!
+//! - [`memtest`] runs the tests selected by a [`PatternSet`] over a range of
+//! memory and stops at the first [`MemFault`].
+//!
+//! - The moving inversion tests fill memory with a pattern, then check and
+//! invert every word ascending, and check and restore it again descending.
+//! This catches stuck bits and most coupling faults between neighbouring cells.
+//!
+//! - The address-in-address test writes the address of every word into it,
+//! followed by its inverse, which catches faults in the address lines.
+//!
+//! - The tested range is overwritten, so it must not contain anything in
+//! use, including the stack of the caller.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::sdram::{self, PatternSet};
+//!
+//! fn main() {
+//!     let result = sdram::memtest(0x9000_0000..0xA000_0000, PatternSet::all(), |done, total| {
+//!         println!("{}%", done * 100 / total);
+//!     });
+//!
+//!     if let Err(fault) = result {
+//!         println!("Memory fault at {:#X}", fault.address);
+//!     }
+//! }
+//! ```
+//!
+//! [`memtest`]: fn.memtest.html
+//! [`PatternSet`]: struct.PatternSet.html
+//! [`MemFault`]: struct.MemFault.html
+
+use core::{
+    mem::size_of,
+    ops::Range,
+    ptr::{read_volatile, write_volatile},
+};
+
+/// The amount of bytes after which progress is reported.
+const PROGRESS_INTERVAL: usize = 0x10_0000;
+
+bitflags! {
+    /// The tests to run in [`memtest`].
+    ///
+    /// [`memtest`]: fn.memtest.html
+    pub struct PatternSet: u32 {
+        /// Moving inversion with all bits cleared and set.
+        const SOLID = 1 << 0;
+        /// Moving inversion with alternating bits.
+        const CHECKERBOARD = 1 << 1;
+        /// Moving inversion with a single bit walking through the word.
+        const WALKING_ONES = 1 << 2;
+        /// Every word holds its own address.
+        const ADDRESS_IN_ADDRESS = 1 << 3;
+    }
+}
+
+/// Description of a failed memory check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemFault {
+    /// The address of the faulty word.
+    pub address: usize,
+    /// The value that was written.
+    pub expected: u32,
+    /// The value that was read back.
+    pub actual: u32,
+}
+
+/// Runs the tests over the words in `range` and the progress reporting.
+struct Tester<F> {
+    /// The first word to test.
+    start: *mut u32,
+    /// The amount of words to test.
+    words: usize,
+    /// The amount of bytes tested so far.
+    done: usize,
+    /// The amount of bytes to test in total.
+    total: usize,
+    /// Gets called with the tested and the total amount of bytes.
+    progress: F,
+}
+
+impl<F: FnMut(usize, usize)> Tester<F> {
+    /// Gets the address of a word.
+    fn address(&self, index: usize) -> *mut u32 {
+        unsafe { self.start.add(index) }
+    }
+
+    /// Accounts for a tested word and reports progress.
+    fn advance(&mut self) {
+        self.done += size_of::<u32>();
+
+        if self.done % PROGRESS_INTERVAL == 0 || self.done == self.total {
+            (self.progress)(self.done, self.total);
+        }
+    }
+
+    /// Checks a word against the expected value.
+    fn check(&self, index: usize, expected: u32) -> Result<(), MemFault> {
+        let address = self.address(index);
+        let actual = unsafe { read_volatile(address) };
+
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(MemFault {
+                address: address as usize,
+                expected,
+                actual,
+            })
+        }
+    }
+
+    /// Runs a moving inversion pass with the given pattern.
+    fn moving_inversion(&mut self, pattern: u32) -> Result<(), MemFault> {
+        for index in 0..self.words {
+            unsafe { write_volatile(self.address(index), pattern) };
+        }
+
+        for index in 0..self.words {
+            self.check(index, pattern)?;
+            unsafe { write_volatile(self.address(index), !pattern) };
+        }
+
+        for index in (0..self.words).rev() {
+            self.check(index, !pattern)?;
+            unsafe { write_volatile(self.address(index), pattern) };
+            self.advance();
+        }
+
+        Ok(())
+    }
+
+    /// Runs the address-in-address test.
+    fn address_in_address(&mut self) -> Result<(), MemFault> {
+        for &invert in [0, u32::max_value()].iter() {
+            for index in 0..self.words {
+                let address = self.address(index);
+                unsafe { write_volatile(address, address as u32 ^ invert) };
+            }
+
+            for index in 0..self.words {
+                self.check(index, self.address(index) as u32 ^ invert)?;
+                self.advance();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Patterns with all bits cleared and set.
+static SOLID_PATTERNS: [u32; 2] = [0, 0xFFFF_FFFF];
+
+/// Patterns with alternating bits.
+static CHECKERBOARD_PATTERNS: [u32; 2] = [0x5555_5555, 0xAAAA_AAAA];
+
+/// Gets the moving inversion patterns of a set.
+fn patterns(set: PatternSet) -> impl Iterator<Item = u32> {
+    let solid = SOLID_PATTERNS
+        .iter()
+        .filter(move |_| set.contains(PatternSet::SOLID));
+    let checkerboard = CHECKERBOARD_PATTERNS
+        .iter()
+        .filter(move |_| set.contains(PatternSet::CHECKERBOARD));
+    let walking_ones = (0..32)
+        .map(|bit| 1 << bit)
+        .filter(move |_| set.contains(PatternSet::WALKING_ONES));
+
+    solid.chain(checkerboard).cloned().chain(walking_ones)
+}
+
+/// Tests the memory in `range` with the given tests.
+///
+/// The range is shrunk to whole 32-bit words. `progress` is called regularly
+/// with the amount of bytes tested so far and the total amount of bytes over
+/// all tests. The contents of the range are destroyed.
+pub fn memtest<F>(range: Range<usize>, set: PatternSet, progress: F) -> Result<(), MemFault>
+where
+    F: FnMut(usize, usize),
+{
+    let align = size_of::<u32>();
+    let start = (range.start + align - 1) & !(align - 1);
+    let end = range.end & !(align - 1);
+    let words = end.saturating_sub(start) / align;
+
+    let mut passes = patterns(set).count();
+    if set.contains(PatternSet::ADDRESS_IN_ADDRESS) {
+        passes += 2;
+    }
+
+    let mut tester = Tester {
+        start: start as *mut u32,
+        words,
+        done: 0,
+        total: passes * words * align,
+        progress,
+    };
+
+    for pattern in patterns(set) {
+        tester.moving_inversion(pattern)?;
+    }
+
+    if set.contains(PatternSet::ADDRESS_IN_ADDRESS) {
+        tester.address_in_address()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that intact memory passes and reports its progress.
+    #[test]
+    fn intact() {
+        let mut buffer = [0u32; 0x100];
+        let range = buffer.as_mut_ptr() as usize..buffer.as_mut_ptr() as usize + 0x400;
+        let mut reported = (0, 0);
+
+        memtest(range, PatternSet::all(), |done, total| {
+            reported = (done, total)
+        })
+        .unwrap();
+        assert_eq!(reported, (38 * 0x400, 38 * 0x400));
+    }
+
+    /// Tests that a mismatch is reported with its address.
+    #[test]
+    fn fault() {
+        let mut buffer = [0u32; 4];
+        let tester = Tester {
+            start: buffer.as_mut_ptr(),
+            words: 4,
+            done: 0,
+            total: 0x10,
+            progress: |_, _| {},
+        };
+
+        buffer[2] = 0x1234;
+        assert_eq!(tester.check(1, 0), Ok(()));
+        assert_eq!(
+            tester.check(2, 0),
+            Err(MemFault {
+                address: &buffer[2] as *const u32 as usize,
+                expected: 0,
+                actual: 0x1234,
+            })
+        );
+    }
+}
@@ -3,14 +3,26 @@
 //! # Implementation
 //!
 //! - [`get_parameters`] is to be used for retrieving SDRAM configuration parameters.
+//! The parameter tables only cover Erista units, so it fails on other revisions.
+//!
+//! - [`set_rate`] switches the EMC to another frequency after initialization.
+//!
+//! - [`memtest`] checks a range of memory for faults, to diagnose bad SDRAM parameters.
 //!
 //! - The [`init`] function initializes the SDRAM and calls  [`config_sdram`] which
 //! does the actual dirty job of writing SDRAM parameters to the respective registers
 //! to configure it.
 //!
+//! - The EMC is programmed through its broadcast aperture, which writes both
+//! channels. Completion is checked on each channel enabled by the parameters,
+//! using the per-channel registers of [`mc::Channel`].
+//!
 //! [`get_parameters`]: fn.get_parameters.html
+//! [`set_rate`]: fn.set_rate.html
+//! [`memtest`]: fn.memtest.html
 //! [`init`]: fn.init.html
 //! [`config_sdram`]: fn.config_sdram.html
+//! [`mc::Channel`]: ../mc/enum.Channel.html
 
 use core::{mem::transmute_copy, ptr::write_volatile};
 
@@ -20,13 +32,26 @@ use self::{config::DRAM_CONFIG, params::Parameters};
 use crate::{
     clock::Car,
     fuse::read_reserved_odm,
+    hw_rev::{self, SocRevision},
     i2c::{I2c, Device},
+    mc::{Channel, EMC_STATUS_TIMING_UPDATE_STALLED},
     pmc::Pmc,
-    timer::{get_microseconds, usleep},
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+pub use self::{
+    memtest::{memtest, MemFault, PatternSet},
+    rate::{set_rate, Timing},
 };
 
 mod config;
+mod memtest;
 mod params;
+mod rate;
+
+/// The maximum time a timing update may take in microseconds.
+const TIMING_UPDATE_TIMEOUT: u32 = 1000;
 
 /// Retrieves the SDRAM ID.
 #[inline]
@@ -34,8 +59,31 @@ fn get_sdram_id() -> usize {
     ((read_reserved_odm(4) & 0x38) >> 3) as usize
 }
 
+/// Waits for all channels enabled by the parameters to apply the pending
+/// timing update.
+///
+/// The EMC is programmed through the broadcast aperture, which only reflects
+/// the status of channel 0.
+fn wait_for_timing_update(params: &Parameters) -> Result<()> {
+    for channel in Channel::enabled_in(params.emc_fbio_cfg7) {
+        let status = &channel.emc().emc_status;
+
+        wait_for(
+            || status.read() & EMC_STATUS_TIMING_UPDATE_STALLED == 0,
+            TIMING_UPDATE_TIMEOUT,
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Configures the SDRAM.
-fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) {
+///
+/// Fails with [`Error::Timeout`] if an enabled channel doesn't apply the
+/// final timing update.
+///
+/// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) -> Result<()> {
     pmc.io_dpd3_req
         .write((((4 * params.emc_pmc_scratch1 >> 2) + 0x8000_0000) ^ 0xFFFF) & 0xC000_FFFF);
     usleep(params.pmc_io_dpd3_req_wait);
@@ -56,16 +104,8 @@ fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) {
             | ((params.pllm_post_divider & 0xFFFF) << 20),
     );
 
-    let mut timeout = false;
-    let wait_end = get_microseconds() + 300;
-
-    while car.pllm_base.read() & 0x8000000 == 0 && !timeout {
-        if get_microseconds() >= wait_end {
-            timeout = true;
-        }
-    }
-
-    if !timeout {
+    // Wait for PLLM to lock, but carry on without it like the bootrom does.
+    if wait_for(|| car.pllm_base.read() & 0x8000000 != 0, 300).is_ok() {
         usleep(10);
     }
 
@@ -495,7 +535,7 @@ fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) {
 
         (*((0x7001B000 + 40) as *const Mmio<u32>)).write(1);
         usleep(params.emc_timing_control_wait);
-        pmc.ddr_cntrl.write(pmc.ddr_cntrl.read() & 0xFFF8_007F);
+        pmc.ddr_cntrl.set_bits(7..19, 0);
         usleep(params.pmc_ddr_ctrl_wait);
 
         if params.memory_type == 2 {
@@ -593,6 +633,7 @@ fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) {
         (*((0x7001B000 + 984) as *const Mmio<u32>)).write(params.emc_sel_dpd_ctrl);
         (*((0x7001B000 + 256) as *const Mmio<u32>)).write(params.emc_fbio_spare | 2);
         (*((0x7001B000 + 40) as *const Mmio<u32>)).write(1);
+        wait_for_timing_update(params)?;
         (*((0x7001B000 + 1368) as *const Mmio<u32>)).write(params.emc_cfg_pipe_clk);
         (*((0x7001B000 + 1240) as *const Mmio<u32>)).write(params.emc_fdpd_ctrl_cmd_no_ramp);
 
@@ -608,22 +649,32 @@ fn config_sdram(car: &Car, pmc: &Pmc, params: &mut Parameters) {
         (*((0x70019000 + 2476) as *const Mmio<u32>)).write(params.mc_mts_carveout_reg_ctrl);
         (*((0x70019000 + 1636) as *const Mmio<u32>)).write(1);
     }
+
+    Ok(())
 }
 
 /// Retrieves the SDRAM parameters.
-pub fn get_parameters() -> Parameters {
+pub fn get_parameters() -> Result<Parameters> {
+    hw_rev::require(SocRevision::Erista)?;
+
     // TODO(Vale): LZ77 compression of the config values.
-    let parameters: Parameters = unsafe { transmute_copy(&DRAM_CONFIG[get_sdram_id()]) };
+    let config = DRAM_CONFIG.get(get_sdram_id()).ok_or(Error::UnsupportedHardware)?;
+    let parameters: Parameters = unsafe { transmute_copy(config) };
 
-    parameters
+    Ok(parameters)
 }
 
 /// Initializes and configures the SDRAM.
-pub fn init(car: &Car, pmc: &Pmc) {
-    let mut params = get_parameters();
+///
+/// The SDRAM rails are set up through the PMIC on `i2c`.
+pub fn init(car: &Car, pmc: &Pmc, i2c: &I2c) -> Result<()> {
+    #[cfg(feature = "profile")]
+    let _span = crate::profile::begin("sdram");
 
-    I2c::C5.write_byte(Device::Max77620Pwr, 0x22, 5).unwrap();
-    I2c::C5.write_byte(Device::Max77620Pwr, 0x17, 40).unwrap();
+    let mut params = get_parameters()?;
+
+    i2c.write_byte(Device::Max77620Pwr, 0x22, 5)?;
+    i2c.write_byte(Device::Max77620Pwr, 0x17, 40)?;
 
     pmc.vddp_sel.write(params.pmc_vddp_sel);
     usleep(params.pmc_vddp_sel_wait);
@@ -642,5 +693,8 @@ pub fn init(car: &Car, pmc: &Pmc) {
         }
     }
 
-    config_sdram(car, pmc, &mut params);
+    #[cfg(feature = "profile")]
+    let _span = crate::profile::begin("sdram config");
+
+    config_sdram(car, pmc, &mut params)
 }
@@ -0,0 +1,248 @@
+//! EMC frequency scaling.
+//!
+//! # Description
+//!
+//! SDRAM initialization leaves the EMC running at the boot frequency of the
+//! configuration table, usually 204MHz. Payloads which need more memory
+//! bandwidth can switch the EMC to a higher rate after training, e.g.
+//! 800MHz or 1600MHz from PLLM.
+//!
+//! Changing the EMC clock is a handshake between the clock controller and
+//! the EMC: the timings of the target rate are written to the shadow copies
+//! of the EMC registers, which the EMC latches when the clock source in the
+//! CAR changes. Afterwards, the DRAM has to be recalibrated.
+//!
+//! # Implementation
+//!
+//! - [`Timing`] describes a target rate: the `CLK_RST_CONTROLLER_CLK_SOURCE_EMC`
+//! value and the EMC and MC register values for it. These depend on the DRAM
+//! and the board and have to be taken from trained tables, Mirage ships none.
+//!
+//! - [`set_rate`] looks up the table of a rate and runs the clock-change
+//! sequence, followed by a ZQ calibration.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::sdram::{self, Timing};
+//!
+//! const TIMINGS: &[Timing] = &[
+//!     // Board-specific tables...
+//! ];
+//!
+//! fn main() {
+//!     sdram::set_rate(1_600_000, TIMINGS).unwrap();
+//! }
+//! ```
+//!
+//! [`Timing`]: struct.Timing.html
+//! [`set_rate`]: fn.set_rate.html
+
+use mirage_mmio::{BlockMmio, Mmio, VolatileStorage};
+
+use crate::{
+    clock::Car,
+    mc::{Channel, EMC_BASE, MC_BASE},
+    timer::{usleep, wait_for},
+    Error, Result,
+};
+
+/// Offset of the `EMC_INTSTATUS_0` register.
+const EMC_INTSTATUS: u32 = 0x0;
+/// Offset of the `EMC_DBG_0` register.
+const EMC_DBG: u32 = 0x8;
+/// Offset of the `EMC_ZQ_CAL_0` register.
+const EMC_ZQ_CAL: u32 = 0x2EC;
+
+/// Offset of the `MC_TIMING_CONTROL_0` register.
+const MC_TIMING_CONTROL: u32 = 0xFC;
+
+/// The clock change has been completed, in `EMC_INTSTATUS`.
+const EMC_INTSTATUS_CLKCHANGE_COMPLETE: u32 = 1 << 4;
+/// Register writes go to the active instead of the shadow registers.
+const EMC_DBG_WRITE_MUX_ACTIVE: u32 = 1 << 1;
+/// Starts a ZQ calibration of both devices.
+const EMC_ZQ_CAL_ZQ_CAL_CMD: u32 = 1 << 0;
+/// Latches the ZQ calibration results of both devices.
+const EMC_ZQ_CAL_ZQ_LATCH_CMD: u32 = 1 << 1;
+
+/// Makes the MC latch the shadowed arbitration registers.
+const MC_TIMING_CONTROL_TIMING_UPDATE: u32 = 1 << 0;
+
+/// The PLLM lock bit in `CLK_RST_CONTROLLER_PLLM_BASE`.
+const PLLM_LOCK: u32 = 1 << 27;
+
+/// The maximum time the clock change may take in microseconds.
+const CLKCHANGE_TIMEOUT: u32 = 1000;
+/// The time a ZQ calibration takes in microseconds.
+const ZQ_CAL_DELAY: u32 = 1;
+
+/// Timing parameters for an EMC rate.
+#[derive(Clone, Copy, Debug)]
+pub struct Timing<'a> {
+    /// The EMC rate in kHz.
+    pub rate: u32,
+    /// The value of `CLK_RST_CONTROLLER_CLK_SOURCE_EMC` for the rate.
+    pub clk_source_emc: u32,
+    /// EMC registers as `(offset, value)` pairs, written to the shadow registers.
+    pub burst_registers: &'a [(u32, u32)],
+    /// MC arbitration registers as `(offset, value)` pairs.
+    pub mc_registers: &'a [(u32, u32)],
+}
+
+impl<'a> Timing<'a> {
+    /// Whether the rate is derived from PLLM.
+    fn uses_pllm(&self) -> bool {
+        match self.clk_source_emc >> 29 {
+            0 | 4 => true,
+            _ => false,
+        }
+    }
+}
+
+/// Gets an EMC register.
+fn emc_register(offset: u32) -> BlockMmio<Mmio<u32>> {
+    unsafe { BlockMmio::new((EMC_BASE + offset) as usize) }
+}
+
+/// Gets an MC register.
+fn mc_register(offset: u32) -> BlockMmio<Mmio<u32>> {
+    unsafe { BlockMmio::new((MC_BASE + offset) as usize) }
+}
+
+/// Finds the timing parameters for a rate in kHz.
+fn find_timing<'a>(rate: u32, timings: &'a [Timing<'a>]) -> Option<&'a Timing<'a>> {
+    timings.iter().find(|timing| timing.rate == rate)
+}
+
+/// Waits for the EMC of every enabled channel to acknowledge the clock change.
+fn wait_for_clock_change() -> Result<()> {
+    for channel in Channel::enabled() {
+        let intstatus = &channel.emc().intstatus;
+
+        wait_for(
+            || intstatus.read() & EMC_INTSTATUS_CLKCHANGE_COMPLETE != 0,
+            CLKCHANGE_TIMEOUT,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Switches the EMC to the given rate in kHz.
+///
+/// The timing parameters of the rate are looked up in `timings`. PLLM has to
+/// be running if the rate is derived from it, which is the case after
+/// [`init`]. SDRAM contents are preserved.
+///
+/// [`init`]: fn.init.html
+pub fn set_rate(rate: u32, timings: &[Timing<'_>]) -> Result<()> {
+    let car = unsafe { Car::get() };
+    let timing = find_timing(rate, timings).ok_or(Error::InvalidArgument)?;
+
+    if timing.uses_pllm() && car.pllm_base.read() & PLLM_LOCK == 0 {
+        return Err(Error::Clock);
+    }
+
+    // Program the shadow registers.
+    let dbg = emc_register(EMC_DBG);
+    let dbg_value = dbg.read();
+    dbg.write(dbg_value & !EMC_DBG_WRITE_MUX_ACTIVE);
+
+    for &(offset, value) in timing.burst_registers {
+        emc_register(offset).write(value);
+    }
+    for &(offset, value) in timing.mc_registers {
+        mc_register(offset).write(value);
+    }
+
+    // Changing the clock source makes the EMC latch the shadow registers.
+    emc_register(EMC_INTSTATUS).write(EMC_INTSTATUS_CLKCHANGE_COMPLETE);
+    car.clk_source_emc.write(timing.clk_source_emc);
+
+    let result = wait_for_clock_change();
+    dbg.write(dbg_value);
+    result?;
+
+    mc_register(MC_TIMING_CONTROL).write(MC_TIMING_CONTROL_TIMING_UPDATE);
+
+    // Recalibrate the DRAM drive strength for the new rate.
+    let zq_cal = emc_register(EMC_ZQ_CAL);
+    zq_cal.write(EMC_ZQ_CAL_ZQ_CAL_CMD);
+    usleep(ZQ_CAL_DELAY);
+    zq_cal.write(EMC_ZQ_CAL_ZQ_LATCH_CMD);
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    const TIMINGS: &[Timing<'static>] = &[
+        Timing {
+            rate: 204_000,
+            clk_source_emc: 0x4000_0002,
+            burst_registers: &[(0x2C, 0x10)],
+            mc_registers: &[],
+        },
+        Timing {
+            rate: 1_600_000,
+            clk_source_emc: 0x0000_0000,
+            burst_registers: &[(0x2C, 0x40)],
+            mc_registers: &[(0x94, 0x08)],
+        },
+    ];
+
+    /// Tests the clock-change sequence to a PLLM-derived rate.
+    #[test]
+    fn switch() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        assert_eq!(
+            set_rate(800_000, TIMINGS).err(),
+            Some(Error::InvalidArgument)
+        );
+        assert_eq!(set_rate(1_600_000, TIMINGS).err(), Some(Error::Clock));
+
+        // Pretend PLLM is locked and only channel 0 acknowledges the change.
+        mock::set(0x6000_6090, u64::from(PLLM_LOCK));
+        mock::set(0x7001_B008, u64::from(EMC_DBG_WRITE_MUX_ACTIVE));
+        mock::set(0x7001_E584, 0x6);
+        mock::set(0x7001_E000, u64::from(EMC_INTSTATUS_CLKCHANGE_COMPLETE));
+
+        assert_eq!(set_rate(1_600_000, TIMINGS).err(), Some(Error::Timeout));
+
+        // Then channel 1 acknowledges it as well.
+        mock::set(0x7001_F000, u64::from(EMC_INTSTATUS_CLKCHANGE_COMPLETE));
+
+        set_rate(1_600_000, TIMINGS).unwrap();
+        assert_eq!(mock::get(0x7001_B02C), 0x40);
+        assert_eq!(mock::get(0x7001_9094), 0x08);
+        assert_eq!(mock::get(0x6000_619C), 0);
+        assert_eq!(mock::get(0x7001_B008), u64::from(EMC_DBG_WRITE_MUX_ACTIVE));
+        assert_eq!(mock::get(0x7001_90FC), 1);
+        assert_eq!(mock::get(0x7001_B2EC), u64::from(EMC_ZQ_CAL_ZQ_LATCH_CMD));
+    }
+
+    /// Tests that channel 0 is waited for if `EMC_FBIO_CFG7` enables none.
+    #[test]
+    fn switch_without_channel_bits() {
+        let _session = mock::start();
+        crate::timer::mock_counter();
+
+        mock::set(0x6000_6090, u64::from(PLLM_LOCK));
+        mock::set(0x7001_B008, u64::from(EMC_DBG_WRITE_MUX_ACTIVE));
+        mock::set(0x7001_E584, 0);
+
+        assert_eq!(set_rate(1_600_000, TIMINGS).err(), Some(Error::Timeout));
+
+        mock::set(0x7001_E000, u64::from(EMC_INTSTATUS_CLKCHANGE_COMPLETE));
+
+        set_rate(1_600_000, TIMINGS).unwrap();
+        assert_eq!(mock::get(0x7001_B02C), 0x40);
+    }
+}
@@ -0,0 +1,116 @@
+//! Security Engine AES block operations.
+//!
+//! # Description
+//!
+//! Key derivation mostly consists of single-block AES-ECB operations with
+//! keys that never leave the SE keyslots. The results either go back into
+//! a keyslot through [`SecurityEngine::decrypt_data_into_keyslot`] or into
+//! memory, where they are used as a seed for the next step.
+//!
+//! # Implementation
+//!
+//! - [`SecurityEngine::aes_ecb_encrypt_block`] and
+//! [`SecurityEngine::aes_ecb_decrypt_block`] transform a single block in
+//! place with a 128-bit key from a keyslot.
+//!
+//! - Both report SE errors as [`Error::Se`] instead of panicking.
+//!
+//! - Modes built on top of ECB, like XTS, process several blocks with a
+//! single SE operation through `aes_ecb`.
+//!
+//! [`SecurityEngine::decrypt_data_into_keyslot`]: struct.SecurityEngine.html#method.decrypt_data_into_keyslot
+//! [`SecurityEngine::aes_ecb_encrypt_block`]: struct.SecurityEngine.html#method.aes_ecb_encrypt_block
+//! [`SecurityEngine::aes_ecb_decrypt_block`]: struct.SecurityEngine.html#method.aes_ecb_decrypt_block
+//! [`Error::Se`]: ../enum.Error.html#variant.Se
+
+use mirage_mmio::VolatileStorage;
+
+use super::{Registers, SecurityEngine, KEYSLOT_AES_MAX, OP_START};
+use crate::{Error, Result};
+
+/// The size of an AES block in bytes.
+pub const AES_BLOCK_SIZE: usize = 0x10;
+
+/// Encrypts with AES into memory.
+pub(super) const SE_CONFIG_ENC_AES_DST_MEMORY: u32 = 1 << 12;
+/// Decrypts with AES into memory.
+pub(super) const SE_CONFIG_DEC_AES_DST_MEMORY: u32 = 1 << 8;
+
+/// Selects the encryption core, with the keyslot in bits 24 and up.
+pub(super) const SE_CRYPTO_CORE_ENCRYPT: u32 = 1 << 8;
+/// Selects the decryption core, with the keyslot in bits 24 and up.
+pub(super) const SE_CRYPTO_CORE_DECRYPT: u32 = 0;
+
+impl SecurityEngine {
+    /// Runs an AES-ECB operation on whole blocks from `source` into
+    /// `destination`, which must have the same length.
+    pub(super) fn aes_ecb(
+        &mut self,
+        config: u32,
+        crypto: u32,
+        keyslot: usize,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
+        let blocks = source.len() / AES_BLOCK_SIZE;
+
+        if keyslot >= KEYSLOT_AES_MAX
+            || blocks == 0
+            || source.len() % AES_BLOCK_SIZE != 0
+            || destination.len() != source.len()
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let register_base = unsafe { Registers::get() };
+
+        register_base.CONFIG_REG.write(config);
+        register_base
+            .CRYPTO_REG
+            .write(((keyslot as u32) << 24) | crypto);
+        register_base.BLOCK_COUNT_REG.write(blocks as u32 - 1);
+
+        self.run_checked_operation(OP_START, destination, source)
+    }
+
+    /// Runs a single-block AES-ECB operation in place.
+    fn aes_ecb_block(
+        &mut self,
+        config: u32,
+        crypto: u32,
+        keyslot: usize,
+        block: &mut [u8; AES_BLOCK_SIZE],
+    ) -> Result<()> {
+        let mut source = *block;
+
+        self.aes_ecb(config, crypto, keyslot, block, &mut source)
+    }
+
+    /// Encrypts a block in place with the key in an AES keyslot.
+    pub fn aes_ecb_encrypt_block(
+        &mut self,
+        keyslot: usize,
+        block: &mut [u8; AES_BLOCK_SIZE],
+    ) -> Result<()> {
+        self.aes_ecb_block(
+            SE_CONFIG_ENC_AES_DST_MEMORY,
+            SE_CRYPTO_CORE_ENCRYPT,
+            keyslot,
+            block,
+        )
+    }
+
+    /// Decrypts a block in place with the key in an AES keyslot.
+    pub fn aes_ecb_decrypt_block(
+        &mut self,
+        keyslot: usize,
+        block: &mut [u8; AES_BLOCK_SIZE],
+    ) -> Result<()> {
+        self.aes_ecb_block(
+            SE_CONFIG_DEC_AES_DST_MEMORY,
+            SE_CRYPTO_CORE_DECRYPT,
+            keyslot,
+            block,
+        )
+    }
+}
@@ -0,0 +1,236 @@
+//! Security Engine context save and state reset.
+//!
+//! # Description
+//!
+//! Before entering LP0, the SE state is encrypted into memory with a
+//! random Secure Rekeying Key (SRK), which the hardware stashes in the
+//! PMC secure scratch registers. The warmboot path of the bootrom uses
+//! it to restore the keyslots on resume. Original firmware expects the
+//! context to be saved in exactly the bootrom format, so bootloaders which
+//! hand over to it have to perform the same sequence.
+//!
+//! # Implementation
+//!
+//! - [`SecurityEngine::save_context`] generates a fresh SRK and encrypts
+//! the sticky bits, the AES and RSA keytables and a known pattern into a
+//! buffer of [`CONTEXT_SIZE`] bytes, in the order the bootrom expects.
+//!
+//! - [`SecurityEngine::reset_state`] wipes all keyslots and IVs and
+//! returns the SE configuration registers to their reset values, which
+//! should be done before handing over to firmware that expects a clean SE.
+//!
+//! [`SecurityEngine::save_context`]: struct.SecurityEngine.html#method.save_context
+//! [`CONTEXT_SIZE`]: constant.CONTEXT_SIZE.html
+//! [`SecurityEngine::reset_state`]: struct.SecurityEngine.html#method.reset_state
+
+use mirage_mmio::VolatileStorage;
+
+use super::{
+    clear_all_keyslots,
+    rng::{
+        SE_CONFIG_ALG_RNG_DST_KEYTABLE, SE_CONFIG_ALG_RNG_DST_MEMORY, SE_CONFIG_ALG_RNG_DST_SRK,
+        SE_RNG_CONFIG_MODE_FORCE_RESEED, SE_RNG_CONFIG_SRC_ENTROPY,
+    },
+    Registers, SecurityEngine, AES_BLOCK_SIZE, KEYSLOT_AES_MAX, KEYSLOT_RSA_MAX, OP_CTX_SAVE,
+    OP_START,
+};
+use crate::{Error, Result};
+
+/// The size of a saved SE context in bytes.
+pub const CONTEXT_SIZE: usize = 0x840;
+
+/// Encrypts with AES into memory.
+const SE_CONFIG_ALG_AES_ENC_DST_MEMORY: u32 = 1 << 12;
+/// Saves the sticky bits.
+const CTX_SAVE_SRC_STICKY_BITS: u32 = 0 << 29;
+/// Saves a part of the RSA keytable.
+const CTX_SAVE_SRC_RSA_KEYTABLE: u32 = 1 << 29;
+/// Saves a part of the AES keytable.
+const CTX_SAVE_SRC_AES_KEYTABLE: u32 = 2 << 29;
+/// Encrypts data from memory.
+const CTX_SAVE_SRC_MEM: u32 = 4 << 29;
+/// Saves the SRK into the PMC.
+const CTX_SAVE_SRC_SRK: u32 = 6 << 29;
+
+/// The low half of a 256-bit AES key.
+const CTX_SAVE_KEY_LOW_BITS: u32 = 0;
+/// The high half of a 256-bit AES key.
+const CTX_SAVE_KEY_HIGH_BITS: u32 = 1;
+/// The original IV of an AES keyslot.
+const CTX_SAVE_KEY_ORIGINAL_IV: u32 = 2;
+/// The updated IV of an AES keyslot.
+const CTX_SAVE_KEY_UPDATED_IV: u32 = 3;
+
+/// The pattern the bootrom checks to validate a restored context.
+const CONTEXT_KNOWN_PATTERN: [u8; AES_BLOCK_SIZE] = [
+    0x7C, 0xED, 0x2D, 0xD2, 0xBA, 0x5C, 0x8F, 0x14, 0x15, 0xE3, 0x26, 0x60, 0x51, 0x60, 0xB6, 0x3B,
+];
+
+impl SecurityEngine {
+    /// Runs an operation and reports SE errors.
+    pub(super) fn run_checked_operation(
+        &mut self,
+        op: u32,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
+        self.run_blocking_operation(op, destination, source)?;
+
+        if self.has_error() {
+            Err(Error::Se)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fills an AES keyslot with a random key.
+    fn generate_random_key(&mut self, keyslot: usize, rng_keyslot: usize) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        self.configure_rng(
+            SE_CONFIG_ALG_RNG_DST_KEYTABLE,
+            rng_keyslot,
+            SE_RNG_CONFIG_SRC_ENTROPY,
+        );
+
+        // Generate both halves of the key.
+        for half in 0..2 {
+            register_base
+                .CRYPTO_KEYTABLE_DST_REG
+                .write(((keyslot as u32) << 8) | half);
+            self.run_checked_operation(OP_START, &mut [], &mut [])?;
+        }
+
+        Ok(())
+    }
+
+    /// Generates a new SRK from the given keyslot.
+    fn generate_srk(&mut self, keyslot: usize) -> Result<()> {
+        self.configure_rng(
+            SE_CONFIG_ALG_RNG_DST_SRK,
+            keyslot,
+            SE_RNG_CONFIG_SRC_ENTROPY | SE_RNG_CONFIG_MODE_FORCE_RESEED,
+        );
+
+        self.run_checked_operation(OP_START, &mut [], &mut [])
+    }
+
+    /// Encrypts a block with the SRK into the context, using the source
+    /// selected in `CONTEXT_SAVE_CONFIG_REG`.
+    fn save_context_block(
+        &mut self,
+        config: u32,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        register_base.CONTEXT_SAVE_CONFIG_REG.write(config);
+        register_base.BLOCK_COUNT_REG.write(0);
+
+        self.run_checked_operation(OP_CTX_SAVE, destination, source)
+    }
+
+    /// Saves the SE context into the given buffer, encrypted with a new SRK.
+    ///
+    /// `srk_keyslot` is overwritten with a random key from which the SRK is
+    /// derived, `rng_keyslot` is used by the RNG. The buffer must be
+    /// accessible by the SE, i.e. reside in the lower 4GiB.
+    pub fn save_context(
+        &mut self,
+        srk_keyslot: usize,
+        rng_keyslot: usize,
+        context: &mut [u8; CONTEXT_SIZE],
+    ) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        if srk_keyslot >= KEYSLOT_AES_MAX || rng_keyslot >= KEYSLOT_AES_MAX {
+            return Err(Error::InvalidArgument);
+        }
+
+        // Generate the SRK.
+        self.generate_random_key(srk_keyslot, rng_keyslot)?;
+        self.generate_srk(srk_keyslot)?;
+
+        // The context starts with a random block.
+        let mut block = [0; AES_BLOCK_SIZE];
+        self.configure_rng(
+            SE_CONFIG_ALG_RNG_DST_MEMORY,
+            rng_keyslot,
+            SE_RNG_CONFIG_SRC_ENTROPY,
+        );
+        self.run_checked_operation(OP_START, &mut block, &mut [])?;
+
+        let mut blocks = context.chunks_exact_mut(AES_BLOCK_SIZE);
+        let mut next = || blocks.next().unwrap();
+
+        register_base
+            .CONFIG_REG
+            .write(SE_CONFIG_ALG_AES_ENC_DST_MEMORY);
+        self.save_context_block(CTX_SAVE_SRC_MEM, next(), &mut block)?;
+
+        // Save the sticky bits.
+        for i in 0..2 {
+            self.save_context_block(CTX_SAVE_SRC_STICKY_BITS | (i << 24), next(), &mut [])?;
+        }
+
+        // Save the AES keytable.
+        for keyslot in 0..KEYSLOT_AES_MAX as u32 {
+            for &part in [CTX_SAVE_KEY_LOW_BITS, CTX_SAVE_KEY_HIGH_BITS].iter() {
+                let config = CTX_SAVE_SRC_AES_KEYTABLE | (keyslot << 8) | part;
+                self.save_context_block(config, next(), &mut [])?;
+            }
+        }
+
+        // Save the original and updated AES IVs.
+        for &part in [CTX_SAVE_KEY_ORIGINAL_IV, CTX_SAVE_KEY_UPDATED_IV].iter() {
+            for keyslot in 0..KEYSLOT_AES_MAX as u32 {
+                let config = CTX_SAVE_SRC_AES_KEYTABLE | (keyslot << 8) | part;
+                self.save_context_block(config, next(), &mut [])?;
+            }
+        }
+
+        // Save the RSA keytable, the exponent of each slot before its modulus.
+        for keyslot in 0..KEYSLOT_RSA_MAX as u32 {
+            for part in 0..2 {
+                for block in 0..0x10 {
+                    let index = 2 * keyslot + (1 - part);
+                    let config = CTX_SAVE_SRC_RSA_KEYTABLE | (index << 16) | (block << 12);
+                    self.save_context_block(config, next(), &mut [])?;
+                }
+            }
+        }
+
+        // Save the known pattern.
+        let mut pattern = CONTEXT_KNOWN_PATTERN;
+        self.save_context_block(CTX_SAVE_SRC_MEM, next(), &mut pattern)?;
+
+        // Store the SRK in the PMC secure scratch registers.
+        self.save_context_block(CTX_SAVE_SRC_SRK, &mut [], &mut [])?;
+        register_base.CONFIG_REG.write(0);
+        self.run_checked_operation(OP_CTX_SAVE, &mut [], &mut [])
+    }
+
+    /// Clears all keyslots and IVs and resets the SE configuration.
+    ///
+    /// Keyslots whose write access has been revoked keep their contents.
+    pub fn reset_state(&mut self) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        clear_all_keyslots()?;
+
+        register_base.CONFIG_REG.write(0);
+        register_base.CRYPTO_REG.write(0);
+        register_base.CONTEXT_SAVE_CONFIG_REG.write(0);
+        register_base.BLOCK_COUNT_REG.write(0);
+        register_base.RNG_CONFIG_REG.write(0);
+        register_base
+            .ERR_STATUS_REG
+            .write(register_base.ERR_STATUS_REG.read());
+        register_base
+            .INT_STATUS_REG
+            .write(register_base.INT_STATUS_REG.read());
+
+        Ok(())
+    }
+}
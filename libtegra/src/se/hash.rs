@@ -0,0 +1,215 @@
+//! Security Engine SHA-256 hashing.
+//!
+//! # Description
+//!
+//! The SE can compute SHA-256 hashes of data in memory, which it fetches
+//! through DMA. Messages may be split across multiple operations, as long
+//! as the total length of the message is known upfront and all chunks but
+//! the last one are a multiple of the SHA-256 block size.
+//!
+//! Since the SE runs independently from the CPU, hashing a chunk can be
+//! overlapped with producing the next one, e.g. reading it from storage.
+//!
+//! # Implementation
+//!
+//! - [`SecurityEngine::calculate_sha256`] hashes a buffer in a single,
+//! blocking operation.
+//!
+//! - [`SecurityEngine::calculate_sha256_pipelined`] double-buffers the
+//! input. While the SE hashes one buffer, a `fill` callback produces the
+//! next chunk into the other one. Every chunk is handed to a `done`
+//! callback while the SE is working on it, which can be used to write
+//! the data out without waiting for the hash.
+//!
+//! - The SE must not be used for anything else while a pipelined hash is
+//! in progress, as the intermediate hash state lives in the SE.
+//!
+//! [`SecurityEngine::calculate_sha256`]: struct.SecurityEngine.html#method.calculate_sha256
+//! [`SecurityEngine::calculate_sha256_pipelined`]: struct.SecurityEngine.html#method.calculate_sha256_pipelined
+
+use mirage_mmio::{ReadOnly, VolatileStorage};
+
+use super::{Ll, Registers, SecurityEngine, OPERATION_TIMEOUT, OP_START};
+use crate::{timer::wait_for, Error, Result};
+
+/// The size of a SHA-256 block in bytes.
+pub const SHA256_BLOCK_SIZE: usize = 0x40;
+/// The size of a SHA-256 hash in bytes.
+pub const SHA256_HASH_SIZE: usize = 0x20;
+
+/// Selects SHA-256 as the encryption mode.
+const SE_CONFIG_ENC_MODE_SHA256: u32 = 5 << 24;
+/// Selects SHA as the encryption algorithm.
+const SE_CONFIG_ENC_ALG_SHA: u32 = 3 << 12;
+/// Writes the result into the hash result registers.
+const SE_CONFIG_DST_HASHREG: u32 = 2 << 2;
+
+/// Starts a new hash from the initial hash values.
+const SHA_INIT_HASH: u32 = 1;
+/// Continues the hash from the current intermediate state.
+const SHA_CONTINUE: u32 = 0;
+
+impl SecurityEngine {
+    /// Starts hashing a chunk of a message without waiting for completion.
+    ///
+    /// The LL must stay alive until the operation is finished.
+    fn start_sha256_operation(&mut self, in_ll: &Ll, total_size: u64, left_size: u64, first: bool) {
+        let register_base = unsafe { Registers::get() };
+
+        register_base.CONFIG_REG.write(
+            SE_CONFIG_ENC_MODE_SHA256 | SE_CONFIG_ENC_ALG_SHA | SE_CONFIG_DST_HASHREG,
+        );
+        register_base
+            .SHA_CONFIG_REG
+            .write(if first { SHA_INIT_HASH } else { SHA_CONTINUE });
+
+        // Both lengths are given in bits.
+        let total_bits = total_size << 3;
+        let left_bits = left_size << 3;
+
+        register_base.SHA_MSG_LENGTH_REG.write(total_bits as u32);
+        register_base._0x208.write((total_bits >> 32) as u32);
+        register_base._0x20C.write(0);
+        register_base._0x210.write(0);
+        register_base.SHA_MSG_LEFT_REG.write(left_bits as u32);
+        register_base._0x218.write((left_bits >> 32) as u32);
+        register_base._0x21C.write(0);
+        register_base._0x220.write(0);
+
+        register_base
+            .IN_LL_ADDR_REG
+            .write(in_ll as *const _ as usize as u32);
+        register_base.OUT_LL_ADDR_REG.write(0);
+
+        register_base
+            .ERR_STATUS_REG
+            .write(register_base.ERR_STATUS_REG.read());
+        register_base
+            .INT_STATUS_REG
+            .write(register_base.INT_STATUS_REG.read());
+        register_base.OPERATION_REG.write(OP_START);
+    }
+
+    /// Waits for the current operation to finish and checks for errors.
+    fn wait_for_operation(&self) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        wait_for(
+            || register_base.INT_STATUS_REG.read() & 0x10 != 0,
+            OPERATION_TIMEOUT,
+        )?;
+
+        if self.has_error() {
+            Err(Error::Se)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the hash from the hash result registers.
+    fn read_sha256_result(&self) -> [u8; SHA256_HASH_SIZE] {
+        let register_base = unsafe { Registers::get() };
+
+        let result = unsafe {
+            &*(register_base.HASH_RESULT_REG.as_ptr()
+                as *const [ReadOnly<u32>; SHA256_HASH_SIZE / 4])
+        };
+
+        let mut hash = [0; SHA256_HASH_SIZE];
+        for (chunk, word) in hash.chunks_exact_mut(4).zip(result.iter()) {
+            chunk.copy_from_slice(&word.read().to_be_bytes());
+        }
+
+        hash
+    }
+
+    /// Creates a LL describing the given data.
+    fn make_input_ll(data: &[u8]) -> Ll {
+        Ll {
+            entries: 0,
+            address: data.as_ptr() as usize as u32,
+            size: data.len() as u32,
+        }
+    }
+
+    /// Calculates the SHA-256 hash of the given data.
+    pub fn calculate_sha256(&mut self, data: &[u8]) -> Result<[u8; SHA256_HASH_SIZE]> {
+        let in_ll = Self::make_input_ll(data);
+
+        self.start_sha256_operation(&in_ll, data.len() as u64, data.len() as u64, true);
+        self.wait_for_operation()?;
+
+        Ok(self.read_sha256_result())
+    }
+
+    /// Calculates the SHA-256 hash of a message of `total_size` bytes,
+    /// which is produced chunk by chunk into two alternating buffers.
+    ///
+    /// `fill` is called with the offset of the next chunk within the message
+    /// and a buffer to read it into, while the SE is still hashing the
+    /// previous chunk. Once a chunk is being hashed, it is passed to `done`.
+    ///
+    /// Both buffers must be of the same size, which has to be a non-zero
+    /// multiple of the SHA-256 block size.
+    pub fn calculate_sha256_pipelined<F, D>(
+        &mut self,
+        total_size: u64,
+        buffers: [&mut [u8]; 2],
+        mut fill: F,
+        mut done: D,
+    ) -> Result<[u8; SHA256_HASH_SIZE]>
+    where
+        F: FnMut(u64, &mut [u8]) -> Result<()>,
+        D: FnMut(&[u8]) -> Result<()>,
+    {
+        let chunk_size = buffers[0].len();
+
+        if chunk_size == 0 || chunk_size % SHA256_BLOCK_SIZE != 0 || buffers[1].len() != chunk_size {
+            return Err(Error::InvalidArgument);
+        }
+
+        if total_size == 0 {
+            return self.calculate_sha256(&[]);
+        }
+
+        // The LL of the chunk that is currently being hashed.
+        let mut in_ll;
+
+        let mut offset = 0;
+        let mut current = 0;
+
+        while offset < total_size {
+            let length = (chunk_size as u64).min(total_size - offset) as usize;
+            let buffer = &mut buffers[current][..length];
+
+            // Produce the next chunk while the SE works on the previous one.
+            let filled = fill(offset, buffer);
+
+            if offset != 0 {
+                self.wait_for_operation()?;
+            }
+            filled?;
+
+            in_ll = Self::make_input_ll(buffer);
+            self.start_sha256_operation(
+                &in_ll,
+                total_size,
+                total_size - offset,
+                offset == 0,
+            );
+
+            // Hand out the chunk while it is being hashed.
+            if let Err(error) = done(buffer) {
+                self.wait_for_operation().ok();
+                return Err(error);
+            }
+
+            offset += length as u64;
+            current ^= 1;
+        }
+
+        self.wait_for_operation()?;
+
+        Ok(self.read_sha256_result())
+    }
+}
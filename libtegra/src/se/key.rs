@@ -0,0 +1,90 @@
+//! Marshalling of keys into the word layout of the SE keytables.
+//!
+//! # Description
+//!
+//! The SE keytables are programmed one 32-bit word at a time. AES keys
+//! and IVs are split into little-endian words in order, which is also how
+//! the SBK is stored in the fuses. RSA keys are big-endian numbers, so
+//! their words are stored starting from the least significant end.
+//!
+//! # Implementation
+//!
+//! - [`aes_key_words`] and [`aes_key_bytes`] convert a 128-bit key between
+//! bytes and keytable words, e.g. to load the SBK read from the fuses.
+//!
+//! - [`aes_word`] and [`rsa_word`] extract single keytable words from keys
+//! of arbitrary size.
+//!
+//! [`aes_key_words`]: fn.aes_key_words.html
+//! [`aes_key_bytes`]: fn.aes_key_bytes.html
+//! [`aes_word`]: fn.aes_word.html
+//! [`rsa_word`]: fn.rsa_word.html
+
+use core::convert::TryInto;
+
+/// The size of a 128-bit AES key in bytes.
+pub const AES_128_KEY_SIZE: usize = 0x10;
+
+/// Splits a 128-bit AES key into the words of the keytable.
+pub fn aes_key_words(key: &[u8; AES_128_KEY_SIZE]) -> [u32; 4] {
+    let mut words = [0; 4];
+
+    for (index, word) in words.iter_mut().enumerate() {
+        *word = aes_word(key, index);
+    }
+
+    words
+}
+
+/// Assembles a 128-bit AES key from the words of the keytable.
+pub fn aes_key_bytes(words: &[u32; 4]) -> [u8; AES_128_KEY_SIZE] {
+    let mut key = [0; AES_128_KEY_SIZE];
+
+    for (bytes, word) in key.chunks_exact_mut(4).zip(words.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+
+    key
+}
+
+/// Gets the keytable word at `index` of an AES key or IV.
+///
+/// Panics if the key is too short to contain the word.
+pub fn aes_word(key: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes(key[4 * index..4 * index + 4].try_into().unwrap())
+}
+
+/// Gets the keytable word at `index` of an RSA modulus or exponent, where
+/// word 0 holds the least significant bytes.
+///
+/// Panics if the key is too short to contain the word.
+pub fn rsa_word(key: &[u8], index: usize) -> u32 {
+    let end = key.len() - 4 * index;
+
+    u32::from_be_bytes(key[end - 4..end].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aes_round_trip() {
+        let mut key = [0; AES_128_KEY_SIZE];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let words = aes_key_words(&key);
+        assert_eq!(words, [0x0302_0100, 0x0706_0504, 0x0B0A_0908, 0x0F0E_0D0C]);
+        assert_eq!(aes_key_bytes(&words), key);
+    }
+
+    #[test]
+    fn rsa_words() {
+        let key = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+
+        assert_eq!(rsa_word(&key, 0), 0x0506_0708);
+        assert_eq!(rsa_word(&key, 1), 0x0102_0304);
+    }
+}
@@ -0,0 +1,302 @@
+//! Security Engine keyslot management.
+//!
+//! # Description
+//!
+//! The SE holds 16 AES and 2 RSA keyslots. Access to each of them is
+//! controlled by a per-slot keytable access register, in which every
+//! permission can be revoked individually. Revoked permissions are
+//! sticky and can only be restored by resetting the SE.
+//!
+//! Additionally, the per-key security registers allow for locking the
+//! access configuration of a slot altogether, so that its permissions
+//! can no longer be changed by non-secure software.
+//!
+//! # Implementation
+//!
+//! - [`KeySlot`] identifies a single AES or RSA keyslot and provides
+//! methods to inspect and revoke its [`KeySlotAccess`] permissions.
+//!
+//! - RSA keyslots only support the `KEY_READ`, `KEY_UPDATE` and `KEY_USE`
+//! permissions. Other permissions are ignored for them.
+//!
+//! - [`clear_all_keyslots`] wipes the keys of all slots, e.g. before
+//! handing over to an untrusted payload.
+//!
+//! - [`dump_keyslots`] writes the lock status of all keyslots to a
+//! [`fmt::Write`] implementor.
+//!
+//! [`KeySlot`]: struct.KeySlot.html
+//! [`KeySlotAccess`]: struct.KeySlotAccess.html
+//! [`clear_all_keyslots`]: fn.clear_all_keyslots.html
+//! [`dump_keyslots`]: fn.dump_keyslots.html
+//! [`fmt::Write`]: https://doc.rust-lang.org/core/fmt/trait.Write.html
+
+use core::fmt;
+
+use mirage_mmio::VolatileStorage;
+
+use super::{Registers, SecurityEngine, KEYSLOT_AES_MAX, KEYSLOT_RSA_MAX};
+use crate::Result;
+
+bitflags! {
+    /// Representation of the permissions of a keyslot.
+    pub struct KeySlotAccess: u32 {
+        /// The key can be read back.
+        const KEY_READ = 1 << 0;
+        /// The key can be overwritten.
+        const KEY_UPDATE = 1 << 1;
+        /// The original IV can be read back.
+        const OIV_READ = 1 << 2;
+        /// The original IV can be overwritten.
+        const OIV_UPDATE = 1 << 3;
+        /// The updated IV can be read back.
+        const UIV_READ = 1 << 4;
+        /// The updated IV can be overwritten.
+        const UIV_UPDATE = 1 << 5;
+        /// The key can be used for crypto operations.
+        const KEY_USE = 1 << 6;
+    }
+}
+
+/// The RSA keytable access bit for `KEY_READ`.
+const RSA_KEY_READ: u32 = 1 << 0;
+/// The RSA keytable access bit for `KEY_UPDATE`.
+const RSA_KEY_UPDATE: u32 = 1 << 1;
+/// The RSA keytable access bit for `KEY_USE`.
+const RSA_KEY_USE: u32 = 1 << 2;
+
+/// The possible kinds of keyslots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySlotKind {
+    /// An AES keyslot.
+    Aes,
+    /// An RSA keyslot.
+    Rsa,
+}
+
+/// Representation of a SE keyslot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeySlot {
+    /// The kind of the keyslot.
+    kind: KeySlotKind,
+    /// The index of the keyslot.
+    index: usize,
+}
+
+// Definitions of known keyslots.
+impl KeySlot {
+    /// The keyslot holding the Secure Boot Key.
+    pub const SBK: Self = KeySlot {
+        kind: KeySlotKind::Aes,
+        index: 0xE,
+    };
+
+    /// The keyslot holding the Secure Storage Key.
+    pub const SSK: Self = KeySlot {
+        kind: KeySlotKind::Aes,
+        index: 0xF,
+    };
+}
+
+impl KeySlot {
+    /// The total number of keyslots.
+    pub const COUNT: usize = KEYSLOT_AES_MAX + KEYSLOT_RSA_MAX;
+
+    /// Gets the AES keyslot with the given index, if it exists.
+    pub fn aes(index: usize) -> Option<Self> {
+        if index < KEYSLOT_AES_MAX {
+            Some(KeySlot {
+                kind: KeySlotKind::Aes,
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Gets the RSA keyslot with the given index, if it exists.
+    pub fn rsa(index: usize) -> Option<Self> {
+        if index < KEYSLOT_RSA_MAX {
+            Some(KeySlot {
+                kind: KeySlotKind::Rsa,
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over all keyslots, AES slots first.
+    pub fn all() -> impl Iterator<Item = KeySlot> {
+        (0..KEYSLOT_AES_MAX)
+            .filter_map(KeySlot::aes)
+            .chain((0..KEYSLOT_RSA_MAX).filter_map(KeySlot::rsa))
+    }
+
+    /// Gets the kind of the keyslot.
+    pub fn kind(&self) -> KeySlotKind {
+        self.kind
+    }
+
+    /// Gets the index of the keyslot.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Reads the current permissions of the keyslot.
+    pub fn access(&self) -> KeySlotAccess {
+        let register_base = unsafe { Registers::get() };
+
+        match self.kind {
+            KeySlotKind::Aes => KeySlotAccess::from_bits_truncate(
+                register_base.AES_KEYSLOT_FLAGS[self.index].read(),
+            ),
+            KeySlotKind::Rsa => {
+                let value = register_base.RSA_KEYSLOT_FLAGS[self.index].read();
+                let mut access = KeySlotAccess::empty();
+
+                access.set(KeySlotAccess::KEY_READ, value & RSA_KEY_READ != 0);
+                access.set(KeySlotAccess::KEY_UPDATE, value & RSA_KEY_UPDATE != 0);
+                access.set(KeySlotAccess::KEY_USE, value & RSA_KEY_USE != 0);
+
+                access
+            }
+        }
+    }
+
+    /// Revokes the given permissions from the keyslot.
+    ///
+    /// Permissions that have already been revoked stay revoked.
+    pub fn lock(&self, permissions: KeySlotAccess) {
+        let register_base = unsafe { Registers::get() };
+
+        match self.kind {
+            KeySlotKind::Aes => {
+                let register = &register_base.AES_KEYSLOT_FLAGS[self.index];
+
+                register.write(register.read() & !permissions.bits());
+            }
+            KeySlotKind::Rsa => {
+                let register = &register_base.RSA_KEYSLOT_FLAGS[self.index];
+
+                let mut mask = 0;
+                if permissions.contains(KeySlotAccess::KEY_READ) {
+                    mask |= RSA_KEY_READ;
+                }
+                if permissions.contains(KeySlotAccess::KEY_UPDATE) {
+                    mask |= RSA_KEY_UPDATE;
+                }
+                if permissions.contains(KeySlotAccess::KEY_USE) {
+                    mask |= RSA_KEY_USE;
+                }
+
+                register.write(register.read() & !mask);
+            }
+        }
+    }
+
+    /// Prevents the key from being read back.
+    pub fn lock_read(&self) {
+        self.lock(KeySlotAccess::KEY_READ);
+    }
+
+    /// Prevents the key from being overwritten.
+    pub fn lock_write(&self) {
+        self.lock(KeySlotAccess::KEY_UPDATE);
+    }
+
+    /// Prevents the key from being used for crypto operations.
+    pub fn lock_keyuse(&self) {
+        self.lock(KeySlotAccess::KEY_USE);
+    }
+
+    /// Locks the access configuration of the keyslot, so that its
+    /// permissions can no longer be changed.
+    pub fn lock_access_config(&self) {
+        let register_base = unsafe { Registers::get() };
+
+        let register = match self.kind {
+            KeySlotKind::Aes => &register_base.AES_KEY_READ_DISABLE_REG,
+            KeySlotKind::Rsa => &register_base.RSA_KEY_READ_DISABLE_REG,
+        };
+
+        register.write(register.read() & !(1 << self.index as u32));
+    }
+
+    /// Whether the access configuration of the keyslot is locked.
+    pub fn is_access_config_locked(&self) -> bool {
+        let register_base = unsafe { Registers::get() };
+
+        let register = match self.kind {
+            KeySlotKind::Aes => &register_base.AES_KEY_READ_DISABLE_REG,
+            KeySlotKind::Rsa => &register_base.RSA_KEY_READ_DISABLE_REG,
+        };
+
+        register.read() & (1 << self.index as u32) == 0
+    }
+}
+
+impl fmt::Display for KeySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            KeySlotKind::Aes => write!(f, "AES {:02X}", self.index),
+            KeySlotKind::Rsa => write!(f, "RSA {:02X}", self.index),
+        }
+    }
+}
+
+/// Clears the keys and IVs of all AES and RSA keyslots.
+///
+/// Keyslots whose `KEY_UPDATE` permission was revoked keep their contents
+/// until the SE is reset.
+pub fn clear_all_keyslots() -> Result<()> {
+    let mut se = SecurityEngine::new();
+
+    for keyslot in 0..KEYSLOT_AES_MAX {
+        se.clear_aes_keyslot(keyslot)?;
+    }
+    for keyslot in 0..KEYSLOT_RSA_MAX {
+        se.clear_rsa_keyslot(keyslot)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the lock status of all keyslots to the given writer.
+///
+/// Every line lists the remaining permissions of a keyslot, using `-`
+/// for revoked ones, followed by whether its configuration is locked.
+pub fn dump_keyslots<W: fmt::Write>(writer: &mut W) -> fmt::Result {
+    let permissions = [
+        (KeySlotAccess::KEY_READ, 'R'),
+        (KeySlotAccess::KEY_UPDATE, 'W'),
+        (KeySlotAccess::OIV_READ, 'r'),
+        (KeySlotAccess::OIV_UPDATE, 'w'),
+        (KeySlotAccess::UIV_READ, 'r'),
+        (KeySlotAccess::UIV_UPDATE, 'w'),
+        (KeySlotAccess::KEY_USE, 'U'),
+    ];
+
+    for keyslot in KeySlot::all() {
+        let access = keyslot.access();
+
+        write!(writer, "{} ", keyslot)?;
+        for (permission, symbol) in permissions.iter() {
+            let symbol = if access.contains(*permission) {
+                *symbol
+            } else {
+                '-'
+            };
+
+            write!(writer, "{}", symbol)?;
+        }
+
+        if keyslot.is_access_config_locked() {
+            writeln!(writer, " locked")?;
+        } else {
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,33 @@
 //! Tegra210 Security Engine driver.
 
-use core::convert::{TryFrom, TryInto};
-
-use mirage_mmio::{Mmio, VolatileStorage};
+use core::convert::TryFrom;
+
+use mirage_mmio::{Mmio, ReadOnly, VolatileStorage};
+
+use crate::{
+    fuse,
+    hw_rev::{self, SocRevision},
+    keys::wipe,
+    retry::RetryPolicy,
+    timer::wait_for,
+    Error, Result,
+};
+
+pub use aes::*;
+pub use context::*;
+pub use hash::*;
+pub use key::*;
+pub use keyslot::*;
+pub use rng::*;
+pub use xts::*;
+
+mod aes;
+mod context;
+mod hash;
+mod key;
+mod keyslot;
+mod rng;
+mod xts;
 
 /// Base address for SE registers.
 pub(crate) const SE_BASE: u32 = 0x7001_2000;
@@ -13,6 +38,9 @@ const KEYSLOT_RSA_MAX: usize = 0x2;
 const KEYSIZE_AES_MAX: usize = 0x20;
 const KEYSIZE_RSA_MAX: usize = 0x100;
 
+/// The time in microseconds an SE operation may take to complete.
+pub(crate) const OPERATION_TIMEOUT: u32 = 1_000_000;
+
 pub const OP_ABORT: u32 = 0;
 pub const OP_START: u32 = 1;
 pub const OP_RESTART: u32 = 2;
@@ -35,7 +63,7 @@ struct Registers {
     pub OUT_LL_ADDR_REG: Mmio<u32>,
     _0x28: Mmio<u32>,
     _0x2C: Mmio<u32>,
-    pub HASH_RESULT_REG: [Mmio<u8>; 0x20],
+    pub HASH_RESULT_REG: [ReadOnly<u8>; 0x20],
     _0x50: [Mmio<u8>; 0x20],
     pub CONTEXT_SAVE_CONFIG_REG: Mmio<u32>,
     _0x74: [Mmio<u8>; 0x18C],
@@ -77,9 +105,9 @@ struct Registers {
     _0x41C: Mmio<u32>,
     pub RSA_KEYTABLE_ADDR: Mmio<u32>,
     pub RSA_KEYTABLE_DATA: Mmio<u32>,
-    pub RSA_OUTPUT: [Mmio<u8>; 0x100],
+    pub RSA_OUTPUT: [ReadOnly<u8>; 0x100],
     _0x528: [Mmio<u8>; 0x2D8],
-    pub FLAGS_REG: Mmio<u32>,
+    pub FLAGS_REG: ReadOnly<u32>,
     pub ERR_STATUS_REG: Mmio<u32>,
     _0x808: Mmio<u32>,
     pub SPARE_0: Mmio<u32>,
@@ -97,6 +125,9 @@ impl VolatileStorage for Registers {
 }
 
 /// Representation of the Security Engine.
+///
+/// All operations reprogram the shared configuration registers and take
+/// `&mut self`, only error checks are possible through a shared reference.
 pub struct SecurityEngine {
     /// A buffer to keep track of the modulus sizes for RSA keyslots.
     modulus_sizes: [usize; KEYSLOT_RSA_MAX],
@@ -126,10 +157,24 @@ impl Ll {
     }
 }
 
-// TODO(Vale): How to design the panic handler in favor of thrown panics?
-
 impl SecurityEngine {
-    fn trigger_blocking_operation(&self, op: u32, destination: &mut [u8], source: &mut [u8]) {
+    fn trigger_blocking_operation(
+        &mut self,
+        op: u32,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
+        self.run_blocking_operation(op, destination, source)?;
+
+        self.check_for_error()
+    }
+
+    fn run_blocking_operation(
+        &mut self,
+        op: u32,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         // Create and set the LLs.
@@ -144,23 +189,29 @@ impl SecurityEngine {
             .write(&mut out_ll as *mut _ as usize as u32);
 
         // Set registers for operation.
+        self.clear_status();
+        register_base.OPERATION_REG.write(op);
+
+        wait_for(
+            || register_base.INT_STATUS_REG.read() & 0x10 != 0,
+            OPERATION_TIMEOUT,
+        )
+    }
+
+    /// Acknowledges all pending errors and interrupts.
+    fn clear_status(&self) {
+        let register_base = unsafe { Registers::get() };
+
         register_base
             .ERR_STATUS_REG
             .write(register_base.ERR_STATUS_REG.read());
         register_base
             .INT_STATUS_REG
             .write(register_base.INT_STATUS_REG.read());
-        register_base.OPERATION_REG.write(op);
-
-        while register_base.INT_STATUS_REG.read() & 0x10 == 0 {
-            // Wait.
-        }
-
-        self.check_for_error();
     }
 
     /// Creates a new Security Engine object.
-    pub const fn new() -> Self {
+    pub(crate) const fn new() -> Self {
         SecurityEngine {
             modulus_sizes: [0; KEYSLOT_RSA_MAX],
             exponent_sizes: [0; KEYSLOT_RSA_MAX],
@@ -169,68 +220,116 @@ impl SecurityEngine {
 
     /// Locks the SBK from being read.
     #[inline]
-    pub(crate) fn lock_sbk(&self) {
-        let register_base = unsafe { Registers::get() };
-
-        register_base.AES_KEYSLOT_FLAGS[0xE].write(0x7E);
+    pub(crate) fn lock_sbk(&mut self) {
+        KeySlot::SBK.lock_read();
     }
 
     /// Locks the SSK from being read.
     #[inline]
-    pub(crate) fn lock_ssk(&self) {
-        let register_base = unsafe { Registers::get() };
+    pub(crate) fn lock_ssk(&mut self) {
+        KeySlot::SSK.lock_read();
+    }
+
+    /// Loads the SBK from the fuses into its keyslot and locks the SBK and
+    /// the SSK from being read.
+    ///
+    /// Fails with [`Error::Fuse`] if the private key fuses are hidden.
+    ///
+    /// [`Error::Fuse`]: ../enum.Error.html#variant.Fuse
+    pub fn load_sbk(&mut self) -> Result<()> {
+        let mut key = fuse::secure_boot_key().ok_or(Error::Fuse)?;
+
+        let result = self.set_aes_keyslot(KeySlot::SBK.index(), &key);
+        wipe(&mut key);
+        result?;
+
+        self.lock_sbk();
+        self.lock_ssk();
 
-        register_base.AES_KEYSLOT_FLAGS[0xF].write(0x7E);
+        Ok(())
     }
 
-    /// Sets the `INT_STATUS_REG` to `0x1F`.
-    #[inline]
-    pub(crate) fn config_brom(&self) {
+    /// Performs the SE configuration that the bootrom skips when booting
+    /// through RCM, i.e. loads the SBK and sets the `INT_STATUS_REG` to
+    /// `0x1F`.
+    ///
+    /// This mirrors the Erista bootrom and is refused on other revisions.
+    pub fn config_brom(&mut self) -> Result<()> {
+        hw_rev::require(SocRevision::Erista)?;
+
+        self.load_sbk()?;
+
         let register_base = unsafe { Registers::get() };
 
         register_base.INT_STATUS_REG.write(0x1F);
+
+        Ok(())
     }
 
-    /// Checks the ERR_STATUS_REG and panics if the value isn't zero.
+    /// Checks the ERR_STATUS_REG and fails with [`Error::Se`] if the value
+    /// isn't zero.
+    ///
+    /// [`Error::Se`]: ../enum.Error.html#variant.Se
     #[inline(always)]
-    pub fn check_error_status_reg(&self) {
+    pub fn check_error_status_reg(&self) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if register_base.ERR_STATUS_REG.read() != 0 {
-            panic!();
+            return Err(Error::Se);
         }
+
+        Ok(())
     }
 
-    /// Verifies that all flags are cleared and panics otherwise.
+    /// Verifies that all flags are cleared and fails with [`Error::Se`]
+    /// otherwise.
+    ///
+    /// [`Error::Se`]: ../enum.Error.html#variant.Se
     #[inline(always)]
-    pub fn verify_flags_cleared(&self) {
+    pub fn verify_flags_cleared(&self) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if register_base.FLAGS_REG.read() & 3 != 0 {
-            panic!();
+            return Err(Error::Se);
         }
+
+        Ok(())
     }
 
-    /// Checks for general SE errors and panics in case there are any.
+    /// Checks for general SE errors and fails with [`Error::Se`] in case
+    /// there are any.
+    ///
+    /// [`Error::Se`]: ../enum.Error.html#variant.Se
     #[inline]
-    pub fn check_for_error(&self) {
+    pub fn check_for_error(&self) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
-        self.check_error_status_reg();
+        self.check_error_status_reg()?;
 
-        self.verify_flags_cleared();
+        self.verify_flags_cleared()?;
 
         if register_base.INT_STATUS_REG.read() & 0x10000 != 0 {
-            panic!();
+            return Err(Error::Se);
         }
+
+        Ok(())
+    }
+
+    /// Checks for general SE errors.
+    pub fn has_error(&self) -> bool {
+        let register_base = unsafe { Registers::get() };
+
+        register_base.ERR_STATUS_REG.read() != 0
+            || register_base.FLAGS_REG.read() & 3 != 0
+            || register_base.INT_STATUS_REG.read() & 0x10000 != 0
     }
 
     /// Sets the flags for an AES keyslot.
-    pub fn set_aes_keyslot_flags(&self, keyslot: usize, flags: u32) {
+    pub fn set_aes_keyslot_flags(&mut self, keyslot: usize, flags: u32) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if keyslot >= KEYSLOT_AES_MAX {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         // Miscellaneous flags.
@@ -245,14 +344,16 @@ impl SecurityEngine {
                 .AES_KEY_READ_DISABLE_REG
                 .write(value & !(1 << keyslot as u32));
         }
+
+        Ok(())
     }
 
     /// Sets the flags for an RSA keyslot.
-    pub fn set_rsa_keyslot_flags(&self, keyslot: usize, flags: u32) {
+    pub fn set_rsa_keyslot_flags(&mut self, keyslot: usize, flags: u32) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if keyslot >= KEYSLOT_RSA_MAX {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         // Miscellaneous flags.
@@ -267,14 +368,16 @@ impl SecurityEngine {
                 .RSA_KEY_READ_DISABLE_REG
                 .write(value & !(1 << keyslot as u32));
         }
+
+        Ok(())
     }
 
     /// Clears an AES keyslot.
-    pub fn clear_aes_keyslot(&self, keyslot: usize) {
+    pub fn clear_aes_keyslot(&mut self, keyslot: usize) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if keyslot >= KEYSLOT_AES_MAX {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         // Zero out the whole keyslot and IV.
@@ -284,14 +387,16 @@ impl SecurityEngine {
                 .write(((keyslot << 4) | i) as u32);
             register_base.AES_KEYTABLE_DATA.write(0);
         }
+
+        Ok(())
     }
 
     /// Clears an RSA keyslot.
-    pub fn clear_rsa_keyslot(&self, keyslot: usize) {
+    pub fn clear_rsa_keyslot(&mut self, keyslot: usize) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if keyslot >= KEYSLOT_RSA_MAX {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         // Zero out the whole keyslot.
@@ -309,30 +414,67 @@ impl SecurityEngine {
                 .write(((keyslot << 7) | i) as u32);
             register_base.RSA_KEYTABLE_DATA.write(0);
         }
+
+        Ok(())
     }
 
-    /// Sets an AES keyslot.
-    pub fn set_aes_keyslot(&self, keyslot: usize, key: &[u8]) {
+    /// Sets an AES keyslot to a 128-bit key.
+    ///
+    /// Fails with [`Error::InvalidArgument`] for an invalid keyslot.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn set_aes_keyslot(
+        &mut self,
+        keyslot: usize,
+        key: &[u8; AES_128_KEY_SIZE],
+    ) -> Result<()> {
+        let mut words = aes_key_words(key);
+
+        let result = self.set_aes_keyslot_words(keyslot, &words);
+
+        for word in words.iter_mut() {
+            unsafe { core::ptr::write_volatile(word, 0) };
+        }
+
+        result
+    }
+
+    /// Sets an AES keyslot to a key given as keytable words.
+    ///
+    /// Keys of 128, 192 and 256 bits, i.e. 4, 6 or 8 words, are supported.
+    /// Fails with [`Error::InvalidArgument`] for other sizes or an invalid
+    /// keyslot.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn set_aes_keyslot_words(&mut self, keyslot: usize, key: &[u32]) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
-        let keysize = key.len();
+        if keyslot >= KEYSLOT_AES_MAX {
+            return Err(Error::InvalidArgument);
+        }
 
-        if keyslot >= KEYSLOT_AES_MAX || keysize > KEYSIZE_AES_MAX {
-            panic!();
+        match key.len() * 4 {
+            0x10 | 0x18 | KEYSIZE_AES_MAX => (),
+            _ => return Err(Error::InvalidArgument),
         }
 
-        for i in 0..keysize >> 2 {
+        for (i, &word) in key.iter().enumerate() {
             register_base
                 .AES_KEYTABLE_ADDR
                 .write(((keyslot << 4) | i) as u32);
-            register_base
-                .AES_KEYTABLE_DATA
-                .write(u32::from_le_bytes((&key[..4 * i]).try_into().unwrap()));
+            register_base.AES_KEYTABLE_DATA.write(word);
         }
+
+        Ok(())
     }
 
     /// Sets an RSA keyslot.
-    pub fn set_rsa_keyslot(&mut self, keyslot: usize, modulus: &[u8], exponent: &[u8]) {
+    pub fn set_rsa_keyslot(
+        &mut self,
+        keyslot: usize,
+        modulus: &[u8],
+        exponent: &[u8],
+    ) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         let modulus_size = modulus.len();
@@ -342,61 +484,55 @@ impl SecurityEngine {
             || modulus_size > KEYSIZE_RSA_MAX
             || exponent_size > KEYSIZE_RSA_MAX
         {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         for i in 0..modulus_size >> 2 {
             register_base
                 .RSA_KEYTABLE_ADDR
                 .write(((keyslot << 7) | 0x40 | i) as u32);
-            register_base.RSA_KEYTABLE_DATA.write(u32::from_be_bytes(
-                (&modulus[..(4 * (modulus_size >> 2)) - (4 * i) - 4])
-                    .try_into()
-                    .unwrap(),
-            ));
+            register_base.RSA_KEYTABLE_DATA.write(rsa_word(modulus, i));
         }
 
         for i in 0..exponent_size >> 2 {
             register_base
                 .RSA_KEYTABLE_ADDR
                 .write(((keyslot << 7) | i) as u32);
-            register_base.RSA_KEYTABLE_DATA.write(u32::from_be_bytes(
-                (&exponent[..(4 * (exponent_size >> 2)) - (4 * i) - 4])
-                    .try_into()
-                    .unwrap(),
-            ));
+            register_base.RSA_KEYTABLE_DATA.write(rsa_word(exponent, i));
         }
 
         self.modulus_sizes[keyslot] = modulus_size;
         self.exponent_sizes[keyslot] = exponent_size;
+
+        Ok(())
     }
 
     /// Sets the IV of the AES keyslot.
-    pub fn set_aes_keyslot_iv(&self, keyslot: usize, iv: &[u8]) {
+    pub fn set_aes_keyslot_iv(&mut self, keyslot: usize, iv: &[u8]) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         let iv_size = iv.len();
 
         if keyslot >= KEYSLOT_AES_MAX || iv_size > 0x10 {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         for i in 0..iv_size >> 2 {
             register_base
                 .AES_KEYTABLE_ADDR
                 .write(((keyslot << 4) | 8 | i) as u32);
-            register_base
-                .AES_KEYTABLE_DATA
-                .write(u32::from_le_bytes((&iv[..4 * i]).try_into().unwrap()));
+            register_base.AES_KEYTABLE_DATA.write(aes_word(iv, i));
         }
+
+        Ok(())
     }
 
     /// Clears the IV of the AES keyslot.
-    pub fn clear_aes_keyslot_iv(&self, keyslot: usize) {
+    pub fn clear_aes_keyslot_iv(&mut self, keyslot: usize) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if keyslot >= KEYSLOT_AES_MAX {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         for i in 0..0x10 >> 2 {
@@ -405,32 +541,33 @@ impl SecurityEngine {
                 .write(((keyslot << 4) | 8 | i) as u32);
             register_base.AES_KEYTABLE_DATA.write(0);
         }
+
+        Ok(())
     }
 
     /// Sets the CRYPTO_CTR_REG to enable CTR mode.
-    pub fn set_ctr(&self, ctr: &[u8]) {
+    pub fn set_ctr(&mut self, ctr: &[u8]) {
         let register_base = unsafe { Registers::get() };
 
         for i in 0..4 {
-            register_base.CRYPTO_CTR_REG[i]
-                .write(u32::from_le_bytes((&ctr[..4 * i]).try_into().unwrap()));
+            register_base.CRYPTO_CTR_REG[i].write(aes_word(ctr, i));
         }
     }
 
     /// Decrypts data from a given keyslot into another keyslot.
     pub fn decrypt_data_into_keyslot(
-        &self,
+        &mut self,
         destination: usize,
         source: usize,
         wrapped_key: &mut [u8],
-    ) {
+    ) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if destination >= KEYSLOT_AES_MAX
             || source >= KEYSLOT_AES_MAX
             || wrapped_key.len() > KEYSIZE_AES_MAX
         {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         register_base.CONFIG_REG.write(0x108);
@@ -440,19 +577,53 @@ impl SecurityEngine {
             .CRYPTO_KEYTABLE_DST_REG
             .write((destination << 8) as u32);
 
-        self.trigger_blocking_operation(OP_START, &mut [0; 0], wrapped_key);
+        self.trigger_blocking_operation(OP_START, &mut [0; 0], wrapped_key)
     }
 
     /// Performs a blocking AES operation.
-    pub fn perform_aes_block_operation(&self, destination: &mut [u8], source: &mut [u8]) {
+    pub fn perform_aes_block_operation(
+        &mut self,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
         let register_base = unsafe { Registers::get() };
 
         if source.len() > 0x10 || destination.len() > 0x10 {
-            panic!();
+            return Err(Error::InvalidArgument);
         }
 
         // Trigger AES operation.
         register_base.BLOCK_COUNT_REG.write(0);
-        self.trigger_blocking_operation(OP_START, destination, source);
+        self.trigger_blocking_operation(OP_START, destination, source)
+    }
+
+    /// Performs a blocking AES operation, retrying it according to the given
+    /// policy in case the SE reports an error.
+    pub fn perform_aes_block_operation_with(
+        &mut self,
+        policy: &RetryPolicy,
+        destination: &mut [u8],
+        source: &mut [u8],
+    ) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        if source.len() > 0x10 || destination.len() > 0x10 {
+            return Err(Error::InvalidArgument);
+        }
+
+        policy.run(|| {
+            // Don't let the errors of a failed attempt fail the next one.
+            self.clear_status();
+
+            // Trigger AES operation.
+            register_base.BLOCK_COUNT_REG.write(0);
+            self.run_blocking_operation(OP_START, destination, source)?;
+
+            if self.has_error() {
+                Err(Error::Se)
+            } else {
+                Ok(())
+            }
+        })
     }
 }
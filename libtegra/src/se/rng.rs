@@ -0,0 +1,158 @@
+//! Security Engine random number generator.
+//!
+//! # Description
+//!
+//! The SE contains a hardware entropy source feeding an AES-CTR based
+//! deterministic random bit generator (DRBG). Its output is suitable for
+//! session keys, nonces and for overwriting memory that held secrets.
+//!
+//! # Implementation
+//!
+//! - [`SecurityEngine::initialize_rng`] enables the entropy source, sets the
+//! automatic reseed interval and instantiates the DRBG. It is run
+//! implicitly by the first call to [`SecurityEngine::generate_random`].
+//!
+//! - [`SecurityEngine::generate_random`] fills a buffer of any length with
+//! random data, in as few SE operations as possible.
+//!
+//! - [`SecurityEngine::reseed_rng`] forces the DRBG to reseed from the
+//! entropy source, e.g. before generating long-term keys.
+//!
+//! [`SecurityEngine::initialize_rng`]: struct.SecurityEngine.html#method.initialize_rng
+//! [`SecurityEngine::generate_random`]: struct.SecurityEngine.html#method.generate_random
+//! [`SecurityEngine::reseed_rng`]: struct.SecurityEngine.html#method.reseed_rng
+
+use mirage_mmio::VolatileStorage;
+
+use super::{Registers, SecurityEngine, AES_BLOCK_SIZE, OP_START};
+use crate::Result;
+
+/// The keyslot used by the DRBG.
+pub const RNG_KEYSLOT: usize = 0;
+
+/// Generates random numbers into memory.
+pub(super) const SE_CONFIG_ALG_RNG_DST_MEMORY: u32 = 2 << 12;
+/// Generates random numbers into a keyslot.
+pub(super) const SE_CONFIG_ALG_RNG_DST_KEYTABLE: u32 = (2 << 12) | (1 << 2);
+/// Generates random numbers into the SRK.
+pub(super) const SE_CONFIG_ALG_RNG_DST_SRK: u32 = (2 << 12) | (3 << 2);
+
+/// Crypto configuration for the RNG, with the keyslot in bits 24 and up.
+const SE_CRYPTO_CONFIG_RNG: u32 = 0x108;
+
+/// Takes the RNG output from the entropy source.
+pub(super) const SE_RNG_CONFIG_SRC_ENTROPY: u32 = 1 << 2;
+/// Forces an instantiation of the DRBG.
+const SE_RNG_CONFIG_MODE_FORCE_INSTANTIATION: u32 = 1;
+/// Forces a reseed of the RNG.
+pub(super) const SE_RNG_CONFIG_MODE_FORCE_RESEED: u32 = 2;
+
+/// Enables the entropy source.
+const SE_RNG_SRC_CONFIG_ENTROPY_SRC: u32 = 1 << 1;
+/// Locks the entropy source configuration.
+const SE_RNG_SRC_CONFIG_ENTROPY_SRC_LOCK: u32 = 1 << 0;
+
+/// The amount of blocks after which the DRBG reseeds itself.
+const RNG_RESEED_INTERVAL: u32 = 70001;
+
+/// The largest amount of random data generated in a single operation.
+const RNG_CHUNK_SIZE: usize = 0x1_0000;
+
+/// Whether the DRBG has been instantiated.
+static mut RNG_INSTANTIATED: bool = false;
+
+impl SecurityEngine {
+    /// Configures the RNG with the given keyslot.
+    pub(super) fn configure_rng(&mut self, config: u32, keyslot: usize, rng_config: u32) {
+        let register_base = unsafe { Registers::get() };
+
+        register_base.CONFIG_REG.write(config);
+        register_base
+            .CRYPTO_REG
+            .write(((keyslot as u32) << 24) | SE_CRYPTO_CONFIG_RNG);
+        register_base.RNG_CONFIG_REG.write(rng_config);
+        register_base.BLOCK_COUNT_REG.write(0);
+    }
+
+    /// Runs an RNG operation in the given mode and discards its output.
+    fn run_rng_mode(&mut self, mode: u32) -> Result<()> {
+        let mut discard = [0; AES_BLOCK_SIZE];
+
+        self.configure_rng(
+            SE_CONFIG_ALG_RNG_DST_MEMORY,
+            RNG_KEYSLOT,
+            SE_RNG_CONFIG_SRC_ENTROPY | mode,
+        );
+        self.run_checked_operation(OP_START, &mut discard, &mut [])
+    }
+
+    /// Enables the entropy source and instantiates the DRBG.
+    pub fn initialize_rng(&mut self) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        register_base
+            .RNG_SRC_CONFIG_REG
+            .write(SE_RNG_SRC_CONFIG_ENTROPY_SRC | SE_RNG_SRC_CONFIG_ENTROPY_SRC_LOCK);
+        register_base
+            .RNG_RESEED_INTERVAL_REG
+            .write(RNG_RESEED_INTERVAL);
+
+        self.run_rng_mode(SE_RNG_CONFIG_MODE_FORCE_INSTANTIATION)?;
+        unsafe {
+            RNG_INSTANTIATED = true;
+        }
+
+        Ok(())
+    }
+
+    /// Forces the DRBG to reseed from the entropy source.
+    pub fn reseed_rng(&mut self) -> Result<()> {
+        if !unsafe { RNG_INSTANTIATED } {
+            return self.initialize_rng();
+        }
+
+        self.run_rng_mode(SE_RNG_CONFIG_MODE_FORCE_RESEED)
+    }
+
+    /// Fills a buffer with random data.
+    ///
+    /// The DRBG is instantiated first if necessary. The buffer must be
+    /// accessible by the SE, i.e. reside in the lower 4GiB.
+    pub fn generate_random(&mut self, buffer: &mut [u8]) -> Result<()> {
+        let register_base = unsafe { Registers::get() };
+
+        if !unsafe { RNG_INSTANTIATED } {
+            self.initialize_rng()?;
+        }
+
+        self.configure_rng(
+            SE_CONFIG_ALG_RNG_DST_MEMORY,
+            RNG_KEYSLOT,
+            SE_RNG_CONFIG_SRC_ENTROPY,
+        );
+
+        let mut chunks = buffer.chunks_exact_mut(RNG_CHUNK_SIZE);
+        for chunk in &mut chunks {
+            register_base
+                .BLOCK_COUNT_REG
+                .write((RNG_CHUNK_SIZE / AES_BLOCK_SIZE - 1) as u32);
+            self.run_checked_operation(OP_START, chunk, &mut [])?;
+        }
+
+        let remainder = chunks.into_remainder();
+        let blocks = remainder.len() / AES_BLOCK_SIZE;
+        let (whole, tail) = remainder.split_at_mut(blocks * AES_BLOCK_SIZE);
+        if blocks > 0 {
+            register_base.BLOCK_COUNT_REG.write(blocks as u32 - 1);
+            self.run_checked_operation(OP_START, whole, &mut [])?;
+        }
+        if !tail.is_empty() {
+            let mut block = [0; AES_BLOCK_SIZE];
+            register_base.BLOCK_COUNT_REG.write(0);
+            self.run_checked_operation(OP_START, &mut block, &mut [])?;
+            tail.copy_from_slice(&block[..tail.len()]);
+        }
+
+        Ok(())
+    }
+}
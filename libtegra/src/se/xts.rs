@@ -0,0 +1,379 @@
+//! Security Engine AES-XTS.
+//!
+//! # Description
+//!
+//! Storage is usually encrypted in the XTS mode, which uses two keys: the
+//! tweak key encrypts the sector number into a tweak, which is mixed into
+//! every block of the sector that is encrypted with the data key. The same
+//! data thus encrypts differently in every sector, without storing an IV.
+//!
+//! Nintendo's BIS partitions on the eMMC use XTS as well, but store the
+//! sector number in the tweak as a big-endian number, unlike IEEE 1619.
+//!
+//! # Implementation
+//!
+//! - The SE of the Tegra210 has no XTS mode, so the tweaks are computed in
+//! software and the blocks are transformed with AES-ECB, several blocks
+//! per SE operation.
+//!
+//! - [`XtsConfig`] describes the keyslots, the sector size and the tweak
+//! layout. [`SecurityEngine::aes_xts_encrypt`] and
+//! [`SecurityEngine::aes_xts_decrypt`] transform whole sectors in place.
+//!
+//! - [`XtsDevice`] wraps a [`BlockDevice`], e.g. a partition, and
+//! transparently decrypts reads and encrypts writes. Accesses have to be
+//! aligned to whole sectors.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     block::BlockDevice,
+//!     se::{SecurityEngine, XtsConfig, XtsDevice},
+//! };
+//!
+//! fn read_sector<D: BlockDevice>(partition: D, se: &mut SecurityEngine) -> [u8; 0x4000] {
+//!     // The keys have been loaded into keyslots 4 and 5 before.
+//!     let config = XtsConfig::nintendo(4, 5, 0x4000);
+//!     let mut device = XtsDevice::new(partition, se, config);
+//!
+//!     let mut sector = [0; 0x4000];
+//!     device.read_blocks(0, &mut sector).unwrap();
+//!
+//!     sector
+//! }
+//! ```
+//!
+//! [`XtsConfig`]: struct.XtsConfig.html
+//! [`SecurityEngine::aes_xts_encrypt`]: struct.SecurityEngine.html#method.aes_xts_encrypt
+//! [`SecurityEngine::aes_xts_decrypt`]: struct.SecurityEngine.html#method.aes_xts_decrypt
+//! [`XtsDevice`]: struct.XtsDevice.html
+//! [`BlockDevice`]: ../block/trait.BlockDevice.html
+
+use super::{
+    aes::{
+        SE_CONFIG_DEC_AES_DST_MEMORY, SE_CONFIG_ENC_AES_DST_MEMORY, SE_CRYPTO_CORE_DECRYPT,
+        SE_CRYPTO_CORE_ENCRYPT,
+    },
+    SecurityEngine, AES_BLOCK_SIZE,
+};
+use crate::{
+    block::{BlockDevice, BLOCK_SIZE},
+    Error, Result,
+};
+
+/// The amount of bytes transformed with a single SE operation.
+const XTS_CHUNK_SIZE: usize = 0x200;
+
+/// The layout of the sector number in the initial tweak.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XtsTweak {
+    /// Little-endian in the first bytes, as specified by IEEE 1619.
+    LittleEndian,
+    /// Big-endian in the last bytes, as used by Nintendo.
+    BigEndian,
+}
+
+/// The parameters of an XTS-encrypted storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XtsConfig {
+    /// The keyslot holding the data key.
+    pub data_keyslot: usize,
+    /// The keyslot holding the tweak key.
+    pub tweak_keyslot: usize,
+    /// The size of a sector in bytes, a multiple of the AES block size.
+    pub sector_size: usize,
+    /// The layout of the sector number in the tweak.
+    pub tweak: XtsTweak,
+}
+
+impl XtsConfig {
+    /// Creates the parameters of standard XTS.
+    pub const fn new(data_keyslot: usize, tweak_keyslot: usize, sector_size: usize) -> Self {
+        XtsConfig {
+            data_keyslot,
+            tweak_keyslot,
+            sector_size,
+            tweak: XtsTweak::LittleEndian,
+        }
+    }
+
+    /// Creates the parameters of Nintendo's XTS variant.
+    pub const fn nintendo(data_keyslot: usize, tweak_keyslot: usize, sector_size: usize) -> Self {
+        XtsConfig {
+            data_keyslot,
+            tweak_keyslot,
+            sector_size,
+            tweak: XtsTweak::BigEndian,
+        }
+    }
+
+    /// Builds the unencrypted tweak of a sector.
+    fn sector_tweak(&self, sector: u64) -> [u8; AES_BLOCK_SIZE] {
+        let mut tweak = [0; AES_BLOCK_SIZE];
+
+        match self.tweak {
+            XtsTweak::LittleEndian => tweak[..8].copy_from_slice(&sector.to_le_bytes()),
+            XtsTweak::BigEndian => tweak[8..].copy_from_slice(&sector.to_be_bytes()),
+        }
+
+        tweak
+    }
+}
+
+/// Multiplies a tweak by the primitive element of GF(2^128), as XTS does
+/// for every block.
+fn next_tweak(tweak: &mut [u8; AES_BLOCK_SIZE]) {
+    let mut carry = 0;
+
+    for byte in tweak.iter_mut() {
+        let next = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = next;
+    }
+
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
+
+/// XORs the consecutive tweaks, starting with `tweak`, into the blocks of
+/// `destination`, taking the blocks from `source`.
+fn apply_tweaks(tweak: &[u8; AES_BLOCK_SIZE], destination: &mut [u8], source: &[u8]) {
+    let mut tweak = *tweak;
+
+    for (output, input) in destination
+        .chunks_exact_mut(AES_BLOCK_SIZE)
+        .zip(source.chunks_exact(AES_BLOCK_SIZE))
+    {
+        for ((o, i), t) in output.iter_mut().zip(input.iter()).zip(tweak.iter()) {
+            *o = i ^ t;
+        }
+
+        next_tweak(&mut tweak);
+    }
+}
+
+impl SecurityEngine {
+    /// Computes the encrypted tweak of a sector.
+    fn xts_tweak(&mut self, config: &XtsConfig, sector: u64) -> Result<[u8; AES_BLOCK_SIZE]> {
+        let mut tweak = config.sector_tweak(sector);
+
+        self.aes_ecb_encrypt_block(config.tweak_keyslot, &mut tweak)?;
+
+        Ok(tweak)
+    }
+
+    /// Transforms blocks of a sector in place, starting with `tweak`, which
+    /// is advanced past the blocks.
+    fn xts_crypt_blocks(
+        &mut self,
+        config: &XtsConfig,
+        encrypt: bool,
+        tweak: &mut [u8; AES_BLOCK_SIZE],
+        data: &mut [u8],
+    ) -> Result<()> {
+        let (se_config, crypto) = if encrypt {
+            (SE_CONFIG_ENC_AES_DST_MEMORY, SE_CRYPTO_CORE_ENCRYPT)
+        } else {
+            (SE_CONFIG_DEC_AES_DST_MEMORY, SE_CRYPTO_CORE_DECRYPT)
+        };
+        let mut buffer = [0; XTS_CHUNK_SIZE];
+
+        for chunk in data.chunks_mut(XTS_CHUNK_SIZE) {
+            let source = &mut buffer[..chunk.len()];
+
+            apply_tweaks(tweak, source, chunk);
+            self.aes_ecb(se_config, crypto, config.data_keyslot, chunk, source)?;
+            apply_tweaks(tweak, source, chunk);
+            chunk.copy_from_slice(source);
+
+            for _ in 0..chunk.len() / AES_BLOCK_SIZE {
+                next_tweak(tweak);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Transforms consecutive sectors in place, starting at `sector`.
+    fn aes_xts(
+        &mut self,
+        config: &XtsConfig,
+        encrypt: bool,
+        sector: u64,
+        data: &mut [u8],
+    ) -> Result<()> {
+        if config.sector_size == 0
+            || config.sector_size % AES_BLOCK_SIZE != 0
+            || data.len() % config.sector_size != 0
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        for (index, chunk) in data.chunks_mut(config.sector_size).enumerate() {
+            let mut tweak = self.xts_tweak(config, sector + index as u64)?;
+
+            self.xts_crypt_blocks(config, encrypt, &mut tweak, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts consecutive sectors in place, starting at `sector`.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the data doesn't consist of
+    /// whole sectors.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn aes_xts_encrypt(
+        &mut self,
+        config: &XtsConfig,
+        sector: u64,
+        data: &mut [u8],
+    ) -> Result<()> {
+        self.aes_xts(config, true, sector, data)
+    }
+
+    /// Decrypts consecutive sectors in place, starting at `sector`.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the data doesn't consist of
+    /// whole sectors.
+    ///
+    /// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+    pub fn aes_xts_decrypt(
+        &mut self,
+        config: &XtsConfig,
+        sector: u64,
+        data: &mut [u8],
+    ) -> Result<()> {
+        self.aes_xts(config, false, sector, data)
+    }
+}
+
+/// A block device that is encrypted with AES-XTS.
+pub struct XtsDevice<'a, D: BlockDevice> {
+    /// The underlying device.
+    device: D,
+    /// The SE doing the encryption.
+    se: &'a mut SecurityEngine,
+    /// The parameters of the encryption.
+    config: XtsConfig,
+}
+
+impl<'a, D: BlockDevice> XtsDevice<'a, D> {
+    /// Wraps a device with the given encryption.
+    ///
+    /// The sector size has to be a multiple of [`BLOCK_SIZE`].
+    ///
+    /// [`BLOCK_SIZE`]: ../block/constant.BLOCK_SIZE.html
+    pub fn new(device: D, se: &'a mut SecurityEngine, config: XtsConfig) -> Self {
+        XtsDevice { device, se, config }
+    }
+
+    /// Gets the underlying device back.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    /// Gets the first sector of an access and validates its alignment.
+    fn first_sector(&self, lba: u64, len: usize) -> Result<u64> {
+        let sector_size = self.config.sector_size;
+
+        if sector_size == 0 || sector_size % BLOCK_SIZE != 0 || len % sector_size != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let blocks_per_sector = (sector_size / BLOCK_SIZE) as u64;
+        if lba % blocks_per_sector != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(lba / blocks_per_sector)
+    }
+}
+
+impl<'a, D: BlockDevice> BlockDevice for XtsDevice<'a, D> {
+    fn block_count(&self) -> u64 {
+        self.device.block_count()
+    }
+
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<()> {
+        let sector = self.first_sector(lba, buffer.len())?;
+
+        self.device.read_blocks(lba, buffer)?;
+        self.se.aes_xts_decrypt(&self.config, sector, buffer)
+    }
+
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<()> {
+        let first_sector = self.first_sector(lba, buffer.len())?;
+        let mut block = [0; BLOCK_SIZE];
+        let mut lba = lba;
+
+        // The input can't be encrypted in place, so the sectors are
+        // encrypted and written one block at a time.
+        for (index, sector) in buffer.chunks(self.config.sector_size).enumerate() {
+            let mut tweak = self.se.xts_tweak(&self.config, first_sector + index as u64)?;
+
+            for data in sector.chunks(BLOCK_SIZE) {
+                block.copy_from_slice(data);
+                self.se.xts_crypt_blocks(&self.config, true, &mut tweak, &mut block)?;
+                self.device.write_blocks(lba, &block)?;
+
+                lba += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the sector number layouts in the tweak.
+    #[test]
+    fn sector_tweak() {
+        let sector = 0x0102_0304_0506_0708;
+
+        let tweak = XtsConfig::new(0, 1, 0x200).sector_tweak(sector);
+        assert_eq!(&tweak[..8], &[8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(&tweak[8..], &[0; 8]);
+
+        let tweak = XtsConfig::nintendo(0, 1, 0x4000).sector_tweak(sector);
+        assert_eq!(&tweak[..8], &[0; 8]);
+        assert_eq!(&tweak[8..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    /// Tests the multiplication of tweaks, including the reduction.
+    #[test]
+    fn tweak_multiplication() {
+        let mut tweak = [0; AES_BLOCK_SIZE];
+        tweak[0] = 0x81;
+        tweak[7] = 0x80;
+        next_tweak(&mut tweak);
+        assert_eq!(tweak[0], 0x02);
+        assert_eq!(tweak[1], 0x01);
+        assert_eq!(tweak[8], 0x01);
+
+        let mut tweak = [0; AES_BLOCK_SIZE];
+        tweak[15] = 0x80;
+        next_tweak(&mut tweak);
+        assert_eq!(tweak[0], 0x87);
+        assert_eq!(tweak[15], 0x00);
+    }
+
+    /// Tests that the tweaks are applied to consecutive blocks.
+    #[test]
+    fn tweaks() {
+        let mut tweak = [0; AES_BLOCK_SIZE];
+        tweak[0] = 1;
+        let source = [0; 2 * AES_BLOCK_SIZE];
+        let mut destination = [0xFF; 2 * AES_BLOCK_SIZE];
+
+        apply_tweaks(&tweak, &mut destination, &source);
+        assert_eq!(destination[0], 1);
+        assert_eq!(destination[AES_BLOCK_SIZE], 2);
+    }
+}
@@ -0,0 +1,91 @@
+//! Wiping of sensitive state before handing over control.
+//!
+//! # Description
+//!
+//! A bootloader that derived keys or loaded encrypted firmware leaves key
+//! material behind in the SE keyslots and in memory. Before chainloading a
+//! payload that isn't trusted with it, all of this has to be cleaned up.
+//!
+//! # Implementation
+//!
+//! - [`scrub`] overwrites a [`Region`] of IRAM or DRAM with random data from
+//! the SE, so no remains of the previous contents can be recovered. Cache
+//! lines covering the region are written back before and discarded after
+//! the SE wrote to memory.
+//!
+//! - [`se::clear_all_keyslots`] wipes the SE keyslots. [`scrub_all`] does
+//! both for a list of regions.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{memory_map::Region, security};
+//!
+//! fn main() {
+//!     let keys = Region::new("keys", 0x4003_0000, 0x1000);
+//!
+//!     unsafe { security::scrub_all(&[keys]).unwrap() };
+//! }
+//! ```
+//!
+//! [`scrub`]: fn.scrub.html
+//! [`Region`]: ../memory_map/struct.Region.html
+//! [`se::clear_all_keyslots`]: ../se/fn.clear_all_keyslots.html
+//! [`scrub_all`]: fn.scrub_all.html
+
+use core::slice;
+
+use crate::{
+    arch,
+    memory_map::{Region, MEMORIES},
+    se::{self, SecurityEngine},
+    Error, Result,
+};
+
+/// Overwrites a region of memory with random data.
+///
+/// Fails with [`Error::InvalidArgument`] if the region doesn't lie within
+/// IRAM, TZRAM or DRAM.
+///
+/// # Safety
+///
+/// The region must not hold anything that is still in use, including the
+/// stack and the code of the caller.
+///
+/// [`Error::InvalidArgument`]: ../enum.Error.html#variant.InvalidArgument
+pub unsafe fn scrub(region: &Region) -> Result<()> {
+    if !MEMORIES.iter().any(|memory| region.is_within(memory)) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let address = region.start as usize;
+    let len = region.size as usize;
+
+    arch::clean_invalidate_range(address, len);
+    arch::data_barrier();
+
+    let memory = slice::from_raw_parts_mut(address as *mut u8, len);
+    let result = SecurityEngine::new().generate_random(memory);
+
+    arch::data_barrier();
+    arch::invalidate_range(address, len);
+
+    result
+}
+
+/// Clears all SE keyslots and scrubs the given regions.
+///
+/// # Safety
+///
+/// See [`scrub`].
+///
+/// [`scrub`]: fn.scrub.html
+pub unsafe fn scrub_all(regions: &[Region]) -> Result<()> {
+    se::clear_all_keyslots()?;
+
+    for region in regions {
+        scrub(region)?;
+    }
+
+    Ok(())
+}
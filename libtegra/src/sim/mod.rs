@@ -0,0 +1,222 @@
+//! Host simulation of the display and button input.
+//!
+//! # Description
+//!
+//! Iterating on UI layout and navigation on real hardware is slow, as
+//! every change requires injecting a new payload. With the `std` feature
+//! enabled, this module provides in-memory implementations of the
+//! [`Canvas`] and [`Input`] abstractions, so UI code can be run as a
+//! regular program on a development host and only the final result has
+//! to be validated on the console.
+//!
+//! # Implementation
+//!
+//! - [`SimDisplay`] is a [`Canvas`] backed by a pixel buffer in memory.
+//! Its contents can be written out as PNG images, either one by one
+//! through [`SimDisplay::save_png`] or as a numbered sequence of frames
+//! through [`SimDisplay::save_frame`].
+//!
+//! - [`ScriptedInput`] is an [`Input`] which replays a fixed sequence of
+//! button states, one per call to [`Input::read`], and reports no buttons
+//! once the script has ended.
+//!
+//! - The PNG encoder stores the image data uncompressed and thus doesn't
+//! require any external dependencies.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{display, sim::SimDisplay};
+//!
+//! fn main() {
+//!     let mut canvas = SimDisplay::framebuffer();
+//!
+//!     display::render_panic_screen(&mut canvas, 0xDEAD_BEEF, format_args!("test"), b"MRG");
+//!     canvas.save_png("panic.png").unwrap();
+//! }
+//! ```
+//!
+//! [`Canvas`]: ../display/trait.Canvas.html
+//! [`Input`]: ../button/trait.Input.html
+//! [`Input::read`]: ../button/trait.Input.html#tymethod.read
+//! [`SimDisplay`]: struct.SimDisplay.html
+//! [`SimDisplay::save_png`]: struct.SimDisplay.html#method.save_png
+//! [`SimDisplay::save_frame`]: struct.SimDisplay.html#method.save_frame
+//! [`ScriptedInput`]: struct.ScriptedInput.html
+
+use std::{fs::File, io, io::Write, path::Path, vec::Vec};
+
+use crate::{
+    button::{Button, Input},
+    checksum::{adler32, crc32},
+    display::{Canvas, Framebuffer},
+};
+
+/// The maximum size of a stored deflate block.
+const DEFLATE_BLOCK_SIZE: usize = 0xFFFF;
+
+/// A [`Canvas`] backed by memory.
+///
+/// [`Canvas`]: ../display/trait.Canvas.html
+pub struct SimDisplay {
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+    frame: u32,
+}
+
+impl SimDisplay {
+    /// Creates a black canvas of the given size.
+    pub fn new(width: u32, height: u32) -> Self {
+        SimDisplay {
+            width,
+            height,
+            pixels: vec![0xFF00_0000; (width * height) as usize],
+            frame: 0,
+        }
+    }
+
+    /// Creates a black canvas with the dimensions of the framebuffer.
+    pub fn framebuffer() -> Self {
+        let framebuffer = Framebuffer::new();
+
+        Self::new(framebuffer.width(), framebuffer.height())
+    }
+
+    /// Gets the color of a pixel, if it is within the canvas.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<u32> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[(x + y * self.width) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Gets all pixels of the canvas, row by row.
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Writes the canvas to a PNG image at the given path.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+
+        file.write_all(&encode_png(self.width, self.height, &self.pixels))
+    }
+
+    /// Writes the canvas as the next frame of a sequence into the given
+    /// directory and returns the number of the frame.
+    ///
+    /// The frames are named `frame0000.png`, `frame0001.png` and so on.
+    pub fn save_frame<P: AsRef<Path>>(&mut self, directory: P) -> io::Result<u32> {
+        let frame = self.frame;
+
+        self.save_png(directory.as_ref().join(format!("frame{:04}.png", frame)))?;
+        self.frame += 1;
+
+        Ok(frame)
+    }
+}
+
+impl Canvas for SimDisplay {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[(x + y * self.width) as usize] = color;
+        }
+    }
+}
+
+/// An [`Input`] which replays a fixed sequence of button states.
+///
+/// [`Input`]: ../button/trait.Input.html
+pub struct ScriptedInput {
+    script: Vec<Button>,
+    position: usize,
+}
+
+impl ScriptedInput {
+    /// Creates an input which replays the given button states.
+    pub fn new(script: &[Button]) -> Self {
+        ScriptedInput {
+            script: script.to_vec(),
+            position: 0,
+        }
+    }
+
+    /// Whether all button states of the script have been read.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.script.len()
+    }
+}
+
+impl Input for ScriptedInput {
+    fn read(&mut self) -> Button {
+        match self.script.get(self.position) {
+            Some(buttons) => {
+                self.position += 1;
+                *buttons
+            }
+            None => Button::empty(),
+        }
+    }
+}
+
+/// Appends a PNG chunk to the output.
+fn write_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = output.len();
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encodes ARGB8888 pixels as an RGBA PNG image.
+fn encode_png(width: u32, height: u32, pixels: &[u32]) -> Vec<u8> {
+    // Every row starts with the filter type, which is always "none".
+    let mut raw = Vec::with_capacity(((width * 4 + 1) * height) as usize);
+    for row in pixels.chunks(width as usize) {
+        raw.push(0);
+
+        for pixel in row {
+            let [a, r, g, b] = pixel.to_be_bytes();
+            raw.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+
+    // Wrap the image data into a zlib stream of stored deflate blocks.
+    let mut zlib = vec![0x78, 0x01];
+    let block_count = (raw.len() + DEFLATE_BLOCK_SIZE - 1) / DEFLATE_BLOCK_SIZE;
+    for (index, block) in raw.chunks(DEFLATE_BLOCK_SIZE).enumerate() {
+        let length = block.len() as u16;
+
+        zlib.push((index + 1 == block_count) as u8);
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut output = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    write_chunk(&mut output, b"IHDR", &header);
+    write_chunk(&mut output, b"IDAT", &zlib);
+    write_chunk(&mut output, b"IEND", &[]);
+
+    output
+}
@@ -0,0 +1,227 @@
+//! Tegra210 Serial Output Resource driver.
+//!
+//! # Description
+//!
+//! The Serial Output Resources (SOR) encode the pixel stream of a display
+//! controller for HDMI, DisplayPort or LVDS outputs. Tegra X1 devices have
+//! two instances, SOR0 and SOR1. On the Switch, SOR1 drives the external
+//! DisplayPort link which the dock converts to HDMI.
+//!
+//! Besides their display functionality, the HDCP registers of SOR1 are
+//! used by the TSEC firmware of older firmware versions to hand the
+//! derived TSEC key over to the bootloader.
+//!
+//! # Implementation
+//!
+//! - The [`Registers`] struct represents the SOR register block. Regions
+//! that aren't used by Mirage are left out as reserved.
+//!
+//! - The [`Sor`] struct represents a SOR instance and holds the [`Clock`]
+//! and the respective [`Registers`] block pointer. [`Sor::SOR0`] and
+//! [`Sor::SOR1`] should be preferred over creating instances manually.
+//!
+//! - [`Sor::hdcp_key`] reads the key the TSEC firmware leaves in the HDCP
+//! registers and [`Sor::clear_hdcp_key`] wipes it again, which should be
+//! done as soon as the key has been fetched.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::sor::Sor;
+//!
+//! fn main() {
+//!     let key = Sor::SOR1.hdcp_key();
+//!     Sor::SOR1.clear_hdcp_key();
+//! }
+//! ```
+//!
+//! [`Registers`]: struct.Registers.html
+//! [`Sor`]: struct.Sor.html
+//! [`Clock`]: ../clock/struct.Clock.html
+//! [`Sor::SOR0`]: struct.Sor.html#associatedconstant.SOR0
+//! [`Sor::SOR1`]: struct.Sor.html#associatedconstant.SOR1
+//! [`Sor::hdcp_key`]: struct.Sor.html#method.hdcp_key
+//! [`Sor::clear_hdcp_key`]: struct.Sor.html#method.clear_hdcp_key
+
+use mirage_mmio::{BlockMmio, Mmio};
+
+use crate::clock::Clock;
+
+/// Base address for SOR0 registers.
+const SOR0_BASE: u32 = 0x5454_0000;
+
+/// Base address for SOR1 registers.
+const SOR1_BASE: u32 = 0x5458_0000;
+
+/// Representation of the SOR registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct Registers {
+    pub SOR_CTXSW: Mmio<u32>,                    // 0x0
+    pub SOR_SUPER_STATE0: Mmio<u32>,             // 0x4
+    pub SOR_SUPER_STATE1: Mmio<u32>,             // 0x8
+    pub SOR_STATE0: Mmio<u32>,                   // 0xC
+    pub SOR_STATE1: Mmio<u32>,                   // 0x10
+    pub SOR_HEAD_STATE0: [Mmio<u32>; 2],         // 0x14
+    pub SOR_HEAD_STATE1: [Mmio<u32>; 2],         // 0x1C
+    pub SOR_HEAD_STATE2: [Mmio<u32>; 2],         // 0x24
+    pub SOR_HEAD_STATE3: [Mmio<u32>; 2],         // 0x2C
+    pub SOR_HEAD_STATE4: [Mmio<u32>; 2],         // 0x34
+    pub SOR_HEAD_STATE5: [Mmio<u32>; 2],         // 0x3C
+    pub SOR_CRC_CNTRL: Mmio<u32>,                // 0x44
+    pub SOR_DP_DEBUG_MVID: Mmio<u32>,            // 0x48
+    pub SOR_CLK_CNTRL: Mmio<u32>,                // 0x4C
+    pub SOR_CAP: Mmio<u32>,                      // 0x50
+    pub SOR_PWR: Mmio<u32>,                      // 0x54
+    pub SOR_TEST: Mmio<u32>,                     // 0x58
+    pub SOR_PLL0: Mmio<u32>,                     // 0x5C
+    pub SOR_PLL1: Mmio<u32>,                     // 0x60
+    pub SOR_PLL2: Mmio<u32>,                     // 0x64
+    pub SOR_PLL3: Mmio<u32>,                     // 0x68
+    pub SOR_CSTM: Mmio<u32>,                     // 0x6C
+    pub SOR_LVDS: Mmio<u32>,                     // 0x70
+    pub SOR_CRCA: Mmio<u32>,                     // 0x74
+    pub SOR_CRCB: Mmio<u32>,                     // 0x78
+    pub SOR_BLANK: Mmio<u32>,                    // 0x7C
+    pub SOR_SEQ_CTL: Mmio<u32>,                  // 0x80
+    pub SOR_LANE_SEQ_CTL: Mmio<u32>,             // 0x84
+    pub SOR_SEQ_INST: [Mmio<u32>; 16],           // 0x88
+    pub SOR_PWM_DIV: Mmio<u32>,                  // 0xC8
+    pub SOR_PWM_CTL: Mmio<u32>,                  // 0xCC
+    _reserved0: [Mmio<u8>; 0x58],
+    pub SOR_XBAR_CTRL: Mmio<u32>,                // 0x128
+    pub SOR_XBAR_POL: Mmio<u32>,                 // 0x12C
+    pub SOR_DP_LINKCTL0: Mmio<u32>,              // 0x130
+    pub SOR_DP_LINKCTL1: Mmio<u32>,              // 0x134
+    pub SOR_LANE_DRIVE_CURRENT0: Mmio<u32>,      // 0x138
+    pub SOR_LANE_DRIVE_CURRENT1: Mmio<u32>,      // 0x13C
+    pub SOR_LANE4_DRIVE_CURRENT0: Mmio<u32>,     // 0x140
+    pub SOR_LANE4_DRIVE_CURRENT1: Mmio<u32>,     // 0x144
+    pub SOR_LANE_PREEMPHASIS0: Mmio<u32>,        // 0x148
+    pub SOR_LANE_PREEMPHASIS1: Mmio<u32>,        // 0x14C
+    pub SOR_LANE4_PREEMPHASIS0: Mmio<u32>,       // 0x150
+    pub SOR_LANE4_PREEMPHASIS1: Mmio<u32>,       // 0x154
+    pub SOR_LANE_POSTCURSOR0: Mmio<u32>,         // 0x158
+    pub SOR_LANE_POSTCURSOR1: Mmio<u32>,         // 0x15C
+    pub SOR_DP_CONFIG0: Mmio<u32>,               // 0x160
+    pub SOR_DP_CONFIG1: Mmio<u32>,               // 0x164
+    pub SOR_DP_MN0: Mmio<u32>,                   // 0x168
+    pub SOR_DP_MN1: Mmio<u32>,                   // 0x16C
+    pub SOR_DP_PADCTL0: Mmio<u32>,               // 0x170
+    pub SOR_DP_PADCTL1: Mmio<u32>,               // 0x174
+    pub SOR_DP_PADCTL2: Mmio<u32>,               // 0x178
+    pub SOR_DP_DEBUG0: Mmio<u32>,                // 0x17C
+    pub SOR_DP_DEBUG1: Mmio<u32>,                // 0x180
+    pub SOR_DP_SPARE0: Mmio<u32>,                // 0x184
+    pub SOR_DP_SPARE1: Mmio<u32>,                // 0x188
+    pub SOR_DP_AUDIO_CTRL: Mmio<u32>,            // 0x18C
+    pub SOR_DP_AUDIO_HBLANK_SYMBOLS: Mmio<u32>,  // 0x190
+    pub SOR_DP_AUDIO_VBLANK_SYMBOLS: Mmio<u32>,  // 0x194
+    _reserved1: [Mmio<u8>; 0x1C],
+    pub SOR_DP_TPG: Mmio<u32>,                   // 0x1B4
+    _reserved2: [Mmio<u8>; 0x2C],
+    pub SOR_DP_HDCP_BKSV_MSB: Mmio<u32>,         // 0x1E4
+    pub SOR_DP_HDCP_BKSV_LSB: Mmio<u32>,         // 0x1E8
+    _reserved3: [Mmio<u8>; 0x14],
+    pub SOR_TMDS_HDCP_AN_MSB: Mmio<u32>,         // 0x200
+    pub SOR_TMDS_HDCP_AN_LSB: Mmio<u32>,         // 0x204
+    pub SOR_TMDS_HDCP_CN_MSB: Mmio<u32>,         // 0x208
+    pub SOR_TMDS_HDCP_CN_LSB: Mmio<u32>,         // 0x20C
+    pub SOR_TMDS_HDCP_AKSV_MSB: Mmio<u32>,       // 0x210
+    pub SOR_TMDS_HDCP_AKSV_LSB: Mmio<u32>,       // 0x214
+    pub SOR_TMDS_HDCP_BKSV_MSB: Mmio<u32>,       // 0x218
+    pub SOR_TMDS_HDCP_BKSV_LSB: Mmio<u32>,       // 0x21C
+}
+
+/// Representation of a Serial Output Resource.
+pub struct Sor {
+    /// The respective registers of the SOR.
+    registers: BlockMmio<Registers>,
+    /// The respective device clock of the SOR.
+    clock: &'static Clock,
+}
+
+// Definitions of known SOR instances.
+impl Sor {
+    /// Representation of SOR0.
+    pub const SOR0: Self = Sor {
+        registers: unsafe { BlockMmio::new(SOR0_BASE as usize) },
+        clock: &Clock::SOR0,
+    };
+
+    /// Representation of SOR1, which drives the DisplayPort output.
+    pub const SOR1: Self = Sor {
+        registers: unsafe { BlockMmio::new(SOR1_BASE as usize) },
+        clock: &Clock::SOR1,
+    };
+}
+
+impl Sor {
+    /// Gets the registers of the SOR.
+    pub fn registers(&self) -> &Registers {
+        &*self.registers
+    }
+
+    /// Gets the device clock of the SOR.
+    pub fn clock(&self) -> &'static Clock {
+        self.clock
+    }
+
+    /// The HDCP registers the TSEC firmware stores its key in, in key order.
+    fn hdcp_key_registers(&self) -> [&Mmio<u32>; 4] {
+        let registers = self.registers();
+
+        [
+            &registers.SOR_DP_HDCP_BKSV_LSB,
+            &registers.SOR_TMDS_HDCP_BKSV_LSB,
+            &registers.SOR_TMDS_HDCP_CN_MSB,
+            &registers.SOR_TMDS_HDCP_CN_LSB,
+        ]
+    }
+
+    /// Reads the key the TSEC firmware left in the HDCP registers.
+    pub fn hdcp_key(&self) -> [u32; 4] {
+        let mut key = [0; 4];
+
+        for (word, register) in key.iter_mut().zip(self.hdcp_key_registers().iter()) {
+            *word = register.read();
+        }
+
+        key
+    }
+
+    /// Clears the key from the HDCP registers.
+    pub fn clear_hdcp_key(&self) {
+        for register in self.hdcp_key_registers().iter() {
+            register.write(0);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// Tests that the HDCP key is fetched from the right registers.
+    #[test]
+    fn hdcp_key() {
+        let _session = mock::start();
+
+        mock::set(0x5458_01E8, 0x1111_1111);
+        mock::set(0x5458_021C, 0x2222_2222);
+        mock::set(0x5458_0208, 0x3333_3333);
+        mock::set(0x5458_020C, 0x4444_4444);
+
+        assert_eq!(
+            Sor::SOR1.hdcp_key(),
+            [0x1111_1111, 0x2222_2222, 0x3333_3333, 0x4444_4444]
+        );
+
+        Sor::SOR1.clear_hdcp_key();
+        for &address in [0x5458_01E8, 0x5458_021C, 0x5458_0208, 0x5458_020C].iter() {
+            assert_eq!(mock::get(address), 0);
+        }
+    }
+}
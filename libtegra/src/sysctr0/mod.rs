@@ -1,9 +1,48 @@
 //! SYSCTR0 control registers.
 //!
 //! Also referred to as PMC Counter 0 registers.
+//!
+//! # Description
+//!
+//! SYSCTR0 is the 64-bit system counter which drives the generic timers
+//! of the CPU cores. It counts at the CLK_M frequency, which gives it a
+//! considerably higher resolution than the microsecond timer.
+//!
+//! # Implementation
+//!
+//! - [`configure`] programs the counter frequency together with the other
+//! time bases derived from CLK_M, `TIMERUS_USEC_CFG` and the `TSC_MULT` value
+//! in the PMC, so that all of them stay coherent, and enables the counter.
+//!
+//! - [`counter`] reads the 64-bit counter value and [`frequency`] its rate
+//! in Hz. [`get_nanoseconds`] converts the counter value to nanoseconds.
+//!
+//! - [`delay_ns`] is a busy-waiting delay with nanosecond granularity.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::sysctr0;
+//!
+//! fn main() {
+//!     sysctr0::configure(19_200_000).unwrap();
+//!
+//!     let start = sysctr0::get_nanoseconds();
+//!     sysctr0::delay_ns(500);
+//!     println!("{}ns passed.", sysctr0::get_nanoseconds() - start);
+//! }
+//! ```
+//!
+//! [`configure`]: fn.configure.html
+//! [`counter`]: fn.counter.html
+//! [`frequency`]: fn.frequency.html
+//! [`get_nanoseconds`]: fn.get_nanoseconds.html
+//! [`delay_ns`]: fn.delay_ns.html
 
 use mirage_mmio::{Mmio, VolatileStorage};
 
+use crate::{pmc::Pmc, timer::TimerRegisters, Error, Result};
+
 /// Base address for SYSCTR0 registers.
 pub(crate) const SYSCTR0_BASE: u32 = 0x700F_0000;
 
@@ -56,3 +95,155 @@ impl VolatileStorage for Sysctr0Registers {
         SYSCTR0_BASE as *const _
     }
 }
+
+/// Enables the counter, in `SYSCTR0_CNTCR`.
+const CNTCR_EN: u32 = 1 << 0;
+/// Halts the counter while a core is in debug state, in `SYSCTR0_CNTCR`.
+const CNTCR_HDBG: u32 = 1 << 1;
+
+/// The number of nanoseconds per second.
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Gets the greatest common divisor of two numbers.
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+
+    a
+}
+
+/// Computes the `TIMERUS_USEC_CFG` value for a CLK_M frequency, which
+/// divides it down to 1MHz by a fraction of two 8-bit numbers.
+fn usec_cfg(clk_m: u32) -> Option<u32> {
+    let divisor = gcd(clk_m, 1_000_000);
+    let dividend = 1_000_000 / divisor;
+    let divisor = clk_m / divisor;
+
+    if dividend > 0x100 || divisor > 0x100 {
+        return None;
+    }
+
+    Some(((dividend - 1) << 8) | (divisor - 1))
+}
+
+/// Computes the `TSC_MULT` value for a CLK_M frequency, the amount of
+/// CLK_M cycles per 16 cycles of the 32.768kHz clock.
+fn tsc_mult(clk_m: u32) -> u32 {
+    (u64::from(clk_m) * 16 / 32_768) as u32
+}
+
+/// Configures the system counter, the microsecond timer and the PMC
+/// timestamp counter for the given CLK_M frequency in Hz, and enables
+/// the system counter.
+///
+/// Fails with `Error::InvalidArgument` if the microsecond timer can't be
+/// derived from the frequency.
+pub fn configure(clk_m: u32) -> Result<()> {
+    let sysctr0 = unsafe { Sysctr0Registers::get() };
+    let timer = unsafe { TimerRegisters::get() };
+    let pmc = unsafe { Pmc::get() };
+
+    if clk_m == 0 {
+        return Err(Error::InvalidArgument);
+    }
+    let usec_cfg = usec_cfg(clk_m).ok_or(Error::InvalidArgument)?;
+
+    sysctr0.CNTFID0.write(clk_m);
+    timer.TIMERUS_USEC_CFG.write(usec_cfg);
+    pmc.tsc_mult
+        .write((pmc.tsc_mult.read() & 0xFFFF_0000) | tsc_mult(clk_m));
+
+    sysctr0
+        .CNTCR
+        .write(sysctr0.CNTCR.read() | CNTCR_EN | CNTCR_HDBG);
+
+    Ok(())
+}
+
+/// Gets the frequency of the system counter in Hz.
+pub fn frequency() -> u32 {
+    let sysctr0 = unsafe { Sysctr0Registers::get() };
+
+    sysctr0.CNTFID0.read()
+}
+
+/// Reads the 64-bit value of the system counter.
+pub fn counter() -> u64 {
+    let sysctr0 = unsafe { Sysctr0Registers::get() };
+
+    // Re-read if the low half wrapped in between.
+    loop {
+        let high = sysctr0.CNTCV1.read();
+        let low = sysctr0.CNTCV0.read();
+
+        if sysctr0.CNTCV1.read() == high {
+            return (u64::from(high) << 32) | u64::from(low);
+        }
+    }
+}
+
+/// Converts an amount of counter ticks at the given frequency to nanoseconds.
+fn ticks_to_nanoseconds(ticks: u64, frequency: u32) -> u64 {
+    let frequency = u64::from(frequency);
+
+    (ticks / frequency) * NANOSECONDS_PER_SECOND
+        + (ticks % frequency) * NANOSECONDS_PER_SECOND / frequency
+}
+
+/// Converts nanoseconds to an amount of counter ticks at the given
+/// frequency, rounding up.
+fn nanoseconds_to_ticks(nanoseconds: u64, frequency: u32) -> u64 {
+    let frequency = u64::from(frequency);
+
+    (nanoseconds / NANOSECONDS_PER_SECOND) * frequency
+        + ((nanoseconds % NANOSECONDS_PER_SECOND) * frequency + NANOSECONDS_PER_SECOND - 1)
+            / NANOSECONDS_PER_SECOND
+}
+
+/// Returns the value of the system counter in nanoseconds.
+pub fn get_nanoseconds() -> u64 {
+    match frequency() {
+        0 => 0,
+        frequency => ticks_to_nanoseconds(counter(), frequency),
+    }
+}
+
+/// Sleeps for at least the given amount of nanoseconds.
+///
+/// The system counter has to be configured with [`configure`].
+///
+/// [`configure`]: fn.configure.html
+pub fn delay_ns(nanoseconds: u64) {
+    let ticks = nanoseconds_to_ticks(nanoseconds, frequency());
+    let start = counter();
+
+    while counter().wrapping_sub(start) < ticks {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the derived time base values for common CLK_M frequencies.
+    #[test]
+    fn time_bases() {
+        assert_eq!(usec_cfg(19_200_000), Some(0x45F));
+        assert_eq!(usec_cfg(38_400_000), Some(0x4BF));
+        assert_eq!(usec_cfg(12_000_000), Some(0x00B));
+        assert_eq!(usec_cfg(19_200_001), None);
+        assert_eq!(tsc_mult(19_200_000), 0x249F);
+
+        assert_eq!(
+            ticks_to_nanoseconds(19_200_000 * 3 + 96, 19_200_000),
+            3_000_005_000
+        );
+        assert_eq!(
+            nanoseconds_to_ticks(3_000_005_000, 19_200_000),
+            19_200_000 * 3 + 96
+        );
+        assert_eq!(nanoseconds_to_ticks(1, 19_200_000), 1);
+    }
+}
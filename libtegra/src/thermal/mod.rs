@@ -0,0 +1,156 @@
+//! Temperature readout through the TMP451 sensor.
+//!
+//! # Description
+//!
+//! The Switch carries a TI TMP451 remote temperature sensor on the I²C 1
+//! bus. Its remote channel measures the thermal diode of the SoC, while
+//! its local channel reports the temperature of the sensor itself, which
+//! sits on the PCB.
+//!
+//! Long-running flows such as cryptography or memory tests can use this
+//! to notice thermal runaway, e.g. on units with degraded thermal paste,
+//! and bail out before the hardware shuts down on its own.
+//!
+//! The SOC_THERM block of the SoC isn't used, as its sensors need fuse
+//! calibration data to report meaningful values.
+//!
+//! # Implementation
+//!
+//! - [`init`] brings up the I²C 1 controller and configures the sensor for
+//! continuous conversions in the default temperature range. All functions
+//! take the handle of that controller.
+//!
+//! - [`cpu_temp_celsius`] and [`pcb_temp_celsius`] read the temperature of
+//! the SoC and the PCB in whole degrees Celsius, while [`read`] gives
+//! millidegree precision for a [`Sensor`].
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{peripherals::Peripherals, thermal};
+//!
+//! fn main() {
+//!     let mut i2c = Peripherals::take().unwrap().i2c_1;
+//!
+//!     thermal::init(&mut i2c).unwrap();
+//!
+//!     if thermal::cpu_temp_celsius(&i2c).unwrap() > 85 {
+//!         panic!("The SoC is overheating!");
+//!     }
+//! }
+//! ```
+//!
+//! [`init`]: fn.init.html
+//! [`cpu_temp_celsius`]: fn.cpu_temp_celsius.html
+//! [`pcb_temp_celsius`]: fn.pcb_temp_celsius.html
+//! [`read`]: fn.read.html
+//! [`Sensor`]: enum.Sensor.html
+
+use crate::{
+    i2c::{Device, I2c},
+    Result,
+};
+
+/// The `TMP451_LOCAL_TEMP_HI` register.
+const LOCAL_TEMP_HI: u8 = 0x00;
+/// The `TMP451_REMOTE_TEMP_HI` register.
+const REMOTE_TEMP_HI: u8 = 0x01;
+/// The `TMP451_CONFIG_RD` register.
+const CONFIG_RD: u8 = 0x03;
+/// The `TMP451_CONFIG_WR` register.
+const CONFIG_WR: u8 = 0x09;
+/// The `TMP451_CONV_RATE_WR` register.
+const CONV_RATE_WR: u8 = 0x0A;
+/// The `TMP451_REMOTE_TEMP_LO` register.
+const REMOTE_TEMP_LO: u8 = 0x10;
+/// The `TMP451_LOCAL_TEMP_LO` register.
+const LOCAL_TEMP_LO: u8 = 0x15;
+
+/// Stops conversions in `CONFIG`.
+const CONFIG_SHUTDOWN: u8 = 1 << 6;
+/// Selects the extended temperature range in `CONFIG`.
+const CONFIG_RANGE_EXTENDED: u8 = 1 << 2;
+
+/// Converts eight times per second.
+const CONV_RATE_8HZ: u8 = 0x7;
+
+/// The offset of temperatures in the extended range, in degrees Celsius.
+const EXTENDED_RANGE_OFFSET: i32 = 64;
+
+/// The temperature sensors of the TMP451.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sensor {
+    /// The thermal diode of the SoC.
+    Cpu,
+    /// The sensor itself, located on the PCB.
+    Pcb,
+}
+
+impl Sensor {
+    /// Gets the registers holding the integer and the fractional part of
+    /// the temperature.
+    fn registers(self) -> (u8, u8) {
+        match self {
+            Sensor::Cpu => (REMOTE_TEMP_HI, REMOTE_TEMP_LO),
+            Sensor::Pcb => (LOCAL_TEMP_HI, LOCAL_TEMP_LO),
+        }
+    }
+}
+
+/// Converts a raw temperature reading into millidegrees Celsius.
+///
+/// The high byte holds the whole degrees, the upper nibble of the low byte
+/// holds the fraction in steps of 1/16 degree.
+fn decode(high: u8, low: u8, extended: bool) -> i32 {
+    let mut degrees = i32::from(high);
+    if extended {
+        degrees -= EXTENDED_RANGE_OFFSET;
+    }
+
+    degrees * 1000 + i32::from(low >> 4) * 1000 / 16
+}
+
+/// Initializes the sensor for continuous conversions.
+pub fn init(i2c: &mut I2c) -> Result<()> {
+    i2c.init();
+
+    let config = i2c.read_byte(Device::Tmp451, CONFIG_RD)?;
+    i2c.write_byte(Device::Tmp451, CONFIG_WR, config & !CONFIG_SHUTDOWN)?;
+    i2c.write_byte(Device::Tmp451, CONV_RATE_WR, CONV_RATE_8HZ)?;
+
+    Ok(())
+}
+
+/// Reads the temperature of a sensor in millidegrees Celsius.
+pub fn read(i2c: &I2c, sensor: Sensor) -> Result<i32> {
+    let (high_register, low_register) = sensor.registers();
+
+    let config = i2c.read_byte(Device::Tmp451, CONFIG_RD)?;
+    let high = i2c.read_byte(Device::Tmp451, high_register)?;
+    let low = i2c.read_byte(Device::Tmp451, low_register)?;
+
+    Ok(decode(high, low, config & CONFIG_RANGE_EXTENDED != 0))
+}
+
+/// Reads the temperature of the SoC in degrees Celsius.
+pub fn cpu_temp_celsius(i2c: &I2c) -> Result<i32> {
+    Ok(read(i2c, Sensor::Cpu)? / 1000)
+}
+
+/// Reads the temperature of the PCB in degrees Celsius.
+pub fn pcb_temp_celsius(i2c: &I2c) -> Result<i32> {
+    Ok(read(i2c, Sensor::Pcb)? / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_temperatures() {
+        assert_eq!(decode(45, 0x80, false), 45_500);
+        assert_eq!(decode(0, 0x10, false), 62);
+        assert_eq!(decode(64 + 30, 0x40, true), 30_250);
+        assert_eq!(decode(54, 0x00, true), -10_000);
+    }
+}
@@ -23,6 +23,22 @@
 //! - The functions [`sleep`], [`msleep`] and [`usleep`] are built on top of this
 //! to cause blocking delays.
 //!
+//! - [`get_microseconds_64`] extends the 32-bit microsecond counter, which wraps
+//! after roughly 71 minutes, to 64 bits by tracking wraparounds. It has to be
+//! called at least once per wrap period to stay accurate.
+//!
+//! - [`wait_for`] polls a condition until it holds or a timeout in
+//! microseconds has passed. Drivers use it for all busy-waits on the
+//! hardware, so a device that doesn't respond fails with
+//! [`Error::Timeout`] instead of hanging the system. The only exceptions
+//! are intentionally blocking reads of external input, i.e.
+//! `Uart::read_byte` and waiting for the next GDB packet.
+//!
+//! - [`Instant`] is a point in time based on [`get_microseconds_64`] that can be
+//! combined with [`Duration`]s. [`sleep_until`] and [`sleep_for`] use it for long
+//! delays which don't mis-time after a wrap and give [`yield_now`] callbacks a
+//! chance to run in the meantime.
+//!
 //! # Example
 //!
 //! ```
@@ -42,9 +58,24 @@
 //! [`sleep`]: fn.sleep.html
 //! [`msleep`]: fn.msleep.html
 //! [`usleep`]: fn.usleep.html
+//! [`get_microseconds_64`]: fn.get_microseconds_64.html
+//! [`wait_for`]: fn.wait_for.html
+//! [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+//! [`Instant`]: struct.Instant.html
+//! [`Duration`]: https://doc.rust-lang.org/nightly/core/time/struct.Duration.html
+//! [`sleep_until`]: fn.sleep_until.html
+//! [`sleep_for`]: fn.sleep_for.html
+//! [`yield_now`]: ../sched/fn.yield_now.html
+
+use core::{
+    ops::{Add, Sub},
+    time::Duration,
+};
 
 use mirage_mmio::{Mmio, VolatileStorage};
 
+use crate::{sched::yield_now, Error, Result};
+
 /// Base address for Timer registers.
 pub(crate) const TIMERS_BASE: u32 = 0x6000_5000;
 
@@ -141,7 +172,7 @@ pub fn get_microseconds() -> u32 {
 /// [`get_microseconds`]: fn.get_microseconds.html
 #[inline]
 pub fn get_time_since(base: u32) -> u32 {
-    get_microseconds() - base
+    get_microseconds().wrapping_sub(base)
 }
 
 /// Sleeps for a given duration in seconds.
@@ -167,3 +198,121 @@ pub fn usleep(duration: u32) {
 
     while (get_microseconds() - start) < duration {}
 }
+
+static mut MICROSECONDS_LAST: u32 = 0;
+
+static mut MICROSECONDS_HIGH: u64 = 0;
+
+/// Polls `condition` until it returns `true` or `timeout` microseconds
+/// have passed.
+///
+/// The condition is checked once more after the timeout, so a slow poll
+/// doesn't cause spurious failures. Fails with [`Error::Timeout`] if the
+/// condition never held. [`yield_now`] callbacks get a chance to run in
+/// between.
+///
+/// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+/// [`yield_now`]: ../sched/fn.yield_now.html
+pub fn wait_for<F: FnMut() -> bool>(mut condition: F, timeout: u32) -> Result<()> {
+    let start = get_microseconds();
+
+    loop {
+        let expired = get_time_since(start) >= timeout;
+
+        if condition() {
+            return Ok(());
+        }
+
+        if expired {
+            return Err(Error::Timeout);
+        }
+
+        yield_now();
+    }
+}
+
+/// Returns the current time in microseconds as a 64-bit value that doesn't wrap.
+pub fn get_microseconds_64() -> u64 {
+    let now = get_microseconds();
+
+    unsafe {
+        if now < MICROSECONDS_LAST {
+            MICROSECONDS_HIGH += 1 << 32;
+        }
+
+        MICROSECONDS_LAST = now;
+
+        MICROSECONDS_HIGH | u64::from(now)
+    }
+}
+
+/// A monotonic point in time with microsecond resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Gets the current point in time.
+    pub fn now() -> Self {
+        Instant(get_microseconds_64())
+    }
+
+    /// Gets the microseconds since the timer was started.
+    pub fn as_micros(&self) -> u64 {
+        self.0
+    }
+
+    /// Gets the time that has passed since this point in time.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now() - *self
+    }
+
+    /// Checks whether this point in time has been reached.
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= *self
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant(self.0.saturating_add(duration.as_micros() as u64))
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        Instant(self.0.saturating_sub(duration.as_micros() as u64))
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        Duration::from_micros(self.0.saturating_sub(earlier.0))
+    }
+}
+
+/// Sleeps until a given point in time has been reached.
+pub fn sleep_until(deadline: Instant) {
+    while !deadline.has_passed() {
+        yield_now();
+    }
+}
+
+/// Sleeps for a given duration.
+pub fn sleep_for(duration: Duration) {
+    sleep_until(Instant::now() + duration);
+}
+
+/// Makes the mocked microsecond counter advance on every read,
+/// so delays terminate in host-side tests.
+#[cfg(all(test, feature = "mock"))]
+pub(crate) fn mock_counter() {
+    let timer = unsafe { TimerRegisters::get() };
+
+    mirage_mmio::mock::auto_increment(&timer.TIMERUS_CNTR_1US as *const _ as usize, 1);
+}
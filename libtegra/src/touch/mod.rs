@@ -0,0 +1,324 @@
+//! Driver for the capacitive touch panel of the Switch.
+//!
+//! # Description
+//!
+//! The touch panel is driven by an STMicroelectronics FTM4 controller
+//! which sits on the I²C 3 bus. It reports contacts as 8-byte events
+//! which are queued in an event stack and read out one at a time.
+//!
+//! The controller is powered from the `ldo6` regulator of the PMIC,
+//! while its logic supply is switched through the GPIO J7.
+//!
+//! # Implementation
+//!
+//! - [`init`] powers up the controller, resets it and waits until it
+//! reports to be ready. The [`FirmwareInfo`] it returns has already been
+//! checked for a valid firmware.
+//!
+//! - [`poll`] reads pending events until it finds a contact, which is
+//! returned as a [`TouchPoint`], or the event stack is empty.
+//!
+//! - [`power_off`] stops sensing and cuts the supplies again, which
+//! should be done before handing over to a payload.
+//!
+//! - All of them take the I²C 3 controller, and those switching the
+//! analog supply also the I²C 5 controller the PMIC is attached to.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{peripherals::Peripherals, touch};
+//!
+//! fn main() {
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!
+//!     touch::init(&mut peripherals.i2c_3, &peripherals.i2c_5).unwrap();
+//!
+//!     loop {
+//!         if let Some(point) = touch::poll(&peripherals.i2c_3) {
+//!             if point.touching {
+//!                 println!("Touched at {}x{}", point.x, point.y);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`init`]: fn.init.html
+//! [`FirmwareInfo`]: struct.FirmwareInfo.html
+//! [`poll`]: fn.poll.html
+//! [`TouchPoint`]: struct.TouchPoint.html
+//! [`power_off`]: fn.power_off.html
+
+use crate::{
+    debug,
+    gpio::{Gpio, GpioConfig, GpioPin, GpioPort},
+    i2c::{Device, I2c},
+    pinmux::Pinmux,
+    power::max77620::Regulator,
+    timer::{get_time_since, get_microseconds, usleep},
+    Error, Result,
+};
+use mirage_mmio::VolatileStorage;
+
+/// The GPIO that switches the logic supply of the controller.
+const TOUCH_VDD_EN: Gpio = Gpio::new(GpioPort::J, GpioPin::P7);
+
+/// The voltage of the analog supply in `ldo6`, in µV.
+const TOUCH_AVDD_VOLTAGE: u32 = 2_900_000;
+
+/// Reads the chip and firmware identification.
+const FTM4_READ_INFO: u8 = 0x80;
+/// Pops the oldest event from the event stack.
+const FTM4_READ_ONE_EVENT: u8 = 0x85;
+/// Stops sensing touches.
+const FTM4_MS_MT_SENSE_OFF: u8 = 0x92;
+/// Starts sensing touches.
+const FTM4_MS_MT_SENSE_ON: u8 = 0x93;
+/// Resets the controller.
+const FTM4_SYSTEM_RESET: u8 = 0xA0;
+/// Discards all queued events.
+const FTM4_CLEAR_EVENT_STACK: u8 = 0xA1;
+
+/// The event stack is empty.
+const FTM4_EV_NO_EVENT: u8 = 0x00;
+/// A finger touched the panel.
+const FTM4_EV_MULTI_TOUCH_ENTER: u8 = 0x03;
+/// A finger moved on the panel.
+const FTM4_EV_MULTI_TOUCH_MOTION: u8 = 0x04;
+/// A finger left the panel.
+const FTM4_EV_MULTI_TOUCH_LEAVE: u8 = 0x05;
+/// The controller finished its reset.
+const FTM4_EV_CONTROLLER_READY: u8 = 0x10;
+
+/// The bits of the first event byte that identify the event.
+const FTM4_MASK_EVENT_ID: u8 = 0x0F;
+
+/// The size of an event in bytes.
+const FTM4_EVENT_SIZE: usize = 8;
+/// The depth of the event stack, which bounds a single poll.
+const FTM4_EVENT_STACK_DEPTH: usize = 32;
+
+/// How long the controller may take to come out of reset, in µs.
+const FTM4_READY_TIMEOUT: u32 = 1_000_000;
+
+/// Identification of the touch controller and its firmware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// The chip ID.
+    pub chip_id: u16,
+    /// The revision of the chip.
+    pub chip_version: u8,
+    /// The version of the firmware.
+    pub firmware_version: u16,
+    /// The ID of the loaded panel configuration.
+    pub config_id: u8,
+    /// The version of the loaded panel configuration.
+    pub config_version: u8,
+}
+
+impl FirmwareInfo {
+    fn from_bytes(info: &[u8; 8]) -> Self {
+        FirmwareInfo {
+            chip_id: u16::from_be_bytes([info[6], info[7]]),
+            chip_version: info[0],
+            firmware_version: u16::from_be_bytes([info[2], info[3]]),
+            config_id: info[4],
+            config_version: info[5],
+        }
+    }
+
+    /// Whether the controller runs a firmware.
+    ///
+    /// A controller without a firmware reports a version of all zeros
+    /// or all ones.
+    pub fn is_valid(&self) -> bool {
+        self.firmware_version != 0 && self.firmware_version != 0xFFFF
+    }
+}
+
+/// A contact reported by the touch controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TouchPoint {
+    /// The slot of the contact, which stays the same while a finger
+    /// moves over the panel.
+    pub id: u8,
+    /// The horizontal position, as reported by the controller.
+    pub x: u16,
+    /// The vertical position, as reported by the controller.
+    pub y: u16,
+    /// Whether the finger is on the panel, `false` when it was lifted.
+    pub touching: bool,
+}
+
+impl TouchPoint {
+    /// Decodes a contact event.
+    fn from_event(event: &[u8; FTM4_EVENT_SIZE]) -> Self {
+        TouchPoint {
+            id: event[0] >> 4,
+            x: u16::from(event[1]) | (u16::from(event[2] & 0xF) << 8),
+            y: u16::from(event[2] >> 4) | (u16::from(event[3]) << 4),
+            touching: event[0] & FTM4_MASK_EVENT_ID != FTM4_EV_MULTI_TOUCH_LEAVE,
+        }
+    }
+}
+
+/// Sends a command without parameters to the controller.
+fn command(i2c: &I2c, command: u8) -> Result<()> {
+    i2c.write(Device::Ftm4Touch, command, &[])?;
+
+    Ok(())
+}
+
+/// Pops the oldest event from the event stack.
+fn read_event(i2c: &I2c) -> Result<[u8; FTM4_EVENT_SIZE]> {
+    let mut event = [0; FTM4_EVENT_SIZE];
+    i2c.read(Device::Ftm4Touch, FTM4_READ_ONE_EVENT, &mut event)?;
+
+    Ok(event)
+}
+
+/// Waits until the controller reports to be ready after a reset.
+fn wait_for_ready(i2c: &I2c) -> Result<()> {
+    let start = get_microseconds();
+
+    while get_time_since(start) < FTM4_READY_TIMEOUT {
+        // The controller doesn't respond while it resets.
+        if let Ok(event) = read_event(i2c) {
+            if event[0] == FTM4_EV_CONTROLLER_READY {
+                return Ok(());
+            }
+        }
+
+        usleep(1000);
+    }
+
+    Err(Error::Timeout)
+}
+
+/// Reads the identification of the controller and its firmware.
+pub fn firmware_info(i2c: &I2c) -> Result<FirmwareInfo> {
+    let mut info = [0; 8];
+    i2c.read(Device::Ftm4Touch, FTM4_READ_INFO, &mut info)?;
+
+    Ok(FirmwareInfo::from_bytes(&info))
+}
+
+/// Powers up the touch controller on `i2c` and starts sensing touches.
+///
+/// The analog supply is switched through the PMIC on `pmic`.
+///
+/// Fails with [`Error::Timeout`] if the controller doesn't come out of
+/// reset and with [`Error::Touch`] if it doesn't run a valid firmware.
+///
+/// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+/// [`Error::Touch`]: ../enum.Error.html#variant.Touch
+pub fn init(i2c: &mut I2c, pmic: &I2c) -> Result<FirmwareInfo> {
+    // Enable the logic supply.
+    TOUCH_VDD_EN.config(GpioConfig::OutputHigh);
+
+    // Bring up I2C3.
+    unsafe { Pinmux::get() }.configure_i2c(i2c);
+    i2c.init();
+
+    // Enable the analog supply.
+    Regulator::LDO6.set_voltage(pmic, TOUCH_AVDD_VOLTAGE)?;
+    Regulator::LDO6.enable(pmic);
+    usleep(10_000);
+
+    command(i2c, FTM4_SYSTEM_RESET)?;
+    wait_for_ready(i2c)?;
+
+    let info = firmware_info(i2c)?;
+    debug!(
+        "Touch: chip {:#06X} rev {}, firmware {:#06X}, config {}.{}",
+        info.chip_id, info.chip_version, info.firmware_version, info.config_id, info.config_version
+    );
+
+    if !info.is_valid() {
+        return Err(Error::Touch);
+    }
+
+    command(i2c, FTM4_CLEAR_EVENT_STACK)?;
+    command(i2c, FTM4_MS_MT_SENSE_ON)?;
+
+    Ok(info)
+}
+
+/// Reads pending events and returns the first contact among them.
+///
+/// Returns `None` if no contact has been reported since the last poll
+/// or if the controller can't be reached.
+pub fn poll(i2c: &I2c) -> Option<TouchPoint> {
+    for _ in 0..FTM4_EVENT_STACK_DEPTH {
+        let event = match read_event(i2c) {
+            Ok(event) => event,
+            Err(error) => {
+                debug!("Touch: failed to read event: {}", error);
+                return None;
+            }
+        };
+
+        if event[0] == FTM4_EV_NO_EVENT {
+            return None;
+        }
+
+        match event[0] & FTM4_MASK_EVENT_ID {
+            FTM4_EV_MULTI_TOUCH_ENTER | FTM4_EV_MULTI_TOUCH_MOTION | FTM4_EV_MULTI_TOUCH_LEAVE => {
+                return Some(TouchPoint::from_event(&event));
+            }
+            // Status and debug events are of no interest.
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Stops sensing touches and powers down the touch controller on `i2c`.
+///
+/// The analog supply is switched off through the PMIC on `pmic`.
+pub fn power_off(i2c: &I2c, pmic: &I2c) -> Result<()> {
+    command(i2c, FTM4_MS_MT_SENSE_OFF)?;
+
+    Regulator::LDO6.disable(pmic);
+    TOUCH_VDD_EN.config(GpioConfig::OutputLow);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the decoding of contact events.
+    #[test]
+    fn decode_events() {
+        let enter = [0x23, 0x34, 0x52, 0x2C, 0, 0, 0, 0];
+        assert_eq!(
+            TouchPoint::from_event(&enter),
+            TouchPoint {
+                id: 2,
+                x: 0x234,
+                y: 0x2C5,
+                touching: true,
+            }
+        );
+
+        let leave = [0x25, 0x34, 0x52, 0x2C, 0, 0, 0, 0];
+        assert!(!TouchPoint::from_event(&leave).touching);
+    }
+
+    /// Tests the parsing of the firmware identification.
+    #[test]
+    fn parse_firmware_info() {
+        let info = FirmwareInfo::from_bytes(&[0x01, 0, 0x00, 0x5A, 0x03, 0x07, 0x36, 0x70]);
+
+        assert_eq!(info.chip_id, 0x3670);
+        assert_eq!(info.chip_version, 1);
+        assert_eq!(info.firmware_version, 0x5A);
+        assert!(info.is_valid());
+
+        assert!(!FirmwareInfo::from_bytes(&[0xFF; 8]).is_valid());
+    }
+}
@@ -12,32 +12,50 @@
 //!
 //! # Implementation
 //!
-//! - Important SOR1 registers are exposed as global constants
-//! within the crate.
+//! - Older firmware hands the key over through the HDCP registers
+//! of SOR1, which are accessed through [`Sor::SOR1`].
 //!
 //! - The [`Registers`] struct represents the TSEC registers
 //! that are mapped to address `0x54500000`.
 //!
-//! - The [`Tsec`] struct holds an instance of [`Registers`] and
-//! provides further hardware abstractions. It allows for loading
-//! and executing Falcon firmware and finally deriving the TSEC
-//! key.
+//! - The Falcon registers of the TSEC are left out of [`Registers`].
+//! They are driven through [`Falcon::TSEC`] instead, which is shared
+//! with the other Falcon-based engines.
+//!
+//! - The [`Tsec`] struct provides further hardware abstractions on
+//! top of [`Falcon::TSEC`]. It takes care of the TSEC clocks and
+//! allows for loading and executing Falcon firmware and finally
+//! deriving the TSEC key.
+//!
+//! - Firmware 6.2.0 and newer use a multi-stage firmware which writes
+//! the keys into SE keyslots directly instead of exposing them through
+//! SOR1. [`Tsec::derive_keys_into_se`] implements this flow, protecting
+//! the firmware with the TSEC carveout while it is running.
+//!
+//! - [`Tsec::read_dmem`], [`Tsec::write_dmem`] and [`Tsec::upload_imem`]
+//! forward to the respective [`Falcon`] methods and provide direct access to
+//! the Falcon memories, which is useful for custom Falcon payloads.
+//!
+//! - TSEC operations may fail for various reasons. All of them return
+//! a [`TsecError`] on failure which describes the cause, including the
+//! raw mailbox value if the firmware reported an unexpected result.
+//! Errors of the underlying [`Falcon`] are converted accordingly.
 //!
 //! # Example
 //!
 //! ```
-//! use mirage_libtegra::tsec::Tsec;
+//! use mirage_libtegra::{firmware::AlignedBlob, include_firmware, tsec::Tsec};
 //!
 //! // Global instance of the TSEC.
 //! const TSEC: Tsec = Tsec::new();
 //!
-//! // Include the TSEC firmware blob stored in another source file.
-//! include!("falcon_fw.rs");
+//! // Embed the TSEC firmware blob, aligned for DMA.
+//! static FALCON_FIRMWARE: &AlignedBlob = include_firmware!("tsec_fw.bin");
 //!
 //! fn main() {
 //!     // Load and execute the firmware.
-//!     TSEC.load_firmware(FALCON_FIRMWARE);
-//!     TSEC.execute_firmware(None);
+//!     TSEC.load_firmware(FALCON_FIRMWARE).unwrap();
+//!     TSEC.execute_firmware(None).unwrap();
 //!
 //!     // Derive the TSEC key.
 //!     let key = TSEC.get_key(1, FALCON_FIRMWARE).unwrap();
@@ -47,289 +65,191 @@
 //! [`Registers`]: struct.Registers.html
 //! [`Registers::get`]: struct.Registers.html#method.get
 //! [`Tsec`]: struct.Tsec.html
-
-use mirage_mmio::{Mmio, VolatileStorage};
-
-use crate::{clock::Clock, timer::get_milliseconds};
+//! [`Sor::SOR1`]: ../sor/struct.Sor.html#associatedconstant.SOR1
+//! [`Falcon`]: ../falcon/struct.Falcon.html
+//! [`Falcon::TSEC`]: ../falcon/struct.Falcon.html#associatedconstant.TSEC
+//! [`TsecError`]: enum.TsecError.html
+//! [`Tsec::derive_keys_into_se`]: struct.Tsec.html#method.derive_keys_into_se
+//! [`Tsec::read_dmem`]: struct.Tsec.html#method.read_dmem
+//! [`Tsec::write_dmem`]: struct.Tsec.html#method.write_dmem
+//! [`Tsec::upload_imem`]: struct.Tsec.html#method.upload_imem
+
+use mirage_mmio::{register_block, Mmio, VolatileStorage};
+
+use crate::{
+    arch,
+    clock::Clock,
+    error,
+    falcon::{Error as FalconError, Falcon},
+    host1x::{Syncpoint, TSEC_SYNCPT_MAGIC},
+    mc::config_tsec_carveout,
+    se::SecurityEngine,
+    sor::Sor,
+};
 
 /// Base address for the TSEC registers.
 pub(crate) const TSEC_BASE: u32 = 0x5450_0000;
 
-/// Base address for SOR1 registers.
-pub(crate) const SOR1_BASE: u32 = 0x5458_0000;
-
-/// Base address for HOST1X registers.
-pub(crate) const HOST1X_BASE: u32 = 0x5000_0000;
-
-/// Representation of the TSEC registers.
-#[repr(C)]
-pub struct Registers {
-    pub tsec_thi_incr_syncpt: Mmio<u32>,       // 0x0
-    pub tsec_thi_incr_syncpt_ctrl: Mmio<u32>,  // 0x4
-    pub tsec_thi_incr_syncpt_err: Mmio<u32>,   // 0x8
-    pub tsec_thi_ctxsw_incr_syncpt: Mmio<u32>, // 0xc
-    _reserved4: [Mmio<u8>; 0x10],
-    pub tsec_thi_ctxsw: Mmio<u32>,           // 0x20
-    pub tsec_thi_ctxsw_next: Mmio<u32>,      // 0x24
-    pub tsec_thi_cont_syncpt_eof: Mmio<u32>, // 0x28
-    pub tsec_thi_cont_syncpt_l1: Mmio<u32>,  // 0x2c
-    pub tsec_thi_streamid0: Mmio<u32>,       // 0x30
-    pub tsec_thi_streamid1: Mmio<u32>,       // 0x34
-    pub tsec_thi_thi_sec: Mmio<u32>,         // 0x38
-    _reserved11: [Mmio<u8>; 0x4],
-    pub tsec_thi_method0: Mmio<u32>, // 0x40
-    pub tsec_thi_method1: Mmio<u32>, // 0x44
-    _reserved13: [Mmio<u8>; 0x18],
-    pub tsec_thi_context_switch: Mmio<u32>, // 0x60
-    _reserved14: [Mmio<u8>; 0x14],
-    pub tsec_thi_int_status: Mmio<u32>,           // 0x78
-    pub tsec_thi_int_mask: Mmio<u32>,             // 0x7c
-    pub tsec_thi_config0: Mmio<u32>,              // 0x80
-    pub tsec_thi_dbg_misc: Mmio<u32>,             // 0x84
-    pub tsec_thi_slcg_override_high_a: Mmio<u32>, // 0x88
-    pub tsec_thi_slcg_override_low_a: Mmio<u32>,  // 0x8c
-    _reserved20: [Mmio<u8>; 0xD70],
-    pub tsec_thi_clk_override: Mmio<u32>, // 0xe00
-    _reserved21: [Mmio<u8>; 0x1FC],
-    pub falcon_irqsset: Mmio<u32>,   // 0x1000
-    pub falcon_irqsclr: Mmio<u32>,   // 0x1004
-    pub falcon_irqstat: Mmio<u32>,   // 0x1008
-    pub falcon_irqmode: Mmio<u32>,   // 0x100c
-    pub falcon_irqmset: Mmio<u32>,   // 0x1010
-    pub falcon_irqmclr: Mmio<u32>,   // 0x1014
-    pub falcon_irqmask: Mmio<u32>,   // 0x1018
-    pub falcon_irqdest: Mmio<u32>,   // 0x101c
-    pub falcon_gptmrint: Mmio<u32>,  // 0x1020
-    pub falcon_gptmrval: Mmio<u32>,  // 0x1024
-    pub falcon_gptmrctl: Mmio<u32>,  // 0x1028
-    pub falcon_ptimer0: Mmio<u32>,   // 0x102c
-    pub falcon_ptimer1: Mmio<u32>,   // 0x1030
-    pub falcon_wdtmrval: Mmio<u32>,  // 0x1034
-    pub falcon_wdtmrctl: Mmio<u32>,  // 0x1038
-    pub falcon_irqdest2: Mmio<u32>,  // 0x103c
-    pub falcon_mailbox0: Mmio<u32>,  // 0x1040
-    pub falcon_mailbox1: Mmio<u32>,  // 0x1044
-    pub falcon_itfen: Mmio<u32>,     // 0x1048
-    pub falcon_idlestate: Mmio<u32>, // 0x104c
-    pub falcon_curctx: Mmio<u32>,    // 0x1050
-    pub falcon_nxtctx: Mmio<u32>,    // 0x1054
-    pub falcon_ctxack: Mmio<u32>,    // 0x1058
-    pub falcon_fhstate: Mmio<u32>,   // 0x105c
-    pub falcon_privstate: Mmio<u32>, // 0x1060
-    pub falcon_mthddata: Mmio<u32>,  // 0x1064
-    pub falcon_mthdid: Mmio<u32>,    // 0x1068
-    pub falcon_mthdwdat: Mmio<u32>,  // 0x106c
-    pub falcon_mthdcount: Mmio<u32>, // 0x1070
-    pub falcon_mthdpop: Mmio<u32>,   // 0x1074
-    pub falcon_mthdramsz: Mmio<u32>, // 0x1078
-    pub falcon_sftreset: Mmio<u32>,  // 0x107c
-    pub falcon_os: Mmio<u32>,        // 0x1080
-    pub falcon_rm: Mmio<u32>,        // 0x1084
-    pub falcon_soft_pm: Mmio<u32>,   // 0x1088
-    pub falcon_soft_mode: Mmio<u32>, // 0x108c
-    pub falcon_debug1: Mmio<u32>,    // 0x1090
-    pub falcon_debuginfo: Mmio<u32>, // 0x1094
-    pub falcon_ibrkpt1: Mmio<u32>,   // 0x1098
-    pub falcon_ibrkpt2: Mmio<u32>,   // 0x109c
-    pub falcon_cgctl: Mmio<u32>,     // 0x10a0
-    pub falcon_engctl: Mmio<u32>,    // 0x10a4
-    pub falcon_pmm: Mmio<u32>,       // 0x10a8
-    pub falcon_addr: Mmio<u32>,      // 0x10ac
-    pub falcon_ibrkpt3: Mmio<u32>,   // 0x10b0
-    pub falcon_ibrkpt4: Mmio<u32>,   // 0x10b4
-    pub falcon_ibrkpt5: Mmio<u32>,   // 0x10b8
-    _reserved68: [Mmio<u8>; 0x14],
-    pub falcon_exci: Mmio<u32>,     // 0x10d0
-    pub falcon_svec_spr: Mmio<u32>, // 0x10d4
-    pub falcon_rstat0: Mmio<u32>,   // 0x10d8
-    pub falcon_rstat3: Mmio<u32>,   // 0x10dc
-    pub falcon_unk_e0: Mmio<u32>,   // 0x10e0
-    _reserved73: [Mmio<u8>; 0x1C],
-    pub falcon_cpuctl: Mmio<u32>,       // 0x1100
-    pub falcon_bootvec: Mmio<u32>,      // 0x1104
-    pub falcon_hwcfg: Mmio<u32>,        // 0x1108
-    pub falcon_dmactl: Mmio<u32>,       // 0x110c
-    pub falcon_dmatrfbase: Mmio<u32>,   // 0x1110
-    pub falcon_dmatrfmoffs: Mmio<u32>,  // 0x1114
-    pub falcon_dmatrfcmd: Mmio<u32>,    // 0x1118
-    pub falcon_dmatrffboffs: Mmio<u32>, // 0x111c
-    pub falcon_dmapoll_fb: Mmio<u32>,   // 0x1120
-    pub falcon_dmapoll_cp: Mmio<u32>,   // 0x1124
-    pub falcon_dbg_state: Mmio<u32>,    // 0x1128
-    pub falcon_hwcfg1: Mmio<u32>,       // 0x112c
-    pub falcon_cpuctl_alias: Mmio<u32>, // 0x1130
-    _reserved86: [Mmio<u8>; 0x4],
-    pub falcon_stackcfg: Mmio<u32>, // 0x1138
-    _reserved87: [Mmio<u8>; 0x4],
-    pub falcon_imctl: Mmio<u32>,       // 0x1140
-    pub falcon_imstat: Mmio<u32>,      // 0x1144
-    pub falcon_traceidx: Mmio<u32>,    // 0x1148
-    pub falcon_tracepc: Mmio<u32>,     // 0x114c
-    pub falcon_imfillrng0: Mmio<u32>,  // 0x1150
-    pub falcon_imfillrng1: Mmio<u32>,  // 0x1154
-    pub falcon_imfillctl: Mmio<u32>,   // 0x1158
-    pub falcon_imctl_debug: Mmio<u32>, // 0x115c
-    pub falcon_cmembase: Mmio<u32>,    // 0x1160
-    pub falcon_dmemapert: Mmio<u32>,   // 0x1164
-    pub falcon_exterraddr: Mmio<u32>,  // 0x1168
-    pub falcon_exterrstat: Mmio<u32>,  // 0x116c
-    _reserved99: [Mmio<u8>; 0xC],
-    pub falcon_cg2: Mmio<u32>,    // 0x117c
-    pub falcon_imemc0: Mmio<u32>, // 0x1180
-    pub falcon_imemd0: Mmio<u32>, // 0x1184
-    pub falcon_imemt0: Mmio<u32>, // 0x1188
-    _reserved103: [Mmio<u8>; 0x4],
-    pub falcon_imemc1: Mmio<u32>, // 0x1190
-    pub falcon_imemd1: Mmio<u32>, // 0x1194
-    pub falcon_imemt1: Mmio<u32>, // 0x1198
-    _reserved106: [Mmio<u8>; 0x4],
-    pub falcon_imemc2: Mmio<u32>, // 0x11a0
-    pub falcon_imemd2: Mmio<u32>, // 0x11a4
-    pub falcon_imemt2: Mmio<u32>, // 0x11a8
-    _reserved109: [Mmio<u8>; 0x4],
-    pub falcon_imemc3: Mmio<u32>, // 0x11b0
-    pub falcon_imemd3: Mmio<u32>, // 0x11b4
-    pub falcon_imemt3: Mmio<u32>, // 0x11b8
-    _reserved112: [Mmio<u8>; 0x4],
-    pub falcon_dmemc0: Mmio<u32>,    // 0x11c0
-    pub falcon_dmemd0: Mmio<u32>,    // 0x11c4
-    pub falcon_dmemc1: Mmio<u32>,    // 0x11c8
-    pub falcon_dmemd1: Mmio<u32>,    // 0x11cc
-    pub falcon_dmemc2: Mmio<u32>,    // 0x11d0
-    pub falcon_dmemd2: Mmio<u32>,    // 0x11d4
-    pub falcon_dmemc3: Mmio<u32>,    // 0x11d8
-    pub falcon_dmemd3: Mmio<u32>,    // 0x11dc
-    pub falcon_dmemc4: Mmio<u32>,    // 0x11e0
-    pub falcon_dmemd4: Mmio<u32>,    // 0x11e4
-    pub falcon_dmemc5: Mmio<u32>,    // 0x11e8
-    pub falcon_dmemd5: Mmio<u32>,    // 0x11ec
-    pub falcon_dmemc6: Mmio<u32>,    // 0x11f0
-    pub falcon_dmemd6: Mmio<u32>,    // 0x11f4
-    pub falcon_dmemc7: Mmio<u32>,    // 0x11f8
-    pub falcon_dmemd7: Mmio<u32>,    // 0x11fc
-    pub falcon_icd_cmd: Mmio<u32>,   // 0x1200
-    pub falcon_icd_addr: Mmio<u32>,  // 0x1204
-    pub falcon_icd_wdata: Mmio<u32>, // 0x1208
-    pub falcon_icd_rdata: Mmio<u32>, // 0x120c
-    _reserved132: [Mmio<u8>; 0x30],
-    pub falcon_sctl: Mmio<u32>,    // 0x1240
-    pub falcon_sstat: Mmio<u32>,   // 0x1244
-    pub falcon_unk_248: Mmio<u32>, // 0x1248
-    pub falcon_unk_24c: Mmio<u32>, // 0x124c
-    pub falcon_unk_250: Mmio<u32>, // 0x1250
-    _reserved137: [Mmio<u8>; 0xC],
-    pub falcon_unk_260: Mmio<u32>, // 0x1260
-    _reserved138: [Mmio<u8>; 0x1C],
-    pub falcon_sprot_imem: Mmio<u32>,   // 0x1280
-    pub falcon_sprot_dmem: Mmio<u32>,   // 0x1284
-    pub falcon_sprot_cpuctl: Mmio<u32>, // 0x1288
-    pub falcon_sprot_misc: Mmio<u32>,   // 0x128c
-    pub falcon_sprot_irq: Mmio<u32>,    // 0x1290
-    pub falcon_sprot_mthd: Mmio<u32>,   // 0x1294
-    pub falcon_sprot_sctl: Mmio<u32>,   // 0x1298
-    pub falcon_sprot_wdtmr: Mmio<u32>,  // 0x129c
-    _reserved146: [Mmio<u8>; 0x20],
-    pub falcon_dmainfo_finished_fbrd_low: Mmio<u32>, // 0x12c0
-    pub falcon_dmainfo_finished_fbrd_high: Mmio<u32>, // 0x12c4
-    pub falcon_dmainfo_finished_fbwr_low: Mmio<u32>, // 0x12c8
-    pub falcon_dmainfo_finished_fbwr_high: Mmio<u32>, // 0x12cc
-    pub falcon_dmainfo_current_fbrd_low: Mmio<u32>,  // 0x12d0
-    pub falcon_dmainfo_current_fbrd_high: Mmio<u32>, // 0x12d4
-    pub falcon_dmainfo_current_fbwr_low: Mmio<u32>,  // 0x12d8
-    pub falcon_dmainfo_current_fbwr_high: Mmio<u32>, // 0x12dc
-    pub falcon_dmainfo_ctl: Mmio<u32>,               // 0x12e0
-    _reserved155: [Mmio<u8>; 0x11C],
-    pub tsec_scp_ctl0: Mmio<u32>,     // 0x1400
-    pub tsec_scp_ctl1: Mmio<u32>,     // 0x1404
-    pub tsec_scp_ctl_stat: Mmio<u32>, // 0x1408
-    pub tsec_scp_ctl_lock: Mmio<u32>, // 0x140c
-    pub tsec_scp_unk_10: Mmio<u32>,   // 0x1410
-    pub tsec_scp_unk_14: Mmio<u32>,   // 0x1414
-    pub tsec_scp_ctl_pkey: Mmio<u32>, // 0x1418
-    pub tsec_scp_unk_1c: Mmio<u32>,   // 0x141c
-    pub tsec_scp_seq_ctl: Mmio<u32>,  // 0x1420
-    pub tsec_scp_seq_val: Mmio<u32>,  // 0x1424
-    pub tsec_scp_seq_stat: Mmio<u32>, // 0x1428
-    _reserved166: [Mmio<u8>; 0x4],
-    pub tsec_scp_insn_stat: Mmio<u32>, // 0x1430
-    _reserved167: [Mmio<u8>; 0x1c],
-    pub tsec_scp_unk_50: Mmio<u32>,    // 0x1450
-    pub tsec_scp_auth_stat: Mmio<u32>, // 0x1454
-    pub tsec_scp_aes_stat: Mmio<u32>,  // 0x1458
-    _reserved170: [Mmio<u8>; 0x14],
-    pub tsec_scp_unk_70: Mmio<u32>, // 0x1470
-    _reserved171: [Mmio<u8>; 0xC],
-    pub tsec_scp_irqstat: Mmio<u32>, // 0x1480
-    pub tsec_scp_irqmask: Mmio<u32>, // 0x1484
-    _reserved173: [Mmio<u8>; 0x8],
-    pub tsec_scp_acl_err: Mmio<u32>,  // 0x1490
-    pub tsec_scp_unk_94: Mmio<u32>,   // 0x1494
-    pub tsec_scp_insn_err: Mmio<u32>, // 0x1498
-    _reserved176: [Mmio<u8>; 0x64],
-    pub tsec_trng_clk_limit_low: Mmio<u32>,  // 0x1500
-    pub tsec_trng_clk_limit_high: Mmio<u32>, // 0x1504
-    pub tsec_trng_unk_08: Mmio<u32>,         // 0x1508
-    pub tsec_trng_test_ctl: Mmio<u32>,       // 0x150c
-    pub tsec_trng_test_cfg0: Mmio<u32>,      // 0x1510
-    pub tsec_trng_test_seed0: Mmio<u32>,     // 0x1514
-    pub tsec_trng_test_cfg1: Mmio<u32>,      // 0x1518
-    pub tsec_trng_test_seed1: Mmio<u32>,     // 0x151c
-    pub tsec_trng_unk_20: Mmio<u32>,         // 0x1520
-    pub tsec_trng_unk_24: Mmio<u32>,         // 0x1524
-    pub tsec_trng_unk_28: Mmio<u32>,         // 0x1528
-    pub tsec_trng_ctl: Mmio<u32>,            // 0x152c
-    _reserved188: [Mmio<u8>; 0xD0],
-    pub tsec_tfbif_ctl: Mmio<u32>,             // 0x1600
-    pub tsec_tfbif_mccif_fifoctrl: Mmio<u32>,  // 0x1604
-    pub tsec_tfbif_throttle: Mmio<u32>,        // 0x1608
-    pub tsec_tfbif_dbg_stat0: Mmio<u32>,       // 0x160c
-    pub tsec_tfbif_dbg_stat1: Mmio<u32>,       // 0x1610
-    pub tsec_tfbif_dbg_rdcount_lo: Mmio<u32>,  // 0x1614
-    pub tsec_tfbif_dbg_rdcount_hi: Mmio<u32>,  // 0x1618
-    pub tsec_tfbif_dbg_wrcount_lo: Mmio<u32>,  // 0x161c
-    pub tsec_tfbif_dbg_wrcount_hi: Mmio<u32>,  // 0x1620
-    pub tsec_tfbif_dbg_r32count: Mmio<u32>,    // 0x1624
-    pub tsec_tfbif_dbg_r64count: Mmio<u32>,    // 0x1628
-    pub tsec_tfbif_dbg_r128count: Mmio<u32>,   // 0x162c
-    pub tsec_tfbif_unk_30: Mmio<u32>,          // 0x1630
-    pub tsec_tfbif_mccif_fifoctrl1: Mmio<u32>, // 0x1634
-    pub tsec_tfbif_wrr_rdp: Mmio<u32>,         // 0x1638
-    _reserved203: [Mmio<u8>; 0x4],
-    pub tsec_tfbif_sprot_emem: Mmio<u32>,           // 0x1640
-    pub tsec_tfbif_transcfg: Mmio<u32>,             // 0x1644
-    pub tsec_tfbif_regioncfg: Mmio<u32>,            // 0x1648
-    pub tsec_tfbif_actmon_active_mask: Mmio<u32>,   // 0x164c
-    pub tsec_tfbif_actmon_active_borps: Mmio<u32>,  // 0x1650
-    pub tsec_tfbif_actmon_active_weight: Mmio<u32>, // 0x1654
-    _reserved209: [Mmio<u8>; 0x8],
-    pub tsec_tfbif_actmon_mcb_mask: Mmio<u32>,   // 0x1660
-    pub tsec_tfbif_actmon_mcb_borps: Mmio<u32>,  // 0x1664
-    pub tsec_tfbif_actmon_mcb_weight: Mmio<u32>, // 0x1668
-    _reserved212: [Mmio<u8>; 0x4],
-    pub tsec_tfbif_thi_transprop: Mmio<u32>, // 0x1670
-    _reserved213: [Mmio<u8>; 0x5C],
-    pub tsec_cg: Mmio<u32>, // 0x16d0
-    _reserved214: [Mmio<u8>; 0x2C],
-    pub tsec_bar0_ctl: Mmio<u32>,     // 0x1700
-    pub tsec_bar0_addr: Mmio<u32>,    // 0x1704
-    pub tsec_bar0_data: Mmio<u32>,    // 0x1708
-    pub tsec_bar0_timeout: Mmio<u32>, // 0x170c
-    _reserved218: [Mmio<u8>; 0xF0],
-    pub tsec_tegra_falcon_ip_ver: Mmio<u32>, // 0x1800
-    pub tsec_tegra_unk_04: Mmio<u32>,        // 0x1804
-    pub tsec_tegra_unk_08: Mmio<u32>,        // 0x1808
-    pub tsec_tegra_unk_0c: Mmio<u32>,        // 0x180c
-    pub tsec_tegra_unk_10: Mmio<u32>,        // 0x1810
-    pub tsec_tegra_unk_14: Mmio<u32>,        // 0x1814
-    pub tsec_tegra_unk_18: Mmio<u32>,        // 0x1818
-    pub tsec_tegra_unk_1c: Mmio<u32>,        // 0x181c
-    pub tsec_tegra_unk_20: Mmio<u32>,        // 0x1820
-    pub tsec_tegra_unk_24: Mmio<u32>,        // 0x1824
-    pub tsec_tegra_unk_28: Mmio<u32>,        // 0x1828
-    pub tsec_tegra_unk_2c: Mmio<u32>,        // 0x182c
-    pub tsec_tegra_unk_30: Mmio<u32>,        // 0x1830
-    pub tsec_tegra_unk_34: Mmio<u32>,        // 0x1834
-    pub tsec_tegra_ctl: Mmio<u32>,           // 0x1838
+/// The magic value the TSEC firmware reports in `FALCON_MAILBOX1` on success.
+const TSEC_MAILBOX_MAGIC: u32 = 0xB0B0_B0B0;
+
+/// The SE keyslot the 6.2.0+ firmware writes the TSEC root key into.
+pub const TSEC_ROOT_KEYSLOT: usize = 0xD;
+
+/// The granularity of the TSEC carveout.
+const TSEC_CARVEOUT_ALIGNMENT: usize = 0x10_0000;
+
+/// Enumeration of possible errors when operating the TSEC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TsecError {
+    /// The Falcon DMA engine didn't become idle in time.
+    DmaTimeout,
+    /// The firmware didn't report back through the mailbox in time.
+    BootTimeout,
+    /// The firmware reported an unexpected value through the mailbox.
+    BadMailboxMagic(u32),
+    /// The firmware doesn't fit into the Falcon IMEM.
+    FirmwareTooLarge,
+    /// The firmware isn't aligned to a 256 byte boundary
+    /// or located outside the 32-bit address space.
+    AlignmentError,
+}
+
+impl From<FalconError> for TsecError {
+    fn from(error: FalconError) -> Self {
+        match error {
+            FalconError::DmaTimeout => TsecError::DmaTimeout,
+            FalconError::MailboxTimeout => TsecError::BootTimeout,
+            FalconError::OutOfBounds => TsecError::FirmwareTooLarge,
+            FalconError::AlignmentError => TsecError::AlignmentError,
+        }
+    }
+}
+
+register_block! {
+    /// Representation of the TSEC registers.
+    pub struct Registers {
+        (0x0000 => pub tsec_thi_incr_syncpt: Mmio<u32>),
+        (0x0004 => pub tsec_thi_incr_syncpt_ctrl: Mmio<u32>),
+        (0x0008 => pub tsec_thi_incr_syncpt_err: Mmio<u32>),
+        (0x000C => pub tsec_thi_ctxsw_incr_syncpt: Mmio<u32>),
+        (0x0010 => _reserved4),
+        (0x0020 => pub tsec_thi_ctxsw: Mmio<u32>),
+        (0x0024 => pub tsec_thi_ctxsw_next: Mmio<u32>),
+        (0x0028 => pub tsec_thi_cont_syncpt_eof: Mmio<u32>),
+        (0x002C => pub tsec_thi_cont_syncpt_l1: Mmio<u32>),
+        (0x0030 => pub tsec_thi_streamid0: Mmio<u32>),
+        (0x0034 => pub tsec_thi_streamid1: Mmio<u32>),
+        (0x0038 => pub tsec_thi_thi_sec: Mmio<u32>),
+        (0x003C => _reserved11),
+        (0x0040 => pub tsec_thi_method0: Mmio<u32>),
+        (0x0044 => pub tsec_thi_method1: Mmio<u32>),
+        (0x0048 => _reserved13),
+        (0x0060 => pub tsec_thi_context_switch: Mmio<u32>),
+        (0x0064 => _reserved14),
+        (0x0078 => pub tsec_thi_int_status: Mmio<u32>),
+        (0x007C => pub tsec_thi_int_mask: Mmio<u32>),
+        (0x0080 => pub tsec_thi_config0: Mmio<u32>),
+        (0x0084 => pub tsec_thi_dbg_misc: Mmio<u32>),
+        (0x0088 => pub tsec_thi_slcg_override_high_a: Mmio<u32>),
+        (0x008C => pub tsec_thi_slcg_override_low_a: Mmio<u32>),
+        (0x0090 => _reserved20),
+        (0x0E00 => pub tsec_thi_clk_override: Mmio<u32>),
+        (0x0E04 => _reserved21),
+        (0x1000 => _falcon), // See falcon::Registers.
+        (0x1400 => pub tsec_scp_ctl0: Mmio<u32>),
+        (0x1404 => pub tsec_scp_ctl1: Mmio<u32>),
+        (0x1408 => pub tsec_scp_ctl_stat: Mmio<u32>),
+        (0x140C => pub tsec_scp_ctl_lock: Mmio<u32>),
+        (0x1410 => pub tsec_scp_unk_10: Mmio<u32>),
+        (0x1414 => pub tsec_scp_unk_14: Mmio<u32>),
+        (0x1418 => pub tsec_scp_ctl_pkey: Mmio<u32>),
+        (0x141C => pub tsec_scp_unk_1c: Mmio<u32>),
+        (0x1420 => pub tsec_scp_seq_ctl: Mmio<u32>),
+        (0x1424 => pub tsec_scp_seq_val: Mmio<u32>),
+        (0x1428 => pub tsec_scp_seq_stat: Mmio<u32>),
+        (0x142C => _reserved166),
+        (0x1430 => pub tsec_scp_insn_stat: Mmio<u32>),
+        (0x1434 => _reserved167),
+        (0x1450 => pub tsec_scp_unk_50: Mmio<u32>),
+        (0x1454 => pub tsec_scp_auth_stat: Mmio<u32>),
+        (0x1458 => pub tsec_scp_aes_stat: Mmio<u32>),
+        (0x145C => _reserved170),
+        (0x1470 => pub tsec_scp_unk_70: Mmio<u32>),
+        (0x1474 => _reserved171),
+        (0x1480 => pub tsec_scp_irqstat: Mmio<u32>),
+        (0x1484 => pub tsec_scp_irqmask: Mmio<u32>),
+        (0x1488 => _reserved173),
+        (0x1490 => pub tsec_scp_acl_err: Mmio<u32>),
+        (0x1494 => pub tsec_scp_unk_94: Mmio<u32>),
+        (0x1498 => pub tsec_scp_insn_err: Mmio<u32>),
+        (0x149C => _reserved176),
+        (0x1500 => pub tsec_trng_clk_limit_low: Mmio<u32>),
+        (0x1504 => pub tsec_trng_clk_limit_high: Mmio<u32>),
+        (0x1508 => pub tsec_trng_unk_08: Mmio<u32>),
+        (0x150C => pub tsec_trng_test_ctl: Mmio<u32>),
+        (0x1510 => pub tsec_trng_test_cfg0: Mmio<u32>),
+        (0x1514 => pub tsec_trng_test_seed0: Mmio<u32>),
+        (0x1518 => pub tsec_trng_test_cfg1: Mmio<u32>),
+        (0x151C => pub tsec_trng_test_seed1: Mmio<u32>),
+        (0x1520 => pub tsec_trng_unk_20: Mmio<u32>),
+        (0x1524 => pub tsec_trng_unk_24: Mmio<u32>),
+        (0x1528 => pub tsec_trng_unk_28: Mmio<u32>),
+        (0x152C => pub tsec_trng_ctl: Mmio<u32>),
+        (0x1530 => _reserved188),
+        (0x1600 => pub tsec_tfbif_ctl: Mmio<u32>),
+        (0x1604 => pub tsec_tfbif_mccif_fifoctrl: Mmio<u32>),
+        (0x1608 => pub tsec_tfbif_throttle: Mmio<u32>),
+        (0x160C => pub tsec_tfbif_dbg_stat0: Mmio<u32>),
+        (0x1610 => pub tsec_tfbif_dbg_stat1: Mmio<u32>),
+        (0x1614 => pub tsec_tfbif_dbg_rdcount_lo: Mmio<u32>),
+        (0x1618 => pub tsec_tfbif_dbg_rdcount_hi: Mmio<u32>),
+        (0x161C => pub tsec_tfbif_dbg_wrcount_lo: Mmio<u32>),
+        (0x1620 => pub tsec_tfbif_dbg_wrcount_hi: Mmio<u32>),
+        (0x1624 => pub tsec_tfbif_dbg_r32count: Mmio<u32>),
+        (0x1628 => pub tsec_tfbif_dbg_r64count: Mmio<u32>),
+        (0x162C => pub tsec_tfbif_dbg_r128count: Mmio<u32>),
+        (0x1630 => pub tsec_tfbif_unk_30: Mmio<u32>),
+        (0x1634 => pub tsec_tfbif_mccif_fifoctrl1: Mmio<u32>),
+        (0x1638 => pub tsec_tfbif_wrr_rdp: Mmio<u32>),
+        (0x163C => _reserved203),
+        (0x1640 => pub tsec_tfbif_sprot_emem: Mmio<u32>),
+        (0x1644 => pub tsec_tfbif_transcfg: Mmio<u32>),
+        (0x1648 => pub tsec_tfbif_regioncfg: Mmio<u32>),
+        (0x164C => pub tsec_tfbif_actmon_active_mask: Mmio<u32>),
+        (0x1650 => pub tsec_tfbif_actmon_active_borps: Mmio<u32>),
+        (0x1654 => pub tsec_tfbif_actmon_active_weight: Mmio<u32>),
+        (0x1658 => _reserved209),
+        (0x1660 => pub tsec_tfbif_actmon_mcb_mask: Mmio<u32>),
+        (0x1664 => pub tsec_tfbif_actmon_mcb_borps: Mmio<u32>),
+        (0x1668 => pub tsec_tfbif_actmon_mcb_weight: Mmio<u32>),
+        (0x166C => _reserved212),
+        (0x1670 => pub tsec_tfbif_thi_transprop: Mmio<u32>),
+        (0x1674 => _reserved213),
+        (0x16D0 => pub tsec_cg: Mmio<u32>),
+        (0x16D4 => _reserved214),
+        (0x1700 => pub tsec_bar0_ctl: Mmio<u32>),
+        (0x1704 => pub tsec_bar0_addr: Mmio<u32>),
+        (0x1708 => pub tsec_bar0_data: Mmio<u32>),
+        (0x170C => pub tsec_bar0_timeout: Mmio<u32>),
+        (0x1710 => _reserved218),
+        (0x1800 => pub tsec_tegra_falcon_ip_ver: Mmio<u32>),
+        (0x1804 => pub tsec_tegra_unk_04: Mmio<u32>),
+        (0x1808 => pub tsec_tegra_unk_08: Mmio<u32>),
+        (0x180C => pub tsec_tegra_unk_0c: Mmio<u32>),
+        (0x1810 => pub tsec_tegra_unk_10: Mmio<u32>),
+        (0x1814 => pub tsec_tegra_unk_14: Mmio<u32>),
+        (0x1818 => pub tsec_tegra_unk_18: Mmio<u32>),
+        (0x181C => pub tsec_tegra_unk_1c: Mmio<u32>),
+        (0x1820 => pub tsec_tegra_unk_20: Mmio<u32>),
+        (0x1824 => pub tsec_tegra_unk_24: Mmio<u32>),
+        (0x1828 => pub tsec_tegra_unk_28: Mmio<u32>),
+        (0x182C => pub tsec_tegra_unk_2c: Mmio<u32>),
+        (0x1830 => pub tsec_tegra_unk_30: Mmio<u32>),
+        (0x1834 => pub tsec_tegra_unk_34: Mmio<u32>),
+        (0x1838 => pub tsec_tegra_ctl: Mmio<u32>),
+        (0x183C => @END),
+    }
 }
 
 impl VolatileStorage for Registers {
@@ -342,195 +262,178 @@ impl VolatileStorage for Registers {
 pub struct Tsec;
 
 impl Tsec {
-    /// Waits until DMA has entered an idle state.
-    fn dma_wait_idle(&self) -> Result<(), ()> {
-        let registers = unsafe { Registers::get() };
-
-        let timeout = get_milliseconds() + 10000;
-
-        while (registers.falcon_dmatrfcmd.read() & (1 << 1)) == 0 {
-            if get_milliseconds() > timeout {
-                return Err(());
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Configures physical DMA transfers to Falcon.
-    fn dma_phys_to_flcn(
-        &self,
-        is_imem: bool,
-        flcn_offset: u32,
-        phys_offset: u32,
-    ) -> Result<(), ()> {
-        let registers = unsafe { Registers::get() };
-
-        let cmd = if is_imem { 0x10 } else { 0x600 };
-
-        registers.falcon_dmatrfmoffs.write(flcn_offset);
-        registers.falcon_dmatrffboffs.write(phys_offset);
-        registers.falcon_dmatrfcmd.write(cmd);
-
-        self.dma_wait_idle()
-    }
-
     /// Creates a new TSEC object.
     pub const fn new() -> Self {
         Tsec
     }
 
+    /// Gets the clocks of all devices used by TSEC.
+    fn clocks() -> [Clock; 6] {
+        [
+            Clock::HOST1X,
+            Clock::TSEC,
+            Clock::SOR_SAFE,
+            Clock::SOR0,
+            Clock::SOR1,
+            Clock::KFUSE,
+        ]
+    }
+
     /// Enables all devices used by TSEC.
     pub fn enable_clocks(&self) {
-        Clock::HOST1X.enable();
-        Clock::TSEC.enable();
-        Clock::SOR_SAFE.enable();
-        Clock::SOR0.enable();
-        Clock::SOR1.enable();
-        Clock::KFUSE.enable();
+        for clock in &mut Self::clocks() {
+            clock.enable();
+        }
     }
 
     /// Disables all devices used by TSEC.
     pub fn disable_clocks(&self) {
-        Clock::HOST1X.disable();
-        Clock::TSEC.disable();
-        Clock::SOR_SAFE.disable();
-        Clock::SOR0.disable();
-        Clock::SOR1.disable();
-        Clock::KFUSE.disable();
+        for clock in &mut Self::clocks() {
+            clock.disable();
+        }
     }
 
     /// Retrieves the TSEC key.
-    pub fn get_key(&self, rev: u32, firmware: &mut [u8]) -> Result<[u32; 4], ()> {
-        let registers = unsafe { Registers::get() };
-
+    pub fn get_key(&self, rev: u32, firmware: &[u8]) -> Result<[u32; 4], TsecError> {
         self.enable_clocks();
 
-        // Configure Falcon.
-        registers.falcon_dmactl.write(0);
-        registers.falcon_irqmset.write(0xFFF2);
-        registers.falcon_irqdest.write(0xFFF0);
-        registers.falcon_itfen.write(3);
+        let result = self.run_key_firmware(rev, firmware);
 
-        if self.dma_wait_idle().is_err() {
+        if result.is_err() {
             self.disable_clocks();
-            return Err(());
         }
 
+        result
+    }
+
+    /// Loads and executes the key generation firmware and reads back the key.
+    fn run_key_firmware(&self, rev: u32, firmware: &[u8]) -> Result<[u32; 4], TsecError> {
         // Load firmware.
-        if self.load_firmware(firmware).is_err() {
-            self.disable_clocks();
-            return Err(());
-        }
+        self.load_firmware(firmware)?;
 
         // Execute firmware.
-        self.execute_firmware(Some(rev));
+        self.execute_firmware(Some(rev))?;
 
-        if self.dma_wait_idle().is_err() {
-            self.disable_clocks();
-            return Err(());
-        }
+        Falcon::TSEC.dma_wait_idle()?;
 
-        let timeout = get_milliseconds() + 2000;
-        while registers.falcon_mailbox1.read() == 0 {
-            if get_milliseconds() > timeout {
-                self.disable_clocks();
-                return Err(());
-            }
+        let mailbox = Falcon::TSEC.wait_for_mailbox1(2000)?;
+        if mailbox != TSEC_MAILBOX_MAGIC {
+            error!("TSEC firmware reported {:#X} instead of the magic", mailbox);
+            return Err(TsecError::BadMailboxMagic(mailbox));
         }
 
-        if registers.falcon_mailbox1.read() != 0xB0B0_B0B0 {
-            self.disable_clocks();
-            return Err(());
-        }
+        // Release the HOST1X handshake.
+        Syncpoint::TSEC.write(0);
+
+        // Fetch the key from SOR1 and clear it from the registers.
+        let key = Sor::SOR1.hdcp_key();
+        Sor::SOR1.clear_hdcp_key();
+
+        Ok(key)
+    }
+
+    /// Loads the TSEC firmware.
+    pub fn load_firmware(&self, firmware: &[u8]) -> Result<(), TsecError> {
+        Falcon::TSEC.load(firmware, firmware.len())?;
+
+        Ok(())
+    }
+
+    /// Reads data from the Falcon DMEM at a given word-aligned offset.
+    pub fn read_dmem(&self, offset: u32, buffer: &mut [u8]) -> Result<(), TsecError> {
+        Falcon::TSEC.read_dmem(offset, buffer)?;
+
+        Ok(())
+    }
+
+    /// Writes data to the Falcon DMEM at a given word-aligned offset.
+    pub fn write_dmem(&self, offset: u32, data: &[u8]) -> Result<(), TsecError> {
+        Falcon::TSEC.write_dmem(offset, data)?;
+
+        Ok(())
+    }
 
-        // Unknown HOST1X write.
-        unsafe {
-            (*((HOST1X_BASE + 0x3300) as *const Mmio<u32>)).write(0);
+    /// Uploads code to the Falcon IMEM at a given word-aligned offset
+    /// through the IMEM port, as an alternative to DMA transfers.
+    ///
+    /// Every 256 byte page is tagged with its virtual page number.
+    pub fn upload_imem(&self, offset: u32, code: &[u8], secure: bool) -> Result<(), TsecError> {
+        Falcon::TSEC.upload_imem(offset, code, secure)?;
+
+        Ok(())
+    }
+
+    /// Runs the multi-stage key generation firmware of 6.2.0 and newer,
+    /// which writes the TSEC root key into [`TSEC_ROOT_KEYSLOT`].
+    ///
+    /// The firmware must be located in DRAM, aligned to a 1MiB boundary.
+    /// While it is running, the region is covered by the TSEC carveout.
+    ///
+    /// [`TSEC_ROOT_KEYSLOT`]: constant.TSEC_ROOT_KEYSLOT.html
+    pub fn derive_keys_into_se(&self, firmware: &[u8], revision: u32) -> Result<(), TsecError> {
+        let address = firmware.as_ptr() as usize;
+        if address % TSEC_CARVEOUT_ALIGNMENT != 0 || address > u32::max_value() as usize {
+            return Err(TsecError::AlignmentError);
         }
 
-        let sor1_dp_hdcp_bksv_lsb = unsafe {
-            &*((SOR1_BASE + 0x1E8) as *const Mmio<u32>)
-        };
+        let size_mb = (firmware.len() + TSEC_CARVEOUT_ALIGNMENT - 1) / TSEC_CARVEOUT_ALIGNMENT;
 
-        let sor1_tmds_hdcp_bksv_lsb = unsafe {
-            &*((SOR1_BASE + 0x21C) as *const Mmio<u32>)
-        };
+        // The later stages are fetched through DMA, bypassing the cache.
+        arch::clean_range(address, firmware.len());
 
-        let sor1_tmds_hdcp_cn_msb = unsafe {
-            &*((SOR1_BASE + 0x208) as *const Mmio<u32>)
-        };
+        self.enable_clocks();
 
-        let sor1_tmds_hdcp_cn_lsb = unsafe {
-            &*((SOR1_BASE + 0x20C) as *const Mmio<u32>)
-        };
+        // Restrict the firmware region to the TSEC while the later stages are running.
+        config_tsec_carveout(address as u32, size_mb as u32, false);
 
-        // Fetch result from SOR1.
-        let mut key: [u32; 0x4] = [0; 4];
-        key[0] = sor1_dp_hdcp_bksv_lsb.read();
-        key[1] = sor1_tmds_hdcp_bksv_lsb.read();
-        key[2] = sor1_tmds_hdcp_cn_msb.read();
-        key[3] = sor1_tmds_hdcp_cn_lsb.read();
+        let result = self.run_root_key_firmware(firmware, revision);
 
-        // Clear SOR1 registers.
-        sor1_dp_hdcp_bksv_lsb.write(0);
-        sor1_tmds_hdcp_bksv_lsb.write(0);
-        sor1_tmds_hdcp_cn_msb.write(0);
-        sor1_tmds_hdcp_cn_lsb.write(0);
+        // Tear down the carveout again.
+        config_tsec_carveout(0, 0, false);
 
-        Ok(key)
+        self.disable_clocks();
+
+        if result.is_ok() {
+            // Prevent the TSEC root key from being read back.
+            SecurityEngine::new()
+                .set_aes_keyslot_flags(TSEC_ROOT_KEYSLOT, 0x80)
+                .expect("The TSEC root keyslot is a valid AES keyslot");
+        }
+
+        result
     }
 
-    /// Loads the TSEC firmware.
-    pub fn load_firmware(&self, firmware: &[u8]) -> Result<(), ()> {
-        let registers = unsafe { Registers::get() };
-
-        let mut res = Ok(());
-
-        // Configure Falcon.
-        registers.falcon_dmactl.write(0);
-        registers.falcon_irqmset.write(0xFFF2);
-        registers.falcon_irqdest.write(0xFFF0);
-        registers.falcon_itfen.write(3);
-
-        if self.dma_wait_idle().is_ok() {
-            // Load firmware.
-            registers
-                .falcon_dmatrfbase
-                .write(firmware.as_ptr() as usize as u32 >> 8);
-
-            let mut addr = 0;
-            while addr < firmware.len() {
-                if self
-                    .dma_phys_to_flcn(true, addr as u32, addr as u32)
-                    .is_err()
-                {
-                    res = Err(());
-                    break;
-                }
-
-                addr += 0x100;
-            }
-        } else {
-            res = Err(());
+    /// Boots the first stage of the 6.2.0+ firmware and waits for the final result.
+    fn run_root_key_firmware(&self, firmware: &[u8], revision: u32) -> Result<(), TsecError> {
+        // Only the boot stage goes into IMEM, it loads the remaining stages on its own.
+        let boot_stage_size = firmware.len().min(Falcon::TSEC.imem_size());
+        Falcon::TSEC.load(firmware, boot_stage_size)?;
+
+        self.execute_firmware(Some(revision))?;
+
+        let mailbox = Falcon::TSEC.wait_for_mailbox1(2000)?;
+
+        // Release the HOST1X handshake.
+        Syncpoint::TSEC.write(0);
+
+        if mailbox != TSEC_MAILBOX_MAGIC {
+            return Err(TsecError::BadMailboxMagic(mailbox));
         }
 
-        res
+        Ok(())
     }
 
     /// Executes the loaded TSEC firmware.
-    pub fn execute_firmware(&self, rev: Option<u32>) {
-        let registers = unsafe { Registers::get() };
+    pub fn execute_firmware(&self, rev: Option<u32>) -> Result<(), TsecError> {
+        // Make sure that all firmware transfers have completed.
+        Falcon::TSEC.dma_wait_idle()?;
 
-        // Unknown HOST1X write.
-        unsafe {
-            (*((HOST1X_BASE + 0x3300) as *const Mmio<u32>)).write(0x34C2_E1DA);
-        }
+        // Prepare the HOST1X handshake.
+        Syncpoint::TSEC.write(TSEC_SYNCPT_MAGIC);
 
         // Execute the firmware.
-        registers.falcon_mailbox1.write(0);
-        registers.falcon_mailbox0.write(rev.unwrap_or(0));
-        registers.falcon_bootvec.write(0);
-        registers.falcon_cpuctl.write(2);
+        Falcon::TSEC.boot(rev.unwrap_or(0));
+
+        Ok(())
     }
 }
@@ -17,8 +17,9 @@
 //! # Implementation
 //!
 //! - The bitflag structs [`FifoControl`], [`InterruptIdentification`],
-//! [`LineControl`], [`LineStatus`], [`VendorStatus`] are abstractions
-//! over possible values in these UART registers.
+//! [`LineControl`], [`ModemControl`], [`LineStatus`], [`ModemStatus`],
+//! [`VendorStatus`] are abstractions over possible values in these UART
+//! registers.
 //!
 //! - The [`Registers`] struct provides abstraction over the UART
 //! registers and the possibility to create pointers to each UART
@@ -28,18 +29,30 @@
 //! the corresponding [`Clock`] for enabling the device and a pointer
 //! to the respective [`Registers`] block to do communication.
 //!
-//! - [`Uart`] holds pre-defined constants which represent the UARTs
-//! A through E and should be preferred over creating instances of
-//! the [`Uart`] struct manually.
+//! - The UARTs A through E are handed out by [`Peripherals::take`], so
+//! each of them has a single owner.
 //!
 //! - [`Uart::init`] has to be called for each device before it can
-//! be used.
+//! be used. The baud rate divisor is computed from the actual frequency
+//! of the UART clock, [`Uart::init_with`] selects a different parent
+//! clock than the default PLLP. [`Uart::set_baud`] changes the baud rate
+//! of an initialized UART.
 //!
 //! - [`Uart::read`] and [`Uart::write`] are the recommended methods
 //! for communicating over UART. For writing data, using the methods
 //! exposed by the [`Write`] trait are however preferred if you're
 //! transmitting strings.
 //!
+//! - [`Uart::set_flow_control`] lets the hardware handle the RTS/CTS
+//! lines, as required by devices like the Joy-Con. [`Uart::set_loopback`]
+//! connects the transmitter to the receiver internally for self-tests.
+//!
+//! - [`Uart::send_break`] transmits a BREAK condition, [`Uart::break_detected`]
+//! reports a received one.
+//!
+//! - [`Uart::write_dma`] moves larger buffers through an APB DMA channel
+//! instead of polling for every byte.
+//!
 //! - The [`Send`] and [`Sync`] traits are implemented for [`Uart`],
 //! instances and its references can be shared safely between thread
 //! boundaries.
@@ -49,12 +62,13 @@
 //! ```
 //! use core::fmt::Write;
 //!
-//! use mirage_libtegra::uart::Uart;
+//! use mirage_libtegra::peripherals::Peripherals;
 //!
 //! fn main() {
-//!     let mut device = &mut Uart::A;
+//!     let mut peripherals = Peripherals::take().unwrap();
+//!     let mut device = &mut peripherals.uart_a;
 //!
-//!     device.init(115_200);
+//!     device.init(115_200).unwrap();
 //!     writeln!(&mut device, "Hello, friend!").ok();
 //! }
 //! ```
@@ -62,26 +76,45 @@
 //! [`FifoControl`]: enum.FifoControl.html
 //! [`InterruptIdentification`]: enum.InterruptIdentification.html
 //! [`LineControl`]: enum.LineControl.html
+//! [`ModemControl`]: enum.ModemControl.html
 //! [`LineStatus`]: enum.LineStatus.html
+//! [`ModemStatus`]: enum.ModemStatus.html
 //! [`VendorStatus`]: enum.VendorStatus.html
 //! [`Registers`]: struct.Registers.html
 //! [`Uart`]: struct.Uart.html
 //! [`Clock`]: ../clock/struct.Clock.html
+//! [`Peripherals::take`]: ../peripherals/struct.Peripherals.html#method.take
 //! [`Uart::init`]: struct.Uart.html#method.init
+//! [`Uart::init_with`]: struct.Uart.html#method.init_with
+//! [`Uart::set_baud`]: struct.Uart.html#method.set_baud
 //! [`Uart::read`]: struct.Uart.html#method.read
 //! [`Uart::write`]: struct.Uart.html#method.write
+//! [`Uart::set_flow_control`]: struct.Uart.html#method.set_flow_control
+//! [`Uart::set_loopback`]: struct.Uart.html#method.set_loopback
+//! [`Uart::send_break`]: struct.Uart.html#method.send_break
+//! [`Uart::break_detected`]: struct.Uart.html#method.break_detected
+//! [`Uart::write_dma`]: struct.Uart.html#method.write_dma
 //! [`Write`]: https://doc.rust-lang.org/nightly/core/fmt/trait.Write.html
 //! [`Send`]: https://doc.rust-lang.org/nightly/core/marker/trait.Send.html
 //! [`Sync`]: https://doc.rust-lang.org/nightly/core/marker/trait.Sync.html
 
 use core::{
-    fmt::{Error, Write},
+    fmt::{self, Write},
     marker::{Send, Sync},
 };
 
-use mirage_mmio::Mmio;
+use mirage_mmio::{assert_layout, Mmio, ReadOnly};
+
+use crate::{
+    apbdma::{self, BusWidth, Channel, Request},
+    clock::{Clock, ClockParent},
+    timer::{usleep, wait_for},
+    Error,
+};
 
-use crate::{clock::Clock, timer::usleep};
+/// The time in microseconds a UART may take to send its FIFO contents,
+/// which suffices for all 64 bytes at 9600 baud.
+pub const IDLE_TIMEOUT: u32 = 100_000;
 
 /// Base address for the UART A registers.
 pub(crate) const UART_A_BASE: u32 = 0x7000_6000;
@@ -195,6 +228,29 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Representation of the `UART_MCR_0` register.
+    ///
+    /// This register denotes the UART Modem Control Register,
+    /// which controls the modem lines and flow control.
+    pub struct ModemControl: u32 {
+        /// Force DTR to active.
+        const DTR = 1 << 0;
+        /// Force RTS to active if `RTS_EN` is disabled.
+        const RTS = 1 << 1;
+        /// Output 1.
+        const OUT1 = 1 << 2;
+        /// Output 2.
+        const OUT2 = 1 << 3;
+        /// Internal loopback of the serial output to the input.
+        const LOOPBK = 1 << 4;
+        /// Only transmit while CTS is active.
+        const CTS_EN = 1 << 5;
+        /// Drive RTS from the fill level of the RX FIFO.
+        const RTS_EN = 1 << 6;
+    }
+}
+
 bitflags! {
     /// Representation of the `UART_LSR_0` register.
     ///
@@ -224,6 +280,30 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Representation of the `UART_MSR_0` register.
+    ///
+    /// This register indicates the state of the modem lines.
+    pub struct ModemStatus: u32 {
+        /// CTS changed since the last read.
+        const DCTS = 1 << 0;
+        /// DSR changed since the last read.
+        const DDSR = 1 << 1;
+        /// RI changed from active to inactive since the last read.
+        const TERI = 1 << 2;
+        /// CD changed since the last read.
+        const DCD = 1 << 3;
+        /// State of the CTS line.
+        const CTS = 1 << 4;
+        /// State of the DSR line.
+        const DSR = 1 << 5;
+        /// State of the RI line.
+        const RI = 1 << 6;
+        /// State of the CD line.
+        const CD = 1 << 7;
+    }
+}
+
 bitflags! {
     /// Representation of the `UART_VENDOR_STATUS_0_0` register.
     ///
@@ -269,9 +349,9 @@ pub struct Registers {
     /// The `UART_MCR_0` register.
     pub MCR: Mmio<u32>,
     /// The `UART_LSR_0` register.
-    pub LSR: Mmio<u32>,
+    pub LSR: ReadOnly<u32>,
     /// The `UART_MSR_0` register.
-    pub MSR: Mmio<u32>,
+    pub MSR: ReadOnly<u32>,
     /// The `UART_SPR_0` register.
     pub SPR: Mmio<u32>,
     /// The `UART_IRDA_CSR_0` register.
@@ -287,45 +367,65 @@ pub struct Registers {
     pub ASR: Mmio<u32>,
 }
 
+assert_layout!(registers_layout, Registers, 0x40, {
+    THR_DLAB: 0x00,
+    IER_DLAB: 0x04,
+    IIR_FCR: 0x08,
+    LCR: 0x0C,
+    MCR: 0x10,
+    LSR: 0x14,
+    MSR: 0x18,
+    SPR: 0x1C,
+    IRDA_CSR: 0x20,
+    RX_FIFO_CFG: 0x24,
+    MIE: 0x28,
+    VENDOR_STATUS: 0x2C,
+    ASR: 0x3C,
+});
+
 /// Representation of a UART.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Methods which change the state of the hardware, including reading from
+/// the RX FIFO, take `&mut self`, so a shared reference only allows for
+/// querying the line status.
+#[derive(Debug, PartialEq, Eq)]
 pub struct Uart {
     /// A pointer to the UART CPU registers used for communication.
     registers: *const Registers,
     /// The device clock to enable data transfer.
-    clock: &'static Clock,
+    clock: Clock,
 }
 
 // Definitions for known UARTs.
 impl Uart {
     /// Representation of the UART A.
-    pub const A: Self = Uart {
+    pub(crate) const A: Self = Uart {
         registers: UART_A_BASE as *const Registers,
-        clock: &Clock::UART_A,
+        clock: Clock::UART_A,
     };
 
     /// Representation of the UART B.
-    pub const B: Self = Uart {
+    pub(crate) const B: Self = Uart {
         registers: UART_B_BASE as *const Registers,
-        clock: &Clock::UART_B,
+        clock: Clock::UART_B,
     };
 
     /// Representation of the UART C.
-    pub const C: Self = Uart {
+    pub(crate) const C: Self = Uart {
         registers: UART_C_BASE as *const Registers,
-        clock: &Clock::UART_C,
+        clock: Clock::UART_C,
     };
 
     /// Representation of the UART D.
-    pub const D: Self = Uart {
+    pub(crate) const D: Self = Uart {
         registers: UART_D_BASE as *const Registers,
-        clock: &Clock::UART_D,
+        clock: Clock::UART_D,
     };
 
     /// Representation of the UART APE.
-    pub const E: Self = Uart {
+    pub(crate) const E: Self = Uart {
         registers: UART_E_BASE as *const Registers,
-        clock: &Clock::UART_APE,
+        clock: Clock::UART_APE,
     };
 }
 
@@ -343,47 +443,137 @@ impl Uart {
     }
 
     /// Blocks until the line has entered the desired state.
+    ///
+    /// The transmitter is idle once all data has been sent, the receiver
+    /// once no data is pending. Fails with [`Error::Timeout`] if the line
+    /// doesn't get there within [`IDLE_TIMEOUT`], e.g. because flow control
+    /// holds back the transmitter.
+    ///
+    /// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+    /// [`IDLE_TIMEOUT`]: constant.IDLE_TIMEOUT.html
     #[inline]
-    pub fn wait_idle(&self, status: VendorStatus) {
+    pub fn wait_idle(&self, status: VendorStatus) -> Result<(), Error> {
         let register_base = unsafe { &*self.registers };
 
         if status.contains(VendorStatus::UART_TX_IDLE) {
-            while (register_base.LSR.read() & LineStatus::TMTY.bits()) == 0 {}
+            wait_for(
+                || (register_base.LSR.read() & LineStatus::TMTY.bits()) != 0,
+                IDLE_TIMEOUT,
+            )?;
         }
 
         if status.contains(VendorStatus::UART_RX_IDLE) {
-            while (register_base.LSR.read() & LineStatus::RDR.bits()) == 0 {}
+            wait_for(
+                || (register_base.LSR.read() & LineStatus::RDR.bits()) == 0,
+                IDLE_TIMEOUT,
+            )?;
         }
+
+        Ok(())
     }
 
-    /// Waits until data have been transmitted.
+    /// Waits until data can be written to the TX FIFO.
     #[inline]
-    fn wait_transmit(&self) {
+    fn wait_transmit(&self) -> Result<(), Error> {
         let register_base = unsafe { &*self.registers };
 
-        while (register_base.LSR.read() & LineStatus::THRE.bits()) == 0 {}
+        wait_for(
+            || (register_base.LSR.read() & LineStatus::THRE.bits()) != 0,
+            IDLE_TIMEOUT,
+        )
     }
 
-    /// Waits until data have been received.
+    /// Waits at most `timeout` microseconds until data have been received.
     #[inline]
-    fn wait_receive(&self) {
+    fn wait_receive(&self, timeout: u32) -> Result<(), Error> {
         let register_base = unsafe { &*self.registers };
 
-        while (register_base.LSR.read() & LineStatus::RDR.bits()) == 0 {}
+        wait_for(
+            || (register_base.LSR.read() & LineStatus::RDR.bits()) != 0,
+            timeout,
+        )
+    }
+
+    /// Computes the divisor for a baud rate from the UART clock frequency,
+    /// rounded to nearest.
+    fn baud_divisor(&self, baud: u32) -> Result<u32, Error> {
+        if baud == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let frequency = u64::from(self.clock.frequency());
+        if frequency == 0 {
+            return Err(Error::Clock);
+        }
+
+        let divisor = (frequency + u64::from(8 * baud)) / u64::from(16 * baud);
+        if divisor == 0 || divisor > 0xFFFF {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(divisor as u32)
+    }
+
+    /// Programs the divisor latches, leaving the other line settings intact.
+    fn write_divisor(&self, divisor: u32) {
+        let register_base = unsafe { &*self.registers };
+
+        // Enable DLAB.
+        let lcr = register_base.LCR.read() & !LineControl::DLAB.bits();
+        register_base.LCR.write(lcr | LineControl::DLAB.bits());
+
+        register_base.THR_DLAB.write(divisor & 0xFF);
+        register_base.IER_DLAB.write(divisor >> 8);
+
+        // Disable DLAB.
+        register_base.LCR.write(lcr);
     }
 
-    /// Initializes the UART.
-    pub fn init(&self, baud: u32) {
+    /// Initializes the UART, clocked from its default parent.
+    ///
+    /// Fails if the frequency of the UART clock is unknown or the baud
+    /// rate can't be derived from it.
+    pub fn init(&mut self, baud: u32) -> Result<(), Error> {
         // Enable device clock.
         self.clock.enable();
 
+        self.configure(baud)
+    }
+
+    /// Initializes the UART, clocked from the given parent with the given
+    /// raw divisor.
+    ///
+    /// See [`Clock::set_source`] for the format of the divisor.
+    ///
+    /// [`Clock::set_source`]: ../clock/struct.Clock.html#method.set_source
+    pub fn init_with(&mut self, baud: u32, parent: ClockParent, divisor: u32) -> Result<(), Error> {
+        // Enable device clock.
+        self.clock.enable_with(parent, divisor)?;
+
+        self.configure(baud)
+    }
+
+    /// Changes the baud rate of an initialized UART.
+    ///
+    /// Pending data is transmitted at the previous baud rate first.
+    pub fn set_baud(&mut self, baud: u32) -> Result<(), Error> {
+        let divisor = self.baud_divisor(baud)?;
+
+        self.wait_idle(VendorStatus::UART_TX_IDLE)?;
+        self.write_divisor(divisor);
+
+        Ok(())
+    }
+
+    /// Configures the line settings and FIFOs of the UART.
+    fn configure(&mut self, baud: u32) -> Result<(), Error> {
         let register_base = unsafe { &*self.registers };
 
-        // Wait for TX idle state.
-        self.wait_idle(VendorStatus::UART_TX_IDLE);
+        // Calculate baud rate divisor.
+        let divisor = self.baud_divisor(baud)?;
 
-        // Calculate baud rate and round to nearest.
-        let baud_rate = (8 * baud + 408_000_000) / (16 * baud);
+        // Wait for TX idle state.
+        self.wait_idle(VendorStatus::UART_TX_IDLE)?;
 
         // Disable interrupts.
         register_base.IER_DLAB.write(0);
@@ -391,18 +581,9 @@ impl Uart {
         // No hardware flow control.
         register_base.MCR.write(0);
 
-        // Enable DLAB and set word length to 8.
-        register_base
-            .LCR
-            .write((LineControl::DLAB | LineControl::WORD_LENGTH_8).bits());
-
-        register_base.THR_DLAB.write(baud_rate);
-        register_base.IER_DLAB.write(baud_rate >> 8);
-
-        // Disable DLAB.
-        register_base
-            .LCR
-            .write(register_base.LCR.read() & !LineControl::DLAB.bits());
+        // Set word length to 8 and program the divisor.
+        register_base.LCR.write(LineControl::WORD_LENGTH_8.bits());
+        self.write_divisor(divisor);
 
         register_base.SPR.read(); // Dummy read.
         self.wait_symbols(baud, 3); // Wait for 3 symbols.
@@ -413,60 +594,323 @@ impl Uart {
         self.wait_cycles(baud, 3); // Wait for 3 baud cycles.
 
         // Flush FIFO.
-        self.wait_idle(VendorStatus::UART_TX_IDLE); // Ensure no data is being written to TX FIFO.
+        self.wait_idle(VendorStatus::UART_TX_IDLE)?; // Ensure no data is being written to TX FIFO.
         register_base.IIR_FCR.write(
             register_base.IIR_FCR.read() | (FifoControl::RX_CLR | FifoControl::TX_CLR).bits(),
         ); // Clear TX and RX FIFOs.
         self.wait_cycles(baud, 32); // Wait for 32 baud cycles.
 
         // Wait for idle state.
-        self.wait_idle(VendorStatus::UART_TX_IDLE | VendorStatus::UART_RX_IDLE);
+        self.wait_idle(VendorStatus::UART_TX_IDLE | VendorStatus::UART_RX_IDLE)
     }
 
     /// Writes a byte (`u8`) over UART.
-    pub fn write_byte(&self, byte: u8) {
+    ///
+    /// The byte is dropped if the transmitter is stuck, so logging can't
+    /// hang the system.
+    pub fn write_byte(&mut self, byte: u8) {
+        self.try_write_byte(byte).ok();
+    }
+
+    /// Writes a byte (`u8`) over UART, failing with [`Error::Timeout`] if
+    /// the transmitter is stuck.
+    ///
+    /// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+    pub fn try_write_byte(&mut self, byte: u8) -> Result<(), Error> {
         let register_base = unsafe { &*self.registers };
 
         // Wait until it is possible to write data.
-        self.wait_transmit();
+        self.wait_transmit()?;
 
         // Write the byte.
         register_base.THR_DLAB.write(u32::from(byte));
+
+        Ok(())
     }
 
     /// Reads a byte (`u8`) over UART.
-    pub fn read_byte(&self) -> u8 {
+    ///
+    /// This is an intentionally blocking read: input may take arbitrarily
+    /// long to arrive, e.g. while waiting for a user, so it waits for as
+    /// long as it takes. It is the only unbounded wait of the driver, use
+    /// [`read_byte_timeout`] to give up at some point.
+    ///
+    /// [`read_byte_timeout`]: #method.read_byte_timeout
+    pub fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Ok(byte) = self.read_byte_timeout(IDLE_TIMEOUT) {
+                return byte;
+            }
+        }
+    }
+
+    /// Reads a byte (`u8`) over UART, waiting at most `timeout`
+    /// microseconds for it to arrive.
+    ///
+    /// Fails with [`Error::Timeout`] if no data arrived in time.
+    ///
+    /// [`Error::Timeout`]: ../enum.Error.html#variant.Timeout
+    pub fn read_byte_timeout(&mut self, timeout: u32) -> Result<u8, Error> {
         let register_base = unsafe { &*self.registers };
 
         // Wait until it is possible to read data.
-        self.wait_receive();
+        self.wait_receive(timeout)?;
 
         // Read byte.
-        register_base.THR_DLAB.read() as u8
+        Ok(register_base.THR_DLAB.read() as u8)
+    }
+
+    /// Writes bytes over UART and waits until they have been sent.
+    ///
+    /// Unlike the `fmt::Write` implementation, this doesn't need any of the
+    /// `core::fmt` machinery.
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        for byte in data {
+            self.write_byte(*byte);
+        }
+
+        self.wait_transmit().ok();
     }
 
     /// Reads bytes into a buffer.
-    pub fn read(&self, buffer: &mut [u8]) {
+    ///
+    /// Like [`read_byte`], this blocks until the whole buffer was filled.
+    ///
+    /// [`read_byte`]: #method.read_byte
+    pub fn read(&mut self, buffer: &mut [u8]) {
         for i in buffer.iter_mut() {
             *i = self.read_byte();
         }
     }
+
+    /// Enables or disables RTS/CTS hardware flow control.
+    ///
+    /// When enabled, data is only transmitted while CTS is active and RTS
+    /// is deasserted while the RX FIFO is full.
+    pub fn set_flow_control(&mut self, enabled: bool) {
+        let register_base = unsafe { &*self.registers };
+        let flags = (ModemControl::CTS_EN | ModemControl::RTS_EN | ModemControl::RTS).bits();
+
+        if enabled {
+            register_base.MCR.write(register_base.MCR.read() | flags);
+        } else {
+            register_base.MCR.write(register_base.MCR.read() & !flags);
+        }
+    }
+
+    /// Enables or disables the internal loopback of the transmitter to
+    /// the receiver.
+    ///
+    /// While enabled, nothing is sent on the TX line.
+    pub fn set_loopback(&mut self, enabled: bool) {
+        let register_base = unsafe { &*self.registers };
+
+        // Let pending data leave before switching.
+        self.wait_idle(VendorStatus::UART_TX_IDLE).ok();
+
+        if enabled {
+            register_base
+                .MCR
+                .write(register_base.MCR.read() | ModemControl::LOOPBK.bits());
+        } else {
+            register_base
+                .MCR
+                .write(register_base.MCR.read() & !ModemControl::LOOPBK.bits());
+        }
+    }
+
+    /// Gets the state of the modem lines.
+    pub fn modem_status(&self) -> ModemStatus {
+        let register_base = unsafe { &*self.registers };
+
+        ModemStatus::from_bits_truncate(register_base.MSR.read())
+    }
+
+    /// Transmits a BREAK condition for the given amount of microseconds.
+    pub fn send_break(&mut self, duration: u32) {
+        let register_base = unsafe { &*self.registers };
+
+        self.wait_idle(VendorStatus::UART_TX_IDLE).ok();
+
+        register_base
+            .LCR
+            .write(register_base.LCR.read() | LineControl::SET_B.bits());
+        usleep(duration);
+        register_base
+            .LCR
+            .write(register_base.LCR.read() & !LineControl::SET_B.bits());
+    }
+
+    /// Whether a BREAK condition has been received.
+    ///
+    /// Reading the line status clears the indication. The receiver puts a
+    /// zero byte into the RX FIFO for every BREAK.
+    pub fn break_detected(&self) -> bool {
+        let register_base = unsafe { &*self.registers };
+
+        (register_base.LSR.read() & LineStatus::BRK.bits()) != 0
+    }
+
+    /// Gets the DMA request of the UART, if it has one.
+    fn dma_request(&self) -> Option<Request> {
+        match self.registers as u32 {
+            UART_A_BASE => Some(Request::UartA),
+            UART_B_BASE => Some(Request::UartB),
+            UART_C_BASE => Some(Request::UartC),
+            UART_D_BASE => Some(Request::UartD),
+            _ => None,
+        }
+    }
+
+    /// Writes a buffer over UART, moving the word-aligned bulk of it
+    /// through the given APB DMA channel.
+    ///
+    /// The bytes before the first and after the last word boundary are
+    /// written by the CPU. UART E has no DMA request and is written by
+    /// the CPU entirely.
+    pub fn write_dma(&mut self, channel: &mut Channel, data: &[u8]) -> Result<(), apbdma::Error> {
+        let register_base = unsafe { &*self.registers };
+
+        let (head, body, tail) = match self.dma_request() {
+            Some(_) => unsafe { data.align_to::<u32>() },
+            None => (data, &[][..], &[][..]),
+        };
+
+        for byte in head {
+            self.write_byte(*byte);
+        }
+
+        if let Some(request) = self.dma_request().filter(|_| !body.is_empty()) {
+            // Let the TX FIFO request data from the DMA.
+            register_base.IIR_FCR.write(
+                (FifoControl::FCR_EN_FIFO
+                    | FifoControl::DMA
+                    | FifoControl::TX_TRIG_FIFO_COUNT_GREATER_4)
+                    .bits(),
+            );
+
+            let result = channel.write(
+                request,
+                &register_base.THR_DLAB as *const _ as u32,
+                BusWidth::Bits8,
+                body,
+            );
+
+            register_base.IIR_FCR.write(FifoControl::FCR_EN_FIFO.bits());
+            result?;
+        }
+
+        for byte in tail {
+            self.write_byte(*byte);
+        }
+
+        // Wait for everything to be written.
+        self.wait_transmit().map_err(|_| apbdma::Error::Timeout)
+    }
 }
 
 impl Write for Uart {
-    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
         // Write data.
         for byte in s.as_bytes() {
             self.write_byte(*byte);
         }
 
         // Wait for everything to be written.
-        self.wait_transmit();
-
-        Ok(())
+        self.wait_transmit().map_err(|_| fmt::Error)
     }
 }
 
 unsafe impl Send for Uart {}
 
 unsafe impl Sync for Uart {}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+    use crate::timer;
+
+    fn address<T>(register: &T) -> usize {
+        register as *const T as usize
+    }
+
+    /// Tests the register sequence that configures the baud rate and FIFOs.
+    #[test]
+    fn init_uart() {
+        let _session = mock::start();
+        timer::mock_counter();
+
+        let mut uart = Uart::A;
+        let registers = unsafe { &*uart.registers };
+
+        // Report the line as idle.
+        mock::set(address(&registers.LSR), u64::from(LineStatus::TMTY.bits()));
+
+        // Clock UART A from PLLP at 408MHz.
+        assert_eq!(uart.init(115_200), Err(Error::Clock));
+        mock::set(0x6000_60A0, 1 << 30);
+
+        let dlab = (LineControl::DLAB | LineControl::WORD_LENGTH_8).bits();
+        let word_length = LineControl::WORD_LENGTH_8.bits();
+        mock::expect_write(address(&registers.LCR), u64::from(word_length));
+        mock::expect_write(address(&registers.LCR), u64::from(dlab));
+        mock::expect_write(address(&registers.LCR), u64::from(word_length));
+
+        uart.init(115_200).unwrap();
+
+        assert!(Clock::UART_A.is_enabled());
+        assert_eq!(mock::writes(address(&registers.MCR)), [0]);
+
+        // 408MHz / (16 * 115200), rounded to nearest.
+        assert_eq!(mock::writes(address(&registers.THR_DLAB)), [221]);
+        assert_eq!(mock::writes(address(&registers.IER_DLAB)), [0, 0]);
+
+        let fifo = FifoControl::FCR_EN_FIFO.bits();
+        let flush = (FifoControl::RX_CLR | FifoControl::TX_CLR).bits();
+        assert_eq!(
+            mock::writes(address(&registers.IIR_FCR)),
+            [u64::from(fifo), u64::from(fifo | flush)]
+        );
+
+        // Switch to 1MBaud, 408MHz / (16 * 1000000) rounded to nearest.
+        uart.set_baud(1_000_000).unwrap();
+        assert_eq!(mock::writes(address(&registers.THR_DLAB)), [221, 26]);
+        assert_eq!(mock::get(address(&registers.LCR)), u64::from(word_length));
+        assert_eq!(uart.set_baud(0), Err(Error::InvalidArgument));
+    }
+
+    /// Tests flow control, loopback and BREAK transmission.
+    #[test]
+    fn line_control() {
+        let _session = mock::start();
+        timer::mock_counter();
+
+        let mut uart = Uart::B;
+        let registers = unsafe { &*uart.registers };
+
+        // Report the line as idle.
+        mock::set(address(&registers.LSR), u64::from(LineStatus::TMTY.bits()));
+
+        uart.set_flow_control(true);
+        uart.set_loopback(true);
+        assert_eq!(mock::get(address(&registers.MCR)), 0x72);
+
+        uart.set_flow_control(false);
+        uart.set_loopback(false);
+        assert_eq!(mock::get(address(&registers.MCR)), 0);
+
+        let lcr = LineControl::WORD_LENGTH_8.bits();
+        mock::set(address(&registers.LCR), u64::from(lcr));
+        uart.send_break(100);
+        assert_eq!(
+            mock::writes(address(&registers.LCR)),
+            [u64::from(lcr | LineControl::SET_B.bits()), u64::from(lcr)]
+        );
+
+        assert!(!uart.break_detected());
+        mock::set(address(&registers.LSR), u64::from(LineStatus::BRK.bits()));
+        assert!(uart.break_detected());
+    }
+}
@@ -0,0 +1,655 @@
+//! USB Mass Storage class over the Bulk-Only Transport.
+//!
+//! # Description
+//!
+//! The USB Mass Storage class lets a payload expose a [`BlockDevice`],
+//! like the SD card or an eMMC partition, to a connected computer, which
+//! can then back it up or repair it with its own tools.
+//!
+//! With the Bulk-Only Transport (BOT), the host sends every command in a
+//! Command Block Wrapper (CBW) over the bulk OUT endpoint. The data phase,
+//! if any, follows on the bulk endpoint of the requested direction and the
+//! device concludes the command with a Command Status Wrapper (CSW) on the
+//! bulk IN endpoint. The commands are taken from the SCSI block command
+//! set, of which the subset used by common operating systems is supported.
+//!
+//! # Implementation
+//!
+//! - [`BulkTransport`] abstracts the bulk endpoints of a USB device
+//! controller after the host has configured the device.
+//!
+//! - [`MassStorage`] answers the commands of the host from a
+//! [`BlockDevice`], transferring data through a caller-provided buffer.
+//! [`MassStorage::process`] handles a single command, [`MassStorage::run`]
+//! handles commands until the host ejects the medium.
+//!
+//! - Failing commands are reported in the CSW and explained through the
+//! sense data, which the host fetches with `REQUEST SENSE`.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{
+//!     block::BlockDevice,
+//!     usb::mass_storage::{BulkTransport, MassStorage},
+//!     Result,
+//! };
+//!
+//! fn share<D: BlockDevice, T: BulkTransport>(sd: &mut D, transport: T) -> Result<()> {
+//!     let mut buffer = [0; 0x8000];
+//!
+//!     MassStorage::new(sd, transport, &mut buffer).run()
+//! }
+//! ```
+//!
+//! [`BlockDevice`]: ../../block/trait.BlockDevice.html
+//! [`BulkTransport`]: trait.BulkTransport.html
+//! [`MassStorage`]: struct.MassStorage.html
+//! [`MassStorage::process`]: struct.MassStorage.html#method.process
+//! [`MassStorage::run`]: struct.MassStorage.html#method.run
+
+use core::convert::TryInto;
+
+use crate::{
+    block::{BlockDevice, BLOCK_SIZE},
+    Error, Result,
+};
+
+/// The signature of a CBW, "USBC".
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+/// The signature of a CSW, "USBS".
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+/// The size of a CBW in bytes.
+const CBW_SIZE: usize = 31;
+/// The size of a CSW in bytes.
+const CSW_SIZE: usize = 13;
+/// Marks a data phase from device to host in the CBW flags.
+const CBW_FLAGS_IN: u8 = 1 << 7;
+
+/// The command completed successfully.
+const CSW_STATUS_PASSED: u8 = 0;
+/// The command failed, the reason is in the sense data.
+const CSW_STATUS_FAILED: u8 = 1;
+/// The host and the device disagree on the data phase.
+const CSW_STATUS_PHASE_ERROR: u8 = 2;
+
+// The supported SCSI operation codes.
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_MODE_SENSE_6: u8 = 0x1A;
+const SCSI_START_STOP_UNIT: u8 = 0x1B;
+const SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
+const SCSI_READ_FORMAT_CAPACITIES: u8 = 0x23;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2A;
+const SCSI_VERIFY_10: u8 = 0x2F;
+const SCSI_SYNCHRONIZE_CACHE_10: u8 = 0x35;
+
+/// Ejects the medium in the `START STOP UNIT` command.
+const START_STOP_LOEJ: u8 = 1 << 1;
+/// Starts the unit in the `START STOP UNIT` command.
+const START_STOP_START: u8 = 1 << 0;
+
+/// The vendor identification reported by `INQUIRY`.
+const INQUIRY_VENDOR: &[u8; 8] = b"Mirage  ";
+/// The product identification reported by `INQUIRY`.
+const INQUIRY_PRODUCT: &[u8; 16] = b"Mass Storage    ";
+/// The product revision reported by `INQUIRY`.
+const INQUIRY_REVISION: &[u8; 4] = b"1.00";
+
+/// Sense data describing why the last command failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Sense {
+    /// The sense key.
+    key: u8,
+    /// The additional sense code.
+    asc: u8,
+    /// The additional sense code qualifier.
+    ascq: u8,
+}
+
+impl Sense {
+    /// No error occurred.
+    const NONE: Self = Sense::new(0x00, 0x00, 0x00);
+    /// The command isn't supported.
+    const INVALID_COMMAND: Self = Sense::new(0x05, 0x20, 0x00);
+    /// The command refers to blocks past the end of the medium.
+    const LBA_OUT_OF_RANGE: Self = Sense::new(0x05, 0x21, 0x00);
+    /// A field of the command is invalid.
+    const INVALID_FIELD: Self = Sense::new(0x05, 0x24, 0x00);
+    /// The medium is write-protected.
+    const WRITE_PROTECTED: Self = Sense::new(0x07, 0x27, 0x00);
+    /// The medium couldn't be read.
+    const READ_ERROR: Self = Sense::new(0x03, 0x11, 0x00);
+    /// The medium couldn't be written.
+    const WRITE_ERROR: Self = Sense::new(0x03, 0x0C, 0x00);
+    /// The medium has been ejected.
+    const MEDIUM_NOT_PRESENT: Self = Sense::new(0x02, 0x3A, 0x00);
+
+    /// Creates sense data from its key and additional code.
+    const fn new(key: u8, asc: u8, ascq: u8) -> Self {
+        Sense { key, asc, ascq }
+    }
+}
+
+/// The bulk endpoints of a configured USB device.
+pub trait BulkTransport {
+    /// Receives a packet from the bulk OUT endpoint into `buffer`.
+    ///
+    /// Returns the amount of bytes received, which may be less than the
+    /// size of `buffer` for a short packet.
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<usize>;
+
+    /// Sends `data` over the bulk IN endpoint.
+    fn send(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Stalls the bulk IN endpoint until the host clears the halt.
+    fn stall_in(&mut self) -> Result<()>;
+}
+
+/// A parsed Command Block Wrapper.
+#[derive(Clone, Copy, Debug)]
+struct Cbw {
+    /// The tag to echo back in the CSW.
+    tag: u32,
+    /// The amount of bytes the host expects to transfer.
+    transfer_length: u32,
+    /// Whether the data is transferred from device to host.
+    is_in: bool,
+    /// The SCSI command block.
+    command: [u8; 16],
+}
+
+impl Cbw {
+    /// Parses and validates a CBW.
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() != CBW_SIZE
+            || u32::from_le_bytes(data[0..4].try_into().unwrap()) != CBW_SIGNATURE
+        {
+            return None;
+        }
+
+        let command_length = data[14] as usize;
+        if command_length == 0 || command_length > 16 {
+            return None;
+        }
+
+        Some(Cbw {
+            tag: u32::from_le_bytes(data[4..8].try_into().unwrap()),
+            transfer_length: u32::from_le_bytes(data[8..12].try_into().unwrap()),
+            is_in: data[12] & CBW_FLAGS_IN != 0,
+            command: data[15..31].try_into().unwrap(),
+        })
+    }
+
+    /// Gets the LBA of a 10-byte read or write command.
+    fn lba(&self) -> u64 {
+        u64::from(u32::from_be_bytes(self.command[2..6].try_into().unwrap()))
+    }
+
+    /// Gets the block count of a 10-byte read or write command.
+    fn blocks(&self) -> u64 {
+        u64::from(u16::from_be_bytes(self.command[7..9].try_into().unwrap()))
+    }
+}
+
+/// The outcome of a command, as reported in the CSW.
+struct Outcome {
+    /// The CSW status.
+    status: u8,
+    /// The amount of bytes actually transferred in the data phase.
+    transferred: u32,
+}
+
+impl Outcome {
+    /// A successful command which transferred the given amount of bytes.
+    fn passed(transferred: u32) -> Self {
+        Outcome {
+            status: CSW_STATUS_PASSED,
+            transferred,
+        }
+    }
+}
+
+/// A USB Mass Storage device backed by a block device.
+pub struct MassStorage<'a, D: BlockDevice + ?Sized, T: BulkTransport> {
+    /// The device to expose.
+    device: &'a mut D,
+    /// The bulk endpoints to the host.
+    transport: T,
+    /// The buffer for data transfers.
+    buffer: &'a mut [u8],
+    /// Whether writes from the host are rejected.
+    read_only: bool,
+    /// Whether the host has ejected the medium.
+    ejected: bool,
+    /// The sense data of the last failed command.
+    sense: Sense,
+}
+
+impl<'a, D: BlockDevice + ?Sized, T: BulkTransport> MassStorage<'a, D, T> {
+    /// Creates a mass storage device exposing `device` through `transport`.
+    ///
+    /// `buffer` holds the data of transfers. Its size is rounded down to
+    /// whole blocks and has to cover at least one block.
+    pub fn new(device: &'a mut D, transport: T, buffer: &'a mut [u8]) -> Self {
+        let size = buffer.len() / BLOCK_SIZE * BLOCK_SIZE;
+        assert!(
+            size != 0,
+            "The transfer buffer must hold at least one block"
+        );
+
+        MassStorage {
+            device,
+            transport,
+            buffer: &mut buffer[..size],
+            read_only: false,
+            ejected: false,
+            sense: Sense::NONE,
+        }
+    }
+
+    /// Makes the device reject writes from the host.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Handles commands until the host ejects the medium.
+    pub fn run(&mut self) -> Result<()> {
+        while !self.ejected {
+            self.process()?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives a single command from the host, handles it and reports
+    /// its status.
+    ///
+    /// Fails with [`Error::InvalidData`] if the host sent an invalid CBW,
+    /// after which the transport needs to be reset by the host.
+    ///
+    /// [`Error::InvalidData`]: ../../enum.Error.html#variant.InvalidData
+    pub fn process(&mut self) -> Result<()> {
+        let mut cbw = [0; CBW_SIZE + 1];
+        let length = self.transport.receive(&mut cbw)?;
+
+        let cbw = match Cbw::parse(&cbw[..length]) {
+            Some(cbw) => cbw,
+            None => {
+                self.transport.stall_in()?;
+                return Err(Error::InvalidData);
+            }
+        };
+
+        let outcome = match self.execute(&cbw) {
+            Ok(outcome) => outcome,
+            Err(sense) => {
+                self.sense = sense;
+                Outcome {
+                    status: CSW_STATUS_FAILED,
+                    transferred: 0,
+                }
+            }
+        };
+
+        let residue = cbw.transfer_length.saturating_sub(outcome.transferred);
+        let status = if outcome.transferred > cbw.transfer_length {
+            CSW_STATUS_PHASE_ERROR
+        } else {
+            outcome.status
+        };
+
+        // Tell the host that no more data follows.
+        if cbw.is_in && residue != 0 {
+            self.transport.stall_in()?;
+        }
+
+        let mut csw = [0; CSW_SIZE];
+        csw[0..4].copy_from_slice(&CSW_SIGNATURE.to_le_bytes());
+        csw[4..8].copy_from_slice(&cbw.tag.to_le_bytes());
+        csw[8..12].copy_from_slice(&residue.to_le_bytes());
+        csw[12] = status;
+
+        self.transport.send(&csw)
+    }
+
+    /// Executes a SCSI command, returning the sense data on failure.
+    fn execute(&mut self, cbw: &Cbw) -> core::result::Result<Outcome, Sense> {
+        let opcode = cbw.command[0];
+
+        if self.ejected && opcode != SCSI_REQUEST_SENSE && opcode != SCSI_INQUIRY {
+            return Err(Sense::MEDIUM_NOT_PRESENT);
+        }
+
+        match opcode {
+            SCSI_TEST_UNIT_READY
+            | SCSI_PREVENT_ALLOW_MEDIUM_REMOVAL
+            | SCSI_SYNCHRONIZE_CACHE_10
+            | SCSI_VERIFY_10 => Ok(Outcome::passed(0)),
+            SCSI_REQUEST_SENSE => {
+                let mut sense = [0; 18];
+                sense[0] = 0x70;
+                sense[2] = self.sense.key;
+                sense[7] = 10;
+                sense[12] = self.sense.asc;
+                sense[13] = self.sense.ascq;
+
+                self.sense = Sense::NONE;
+                self.send_response(cbw, &sense)
+            }
+            SCSI_INQUIRY => {
+                let mut inquiry = [0; 36];
+                // A removable direct-access block device, SPC-2.
+                inquiry[1] = 0x80;
+                inquiry[2] = 0x04;
+                inquiry[3] = 0x02;
+                inquiry[4] = inquiry.len() as u8 - 5;
+                inquiry[8..16].copy_from_slice(INQUIRY_VENDOR);
+                inquiry[16..32].copy_from_slice(INQUIRY_PRODUCT);
+                inquiry[32..36].copy_from_slice(INQUIRY_REVISION);
+
+                self.send_response(cbw, &inquiry)
+            }
+            SCSI_MODE_SENSE_6 => {
+                let write_protect = if self.read_only { 0x80 } else { 0 };
+                self.send_response(cbw, &[3, 0, write_protect, 0])
+            }
+            SCSI_START_STOP_UNIT => {
+                let flags = cbw.command[4];
+                if flags & START_STOP_LOEJ != 0 && flags & START_STOP_START == 0 {
+                    self.ejected = true;
+                }
+
+                Ok(Outcome::passed(0))
+            }
+            SCSI_READ_FORMAT_CAPACITIES => {
+                let mut capacities = [0; 12];
+                capacities[3] = 8;
+                capacities[4..8].copy_from_slice(&self.block_count().to_be_bytes());
+                // Formatted media.
+                capacities[8] = 0x02;
+                capacities[9..12].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes()[1..]);
+
+                self.send_response(cbw, &capacities)
+            }
+            SCSI_READ_CAPACITY_10 => {
+                let mut capacity = [0; 8];
+                capacity[0..4].copy_from_slice(&self.block_count().saturating_sub(1).to_be_bytes());
+                capacity[4..8].copy_from_slice(&(BLOCK_SIZE as u32).to_be_bytes());
+
+                self.send_response(cbw, &capacity)
+            }
+            SCSI_READ_10 => self.read(cbw),
+            SCSI_WRITE_10 => self.write(cbw),
+            _ => Err(Sense::INVALID_COMMAND),
+        }
+    }
+
+    /// Gets the amount of blocks reported to the host.
+    fn block_count(&self) -> u32 {
+        self.device.block_count().min(u64::from(u32::max_value())) as u32
+    }
+
+    /// Sends a response in the data phase, truncated to what the host
+    /// expects.
+    fn send_response(
+        &mut self,
+        cbw: &Cbw,
+        response: &[u8],
+    ) -> core::result::Result<Outcome, Sense> {
+        if !cbw.is_in && cbw.transfer_length != 0 {
+            return Err(Sense::INVALID_FIELD);
+        }
+
+        let length = response.len().min(cbw.transfer_length as usize);
+        if length != 0 {
+            self.transport
+                .send(&response[..length])
+                .map_err(|_| Sense::INVALID_FIELD)?;
+        }
+
+        Ok(Outcome::passed(length as u32))
+    }
+
+    /// Checks the data phase and block range of a read or write command.
+    ///
+    /// Returns the amount of bytes to transfer.
+    fn check_transfer(&self, cbw: &Cbw, is_in: bool) -> core::result::Result<u32, Sense> {
+        let length = cbw.blocks() * BLOCK_SIZE as u64;
+        if length != 0 && (cbw.is_in != is_in || u64::from(cbw.transfer_length) < length) {
+            return Err(Sense::INVALID_FIELD);
+        }
+
+        if cbw.lba() + cbw.blocks() > self.device.block_count() {
+            return Err(Sense::LBA_OUT_OF_RANGE);
+        }
+
+        Ok(length as u32)
+    }
+
+    /// Handles the `READ (10)` command.
+    fn read(&mut self, cbw: &Cbw) -> core::result::Result<Outcome, Sense> {
+        let length = self.check_transfer(cbw, true)?;
+
+        let mut lba = cbw.lba();
+        let mut transferred = 0;
+
+        while transferred < length {
+            let size = self.buffer.len().min((length - transferred) as usize);
+            let chunk = &mut self.buffer[..size];
+
+            self.device
+                .read_blocks(lba, chunk)
+                .map_err(|_| Sense::READ_ERROR)?;
+            self.transport.send(chunk).map_err(|_| Sense::READ_ERROR)?;
+
+            lba += (size / BLOCK_SIZE) as u64;
+            transferred += size as u32;
+        }
+
+        Ok(Outcome::passed(transferred))
+    }
+
+    /// Handles the `WRITE (10)` command.
+    fn write(&mut self, cbw: &Cbw) -> core::result::Result<Outcome, Sense> {
+        if self.read_only {
+            return Err(Sense::WRITE_PROTECTED);
+        }
+
+        let length = self.check_transfer(cbw, false)?;
+
+        let mut lba = cbw.lba();
+        let mut transferred = 0;
+
+        while transferred < length {
+            let size = self.buffer.len().min((length - transferred) as usize);
+            let chunk = &mut self.buffer[..size];
+
+            // The chunk may arrive in several packets.
+            let mut received = 0;
+            while received < size {
+                received += self
+                    .transport
+                    .receive(&mut chunk[received..])
+                    .map_err(|_| Sense::WRITE_ERROR)?;
+            }
+
+            self.device
+                .write_blocks(lba, chunk)
+                .map_err(|_| Sense::WRITE_ERROR)?;
+
+            lba += (size / BLOCK_SIZE) as u64;
+            transferred += size as u32;
+        }
+
+        Ok(Outcome::passed(transferred))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A transport replaying a script of packets from the host.
+    struct Script<'a> {
+        /// The packets the host sends.
+        packets: &'a [&'a [u8]],
+        /// The data the device sent, concatenated.
+        sent: [u8; 0x1000],
+        /// The amount of bytes in `sent`.
+        sent_length: usize,
+        /// The amount of times the IN endpoint was stalled.
+        stalls: usize,
+    }
+
+    impl<'a> Script<'a> {
+        fn new(packets: &'a [&'a [u8]]) -> Self {
+            Script {
+                packets,
+                sent: [0; 0x1000],
+                sent_length: 0,
+                stalls: 0,
+            }
+        }
+    }
+
+    impl BulkTransport for &mut Script<'_> {
+        fn receive(&mut self, buffer: &mut [u8]) -> Result<usize> {
+            let (packet, rest) = self.packets.split_first().ok_or(Error::Timeout)?;
+            buffer[..packet.len()].copy_from_slice(packet);
+            self.packets = rest;
+
+            Ok(packet.len())
+        }
+
+        fn send(&mut self, data: &[u8]) -> Result<()> {
+            self.sent[self.sent_length..self.sent_length + data.len()].copy_from_slice(data);
+            self.sent_length += data.len();
+
+            Ok(())
+        }
+
+        fn stall_in(&mut self) -> Result<()> {
+            self.stalls += 1;
+            Ok(())
+        }
+    }
+
+    /// Builds a CBW for a command.
+    fn cbw(tag: u32, transfer_length: u32, is_in: bool, command: &[u8]) -> [u8; CBW_SIZE] {
+        let mut cbw = [0; CBW_SIZE];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&transfer_length.to_le_bytes());
+        cbw[12] = if is_in { CBW_FLAGS_IN } else { 0 };
+        cbw[14] = command.len() as u8;
+        cbw[15..15 + command.len()].copy_from_slice(command);
+
+        cbw
+    }
+
+    /// Gets the status and residue of the CSW at the end of the sent data.
+    fn csw(script: &Script<'_>, tag: u32) -> (u8, u32) {
+        let csw = &script.sent[script.sent_length - CSW_SIZE..script.sent_length];
+        assert_eq!(&csw[0..4], &CSW_SIGNATURE.to_le_bytes());
+        assert_eq!(&csw[4..8], &tag.to_le_bytes());
+
+        (csw[12], u32::from_le_bytes(csw[8..12].try_into().unwrap()))
+    }
+
+    #[test]
+    fn read_and_write_blocks() {
+        let mut disk = [0; 4 * BLOCK_SIZE];
+        let mut buffer = [0; BLOCK_SIZE];
+
+        let data = [0xA5; 2 * BLOCK_SIZE];
+        let write = cbw(
+            1,
+            data.len() as u32,
+            false,
+            &[SCSI_WRITE_10, 0, 0, 0, 0, 1, 0, 0, 2, 0],
+        );
+        let packets: [&[u8]; 3] = [&write, &data[..BLOCK_SIZE], &data[BLOCK_SIZE..]];
+        let mut script = Script::new(&packets);
+
+        MassStorage::new(&mut disk[..], &mut script, &mut buffer)
+            .process()
+            .unwrap();
+        assert_eq!(csw(&script, 1), (CSW_STATUS_PASSED, 0));
+        assert_eq!(&disk[BLOCK_SIZE..3 * BLOCK_SIZE], &data[..]);
+
+        let read = cbw(
+            2,
+            BLOCK_SIZE as u32,
+            true,
+            &[SCSI_READ_10, 0, 0, 0, 0, 2, 0, 0, 1, 0],
+        );
+        let packets: [&[u8]; 1] = [&read];
+        let mut script = Script::new(&packets);
+
+        MassStorage::new(&mut disk[..], &mut script, &mut buffer)
+            .process()
+            .unwrap();
+        assert_eq!(&script.sent[..BLOCK_SIZE], &data[..BLOCK_SIZE]);
+        assert_eq!(csw(&script, 2), (CSW_STATUS_PASSED, 0));
+    }
+
+    #[test]
+    fn report_errors() {
+        let mut disk = [0; 4 * BLOCK_SIZE];
+        let mut buffer = [0; BLOCK_SIZE];
+
+        let read = cbw(
+            3,
+            BLOCK_SIZE as u32,
+            true,
+            &[SCSI_READ_10, 0, 0, 0, 0, 4, 0, 0, 1, 0],
+        );
+        let sense = cbw(4, 18, true, &[SCSI_REQUEST_SENSE, 0, 0, 0, 18, 0]);
+        let packets: [&[u8]; 2] = [&read, &sense];
+        let mut script = Script::new(&packets);
+
+        {
+            let mut storage = MassStorage::new(&mut disk[..], &mut script, &mut buffer);
+            storage.process().unwrap();
+            storage.process().unwrap();
+        }
+
+        assert_eq!(script.stalls, 1);
+        assert_eq!(script.sent[CSW_SIZE + 2], Sense::LBA_OUT_OF_RANGE.key);
+        assert_eq!(script.sent[CSW_SIZE + 12], Sense::LBA_OUT_OF_RANGE.asc);
+        assert_eq!(csw(&script, 4), (CSW_STATUS_PASSED, 0));
+
+        let packets: [&[u8]; 1] = [b"garbage"];
+        let mut script = Script::new(&packets);
+        assert_eq!(
+            MassStorage::new(&mut disk[..], &mut script, &mut buffer).process(),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn report_capacity() {
+        let mut disk = [0; 4 * BLOCK_SIZE];
+        let mut buffer = [0; BLOCK_SIZE];
+
+        let capacity = cbw(
+            5,
+            8,
+            true,
+            &[SCSI_READ_CAPACITY_10, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+        let packets: [&[u8]; 1] = [&capacity];
+        let mut script = Script::new(&packets);
+
+        MassStorage::new(&mut disk[..], &mut script, &mut buffer)
+            .process()
+            .unwrap();
+        assert_eq!(&script.sent[..8], &[0, 0, 0, 3, 0, 0, 2, 0]);
+        assert_eq!(csw(&script, 5), (CSW_STATUS_PASSED, 0));
+    }
+}
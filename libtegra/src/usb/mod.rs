@@ -0,0 +1,195 @@
+//! Detection of the power supplied over USB.
+//!
+//! # Description
+//!
+//! The Switch can be powered from anything between a PC port delivering
+//! 500mA and a USB Power Delivery charger negotiating 15V. Payloads which
+//! turn up the backlight or power the GPU should check that the supply can
+//! sustain it, as the battery may otherwise drain or brown out.
+//!
+//! Two chips take part in this. The ROHM BM92T type-C controller on I²C 1
+//! negotiates Power Delivery contracts. The TI BQ24193 charger, on the same
+//! bus, runs the BC1.2 detection on the D+/D- lines of the USB pad, which
+//! tells standard host ports apart from dedicated chargers.
+//!
+//! # Implementation
+//!
+//! - [`detected_power`] returns the [`PowerSource`] with the most power
+//! available. A Power Delivery contract takes precedence over the BC1.2
+//! result, which in turn is reported when no contract exists.
+//!
+//! - [`PowerSource::milliwatts`] gives the power that can be drawn from the
+//! source, for comparing it against a budget.
+//!
+//! - I²C 1 has to be initialized. A failing transfer to either chip makes
+//! its result unknown instead of failing the detection.
+//!
+//! - The [`mass_storage`] module implements the USB Mass Storage class on
+//! top of the bulk endpoints of a USB device controller.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::{peripherals::Peripherals, usb};
+//!
+//! fn main() {
+//!     let peripherals = Peripherals::take().unwrap();
+//!
+//!     if usb::detected_power(&peripherals.i2c_1).milliwatts() >= 7_500 {
+//!         // Enough power for full backlight brightness.
+//!     }
+//! }
+//! ```
+//!
+//! [`detected_power`]: fn.detected_power.html
+//! [`PowerSource`]: enum.PowerSource.html
+//! [`PowerSource::milliwatts`]: enum.PowerSource.html#method.milliwatts
+//! [`mass_storage`]: mass_storage/index.html
+
+pub mod mass_storage;
+
+use crate::i2c::{Device, I2c, I2cError};
+
+/// The `CURRENT_PDO` register of the BM92T, a block holding the PDO of the
+/// active contract.
+const BM92T_REG_CURRENT_PDO: u8 = 0x28;
+
+/// The `REG08` system status register of the BQ24193.
+const BQ24193_REG_STATUS: u8 = 0x08;
+
+/// The power good bit in the BQ24193 status.
+const BQ24193_STATUS_PG: u8 = 1 << 2;
+
+/// The bus voltage of USB ports without a Power Delivery contract in mV.
+const VBUS_DEFAULT: u32 = 5_000;
+
+/// Sources of power detected on the USB port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerSource {
+    /// Nothing is supplying power.
+    None,
+    /// Power is present, but the type of the source is unknown.
+    Unknown,
+    /// A standard downstream port, e.g. of a PC, supplying 500mA.
+    StandardPort,
+    /// A dedicated charging port supplying up to 1.5A.
+    DedicatedCharger,
+    /// A Power Delivery contract with the given voltage and current.
+    PowerDelivery {
+        /// The negotiated voltage in mV.
+        millivolts: u32,
+        /// The negotiated maximum current in mA.
+        milliamps: u32,
+    },
+}
+
+impl PowerSource {
+    /// Decodes a fixed supply PDO as reported by the BM92T.
+    ///
+    /// Returns `None` for other supply types and unused PDOs.
+    fn from_pdo(pdo: u32) -> Option<Self> {
+        // Fixed supply PDOs have the type 0 in bits 30-31.
+        if pdo >> 30 != 0 {
+            return None;
+        }
+
+        let milliamps = (pdo & 0x3FF) * 10;
+        let millivolts = ((pdo >> 10) & 0x3FF) * 50;
+
+        if millivolts == 0 || milliamps == 0 {
+            None
+        } else {
+            Some(PowerSource::PowerDelivery {
+                millivolts,
+                milliamps,
+            })
+        }
+    }
+
+    /// Decodes the BQ24193 system status.
+    fn from_charger_status(status: u8) -> Self {
+        match status >> 6 {
+            1 => PowerSource::StandardPort,
+            2 => PowerSource::DedicatedCharger,
+            // The console is the source in OTG mode.
+            3 => PowerSource::None,
+            _ if status & BQ24193_STATUS_PG != 0 => PowerSource::Unknown,
+            _ => PowerSource::None,
+        }
+    }
+
+    /// Gets the power that can be drawn from the source in mW.
+    ///
+    /// Unknown sources are assumed to supply as much as a standard port.
+    pub fn milliwatts(&self) -> u32 {
+        match *self {
+            PowerSource::None => 0,
+            PowerSource::Unknown | PowerSource::StandardPort => VBUS_DEFAULT * 500 / 1000,
+            PowerSource::DedicatedCharger => VBUS_DEFAULT * 1_500 / 1000,
+            PowerSource::PowerDelivery {
+                millivolts,
+                milliamps,
+            } => millivolts * milliamps / 1000,
+        }
+    }
+}
+
+/// Reads the PDO of the active Power Delivery contract from the BM92T.
+fn read_current_pdo(i2c: &I2c) -> Result<u32, I2cError> {
+    // Block reads start with the amount of bytes that follow.
+    let mut buffer = [0; 5];
+    i2c.read(Device::Bm92t, BM92T_REG_CURRENT_PDO, &mut buffer)?;
+
+    if buffer[0] < 4 {
+        return Ok(0);
+    }
+
+    Ok(u32::from_le_bytes([
+        buffer[1], buffer[2], buffer[3], buffer[4],
+    ]))
+}
+
+/// Detects the source of the power supplied over USB.
+///
+/// `i2c` has to be the initialized I²C 1.
+pub fn detected_power(i2c: &I2c) -> PowerSource {
+    if let Some(source) = read_current_pdo(i2c).ok().and_then(PowerSource::from_pdo) {
+        return source;
+    }
+
+    match i2c.read_byte(Device::Bq24193, BQ24193_REG_STATUS) {
+        Ok(status) => PowerSource::from_charger_status(status),
+        Err(_) => PowerSource::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests decoding of the PDO and charger status.
+    #[test]
+    fn decode() {
+        // 15V at 2.6A.
+        let pdo = (300 << 10) | 260;
+        assert_eq!(
+            PowerSource::from_pdo(pdo),
+            Some(PowerSource::PowerDelivery {
+                millivolts: 15_000,
+                milliamps: 2_600,
+            })
+        );
+        assert_eq!(PowerSource::from_pdo(pdo).unwrap().milliwatts(), 39_000);
+        assert_eq!(PowerSource::from_pdo(0), None);
+        assert_eq!(PowerSource::from_pdo(0xC000_0000 | pdo), None);
+
+        assert_eq!(
+            PowerSource::from_charger_status(0x44),
+            PowerSource::StandardPort
+        );
+        assert_eq!(PowerSource::from_charger_status(0x84).milliwatts(), 7_500);
+        assert_eq!(PowerSource::from_charger_status(0xC4), PowerSource::None);
+        assert_eq!(PowerSource::from_charger_status(0x04), PowerSource::Unknown);
+        assert_eq!(PowerSource::from_charger_status(0x00), PowerSource::None);
+    }
+}
@@ -0,0 +1,424 @@
+//! Tegra210 XUSB pad controller driver.
+//!
+//! # Description
+//!
+//! The XUSB PADCTL block sits between the USB controllers and the physical
+//! pads. It decides which controller owns a USB2 pad, which function the
+//! high-speed lanes serve (PCIe, USB3 or SATA) and whether the VBUS and ID
+//! signals come from the pins or from software overrides. It also holds the
+//! calibration and power controls of the USB2 pads.
+//!
+//! The type-C port of the Switch is wired to the USB2 OTG pad 0. Its VBUS
+//! and ID pins aren't connected to the SoC, as the type-C controller handles
+//! them, so they have to be overridden for the controllers to operate.
+//!
+//! # Implementation
+//!
+//! - The [`Registers`] struct represents the PADCTL registers that are
+//! mapped to address `0x7009F000`. The fields of the registers used by this
+//! driver are defined with `register_bitfields!`.
+//!
+//! - [`init_otg_pad`] takes the PADCTL out of reset, calibrates the USB2
+//! bias and OTG pad 0 from the fuses and powers them up. [`power_down_otg_pad`]
+//! reverts this.
+//!
+//! - [`set_otg_pad_owner`] and [`set_port_mode`] assign the OTG pad to a
+//! controller and choose between host and device mode.
+//!
+//! - [`set_lane_function`] muxes a high-speed lane to one of its functions.
+//!
+//! - [`set_vbus_override`] and [`set_id_override`] replace the VBUS and ID
+//! pin states with software values, or hand them back to the pins.
+//!
+//! # Example
+//!
+//! ```
+//! use mirage_libtegra::xusb_padctl::{self, IdState, PadOwner, PortMode};
+//!
+//! fn main() {
+//!     // Prepare the type-C port for device mode.
+//!     xusb_padctl::init_otg_pad();
+//!     xusb_padctl::set_otg_pad_owner(PadOwner::Xusb);
+//!     xusb_padctl::set_port_mode(PortMode::Device);
+//!     xusb_padctl::set_id_override(Some(IdState::Floating));
+//!     xusb_padctl::set_vbus_override(Some(true));
+//! }
+//! ```
+//!
+//! [`Registers`]: struct.Registers.html
+//! [`init_otg_pad`]: fn.init_otg_pad.html
+//! [`power_down_otg_pad`]: fn.power_down_otg_pad.html
+//! [`set_otg_pad_owner`]: fn.set_otg_pad_owner.html
+//! [`set_port_mode`]: fn.set_port_mode.html
+//! [`set_lane_function`]: fn.set_lane_function.html
+//! [`set_vbus_override`]: fn.set_vbus_override.html
+//! [`set_id_override`]: fn.set_id_override.html
+
+use mirage_mmio::{register_bitfields, Mmio, VolatileStorage};
+
+use crate::{
+    clock::{Car, Clock},
+    fuse::FuseChip,
+    timer::usleep,
+    Error, Result,
+};
+
+/// Base address for XUSB PADCTL registers.
+pub(crate) const XUSB_PADCTL_BASE: u32 = 0x7009_F000;
+
+/// The XUSB_PADCTL bit in the W reset registers.
+const RST_W_XUSB_PADCTL: u32 = 1 << 14;
+
+/// The amount of high-speed lanes.
+const LANE_COUNT: u32 = 7;
+
+register_bitfields! {
+    u32,
+    /// The `XUSB_PADCTL_USB2_PAD_MUX_0` register.
+    pub USB2_PAD_MUX [
+        /// The owner of the OTG pad 0, see `PadOwner`.
+        USB2_OTG_PAD_PORT0 OFFSET(0) NUMBITS(2),
+        /// The owner of the bias pad, where 1 is XUSB.
+        USB2_BIAS_PAD OFFSET(18) NUMBITS(2),
+    ],
+    /// The `XUSB_PADCTL_USB2_PORT_CAP_0` register.
+    pub USB2_PORT_CAP [
+        /// The mode of port 0, see `PortMode`.
+        PORT0_CAP OFFSET(0) NUMBITS(2),
+    ],
+    /// The `XUSB_PADCTL_USB2_OTG_PAD0_CTL_0_0` register.
+    pub USB2_OTG_PAD_CTL_0 [
+        /// The high-speed driver current.
+        HS_CURR_LEVEL OFFSET(0) NUMBITS(6),
+        /// Powers down the pad.
+        PD OFFSET(26) NUMBITS(1),
+        /// Powers down the pad's second stage.
+        PD2 OFFSET(27) NUMBITS(1),
+        /// Powers down the pad's input buffers.
+        PD_ZI OFFSET(29) NUMBITS(1),
+    ],
+    /// The `XUSB_PADCTL_USB2_OTG_PAD0_CTL_1_0` register.
+    pub USB2_OTG_PAD_CTL_1 [
+        /// Powers down the differential receiver.
+        PD_DR OFFSET(2) NUMBITS(1),
+        /// The adjustment of the high-speed termination.
+        TERM_RANGE_ADJ OFFSET(3) NUMBITS(4),
+        /// The pull-down resistor calibration.
+        RPD_CTRL OFFSET(26) NUMBITS(5),
+    ],
+    /// The `XUSB_PADCTL_USB2_BIAS_PAD_CTL_0_0` register.
+    pub USB2_BIAS_PAD_CTL_0 [
+        /// The high-speed squelch detector level.
+        HS_SQUELCH_LEVEL OFFSET(0) NUMBITS(3),
+        /// The high-speed disconnect detector level.
+        HS_DISCON_LEVEL OFFSET(3) NUMBITS(3),
+        /// Powers down the bias pad.
+        PD OFFSET(11) NUMBITS(1),
+    ],
+    /// The `XUSB_PADCTL_USB2_BIAS_PAD_CTL_1_0` register.
+    pub USB2_BIAS_PAD_CTL_1 [
+        /// The delay before tracking starts, in tracking clock cycles.
+        TRK_START_TIMER OFFSET(12) NUMBITS(7),
+        /// The delay before tracking is reset, in tracking clock cycles.
+        TRK_DONE_RESET_TIMER OFFSET(19) NUMBITS(7),
+        /// Powers down the tracking circuit.
+        PD_TRK OFFSET(26) NUMBITS(1),
+    ],
+    /// The `XUSB_PADCTL_USB2_VBUS_ID_0` register.
+    pub USB2_VBUS_ID [
+        /// The VBUS source, where 0 is the pin and 1 is `VBUS_OVERRIDE`.
+        VBUS_SOURCE_SELECT OFFSET(12) NUMBITS(2),
+        /// The overridden VBUS state.
+        VBUS_OVERRIDE OFFSET(14) NUMBITS(1),
+        /// The ID source, where 0 is the pin and 1 is `ID_OVERRIDE`.
+        ID_SOURCE_SELECT OFFSET(16) NUMBITS(2),
+        /// The overridden ID state, see `IdState`.
+        ID_OVERRIDE OFFSET(18) NUMBITS(4),
+    ],
+}
+
+register_bitfields! {
+    u32,
+    /// The `FUSE_SKU_USB_CALIB_0` register.
+    SKU_USB_CALIB [
+        /// The high-speed driver current of pad 0.
+        HS_CURR_LEVEL_PAD0 OFFSET(0) NUMBITS(6),
+        /// The adjustment of the high-speed termination.
+        HS_TERM_RANGE_ADJ OFFSET(7) NUMBITS(4),
+        /// The high-speed squelch detector level.
+        HS_SQUELCH_LEVEL OFFSET(29) NUMBITS(3),
+    ],
+    /// The `FUSE_USB_CALIB_EXT_0` register.
+    USB_CALIB_EXT [
+        /// The pull-down resistor calibration.
+        RPD_CTRL OFFSET(0) NUMBITS(5),
+    ],
+}
+
+/// Representation of the XUSB PADCTL registers.
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct Registers {
+    /// The `XUSB_PADCTL_BOOT_MEDIA_0` register.
+    pub BOOT_MEDIA: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_PAD_MUX_0` register.
+    pub USB2_PAD_MUX: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_PORT_CAP_0` register.
+    pub USB2_PORT_CAP: Mmio<u32>,
+    /// The `XUSB_PADCTL_SNPS_OC_MAP_0` register.
+    pub SNPS_OC_MAP: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_OC_MAP_0` register.
+    pub USB2_OC_MAP: Mmio<u32>,
+    /// The `XUSB_PADCTL_SS_PORT_MAP_0` register.
+    pub SS_PORT_MAP: Mmio<u32>,
+    /// The `XUSB_PADCTL_OC_DET_0` register.
+    pub OC_DET: Mmio<u32>,
+    _reserved0: Mmio<u32>,
+    /// The `XUSB_PADCTL_ELPG_PROGRAM_0_0` register.
+    pub ELPG_PROGRAM_0: Mmio<u32>,
+    /// The `XUSB_PADCTL_ELPG_PROGRAM_1_0` register.
+    pub ELPG_PROGRAM_1: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB3_PAD_MUX_0` register.
+    pub USB3_PAD_MUX: Mmio<u32>,
+    _reserved1: [Mmio<u32>; 0x15],
+    /// The `XUSB_PADCTL_USB2_BATTERY_CHRG_OTGPAD0_CTL0_0` register.
+    pub USB2_BATTERY_CHRG_OTGPAD0_CTL0: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_BATTERY_CHRG_OTGPAD0_CTL1_0` register.
+    pub USB2_BATTERY_CHRG_OTGPAD0_CTL1: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_OTG_PAD0_CTL_0_0` register.
+    pub USB2_OTG_PAD0_CTL_0: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_OTG_PAD0_CTL_1_0` register.
+    pub USB2_OTG_PAD0_CTL_1: Mmio<u32>,
+    _reserved2: [Mmio<u32>; 0x7D],
+    /// The `XUSB_PADCTL_USB2_BIAS_PAD_CTL_0_0` register.
+    pub USB2_BIAS_PAD_CTL_0: Mmio<u32>,
+    /// The `XUSB_PADCTL_USB2_BIAS_PAD_CTL_1_0` register.
+    pub USB2_BIAS_PAD_CTL_1: Mmio<u32>,
+    _reserved3: [Mmio<u32>; 0x275],
+    /// The `XUSB_PADCTL_USB2_VBUS_ID_0` register.
+    pub USB2_VBUS_ID: Mmio<u32>,
+}
+
+impl VolatileStorage for Registers {
+    unsafe fn make_ptr() -> *const Self {
+        XUSB_PADCTL_BASE as *const _
+    }
+}
+
+/// Controllers which can own the USB2 OTG pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadOwner {
+    /// The legacy USB2 controller.
+    Snps = 0,
+    /// The XUSB host and device controllers.
+    Xusb = 1,
+    /// The UART which is muxed onto the pad for debugging.
+    Uart = 2,
+}
+
+/// Modes of the USB2 port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortMode {
+    /// The port is disabled.
+    Disabled = 0,
+    /// The port operates as a host.
+    Host = 1,
+    /// The port operates as a device.
+    Device = 2,
+    /// The port switches roles based on the ID state.
+    Otg = 3,
+}
+
+/// States of the ID signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdState {
+    /// ID is grounded, which selects the host role.
+    Grounded = 0,
+    /// ID is floating, which selects the device role.
+    Floating = 8,
+}
+
+/// Functions of the high-speed lanes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaneFunction {
+    /// The lane is used by the PCIe controller.
+    Pcie = 0,
+    /// The lane is used by the XUSB controller as a SuperSpeed lane.
+    Usb3 = 1,
+    /// The lane is used by the SATA controller.
+    Sata = 2,
+}
+
+/// Takes the PADCTL out of reset, calibrates the USB2 bias pad and the OTG
+/// pad 0 from the fuses and powers them up.
+pub fn init_otg_pad() {
+    let car = unsafe { Car::get() };
+    let fuse_chip = unsafe { FuseChip::get() };
+    let padctl = unsafe { Registers::get() };
+
+    car.rst_dev_w_clr.write(RST_W_XUSB_PADCTL);
+    let mut clock = Clock::USB2_TRK;
+    clock.enable();
+
+    let calib = fuse_chip.sku_usb_calib.read();
+    let calib_ext = fuse_chip.usb_calib_ext.read();
+
+    // Calibrate the bias pad and start tracking.
+    padctl
+        .USB2_PAD_MUX
+        .write_field(USB2_PAD_MUX::USB2_BIAS_PAD, 1);
+    padctl.USB2_BIAS_PAD_CTL_0.modify(|v| {
+        let v = USB2_BIAS_PAD_CTL_0::HS_SQUELCH_LEVEL
+            .set(v, SKU_USB_CALIB::HS_SQUELCH_LEVEL.get(calib));
+        let v = USB2_BIAS_PAD_CTL_0::HS_DISCON_LEVEL.set(v, 7);
+        USB2_BIAS_PAD_CTL_0::PD.set(v, 0)
+    });
+    padctl.USB2_BIAS_PAD_CTL_1.modify(|v| {
+        let v = USB2_BIAS_PAD_CTL_1::TRK_START_TIMER.set(v, 0x1E);
+        let v = USB2_BIAS_PAD_CTL_1::TRK_DONE_RESET_TIMER.set(v, 0xA);
+        USB2_BIAS_PAD_CTL_1::PD_TRK.set(v, 0)
+    });
+    usleep(100);
+
+    // Calibrate and power up the OTG pad.
+    padctl.USB2_OTG_PAD0_CTL_0.modify(|v| {
+        let v =
+            USB2_OTG_PAD_CTL_0::HS_CURR_LEVEL.set(v, SKU_USB_CALIB::HS_CURR_LEVEL_PAD0.get(calib));
+        let v = USB2_OTG_PAD_CTL_0::PD.set(v, 0);
+        let v = USB2_OTG_PAD_CTL_0::PD2.set(v, 0);
+        USB2_OTG_PAD_CTL_0::PD_ZI.set(v, 0)
+    });
+    padctl.USB2_OTG_PAD0_CTL_1.modify(|v| {
+        let v =
+            USB2_OTG_PAD_CTL_1::TERM_RANGE_ADJ.set(v, SKU_USB_CALIB::HS_TERM_RANGE_ADJ.get(calib));
+        let v = USB2_OTG_PAD_CTL_1::RPD_CTRL.set(v, USB_CALIB_EXT::RPD_CTRL.get(calib_ext));
+        USB2_OTG_PAD_CTL_1::PD_DR.set(v, 0)
+    });
+}
+
+/// Powers down the OTG pad 0 and the USB2 bias pad.
+pub fn power_down_otg_pad() {
+    let padctl = unsafe { Registers::get() };
+
+    padctl.USB2_OTG_PAD0_CTL_0.modify(|v| {
+        let v = USB2_OTG_PAD_CTL_0::PD.set(v, 1);
+        let v = USB2_OTG_PAD_CTL_0::PD2.set(v, 1);
+        USB2_OTG_PAD_CTL_0::PD_ZI.set(v, 1)
+    });
+    padctl
+        .USB2_OTG_PAD0_CTL_1
+        .write_field(USB2_OTG_PAD_CTL_1::PD_DR, 1);
+
+    padctl
+        .USB2_BIAS_PAD_CTL_1
+        .write_field(USB2_BIAS_PAD_CTL_1::PD_TRK, 1);
+    padctl
+        .USB2_BIAS_PAD_CTL_0
+        .write_field(USB2_BIAS_PAD_CTL_0::PD, 1);
+
+    let mut clock = Clock::USB2_TRK;
+    clock.disable();
+}
+
+/// Assigns the USB2 OTG pad 0 to a controller.
+pub fn set_otg_pad_owner(owner: PadOwner) {
+    let padctl = unsafe { Registers::get() };
+
+    padctl
+        .USB2_PAD_MUX
+        .write_field(USB2_PAD_MUX::USB2_OTG_PAD_PORT0, owner as u32);
+}
+
+/// Sets the mode of the USB2 port 0.
+pub fn set_port_mode(mode: PortMode) {
+    let padctl = unsafe { Registers::get() };
+
+    padctl
+        .USB2_PORT_CAP
+        .write_field(USB2_PORT_CAP::PORT0_CAP, mode as u32);
+}
+
+/// Muxes a high-speed lane to the given function.
+///
+/// Lanes 0 to 6 exist, but SATA is only available on lane 6.
+pub fn set_lane_function(lane: u32, function: LaneFunction) -> Result<()> {
+    let padctl = unsafe { Registers::get() };
+
+    if lane >= LANE_COUNT || (function == LaneFunction::Sata && lane != LANE_COUNT - 1) {
+        return Err(Error::InvalidArgument);
+    }
+
+    // The PCIe lanes start at bit 12, the SATA lane follows them.
+    let shift = 12 + 2 * lane;
+    padctl
+        .USB3_PAD_MUX
+        .modify(|v| (v & !(3 << shift)) | ((function as u32) << shift));
+
+    Ok(())
+}
+
+/// Overrides the VBUS state with the given value, or makes the controllers
+/// use the VBUS pin again if `None` is given.
+pub fn set_vbus_override(valid: Option<bool>) {
+    let padctl = unsafe { Registers::get() };
+
+    padctl.USB2_VBUS_ID.modify(|v| match valid {
+        Some(valid) => {
+            let v = USB2_VBUS_ID::VBUS_SOURCE_SELECT.set(v, 1);
+            USB2_VBUS_ID::VBUS_OVERRIDE.set(v, valid as u32)
+        }
+        None => {
+            let v = USB2_VBUS_ID::VBUS_SOURCE_SELECT.set(v, 0);
+            USB2_VBUS_ID::VBUS_OVERRIDE.set(v, 0)
+        }
+    });
+}
+
+/// Overrides the ID state with the given value, or makes the controllers
+/// use the ID pin again if `None` is given.
+pub fn set_id_override(state: Option<IdState>) {
+    let padctl = unsafe { Registers::get() };
+
+    padctl.USB2_VBUS_ID.modify(|v| match state {
+        Some(state) => {
+            let v = USB2_VBUS_ID::ID_SOURCE_SELECT.set(v, 1);
+            USB2_VBUS_ID::ID_OVERRIDE.set(v, state as u32)
+        }
+        None => {
+            let v = USB2_VBUS_ID::ID_SOURCE_SELECT.set(v, 0);
+            USB2_VBUS_ID::ID_OVERRIDE.set(v, IdState::Floating as u32)
+        }
+    });
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use mirage_mmio::mock;
+
+    use super::*;
+
+    /// Tests the port configuration and the VBUS and ID overrides.
+    #[test]
+    fn overrides() {
+        let _session = mock::start();
+
+        set_otg_pad_owner(PadOwner::Xusb);
+        set_port_mode(PortMode::Device);
+        assert_eq!(mock::get(0x7009_F004), 1);
+        assert_eq!(mock::get(0x7009_F008), 2);
+
+        set_id_override(Some(IdState::Floating));
+        set_vbus_override(Some(true));
+        assert_eq!(mock::get(0x7009_FC60), 0x0021_5000);
+        set_vbus_override(None);
+        assert_eq!(mock::get(0x7009_FC60), 0x0021_0000);
+
+        set_lane_function(6, LaneFunction::Sata).unwrap();
+        set_lane_function(0, LaneFunction::Usb3).unwrap();
+        assert_eq!(mock::get(0x7009_F028), 0x0200_1000);
+        assert_eq!(
+            set_lane_function(1, LaneFunction::Sata),
+            Err(Error::InvalidArgument)
+        );
+    }
+}
@@ -44,6 +44,20 @@
 //! you can do that by casting the addresses to [`Mmio`] pointers and
 //! dereferencing them.
 //!
+//! Drivers that manage several instances of the same register block, e.g.
+//! one per controller, can store a [`BlockMmio`] instead, which is a cheap,
+//! copyable handle to the block at a given address.
+//!
+//! # Access permissions
+//!
+//! Not all registers can be both read and written. Status registers are
+//! usually read-only, while FIFOs and command registers may be write-only.
+//! [`ReadOnly`] and [`WriteOnly`] only expose the respective operation, so
+//! register blocks can encode these permissions and illegal accesses fail
+//! at compile time. [`ReadWrite`] is an alias of [`Mmio`] for symmetry.
+//! Register blocks that use [`Mmio`] throughout simply haven't had their
+//! permissions encoded yet.
+//!
 //! # Safety
 //!
 //! The dereferencing of raw pointers is done internally.
@@ -57,6 +71,12 @@
 //! structure are marked as unsafe - the general risk to trigger UB is
 //! always present.
 //!
+//! # Testing
+//!
+//! With the `mock` feature, register accesses can be redirected to a
+//! register map in host memory, so drivers can be unit-tested off-device.
+//! See the [`mock`] module for details.
+//!
 //! # Usage
 //!
 //! ``` no_run
@@ -68,7 +88,9 @@
 //! pub struct RegisterBlock {
 //!     pub some_reg: Mmio<u32>,
 //!     _unknown: [Mmio<u32>; 4],
-//!     pub another_reg: Mmio<u32>,
+//!     pub another_reg: ReadWrite<u32>,
+//!     pub status_reg: ReadOnly<u32>,
+//!     pub fifo_reg: WriteOnly<u32>,
 //! }
 //!
 //! impl VolatileStorage for RegisterBlock {
@@ -92,6 +114,9 @@
 //!     let some_value = registers.some_reg.read();
 //!     // Modify the value of another one.
 //!     registers.another_reg.write(some_value << register.read());
+//!
+//!     // Status registers can only be read, FIFOs only written.
+//!     registers.fifo_reg.write(registers.status_reg.read());
 //! }
 //! ```
 //!
@@ -105,20 +130,36 @@
 //! [`VolatileStorage`]: trait.VolatileStorage.html
 //! [`VolatileStorage::make_ptr`]: trait.VolatileStorage.html#method.make_ptr
 //! [`VolatileStorage::get`]: trait.VolatileStorage.html#method.get
+//! [`BlockMmio`]: struct.BlockMmio.html
+//! [`ReadOnly`]: struct.ReadOnly.html
+//! [`WriteOnly`]: struct.WriteOnly.html
+//! [`ReadWrite`]: type.ReadWrite.html
+//! [`mock`]: mock/index.html
 
 #![no_std]
 #![deny(missing_docs)]
 #![feature(const_fn)]
 
 extern crate num_traits;
+#[cfg(feature = "mock")]
+#[macro_use]
+extern crate std;
 
 use core::{
     cell::UnsafeCell,
     fmt,
+    marker::PhantomData,
+    mem::size_of,
+    ops::{Deref, Range},
     ptr::{read_volatile, write_volatile},
 };
 
 use num_traits::PrimInt;
+#[cfg(feature = "mock")]
+use num_traits::NumCast;
+
+#[cfg(feature = "mock")]
+pub mod mock;
 
 /// A mutable hardware register location in memory.
 struct RegisterCell<T: PrimInt> {
@@ -152,6 +193,14 @@ impl<T: PrimInt> RegisterCell<T> {
     /// [volatile read]: https://doc.rust-lang.org/core/ptr/fn.read_volatile.html
     #[inline(always)]
     pub unsafe fn get(&self) -> T {
+        #[cfg(feature = "mock")]
+        {
+            if mock::is_active() {
+                let value = mock::read(self.register.get() as usize);
+                return <T as NumCast>::from(value).expect("mocked value exceeds the register");
+            }
+        }
+
         read_volatile(self.register.get())
     }
 
@@ -163,6 +212,14 @@ impl<T: PrimInt> RegisterCell<T> {
     /// [volatile write]: https://doc.rust-lang.org/core/ptr/fn.write_volatile.html
     #[inline(always)]
     pub unsafe fn set(&self, value: T) {
+        #[cfg(feature = "mock")]
+        {
+            if mock::is_active() {
+                let value = value.to_u64().expect("negative register values can't be mocked");
+                return mock::write(self.register.get() as usize, value);
+            }
+        }
+
         write_volatile(self.register.get(), value)
     }
 }
@@ -232,6 +289,44 @@ impl<T: PrimInt> Mmio<T> {
     pub fn write(&self, value: T) {
         unsafe { self.value.set(value) }
     }
+
+    /// Reads the underlying hardware register, passes the value
+    /// to the given closure and writes back the result.
+    #[inline(always)]
+    pub fn modify<F: FnOnce(T) -> T>(&self, f: F) {
+        self.write(f(self.read()));
+    }
+
+    /// Reads the bits in the given range of the
+    /// underlying hardware register, shifted down.
+    #[inline(always)]
+    pub fn get_bits(&self, bits: Range<usize>) -> T {
+        Field::new(bits.start, bits.end - bits.start).get(self.read())
+    }
+
+    /// Replaces the bits in the given range of the underlying
+    /// hardware register with the given value.
+    ///
+    /// Bits of the value that don't fit into the range are dropped.
+    #[inline(always)]
+    pub fn set_bits(&self, bits: Range<usize>, value: T) {
+        let field = Field::new(bits.start, bits.end - bits.start);
+
+        self.modify(|v| field.set(v, value));
+    }
+
+    /// Reads a field of the underlying hardware register.
+    #[inline(always)]
+    pub fn read_field(&self, field: Field<T>) -> T {
+        field.get(self.read())
+    }
+
+    /// Replaces a field of the underlying hardware register
+    /// with the given value, preserving all other bits.
+    #[inline(always)]
+    pub fn write_field(&self, field: Field<T>, value: T) {
+        self.modify(|v| field.set(v, value));
+    }
 }
 
 impl<T> fmt::Debug for Mmio<T>
@@ -243,6 +338,421 @@ where
     }
 }
 
+/// A named range of bits within a register.
+///
+/// Fields are usually declared through the [`register_bitfields`] macro
+/// and accessed through [`Mmio::read_field`] and [`Mmio::write_field`].
+///
+/// [`register_bitfields`]: macro.register_bitfields.html
+/// [`Mmio::read_field`]: struct.Mmio.html#method.read_field
+/// [`Mmio::write_field`]: struct.Mmio.html#method.write_field
+pub struct Field<T: PrimInt> {
+    /// The position of the lowest bit of the field.
+    pub shift: usize,
+    /// The number of bits in the field.
+    pub width: usize,
+    /// Marks the type of the register.
+    _register: PhantomData<T>,
+}
+
+impl<T: PrimInt> Field<T> {
+    /// Creates a field of `width` bits, starting at bit `shift`.
+    pub const fn new(shift: usize, width: usize) -> Self {
+        Field {
+            shift,
+            width,
+            _register: PhantomData,
+        }
+    }
+
+    /// Gets the mask of the field, not shifted into place.
+    #[inline(always)]
+    pub fn max(&self) -> T {
+        let bits = size_of::<T>() * 8;
+
+        if self.width >= bits {
+            !T::zero()
+        } else {
+            !(!T::zero() << self.width)
+        }
+    }
+
+    /// Gets the mask of the field, shifted into place.
+    #[inline(always)]
+    pub fn mask(&self) -> T {
+        self.max() << self.shift
+    }
+
+    /// Extracts the field from a register value.
+    #[inline(always)]
+    pub fn get(&self, register: T) -> T {
+        (register >> self.shift) & self.max()
+    }
+
+    /// Replaces the field in a register value with the given value.
+    #[inline(always)]
+    pub fn set(&self, register: T, value: T) -> T {
+        (register & !self.mask()) | ((value & self.max()) << self.shift)
+    }
+
+    /// Gets the given value shifted into the place of the field.
+    #[inline(always)]
+    pub fn val(&self, value: T) -> T {
+        (value & self.max()) << self.shift
+    }
+}
+
+impl<T: PrimInt> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: PrimInt> Copy for Field<T> {}
+
+impl<T: PrimInt> fmt::Debug for Field<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Field")
+            .field("shift", &self.shift)
+            .field("width", &self.width)
+            .finish()
+    }
+}
+
+/// Declares the fields of registers as [`Field`] constants.
+///
+/// Every register becomes a module that contains a constant per field.
+///
+/// ```
+/// use mirage_mmio::{register_bitfields, Mmio};
+///
+/// register_bitfields! {
+///     u32,
+///     /// The `CLK_SOURCE_MSELECT` register.
+///     pub CLK_SOURCE_MSELECT [
+///         /// The clock divisor.
+///         DIVISOR OFFSET(0) NUMBITS(8),
+///         /// The clock source.
+///         SOURCE OFFSET(29) NUMBITS(3),
+///     ]
+/// }
+///
+/// fn configure(register: &Mmio<u32>) {
+///     register.write_field(CLK_SOURCE_MSELECT::DIVISOR, 6);
+/// }
+/// ```
+///
+/// [`Field`]: struct.Field.html
+#[macro_export]
+macro_rules! register_bitfields {
+    (
+        $t:ty,
+        $(
+            $(#[$attr:meta])*
+            $vis:vis $register:ident [
+                $(
+                    $(#[$field_attr:meta])*
+                    $field:ident OFFSET($shift:expr) NUMBITS($width:expr)
+                ),* $(,)?
+            ]
+        ),* $(,)?
+    ) => {
+        $(
+            $(#[$attr])*
+            #[allow(non_snake_case)]
+            $vis mod $register {
+                $(
+                    $(#[$field_attr])*
+                    pub const $field: $crate::Field<$t> = $crate::Field::new($shift, $width);
+                )*
+            }
+        )*
+    };
+}
+
+/// Declares a `#[repr(C)]` register block with explicit offsets per field.
+///
+/// Every entry maps an offset to a register or to a reserved gap. Gaps are
+/// filled with padding up to the offset of the next entry, and the block is
+/// closed with an `@END` entry at its total size. The size of every register
+/// is checked against the distance to the next entry at compile time, so an
+/// offset that doesn't match the declared fields fails to build.
+///
+/// Entries are processed one at a time, so blocks with more than about 100
+/// entries need a higher `recursion_limit` in the crate that uses them.
+///
+/// ```
+/// use mirage_mmio::{register_block, Mmio, ReadOnly};
+///
+/// register_block! {
+///     /// Representation of the XYZ registers.
+///     pub struct Registers {
+///         (0x000 => pub control: Mmio<u32>),
+///         (0x004 => _reserved0),
+///         (0x010 => pub status: ReadOnly<u32>),
+///         (0x014 => pub fifo: [Mmio<u32>; 4]),
+///         (0x024 => @END),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! register_block {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($entry:tt),* $(,)?
+        }
+    ) => {
+        $crate::register_block!(
+            @munch [$(#[$attr])* $vis struct $name] [] $($entry),*
+        );
+    };
+
+    // A reserved gap up to the next entry.
+    (
+        @munch $header:tt [$($fields:tt)*]
+        ($offset:expr => $reserved:ident),
+        ($next:expr => $($entry:tt)*)
+        $(, $rest:tt)*
+    ) => {
+        $crate::register_block!(
+            @munch $header [
+                $($fields)*
+                $reserved: [$crate::Mmio<u8>; $next - $offset],
+            ]
+            ($next => $($entry)*) $(, $rest)*
+        );
+    };
+
+    // A register, which has to span exactly up to the next entry.
+    (
+        @munch $header:tt [$($fields:tt)*]
+        ($offset:expr => $(#[$field_attr:meta])* $field_vis:vis $field:ident : $t:ty),
+        ($next:expr => $($entry:tt)*)
+        $(, $rest:tt)*
+    ) => {
+        const _: [(); $next - $offset] = [(); ::core::mem::size_of::<$t>()];
+
+        $crate::register_block!(
+            @munch $header [
+                $($fields)*
+                $(#[$field_attr])* $field_vis $field: $t,
+            ]
+            ($next => $($entry)*) $(, $rest)*
+        );
+    };
+
+    // The end of the block.
+    (
+        @munch [$(#[$attr:meta])* $vis:vis struct $name:ident] [$($fields:tt)*]
+        ($end:expr => @END)
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        $vis struct $name {
+            $($fields)*
+        }
+
+        const _: [(); $end] = [(); ::core::mem::size_of::<$name>()];
+    };
+}
+
+/// Checks the layout of a register block against its documented offsets.
+///
+/// The size of the block is checked at compile time. Field offsets can't be
+/// computed in constant expressions, so they are checked by a unit test with
+/// the given name instead.
+///
+/// ```
+/// use mirage_mmio::{assert_layout, Mmio};
+///
+/// #[repr(C)]
+/// pub struct Registers {
+///     pub control: Mmio<u32>,
+///     _reserved: [Mmio<u8>; 0xC],
+///     pub status: Mmio<u32>,
+/// }
+///
+/// assert_layout!(registers_layout, Registers, 0x14, {
+///     control: 0x00,
+///     status: 0x10,
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_layout {
+    ($test:ident, $name:ty, $size:expr, { $($field:ident: $offset:expr),* $(,)? }) => {
+        const _: [(); $size] = [(); ::core::mem::size_of::<$name>()];
+
+        #[cfg(test)]
+        #[test]
+        fn $test() {
+            // Register blocks consist of plain integers, so zeroes are valid.
+            let block: $name = unsafe { ::core::mem::zeroed() };
+            let base = &block as *const $name as usize;
+
+            $(
+                assert_eq!(
+                    &block.$field as *const _ as usize - base,
+                    $offset,
+                    concat!("offset of `", stringify!($field), "`"),
+                );
+            )*
+        }
+    };
+}
+
+/// A memory-mapped hardware register that can be both read and written.
+pub type ReadWrite<T> = Mmio<T>;
+
+/// Abstraction of a read-only memory-mapped hardware register.
+///
+/// Writing to it, e.g. to a status register, fails at compile time.
+pub struct ReadOnly<T: PrimInt> {
+    /// The underlying [`RegisterCell`],
+    /// managing the memory region.
+    ///
+    /// [`RegisterCell`]: struct.RegisterCell.html
+    value: RegisterCell<T>,
+}
+
+impl<T: PrimInt> ReadOnly<T> {
+    /// Reads the underlying hardware register
+    /// and returns the resulting value.
+    #[inline(always)]
+    pub fn read(&self) -> T {
+        unsafe { self.value.get() }
+    }
+
+    /// Reads the bits in the given range of the
+    /// underlying hardware register, shifted down.
+    #[inline(always)]
+    pub fn get_bits(&self, bits: Range<usize>) -> T {
+        Field::new(bits.start, bits.end - bits.start).get(self.read())
+    }
+
+    /// Reads a field of the underlying hardware register.
+    #[inline(always)]
+    pub fn read_field(&self, field: Field<T>) -> T {
+        field.get(self.read())
+    }
+}
+
+impl<T> fmt::Debug for ReadOnly<T>
+where
+    T: fmt::Debug + PrimInt,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ReadOnly")
+            .field("value", &self.read())
+            .finish()
+    }
+}
+
+/// Abstraction of a write-only memory-mapped hardware register.
+///
+/// Reading from it, e.g. from a FIFO that is popped on
+/// writes only, fails at compile time.
+pub struct WriteOnly<T: PrimInt> {
+    /// The underlying [`RegisterCell`],
+    /// managing the memory region.
+    ///
+    /// [`RegisterCell`]: struct.RegisterCell.html
+    value: RegisterCell<T>,
+}
+
+impl<T: PrimInt> WriteOnly<T> {
+    /// Writes the given value to the
+    /// underlying hardware register.
+    #[inline(always)]
+    pub fn write(&self, value: T) {
+        unsafe { self.value.set(value) }
+    }
+}
+
+impl<T: PrimInt> fmt::Debug for WriteOnly<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        // Reading the register may have side effects, so it is left out.
+        f.debug_struct("WriteOnly").finish()
+    }
+}
+
+/// A handle to a block of memory-mapped registers at a fixed address.
+///
+/// Unlike [`VolatileStorage`], which ties a register block struct to a
+/// single address, a [`BlockMmio`] can be created for each instance of a
+/// block and stored in `const` driver definitions. It dereferences to the
+/// register block struct.
+///
+/// [`VolatileStorage`]: trait.VolatileStorage.html
+/// [`BlockMmio`]: struct.BlockMmio.html
+pub struct BlockMmio<R> {
+    /// The address where the register block is mapped.
+    address: usize,
+    /// Marks the type of the register block.
+    _block: PhantomData<*const R>,
+}
+
+impl<R> BlockMmio<R> {
+    /// Creates a handle to the register block at the given address.
+    ///
+    /// NOTE: unsafe because the handle will be dereferenced,
+    /// which triggers Undefined Behavior for falsy addresses.
+    pub const unsafe fn new(address: usize) -> Self {
+        BlockMmio {
+            address,
+            _block: PhantomData,
+        }
+    }
+
+    /// Gets the address where the register block is mapped.
+    #[inline(always)]
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Gets a raw pointer to the register block.
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *const R {
+        self.address as *const R
+    }
+}
+
+impl<R> Deref for BlockMmio<R> {
+    type Target = R;
+
+    #[inline(always)]
+    fn deref(&self) -> &R {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<R> Clone for BlockMmio<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for BlockMmio<R> {}
+
+impl<R> PartialEq for BlockMmio<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+    }
+}
+
+impl<R> Eq for BlockMmio<R> {}
+
+impl<R> fmt::Debug for BlockMmio<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "BlockMmio({:#x})", self.address)
+    }
+}
+
+// Register blocks are plain memory-mapped hardware, shared by design.
+unsafe impl<R> Send for BlockMmio<R> {}
+
+unsafe impl<R> Sync for BlockMmio<R> {}
+
 #[cfg(test)]
 mod tests {
     extern crate alloc;
@@ -292,4 +802,80 @@ mod tests {
             format!("{:?}", register)
         );
     }
+
+    register_bitfields! {
+        u32,
+        TEST_REGISTER [
+            LOW OFFSET(0) NUMBITS(4),
+            HIGH OFFSET(28) NUMBITS(4),
+            ALL OFFSET(0) NUMBITS(32),
+        ]
+    }
+
+    register_block! {
+        struct TestBlock {
+            (0x00 => first: Mmio<u32>),
+            (0x04 => _reserved0),
+            (0x10 => second: ReadOnly<u16>),
+            (0x12 => _reserved1),
+            (0x14 => third: [Mmio<u32>; 3]),
+            (0x20 => @END),
+        }
+    }
+
+    /// Tests the layout of register blocks.
+    #[test]
+    fn register_block_layout() {
+        let block: [u32; 8] = [1, 0, 0, 0, 0xABCD, 0, 0, 0x5678];
+        let block = unsafe { &*(&block as *const [u32; 8] as *const TestBlock) };
+
+        assert_eq!(1, block.first.read());
+        assert_eq!(0xABCD, block.second.read());
+        assert_eq!(0x5678, block.third[2].read());
+    }
+
+    /// Tests reading and writing bitfields of registers.
+    #[test]
+    fn register_fields() {
+        let x: u32 = 0x1234_5678;
+        let register = unsafe { &*(&x as *const u32 as *const Mmio<u32>) };
+
+        assert_eq!(0x8, register.read_field(TEST_REGISTER::LOW));
+        assert_eq!(0x1, register.read_field(TEST_REGISTER::HIGH));
+        assert_eq!(0x45, register.get_bits(12..20));
+
+        register.write_field(TEST_REGISTER::HIGH, 0xA);
+        assert_eq!(0xA234_5678, register.read());
+
+        // Excess bits of the value are dropped.
+        register.set_bits(4..8, 0x1F);
+        assert_eq!(0xA234_56F8, register.read());
+
+        register.modify(|v| v & !TEST_REGISTER::LOW.mask());
+        assert_eq!(0xA234_56F0, register.read());
+
+        assert_eq!(!0, TEST_REGISTER::ALL.mask());
+        assert_eq!(0x3000_0000, TEST_REGISTER::HIGH.val(0x3));
+    }
+
+    /// Tests the access-restricted registers and register blocks.
+    #[test]
+    fn restricted_registers() {
+        #[repr(C)]
+        struct Block {
+            status: ReadOnly<u32>,
+            fifo: WriteOnly<u32>,
+        }
+
+        let memory: [u32; 2] = [0x1234, 0];
+        let block = unsafe { BlockMmio::<Block>::new(memory.as_ptr() as usize) };
+
+        assert_eq!(0x1234, block.status.read());
+
+        block.fifo.write(0x5678);
+        assert_eq!(0x5678, memory[1]);
+
+        assert_eq!(block, block.clone());
+        assert_eq!(format!("{:?}", block.fifo), "WriteOnly");
+    }
 }
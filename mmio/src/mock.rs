@@ -0,0 +1,281 @@
+//! A mocked register backend for host-side unit tests.
+//!
+//! Drivers create their register references by casting fixed addresses
+//! like `0x6000_6000` into pointers, which are not mapped on a development
+//! machine. While a [`Session`] is active on the current thread, every
+//! access through [`Mmio`], [`ReadOnly`] and [`WriteOnly`] is redirected
+//! to a register map keyed by the address of the register instead of
+//! touching memory.
+//!
+//! Registers read as zero until they are written or preset through
+//! [`set`]. Tests can additionally queue the values of upcoming reads
+//! with [`expect_read`], check the values of upcoming writes with
+//! [`expect_write`] and inspect all writes through [`writes`]. Counter
+//! registers, like the microsecond timer drivers poll in delays, can be
+//! made to advance on every read with [`auto_increment`].
+//!
+//! Each register is stored as a `u64`, regardless of its access width.
+//! Overlapping accesses of different widths are not modeled.
+//!
+//! The state is thread-local, so tests running in parallel don't
+//! interfere with each other.
+//!
+//! [`Session`]: struct.Session.html
+//! [`Mmio`]: ../struct.Mmio.html
+//! [`ReadOnly`]: ../struct.ReadOnly.html
+//! [`WriteOnly`]: ../struct.WriteOnly.html
+//! [`set`]: fn.set.html
+//! [`expect_read`]: fn.expect_read.html
+//! [`expect_write`]: fn.expect_write.html
+//! [`writes`]: fn.writes.html
+//! [`auto_increment`]: fn.auto_increment.html
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    thread,
+    vec::Vec,
+};
+
+/// The state of a single mocked register.
+#[derive(Default)]
+struct Register {
+    /// The current value of the register.
+    value: u64,
+    /// The amount added to the value after every read.
+    increment: u64,
+    /// Queued values to be returned by upcoming reads.
+    reads: VecDeque<u64>,
+    /// Values that upcoming writes are expected to have.
+    expected_writes: VecDeque<u64>,
+    /// All values written to the register, in order.
+    writes: Vec<u64>,
+}
+
+/// The mocked registers of the current thread.
+#[derive(Default)]
+struct State {
+    /// Whether register accesses are currently redirected.
+    active: bool,
+    /// The registers that have been accessed or configured, by address.
+    registers: BTreeMap<usize, Register>,
+}
+
+impl State {
+    fn verify(&self) {
+        for (address, register) in self.registers.iter() {
+            assert!(
+                register.reads.is_empty(),
+                "{} expected read(s) of {:#x} didn't happen",
+                register.reads.len(),
+                address
+            );
+            assert!(
+                register.expected_writes.is_empty(),
+                "expected write(s) {:x?} to {:#x} didn't happen",
+                register.expected_writes,
+                address
+            );
+        }
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+fn with_register<F: FnOnce(&mut Register) -> R, R>(address: usize, f: F) -> R {
+    STATE.with(|state| f(state.borrow_mut().registers.entry(address).or_default()))
+}
+
+/// A scope in which register accesses of the current thread are mocked.
+///
+/// Created through [`start`]. On drop, the register map is cleared and
+/// it is verified that all expected reads and writes have happened.
+///
+/// [`start`]: fn.start.html
+pub struct Session {
+    _private: (),
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let state = STATE.with(|state| state.replace(State::default()));
+
+        // Don't mask the reason of a failing test with a second panic.
+        if !thread::panicking() {
+            state.verify();
+        }
+    }
+}
+
+/// Starts redirecting the register accesses of the current thread
+/// to an empty register map.
+///
+/// # Panics
+///
+/// Panics if a session is already active on the current thread.
+pub fn start() -> Session {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        assert!(!state.active, "a mock session is already active");
+        *state = State {
+            active: true,
+            registers: BTreeMap::new(),
+        };
+    });
+
+    Session { _private: () }
+}
+
+/// Whether register accesses of the current thread are mocked.
+pub fn is_active() -> bool {
+    STATE.with(|state| state.borrow().active)
+}
+
+/// Sets the current value of the register at the given address.
+pub fn set(address: usize, value: u64) {
+    with_register(address, |register| register.value = value);
+}
+
+/// Gets the current value of the register at the given address
+/// without triggering any read effects.
+pub fn get(address: usize) -> u64 {
+    with_register(address, |register| register.value)
+}
+
+/// Makes the register at the given address advance by `step`
+/// after every read, like a free-running counter.
+pub fn auto_increment(address: usize, step: u64) {
+    with_register(address, |register| register.increment = step);
+}
+
+/// Queues a value to be returned by an upcoming read of the register
+/// at the given address.
+///
+/// Queued values are returned in order, before the current value.
+pub fn expect_read(address: usize, value: u64) {
+    with_register(address, |register| register.reads.push_back(value));
+}
+
+/// Expects an upcoming write of the given value to the register
+/// at the given address.
+///
+/// Writes of a different value panic as long as expectations are queued.
+pub fn expect_write(address: usize, value: u64) {
+    with_register(address, |register| register.expected_writes.push_back(value));
+}
+
+/// Gets all values that have been written to the register
+/// at the given address, oldest first.
+pub fn writes(address: usize) -> Vec<u64> {
+    with_register(address, |register| register.writes.clone())
+}
+
+/// Checks that all queued reads and writes have happened.
+///
+/// # Panics
+///
+/// Panics with the address of the first register with outstanding
+/// expectations.
+pub fn verify() {
+    STATE.with(|state| state.borrow().verify());
+}
+
+/// Performs a mocked read of the register at the given address.
+pub(crate) fn read(address: usize) -> u64 {
+    with_register(address, |register| {
+        if let Some(value) = register.reads.pop_front() {
+            register.value = value;
+        }
+
+        let value = register.value;
+        register.value = value.wrapping_add(register.increment);
+
+        value
+    })
+}
+
+/// Performs a mocked write to the register at the given address.
+pub(crate) fn write(address: usize, value: u64) {
+    with_register(address, |register| {
+        if let Some(expected) = register.expected_writes.pop_front() {
+            assert_eq!(
+                expected, value,
+                "unexpected write to {:#x}: expected {:#x}, got {:#x}",
+                address, expected, value
+            );
+        }
+
+        register.writes.push(value);
+        register.value = value;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    const STATUS: usize = 0x7000_0000;
+    const CONTROL: usize = 0x7000_0004;
+    const COUNTER: usize = 0x7000_0008;
+
+    fn register(address: usize) -> &'static Mmio<u32> {
+        unsafe { &*(address as *const Mmio<u32>) }
+    }
+
+    /// Tests that accesses are redirected to the register map.
+    #[test]
+    fn mocked_accesses() {
+        let _session = mock::start();
+
+        mock::set(STATUS, 0x80);
+        assert_eq!(0x80, register(STATUS).read());
+
+        register(CONTROL).write_field(Field::new(4, 4), 0xA);
+        register(CONTROL).modify(|v| v | 1);
+        assert_eq!(0xA1, mock::get(CONTROL));
+        assert_eq!(vec![0xA0, 0xA1], mock::writes(CONTROL));
+
+        mock::auto_increment(COUNTER, 3);
+        assert_eq!(0, register(COUNTER).read());
+        assert_eq!(3, register(COUNTER).read());
+    }
+
+    /// Tests queued reads and checked writes.
+    #[test]
+    fn expectations() {
+        let _session = mock::start();
+
+        mock::expect_read(STATUS, 1);
+        mock::expect_read(STATUS, 2);
+        mock::expect_write(CONTROL, 0x10);
+
+        assert_eq!(1, register(STATUS).read());
+        assert_eq!(2, register(STATUS).read());
+        // The last queued value sticks.
+        assert_eq!(2, register(STATUS).read());
+
+        register(CONTROL).write(0x10);
+    }
+
+    /// Tests that wrong writes are reported.
+    #[test]
+    #[should_panic(expected = "unexpected write")]
+    fn unexpected_write() {
+        let _session = mock::start();
+
+        mock::expect_write(CONTROL, 0x10);
+        register(CONTROL).write(0x20);
+    }
+
+    /// Tests that missing writes are reported at the end of a session.
+    #[test]
+    #[should_panic(expected = "didn't happen")]
+    fn missing_write() {
+        let _session = mock::start();
+
+        mock::expect_write(CONTROL, 0x10);
+    }
+}